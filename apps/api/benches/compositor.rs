@@ -0,0 +1,73 @@
+//! Design compositing on a default-size template
+//!
+//! Run with `cargo bench --bench compositor`. Save a baseline with
+//! `-- --save-baseline main` before a change and compare against it with
+//! `-- --baseline main` after.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+use std::hint::black_box;
+
+use r_image_magic::engine::{Compositor, BLEND_MODES};
+
+/// Deterministic pseudo-random RGBA image, with some transparent and soft pixels
+fn noise_image(width: u32, height: u32, seed: u32) -> RgbaImage {
+    let mut state = seed.wrapping_mul(2_654_435_761).max(1);
+    RgbaImage::from_fn(width, height, |_, _| {
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state >> 24) as u8
+        };
+        let [r, g, b] = [next(), next(), next()];
+        let a = match next() {
+            0..=31 => 0,
+            32..=159 => 255,
+            a => a,
+        };
+        Rgba([r, g, b, a])
+    })
+}
+
+fn composite_design(c: &mut Criterion) {
+    let compositor = Compositor::new();
+    let base = DynamicImage::ImageRgba8(noise_image(4000, 4000, 4));
+    let design = DynamicImage::ImageRgba8(noise_image(2400, 3000, 5));
+    let mask = GrayImage::from_fn(2400, 3000, |x, y| Luma([((x * 7 + y * 13) % 256) as u8]));
+
+    let mut group = c.benchmark_group("composite_design");
+    group.sample_size(10);
+    for mode in BLEND_MODES {
+        group.bench_with_input(BenchmarkId::new("unmasked", mode), mode, |b, mode| {
+            b.iter(|| {
+                compositor.composite_design(
+                    black_box(&base),
+                    black_box(&design),
+                    800,
+                    500,
+                    255,
+                    mode,
+                    None,
+                )
+            })
+        });
+    }
+    group.bench_function("masked/multiply", |b| {
+        b.iter(|| {
+            compositor.composite_design(
+                black_box(&base),
+                black_box(&design),
+                800,
+                500,
+                200,
+                "multiply",
+                Some(&mask),
+            )
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, composite_design);
+criterion_main!(benches);
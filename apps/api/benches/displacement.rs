@@ -0,0 +1,80 @@
+//! Displacement of a print-size design against the pre-`f32` implementation
+//!
+//! Run with `cargo bench --bench displacement`. Both implementations are in
+//! one group, so criterion warms each up and reports them side by side; the
+//! current one should stay within about 20% of the reference.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
+use rayon::prelude::*;
+use std::hint::black_box;
+
+use r_image_magic::engine::apply_displacement;
+
+/// The displacement before premultiplied `f32` sampling: per-pixel lookups
+/// on the `u8` image, collected row by row
+fn apply_displacement_reference(
+    design: &DynamicImage,
+    displacement_map: &GrayImage,
+    strength: f64,
+) -> RgbaImage {
+    let (width, height) = design.dimensions();
+    let design_rgba = design.to_rgba8();
+    let rows: Vec<Vec<Rgba<u8>>> = (0..height)
+        .into_par_iter()
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let disp_value = displacement_map.get_pixel(x, y).0[0] as f64 / 255.0 - 0.5;
+                    let src_x = (x as f64 + disp_value * strength).clamp(0.0, (width - 1) as f64);
+                    let src_y = (y as f64 + disp_value * strength).clamp(0.0, (height - 1) as f64);
+                    let (x0, y0) = (src_x.floor() as u32, src_y.floor() as u32);
+                    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+                    let (dx, dy) = (src_x - x0 as f64, src_y - y0 as f64);
+                    let corners = [
+                        (design_rgba.get_pixel(x0, y0), (1.0 - dx) * (1.0 - dy)),
+                        (design_rgba.get_pixel(x1, y0), dx * (1.0 - dy)),
+                        (design_rgba.get_pixel(x0, y1), (1.0 - dx) * dy),
+                        (design_rgba.get_pixel(x1, y1), dx * dy),
+                    ];
+                    Rgba(std::array::from_fn(|i| {
+                        let value: f64 = corners
+                            .iter()
+                            .map(|(pixel, weight)| pixel.0[i] as f64 * weight)
+                            .sum();
+                        value.clamp(0.0, 255.0) as u8
+                    }))
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut output = RgbaImage::new(width, height);
+    for (y, row) in rows.into_iter().enumerate() {
+        for (x, pixel) in row.into_iter().enumerate() {
+            output.put_pixel(x as u32, y as u32, pixel);
+        }
+    }
+    output
+}
+
+fn displacement(c: &mut Criterion) {
+    let design = DynamicImage::ImageRgba8(RgbaImage::from_fn(2400, 3000, |x, y| {
+        Rgba([(x % 251) as u8, (y % 241) as u8, ((x + y) % 239) as u8, 255])
+    }));
+    let map = GrayImage::from_fn(2400, 3000, |x, y| Luma([((x / 7 + y / 5) % 256) as u8]));
+    let map_image = DynamicImage::ImageLuma8(map.clone());
+
+    let mut group = c.benchmark_group("apply_displacement");
+    group.sample_size(20);
+    group.bench_function("reference", |b| {
+        b.iter(|| apply_displacement_reference(black_box(&design), black_box(&map), 10.0))
+    });
+    group.bench_function("current", |b| {
+        b.iter(|| apply_displacement(black_box(&design), black_box(&map_image), None, 10.0))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, displacement);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+-- R-Image-Magic Webhooks Schema
+-- Migration: 004_webhooks.sql
+-- Created: 2026-10-16
+-- Purpose: Per-key webhook subscriptions, event log, and delivery log for replay
+
+-- Webhook subscriptions, owned by an API key
+CREATE TABLE IF NOT EXISTS webhook_subscriptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+
+    -- Delivery target
+    url TEXT NOT NULL,
+    secret VARCHAR(64) NOT NULL,              -- HMAC-SHA256 signing secret
+
+    -- Event filters, e.g. {'render.completed', 'sync.*'}; empty means all events
+    event_types TEXT[] NOT NULL DEFAULT '{}',
+
+    -- Status
+    is_active BOOLEAN NOT NULL DEFAULT true,
+
+    -- Timestamps
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_subscriptions_api_key ON webhook_subscriptions(api_key_id);
+
+DROP TRIGGER IF EXISTS update_webhook_subscriptions_updated_at ON webhook_subscriptions;
+CREATE TRIGGER update_webhook_subscriptions_updated_at
+    BEFORE UPDATE ON webhook_subscriptions
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
+
+-- Events emitted by the service, kept so deliveries can be replayed
+CREATE TABLE IF NOT EXISTS webhook_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE CASCADE, -- NULL for system events (sync.*)
+    event_type VARCHAR(100) NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_events_api_key ON webhook_events(api_key_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_webhook_events_created ON webhook_events(created_at DESC);
+
+-- One row per delivery attempt
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subscription_id UUID NOT NULL REFERENCES webhook_subscriptions(id) ON DELETE CASCADE,
+    event_id UUID NOT NULL REFERENCES webhook_events(id) ON DELETE CASCADE,
+
+    -- Outcome
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, delivered, failed
+    response_status INTEGER,
+    error_message TEXT,
+    duration_ms INTEGER,
+    is_redelivery BOOLEAN NOT NULL DEFAULT false,
+
+    -- Timestamps
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_subscription ON webhook_deliveries(subscription_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_event ON webhook_deliveries(event_id);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_status ON webhook_deliveries(status);
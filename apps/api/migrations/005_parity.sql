@@ -0,0 +1,28 @@
+-- R-Image-Magic Provider Parity Schema
+-- Migration: 005_parity.sql
+-- Created: 2026-10-16
+-- Purpose: Per-product similarity scores between our mockups and provider-generated previews
+
+-- One row per product checked in a parity run
+CREATE TABLE IF NOT EXISTS parity_results (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    run_id UUID NOT NULL,
+
+    -- What was compared
+    provider_code VARCHAR(50) NOT NULL,
+    product_external_id VARCHAR(255) NOT NULL,
+    variant_external_id VARCHAR(255),
+    placement VARCHAR(50) NOT NULL,
+    provider_mockup_url TEXT,
+
+    -- Metrics; NULL when the comparison failed
+    ssim DOUBLE PRECISION,
+    delta_e DOUBLE PRECISION,
+    score DOUBLE PRECISION,                   -- 0-100
+    error TEXT,
+
+    checked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_parity_results_product ON parity_results(provider_code, product_external_id, checked_at DESC);
+CREATE INDEX IF NOT EXISTS idx_parity_results_run ON parity_results(run_id);
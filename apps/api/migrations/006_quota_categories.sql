@@ -0,0 +1,36 @@
+-- R-Image-Magic Quota Categories Schema
+-- Migration: 006_quota_categories.sql
+-- Created: 2026-10-16
+-- Purpose: Separate monthly budgets for render, catalog, sync, and other endpoints
+
+-- Endpoint category of each logged request
+ALTER TABLE usage_logs ADD COLUMN IF NOT EXISTS category VARCHAR(20) NOT NULL DEFAULT 'other';
+
+UPDATE usage_logs SET category = CASE
+        WHEN endpoint LIKE '/api/v1/mockups/%' OR endpoint LIKE '/api/v1/tile%' THEN 'render'
+        WHEN endpoint LIKE '/api/v1/catalog/%' OR endpoint LIKE '/api/v1/templates%' THEN 'catalog'
+        WHEN endpoint LIKE '/api/v1/sync/%' THEN 'sync'
+        ELSE 'other'
+    END
+WHERE category = 'other';
+
+-- Monthly request counts per category, checked against per-tier budgets
+CREATE TABLE IF NOT EXISTS monthly_category_usage (
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    year_month VARCHAR(7) NOT NULL,           -- Format: '2026-10'
+    category VARCHAR(20) NOT NULL,            -- render, catalog, sync, other
+    total_requests INTEGER NOT NULL DEFAULT 0,
+
+    -- Timestamps
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (api_key_id, year_month, category)
+);
+
+-- Seed counters from logs still within retention
+INSERT INTO monthly_category_usage (api_key_id, year_month, category, total_requests)
+SELECT api_key_id, to_char(created_at AT TIME ZONE 'UTC', 'YYYY-MM'), category, COUNT(*)::INTEGER
+FROM usage_logs
+GROUP BY api_key_id, to_char(created_at AT TIME ZONE 'UTC', 'YYYY-MM'), category
+ON CONFLICT (api_key_id, year_month, category) DO NOTHING;
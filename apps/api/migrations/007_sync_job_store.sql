@@ -0,0 +1,21 @@
+-- R-Image-Magic Sync Job Store Schema
+-- Migration: 007_sync_job_store.sql
+-- Created: 2026-10-16
+-- Purpose: Persist orchestrator sync jobs with cursors and heartbeats so progress survives restarts
+
+ALTER TABLE pod_sync_jobs ADD COLUMN IF NOT EXISTS product_id VARCHAR(255);
+ALTER TABLE pod_sync_jobs ADD COLUMN IF NOT EXISTS parent_job_id UUID;
+ALTER TABLE pod_sync_jobs ADD COLUMN IF NOT EXISTS cursor TEXT;            -- Where a resumed job picks up (next catalog page)
+ALTER TABLE pod_sync_jobs ADD COLUMN IF NOT EXISTS heartbeat_at TIMESTAMPTZ;
+
+-- Jobs left running by the old handler never had a worker; close them out
+UPDATE pod_sync_jobs
+SET status = 'failed',
+    completed_at = COALESCE(completed_at, NOW()),
+    error_message = COALESCE(error_message, 'Interrupted: no worker was attached to this job')
+WHERE status IN ('pending', 'running') AND heartbeat_at IS NULL;
+
+-- At most one active job per provider
+CREATE UNIQUE INDEX IF NOT EXISTS idx_pod_sync_jobs_one_active
+    ON pod_sync_jobs(provider_id) WHERE status IN ('pending', 'running');
+CREATE INDEX IF NOT EXISTS idx_pod_sync_jobs_parent ON pod_sync_jobs(parent_job_id);
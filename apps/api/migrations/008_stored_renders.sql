@@ -0,0 +1,16 @@
+-- R-Image-Magic Stored Renders Schema
+-- Migration: 008_stored_renders.sql
+-- Created: 2026-10-16
+-- Purpose: Track mockups kept in R2 per API key so tiers can limit stored renders
+
+CREATE TABLE IF NOT EXISTS stored_renders (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    r2_key TEXT NOT NULL UNIQUE,              -- generated/{date}/{uuid}.{ext} or generated/batch/{job}/{file}
+
+    -- Timestamps
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_stored_renders_api_key ON stored_renders(api_key_id);
+CREATE INDEX IF NOT EXISTS idx_stored_renders_created ON stored_renders(created_at);
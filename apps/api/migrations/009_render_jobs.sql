@@ -0,0 +1,27 @@
+-- R-Image-Magic Async Render Jobs Schema
+-- Migration: 009_render_jobs.sql
+-- Created: 2026-10-16
+-- Purpose: Persist async mockup renders so finished results survive a restart
+
+CREATE TABLE IF NOT EXISTS render_jobs (
+    id UUID PRIMARY KEY,
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE CASCADE,   -- NULL when auth is disabled
+    template_id VARCHAR(255) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',                 -- queued, processing, completed, failed
+    progress SMALLINT NOT NULL DEFAULT 0,                         -- percent complete
+
+    -- Outcome
+    result JSONB,                                                 -- generate response once completed
+    error_code VARCHAR(64),
+    error_message TEXT,
+
+    -- Timestamps
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ                                        -- set when the job finishes
+);
+
+CREATE INDEX IF NOT EXISTS idx_render_jobs_expires ON render_jobs(expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_render_jobs_unfinished ON render_jobs(created_at)
+    WHERE status IN ('queued', 'processing');
@@ -0,0 +1,10 @@
+-- R-Image-Magic Incremental Sync Schema
+-- Migration: 010_incremental_sync.sql
+-- Created: 2026-10-16
+-- Purpose: Track products skipped by incremental syncs and mirrored assets per source URL
+
+ALTER TABLE pod_sync_jobs ADD COLUMN IF NOT EXISTS skipped_items INTEGER NOT NULL DEFAULT 0;
+
+-- Each sync updates an asset's row instead of adding another
+CREATE UNIQUE INDEX IF NOT EXISTS idx_pod_assets_product_source
+    ON pod_mockup_assets(product_id, source_url);
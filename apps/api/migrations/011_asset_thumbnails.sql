@@ -0,0 +1,6 @@
+-- R-Image-Magic Asset Thumbnails Schema
+-- Migration: 011_asset_thumbnails.sql
+-- Created: 2026-10-16
+-- Purpose: Track the downscaled thumbnail stored alongside each mirrored asset
+
+ALTER TABLE pod_mockup_assets ADD COLUMN IF NOT EXISTS thumbnail_r2_key VARCHAR(500);
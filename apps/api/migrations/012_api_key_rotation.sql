@@ -0,0 +1,24 @@
+-- R-Image-Magic API Key Rotation Schema
+-- Migration: 012_api_key_rotation.sql
+-- Created: 2026-10-16
+-- Purpose: Rotate an API key's secret in place, with a grace period for the old one
+
+-- Hash of the secret replaced by the last rotation, valid until old_key_expires_at
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS old_key_hash VARCHAR(64);
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS old_key_expires_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_old_key_hash ON api_keys(old_key_hash)
+    WHERE old_key_hash IS NOT NULL;
+
+-- Audit log of key rotations
+CREATE TABLE IF NOT EXISTS api_key_rotations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    rotated_by UUID REFERENCES api_keys(id) ON DELETE SET NULL,
+    old_key_prefix VARCHAR(12) NOT NULL,
+    new_key_prefix VARCHAR(12) NOT NULL,
+    grace_period_minutes INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_key_rotations_key ON api_key_rotations(api_key_id, created_at DESC);
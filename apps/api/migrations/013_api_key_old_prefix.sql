@@ -0,0 +1,21 @@
+-- R-Image-Magic API Key Old Prefix
+-- Migration: 013_api_key_old_prefix.sql
+-- Created: 2026-10-16
+-- Purpose: Find a rotated key's old secret by prefix, so hashes are compared in the application
+
+-- Prefix of the secret replaced by the last rotation, alongside old_key_hash
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS old_key_prefix VARCHAR(12);
+
+-- Backfill keys still in a grace period from their latest rotation
+UPDATE api_keys k SET old_key_prefix = r.old_key_prefix
+FROM (
+    SELECT DISTINCT ON (api_key_id) api_key_id, old_key_prefix
+    FROM api_key_rotations
+    ORDER BY api_key_id, created_at DESC
+) r
+WHERE r.api_key_id = k.id
+  AND k.old_key_hash IS NOT NULL
+  AND k.old_key_prefix IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_old_key_prefix ON api_keys(old_key_prefix)
+    WHERE old_key_prefix IS NOT NULL;
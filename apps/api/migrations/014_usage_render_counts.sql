@@ -0,0 +1,10 @@
+-- R-Image-Magic Usage Render Counts
+-- Migration: 014_usage_render_counts.sql
+-- Created: 2026-10-16
+-- Purpose: Record mockups rendered per request and break usage down by template
+
+-- Mockups rendered by the request, NULL for requests that don't render
+ALTER TABLE usage_logs ADD COLUMN IF NOT EXISTS render_count INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_usage_logs_key_template ON usage_logs(api_key_id, created_at, template_id)
+    WHERE template_id IS NOT NULL;
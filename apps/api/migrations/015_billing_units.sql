@@ -0,0 +1,18 @@
+-- R-Image-Magic Billing Units
+-- Migration: 015_billing_units.sql
+-- Created: 2026-10-16
+-- Purpose: Count usage and quotas in weighted billing units instead of requests
+
+-- Units each request cost; requests logged before units count as one each
+ALTER TABLE usage_logs ADD COLUMN IF NOT EXISTS billable_units INTEGER NOT NULL DEFAULT 1;
+
+-- Monthly unit totals, alongside the request counts
+ALTER TABLE monthly_usage ADD COLUMN IF NOT EXISTS billable_units INTEGER;
+UPDATE monthly_usage SET billable_units = total_requests WHERE billable_units IS NULL;
+ALTER TABLE monthly_usage ALTER COLUMN billable_units SET DEFAULT 0;
+ALTER TABLE monthly_usage ALTER COLUMN billable_units SET NOT NULL;
+
+ALTER TABLE monthly_category_usage ADD COLUMN IF NOT EXISTS billable_units INTEGER;
+UPDATE monthly_category_usage SET billable_units = total_requests WHERE billable_units IS NULL;
+ALTER TABLE monthly_category_usage ALTER COLUMN billable_units SET DEFAULT 0;
+ALTER TABLE monthly_category_usage ALTER COLUMN billable_units SET NOT NULL;
@@ -0,0 +1,19 @@
+-- R-Image-Magic Quota Alerts
+-- Migration: 016_quota_alerts.sql
+-- Created: 2026-10-16
+-- Purpose: Warn keys by webhook as their monthly usage approaches the quota
+
+-- Where a key's quota threshold alerts are POSTed; NULL sends none
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS webhook_url TEXT;
+
+-- Thresholds already alerted, so each fires once per key, category and month
+CREATE TABLE IF NOT EXISTS quota_alerts (
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    year_month VARCHAR(7) NOT NULL,           -- Format: '2026-10'
+    category VARCHAR(20) NOT NULL,            -- render, catalog, sync, other
+    threshold INTEGER NOT NULL,               -- Percent of the quota, e.g. 80
+
+    fired_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (api_key_id, year_month, category, threshold)
+);
@@ -0,0 +1,7 @@
+-- R-Image-Magic Usage Request IDs
+-- Migration: 017_usage_request_id.sql
+-- Created: 2026-10-16
+-- Purpose: Tie usage log rows to the request ID echoed to clients and logged
+
+-- X-Request-Id of the logged request; NULL for rows logged before this column
+ALTER TABLE usage_logs ADD COLUMN IF NOT EXISTS request_id TEXT;
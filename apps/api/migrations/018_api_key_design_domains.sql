@@ -0,0 +1,7 @@
+-- R-Image-Magic Design Domain Allowlists
+-- Migration: 018_api_key_design_domains.sql
+-- Created: 2026-10-16
+-- Purpose: Limit the hosts a key's design URLs may be fetched from
+
+-- JSON array of domains, subdomains included; NULL or [] allows any public host
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS allowed_design_domains JSONB;
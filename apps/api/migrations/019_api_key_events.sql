@@ -0,0 +1,19 @@
+-- R-Image-Magic API Key Soft Deletion and Audit Trail
+-- Migration: 019_api_key_events.sql
+-- Created: 2026-10-16
+-- Purpose: Keep deleted keys, and their usage history, with a log of every change to a key
+
+-- Set when a key is deleted; deleted keys no longer authenticate or list by default
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+-- Audit log of key lifecycle events
+CREATE TABLE IF NOT EXISTS key_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    event_type VARCHAR(20) NOT NULL,          -- created, revoked, rotated, updated, deleted
+    actor_key_id UUID REFERENCES api_keys(id) ON DELETE SET NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_key_events_key ON key_events(key_id, created_at);
@@ -0,0 +1,22 @@
+-- R-Image-Magic Catalog Search
+-- Migration: 020_catalog_search.sql
+-- Created: 2026-10-16
+-- Purpose: Full-text search over synced products, their brands, and their variants
+
+-- Product name (A), brand and model (B), product type (C), and the sizes and
+-- colors of available variants (D). Kept current by the catalog sync, which
+-- rewrites it whenever a product or its variants change.
+ALTER TABLE pod_products ADD COLUMN IF NOT EXISTS search_vector TSVECTOR;
+
+CREATE INDEX IF NOT EXISTS idx_pod_products_search ON pod_products USING GIN (search_vector);
+
+-- Backfill products synced before this migration
+UPDATE pod_products p SET search_vector =
+    setweight(to_tsvector('simple', COALESCE(p.name, '')), 'A') ||
+    setweight(to_tsvector('simple', concat_ws(' ', p.brand, p.model)), 'B') ||
+    setweight(to_tsvector('simple', COALESCE(p.product_type, '')), 'C') ||
+    setweight(to_tsvector('simple', COALESCE((
+        SELECT string_agg(DISTINCT concat_ws(' ', v.size, v.color_name), ' ')
+        FROM pod_product_variants v
+        WHERE v.product_id = p.id AND v.is_available
+    ), '')), 'D');
@@ -0,0 +1,10 @@
+-- R-Image-Magic Asset Cleanup
+-- Migration: 021_asset_cleanup.sql
+-- Created: 2026-10-16
+-- Purpose: Record orphaned asset cleanups as sync jobs and look up thumbnails by key
+
+-- Scanned, orphaned, and deleted keys and bytes reclaimed by asset_cleanup jobs
+ALTER TABLE pod_sync_jobs ADD COLUMN IF NOT EXISTS cleanup_summary JSONB;
+
+-- Cleanups check listed keys against both columns
+CREATE INDEX IF NOT EXISTS idx_pod_assets_thumbnail_r2_key ON pod_mockup_assets(thumbnail_r2_key);
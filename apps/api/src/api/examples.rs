@@ -0,0 +1,305 @@
+//! Request examples built from the loaded templates
+//!
+//! Served at `/api-docs/examples` and embedded into the OpenAPI spec so the
+//! Swagger UI's "Try it out" starts from a request that actually renders.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use utoipa::openapi::example::ExampleBuilder;
+use utoipa::openapi::RefOr;
+
+use crate::engine::{ChromaSubsampling, JpegPreset, OutputFormat, TemplateManager, BLEND_MODES};
+
+/// Template named in examples when none are loaded; written by `bootstrap-templates`
+const FALLBACK_TEMPLATE_ID: &str = "starter-tshirt-white-front";
+
+/// Print area assumed when the example template is not loaded
+const FALLBACK_PRINT_AREA: (i32, i32) = (1800, 2400);
+
+/// Templates listed in the examples document
+const MAX_EXAMPLE_TEMPLATES: usize = 10;
+
+/// Templates rendered in the batch example
+const BATCH_EXAMPLE_ITEMS: usize = 3;
+
+/// A common design position, with offsets relative to the print area size
+struct PlacementPreset {
+    name: &'static str,
+    description: &'static str,
+    scale: f64,
+    offset_x_ratio: f64,
+    offset_y_ratio: f64,
+}
+
+const PLACEMENT_PRESETS: &[PlacementPreset] = &[
+    PlacementPreset {
+        name: "center_chest",
+        description: "Standard front print, centered and raised toward the chest",
+        scale: 0.45,
+        offset_x_ratio: 0.0,
+        offset_y_ratio: -0.12,
+    },
+    PlacementPreset {
+        name: "left_chest",
+        description: "Pocket-sized hit over the wearer's left chest",
+        scale: 0.15,
+        offset_x_ratio: 0.22,
+        offset_y_ratio: -0.3,
+    },
+    PlacementPreset {
+        name: "full_front",
+        description: "Design filling most of the print area",
+        scale: 0.9,
+        offset_x_ratio: 0.0,
+        offset_y_ratio: 0.0,
+    },
+];
+
+/// A placement preset resolved against one template's print area
+#[derive(Debug, Clone, Serialize)]
+pub struct PresetPlacement {
+    pub description: &'static str,
+    pub scale: f64,
+    pub offset_x: i32,
+    pub offset_y: i32,
+}
+
+impl PlacementPreset {
+    fn resolve(&self, (width, height): (i32, i32)) -> PresetPlacement {
+        PresetPlacement {
+            description: self.description,
+            scale: self.scale,
+            offset_x: (self.offset_x_ratio * width as f64).round() as i32,
+            offset_y: (self.offset_y_ratio * height as f64).round() as i32,
+        }
+    }
+
+    /// The placement object as sent in a request
+    fn request_json(&self, print_area: (i32, i32)) -> Value {
+        let placement = self.resolve(print_area);
+        json!({
+            "scale": placement.scale,
+            "offset_x": placement.offset_x,
+            "offset_y": placement.offset_y,
+        })
+    }
+}
+
+/// Valid enum values and ready-to-send request bodies
+#[derive(Debug, Serialize)]
+pub struct RequestExamples {
+    /// Loaded template IDs, sorted; the first is used in the examples
+    pub template_ids: Vec<String>,
+    pub blend_modes: Vec<&'static str>,
+    pub output_formats: Vec<OutputFormat>,
+    pub jpeg_presets: Vec<JpegPreset>,
+    pub chroma_subsampling: Vec<ChromaSubsampling>,
+    /// Presets resolved against the example template's print area
+    pub placement_presets: BTreeMap<&'static str, PresetPlacement>,
+    /// Request bodies keyed by endpoint and variant
+    pub requests: BTreeMap<&'static str, Value>,
+}
+
+impl RequestExamples {
+    /// Build examples from the templates currently loaded
+    pub fn from_templates(manager: &TemplateManager) -> Self {
+        let mut template_ids = manager.list_ids();
+        template_ids.sort();
+        template_ids.truncate(MAX_EXAMPLE_TEMPLATES);
+
+        let print_area = |id: &str| {
+            manager.get(id).map_or(FALLBACK_PRINT_AREA, |template| {
+                let area = &template.metadata.print_area;
+                (area.width, area.height)
+            })
+        };
+        let template_id = template_ids
+            .first()
+            .cloned()
+            .unwrap_or_else(|| FALLBACK_TEMPLATE_ID.to_string());
+        let area = print_area(&template_id);
+        let preset = |name: &str| {
+            PLACEMENT_PRESETS
+                .iter()
+                .find(|preset| preset.name == name)
+                .expect("placement preset exists")
+        };
+
+        let placement_presets = PLACEMENT_PRESETS
+            .iter()
+            .map(|preset| (preset.name, preset.resolve(area)))
+            .collect();
+
+        let mut requests = BTreeMap::new();
+        requests.insert(
+            "generate",
+            json!({
+                "design_url": "https://example.com/designs/logo.png",
+                "template_id": template_id,
+                "placement": preset("center_chest").request_json(area),
+                "options": {
+                    "displacement_strength": 10.0,
+                    "output_format": "png",
+                },
+            }),
+        );
+        requests.insert(
+            "generate_multiple_designs",
+            json!({
+                "template_id": template_id,
+                "designs": [
+                    {
+                        "design_url": "https://example.com/designs/logo.png",
+                        "placement": preset("center_chest").request_json(area),
+                    },
+                    {
+                        "design_url": "https://example.com/designs/pocket.png",
+                        "placement": preset("left_chest").request_json(area),
+                        "blend_mode": "normal",
+                    },
+                ],
+            }),
+        );
+
+        let batch_ids: Vec<&str> = if template_ids.is_empty() {
+            vec![FALLBACK_TEMPLATE_ID]
+        } else {
+            template_ids
+                .iter()
+                .take(BATCH_EXAMPLE_ITEMS)
+                .map(String::as_str)
+                .collect()
+        };
+        let items: Vec<Value> = batch_ids
+            .iter()
+            .map(|id| {
+                json!({
+                    "template_id": id,
+                    "placement": preset("center_chest").request_json(print_area(id)),
+                })
+            })
+            .collect();
+        requests.insert(
+            "generate_batch",
+            json!({
+                "design_url": "https://example.com/designs/logo.png",
+                "items": items,
+                "options": {
+                    "output_format": "jpeg",
+                    "jpeg_preset": "web",
+                },
+            }),
+        );
+
+        RequestExamples {
+            template_ids,
+            blend_modes: BLEND_MODES.to_vec(),
+            output_formats: vec![OutputFormat::Png, OutputFormat::Jpeg, OutputFormat::Webp],
+            jpeg_presets: vec![
+                JpegPreset::Web,
+                JpegPreset::Standard,
+                JpegPreset::High,
+                JpegPreset::Print,
+            ],
+            chroma_subsampling: vec![
+                ChromaSubsampling::Yuv444,
+                ChromaSubsampling::Yuv422,
+                ChromaSubsampling::Yuv420,
+            ],
+            placement_presets,
+            requests,
+        }
+    }
+
+    /// Attach the request bodies to the generate and batch operations of `doc`
+    pub fn embed(&self, doc: &mut utoipa::openapi::OpenApi) {
+        let embedded = [
+            ("/api/v1/mockups/generate", "generate", "Single design"),
+            (
+                "/api/v1/mockups/generate",
+                "generate_multiple_designs",
+                "Chest logo plus pocket hit",
+            ),
+            (
+                "/api/v1/mockups/generate-batch",
+                "generate_batch",
+                "One design on several templates",
+            ),
+        ];
+
+        for (path, name, summary) in embedded {
+            let content = doc
+                .paths
+                .paths
+                .get_mut(path)
+                .and_then(|item| item.post.as_mut())
+                .and_then(|operation| operation.request_body.as_mut())
+                .and_then(|body| body.content.get_mut("application/json"));
+            let (Some(content), Some(value)) = (content, self.requests.get(name)) else {
+                continue;
+            };
+            let example = ExampleBuilder::new()
+                .summary(summary)
+                .value(Some(value.clone()))
+                .build();
+            content.examples.insert(name.to_string(), RefOr::T(example));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::generate::GenerateRequest;
+    use crate::api::openapi::ApiDoc;
+    use crate::domain::PlacementSpec;
+    use utoipa::OpenApi;
+
+    #[test]
+    fn test_presets_fit_any_print_area() {
+        for area in [(1800, 2400), (480, 600), (1000, 400)] {
+            for preset in PLACEMENT_PRESETS {
+                let resolved = preset.resolve(area);
+                let placement = PlacementSpec {
+                    scale: resolved.scale,
+                    offset_x: resolved.offset_x,
+                    offset_y: resolved.offset_y,
+                    print_area_width: area.0,
+                    print_area_height: area.1,
+                    ..PlacementSpec::default()
+                };
+                assert!(
+                    placement.validate().is_ok(),
+                    "{} does not fit {:?}",
+                    preset.name,
+                    area
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_examples_parse_and_embed() {
+        let dir = std::env::temp_dir().join(format!("examples-{}", uuid::Uuid::new_v4()));
+        let examples = RequestExamples::from_templates(&TemplateManager::new(&dir).unwrap());
+        assert!(examples.template_ids.is_empty());
+
+        for name in ["generate", "generate_multiple_designs"] {
+            let request: GenerateRequest =
+                serde_json::from_value(examples.requests[name].clone()).unwrap();
+            assert_eq!(request.template_id, FALLBACK_TEMPLATE_ID);
+        }
+
+        let mut doc = ApiDoc::openapi();
+        examples.embed(&mut doc);
+        let spec = serde_json::to_value(&doc).unwrap();
+        let content = &spec["paths"]["/api/v1/mockups/generate"]["post"]["requestBody"]["content"]
+            ["application/json"];
+        assert_eq!(
+            content["examples"]["generate"]["value"]["template_id"],
+            FALLBACK_TEMPLATE_ID
+        );
+        assert!(content["examples"]["generate_multiple_designs"].is_object());
+    }
+}
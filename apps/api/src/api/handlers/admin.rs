@@ -0,0 +1,339 @@
+//! Admin endpoints
+//!
+//! Runtime operations restricted to enterprise tier keys.
+
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::middleware::ApiKeyAuth;
+use crate::config::Settings;
+use crate::db::{parse_year_month, UsageRepository};
+use crate::engine::EvictionPolicy;
+use crate::parity::ParityError;
+use crate::AppState;
+
+/// Reject keys below the enterprise tier
+pub(crate) fn require_enterprise(req: &HttpRequest, action: &str) -> Result<(), HttpResponse> {
+    match req.extensions().get::<ApiKeyAuth>() {
+        Some(auth) if auth.tier == "enterprise" => Ok(()),
+        Some(_) => Err(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "forbidden",
+            "message": format!("Only enterprise tier keys can {}", action)
+        }))),
+        None => Err(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "unauthorized",
+            "message": "API key required"
+        }))),
+    }
+}
+
+/// Reload configuration and apply the settings that can change at runtime
+/// POST /api/v1/admin/config/reload
+///
+/// Currently applies the template idle eviction timeout, the billing unit
+/// weights and the quota alert thresholds. Other settings still require a
+/// restart.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/config/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Settings applied, with any configuration warnings"),
+        (status = 400, description = "Configuration could not be loaded or is invalid"),
+        (status = 403, description = "Not an enterprise key")
+    )
+)]
+pub async fn reload_config(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "reload configuration") {
+        return response;
+    }
+
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            tracing::warn!(error = %e, "Configuration reload failed");
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Failed to load configuration: {}", e)
+            }));
+        }
+    };
+
+    let report = settings.validate();
+    if report.has_errors() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Configuration is invalid",
+            "issues": report.issues,
+        }));
+    }
+
+    let policy = EvictionPolicy {
+        idle_timeout: settings.templates.idle_timeout(),
+        max_resident_templates: settings.templates.resident_template_limit(),
+        max_resident_bytes: settings.templates.resident_bytes_limit(),
+    };
+    state.template_manager.set_eviction_policy(policy);
+    *state.billing.write() = settings.billing.clone();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "applied": {
+            "templates": {
+                "idle_eviction_secs": settings.templates.idle_eviction_secs,
+                "max_resident_templates": settings.templates.max_resident_templates,
+                "max_resident_bytes": settings.templates.max_resident_bytes,
+            },
+            "billing": {
+                "weights": settings.billing.weights,
+                "alert_thresholds": settings.billing.alert_thresholds,
+            }
+        },
+        "warnings": report.issues,
+        "template_memory": state.template_manager.memory_stats(),
+    }))
+}
+
+/// Query parameters for rebuilding usage aggregates
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RebuildUsageQuery {
+    /// Month to rebuild, as `YYYY-MM`
+    pub month: String,
+    /// Report the recomputed values without writing them
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Recompute a month's usage aggregates from the raw usage logs
+/// POST /api/v1/admin/usage/rebuild?month=YYYY-MM
+///
+/// Each key is rebuilt in its own transaction. With `dry_run=true` nothing
+/// is written and the response shows what would change.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/usage/rebuild",
+    tag = "admin",
+    params(RebuildUsageQuery),
+    responses(
+        (status = 200, description = "Keys scanned, and the aggregates that changed or would change"),
+        (status = 400, description = "Month is not YYYY-MM"),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 503, description = "No database configured")
+    )
+)]
+pub async fn rebuild_usage(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<RebuildUsageQuery>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "rebuild usage aggregates") {
+        return response;
+    }
+
+    let Some((year, month)) = parse_year_month(&query.month) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "invalid_month",
+            "message": "month must be formatted as YYYY-MM"
+        }));
+    };
+
+    let Some(pool) = state.db_pool.clone() else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "database_unavailable",
+            "message": "Usage aggregates require a database"
+        }));
+    };
+
+    let repo = UsageRepository::new(pool);
+    match repo.rebuild_monthly_usage(year, month, query.dry_run).await {
+        Ok(rebuilds) => {
+            let changed: Vec<_> = rebuilds.iter().filter(|r| r.changed()).collect();
+            HttpResponse::Ok().json(serde_json::json!({
+                "month": format!("{:04}-{:02}", year, month),
+                "dry_run": query.dry_run,
+                "keys_scanned": rebuilds.len(),
+                "keys_changed": changed.len(),
+                "changes": changed,
+            }))
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, month = %query.month, "Usage rebuild failed");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to rebuild usage aggregates"
+            }))
+        }
+    }
+}
+
+/// Largest number of products sampled into one parity run
+const MAX_PARITY_SAMPLE: i64 = 50;
+
+/// Request body for starting a parity run
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StartParityRunRequest {
+    /// Provider code, e.g. `printful`
+    pub provider: String,
+    /// Design rendered by both the provider and us
+    pub design_url: String,
+    /// Number of catalog products to sample (default 5, max 50)
+    #[serde(default = "default_parity_sample")]
+    #[schema(default = 5)]
+    pub sample_size: i64,
+    /// Check these products instead of sampling the catalog
+    #[serde(default)]
+    pub product_ids: Vec<String>,
+}
+
+fn default_parity_sample() -> i64 {
+    5
+}
+
+/// Start a provider mockup parity run in the background
+/// POST /api/v1/admin/parity/runs
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/parity/runs",
+    tag = "admin",
+    request_body = StartParityRunRequest,
+    responses(
+        (status = 202, description = "Run started, with the products it checks"),
+        (status = 400, description = "Invalid design URL, sample size, or provider"),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 409, description = "A parity run is already in progress"),
+        (status = 503, description = "No database configured")
+    )
+)]
+pub async fn start_parity_run(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<StartParityRunRequest>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "run parity checks") {
+        return response;
+    }
+
+    let Some(parity) = state.parity.clone() else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "database_unavailable",
+            "message": "Parity results require a database"
+        }));
+    };
+
+    let body = body.into_inner();
+    if !body.design_url.starts_with("http://") && !body.design_url.starts_with("https://") {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "invalid_design_url",
+            "message": "design_url must be an http(s) URL the provider can fetch"
+        }));
+    }
+    if !(1..=MAX_PARITY_SAMPLE).contains(&body.sample_size)
+        || body.product_ids.len() > MAX_PARITY_SAMPLE as usize
+    {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "invalid_sample_size",
+            "message": format!("A run checks between 1 and {} products", MAX_PARITY_SAMPLE)
+        }));
+    }
+
+    match parity
+        .start(
+            &body.provider,
+            body.design_url,
+            body.product_ids,
+            body.sample_size,
+        )
+        .await
+    {
+        Ok((run_id, products)) => HttpResponse::Accepted().json(serde_json::json!({
+            "run_id": run_id,
+            "provider": body.provider.to_lowercase(),
+            "products": products,
+        })),
+        Err(e @ ParityError::AlreadyRunning) => HttpResponse::Conflict().json(serde_json::json!({
+            "error": "run_in_progress",
+            "message": e.to_string()
+        })),
+        Err(e @ (ParityError::ProviderNotFound(_) | ParityError::NoProducts(_))) => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "invalid_provider",
+                "message": e.to_string()
+            }))
+        }
+        Err(ParityError::Db(e)) => {
+            tracing::warn!(error = %e, "Failed to start parity run");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to start parity run"
+            }))
+        }
+    }
+}
+
+/// Query parameters for the parity report
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ParityReportQuery {
+    /// Only include this provider
+    pub provider: Option<String>,
+    /// Maximum number of products returned (default 100)
+    pub limit: Option<i64>,
+}
+
+/// Latest parity score per product, worst first
+/// GET /api/v1/admin/parity
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/parity",
+    tag = "admin",
+    params(ParityReportQuery),
+    responses(
+        (status = 200, description = "Score summary and the latest result per product"),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 503, description = "No database configured")
+    )
+)]
+pub async fn parity_report(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ParityReportQuery>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "view parity results") {
+        return response;
+    }
+
+    let Some(parity) = state.parity.clone() else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "database_unavailable",
+            "message": "Parity results require a database"
+        }));
+    };
+
+    let provider = query.provider.as_deref().map(str::to_lowercase);
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    match parity.repo().latest(provider.as_deref(), limit).await {
+        Ok(results) => {
+            let scores: Vec<f64> = results.iter().filter_map(|r| r.score).collect();
+            let average_score =
+                (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64);
+            let min_score = scores.iter().copied().reduce(f64::min);
+
+            HttpResponse::Ok().json(serde_json::json!({
+                "summary": {
+                    "products": results.len(),
+                    "scored": scores.len(),
+                    "failed": results.len() - scores.len(),
+                    "average_score": average_score,
+                    "min_score": min_score,
+                },
+                "results": results,
+            }))
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to load parity results");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to load parity results"
+            }))
+        }
+    }
+}
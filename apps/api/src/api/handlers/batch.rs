@@ -0,0 +1,580 @@
+//! Batch mockup generation endpoint
+
+use actix_multipart::Multipart;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::generate::{
+    bad_request, design_domains, design_error_status, ensure_design_domains,
+    ensure_saved_render_capacity, publish_render_event, read_upload, record_saved_render, ApiError,
+    Dimensions, ErrorResponse, GenerateOptions,
+};
+use crate::api::middleware::{ApiKeyAuth, RenderUsage, RequestId};
+use crate::domain::PlacementSpec;
+use crate::engine::{
+    DesignLayer, DesignSource, GenerationLimits, MockupRequest, MockupResult, OutputSettings,
+    TemplateError,
+};
+use crate::jobs::{JobFile, JobOutputs};
+use crate::uploads::{FailedUpload, UploadTarget};
+use crate::webhooks::EventType;
+use crate::AppState;
+
+/// Most templates a single batch may target
+const MAX_BATCH_ITEMS: usize = 50;
+
+/// One template to render the batch design onto
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchItem {
+    /// Template ID (e.g., "white_male_front")
+    pub template_id: String,
+    /// Placement specification
+    pub placement: PlacementSpec,
+    /// Overrides `options.displacement_strength` for this template
+    pub displacement_strength: Option<f64>,
+}
+
+/// Request body for batch generation
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GenerateBatchRequest {
+    /// URL of the design image, fetched once for the whole batch
+    pub design_url: String,
+    /// Templates to render, in response order
+    pub items: Vec<BatchItem>,
+    /// Options shared by every item
+    #[serde(default)]
+    pub options: GenerateOptions,
+    /// Store outputs in R2 and return their URLs instead of data URIs
+    #[serde(default)]
+    pub upload: bool,
+}
+
+/// Request part of a multipart batch upload
+#[derive(Debug, Deserialize)]
+pub struct GenerateBatchUploadRequest {
+    pub items: Vec<BatchItem>,
+    #[serde(default)]
+    pub options: GenerateOptions,
+    #[serde(default)]
+    pub upload: bool,
+}
+
+/// Response for batch generation
+#[derive(Serialize, ToSchema)]
+pub struct GenerateBatchResponse {
+    pub success: bool,
+    /// Download every successful output as a ZIP from `/api/v1/jobs/{job_id}/download`;
+    /// null when no item succeeded
+    pub job_id: Option<Uuid>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub generation_time_ms: u64,
+    /// One entry per requested item, in request order
+    pub results: Vec<BatchItemResult>,
+}
+
+/// Outcome of one batch item
+#[derive(Serialize, ToSchema)]
+pub struct BatchItemResult {
+    pub template_id: String,
+    pub success: bool,
+    /// Data URI, or the uploaded object's public URL
+    pub mockup_url: Option<String>,
+    /// R2 key of the uploaded output
+    pub r2_key: Option<String>,
+    /// Set when the R2 upload failed and is being retried; the data URI is returned meanwhile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub render_id: Option<Uuid>,
+    /// MIME type of the encoded mockup
+    pub content_type: Option<String>,
+    pub dimensions: Option<Dimensions>,
+    /// Set when the displacement strength was clamped to the template's range
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    pub error: Option<ApiError>,
+}
+
+impl BatchItemResult {
+    fn failed(template_id: String, code: &str, message: String) -> Self {
+        BatchItemResult {
+            template_id,
+            success: false,
+            mockup_url: None,
+            r2_key: None,
+            render_id: None,
+            content_type: None,
+            dimensions: None,
+            warning: None,
+            error: Some(ApiError {
+                code: code.to_string(),
+                message,
+            }),
+        }
+    }
+}
+
+/// POST /api/v1/mockups/generate-batch - Render one design onto many templates
+#[utoipa::path(
+    post,
+    path = "/api/v1/mockups/generate-batch",
+    tag = "mockups",
+    request_body = GenerateBatchRequest,
+    responses(
+        (status = 200, description = "Batch processed; check each item's success", body = GenerateBatchResponse),
+        (status = 400, description = "Invalid batch or design could not be fetched", body = ErrorResponse),
+        (status = 403, description = "Design URL is outside the key's allowed design domains (`DESIGN_DOMAIN_NOT_ALLOWED`)", body = ErrorResponse),
+        (status = 422, description = "Design URL points at an internal host, or returned an oversized, undersized, or non-PNG/JPEG/WebP image", body = ErrorResponse)
+    )
+)]
+pub async fn generate_batch(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<GenerateBatchRequest>,
+) -> HttpResponse {
+    let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+    let body = body.into_inner();
+
+    let output = match validate_batch(&state, &body.items, &body.options, body.upload) {
+        Ok(output) => output,
+        Err(response) => return response,
+    };
+    if let Err(response) = ensure_design_domains(&req, [body.design_url.as_str()]) {
+        return response;
+    }
+    if body.upload {
+        let count = body.items.len() as i64;
+        if let Err(response) = ensure_saved_render_capacity(&req, &state, count).await {
+            return response;
+        }
+    }
+
+    info!(
+        design_url = %body.design_url,
+        items = body.items.len(),
+        "Processing batch mockup generation request"
+    );
+
+    // Every item shares one download of the design; the options were checked by validate_batch
+    let limits = body
+        .options
+        .generation_limits(&state.settings.server, Instant::now(), design_domains(&req))
+        .unwrap_or_default();
+    let design = match state
+        .template_manager
+        .fetch_design_bytes(&body.design_url, &limits)
+        .await
+    {
+        Ok(design) => design,
+        Err(e) => {
+            if matches!(
+                e.design_code(),
+                Some("DESIGN_URL_FORBIDDEN" | "DESIGN_DOMAIN_NOT_ALLOWED")
+            ) {
+                warn!(
+                    api_key_id = ?api_key_id,
+                    design_url = %body.design_url,
+                    error = %e,
+                    "Refused design URL"
+                );
+            } else {
+                error!(error = %e, "Failed to fetch batch design");
+            }
+            return match e.design_code() {
+                Some(code) => HttpResponse::build(design_error_status(code)).json(ErrorResponse {
+                    success: false,
+                    request_id: RequestId::current(),
+                    error: ApiError {
+                        code: code.to_string(),
+                        message: e.to_string(),
+                    },
+                }),
+                None => bad_request("DESIGN_FETCH_FAILED", e.to_string()),
+            };
+        }
+    };
+
+    render_batch(
+        &req,
+        state,
+        api_key_id,
+        design,
+        body.items,
+        body.options,
+        output,
+        body.upload,
+    )
+    .await
+}
+
+/// POST /api/v1/mockups/generate-batch (multipart/form-data) - Batch from an uploaded design
+///
+/// Parts: `design` (image file) and `request` (JSON with items, options, and upload).
+pub async fn generate_batch_upload(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    payload: Multipart,
+) -> HttpResponse {
+    let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+
+    let (design, request) = match read_upload(payload, state.settings.server.max_upload_bytes).await
+    {
+        Ok(parts) => parts,
+        Err(response) => return response,
+    };
+    let request: GenerateBatchUploadRequest = match serde_json::from_slice(&request) {
+        Ok(parsed) => parsed,
+        Err(e) => return bad_request("INVALID_REQUEST", e.to_string()),
+    };
+
+    let output = match validate_batch(&state, &request.items, &request.options, request.upload) {
+        Ok(output) => output,
+        Err(response) => return response,
+    };
+    if request.upload {
+        let count = request.items.len() as i64;
+        if let Err(response) = ensure_saved_render_capacity(&req, &state, count).await {
+            return response;
+        }
+    }
+
+    info!(
+        design_bytes = design.len(),
+        items = request.items.len(),
+        "Processing batch mockup upload request"
+    );
+
+    render_batch(
+        &req,
+        state,
+        api_key_id,
+        design,
+        request.items,
+        request.options,
+        output,
+        request.upload,
+    )
+    .await
+}
+
+/// Reject batches that cannot be processed before fetching anything
+fn validate_batch(
+    state: &AppState,
+    items: &[BatchItem],
+    options: &GenerateOptions,
+    upload: bool,
+) -> Result<OutputSettings, HttpResponse> {
+    if items.is_empty() {
+        return Err(bad_request(
+            "INVALID_BATCH",
+            "Batch must contain at least one item".to_string(),
+        ));
+    }
+    if items.len() > MAX_BATCH_ITEMS {
+        return Err(bad_request(
+            "INVALID_BATCH",
+            format!(
+                "Batch has {} items; the limit is {}",
+                items.len(),
+                MAX_BATCH_ITEMS
+            ),
+        ));
+    }
+    if upload && !state.jobs.can_upload() {
+        return Err(bad_request(
+            "UPLOAD_UNAVAILABLE",
+            "Uploading outputs requires R2 to be configured".to_string(),
+        ));
+    }
+    options.background_removal()?;
+    options.realism()?;
+    options.fetch_timeout(&state.settings.server)?;
+    options.output_settings(state.settings.output.jpeg_preset)
+}
+
+/// Inputs shared by every item of a batch
+struct BatchContext {
+    state: web::Data<AppState>,
+    api_key_id: Option<Uuid>,
+    job_id: Uuid,
+    design: Bytes,
+    options: GenerateOptions,
+    output: OutputSettings,
+    upload: bool,
+    /// Cancelled when the batch request goes away; each item gets its own deadline
+    limits: GenerationLimits,
+}
+
+/// Render every item, at most `server.batch_concurrency` at a time
+async fn render_batch(
+    req: &HttpRequest,
+    state: web::Data<AppState>,
+    api_key_id: Option<Uuid>,
+    design: Bytes,
+    items: Vec<BatchItem>,
+    options: GenerateOptions,
+    output: OutputSettings,
+    upload: bool,
+) -> HttpResponse {
+    let start = Instant::now();
+
+    let concurrency = state
+        .settings
+        .server
+        .batch_concurrency
+        .unwrap_or_else(num_cpus::get)
+        .max(1);
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut outputs = JobOutputs::new("batch", api_key_id, Vec::new());
+    // Items render on blocking threads that outlive a disconnected client unless told to stop
+    let mut limits = GenerationLimits::default();
+    let _cancel = limits.cancel_on_drop();
+    let ctx = Arc::new(BatchContext {
+        state: state.clone(),
+        api_key_id,
+        job_id: outputs.id,
+        design,
+        options,
+        output,
+        upload,
+        limits,
+    });
+
+    // join_all keeps results in request order while renders finish in any order
+    let renders = items.into_iter().enumerate().map(|(index, item)| {
+        let ctx = ctx.clone();
+        let semaphore = semaphore.clone();
+        async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .expect("batch semaphore is never closed");
+            render_item(&ctx, index, item).await
+        }
+    });
+    let rendered = futures::future::join_all(renders).await;
+
+    let mut results = Vec::with_capacity(rendered.len());
+    for (result, file) in rendered {
+        results.push(result);
+        outputs.files.extend(file);
+    }
+
+    let succeeded = outputs.files.len();
+    let failed = results.len() - succeeded;
+    let job_id = (succeeded > 0).then(|| state.jobs.insert(outputs));
+    let elapsed = start.elapsed().as_millis() as u64;
+
+    info!(
+        succeeded,
+        failed,
+        generation_time_ms = elapsed,
+        "Batch mockup generation finished"
+    );
+
+    // Usage is attributed to the template only when every item used it
+    let template_id = results
+        .first()
+        .map(|first| first.template_id.clone())
+        .filter(|first| results.iter().all(|r| &r.template_id == first));
+    RenderUsage::new(template_id, succeeded as i32).record(req);
+
+    HttpResponse::Ok().json(GenerateBatchResponse {
+        success: true,
+        job_id,
+        succeeded,
+        failed,
+        generation_time_ms: elapsed,
+        results,
+    })
+}
+
+/// Render one item, returning its result and, on success, its job output file
+async fn render_item(
+    ctx: &BatchContext,
+    index: usize,
+    item: BatchItem,
+) -> (BatchItemResult, Option<JobFile>) {
+    let template_id = item.template_id;
+    let displacement_strength = item
+        .displacement_strength
+        .or(ctx.options.displacement_strength);
+
+    let (result, warning) =
+        match render_design(ctx, &template_id, item.placement, displacement_strength).await {
+            Ok(rendered) => rendered,
+            Err((code, message)) => {
+                warn!(template_id = %template_id, code, error = %message, "Batch item failed");
+                publish_render_event(
+                    &ctx.state,
+                    ctx.api_key_id,
+                    EventType::RenderFailed,
+                    serde_json::json!({
+                        "template_id": template_id,
+                        "batch_id": ctx.job_id,
+                        "error": message,
+                    }),
+                );
+                return (BatchItemResult::failed(template_id, code, message), None);
+            }
+        };
+
+    publish_render_event(
+        &ctx.state,
+        ctx.api_key_id,
+        EventType::RenderCompleted,
+        serde_json::json!({
+            "template_id": template_id,
+            "batch_id": ctx.job_id,
+            "width": result.width,
+            "height": result.height,
+        }),
+    );
+
+    let name = format!(
+        "{:02}-{}.{}",
+        index + 1,
+        template_id,
+        ctx.output.format.extension()
+    );
+    let (file, mockup_url, r2_key, render_id) = if ctx.upload {
+        let key = format!("generated/batch/{}/{}", ctx.job_id, name);
+        match ctx
+            .state
+            .jobs
+            .upload(
+                name.clone(),
+                key.clone(),
+                result.bytes.clone(),
+                result.content_type,
+            )
+            .await
+        {
+            Ok((file, public_url)) => (file, public_url, Some(key), None),
+            Err(e) => {
+                error!(template_id = %template_id, error = %e, "Failed to upload batch output");
+                // Keep the output in the job and retry the upload in the background
+                let deferred = ctx.state.uploads.defer(
+                    ctx.api_key_id,
+                    &template_id,
+                    result.content_type,
+                    result.bytes.clone(),
+                    vec![FailedUpload {
+                        target: UploadTarget::R2,
+                        r2_key: Some(key.clone()),
+                        error: e.to_string(),
+                    }],
+                );
+                let Some(render_id) = deferred else {
+                    let failed =
+                        BatchItemResult::failed(template_id, "UPLOAD_FAILED", e.to_string());
+                    return (failed, None);
+                };
+                let file = JobFile::in_memory(name, result.bytes.clone());
+                (file, Some(result.data_uri()), Some(key), Some(render_id))
+            }
+        }
+    } else {
+        let file = JobFile::in_memory(name, result.bytes.clone());
+        (file, Some(result.data_uri()), None, None)
+    };
+    if let Some(ref key) = r2_key {
+        record_saved_render(&ctx.state, ctx.api_key_id, key).await;
+    }
+
+    let item_result = BatchItemResult {
+        template_id,
+        success: true,
+        mockup_url,
+        r2_key,
+        render_id,
+        content_type: Some(result.content_type.to_string()),
+        dimensions: Some(Dimensions {
+            width: result.width,
+            height: result.height,
+        }),
+        warning,
+        error: None,
+    };
+    (item_result, Some(file))
+}
+
+/// Composite the design onto one template, with any displacement clamp warning,
+/// returning an error code and message on failure
+async fn render_design(
+    ctx: &BatchContext,
+    template_id: &str,
+    mut placement: PlacementSpec,
+    displacement_strength: Option<f64>,
+) -> Result<(MockupResult, Option<String>), (&'static str, String)> {
+    let manager = ctx.state.template_manager.clone();
+    let template = manager.get(template_id).ok_or_else(|| {
+        (
+            "TEMPLATE_NOT_FOUND",
+            format!("Template '{}' does not exist", template_id),
+        )
+    })?;
+
+    let print_area = &template.metadata.print_area;
+    placement = placement.in_print_area(print_area.width, print_area.height);
+    placement
+        .validate()
+        .map_err(|e| ("INVALID_PLACEMENT", e.to_string()))?;
+    let displacement = &template.metadata.displacement;
+    // Checked by validate_batch
+    let realism_strength = match ctx.options.realism().ok().flatten() {
+        Some(realism) => {
+            let stats = manager
+                .displacement_stats(&template)
+                .await
+                .map_err(|e| ("GENERATION_FAILED", e.to_string()))?;
+            Some(displacement.strength_for_realism(realism, stats.as_ref()))
+        }
+        None => None,
+    };
+    let (displacement_strength, warning) = displacement
+        .resolve_strength(
+            displacement_strength.or(realism_strength),
+            ctx.options.strict_displacement,
+        )
+        .map_err(|message| ("INVALID_DISPLACEMENT", message))?;
+
+    let request = MockupRequest {
+        designs: vec![DesignLayer {
+            design: DesignSource::Bytes(ctx.design.clone()),
+            placement,
+            displacement_strength,
+            blend_mode: None,
+        }],
+        template_id: template_id.to_string(),
+        apply_displacement: ctx.options.apply_displacement,
+        tint_color: ctx.options.tint_color.clone(),
+        // Checked by validate_batch
+        remove_background: ctx.options.background_removal().ok().flatten(),
+        output: ctx.output,
+        limits: GenerationLimits {
+            deadline: Some(Instant::now() + ctx.state.settings.server.generation_timeout()),
+            ..ctx.limits.clone()
+        },
+    };
+
+    // Compositing is CPU-bound; run it off the worker thread so items render in parallel
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || runtime.block_on(manager.generate_mockup(&request)))
+        .await
+        .map_err(|e| ("GENERATION_FAILED", e.to_string()))?
+        .map(|result| (result, warning))
+        .map_err(|e| match e {
+            TemplateError::Saturated { .. } | TemplateError::ShuttingDown => {
+                ("SERVER_BUSY", e.to_string())
+            }
+            TemplateError::TimedOut(_) => ("GENERATION_TIMEOUT", e.to_string()),
+            e => ("GENERATION_FAILED", e.to_string()),
+        })
+}
@@ -4,12 +4,23 @@
 
 use actix_web::{web, HttpResponse};
 use serde::{Deserialize, Serialize};
+use tokio_postgres::types::ToSql;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::db::DbPool;
+use crate::config::R2Settings;
+use crate::db::{
+    CatalogRepository, DbPool, Page, ProductSearchHit, ProviderRepository, SearchQuery,
+    MAX_PER_PAGE,
+};
+use crate::domain::{DbPodProvider, UnifiedPrintArea, UnifiedProduct};
+use crate::providers::live::LiveCatalogError;
+use crate::providers::{CatalogPage, ProviderError};
+use crate::AppState;
 
 /// Query parameters for listing products
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ProductsQuery {
     /// Filter by provider code
     pub provider: Option<String>,
@@ -19,35 +30,201 @@ pub struct ProductsQuery {
     pub product_type: Option<String>,
     /// Search by name
     pub search: Option<String>,
+    /// Only products with a variant in this size (case-insensitive)
+    pub size: Option<String>,
+    /// Only products with a variant in this color: a name (case-insensitive) or `#RRGGBB`
+    pub color: Option<String>,
+    /// Require the matching variant to be in stock; defaults to true when
+    /// `size` or `color` is given
+    pub in_stock: Option<bool>,
+    /// name (default), price, last_synced, or variant_count
+    pub sort: Option<String>,
+    /// asc (default) or desc
+    pub order: Option<String>,
     /// Page number (1-based)
     #[serde(default = "default_page")]
+    #[param(default = 1)]
     pub page: u32,
     /// Items per page
     #[serde(default = "default_per_page")]
+    #[param(default = 50)]
     pub per_page: u32,
 }
 
-fn default_page() -> u32 {
+pub(crate) fn default_page() -> u32 {
     1
 }
-fn default_per_page() -> u32 {
+pub(crate) fn default_per_page() -> u32 {
     50
 }
 
+/// Query parameters for catalog search
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CatalogSearchQuery {
+    /// Words to find in product names, brands, models, types, and variant
+    /// sizes and colors; every word must match, the last one as a prefix
+    pub q: Option<String>,
+    /// Most results to return, up to 100
+    #[serde(default = "default_search_limit")]
+    #[param(default = 20)]
+    pub limit: u32,
+}
+
+fn default_search_limit() -> u32 {
+    20
+}
+
+/// Catalog search results, best match first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CatalogSearchResponse {
+    pub query: String,
+    pub results: Vec<ProductSearchHit>,
+}
+
+/// Query parameters for product details
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProductDetailQuery {
+    /// Include synced mockup assets (default true)
+    #[serde(default = "default_include_assets")]
+    #[param(default = true)]
+    pub include_assets: bool,
+}
+
+fn default_include_assets() -> bool {
+    true
+}
+
+/// Field the product list is ordered by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductSort {
+    #[default]
+    Name,
+    Price,
+    LastSynced,
+    VariantCount,
+}
+
+impl ProductSort {
+    /// Every accepted `sort` value
+    const ALLOWED: [&'static str; 4] = ["name", "price", "last_synced", "variant_count"];
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "name" => Some(ProductSort::Name),
+            "price" => Some(ProductSort::Price),
+            "last_synced" => Some(ProductSort::LastSynced),
+            "variant_count" => Some(ProductSort::VariantCount),
+            _ => None,
+        }
+    }
+
+    /// Column the sort orders by
+    fn column(self) -> &'static str {
+        match self {
+            ProductSort::Name => "p.name",
+            ProductSort::Price => "p.base_price_cents",
+            ProductSort::LastSynced => "p.last_synced_at",
+            ProductSort::VariantCount => "variant_count",
+        }
+    }
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Sort applied to a product list, echoed in the response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ProductOrdering {
+    pub sort: ProductSort,
+    pub order: SortOrder,
+}
+
+impl ProductOrdering {
+    /// Validate the `sort` and `order` query parameters
+    fn from_query(query: &ProductsQuery) -> Result<Self, HttpResponse> {
+        let sort = match query.sort.as_deref() {
+            None => ProductSort::default(),
+            Some(value) => ProductSort::parse(value).ok_or_else(|| {
+                HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Invalid sort '{}'", value),
+                    "allowed": ProductSort::ALLOWED,
+                }))
+            })?,
+        };
+        let order = match query.order.as_deref() {
+            None | Some("asc") => SortOrder::Asc,
+            Some("desc") => SortOrder::Desc,
+            Some(value) => {
+                return Err(HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Invalid order '{}'", value),
+                    "allowed": ["asc", "desc"],
+                })))
+            }
+        };
+        Ok(ProductOrdering { sort, order })
+    }
+
+    /// ORDER BY clause; ties fall back to the name, then the ID, so pages don't overlap
+    fn order_by(self) -> String {
+        let direction = match self.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        match self.sort {
+            ProductSort::Name => format!("ORDER BY p.name {}, p.id", direction),
+            ProductSort::Price => format!(
+                "ORDER BY {} {} NULLS LAST, p.name, p.id",
+                self.sort.column(),
+                direction
+            ),
+            sort => format!("ORDER BY {} {}, p.name, p.id", sort.column(), direction),
+        }
+    }
+}
+
 /// Provider response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ProviderResponse {
     pub id: Uuid,
     pub code: String,
     pub name: String,
+    pub api_base_url: String,
+    pub auth_type: String,
     pub is_active: bool,
     pub sync_enabled: bool,
+    pub sync_interval_hours: Option<i32>,
     pub last_sync_at: Option<String>,
     pub rate_limit_per_minute: i32,
 }
 
+impl From<DbPodProvider> for ProviderResponse {
+    fn from(provider: DbPodProvider) -> Self {
+        ProviderResponse {
+            id: provider.id,
+            code: provider.code,
+            name: provider.name,
+            api_base_url: provider.api_base_url,
+            auth_type: provider.auth_type,
+            is_active: provider.is_active,
+            sync_enabled: provider.sync_enabled,
+            sync_interval_hours: provider.sync_interval_hours,
+            last_sync_at: provider.last_sync_at.map(|dt| dt.to_rfc3339()),
+            rate_limit_per_minute: provider.rate_limit_per_minute,
+        }
+    }
+}
+
 /// Category response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CategoryResponse {
     pub id: Uuid,
     pub slug: String,
@@ -56,7 +233,7 @@ pub struct CategoryResponse {
 }
 
 /// Product summary response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ProductSummaryResponse {
     pub id: Uuid,
     pub provider_code: String,
@@ -69,7 +246,7 @@ pub struct ProductSummaryResponse {
 }
 
 /// Product detail response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ProductDetailResponse {
     pub id: Uuid,
     pub provider_code: String,
@@ -82,10 +259,13 @@ pub struct ProductDetailResponse {
     pub base_price_cents: Option<i32>,
     pub variants: Vec<VariantResponse>,
     pub print_areas: Vec<PrintAreaResponse>,
+    /// Product-level mockup assets; omitted when `include_assets=false`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Vec<AssetResponse>>,
 }
 
 /// Variant response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct VariantResponse {
     pub id: Uuid,
     pub external_variant_id: String,
@@ -95,10 +275,81 @@ pub struct VariantResponse {
     pub color_hex: Option<String>,
     pub is_available: bool,
     pub price_cents: Option<i32>,
+    /// Mockup assets specific to this variant; omitted when `include_assets=false`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Vec<AssetResponse>>,
+}
+
+/// Mockup asset response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AssetResponse {
+    pub id: Uuid,
+    pub asset_type: String,
+    pub placement: Option<String>,
+    pub width_px: Option<i32>,
+    pub height_px: Option<i32>,
+    /// Public R2 URL, or the provider's URL until the asset is mirrored
+    pub url: String,
+    /// Public R2 URL of a downscaled copy, once one has been made
+    pub thumbnail_url: Option<String>,
+}
+
+/// URL an asset is served from
+///
+/// Mirrored assets are served through the R2 public URL prefix when one is
+/// configured for the bucket they were stored in; anything else falls back to
+/// the provider's source URL.
+fn asset_url(
+    source_url: String,
+    r2_bucket: Option<&str>,
+    r2_key: Option<&str>,
+    r2: Option<&R2Settings>,
+) -> String {
+    r2_public_url(r2_bucket, r2_key, r2).unwrap_or(source_url)
+}
+
+/// Public URL of an object mirrored to R2, if its bucket has a public prefix
+fn r2_public_url(
+    r2_bucket: Option<&str>,
+    r2_key: Option<&str>,
+    r2: Option<&R2Settings>,
+) -> Option<String> {
+    let prefix = r2.and_then(|r2| {
+        let bucket_matches = r2_bucket.map_or(true, |bucket| bucket == r2.bucket_name);
+        r2.public_url_prefix.as_deref().filter(|_| bucket_matches)
+    });
+    match (prefix, r2_key) {
+        (Some(prefix), Some(key)) if !key.is_empty() => {
+            Some(format!("{}/{}", prefix.trim_end_matches('/'), key))
+        }
+        _ => None,
+    }
+}
+
+/// Move variant-specific assets under their variant, returning the rest
+///
+/// Assets whose variant isn't in `variants` stay at the product level.
+fn group_assets(
+    variants: &mut [VariantResponse],
+    assets: Vec<(Option<Uuid>, AssetResponse)>,
+) -> Vec<AssetResponse> {
+    for variant in variants.iter_mut() {
+        variant.assets = Some(Vec::new());
+    }
+
+    let mut product_assets = Vec::new();
+    for (variant_id, asset) in assets {
+        let variant = variant_id.and_then(|id| variants.iter_mut().find(|v| v.id == id));
+        match variant.and_then(|v| v.assets.as_mut()) {
+            Some(variant_assets) => variant_assets.push(asset),
+            None => product_assets.push(asset),
+        }
+    }
+    product_assets
 }
 
 /// Print area response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PrintAreaResponse {
     pub id: Uuid,
     pub placement: String,
@@ -111,13 +362,30 @@ pub struct PrintAreaResponse {
 }
 
 /// Paginated response wrapper
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PaginatedResponse<T> {
     pub items: Vec<T>,
     pub total: i64,
     pub page: u32,
     pub per_page: u32,
     pub total_pages: u32,
+    /// Applied sort, for listings that can be sorted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<ProductOrdering>,
+}
+
+impl<T> From<Page<T>> for PaginatedResponse<T> {
+    fn from(page: Page<T>) -> Self {
+        let total_pages = page.total_pages();
+        PaginatedResponse {
+            items: page.items,
+            total: page.total,
+            page: page.page,
+            per_page: page.per_page,
+            total_pages,
+            sort: None,
+        }
+    }
 }
 
 /// Helper macro to get database client
@@ -125,6 +393,10 @@ macro_rules! get_client {
     ($pool:expr) => {
         match $pool.get().await {
             Ok(c) => c,
+            Err(e) if e.is_busy() => {
+                tracing::warn!("Database connection wait timed out: {}", e);
+                return crate::api::handlers::database_busy();
+            }
             Err(e) => {
                 tracing::error!("Failed to get database connection: {}", e);
                 return HttpResponse::InternalServerError().json(serde_json::json!({
@@ -136,36 +408,23 @@ macro_rules! get_client {
 }
 
 /// List all POD providers
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/providers",
+    tag = "catalog",
+    responses(
+        (status = 200, description = "Every provider, with its sync settings", body = Vec<ProviderResponse>)
+    )
+)]
 pub async fn list_providers(pool: web::Data<DbPool>) -> HttpResponse {
-    let client = get_client!(pool);
-
-    let query = r#"
-        SELECT
-            id, code, name, is_active, sync_enabled,
-            last_sync_at, rate_limit_per_minute
-        FROM pod_providers
-        ORDER BY name
-    "#;
-
-    match client.query(query, &[]).await {
-        Ok(rows) => {
-            let providers: Vec<ProviderResponse> = rows
-                .iter()
-                .map(|row| ProviderResponse {
-                    id: row.get("id"),
-                    code: row.get("code"),
-                    name: row.get("name"),
-                    is_active: row.get("is_active"),
-                    sync_enabled: row.get("sync_enabled"),
-                    last_sync_at: row
-                        .get::<_, Option<chrono::DateTime<chrono::Utc>>>("last_sync_at")
-                        .map(|dt| dt.to_rfc3339()),
-                    rate_limit_per_minute: row.get("rate_limit_per_minute"),
-                })
-                .collect();
-
-            HttpResponse::Ok().json(providers)
-        }
+    match ProviderRepository::new(pool.get_ref().clone()).list().await {
+        Ok(providers) => HttpResponse::Ok().json(
+            providers
+                .into_iter()
+                .map(ProviderResponse::from)
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) if e.is_busy() => crate::api::handlers::database_busy(),
         Err(e) => {
             tracing::error!("Failed to list providers: {}", e);
             HttpResponse::InternalServerError().json(serde_json::json!({
@@ -176,6 +435,14 @@ pub async fn list_providers(pool: web::Data<DbPool>) -> HttpResponse {
 }
 
 /// List all product categories with counts
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/categories",
+    tag = "catalog",
+    responses(
+        (status = 200, description = "Categories with their product counts", body = Vec<CategoryResponse>)
+    )
+)]
 pub async fn list_categories(pool: web::Data<DbPool>) -> HttpResponse {
     let client = get_client!(pool);
 
@@ -212,7 +479,114 @@ pub async fn list_categories(pool: web::Data<DbPool>) -> HttpResponse {
     }
 }
 
+/// WHERE clause for the product list filters, with its parameters bound as
+/// `$1`, `$2`, ... in the order the filters appear
+#[derive(Debug, PartialEq)]
+struct ProductFilter {
+    where_clause: String,
+    params: Vec<String>,
+}
+
+impl ProductFilter {
+    fn from_query(query: &ProductsQuery) -> Self {
+        let filters = [
+            ("pr.code = ", query.provider.clone()),
+            ("c.slug = ", query.category.clone()),
+            ("p.product_type = ", query.product_type.clone()),
+            (
+                "p.name ILIKE ",
+                query
+                    .search
+                    .as_deref()
+                    .map(|search| format!("%{}%", escape_like(search))),
+            ),
+        ];
+
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        for (condition, value) in filters {
+            if let Some(value) = value {
+                params.push(value);
+                conditions.push(format!("{}${}", condition, params.len()));
+            }
+        }
+
+        // Variant attributes match when at least one variant has all of them
+        let color = query.color.as_deref().map(|color| {
+            if is_hex_color(color) {
+                ("LOWER(v.color_hex) = LOWER(", color)
+            } else {
+                ("LOWER(v.color_name) = LOWER(", color)
+            }
+        });
+        let variant_filters = [
+            query
+                .size
+                .as_deref()
+                .map(|size| ("LOWER(v.size) = LOWER(", size)),
+            color,
+        ];
+
+        let mut variant_conditions = vec!["v.product_id = p.id".to_string()];
+        for (condition, value) in variant_filters.into_iter().flatten() {
+            params.push(value.to_string());
+            variant_conditions.push(format!("{}${})", condition, params.len()));
+        }
+        let has_variant_filter = variant_conditions.len() > 1;
+        let in_stock = query.in_stock.unwrap_or(has_variant_filter);
+        if in_stock {
+            variant_conditions.push("v.is_available".to_string());
+            variant_conditions.push("v.in_stock IS NOT FALSE".to_string());
+        }
+        if has_variant_filter || in_stock {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM pod_product_variants v WHERE {})",
+                variant_conditions.join(" AND ")
+            ));
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        ProductFilter {
+            where_clause,
+            params,
+        }
+    }
+}
+
+/// Whether a color filter is a `#RRGGBB` hex value rather than a name
+fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Escape LIKE wildcards so user input matches literally; `\` is Postgres' default escape
+fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 /// List products with filtering and pagination
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/products",
+    tag = "catalog",
+    params(ProductsQuery),
+    responses(
+        (status = 200, description = "One page of matching products", body = PaginatedResponse<ProductSummaryResponse>),
+        (status = 400, description = "Unknown sort field or order")
+    )
+)]
 pub async fn list_products(
     pool: web::Data<DbPool>,
     query_params: web::Query<ProductsQuery>,
@@ -222,29 +596,16 @@ pub async fn list_products(
     let offset = ((query_params.page.saturating_sub(1)) * query_params.per_page) as i64;
     let limit = query_params.per_page as i64;
 
-    // Build dynamic WHERE clause
-    let mut conditions = Vec::new();
-    if let Some(ref p) = query_params.provider {
-        conditions.push(format!("pr.code = '{}'", p.replace('\'', "''")));
-    }
-    if let Some(ref c) = query_params.category {
-        conditions.push(format!("c.slug = '{}'", c.replace('\'', "''")));
-    }
-    if let Some(ref t) = query_params.product_type {
-        conditions.push(format!("p.product_type = '{}'", t.replace('\'', "''")));
-    }
-    if let Some(ref s) = query_params.search {
-        conditions.push(format!(
-            "p.name ILIKE '%{}%'",
-            s.replace('\'', "''").replace('%', "\\%")
-        ));
-    }
-
-    let where_clause = if conditions.is_empty() {
-        String::new()
-    } else {
-        format!("WHERE {}", conditions.join(" AND "))
+    let ordering = match ProductOrdering::from_query(&query_params) {
+        Ok(ordering) => ordering,
+        Err(response) => return response,
     };
+    let filter = ProductFilter::from_query(&query_params);
+    let mut params: Vec<&(dyn ToSql + Sync)> = filter
+        .params
+        .iter()
+        .map(|param| param as &(dyn ToSql + Sync))
+        .collect();
 
     // Count query
     let count_sql = format!(
@@ -255,10 +616,10 @@ pub async fn list_products(
         LEFT JOIN product_categories c ON p.category_id = c.id
         {}
     "#,
-        where_clause
+        filter.where_clause
     );
 
-    let total: i64 = match client.query_one(&count_sql, &[]).await {
+    let total: i64 = match client.query_one(&count_sql, &params).await {
         Ok(row) => row.get("total"),
         Err(e) => {
             tracing::error!("Failed to count products: {}", e);
@@ -268,7 +629,7 @@ pub async fn list_products(
         }
     };
 
-    // Data query
+    // Data query; LIMIT and OFFSET follow the filter parameters
     let data_sql = format!(
         r#"
         SELECT
@@ -280,13 +641,18 @@ pub async fn list_products(
         JOIN pod_providers pr ON p.provider_id = pr.id
         LEFT JOIN product_categories c ON p.category_id = c.id
         {}
-        ORDER BY p.name
-        LIMIT {} OFFSET {}
+        {}
+        LIMIT ${} OFFSET ${}
     "#,
-        where_clause, limit, offset
+        filter.where_clause,
+        ordering.order_by(),
+        params.len() + 1,
+        params.len() + 2
     );
+    params.push(&limit);
+    params.push(&offset);
 
-    match client.query(&data_sql, &[]).await {
+    match client.query(&data_sql, &params).await {
         Ok(rows) => {
             let products: Vec<ProductSummaryResponse> = rows
                 .iter()
@@ -310,6 +676,7 @@ pub async fn list_products(
                 page: query_params.page,
                 per_page: query_params.per_page,
                 total_pages,
+                sort: Some(ordering),
             })
         }
         Err(e) => {
@@ -321,8 +688,67 @@ pub async fn list_products(
     }
 }
 
+/// Search synced products by name, brand, model, type, and variant size or color
+///
+/// A product named exactly as the query comes first, then products by
+/// relevance. Each result lists its variants whose size or color matched.
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/search",
+    tag = "catalog",
+    params(CatalogSearchQuery),
+    responses(
+        (status = 200, description = "Matching products, best match first", body = CatalogSearchResponse),
+        (status = 400, description = "Query has no words to search for")
+    )
+)]
+pub async fn search_products(
+    pool: web::Data<DbPool>,
+    query_params: web::Query<CatalogSearchQuery>,
+) -> HttpResponse {
+    let Some(query) = query_params.q.as_deref().and_then(SearchQuery::parse) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "q must contain at least one word to search for"
+        }));
+    };
+    let limit = i64::from(query_params.limit.clamp(1, MAX_PER_PAGE));
+
+    let repo = CatalogRepository::new(pool.get_ref().clone());
+    match repo.search_products(&query, limit).await {
+        Ok(results) => HttpResponse::Ok().json(CatalogSearchResponse {
+            query: query.text,
+            results,
+        }),
+        Err(e) if e.is_busy() => crate::api::handlers::database_busy(),
+        Err(e) => {
+            tracing::error!("Failed to search products: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to search products"
+            }))
+        }
+    }
+}
+
 /// Get product details by ID
-pub async fn get_product(pool: web::Data<DbPool>, path: web::Path<Uuid>) -> HttpResponse {
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/products/{id}",
+    tag = "catalog",
+    params(
+        ("id" = Uuid, Path, description = "Catalog product ID"),
+        ProductDetailQuery
+    ),
+    responses(
+        (status = 200, description = "Product with its variants, print areas, and assets", body = ProductDetailResponse),
+        (status = 404, description = "Product not found")
+    )
+)]
+pub async fn get_product(
+    state: web::Data<AppState>,
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<ProductDetailQuery>,
+) -> HttpResponse {
     let client = get_client!(pool);
     let product_id = path.into_inner();
 
@@ -361,7 +787,8 @@ pub async fn get_product(pool: web::Data<DbPool>, path: web::Path<Uuid>) -> Http
         ORDER BY size, color_name
     "#;
 
-    let variants: Vec<VariantResponse> = match client.query(variants_sql, &[&product_id]).await {
+    let mut variants: Vec<VariantResponse> = match client.query(variants_sql, &[&product_id]).await
+    {
         Ok(rows) => rows
             .iter()
             .map(|row| VariantResponse {
@@ -373,6 +800,7 @@ pub async fn get_product(pool: web::Data<DbPool>, path: web::Path<Uuid>) -> Http
                 color_hex: row.get("color_hex"),
                 is_available: row.get("is_available"),
                 price_cents: row.get("price_cents"),
+                assets: None,
             })
             .collect(),
         Err(_) => Vec::new(),
@@ -403,6 +831,55 @@ pub async fn get_product(pool: web::Data<DbPool>, path: web::Path<Uuid>) -> Http
         Err(_) => Vec::new(),
     };
 
+    // Get mockup assets, skipping ones that failed to sync
+    let assets = if query.include_assets {
+        let assets_sql = r#"
+            SELECT id, variant_id, asset_type, placement, width_px, height_px,
+                   source_url, r2_bucket, r2_key, thumbnail_r2_key
+            FROM pod_mockup_assets
+            WHERE product_id = $1 AND status <> 'failed'
+            ORDER BY asset_type, placement NULLS FIRST, created_at
+        "#;
+
+        let r2 = state.settings.r2.as_ref();
+        let assets = match client.query(assets_sql, &[&product_id]).await {
+            Ok(rows) => rows
+                .iter()
+                .map(|row| {
+                    let r2_bucket: Option<String> = row.get("r2_bucket");
+                    let r2_key: Option<String> = row.get("r2_key");
+                    let thumbnail_r2_key: Option<String> = row.get("thumbnail_r2_key");
+                    let asset = AssetResponse {
+                        id: row.get("id"),
+                        asset_type: row.get("asset_type"),
+                        placement: row.get("placement"),
+                        width_px: row.get("width_px"),
+                        height_px: row.get("height_px"),
+                        url: asset_url(
+                            row.get("source_url"),
+                            r2_bucket.as_deref(),
+                            r2_key.as_deref(),
+                            r2,
+                        ),
+                        thumbnail_url: r2_public_url(
+                            r2_bucket.as_deref(),
+                            thumbnail_r2_key.as_deref(),
+                            r2,
+                        ),
+                    };
+                    (row.get::<_, Option<Uuid>>("variant_id"), asset)
+                })
+                .collect(),
+            Err(e) => {
+                tracing::error!("Failed to get mockup assets: {}", e);
+                Vec::new()
+            }
+        };
+        Some(group_assets(&mut variants, assets))
+    } else {
+        None
+    };
+
     HttpResponse::Ok().json(ProductDetailResponse {
         id: product_row.get("id"),
         provider_code: product_row.get("provider_code"),
@@ -415,10 +892,22 @@ pub async fn get_product(pool: web::Data<DbPool>, path: web::Path<Uuid>) -> Http
         base_price_cents: product_row.get("base_price_cents"),
         variants,
         print_areas,
+        assets,
     })
 }
 
 /// Get print areas for a product
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/products/{id}/print-areas",
+    tag = "catalog",
+    params(
+        ("id" = Uuid, Path, description = "Catalog product ID")
+    ),
+    responses(
+        (status = 200, description = "The product's print areas", body = Vec<PrintAreaResponse>)
+    )
+)]
 pub async fn get_print_areas(pool: web::Data<DbPool>, path: web::Path<Uuid>) -> HttpResponse {
     let client = get_client!(pool);
     let product_id = path.into_inner();
@@ -456,3 +945,539 @@ pub async fn get_print_areas(pool: web::Data<DbPool>, path: web::Path<Uuid>) ->
         }
     }
 }
+
+/// Largest page a live product list may ask a provider for
+const MAX_LIVE_PER_PAGE: u32 = 100;
+
+/// Query parameters for a provider's live product list
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LiveProductsQuery {
+    /// Page number (1-based)
+    #[serde(default = "default_page")]
+    #[param(default = 1)]
+    pub page: u32,
+    /// Items per page, at most 100
+    #[serde(default = "default_per_page")]
+    #[param(default = 50)]
+    pub per_page: u32,
+}
+
+/// List a provider's products straight from its API
+///
+/// Pages are cached for a few minutes so browsing doesn't use up the
+/// provider's rate limit.
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/live/{provider}/products",
+    tag = "catalog",
+    params(
+        ("provider" = String, Path, description = "Provider code, e.g. printful"),
+        LiveProductsQuery
+    ),
+    responses(
+        (status = 200, description = "One page of the provider's products", body = CatalogPage<UnifiedProduct>),
+        (status = 400, description = "Invalid page or per_page"),
+        (status = 404, description = "Provider not configured"),
+        (status = 429, description = "Provider rate limit hit; see Retry-After"),
+        (status = 502, description = "Provider request failed")
+    )
+)]
+pub async fn list_live_products(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<LiveProductsQuery>,
+) -> HttpResponse {
+    let provider_code = path.into_inner();
+    if query.page == 0 || query.per_page == 0 || query.per_page > MAX_LIVE_PER_PAGE {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "page must be at least 1 and per_page between 1 and {}",
+                MAX_LIVE_PER_PAGE
+            )
+        }));
+    }
+
+    match state
+        .live_catalog
+        .products(&provider_code, query.page, query.per_page)
+        .await
+    {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(e) => live_error_response(&provider_code, e),
+    }
+}
+
+/// Get one product straight from the provider's API
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/live/{provider}/products/{external_id}",
+    tag = "catalog",
+    params(
+        ("provider" = String, Path, description = "Provider code, e.g. printful"),
+        ("external_id" = String, Path, description = "The provider's product ID")
+    ),
+    responses(
+        (status = 200, description = "The product as the provider reports it", body = UnifiedProduct),
+        (status = 404, description = "Provider not configured or product unknown"),
+        (status = 429, description = "Provider rate limit hit; see Retry-After"),
+        (status = 502, description = "Provider request failed")
+    )
+)]
+pub async fn get_live_product(
+    state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (provider_code, external_id) = path.into_inner();
+
+    match state
+        .live_catalog
+        .product(&provider_code, &external_id)
+        .await
+    {
+        Ok(product) => HttpResponse::Ok().json(product),
+        Err(e) => live_error_response(&provider_code, e),
+    }
+}
+
+/// Get a product's print areas straight from the provider's API
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalog/live/{provider}/products/{external_id}/print-areas",
+    tag = "catalog",
+    params(
+        ("provider" = String, Path, description = "Provider code, e.g. printful"),
+        ("external_id" = String, Path, description = "The provider's product ID")
+    ),
+    responses(
+        (status = 200, description = "The product's print areas as the provider reports them", body = Vec<UnifiedPrintArea>),
+        (status = 404, description = "Provider not configured or product unknown"),
+        (status = 429, description = "Provider rate limit hit; see Retry-After"),
+        (status = 502, description = "Provider request failed")
+    )
+)]
+pub async fn get_live_print_areas(
+    state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (provider_code, external_id) = path.into_inner();
+
+    match state
+        .live_catalog
+        .print_areas(&provider_code, &external_id)
+        .await
+    {
+        Ok(areas) => HttpResponse::Ok().json(areas),
+        Err(e) => live_error_response(&provider_code, e),
+    }
+}
+
+/// Response for a failed live catalog request
+///
+/// Failures upstream are a 502 carrying the provider's error, except for
+/// products the provider doesn't know and its rate limiting.
+fn live_error_response(provider_code: &str, error: LiveCatalogError) -> HttpResponse {
+    let error = match error {
+        LiveCatalogError::NotConfigured(_) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": format!("Provider '{}' is not configured", provider_code)
+            }));
+        }
+        LiveCatalogError::Provider(error) => error,
+    };
+
+    tracing::warn!(provider = provider_code, error = %error, "Live catalog request failed");
+    let body = serde_json::json!({
+        "error": "Provider request failed",
+        "provider": provider_code,
+        "detail": error.to_string()
+    });
+    match error {
+        ProviderError::NotFound(_) => HttpResponse::NotFound().json(body),
+        ProviderError::RateLimited { retry_after_secs } => HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", retry_after_secs.to_string()))
+            .json(body),
+        _ => HttpResponse::BadGateway().json(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(
+        provider: Option<&str>,
+        category: Option<&str>,
+        product_type: Option<&str>,
+        search: Option<&str>,
+    ) -> ProductsQuery {
+        ProductsQuery {
+            provider: provider.map(str::to_string),
+            category: category.map(str::to_string),
+            product_type: product_type.map(str::to_string),
+            search: search.map(str::to_string),
+            size: None,
+            color: None,
+            in_stock: None,
+            sort: None,
+            order: None,
+            page: default_page(),
+            per_page: default_per_page(),
+        }
+    }
+
+    #[test]
+    fn test_no_filters_leave_no_where_clause() {
+        let filter = ProductFilter::from_query(&query(None, None, None, None));
+        assert_eq!(filter.where_clause, "");
+        assert!(filter.params.is_empty());
+    }
+
+    #[test]
+    fn test_each_filter_binds_one_parameter() {
+        let cases = [
+            (
+                query(Some("printful"), None, None, None),
+                "pr.code = $1",
+                "printful",
+            ),
+            (
+                query(None, Some("shirts"), None, None),
+                "c.slug = $1",
+                "shirts",
+            ),
+            (
+                query(None, None, Some("t-shirt"), None),
+                "p.product_type = $1",
+                "t-shirt",
+            ),
+            (
+                query(None, None, None, Some("tee")),
+                "p.name ILIKE $1",
+                "%tee%",
+            ),
+        ];
+        for (query, condition, param) in cases {
+            let filter = ProductFilter::from_query(&query);
+            assert_eq!(filter.where_clause, format!("WHERE {}", condition));
+            assert_eq!(filter.params, vec![param.to_string()]);
+        }
+    }
+
+    #[test]
+    fn test_parameters_numbered_in_filter_order() {
+        let filter =
+            ProductFilter::from_query(&query(Some("printify"), None, Some("mug"), Some("coffee")));
+        assert_eq!(
+            filter.where_clause,
+            "WHERE pr.code = $1 AND p.product_type = $2 AND p.name ILIKE $3"
+        );
+        assert_eq!(filter.params, vec!["printify", "mug", "%coffee%"]);
+
+        let filter = ProductFilter::from_query(&query(
+            Some("printful"),
+            Some("shirts"),
+            Some("t-shirt"),
+            Some("tee"),
+        ));
+        assert_eq!(
+            filter.where_clause,
+            "WHERE pr.code = $1 AND c.slug = $2 AND p.product_type = $3 AND p.name ILIKE $4"
+        );
+        assert_eq!(filter.params.len(), 4);
+    }
+
+    #[test]
+    fn test_user_input_stays_out_of_sql() {
+        let filter = ProductFilter::from_query(&query(
+            Some("x' OR '1'='1"),
+            None,
+            None,
+            Some("100% cotton_tee's \\ best"),
+        ));
+        assert_eq!(
+            filter.where_clause,
+            "WHERE pr.code = $1 AND p.name ILIKE $2"
+        );
+        assert_eq!(filter.params[0], "x' OR '1'='1");
+        assert_eq!(filter.params[1], "%100\\% cotton\\_tee's \\\\ best%");
+    }
+
+    #[test]
+    fn test_size_and_color_match_one_in_stock_variant() {
+        let mut query = query(Some("printful"), None, None, None);
+        query.size = Some("3XL".to_string());
+        query.color = Some("Black".to_string());
+        let filter = ProductFilter::from_query(&query);
+
+        assert_eq!(
+            filter.where_clause,
+            "WHERE pr.code = $1 AND EXISTS (SELECT 1 FROM pod_product_variants v \
+             WHERE v.product_id = p.id AND LOWER(v.size) = LOWER($2) \
+             AND LOWER(v.color_name) = LOWER($3) AND v.is_available AND v.in_stock IS NOT FALSE)"
+        );
+        assert_eq!(filter.params, ["printful", "3XL", "Black"]);
+    }
+
+    #[test]
+    fn test_out_of_stock_variants_only_match_when_stock_filter_off() {
+        let mut sized = query(None, None, None, None);
+        sized.size = Some("xl".to_string());
+        assert!(ProductFilter::from_query(&sized)
+            .where_clause
+            .contains("v.in_stock IS NOT FALSE"));
+
+        sized.in_stock = Some(false);
+        let filter = ProductFilter::from_query(&sized);
+        assert_eq!(
+            filter.where_clause,
+            "WHERE EXISTS (SELECT 1 FROM pod_product_variants v \
+             WHERE v.product_id = p.id AND LOWER(v.size) = LOWER($1))"
+        );
+    }
+
+    #[test]
+    fn test_in_stock_alone_requires_any_available_variant() {
+        let mut stocked = query(None, None, None, None);
+        stocked.in_stock = Some(true);
+        let filter = ProductFilter::from_query(&stocked);
+        assert_eq!(
+            filter.where_clause,
+            "WHERE EXISTS (SELECT 1 FROM pod_product_variants v \
+             WHERE v.product_id = p.id AND v.is_available AND v.in_stock IS NOT FALSE)"
+        );
+        assert!(filter.params.is_empty());
+    }
+
+    #[test]
+    fn test_hex_color_matches_color_hex() {
+        let mut query = query(None, None, None, None);
+        query.color = Some("#1A1A1A".to_string());
+        let filter = ProductFilter::from_query(&query);
+        assert!(filter
+            .where_clause
+            .contains("LOWER(v.color_hex) = LOWER($1)"));
+        assert_eq!(filter.params, ["#1A1A1A"]);
+
+        assert!(!is_hex_color("black"));
+        assert!(!is_hex_color("#12345"));
+        assert!(!is_hex_color("#GGGGGG"));
+    }
+
+    #[test]
+    fn test_default_ordering_is_name_ascending() {
+        let ordering = ProductOrdering::from_query(&query(None, None, None, None)).unwrap();
+        assert_eq!(ordering, ProductOrdering::default());
+        assert_eq!(ordering.order_by(), "ORDER BY p.name ASC, p.id");
+        assert_eq!(
+            serde_json::to_value(ordering).unwrap(),
+            serde_json::json!({"sort": "name", "order": "asc"})
+        );
+    }
+
+    #[test]
+    fn test_ordering_by_each_sort_field() {
+        let order_by = |sort: &str, order: Option<&str>| {
+            let mut query = query(None, None, None, None);
+            query.sort = Some(sort.to_string());
+            query.order = order.map(str::to_string);
+            ProductOrdering::from_query(&query).unwrap().order_by()
+        };
+
+        assert_eq!(
+            order_by("price", None),
+            "ORDER BY p.base_price_cents ASC NULLS LAST, p.name, p.id"
+        );
+        assert_eq!(
+            order_by("price", Some("desc")),
+            "ORDER BY p.base_price_cents DESC NULLS LAST, p.name, p.id"
+        );
+        assert_eq!(
+            order_by("last_synced", Some("desc")),
+            "ORDER BY p.last_synced_at DESC, p.name, p.id"
+        );
+        assert_eq!(
+            order_by("variant_count", Some("asc")),
+            "ORDER BY variant_count ASC, p.name, p.id"
+        );
+        assert_eq!(order_by("name", Some("desc")), "ORDER BY p.name DESC, p.id");
+    }
+
+    #[test]
+    fn test_invalid_sort_rejected() {
+        let mut invalid = query(None, None, None, None);
+        invalid.sort = Some("p.name; DROP TABLE pod_products".to_string());
+        let response = ProductOrdering::from_query(&invalid).unwrap_err();
+        assert_eq!(response.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let mut invalid = query(None, None, None, None);
+        invalid.order = Some("sideways".to_string());
+        assert!(ProductOrdering::from_query(&invalid).is_err());
+    }
+
+    fn r2_settings(prefix: Option<&str>) -> R2Settings {
+        R2Settings {
+            account_id: "account".to_string(),
+            access_key_id: "key".to_string(),
+            secret_access_key: "secret".to_string(),
+            bucket_name: "pod-assets".to_string(),
+            public_url_prefix: prefix.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_asset_url_prefers_mirrored_copy() {
+        let source = "https://provider.example/front.png".to_string();
+        let r2 = r2_settings(Some("https://cdn.example/"));
+
+        assert_eq!(
+            asset_url(
+                source.clone(),
+                Some("pod-assets"),
+                Some("printful/71/front.png"),
+                Some(&r2)
+            ),
+            "https://cdn.example/printful/71/front.png"
+        );
+        // Not mirrored yet
+        assert_eq!(asset_url(source.clone(), None, None, Some(&r2)), source);
+        // Mirrored to a bucket the prefix doesn't serve
+        assert_eq!(
+            asset_url(source.clone(), Some("other"), Some("a.png"), Some(&r2)),
+            source
+        );
+        // No public prefix, or no R2 at all
+        let private = r2_settings(None);
+        assert_eq!(
+            asset_url(
+                source.clone(),
+                Some("pod-assets"),
+                Some("a.png"),
+                Some(&private)
+            ),
+            source
+        );
+        assert_eq!(
+            asset_url(source.clone(), Some("pod-assets"), Some("a.png"), None),
+            source
+        );
+    }
+
+    #[test]
+    fn test_thumbnail_url_only_when_mirrored() {
+        let r2 = r2_settings(Some("https://cdn.example"));
+        assert_eq!(
+            r2_public_url(
+                Some("pod-assets"),
+                Some("printful/products/71/thumbnails/thumb_front.webp"),
+                Some(&r2)
+            )
+            .as_deref(),
+            Some("https://cdn.example/printful/products/71/thumbnails/thumb_front.webp")
+        );
+        assert_eq!(r2_public_url(Some("pod-assets"), None, Some(&r2)), None);
+        assert_eq!(
+            r2_public_url(Some("pod-assets"), Some("a.webp"), Some(&r2_settings(None))),
+            None
+        );
+    }
+
+    fn variant(id: Uuid) -> VariantResponse {
+        VariantResponse {
+            id,
+            external_variant_id: id.to_string(),
+            sku: None,
+            size: None,
+            color_name: None,
+            color_hex: None,
+            is_available: true,
+            price_cents: None,
+            assets: None,
+        }
+    }
+
+    fn asset(asset_type: &str) -> AssetResponse {
+        AssetResponse {
+            id: Uuid::new_v4(),
+            asset_type: asset_type.to_string(),
+            placement: None,
+            width_px: None,
+            height_px: None,
+            url: String::new(),
+            thumbnail_url: None,
+        }
+    }
+
+    #[test]
+    fn test_variant_assets_grouped_under_their_variant() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut variants = vec![variant(first), variant(second)];
+
+        let product_assets = group_assets(
+            &mut variants,
+            vec![
+                (None, asset("thumbnail")),
+                (Some(first), asset("base_image")),
+                (Some(Uuid::new_v4()), asset("mockup_template")),
+            ],
+        );
+
+        let types: Vec<_> = product_assets
+            .iter()
+            .map(|a| a.asset_type.as_str())
+            .collect();
+        assert_eq!(types, ["thumbnail", "mockup_template"]);
+        let first_assets = variants[0].assets.as_ref().unwrap();
+        assert_eq!(first_assets.len(), 1);
+        assert_eq!(first_assets[0].asset_type, "base_image");
+        assert!(variants[1].assets.as_ref().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_live_error_statuses() {
+        use actix_web::http::StatusCode;
+
+        let cases = [
+            (
+                LiveCatalogError::NotConfigured("gelato".to_string()),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                LiveCatalogError::Provider(ProviderError::NotFound("71".to_string())),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                LiveCatalogError::Provider(ProviderError::RateLimited {
+                    retry_after_secs: 30,
+                }),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            (
+                LiveCatalogError::Provider(ProviderError::ApiError {
+                    status: 500,
+                    message: "Internal server error".to_string(),
+                }),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                LiveCatalogError::Provider(ProviderError::AuthFailed("expired".to_string())),
+                StatusCode::BAD_GATEWAY,
+            ),
+        ];
+
+        for (error, status) in cases {
+            assert_eq!(live_error_response("gelato", error).status(), status);
+        }
+    }
+
+    #[test]
+    fn test_live_rate_limit_passes_retry_after() {
+        let response = live_error_response(
+            "printful",
+            LiveCatalogError::Provider(ProviderError::RateLimited {
+                retry_after_secs: 30,
+            }),
+        );
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "30");
+    }
+}
@@ -0,0 +1,255 @@
+//! Design analysis handlers
+//!
+//! Endpoints for evaluating a design against POD product print areas.
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::generate::{design_domains, ensure_design_domains};
+use crate::db::DbPool;
+use crate::domain::{
+    assess_fit, count_colors, DesignProfile, FitAssessment, PrintConstraints, PrintPlacement,
+    UnifiedPrintArea,
+};
+use crate::engine::GenerationLimits;
+use crate::AppState;
+
+/// Colors counted before a design is treated as full-color
+const MAX_COUNTED_COLORS: u32 = 256;
+
+/// Request for a design fit report
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FitReportRequest {
+    /// URL of the design image to evaluate
+    pub design_url: String,
+    /// Filter by provider code
+    pub provider: Option<String>,
+    /// Filter by product type
+    pub product_type: Option<String>,
+    /// Filter by print placement (front, back, ...)
+    pub placement: Option<String>,
+    /// Maximum number of products to return
+    #[serde(default = "default_limit")]
+    #[schema(default = 25)]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    25
+}
+
+/// Fit report response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FitReportResponse {
+    pub design: DesignProfile,
+    /// Number of print areas evaluated
+    pub evaluated_print_areas: usize,
+    /// Products ranked by their best-fitting print area
+    pub products: Vec<ProductFitResponse>,
+}
+
+/// Best fit of the design on one product
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProductFitResponse {
+    pub product_id: Uuid,
+    pub provider_code: String,
+    pub product_name: String,
+    pub product_type: String,
+    pub print_area_id: Uuid,
+    pub placement: String,
+    pub print_area_name: String,
+    pub width_px: i32,
+    pub height_px: i32,
+    pub printable: bool,
+    pub fit: FitAssessment,
+}
+
+/// Helper macro to get database client
+macro_rules! get_client {
+    ($pool:expr) => {
+        match $pool.get().await {
+            Ok(c) => c,
+            Err(e) if e.is_busy() => {
+                tracing::warn!("Database connection wait timed out: {}", e);
+                return crate::api::handlers::database_busy();
+            }
+            Err(e) => {
+                tracing::error!("Failed to get database connection: {}", e);
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": "Database connection failed"
+                }));
+            }
+        }
+    };
+}
+
+/// Rank products by how well a design fits their print areas
+/// POST /api/v1/designs/fit-report
+#[utoipa::path(
+    post,
+    path = "/api/v1/designs/fit-report",
+    tag = "designs",
+    request_body = FitReportRequest,
+    responses(
+        (status = 200, description = "Products ranked by their best-fitting print area", body = FitReportResponse),
+        (status = 400, description = "Design could not be fetched or decoded"),
+        (status = 403, description = "Design URL or a redirect is outside the key's allowed design domains")
+    )
+)]
+pub async fn fit_report(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    state: web::Data<AppState>,
+    body: web::Json<FitReportRequest>,
+) -> HttpResponse {
+    if let Err(response) = ensure_design_domains(&req, [body.design_url.as_str()]) {
+        return response;
+    }
+    let limits = GenerationLimits {
+        fetch_timeout: Some(state.settings.server.max_fetch_timeout()),
+        design: state.settings.server.design_limits(),
+        design_domains: design_domains(&req),
+        ..GenerationLimits::default()
+    };
+    let bytes = match state
+        .template_manager
+        .fetch_design_bytes(&body.design_url, &limits)
+        .await
+    {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to fetch design for fit report");
+            if e.design_code() == Some("DESIGN_DOMAIN_NOT_ALLOWED") {
+                return HttpResponse::Forbidden().json(serde_json::json!({
+                    "error": e.to_string()
+                }));
+            }
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Failed to fetch design: {}", e)
+            }));
+        }
+    };
+
+    // Decoding and color counting are CPU bound
+    let design = match web::block(move || profile_design(&bytes)).await {
+        Ok(Ok(design)) => design,
+        Ok(Err(e)) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Failed to decode design: {}", e)
+            }));
+        }
+        Err(e) => {
+            tracing::error!("Design profiling task failed: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to analyze design"
+            }));
+        }
+    };
+
+    let client = get_client!(pool);
+
+    let sql = r#"
+        SELECT
+            pa.id, pa.placement, pa.name, pa.width_px, pa.height_px,
+            COALESCE(pa.print_dpi, 300) as print_dpi,
+            COALESCE(pa.constraints, '{}')::TEXT as constraints,
+            p.id as product_id, p.name as product_name, p.product_type,
+            pr.code as provider_code
+        FROM pod_print_areas pa
+        JOIN pod_products p ON pa.product_id = p.id
+        JOIN pod_providers pr ON p.provider_id = pr.id
+        WHERE p.is_available = true AND pr.is_active = true
+          AND ($1::TEXT IS NULL OR pr.code = $1)
+          AND ($2::TEXT IS NULL OR p.product_type = $2)
+          AND ($3::TEXT IS NULL OR pa.placement = $3)
+    "#;
+
+    let rows = match client
+        .query(sql, &[&body.provider, &body.product_type, &body.placement])
+        .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!("Failed to load print areas for fit report: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to load print areas"
+            }));
+        }
+    };
+
+    // Keep the best-scoring print area per product
+    let mut best: HashMap<Uuid, ProductFitResponse> = HashMap::new();
+    for row in &rows {
+        let placement: String = row.get("placement");
+        let constraints: String = row.get("constraints");
+
+        let mut area = UnifiedPrintArea::new(
+            PrintPlacement::from_str(&placement),
+            row.get("name"),
+            row.get("width_px"),
+            row.get("height_px"),
+        );
+        area.print_dpi = row.get("print_dpi");
+        area.constraints =
+            serde_json::from_str::<PrintConstraints>(&constraints).unwrap_or_default();
+
+        let fit = assess_fit(&design, &area);
+        let product_id: Uuid = row.get("product_id");
+
+        let better = best
+            .get(&product_id)
+            .map(|current| fit.score > current.fit.score)
+            .unwrap_or(true);
+        if better {
+            best.insert(
+                product_id,
+                ProductFitResponse {
+                    product_id,
+                    provider_code: row.get("provider_code"),
+                    product_name: row.get("product_name"),
+                    product_type: row.get("product_type"),
+                    print_area_id: row.get("id"),
+                    placement,
+                    print_area_name: area.name,
+                    width_px: area.width_px,
+                    height_px: area.height_px,
+                    printable: fit.is_printable(),
+                    fit,
+                },
+            );
+        }
+    }
+
+    let mut products: Vec<ProductFitResponse> = best.into_values().collect();
+    products.sort_by(|a, b| {
+        b.printable
+            .cmp(&a.printable)
+            .then(b.fit.score.total_cmp(&a.fit.score))
+            .then_with(|| a.product_name.cmp(&b.product_name))
+    });
+    products.truncate(body.limit.clamp(1, 100));
+
+    HttpResponse::Ok().json(FitReportResponse {
+        design,
+        evaluated_print_areas: rows.len(),
+        products,
+    })
+}
+
+/// Decode a design and collect the properties used for fit scoring
+fn profile_design(bytes: &[u8]) -> Result<DesignProfile, image::ImageError> {
+    let format = image::guess_format(bytes).ok();
+    let image = image::load_from_memory(bytes)?;
+    let rgba = image.to_rgba8();
+
+    Ok(DesignProfile {
+        width: rgba.width(),
+        height: rgba.height(),
+        file_size_bytes: bytes.len() as u64,
+        format: format.map(|f| format!("{:?}", f).to_uppercase()),
+        color_count: Some(count_colors(rgba.as_raw(), MAX_COUNTED_COLORS)),
+    })
+}
@@ -0,0 +1,14 @@
+//! Request examples endpoint
+
+use actix_web::{web, HttpResponse};
+
+use crate::api::examples::RequestExamples;
+use crate::AppState;
+
+/// GET /api-docs/examples - Example requests for the loaded templates
+///
+/// Lists valid template IDs, blend modes, output enums, and placement presets
+/// alongside ready-to-send generate and batch bodies.
+pub async fn request_examples(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(RequestExamples::from_templates(&state.template_manager))
+}
@@ -1,41 +1,628 @@
 //! Mockup generation endpoint
 
-use actix_web::{web, HttpResponse};
+use actix_multipart::{Field, Multipart};
+use actix_web::{
+    guard::GuardContext,
+    http::{header, StatusCode},
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
+use bytes::{Bytes, BytesMut};
+use chrono::Utc;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
-use tracing::{error, info};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 use utoipa::ToSchema;
+use uuid::Uuid;
 
-use crate::domain::PlacementSpec;
-use crate::engine::MockupRequest;
+use super::usage::ensure_resource_capacity;
+use crate::api::middleware::{ApiKeyAuth, RenderUsage, RequestId};
+use crate::api::validation::{FieldErrors, ValidationErrorResponse};
+use crate::config::ServerSettings;
+use crate::db::{ResourceKind, ResourceRepository};
+use crate::domain::{PhysicalPlacement, PlacementSpec, PrintPlacement, DEFAULT_PRINT_DPI};
+use crate::engine::{
+    BackgroundRemoval, BackgroundRemovalMode, ChromaSubsampling, DesignLayer, DesignSource,
+    DisplacementConfig, DisplacementStats, GenerationLimits, JpegPreset, MockupRequest,
+    MockupResult, OutputFormat, OutputSettings, TemplateError, TemplateImages, TemplateMetadata,
+    BLEND_MODES, DEFAULT_MIN_DPI,
+};
+use crate::jobs::{RenderJobError, RenderJobStatus};
+use crate::net::url_in_domains;
+use crate::storage::{AssetPath, CacheStatus, RenderCacheKey};
+use crate::sync::{OnDemandError, ProductTemplateError};
+use crate::uploads::{FailedUpload, UploadTarget};
+use crate::webhooks::EventType;
 use crate::AppState;
 
+/// Most designs a single mockup may composite
+const MAX_DESIGNS: usize = 10;
+
 /// Request body for mockup generation
+///
+/// Either `design_url` and `placement` (or `physical_placement` or `preset`)
+/// for a single design, or `designs`.
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct GenerateRequest {
     /// URL of the design image to composite
-    pub design_url: String,
+    #[serde(default)]
+    pub design_url: Option<String>,
     /// Template ID (e.g., "white_male_front")
     pub template_id: String,
     /// Placement specification
-    pub placement: PlacementSpec,
+    #[serde(default)]
+    pub placement: Option<PlacementSpec>,
+    /// Placement as a physical print size, instead of `placement`
+    #[serde(default)]
+    pub physical_placement: Option<PhysicalPlacement>,
+    /// Named placement preset (e.g. "left_chest"), instead of `placement`; see
+    /// `GET /api/v1/templates/{template_id}/presets`
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Designs composited in order onto the same template
+    #[serde(default)]
+    pub designs: Vec<DesignInput>,
     /// Optional generation options
     #[serde(default)]
     pub options: GenerateOptions,
 }
 
+/// One design of a multi-design request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DesignInput {
+    /// URL of the design image to composite
+    pub design_url: String,
+    /// Placement specification
+    pub placement: PlacementSpec,
+    /// Overrides `options.displacement_strength` for this design
+    pub displacement_strength: Option<f64>,
+    /// "normal", "multiply", "screen", or "overlay"; defaults to the template's blend mode
+    pub blend_mode: Option<String>,
+}
+
+impl GenerateRequest {
+    /// Replace `preset` with the placement it names in `template`
+    ///
+    /// Unknown names are reported by `validate`, which runs first.
+    fn resolve_preset(&mut self, template: &TemplateMetadata) {
+        if let Some(preset) = self.preset.take() {
+            self.placement = template.preset_placement(&preset);
+        }
+    }
+
+    /// Replace `physical_placement` with the pixel placement it converts to in `template`
+    ///
+    /// Returns how the physical size was interpreted. Conversion errors are
+    /// reported by `validate`, which runs first.
+    fn resolve_physical_placement(&mut self, template: &TemplateMetadata) -> Option<PrintSize> {
+        let physical = self.physical_placement.take()?;
+        let dpi = physical
+            .dpi
+            .or(template.print_dpi)
+            .unwrap_or(DEFAULT_PRINT_DPI);
+        let print_area = &template.print_area;
+        let placement = PlacementSpec::from_physical(
+            &physical,
+            template.print_dpi,
+            print_area.width,
+            print_area.height,
+        )
+        .ok()?;
+        self.placement = Some(placement.clone());
+        Some(PrintSize::new(
+            PhysicalPlacement {
+                dpi: Some(dpi),
+                ..physical
+            },
+            placement,
+        ))
+    }
+
+    /// Design layers from either request shape, each with its requested displacement strength
+    fn design_layers(&self) -> Result<Vec<RequestedLayer>, HttpResponse> {
+        let displacement_strength = self.options.displacement_strength;
+        match (&self.design_url, &self.placement, self.designs.is_empty()) {
+            (Some(design_url), Some(placement), true) => Ok(vec![RequestedLayer::new(
+                DesignSource::Url(design_url.clone()),
+                placement.clone(),
+                displacement_strength,
+                None,
+            )]),
+            (None, None, false) => {
+                if self.designs.len() > MAX_DESIGNS {
+                    return Err(bad_request(
+                        "INVALID_REQUEST",
+                        format!("A mockup may composite at most {} designs", MAX_DESIGNS),
+                    ));
+                }
+                self.designs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, input)| {
+                        if let Some(mode) = &input.blend_mode {
+                            if !BLEND_MODES.contains(&mode.as_str()) {
+                                return Err(bad_request(
+                                    "INVALID_REQUEST",
+                                    format!(
+                                        "designs[{}].blend_mode must be one of: {}",
+                                        index,
+                                        BLEND_MODES.join(", ")
+                                    ),
+                                ));
+                            }
+                        }
+                        Ok(RequestedLayer::new(
+                            DesignSource::Url(input.design_url.clone()),
+                            input.placement.clone(),
+                            input.displacement_strength.or(displacement_strength),
+                            input.blend_mode.clone(),
+                        ))
+                    })
+                    .collect()
+            }
+            (None, None, true) => Err(bad_request(
+                "INVALID_REQUEST",
+                "Provide design_url and placement, or designs".to_string(),
+            )),
+            (_, _, true) => Err(bad_request(
+                "INVALID_REQUEST",
+                "design_url and placement must be given together".to_string(),
+            )),
+            (_, _, false) => Err(bad_request(
+                "INVALID_REQUEST",
+                "Use either designs or design_url and placement, not both".to_string(),
+            )),
+        }
+    }
+
+    /// Every invalid field of the request, checked against `template` when it exists
+    ///
+    /// Without a template, bounds and displacement range checks are skipped and
+    /// the handler answers 404 for the unknown ID as before.
+    fn validate(&self, template: Option<&TemplateMetadata>) -> FieldErrors {
+        let mut errors = FieldErrors::default();
+        if self.template_id.trim().is_empty() {
+            errors.required("template_id", "template_id must not be empty");
+        }
+        let print_area = template.map(|t| (t.print_area.width, t.print_area.height));
+        let displacement = template.map(|t| &t.displacement);
+        let strict = self.options.strict_displacement;
+
+        let placed =
+            self.placement.is_some() || self.physical_placement.is_some() || self.preset.is_some();
+        match (&self.design_url, placed, self.designs.is_empty()) {
+            (Some(design_url), true, true) => {
+                errors.check_http_url("design_url", design_url);
+                match (&self.placement, &self.physical_placement, &self.preset) {
+                    (Some(placement), None, None) => {
+                        errors.check_placement("", placement, print_area)
+                    }
+                    (None, Some(physical), None) => errors.check_physical(
+                        "",
+                        physical,
+                        template.and_then(|t| t.print_dpi),
+                        print_area,
+                    ),
+                    (None, None, Some(preset)) => {
+                        if let Some(template) = template {
+                            if template.preset_placement(preset).is_none() {
+                                let names: Vec<_> =
+                                    template.placement_presets().into_keys().collect();
+                                errors.add(
+                                    "preset",
+                                    "invalid_value",
+                                    format!("Must be one of: {}", names.join(", ")),
+                                    Some(Value::from(preset.as_str())),
+                                );
+                            }
+                        }
+                    }
+                    (_, _, preset) => errors.add(
+                        if preset.is_some() {
+                            "preset"
+                        } else {
+                            "physical_placement"
+                        },
+                        "conflict",
+                        "Use only one of placement, physical_placement, or preset",
+                        None,
+                    ),
+                }
+            }
+            (None, false, false) => {
+                if self.designs.len() > MAX_DESIGNS {
+                    errors.add(
+                        "designs",
+                        "too_many",
+                        format!("A mockup may composite at most {} designs", MAX_DESIGNS),
+                        Some(Value::from(self.designs.len())),
+                    );
+                }
+                for (index, input) in self.designs.iter().enumerate() {
+                    let prefix = format!("designs[{}].", index);
+                    errors.check_http_url(format!("{}design_url", prefix), &input.design_url);
+                    errors.check_placement(&prefix, &input.placement, print_area);
+                    if let Some(mode) = &input.blend_mode {
+                        if !BLEND_MODES.contains(&mode.as_str()) {
+                            errors.add(
+                                format!("{}blend_mode", prefix),
+                                "invalid_value",
+                                format!("Must be one of: {}", BLEND_MODES.join(", ")),
+                                Some(Value::from(mode.as_str())),
+                            );
+                        }
+                    }
+                    if let (Some(strength), Some(displacement)) =
+                        (input.displacement_strength, displacement)
+                    {
+                        check_strength(
+                            &mut errors,
+                            format!("{}displacement_strength", prefix),
+                            strength,
+                            displacement,
+                            strict,
+                        );
+                    }
+                }
+            }
+            (None, false, true) => {
+                errors.required("design_url", "Provide design_url and placement, or designs");
+            }
+            (Some(_), false, true) => {
+                errors.required(
+                    "placement",
+                    "design_url and placement must be given together",
+                );
+            }
+            (None, true, true) => {
+                errors.required(
+                    "design_url",
+                    "design_url and placement must be given together",
+                );
+            }
+            (_, _, false) => errors.add(
+                "designs",
+                "conflict",
+                "Use either designs or design_url and placement, not both",
+                None,
+            ),
+        }
+
+        // options.displacement_strength only applies to designs without their own
+        let options_strength_used = self.designs.is_empty()
+            || self
+                .designs
+                .iter()
+                .any(|d| d.displacement_strength.is_none());
+        if options_strength_used {
+            if let (Some(strength), Some(displacement)) =
+                (self.options.displacement_strength, displacement)
+            {
+                check_strength(
+                    &mut errors,
+                    "options.displacement_strength".to_string(),
+                    strength,
+                    displacement,
+                    strict,
+                );
+            }
+        }
+        self.options.check(&mut errors);
+        errors
+    }
+}
+
+/// Flag a displacement strength outside the template's range, when out-of-range
+/// strengths are rejected rather than clamped
+fn check_strength(
+    errors: &mut FieldErrors,
+    field: String,
+    strength: f64,
+    displacement: &DisplacementConfig,
+    strict: bool,
+) {
+    if let Err(message) = displacement.resolve_strength(Some(strength), strict) {
+        errors.add(field, "out_of_range", message, Some(Value::from(strength)));
+    }
+}
+
+/// A design layer whose displacement strength is not yet checked against its template
+struct RequestedLayer {
+    layer: DesignLayer,
+    displacement_strength: Option<f64>,
+    /// Set when the placement was given as a physical print size
+    print_size: Option<PrintSize>,
+}
+
+impl RequestedLayer {
+    fn new(
+        design: DesignSource,
+        placement: PlacementSpec,
+        displacement_strength: Option<f64>,
+        blend_mode: Option<String>,
+    ) -> Self {
+        Self {
+            layer: DesignLayer {
+                design,
+                placement,
+                // Set by resolve_displacement
+                displacement_strength: 0.0,
+                blend_mode,
+            },
+            displacement_strength,
+            print_size: None,
+        }
+    }
+}
+
+/// Resolve each layer's displacement strength against the template's range
+///
+/// Layers without a strength take the one for `options.realism` when given,
+/// and the template's default otherwise. Returns the layers and any clamp
+/// warnings, or a 400 naming the allowed range when `strict_displacement` is set.
+fn resolve_displacement(
+    requested: Vec<RequestedLayer>,
+    displacement: &DisplacementConfig,
+    stats: Option<&DisplacementStats>,
+    options: &GenerateOptions,
+) -> Result<(Vec<DesignLayer>, Vec<String>), HttpResponse> {
+    let realism_strength = options
+        .realism()?
+        .map(|realism| displacement.strength_for_realism(realism, stats));
+    let multiple = requested.len() > 1;
+    let mut warnings = Vec::new();
+    let mut layers = Vec::with_capacity(requested.len());
+    for (index, requested) in requested.into_iter().enumerate() {
+        let mut layer = requested.layer;
+        let prefix = |message: String| {
+            if multiple {
+                format!("designs[{}]: {}", index, message)
+            } else {
+                message
+            }
+        };
+        let (strength, warning) = displacement
+            .resolve_strength(
+                requested.displacement_strength.or(realism_strength),
+                options.strict_displacement,
+            )
+            .map_err(|message| bad_request("INVALID_DISPLACEMENT", prefix(message)))?;
+        layer.displacement_strength = strength;
+        warnings.extend(warning.map(prefix));
+        layers.push(layer);
+    }
+    Ok((layers, warnings))
+}
+
 /// Optional generation options
 #[derive(Debug, Default, Deserialize, ToSchema)]
 pub struct GenerateOptions {
-    /// Displacement strength (0-30, default 10)
-    #[serde(default = "default_displacement")]
-    pub displacement_strength: f64,
+    /// Displacement strength; defaults to the template's `strength_default` and is
+    /// clamped to its `strength_range` with a warning
+    pub displacement_strength: Option<f64>,
+    /// Reject an out-of-range `displacement_strength` with a 400 instead of clamping it
+    #[serde(default)]
+    pub strict_displacement: bool,
+    /// How pronounced fabric folds look, from 0 (flat) to 1 (most natural), mapped to
+    /// a strength for each template's displacement map; `displacement_strength` wins
+    pub realism: Option<f64>,
+    /// Run (true) or skip (false) the displacement pass; defaults to off for flat
+    /// products such as posters and phone cases, on otherwise
+    pub apply_displacement: Option<bool>,
     /// Hex color to tint the product template (e.g. "0D0D0D" for black)
     pub tint_color: Option<String>,
+    /// Make white and near-white design pixels transparent, for artwork exported on a
+    /// white background (default false). Designs with any transparency are left as-is
+    #[serde(default)]
+    pub remove_background: bool,
+    /// "luminance" removes every white pixel, "border_flood" only white regions connected
+    /// to the image border, "none" disables removal. Overrides `remove_background`, which
+    /// selects "luminance"
+    pub background_removal_mode: Option<BackgroundRemovalMode>,
+    /// Thresholds for background removal
+    pub background_removal: Option<BackgroundRemoval>,
+    /// Output encoding: "png" (default), "jpeg", or "webp"
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    /// Quality for jpeg/webp output (1-100); JPEG defaults to the preset's quality, WebP to 85
+    pub quality: Option<u8>,
+    /// JPEG quality and subsampling preset: "web", "standard", "high", or "print"
+    /// (defaults to `output.jpeg_preset`)
+    pub jpeg_preset: Option<JpegPreset>,
+    /// JPEG chroma subsampling ("4:4:4", "4:2:2", "4:2:0"), overriding the preset
+    pub chroma_subsampling: Option<ChromaSubsampling>,
+    /// Hex color JPEG output is flattened onto where the mockup is transparent (default white)
+    pub background_color: Option<String>,
+    /// "json" (default) or "binary"; when omitted, an `Accept: image/*` header selects binary
+    pub response_mode: Option<ResponseMode>,
+    /// Upload the mockup to Cloudinary and return its URL instead of a data URI (JSON mode only)
+    #[serde(default)]
+    pub upload: bool,
+    /// Also store the mockup in R2 under `generated/{date}/{uuid}.{ext}` (JSON mode only)
+    #[serde(default)]
+    pub store_in_r2: bool,
+    /// Give up fetching a design URL after this many milliseconds; capped at the
+    /// server's `max_fetch_timeout_secs`, which is also the default
+    pub fetch_timeout_ms: Option<u64>,
+    /// Serve an identical earlier render from the render cache and store this one
+    /// (default true); false always composites
+    pub cache: Option<bool>,
+}
+
+/// How the generated mockup is returned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseMode {
+    /// JSON body with the image as a base64 data URI
+    #[default]
+    Json,
+    /// Raw image body with its Content-Type
+    Binary,
+}
+
+impl GenerateOptions {
+    /// Output settings, with JPEG defaults from `default_preset` unless the request picks one
+    pub(crate) fn output_settings(
+        &self,
+        default_preset: JpegPreset,
+    ) -> Result<OutputSettings, HttpResponse> {
+        OutputSettings::with_preset(
+            self.output_format,
+            self.jpeg_preset.unwrap_or(default_preset),
+            self.quality,
+            self.chroma_subsampling,
+            self.background_color.as_deref(),
+        )
+        .map_err(|message| bad_request("INVALID_OUTPUT", message))
+    }
+
+    /// Background removal mode and thresholds, when removal is enabled
+    pub(crate) fn background_removal(&self) -> Result<Option<BackgroundRemoval>, HttpResponse> {
+        let mode = match self.background_removal_mode {
+            Some(mode) => mode,
+            None if self.remove_background => BackgroundRemovalMode::Luminance,
+            None => return Ok(None),
+        };
+        if mode == BackgroundRemovalMode::None {
+            return Ok(None);
+        }
+        let removal = BackgroundRemoval {
+            mode,
+            ..self.background_removal.unwrap_or_default()
+        };
+        removal
+            .validate()
+            .map_err(|message| bad_request("INVALID_BACKGROUND_REMOVAL", message))?;
+        Ok(Some(removal))
+    }
+
+    /// Record invalid numeric options under `options.*`
+    ///
+    /// `output_format`, `jpeg_preset`, and the other enums are rejected while
+    /// the body is deserialized, before this runs.
+    fn check(&self, errors: &mut FieldErrors) {
+        if let Some(realism) = self.realism {
+            if !(0.0..=1.0).contains(&realism) {
+                errors.add(
+                    "options.realism",
+                    "out_of_range",
+                    "Must be between 0 and 1",
+                    Some(Value::from(realism)),
+                );
+            }
+        }
+        if let Some(quality) = self.quality {
+            if !(1..=100).contains(&quality) {
+                errors.add(
+                    "options.quality",
+                    "out_of_range",
+                    "Must be between 1 and 100",
+                    Some(Value::from(quality)),
+                );
+            }
+        }
+        if self.fetch_timeout_ms == Some(0) {
+            errors.add(
+                "options.fetch_timeout_ms",
+                "out_of_range",
+                "Must be at least 1",
+                Some(Value::from(0)),
+            );
+        }
+    }
+
+    /// Realism level, checked to lie within 0-1
+    pub(crate) fn realism(&self) -> Result<Option<f64>, HttpResponse> {
+        match self.realism {
+            Some(realism) if !(0.0..=1.0).contains(&realism) => Err(bad_request(
+                "INVALID_REALISM",
+                format!("realism must be between 0 and 1, got {}", realism),
+            )),
+            realism => Ok(realism),
+        }
+    }
+
+    /// Design fetch timeout, capped at the server's maximum
+    pub(crate) fn fetch_timeout(&self, server: &ServerSettings) -> Result<Duration, HttpResponse> {
+        let max_fetch_timeout = server.max_fetch_timeout();
+        match self.fetch_timeout_ms {
+            Some(0) => Err(bad_request(
+                "INVALID_FETCH_TIMEOUT",
+                "fetch_timeout_ms must be at least 1".to_string(),
+            )),
+            Some(ms) => Ok(Duration::from_millis(ms).min(max_fetch_timeout)),
+            None => Ok(max_fetch_timeout),
+        }
+    }
+
+    /// Fetch timeout and overall deadline for a generation starting at
+    /// `start`, fetching designs only from `design_domains`
+    pub(crate) fn generation_limits(
+        &self,
+        server: &ServerSettings,
+        start: Instant,
+        design_domains: Vec<String>,
+    ) -> Result<GenerationLimits, HttpResponse> {
+        Ok(GenerationLimits {
+            fetch_timeout: Some(self.fetch_timeout(server)?),
+            deadline: Some(start + server.generation_timeout()),
+            design: server.design_limits(),
+            design_domains,
+            ..GenerationLimits::default()
+        })
+    }
+
+    /// Explicit `response_mode` wins; otherwise negotiate from the Accept header
+    fn response_mode(&self, req: &HttpRequest) -> ResponseMode {
+        self.response_mode.unwrap_or_else(|| {
+            let accept = req
+                .headers()
+                .get(header::ACCEPT)
+                .and_then(|v| v.to_str().ok());
+            response_mode_for_accept(accept)
+        })
+    }
+}
+
+/// Binary when the client's first preference is an image type
+fn response_mode_for_accept(accept: Option<&str>) -> ResponseMode {
+    let first = accept
+        .and_then(|accept| accept.split([',', ';']).next())
+        .map(|media| media.trim().to_ascii_lowercase());
+    match first {
+        Some(media) if media.starts_with("image/") => ResponseMode::Binary,
+        _ => ResponseMode::Json,
+    }
+}
+
+/// Request body for generating against a provider variant's mockup template
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GenerateFromCatalogRequest {
+    /// URL of the design image to composite
+    pub design_url: String,
+    /// Provider code (e.g., "printful")
+    pub provider: String,
+    /// Provider's product ID
+    pub product_id: String,
+    /// Provider's variant ID, for variant-specific templates
+    #[serde(default)]
+    pub variant_id: Option<String>,
+    /// Print placement on the product (default "front")
+    #[serde(default = "default_print_placement")]
+    pub print_placement: String,
+    /// Placement specification
+    pub placement: PlacementSpec,
+    /// Optional generation options
+    #[serde(default)]
+    pub options: GenerateOptions,
+    /// Fetch the provider's mockup template when it is not cached yet
+    #[serde(default)]
+    pub fetch_on_demand: bool,
 }
 
-fn default_displacement() -> f64 {
-    10.0
+fn default_print_placement() -> String {
+    "front".to_string()
 }
 
 /// Response for successful mockup generation
@@ -43,6 +630,21 @@ fn default_displacement() -> f64 {
 pub struct GenerateResponse {
     pub success: bool,
     pub mockup_url: String,
+    /// Cloudinary public ID when the mockup was uploaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_id: Option<String>,
+    /// R2 key when the mockup was stored with `store_in_r2`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r2_key: Option<String>,
+    /// Public URL of the stored R2 object, when a public URL prefix is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+    /// Set when a requested upload failed; the mockup is still returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    /// Set when a failed upload is being retried; poll `/api/v1/renders/{render_id}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub render_id: Option<Uuid>,
     pub metadata: GenerateMetadata,
 }
 
@@ -51,10 +653,74 @@ pub struct GenerateResponse {
 pub struct GenerateMetadata {
     pub generation_time_ms: u64,
     pub template_used: String,
+    /// MIME type of the encoded mockup
+    pub content_type: String,
     pub dimensions: Dimensions,
+    /// How `physical_placement` was interpreted, when given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub print_size: Option<PrintSize>,
+}
+
+/// A physical placement and the pixel placement it prints as
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PrintSize {
+    /// The requested placement, with the DPI it was converted at
+    pub physical: PhysicalPlacement,
+    /// The equivalent placement in the template's print area
+    pub pixels: PlacementSpec,
+    /// Printed design size in pixels
+    pub design_dimensions: Dimensions,
+}
+
+impl PrintSize {
+    fn new(physical: PhysicalPlacement, pixels: PlacementSpec) -> Self {
+        let (width, height) = pixels.get_design_dimensions();
+        Self {
+            physical,
+            design_dimensions: Dimensions {
+                width: width as u32,
+                height: height as u32,
+            },
+            pixels,
+        }
+    }
 }
 
+/// Response for generation against a provider template
 #[derive(Serialize, ToSchema)]
+pub struct GenerateFromCatalogResponse {
+    pub success: bool,
+    pub mockup_url: String,
+    /// Cloudinary public ID when the mockup was uploaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_id: Option<String>,
+    /// R2 key when the mockup was stored with `store_in_r2`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r2_key: Option<String>,
+    /// Public URL of the stored R2 object, when a public URL prefix is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+    /// Set when a requested upload failed; the mockup is still returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    /// Set when a failed upload is being retried; poll `/api/v1/renders/{render_id}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub render_id: Option<Uuid>,
+    pub metadata: GenerateMetadata,
+    pub template: CatalogTemplateSource,
+}
+
+/// Where the provider template came from
+#[derive(Serialize, ToSchema)]
+pub struct CatalogTemplateSource {
+    /// "r2_cache" or "provider"; for catalog products "r2" or "local_cache"
+    pub source: String,
+    pub source_url: String,
+    /// R2 key of the cached template image
+    pub r2_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct Dimensions {
     pub width: u32,
     pub height: u32,
@@ -64,6 +730,9 @@ pub struct Dimensions {
 #[derive(Serialize, ToSchema)]
 pub struct ErrorResponse {
     pub success: bool,
+    /// ID of the failed request, also sent as `X-Request-Id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
     pub error: ApiError,
 }
 
@@ -79,99 +748,2228 @@ pub struct ApiError {
     path = "/api/v1/mockups/generate",
     tag = "mockups",
     request_body = GenerateRequest,
+    params(
+        ("async" = Option<bool>, Query, description = "Return 202 with a job ID and render in the background")
+    ),
     responses(
         (status = 200, description = "Mockup generated successfully", body = GenerateResponse),
-        (status = 400, description = "Invalid placement specification", body = ErrorResponse),
+        (status = 202, description = "Render queued; poll status_url for the result", body = RenderJobAccepted),
+        (status = 400, description = "Malformed request body", body = ErrorResponse),
+        (status = 403, description = "Design URL is outside the key's allowed design domains (`DESIGN_DOMAIN_NOT_ALLOWED`)", body = ErrorResponse),
         (status = 404, description = "Template not found", body = ErrorResponse),
-        (status = 500, description = "Generation failed", body = ErrorResponse)
+        (status = 422, description = "One or more fields are invalid (`VALIDATION_FAILED`, listing each), or the design URL points at an internal host, or returned an oversized, undersized, or non-PNG/JPEG/WebP image", body = ValidationErrorResponse),
+        (status = 500, description = "Generation failed", body = ErrorResponse),
+        (status = 503, description = "No generation slot freed up in time, or the server is shutting down; see Retry-After", body = ErrorResponse),
+        (status = 504, description = "Generation ran past its deadline", body = ErrorResponse)
     )
 )]
 pub async fn generate_mockup(
+    req: HttpRequest,
     state: web::Data<AppState>,
+    query: web::Query<GenerateQuery>,
     body: web::Json<GenerateRequest>,
 ) -> HttpResponse {
-    let start = Instant::now();
+    let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+
+    let mut body = body.into_inner();
+    let template = state.template_manager.get(&body.template_id);
+    if let Err(response) = body
+        .validate(template.as_ref().map(|t| &t.metadata))
+        .into_result()
+    {
+        return response;
+    }
+    // Presets and inches only map to pixels in a template's print area
+    let needs_template = body.physical_placement.is_some() || body.preset.is_some();
+    let print_size = match (&template, needs_template) {
+        (Some(template), true) => {
+            body.resolve_preset(&template.metadata);
+            body.resolve_physical_placement(&template.metadata)
+        }
+        (None, true) => return template_not_found(&body.template_id),
+        (_, false) => None,
+    };
+    let mut designs = match body.design_layers() {
+        Ok(designs) => designs,
+        Err(response) => return response,
+    };
+    if let Err(response) = ensure_design_domains(&req, design_urls(&designs)) {
+        return response;
+    }
+    if let Some(first) = designs.first_mut() {
+        first.print_size = print_size;
+    }
 
     info!(
         template_id = %body.template_id,
-        design_url = %body.design_url,
+        designs = designs.len(),
         "Processing mockup generation request"
     );
 
-    // Validate template exists and get its print area dimensions
-    let template = match state.template_manager.get(&body.template_id) {
-        Some(t) => t,
-        None => {
-            error!(template_id = %body.template_id, "Template not found");
-            return HttpResponse::NotFound().json(ErrorResponse {
-                success: false,
-                error: ApiError {
-                    code: "TEMPLATE_NOT_FOUND".to_string(),
-                    message: format!("Template '{}' does not exist", body.template_id),
-                },
-            });
+    // Async results are polled as JSON, so images are never returned inline
+    let response_mode = if query.run_async {
+        ResponseMode::Json
+    } else {
+        body.options.response_mode(&req)
+    };
+    if let Err(response) = ensure_render_storage(&req, &state, &body.options, response_mode).await {
+        return response;
+    }
+    if query.run_async {
+        let template_id = body.template_id.clone();
+        let response = start_render_job(
+            state,
+            api_key_id,
+            design_domains(&req),
+            designs,
+            body.template_id,
+            body.options,
+        )
+        .await;
+        return record_render_usage(&req, &template_id, response);
+    }
+    let response = render_template_mockup(
+        &state,
+        api_key_id,
+        design_domains(&req),
+        designs,
+        &body.template_id,
+        &body.options,
+        response_mode,
+    )
+    .await;
+    record_render_usage(&req, &body.template_id, response)
+}
+
+/// Record one mockup of `template_id` in the request's usage log, if `response` succeeded
+fn record_render_usage(
+    req: &HttpRequest,
+    template_id: &str,
+    response: HttpResponse,
+) -> HttpResponse {
+    if response.status().is_success() {
+        RenderUsage::new(Some(template_id.to_string()), 1).record(req);
+    }
+    response
+}
+
+/// Query parameters of `POST /api/v1/mockups/generate`
+#[derive(Debug, Default, Deserialize)]
+pub struct GenerateQuery {
+    /// Answer 202 with a job ID right away and render in the background
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+}
+
+/// Response to an async generation request
+#[derive(Serialize, ToSchema)]
+pub struct RenderJobAccepted {
+    pub success: bool,
+    pub job_id: Uuid,
+    pub status: RenderJobStatus,
+    /// Poll this URL for progress and the result
+    pub status_url: String,
+}
+
+/// Queue a render job and run it on a background task
+///
+/// The job records the JSON body the synchronous endpoint would have
+/// returned: the generate response on success, its `error` otherwise.
+async fn start_render_job(
+    state: web::Data<AppState>,
+    api_key_id: Option<Uuid>,
+    design_domains: Vec<String>,
+    designs: Vec<RequestedLayer>,
+    template_id: String,
+    options: GenerateOptions,
+) -> HttpResponse {
+    let job = state.render_jobs.create(api_key_id, &template_id).await;
+    let job_id = job.job_id;
+    info!(job_id = %job_id, template_id = %template_id, "Queued async mockup generation");
+
+    // Rendering holds the response body, which is not Send, so it stays on this worker
+    actix_web::rt::spawn(async move {
+        state.render_jobs.start(job_id).await;
+        let response = render_template_mockup(
+            &state,
+            api_key_id,
+            design_domains,
+            designs,
+            &template_id,
+            &options,
+            ResponseMode::Json,
+        )
+        .await;
+
+        let succeeded = response.status().is_success();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
+            .unwrap_or_default();
+        if succeeded {
+            state.render_jobs.complete(job_id, body).await;
+        } else {
+            let field = |name: &str, default: &str| {
+                body["error"][name].as_str().unwrap_or(default).to_string()
+            };
+            let error = RenderJobError {
+                code: field("code", "GENERATION_FAILED"),
+                message: field("message", "Mockup generation failed"),
+            };
+            state.render_jobs.fail(job_id, error).await;
+        }
+    });
+
+    let status_url = format!("/api/v1/mockups/jobs/{}", job_id);
+    HttpResponse::Accepted()
+        .insert_header((header::LOCATION, status_url.clone()))
+        .json(RenderJobAccepted {
+            success: true,
+            job_id,
+            status: job.status,
+            status_url,
+        })
+}
+
+/// Request part of a multipart generation upload
+#[derive(Debug, Deserialize)]
+pub struct GenerateUploadRequest {
+    /// Template ID (e.g., "white_male_front")
+    pub template_id: String,
+    /// Placement specification
+    pub placement: PlacementSpec,
+    /// Optional generation options
+    #[serde(default)]
+    pub options: GenerateOptions,
+}
+
+impl GenerateUploadRequest {
+    /// Every invalid field of the request part, checked against `template` when it exists
+    fn validate(&self, template: Option<&TemplateMetadata>) -> FieldErrors {
+        let mut errors = FieldErrors::default();
+        if self.template_id.trim().is_empty() {
+            errors.required("template_id", "template_id must not be empty");
+        }
+        let print_area = template.map(|t| (t.print_area.width, t.print_area.height));
+        errors.check_placement("", &self.placement, print_area);
+        if let (Some(strength), Some(template)) = (self.options.displacement_strength, template) {
+            check_strength(
+                &mut errors,
+                "options.displacement_strength".to_string(),
+                strength,
+                &template.displacement,
+                self.options.strict_displacement,
+            );
+        }
+        self.options.check(&mut errors);
+        errors
+    }
+}
+
+/// Largest accepted `request` part of a multipart upload
+const MAX_REQUEST_PART_BYTES: usize = 64 * 1024;
+
+/// Route guard matching multipart/form-data requests
+pub fn is_multipart(ctx: &GuardContext) -> bool {
+    ctx.head()
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |ct| {
+            ct.trim_start()
+                .to_ascii_lowercase()
+                .starts_with("multipart/form-data")
+        })
+}
+
+/// POST /api/v1/mockups/generate (multipart/form-data) - Generate from an uploaded design
+///
+/// Parts: `design` (image file) and `request` (JSON with template_id,
+/// placement, and options).
+pub async fn generate_mockup_upload(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    payload: Multipart,
+) -> HttpResponse {
+    let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+
+    let (design, request) = match read_upload(payload, state.settings.server.max_upload_bytes).await
+    {
+        Ok(parts) => parts,
+        Err(response) => return response,
+    };
+    let request: GenerateUploadRequest = match serde_json::from_slice(&request) {
+        Ok(parsed) => parsed,
+        Err(e) => return bad_request("INVALID_REQUEST", e.to_string()),
+    };
+    let template = state.template_manager.get(&request.template_id);
+    if let Err(response) = request
+        .validate(template.as_ref().map(|t| &t.metadata))
+        .into_result()
+    {
+        return response;
+    }
+
+    info!(
+        template_id = %request.template_id,
+        design_bytes = design.len(),
+        "Processing mockup upload request"
+    );
+
+    let response_mode = request.options.response_mode(&req);
+    if let Err(response) =
+        ensure_render_storage(&req, &state, &request.options, response_mode).await
+    {
+        return response;
+    }
+    let designs = vec![RequestedLayer::new(
+        DesignSource::Bytes(design),
+        request.placement.clone(),
+        request.options.displacement_strength,
+        None,
+    )];
+    let response = render_template_mockup(
+        &state,
+        api_key_id,
+        design_domains(&req),
+        designs,
+        &request.template_id,
+        &request.options,
+        response_mode,
+    )
+    .await;
+    record_render_usage(&req, &request.template_id, response)
+}
+
+/// Read the `design` image and raw `request` JSON parts of a multipart upload
+pub(crate) async fn read_upload(
+    mut payload: Multipart,
+    max_upload_bytes: usize,
+) -> Result<(Bytes, Bytes), HttpResponse> {
+    let mut design = None;
+    let mut request = None;
+
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|e| bad_request("INVALID_MULTIPART", e.to_string()))?;
+
+        match field.name() {
+            Some("design") => {
+                let is_image = field
+                    .content_type()
+                    .map_or(false, |ct| ct.type_().as_str() == "image");
+                if !is_image {
+                    return Err(unsupported_media_type(
+                        "The design part must have an image/* content type",
+                    ));
+                }
+                design = Some(read_field(&mut field, max_upload_bytes).await?);
+            }
+            Some("request") => {
+                request = Some(read_field(&mut field, MAX_REQUEST_PART_BYTES).await?);
+            }
+            // Unknown parts are skipped when the next part is read
+            _ => {}
         }
+    }
+
+    let Some(design) = design else {
+        return Err(bad_request(
+            "MISSING_DESIGN",
+            "Multipart body has no design part".to_string(),
+        ));
+    };
+    let Some(request) = request else {
+        return Err(bad_request(
+            "INVALID_REQUEST",
+            "Multipart body has no request part".to_string(),
+        ));
     };
 
-    // Create placement with template's actual print area dimensions
-    let mut placement = body.placement.clone();
-    placement.print_area_width = template.metadata.print_area.width as i32;
-    placement.print_area_height = template.metadata.print_area.height as i32;
+    // The declared content type is only a hint; the bytes must be a known image format
+    if image::guess_format(&design).is_err() {
+        return Err(unsupported_media_type(
+            "The design part is not a supported image format",
+        ));
+    }
+
+    Ok((design, request))
+}
+
+fn unsupported_media_type(message: &str) -> HttpResponse {
+    HttpResponse::UnsupportedMediaType().json(ErrorResponse {
+        success: false,
+        request_id: RequestId::current(),
+        error: ApiError {
+            code: "UNSUPPORTED_MEDIA_TYPE".to_string(),
+            message: message.to_string(),
+        },
+    })
+}
+
+/// Retry-After for generations refused while this instance drains; another
+/// instance picks up the retry
+const SHUTDOWN_RETRY_AFTER_SECS: u64 = 1;
 
-    // Validate placement with correct dimensions
-    if let Err(e) = placement.validate() {
-        error!(error = %e, "Invalid placement specification");
-        return HttpResponse::BadRequest().json(ErrorResponse {
+/// 503 for a generation that found no free slot in time or arrived during
+/// shutdown, with a Retry-After hint
+fn server_busy(retry_after_secs: u64, message: String) -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .insert_header((header::RETRY_AFTER, retry_after_secs.to_string()))
+        .json(ErrorResponse {
             success: false,
+            request_id: RequestId::current(),
             error: ApiError {
-                code: "INVALID_PLACEMENT".to_string(),
-                message: e.to_string(),
+                code: "SERVER_BUSY".to_string(),
+                message,
             },
+        })
+}
+
+/// 504 for a generation that ran past its deadline, 422 for a design that failed its
+/// size or format checks, 500 for any other failure
+fn generation_failed(e: &TemplateError) -> HttpResponse {
+    let (mut response, code) = match e {
+        TemplateError::TimedOut(_) => (HttpResponse::GatewayTimeout(), "GENERATION_TIMEOUT"),
+        TemplateError::InvalidDesign { code, .. } => {
+            (HttpResponse::build(design_error_status(code)), *code)
+        }
+        _ => (HttpResponse::InternalServerError(), "GENERATION_FAILED"),
+    };
+    response.json(ErrorResponse {
+        success: false,
+        request_id: RequestId::current(),
+        error: ApiError {
+            code: code.to_string(),
+            message: e.to_string(),
+        },
+    })
+}
+
+/// Status for a design refused with `code`: 403 outside the key's design
+/// domains, 422 otherwise
+pub(crate) fn design_error_status(code: &str) -> StatusCode {
+    match code {
+        "DESIGN_DOMAIN_NOT_ALLOWED" => StatusCode::FORBIDDEN,
+        _ => StatusCode::UNPROCESSABLE_ENTITY,
+    }
+}
+
+/// Read a multipart field, answering 413 once it exceeds `limit` bytes
+pub(crate) async fn read_field(field: &mut Field, limit: usize) -> Result<Bytes, HttpResponse> {
+    let mut data = BytesMut::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|e| bad_request("INVALID_MULTIPART", e.to_string()))?;
+        if data.len() + chunk.len() > limit {
+            return Err(HttpResponse::PayloadTooLarge().json(ErrorResponse {
+                success: false,
+                request_id: RequestId::current(),
+                error: ApiError {
+                    code: "PAYLOAD_TOO_LARGE".to_string(),
+                    message: format!("Upload part exceeds the {} byte limit", limit),
+                },
+            }));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data.freeze())
+}
+
+/// Refuse a request that would store more renders in R2 than the key's tier allows
+///
+/// Only JSON responses store renders, and only when storage is configured.
+async fn ensure_render_storage(
+    req: &HttpRequest,
+    state: &AppState,
+    options: &GenerateOptions,
+    response_mode: ResponseMode,
+) -> Result<(), HttpResponse> {
+    if !options.store_in_r2 || response_mode != ResponseMode::Json || state.storage.is_none() {
+        return Ok(());
+    }
+    ensure_saved_render_capacity(req, state, 1).await
+}
+
+/// Refuse design URLs hosted outside the requesting key's allowed design domains
+///
+/// Runs before any fetch, so before the SSRF guard sees the URLs; the fetch
+/// applies the same domains again to every redirect hop.
+pub(crate) fn ensure_design_domains<'a>(
+    req: &HttpRequest,
+    urls: impl IntoIterator<Item = &'a str>,
+) -> Result<(), HttpResponse> {
+    let extensions = req.extensions();
+    let Some(auth) = extensions.get::<ApiKeyAuth>() else {
+        return Ok(());
+    };
+    let domains = &auth.allowed_design_domains;
+    match urls.into_iter().find(|url| !url_in_domains(url, domains)) {
+        Some(url) => Err(error_response(
+            StatusCode::FORBIDDEN,
+            "DESIGN_DOMAIN_NOT_ALLOWED",
+            format!(
+                "Design URL {} is not hosted on one of this key's allowed design domains ({})",
+                url,
+                domains.join(", ")
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Domains the requesting key may fetch designs from; empty allows any
+pub(crate) fn design_domains(req: &HttpRequest) -> Vec<String> {
+    req.extensions()
+        .get::<ApiKeyAuth>()
+        .map(|auth| auth.allowed_design_domains.clone())
+        .unwrap_or_default()
+}
+
+/// URLs of the designs fetched rather than uploaded
+fn design_urls(designs: &[RequestedLayer]) -> impl Iterator<Item = &str> {
+    designs
+        .iter()
+        .filter_map(|requested| match &requested.layer.design {
+            DesignSource::Url(url) => Some(url.as_str()),
+            DesignSource::Bytes(_) => None,
+        })
+}
+
+/// Check that `count` more saved renders fit under the requesting key's limit
+pub(crate) async fn ensure_saved_render_capacity(
+    req: &HttpRequest,
+    state: &AppState,
+    count: i64,
+) -> Result<(), HttpResponse> {
+    let auth = req.extensions().get::<ApiKeyAuth>().cloned();
+    match (&state.db_pool, auth) {
+        (Some(pool), Some(auth)) => {
+            ensure_resource_capacity(pool, &auth, ResourceKind::SavedRenders, count).await
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Count a render stored in R2 toward the key's saved render limit
+pub(crate) async fn record_saved_render(state: &AppState, api_key_id: Option<Uuid>, r2_key: &str) {
+    let (Some(pool), Some(api_key_id)) = (&state.db_pool, api_key_id) else {
+        return;
+    };
+    let repo = ResourceRepository::new(pool.clone());
+    if let Err(e) = repo.record_render(api_key_id, r2_key).await {
+        warn!(error = %e, r2_key = %r2_key, "Failed to record saved render");
+    }
+}
+
+/// 404 for a template ID that does not exist
+fn template_not_found(template_id: &str) -> HttpResponse {
+    error!(template_id = %template_id, "Template not found");
+    HttpResponse::NotFound().json(ErrorResponse {
+        success: false,
+        request_id: RequestId::current(),
+        error: ApiError {
+            code: "TEMPLATE_NOT_FOUND".to_string(),
+            message: format!("Template '{}' does not exist", template_id),
+        },
+    })
+}
+
+pub(crate) fn bad_request(code: &str, message: String) -> HttpResponse {
+    HttpResponse::BadRequest().json(ErrorResponse {
+        success: false,
+        request_id: RequestId::current(),
+        error: ApiError {
+            code: code.to_string(),
+            message,
+        },
+    })
+}
+
+/// Error response with any status
+fn error_response(status: StatusCode, code: &str, message: String) -> HttpResponse {
+    HttpResponse::build(status).json(ErrorResponse {
+        success: false,
+        request_id: RequestId::current(),
+        error: ApiError {
+            code: code.to_string(),
+            message,
+        },
+    })
+}
+
+/// Render designs onto a loaded template, shared by the JSON and upload flows
+async fn render_template_mockup(
+    state: &AppState,
+    api_key_id: Option<Uuid>,
+    design_domains: Vec<String>,
+    designs: Vec<RequestedLayer>,
+    template_id: &str,
+    options: &GenerateOptions,
+    response_mode: ResponseMode,
+) -> HttpResponse {
+    let start = Instant::now();
+    // Events report the first design
+    let design_url = designs
+        .first()
+        .and_then(|requested| match &requested.layer.design {
+            DesignSource::Url(url) => Some(url.clone()),
+            DesignSource::Bytes(_) => None,
         });
+    let print_size = designs
+        .first()
+        .and_then(|requested| requested.print_size.clone());
+    let output = match options.output_settings(state.settings.output.jpeg_preset) {
+        Ok(output) => output,
+        Err(response) => return response,
+    };
+    let remove_background = match options.background_removal() {
+        Ok(removal) => removal,
+        Err(response) => return response,
+    };
+    let mut limits = match options.generation_limits(&state.settings.server, start, design_domains)
+    {
+        Ok(limits) => limits,
+        Err(response) => return response,
+    };
+
+    // Validate template exists and get its print area dimensions
+    let template = match state.template_manager.get(template_id) {
+        Some(t) => t,
+        None => return template_not_found(template_id),
+    };
+
+    // Realism strengths are tuned to the displacement map, measured on first decode
+    let stats = if options.realism.is_some() {
+        match state.template_manager.displacement_stats(&template).await {
+            Ok(stats) => stats,
+            Err(e) => {
+                error!(error = %e, template_id = %template_id, "Failed to load template images");
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    success: false,
+                    request_id: RequestId::current(),
+                    error: ApiError {
+                        code: "GENERATION_FAILED".to_string(),
+                        message: e.to_string(),
+                    },
+                });
+            }
+        }
+    } else {
+        None
+    };
+    let (mut designs, warnings) = match resolve_displacement(
+        designs,
+        &template.metadata.displacement,
+        stats.as_ref(),
+        options,
+    ) {
+        Ok(resolved) => resolved,
+        Err(response) => return response,
+    };
+
+    // Size each placement to the template's print area, converting display space
+    // offsets, and validate it there
+    let multiple = designs.len() > 1;
+    let print_area = &template.metadata.print_area;
+    for (index, layer) in designs.iter_mut().enumerate() {
+        let placement = &mut layer.placement;
+        *placement = placement.in_print_area(print_area.width, print_area.height);
+
+        if let Err(e) = placement.validate() {
+            error!(error = %e, index, "Invalid placement specification");
+            let message = if multiple {
+                format!("designs[{}]: {}", index, e)
+            } else {
+                e.to_string()
+            };
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                success: false,
+                request_id: RequestId::current(),
+                error: ApiError {
+                    code: "INVALID_PLACEMENT".to_string(),
+                    message,
+                },
+            });
+        }
     }
 
-    // Create mockup request with adjusted placement
-    let request = MockupRequest {
-        design_url: body.design_url.clone(),
-        template_id: body.template_id.clone(),
-        placement,
-        displacement_strength: body.options.displacement_strength,
-        tint_color: body.options.tint_color.clone(),
+    // Stops work on other threads if the client disconnects mid-generation
+    let _cancel = limits.cancel_on_drop();
+
+    // Create mockup request with adjusted placements
+    let mut request = MockupRequest {
+        designs,
+        template_id: template_id.to_string(),
+        apply_displacement: options.apply_displacement,
+        tint_color: options.tint_color.clone(),
+        remove_background,
+        output,
+        limits,
     };
 
-    // Generate mockup (this is the heavy lifting)
-    match state.template_manager.generate_mockup(&request).await {
-        Ok(result) => {
+    // Generate mockup (this is the heavy lifting), unless an identical one is cached
+    let use_cache = options.cache.unwrap_or(true);
+    match generate_cached(state, &mut request, template.metadata.version, use_cache).await {
+        Ok((result, cache_status)) => {
             let elapsed = start.elapsed().as_millis() as u64;
 
             info!(
-                template_id = %body.template_id,
+                template_id = %template_id,
                 generation_time_ms = elapsed,
                 "Mockup generated successfully"
             );
 
-            HttpResponse::Ok().json(GenerateResponse {
+            publish_render_event(
+                state,
+                api_key_id,
+                EventType::RenderCompleted,
+                serde_json::json!({
+                    "template_id": template_id,
+                    "design_url": design_url,
+                    "generation_time_ms": elapsed,
+                    "width": result.width,
+                    "height": result.height,
+                }),
+            );
+
+            if response_mode == ResponseMode::Binary {
+                let mut response = binary_response(result, elapsed, template_id, &warnings);
+                set_cache_status(&mut response, cache_status);
+                return response;
+            }
+
+            let location =
+                mockup_location(state, &result, options, api_key_id, template_id, warnings).await;
+            let mut response = HttpResponse::Ok().json(GenerateResponse {
                 success: true,
-                mockup_url: result.url,
+                mockup_url: location.url,
+                public_id: location.public_id,
+                r2_key: location.r2_key,
+                public_url: location.public_url,
+                warning: location.warning,
+                render_id: location.render_id,
                 metadata: GenerateMetadata {
                     generation_time_ms: elapsed,
-                    template_used: body.template_id.clone(),
+                    template_used: template_id.to_string(),
+                    content_type: result.content_type.to_string(),
                     dimensions: Dimensions {
                         width: result.width,
                         height: result.height,
                     },
+                    print_size,
                 },
-            })
+            });
+            set_cache_status(&mut response, cache_status);
+            response
         }
-        Err(e) => {
-            error!(error = %e, "Mockup generation failed");
-            HttpResponse::InternalServerError().json(ErrorResponse {
-                success: false,
-                error: ApiError {
-                    code: "GENERATION_FAILED".to_string(),
-                    message: e.to_string(),
+        Err(
+            e @ TemplateError::Saturated {
+                retry_after_secs, ..
+            },
+        ) => {
+            warn!(template_id = %template_id, error = %e, "Mockup generation rejected");
+            server_busy(retry_after_secs, e.to_string())
+        }
+        Err(e @ TemplateError::ShuttingDown) => {
+            warn!(template_id = %template_id, "Mockup generation refused during shutdown");
+            server_busy(SHUTDOWN_RETRY_AFTER_SECS, e.to_string())
+        }
+        Err(
+            e @ TemplateError::InvalidDesign {
+                code: "DESIGN_URL_FORBIDDEN",
+                ..
+            },
+        ) => {
+            warn!(
+                api_key_id = ?api_key_id,
+                design_url = ?design_url,
+                error = %e,
+                "Refused design URL"
+            );
+            generation_failed(&e)
+        }
+        Err(e) => {
+            error!(error = %e, "Mockup generation failed");
+
+            publish_render_event(
+                state,
+                api_key_id,
+                EventType::RenderFailed,
+                serde_json::json!({
+                    "template_id": template_id,
+                    "design_url": design_url,
+                    "error": e.to_string(),
+                }),
+            );
+            generation_failed(&e)
+        }
+    }
+}
+
+/// Request body for a print file: a generate request and how low resolution is handled
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PrintFileRequest {
+    #[serde(flatten)]
+    pub request: GenerateRequest,
+    /// Reject designs below the template's `min_dpi` at their printed size with a
+    /// 422 instead of returning the file with a warning
+    #[serde(default)]
+    pub strict_resolution: bool,
+}
+
+/// POST /api/v1/mockups/print-file - Render designs as a print-ready file
+#[utoipa::path(
+    post,
+    path = "/api/v1/mockups/print-file",
+    tag = "mockups",
+    request_body = PrintFileRequest,
+    responses(
+        (status = 200, description = "PNG the size of the template's print area with the designs on a transparent background and its DPI recorded; `X-Design-Dpi` lists each design's resolution at its printed size and `X-Print-Warning` flags any below the template's minimum", content_type = "image/png"),
+        (status = 400, description = "Malformed request body", body = ErrorResponse),
+        (status = 403, description = "Design URL is outside the key's allowed design domains (`DESIGN_DOMAIN_NOT_ALLOWED`)", body = ErrorResponse),
+        (status = 404, description = "Template not found", body = ErrorResponse),
+        (status = 422, description = "One or more fields are invalid (`VALIDATION_FAILED`, listing each), a design is below the template's minimum DPI with `strict_resolution` (`LOW_RESOLUTION`), or the design URL failed its checks", body = ValidationErrorResponse),
+        (status = 500, description = "Rendering failed", body = ErrorResponse),
+        (status = 503, description = "No generation slot freed up in time, or the server is shutting down; see Retry-After", body = ErrorResponse),
+        (status = 504, description = "Rendering ran past its deadline", body = ErrorResponse)
+    )
+)]
+pub async fn generate_print_file(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<PrintFileRequest>,
+) -> HttpResponse {
+    let start = Instant::now();
+    let PrintFileRequest {
+        request: mut body,
+        strict_resolution,
+    } = body.into_inner();
+    let template = state.template_manager.get(&body.template_id);
+    if let Err(response) = body
+        .validate(template.as_ref().map(|t| &t.metadata))
+        .into_result()
+    {
+        return response;
+    }
+    let Some(template) = template else {
+        return template_not_found(&body.template_id);
+    };
+    let metadata = &template.metadata;
+    body.resolve_preset(metadata);
+    let print_size = body.resolve_physical_placement(metadata);
+    let designs = match body.design_layers() {
+        Ok(designs) => designs,
+        Err(response) => return response,
+    };
+    if let Err(response) = ensure_design_domains(&req, design_urls(&designs)) {
+        return response;
+    }
+    let domains = design_domains(&req);
+    let mut limits = match body
+        .options
+        .generation_limits(&state.settings.server, start, domains)
+    {
+        Ok(limits) => limits,
+        Err(response) => return response,
+    };
+
+    // A physical placement's DPI sets how its inches map to print area pixels
+    let dpi = print_size
+        .and_then(|size| size.physical.dpi)
+        .or(metadata.print_dpi)
+        .unwrap_or(DEFAULT_PRINT_DPI);
+    let print_area = &metadata.print_area;
+    let designs: Vec<_> = designs
+        .into_iter()
+        .map(|requested| {
+            let mut layer = requested.layer;
+            layer.placement = layer
+                .placement
+                .in_print_area(print_area.width, print_area.height);
+            layer
+        })
+        .collect();
+
+    info!(
+        template_id = %body.template_id,
+        designs = designs.len(),
+        dpi,
+        "Processing print file request"
+    );
+    let _cancel = limits.cancel_on_drop();
+    let print_file = match state
+        .template_manager
+        .print_file(&designs, dpi, &limits)
+        .await
+    {
+        Ok(print_file) => print_file,
+        Err(
+            e @ TemplateError::Saturated {
+                retry_after_secs, ..
+            },
+        ) => {
+            warn!(template_id = %body.template_id, error = %e, "Print file rejected");
+            return server_busy(retry_after_secs, e.to_string());
+        }
+        Err(e @ TemplateError::ShuttingDown) => {
+            return server_busy(SHUTDOWN_RETRY_AFTER_SECS, e.to_string());
+        }
+        Err(e) => {
+            error!(template_id = %body.template_id, error = %e, "Print file failed");
+            return generation_failed(&e);
+        }
+    };
+
+    let warnings = low_resolution_warnings(
+        &print_file.design_dpi,
+        metadata.min_dpi.unwrap_or(DEFAULT_MIN_DPI),
+    );
+    if strict_resolution && !warnings.is_empty() {
+        return HttpResponse::UnprocessableEntity().json(ErrorResponse {
+            success: false,
+            request_id: RequestId::current(),
+            error: ApiError {
+                code: "LOW_RESOLUTION".to_string(),
+                message: warnings.join("; "),
+            },
+        });
+    }
+
+    let elapsed = start.elapsed().as_millis() as u64;
+    let design_dpi: Vec<_> = print_file
+        .design_dpi
+        .iter()
+        .map(|dpi| format!("{:.0}", dpi))
+        .collect();
+    let mut response = HttpResponse::Ok();
+    if !warnings.is_empty() {
+        response.insert_header(("X-Print-Warning", warnings.join("; ")));
+    }
+    let response = response
+        .content_type("image/png")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}-print.png\"", body.template_id),
+        ))
+        .insert_header(("X-Print-Width", print_file.width.to_string()))
+        .insert_header(("X-Print-Height", print_file.height.to_string()))
+        .insert_header(("X-Print-Dpi", print_file.dpi.to_string()))
+        .insert_header(("X-Design-Dpi", design_dpi.join(", ")))
+        .insert_header(("X-Generation-Time-Ms", elapsed.to_string()))
+        .insert_header(header::ContentEncoding::Identity)
+        .body(print_file.bytes);
+    record_render_usage(&req, &body.template_id, response)
+}
+
+/// One warning per design whose resolution at its printed size is below `min_dpi`
+fn low_resolution_warnings(design_dpi: &[f64], min_dpi: u32) -> Vec<String> {
+    let multiple = design_dpi.len() > 1;
+    design_dpi
+        .iter()
+        .enumerate()
+        .filter(|(_, dpi)| **dpi < min_dpi as f64)
+        .map(|(index, dpi)| {
+            let message = format!(
+                "Design prints at {:.0} DPI, below the template's minimum of {} DPI",
+                dpi, min_dpi
+            );
+            if multiple {
+                format!("designs[{}]: {}", index, message)
+            } else {
+                message
+            }
+        })
+        .collect()
+}
+
+/// Generate a mockup, or return the bytes encoded for an identical earlier request
+///
+/// Designs are downloaded first so they're fingerprinted by content; the
+/// compositor then decodes the downloaded bytes instead of fetching again.
+async fn generate_cached(
+    state: &AppState,
+    request: &mut MockupRequest,
+    template_version: u32,
+    use_cache: bool,
+) -> Result<(MockupResult, CacheStatus), TemplateError> {
+    if !use_cache || !state.render_cache.is_enabled() {
+        let result = state.template_manager.generate_mockup(request).await?;
+        return Ok((result, CacheStatus::Bypass));
+    }
+
+    state.template_manager.download_designs(request).await?;
+    let Some(key) = RenderCacheKey::for_request(request, template_version) else {
+        let result = state.template_manager.generate_mockup(request).await?;
+        return Ok((result, CacheStatus::Bypass));
+    };
+    if let Some(result) = state.render_cache.get(&key).await {
+        return Ok((result, CacheStatus::Hit));
+    }
+    let result = state.template_manager.generate_mockup(request).await?;
+    state.render_cache.put(&key, result.bytes.clone()).await;
+    Ok((result, CacheStatus::Miss))
+}
+
+/// Tell the client whether the mockup came from the render cache
+fn set_cache_status(response: &mut HttpResponse, status: CacheStatus) {
+    response.headers_mut().insert(
+        header::HeaderName::from_static("x-render-cache"),
+        header::HeaderValue::from_static(status.as_str()),
+    );
+}
+
+/// POST /api/v1/mockups/generate-from-catalog - Generate against a provider template
+#[utoipa::path(
+    post,
+    path = "/api/v1/mockups/generate-from-catalog",
+    tag = "mockups",
+    request_body = GenerateFromCatalogRequest,
+    responses(
+        (status = 200, description = "Mockup generated successfully", body = GenerateFromCatalogResponse),
+        (status = 400, description = "Invalid placement specification or unknown provider", body = ErrorResponse),
+        (status = 403, description = "Design URL is outside the key's allowed design domains (`DESIGN_DOMAIN_NOT_ALLOWED`)", body = ErrorResponse),
+        (status = 404, description = "Template not cached or not offered by the provider", body = ErrorResponse),
+        (status = 422, description = "Design URL points at an internal host, or returned an oversized, undersized, or non-PNG/JPEG/WebP image", body = ErrorResponse),
+        (status = 502, description = "Provider or template download failed", body = ErrorResponse),
+        (status = 500, description = "Generation failed", body = ErrorResponse),
+        (status = 503, description = "No generation slot freed up in time, or the server is shutting down; see Retry-After", body = ErrorResponse),
+        (status = 504, description = "Generation ran past its deadline", body = ErrorResponse)
+    )
+)]
+pub async fn generate_from_catalog(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<GenerateFromCatalogRequest>,
+) -> HttpResponse {
+    let start = Instant::now();
+    let print_placement = PrintPlacement::from_str(&body.print_placement);
+
+    info!(
+        provider = %body.provider,
+        product_id = %body.product_id,
+        variant_id = ?body.variant_id,
+        fetch_on_demand = body.fetch_on_demand,
+        "Processing catalog mockup generation request"
+    );
+
+    if let Err(response) = ensure_design_domains(&req, [body.design_url.as_str()]) {
+        return response;
+    }
+    let render = match CatalogRender::prepare(&req, &state, &body.options, start).await {
+        Ok(render) => render,
+        Err(response) => return response,
+    };
+
+    let template = match state
+        .on_demand_templates
+        .resolve(
+            &body.provider,
+            &body.product_id,
+            body.variant_id.as_deref(),
+            &print_placement,
+            body.fetch_on_demand,
+        )
+        .await
+    {
+        Ok(template) => template,
+        Err(e) => {
+            error!(error = %e, "Failed to resolve provider template");
+            let (status, code) = match &e {
+                OnDemandError::NotCached(_) => (StatusCode::NOT_FOUND, "TEMPLATE_NOT_CACHED"),
+                OnDemandError::NoMockupTemplate(_) => (StatusCode::NOT_FOUND, "TEMPLATE_NOT_FOUND"),
+                OnDemandError::ProviderNotFound(_) => {
+                    (StatusCode::BAD_REQUEST, "PROVIDER_NOT_FOUND")
+                }
+                OnDemandError::Provider(_) | OnDemandError::Download(_) => {
+                    (StatusCode::BAD_GATEWAY, "PROVIDER_ERROR")
+                }
+                OnDemandError::Storage(_) | OnDemandError::Image(_) => {
+                    (StatusCode::INTERNAL_SERVER_ERROR, "TEMPLATE_LOAD_FAILED")
+                }
+            };
+            return error_response(status, code, e.to_string());
+        }
+    };
+
+    let source = CatalogTemplateSource {
+        source: template.source.as_str().to_string(),
+        source_url: template.source_url,
+        r2_key: template.r2_key,
+    };
+    render
+        .render(
+            &body.design_url,
+            &body.placement,
+            &template.metadata,
+            &template.images,
+            source,
+            Vec::new(),
+        )
+        .await
+}
+
+/// Request body for generating a mockup of a synced catalog product
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GenerateFromProductRequest {
+    /// URL of the design image to composite
+    pub design_url: String,
+    /// Catalog product ID (see `GET /api/v1/catalog/products`)
+    pub product_id: Uuid,
+    /// Catalog variant ID, for variant-specific mockup images
+    #[serde(default)]
+    pub variant_id: Option<Uuid>,
+    /// Print placement on the product (default "front")
+    #[serde(default = "default_print_placement")]
+    pub print_placement: String,
+    /// Placement specification
+    pub placement: PlacementSpec,
+    /// Optional generation options
+    #[serde(default)]
+    pub options: GenerateOptions,
+}
+
+/// POST /api/v1/mockups/generate-from-product - Generate against a synced catalog product
+///
+/// Renders on the product's mockup image mirrored to R2 by the asset sync,
+/// with the print area from the catalog. Provider mockups have no
+/// displacement map, so designs are multiplied flat onto the photo.
+#[utoipa::path(
+    post,
+    path = "/api/v1/mockups/generate-from-product",
+    tag = "mockups",
+    request_body = GenerateFromProductRequest,
+    responses(
+        (status = 200, description = "Mockup generated successfully", body = GenerateFromCatalogResponse),
+        (status = 400, description = "Invalid placement specification", body = ErrorResponse),
+        (status = 403, description = "Design URL is outside the key's allowed design domains (`DESIGN_DOMAIN_NOT_ALLOWED`)", body = ErrorResponse),
+        (status = 404, description = "Unknown product or variant, or no synced mockup image or print area for the placement", body = ErrorResponse),
+        (status = 422, description = "Design URL points at an internal host, or returned an oversized, undersized, or non-PNG/JPEG/WebP image", body = ErrorResponse),
+        (status = 500, description = "Generation or template download failed", body = ErrorResponse),
+        (status = 503, description = "No database configured, no generation slot freed up in time, or the server is shutting down", body = ErrorResponse),
+        (status = 504, description = "Generation ran past its deadline", body = ErrorResponse)
+    )
+)]
+pub async fn generate_from_product(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    body: web::Json<GenerateFromProductRequest>,
+) -> HttpResponse {
+    let start = Instant::now();
+    let print_placement = PrintPlacement::from_str(&body.print_placement);
+
+    info!(
+        product_id = %body.product_id,
+        variant_id = ?body.variant_id,
+        placement = %print_placement,
+        "Processing catalog product mockup generation request"
+    );
+
+    if let Err(response) = ensure_design_domains(&req, [body.design_url.as_str()]) {
+        return response;
+    }
+    let render = match CatalogRender::prepare(&req, &state, &body.options, start).await {
+        Ok(render) => render,
+        Err(response) => return response,
+    };
+
+    let resolved = match state
+        .product_templates
+        .resolve(body.product_id, body.variant_id, &print_placement)
+        .await
+    {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            error!(error = %e, product_id = %body.product_id, "Failed to resolve product template");
+            let (status, code) = match &e {
+                ProductTemplateError::NoDatabase => {
+                    (StatusCode::SERVICE_UNAVAILABLE, "DATABASE_UNAVAILABLE")
+                }
+                ProductTemplateError::ProductNotFound(_) => {
+                    (StatusCode::NOT_FOUND, "PRODUCT_NOT_FOUND")
+                }
+                ProductTemplateError::VariantNotFound { .. } => {
+                    (StatusCode::NOT_FOUND, "VARIANT_NOT_FOUND")
+                }
+                ProductTemplateError::NoMockupImage(_) | ProductTemplateError::NoPrintArea(_) => {
+                    (StatusCode::NOT_FOUND, "TEMPLATE_NOT_FOUND")
+                }
+                ProductTemplateError::Database(_)
+                | ProductTemplateError::Storage(_)
+                | ProductTemplateError::Image(_) => {
+                    (StatusCode::INTERNAL_SERVER_ERROR, "TEMPLATE_LOAD_FAILED")
+                }
+            };
+            return error_response(status, code, e.to_string());
+        }
+    };
+
+    let template = resolved.template;
+    let source = CatalogTemplateSource {
+        source: if resolved.cached { "local_cache" } else { "r2" }.to_string(),
+        source_url: resolved.source_url,
+        r2_key: Some(resolved.r2_key),
+    };
+    render
+        .render(
+            &body.design_url,
+            &body.placement,
+            &template.metadata,
+            &template.images,
+            source,
+            template.warnings,
+        )
+        .await
+}
+
+/// A render against a template resolved outside the template manager
+struct CatalogRender<'a> {
+    req: &'a HttpRequest,
+    state: &'a AppState,
+    options: &'a GenerateOptions,
+    start: Instant,
+    output: OutputSettings,
+    remove_background: Option<BackgroundRemoval>,
+    limits: GenerationLimits,
+    response_mode: ResponseMode,
+}
+
+impl<'a> CatalogRender<'a> {
+    /// Check the request's options before the template is fetched
+    async fn prepare(
+        req: &'a HttpRequest,
+        state: &'a AppState,
+        options: &'a GenerateOptions,
+        start: Instant,
+    ) -> Result<Self, HttpResponse> {
+        let output = options.output_settings(state.settings.output.jpeg_preset)?;
+        let remove_background = options.background_removal()?;
+        let limits =
+            options.generation_limits(&state.settings.server, start, design_domains(req))?;
+        let response_mode = options.response_mode(req);
+        ensure_render_storage(req, state, options, response_mode).await?;
+
+        Ok(CatalogRender {
+            req,
+            state,
+            options,
+            start,
+            output,
+            remove_background,
+            limits,
+            response_mode,
+        })
+    }
+
+    /// Composite the design onto the template, sized to its print area
+    ///
+    /// `warnings` about the template are reported with the mockup.
+    async fn render(
+        mut self,
+        design_url: &str,
+        placement: &PlacementSpec,
+        metadata: &TemplateMetadata,
+        images: &TemplateImages,
+        source: CatalogTemplateSource,
+        mut warnings: Vec<String>,
+    ) -> HttpResponse {
+        let (req, state, options) = (self.req, self.state, self.options);
+        let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+
+        // Size the placement to the template's print area
+        let print_area = &metadata.print_area;
+        let placement = placement.in_print_area(print_area.width, print_area.height);
+
+        if let Err(e) = placement.validate() {
+            error!(error = %e, "Invalid placement specification");
+            return bad_request("INVALID_PLACEMENT", e.to_string());
+        }
+
+        let requested = vec![RequestedLayer::new(
+            DesignSource::Url(design_url.to_string()),
+            placement,
+            options.displacement_strength,
+            None,
+        )];
+        let stats = images.displacement_stats(metadata);
+        let designs = match resolve_displacement(
+            requested,
+            &metadata.displacement,
+            stats.as_ref(),
+            options,
+        ) {
+            Ok((designs, displacement_warnings)) => {
+                warnings.extend(displacement_warnings);
+                designs
+            }
+            Err(response) => return response,
+        };
+
+        let template_id = metadata.id.clone();
+        let _cancel = self.limits.cancel_on_drop();
+        let request = MockupRequest {
+            designs,
+            template_id: template_id.clone(),
+            apply_displacement: options.apply_displacement,
+            tint_color: options.tint_color.clone(),
+            remove_background: self.remove_background,
+            output: self.output,
+            limits: self.limits,
+        };
+        match state
+            .template_manager
+            .generate_with(&request, metadata, images)
+            .await
+        {
+            Ok(result) => {
+                let elapsed = self.start.elapsed().as_millis() as u64;
+
+                info!(
+                    template_id = %template_id,
+                    source = %source.source,
+                    generation_time_ms = elapsed,
+                    "Catalog mockup generated successfully"
+                );
+                RenderUsage::new(Some(template_id.clone()), 1).record(req);
+
+                publish_render_event(
+                    state,
+                    api_key_id,
+                    EventType::RenderCompleted,
+                    serde_json::json!({
+                        "template_id": template_id,
+                        "design_url": design_url,
+                        "generation_time_ms": elapsed,
+                        "width": result.width,
+                        "height": result.height,
+                    }),
+                );
+
+                if self.response_mode == ResponseMode::Binary {
+                    return binary_response(result, elapsed, &template_id, &warnings);
+                }
+
+                let location =
+                    mockup_location(state, &result, options, api_key_id, &template_id, warnings)
+                        .await;
+                HttpResponse::Ok().json(GenerateFromCatalogResponse {
+                    success: true,
+                    mockup_url: location.url,
+                    public_id: location.public_id,
+                    r2_key: location.r2_key,
+                    public_url: location.public_url,
+                    warning: location.warning,
+                    render_id: location.render_id,
+                    metadata: GenerateMetadata {
+                        generation_time_ms: elapsed,
+                        template_used: template_id,
+                        content_type: result.content_type.to_string(),
+                        dimensions: Dimensions {
+                            width: result.width,
+                            height: result.height,
+                        },
+                        print_size: None,
+                    },
+                    template: source,
+                })
+            }
+            Err(
+                e @ TemplateError::Saturated {
+                    retry_after_secs, ..
+                },
+            ) => {
+                warn!(template_id = %template_id, error = %e, "Catalog mockup generation rejected");
+                server_busy(retry_after_secs, e.to_string())
+            }
+            Err(e @ TemplateError::ShuttingDown) => {
+                warn!(template_id = %template_id, "Catalog mockup generation refused during shutdown");
+                server_busy(SHUTDOWN_RETRY_AFTER_SECS, e.to_string())
+            }
+            Err(
+                e @ TemplateError::InvalidDesign {
+                    code: "DESIGN_URL_FORBIDDEN",
+                    ..
                 },
+            ) => {
+                warn!(
+                    api_key_id = ?api_key_id,
+                    design_url = %design_url,
+                    error = %e,
+                    "Refused design URL"
+                );
+                generation_failed(&e)
+            }
+            Err(e) => {
+                error!(error = %e, "Catalog mockup generation failed");
+
+                publish_render_event(
+                    state,
+                    api_key_id,
+                    EventType::RenderFailed,
+                    serde_json::json!({
+                        "template_id": template_id,
+                        "design_url": design_url,
+                        "error": e.to_string(),
+                    }),
+                );
+                generation_failed(&e)
+            }
+        }
+    }
+}
+
+/// Where a JSON response points the caller to the mockup
+struct MockupLocation {
+    url: String,
+    public_id: Option<String>,
+    r2_key: Option<String>,
+    public_url: Option<String>,
+    warning: Option<String>,
+    render_id: Option<Uuid>,
+}
+
+/// Cloudinary URL when an upload was requested, falling back to the data URI,
+/// plus the R2 copy when `store_in_r2` is set
+///
+/// `warnings` from earlier in the request are reported alongside upload warnings.
+/// Failed uploads are handed to the upload queue for background retry. A
+/// deferred R2 upload still reports its key and public URL, which resolve once
+/// the retry lands.
+async fn mockup_location(
+    state: &AppState,
+    result: &MockupResult,
+    options: &GenerateOptions,
+    api_key_id: Option<Uuid>,
+    template_id: &str,
+    mut warnings: Vec<String>,
+) -> MockupLocation {
+    let mut failed = Vec::new();
+    let mut location = MockupLocation {
+        url: result.data_uri(),
+        public_id: None,
+        r2_key: None,
+        public_url: None,
+        warning: None,
+        render_id: None,
+    };
+
+    if options.upload {
+        match &state.cloudinary {
+            Some(cloudinary) => match cloudinary.upload(location.url.clone()).await {
+                Ok(uploaded) => {
+                    location.url = uploaded.secure_url;
+                    location.public_id = Some(uploaded.public_id);
+                }
+                Err(e) => {
+                    warn!(error = %e, "Cloudinary upload failed, returning data URI");
+                    warnings.push(format!("Cloudinary upload failed: {}", e));
+                    failed.push(FailedUpload {
+                        target: UploadTarget::Cloudinary,
+                        r2_key: None,
+                        error: e.to_string(),
+                    });
+                }
+            },
+            None => warnings.push("Cloudinary is not configured; returning a data URI".to_string()),
+        }
+    }
+
+    if options.store_in_r2 {
+        match &state.storage {
+            Some(storage) => {
+                let path = AssetPath::generated(
+                    Utc::now().date_naive(),
+                    Uuid::new_v4(),
+                    options.output_format.extension(),
+                );
+                match storage
+                    .upload(&path, result.bytes.to_vec(), result.content_type)
+                    .await
+                {
+                    Ok(stored) => {
+                        location.r2_key = Some(stored.key);
+                        location.public_url = stored.public_url;
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Storing generated mockup failed");
+                        warnings.push(format!("Storage upload failed: {}", e));
+                        failed.push(FailedUpload {
+                            target: UploadTarget::R2,
+                            r2_key: Some(path.to_key()),
+                            error: e.to_string(),
+                        });
+                    }
+                }
+            }
+            None => warnings.push("Storage is not configured; mockup not stored".to_string()),
+        }
+    }
+
+    let deferred_r2_key = failed
+        .iter()
+        .find(|upload| upload.target == UploadTarget::R2)
+        .and_then(|upload| upload.r2_key.clone());
+    if !failed.is_empty() {
+        location.render_id = state.uploads.defer(
+            api_key_id,
+            template_id,
+            result.content_type,
+            result.bytes.clone(),
+            failed,
+        );
+        if location.render_id.is_some() {
+            warnings.push("failed uploads will be retried in the background".to_string());
+            if let (Some(storage), Some(key)) = (&state.storage, deferred_r2_key) {
+                location.public_url = storage.public_url(&key);
+                location.r2_key = Some(key);
+            }
+        }
+    }
+
+    if let Some(ref key) = location.r2_key {
+        record_saved_render(state, api_key_id, key).await;
+    }
+
+    if !warnings.is_empty() {
+        location.warning = Some(warnings.join("; "));
+    }
+    location
+}
+
+/// Raw image response; the encoded bytes are sent as-is with an exact Content-Length
+fn binary_response(
+    result: MockupResult,
+    elapsed: u64,
+    template_id: &str,
+    warnings: &[String],
+) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    if !warnings.is_empty() {
+        response.insert_header(("X-Mockup-Warning", warnings.join("; ")));
+    }
+    response
+        .content_type(result.content_type)
+        .insert_header(("X-Mockup-Width", result.width.to_string()))
+        .insert_header(("X-Mockup-Height", result.height.to_string()))
+        .insert_header(("X-Generation-Time-Ms", elapsed.to_string()))
+        .insert_header(("X-Template-Used", template_id.to_string()))
+        // Images are already compressed; skip the Compress middleware
+        .insert_header(header::ContentEncoding::Identity)
+        .body(result.bytes)
+}
+
+/// Notify the requesting key's webhooks about a render outcome
+pub(crate) fn publish_render_event(
+    state: &AppState,
+    api_key_id: Option<Uuid>,
+    event_type: EventType,
+    data: serde_json::Value,
+) {
+    if let (Some(webhooks), Some(key_id)) = (&state.webhooks, api_key_id) {
+        webhooks.publish(Some(key_id), event_type, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::DisplacementMode;
+
+    #[test]
+    fn test_render_usage_only_counts_successful_renders() {
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        record_render_usage(&req, "tee", server_busy(10, "busy".to_string()));
+        assert!(req.extensions().get::<RenderUsage>().is_none());
+
+        record_render_usage(&req, "tee", HttpResponse::Ok().finish());
+        assert_eq!(
+            req.extensions().get::<RenderUsage>(),
+            Some(&RenderUsage::new(Some("tee".to_string()), 1))
+        );
+    }
+
+    fn key_with_domains(domains: &[&str]) -> ApiKeyAuth {
+        ApiKeyAuth {
+            key_id: Uuid::nil(),
+            tier: "pro".to_string(),
+            rate_limit: 100,
+            monthly_quota: 10000,
+            owner_email: "domains@example.com".to_string(),
+            allowed_design_domains: domains.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_design_domains_limit_restricted_keys() {
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        req.extensions_mut()
+            .insert(key_with_domains(&["cdn.acme.com"]));
+
+        for url in [
+            "https://cdn.acme.com/a.png",
+            "https://eu.cdn.acme.com/a.png",
+            "https://CDN.ACME.com/a.png",
+        ] {
+            assert!(ensure_design_domains(&req, [url]).is_ok(), "{url}");
+        }
+        for url in ["https://evil.example/a.png", "http://203.0.114.10/a.png"] {
+            let response =
+                ensure_design_domains(&req, ["https://cdn.acme.com/a.png", url]).unwrap_err();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_design_domains_without_restriction() {
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        assert!(ensure_design_domains(&req, ["http://203.0.114.10/a.png"]).is_ok());
+
+        req.extensions_mut().insert(key_with_domains(&[]));
+        assert!(ensure_design_domains(&req, ["https://anywhere.example/a.png"]).is_ok());
+    }
+
+    #[test]
+    fn test_server_busy_sets_retry_after() {
+        let response = server_busy(10, "busy".to_string());
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "10");
+    }
+
+    #[actix_web::test]
+    async fn test_error_envelope_carries_request_id() {
+        use crate::api::middleware::AssignRequestId;
+        use actix_web::{test, App};
+
+        let app = test::init_service(App::new().wrap(AssignRequestId).route(
+            "/fail",
+            web::get().to(|| async { bad_request("INVALID_REQUEST", "nope".to_string()) }),
+        ))
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/fail")
+            .insert_header(("X-Request-Id", "req-123"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["request_id"], "req-123");
+        assert_eq!(body["error"]["code"], "INVALID_REQUEST");
+
+        // Outside a request there's no ID to report
+        let response = serde_json::to_value(ErrorResponse {
+            success: false,
+            request_id: RequestId::current(),
+            error: ApiError {
+                code: "X".to_string(),
+                message: "x".to_string(),
+            },
+        })
+        .unwrap();
+        assert!(response.get("request_id").is_none());
+    }
+
+    #[test]
+    fn test_timed_out_generation_is_a_gateway_timeout() {
+        let timed_out = TemplateError::TimedOut("deadline passed before compositing".to_string());
+        assert_eq!(
+            generation_failed(&timed_out).status(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        let failed = TemplateError::MetadataLoad("bad".to_string());
+        assert_eq!(
+            generation_failed(&failed).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn test_invalid_design_is_unprocessable() {
+        let too_small = TemplateError::InvalidDesign {
+            code: "DESIGN_TOO_SMALL",
+            message: "Design image is 1x1; the smallest accepted is 50x50".to_string(),
+        };
+        let response = generation_failed(&too_small);
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn test_fetch_timeout_capped_at_server_max() {
+        let server = crate::config::Settings::default().server;
+        let options = |fetch_timeout_ms| GenerateOptions {
+            fetch_timeout_ms,
+            ..GenerateOptions::default()
+        };
+
+        assert_eq!(
+            options(None).fetch_timeout(&server).unwrap(),
+            server.max_fetch_timeout()
+        );
+        assert_eq!(
+            options(Some(250)).fetch_timeout(&server).unwrap(),
+            Duration::from_millis(250)
+        );
+        assert_eq!(
+            options(Some(u64::MAX)).fetch_timeout(&server).unwrap(),
+            server.max_fetch_timeout()
+        );
+        assert!(options(Some(0)).fetch_timeout(&server).is_err());
+    }
+
+    #[test]
+    fn test_response_mode_for_accept() {
+        assert_eq!(response_mode_for_accept(None), ResponseMode::Json);
+        assert_eq!(
+            response_mode_for_accept(Some("application/json")),
+            ResponseMode::Json
+        );
+        assert_eq!(
+            response_mode_for_accept(Some("Image/WebP;q=0.9, application/json")),
+            ResponseMode::Binary
+        );
+        assert_eq!(
+            response_mode_for_accept(Some("application/json, image/png")),
+            ResponseMode::Json
+        );
+        assert_eq!(response_mode_for_accept(Some("*/*")), ResponseMode::Json);
+    }
+
+    #[test]
+    fn test_background_removal_mode() {
+        let mode = |json: &str| {
+            let options: GenerateOptions = serde_json::from_str(json).unwrap();
+            options
+                .background_removal()
+                .unwrap()
+                .map(|removal| removal.mode)
+        };
+        assert_eq!(mode("{}"), None);
+        assert_eq!(
+            mode(r#"{"remove_background": true}"#),
+            Some(BackgroundRemovalMode::Luminance)
+        );
+        assert_eq!(
+            mode(r#"{"background_removal_mode": "border_flood"}"#),
+            Some(BackgroundRemovalMode::BorderFlood)
+        );
+        assert_eq!(
+            mode(r#"{"remove_background": true, "background_removal_mode": "none"}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_response_mode_defaults_to_json() {
+        let options: GenerateOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options.response_mode, None);
+
+        let options: GenerateOptions =
+            serde_json::from_str(r#"{"response_mode": "binary"}"#).unwrap();
+        assert_eq!(options.response_mode, Some(ResponseMode::Binary));
+    }
+
+    fn parse_request(value: serde_json::Value) -> GenerateRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn centered() -> serde_json::Value {
+        serde_json::json!({"scale": 0.4, "offset_x": 0, "offset_y": 0})
+    }
+
+    #[test]
+    fn test_design_layers_from_either_shape() {
+        let single = parse_request(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "tee",
+            "placement": centered(),
+            "options": {"displacement_strength": 5},
+        }));
+        let layers = single.design_layers().unwrap();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].displacement_strength, Some(5.0));
+        assert!(layers[0].layer.blend_mode.is_none());
+
+        let multiple = parse_request(serde_json::json!({
+            "template_id": "tee",
+            "options": {"displacement_strength": 5},
+            "designs": [
+                {"design_url": "https://example.com/a.png", "placement": centered()},
+                {
+                    "design_url": "https://example.com/b.png",
+                    "placement": {"scale": 0.2, "offset_x": 300, "offset_y": -400},
+                    "displacement_strength": 0,
+                    "blend_mode": "normal",
+                },
+            ],
+        }));
+        let layers = multiple.design_layers().unwrap();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].displacement_strength, Some(5.0));
+        assert_eq!(layers[1].displacement_strength, Some(0.0));
+        assert_eq!(layers[1].layer.blend_mode.as_deref(), Some("normal"));
+    }
+
+    fn displacement() -> DisplacementConfig {
+        DisplacementConfig {
+            enabled: true,
+            strength_default: 8.0,
+            strength_range: (4.0, 16.0),
+            mode: DisplacementMode::Uniform,
+        }
+    }
+
+    fn requested(strengths: &[Option<f64>]) -> Vec<RequestedLayer> {
+        strengths
+            .iter()
+            .map(|&strength| {
+                RequestedLayer::new(
+                    DesignSource::Url("https://example.com/a.png".to_string()),
+                    PlacementSpec::default(),
+                    strength,
+                    None,
+                )
             })
+            .collect()
+    }
+
+    fn options(json: serde_json::Value) -> GenerateOptions {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_displacement_defaults_and_clamps() {
+        let (layers, warnings) = resolve_displacement(
+            requested(&[None, Some(2.0), Some(50.0)]),
+            &displacement(),
+            None,
+            &options(serde_json::json!({})),
+        )
+        .unwrap();
+        let strengths: Vec<f64> = layers.iter().map(|l| l.displacement_strength).collect();
+        assert_eq!(strengths, vec![8.0, 4.0, 16.0]);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("designs[1]: "));
+        assert!(warnings[1].starts_with("designs[2]: "));
+    }
+
+    #[test]
+    fn test_resolve_displacement_strict_rejects_out_of_range() {
+        let strict = options(serde_json::json!({"strict_displacement": true}));
+        let resolve = |strength: f64, displacement: &DisplacementConfig| {
+            resolve_displacement(requested(&[Some(strength)]), displacement, None, &strict)
+        };
+        let response = resolve(50.0, &displacement()).unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(resolve(2.0, &displacement()).is_err());
+        assert!(resolve(12.0, &displacement()).is_ok());
+
+        let mut disabled = displacement();
+        disabled.enabled = false;
+        let (layers, warnings) = resolve(50.0, &disabled).unwrap();
+        assert_eq!(layers[0].displacement_strength, 50.0);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_displacement_realism() {
+        let stats = DisplacementStats {
+            mean_deviation: 0.1,
+            p95_deviation: 0.25,
+        };
+        let realism = options(serde_json::json!({"realism": 0.5}));
+        let (layers, _) = resolve_displacement(
+            requested(&[None, Some(5.0)]),
+            &displacement(),
+            Some(&stats),
+            &realism,
+        )
+        .unwrap();
+        // 0.5 of the full-realism shift over a 0.25 p95 deviation; explicit strengths win
+        assert!((layers[0].displacement_strength - 8.0).abs() < 1e-9);
+        assert_eq!(layers[1].displacement_strength, 5.0);
+
+        let out_of_range = options(serde_json::json!({"realism": 1.5}));
+        assert!(
+            resolve_displacement(requested(&[None]), &displacement(), None, &out_of_range).is_err()
+        );
+    }
+
+    #[test]
+    fn test_design_layers_omitted_strength() {
+        let request = parse_request(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "tee",
+            "placement": centered(),
+        }));
+        let layers = request.design_layers().unwrap();
+        assert_eq!(layers[0].displacement_strength, None);
+    }
+
+    #[test]
+    fn test_design_layers_rejects_mixed_or_invalid_shapes() {
+        let design = serde_json::json!({
+            "design_url": "https://example.com/b.png",
+            "placement": centered(),
+        });
+
+        let missing = parse_request(serde_json::json!({"template_id": "tee"}));
+        assert!(missing.design_layers().is_err());
+
+        let partial = parse_request(serde_json::json!({
+            "template_id": "tee",
+            "design_url": "https://example.com/a.png",
+        }));
+        assert!(partial.design_layers().is_err());
+
+        let both = parse_request(serde_json::json!({
+            "template_id": "tee",
+            "design_url": "https://example.com/a.png",
+            "placement": centered(),
+            "designs": [design.clone()],
+        }));
+        assert!(both.design_layers().is_err());
+
+        let mut blended = design;
+        blended["blend_mode"] = "dodge".into();
+        let bad_blend = parse_request(serde_json::json!({
+            "template_id": "tee",
+            "designs": [blended],
+        }));
+        assert!(bad_blend.design_layers().is_err());
+    }
+
+    fn tee_template() -> TemplateMetadata {
+        let mut template = TemplateMetadata::from_provider_mockup(
+            "tee",
+            "front",
+            crate::engine::TemplateDimensions {
+                width: 2000,
+                height: 2600,
+            },
+            crate::engine::PrintArea {
+                x: 100,
+                y: 100,
+                width: 1800,
+                height: 2400,
+            },
+        );
+        template.displacement = displacement();
+        template
+    }
+
+    /// JSON pointer, invalid value, and the field and code it is reported under
+    type FieldCase = (&'static str, serde_json::Value, &'static str, &'static str);
+
+    /// A valid multi-design request, and one invalid field at a time with the error it causes
+    fn invalid_field_cases() -> (serde_json::Value, Vec<FieldCase>) {
+        let valid = serde_json::json!({
+            "template_id": "tee",
+            "options": {"strict_displacement": true},
+            "designs": [{
+                "design_url": "https://example.com/a.png",
+                "placement": centered(),
+                "displacement_strength": 8,
+                "blend_mode": "multiply",
+            }],
+        });
+        let cases = vec![
+            (
+                "/template_id",
+                serde_json::json!(" "),
+                "template_id",
+                "required",
+            ),
+            (
+                "/designs/0/design_url",
+                serde_json::json!("ftp://example.com/a.png"),
+                "designs[0].design_url",
+                "invalid_url",
+            ),
+            (
+                "/designs/0/placement/scale",
+                serde_json::json!(1.5),
+                "designs[0].placement.scale",
+                "out_of_range",
+            ),
+            (
+                "/designs/0/placement/offset_x",
+                serde_json::json!(900),
+                "designs[0].placement.offset_x",
+                "out_of_bounds",
+            ),
+            (
+                "/designs/0/blend_mode",
+                serde_json::json!("dodge"),
+                "designs[0].blend_mode",
+                "invalid_value",
+            ),
+            (
+                "/designs/0/displacement_strength",
+                serde_json::json!(50.0),
+                "designs[0].displacement_strength",
+                "out_of_range",
+            ),
+            (
+                "/options/realism",
+                serde_json::json!(1.5),
+                "options.realism",
+                "out_of_range",
+            ),
+            (
+                "/options/quality",
+                serde_json::json!(0),
+                "options.quality",
+                "out_of_range",
+            ),
+            (
+                "/options/fetch_timeout_ms",
+                serde_json::json!(0),
+                "options.fetch_timeout_ms",
+                "out_of_range",
+            ),
+        ];
+        (valid, cases)
+    }
+
+    fn set(request: &mut serde_json::Value, pointer: &str, value: serde_json::Value) {
+        let (parent, key) = pointer.rsplit_once('/').unwrap();
+        request.pointer_mut(parent).unwrap()[key] = value;
+    }
+
+    #[test]
+    fn test_validate_reports_each_invalid_field() {
+        let template = tee_template();
+        let (valid, cases) = invalid_field_cases();
+        assert!(parse_request(valid.clone())
+            .validate(Some(&template))
+            .is_empty());
+
+        for (pointer, value, field, code) in cases {
+            let mut request = valid.clone();
+            set(&mut request, pointer, value.clone());
+            let errors = parse_request(request).validate(Some(&template)).fields;
+            assert_eq!(errors.len(), 1, "{}: {:?}", pointer, errors);
+            assert_eq!(errors[0].field, field);
+            assert_eq!(errors[0].code, code, "{}", field);
+            if code != "required" {
+                assert_eq!(errors[0].value.as_ref(), Some(&value), "{}", field);
+            }
         }
     }
+
+    #[test]
+    fn test_validate_reports_every_invalid_field_together() {
+        let template = tee_template();
+        let (mut request, cases) = invalid_field_cases();
+        // Scale is checked before bounds, so it would hide the offset error
+        let cases: Vec<_> = cases
+            .into_iter()
+            .filter(|(pointer, ..)| !pointer.ends_with("scale"))
+            .collect();
+        for (pointer, value, ..) in &cases {
+            set(&mut request, pointer, value.clone());
+        }
+        let request = parse_request(request);
+        let errors = request.validate(Some(&template));
+        let fields: Vec<_> = errors.fields.iter().map(|e| e.field.as_str()).collect();
+        let expected: Vec<_> = cases.iter().map(|(_, _, field, _)| *field).collect();
+        assert_eq!(fields, expected);
+
+        // The handler answers before any design is fetched or composited
+        let response = errors.into_result().unwrap_err();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn test_validate_request_shape() {
+        let codes = |value: serde_json::Value| -> Vec<(String, String)> {
+            parse_request(value)
+                .validate(None)
+                .fields
+                .into_iter()
+                .map(|e| (e.field, e.code))
+                .collect()
+        };
+        let pair = |field: &str, code: &str| vec![(field.to_string(), code.to_string())];
+
+        assert_eq!(
+            codes(serde_json::json!({"template_id": "tee"})),
+            pair("design_url", "required")
+        );
+        assert_eq!(
+            codes(
+                serde_json::json!({"template_id": "tee", "design_url": "https://example.com/a.png"})
+            ),
+            pair("placement", "required")
+        );
+        let design =
+            serde_json::json!({"design_url": "https://example.com/b.png", "placement": centered()});
+        assert_eq!(
+            codes(serde_json::json!({
+                "template_id": "tee",
+                "design_url": "https://example.com/a.png",
+                "placement": centered(),
+                "designs": [design.clone()],
+            })),
+            pair("designs", "conflict")
+        );
+        let designs = vec![design; MAX_DESIGNS + 1];
+        assert_eq!(
+            codes(serde_json::json!({"template_id": "tee", "designs": designs})),
+            pair("designs", "too_many")
+        );
+    }
+
+    #[test]
+    fn test_validate_without_template_skips_template_checks() {
+        // Off-center and over-strength, but an unknown template is answered with a 404 later
+        let request = parse_request(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "missing",
+            "placement": {"scale": 0.4, "offset_x": 900, "offset_y": 0},
+            "options": {"displacement_strength": 50, "strict_displacement": true},
+        }));
+        assert!(request.validate(None).is_empty());
+
+        let errors = request.validate(Some(&tee_template())).fields;
+        let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["placement.offset_x", "options.displacement_strength"]
+        );
+    }
+
+    #[test]
+    fn test_lenient_displacement_strength_is_clamped_not_rejected() {
+        let request = parse_request(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "tee",
+            "placement": centered(),
+            "options": {"displacement_strength": 50},
+        }));
+        assert!(request.validate(Some(&tee_template())).is_empty());
+    }
+
+    #[test]
+    fn test_physical_placement_resolves_against_the_template() {
+        let mut template = tee_template();
+        template.print_dpi = Some(150);
+        let mut request = parse_request(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "tee",
+            "physical_placement": {"width_inches": 6, "offset_y_inches": -2},
+        }));
+        assert!(request.validate(Some(&template)).is_empty());
+
+        let print_size = request.resolve_physical_placement(&template).unwrap();
+        assert_eq!(print_size.physical.dpi, Some(150));
+        assert_eq!(print_size.pixels.scale, 0.5);
+        assert_eq!(print_size.pixels.offset_y, -300);
+        assert_eq!(print_size.design_dimensions.width, 900);
+        assert_eq!(print_size.design_dimensions.height, 1200);
+
+        // The converted placement feeds the usual single-design shape
+        assert!(request.physical_placement.is_none());
+        let layers = request.design_layers().unwrap();
+        assert_eq!(layers[0].layer.placement.offset_y, -300);
+    }
+
+    #[test]
+    fn test_validate_physical_placement() {
+        let template = tee_template();
+        let codes = |value: serde_json::Value| -> Vec<(String, String)> {
+            parse_request(value)
+                .validate(Some(&template))
+                .fields
+                .into_iter()
+                .map(|e| (e.field, e.code))
+                .collect()
+        };
+        let pair = |field: &str, code: &str| vec![(field.to_string(), code.to_string())];
+
+        // 1800 px at the default 300 DPI fits 6 in
+        assert_eq!(
+            codes(serde_json::json!({
+                "design_url": "https://example.com/a.png",
+                "template_id": "tee",
+                "physical_placement": {"width_inches": 6.5},
+            })),
+            pair("physical_placement.width_inches", "out_of_bounds")
+        );
+        assert_eq!(
+            codes(serde_json::json!({
+                "design_url": "https://example.com/a.png",
+                "template_id": "tee",
+                "placement": centered(),
+                "physical_placement": {"width_inches": 3},
+            })),
+            pair("physical_placement", "conflict")
+        );
+    }
+
+    #[test]
+    fn test_preset_resolves_against_the_template() {
+        let template = tee_template();
+        let mut request = parse_request(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "tee",
+            "preset": "left_chest",
+        }));
+        assert!(request.validate(Some(&template)).is_empty());
+
+        request.resolve_preset(&template);
+        let layers = request.design_layers().unwrap();
+        assert_eq!(
+            layers[0].layer.placement.get_absolute_position(),
+            (1080, 240)
+        );
+
+        let unknown = parse_request(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "tee",
+            "preset": "sleeve",
+        }));
+        let errors = unknown.validate(Some(&template)).fields;
+        assert_eq!(errors[0].field, "preset");
+        assert_eq!(errors[0].code, "invalid_value");
+        assert!(errors[0].message.contains("left_chest"));
+
+        let both = parse_request(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "tee",
+            "placement": centered(),
+            "preset": "left_chest",
+        }));
+        let errors = both.validate(Some(&template)).fields;
+        assert_eq!(
+            (errors[0].field.as_str(), errors[0].code.as_str()),
+            ("preset", "conflict")
+        );
+    }
+
+    #[test]
+    fn test_low_resolution_warnings_name_each_design() {
+        assert!(low_resolution_warnings(&[300.0], 150).is_empty());
+
+        let warnings = low_resolution_warnings(&[120.4], 150);
+        assert_eq!(
+            warnings,
+            vec!["Design prints at 120 DPI, below the template's minimum of 150 DPI"]
+        );
+
+        let warnings = low_resolution_warnings(&[300.0, 149.9, 100.0], 150);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("designs[1]: "));
+        assert!(warnings[1].starts_with("designs[2]: "));
+    }
+
+    #[test]
+    fn test_print_file_request_accepts_generate_fields() {
+        let request: PrintFileRequest = serde_json::from_value(serde_json::json!({
+            "design_url": "https://example.com/a.png",
+            "template_id": "tee",
+            "preset": "left_chest",
+            "strict_resolution": true,
+        }))
+        .unwrap();
+        assert!(request.strict_resolution);
+        assert_eq!(request.request.preset.as_deref(), Some("left_chest"));
+        assert!(request.request.validate(Some(&tee_template())).is_empty());
+    }
 }
@@ -1,18 +1,35 @@
-//! Health check endpoint
+//! Health check endpoints
+//!
+//! `/health/live` only shows the process is serving requests. `/health/ready`
+//! checks the dependencies a request needs and fails when a critical one is
+//! down, so orchestrators stop routing to an instance that can't serve.
 
 use actix_web::{web, HttpResponse};
 use serde::Serialize;
-use std::time::SystemTime;
+use std::future::Future;
+use std::time::{Duration, Instant, SystemTime};
 use utoipa::ToSchema;
 
+use crate::db::PoolStatus;
 use crate::AppState;
 
+/// Object looked up by the storage check; a missing object still proves the backend answers
+const STORAGE_PROBE_KEY: &str = "health/ready";
+
 #[derive(Serialize, ToSchema)]
 pub struct HealthResponse {
     pub status: &'static str,
     pub version: &'static str,
     pub uptime_seconds: u64,
     pub templates_loaded: usize,
+    /// Template directories that failed to load or validate
+    pub templates_failed: usize,
+    /// Mockups being generated right now
+    pub generations_in_flight: usize,
+    /// Generations waiting for a free slot
+    pub generations_queued: usize,
+    /// Generations allowed to run at once
+    pub max_concurrent_generations: usize,
 }
 
 /// GET /health - Health check endpoint
@@ -20,8 +37,10 @@ pub struct HealthResponse {
     get,
     path = "/health",
     tag = "system",
+    security(()),
     responses(
-        (status = 200, description = "Service is healthy", body = HealthResponse)
+        (status = 200, description = "Service is healthy", body = HealthResponse),
+        (status = 503, description = "Service is draining for shutdown", body = HealthResponse)
     )
 )]
 pub async fn health_check(state: web::Data<AppState>) -> HttpResponse {
@@ -30,13 +49,223 @@ pub async fn health_check(state: web::Data<AppState>) -> HttpResponse {
         .duration_since(SystemTime::UNIX_EPOCH)
         .map(|d| d.as_secs() % 86400) // Wrap at 24 hours for demo
         .unwrap_or(0);
+    let generations = state.template_manager.generation_load();
+
+    // Failing the check takes a draining instance out of the load balancer
+    let draining = state.shutdown.is_draining();
 
     let response = HealthResponse {
-        status: "healthy",
+        status: if draining { "draining" } else { "healthy" },
         version: env!("CARGO_PKG_VERSION"),
         uptime_seconds: uptime,
         templates_loaded: state.template_manager.template_count(),
+        templates_failed: state.template_manager.load_report().failed.len(),
+        generations_in_flight: generations.in_flight,
+        generations_queued: generations.queued,
+        max_concurrent_generations: generations.max_concurrent,
+    };
+
+    if draining {
+        HttpResponse::ServiceUnavailable().json(response)
+    } else {
+        HttpResponse::Ok().json(response)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LivenessResponse {
+    pub status: &'static str,
+}
+
+/// GET /health/live - Liveness probe
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "system",
+    security(()),
+    responses(
+        (status = 200, description = "Process is serving requests", body = LivenessResponse)
+    )
+)]
+pub async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().json(LivenessResponse { status: "alive" })
+}
+
+/// Result of checking one dependency
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DependencyCheck {
+    pub name: &'static str,
+    /// Whether a failure makes the instance not ready
+    pub critical: bool,
+    /// `ok` or `failed`
+    pub status: &'static str,
+    pub response_time_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyCheck {
+    fn failed(&self) -> bool {
+        self.status == "failed"
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// `ready`, `not_ready`, or `draining` during shutdown
+    pub status: &'static str,
+    /// Checked dependencies; unconfigured ones are left out
+    pub checks: Vec<DependencyCheck>,
+    /// Database connections after the checks, when a database is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database_pool: Option<PoolStatus>,
+}
+
+/// Run `check`, failing it if it takes longer than `timeout`
+async fn run_check<F>(
+    name: &'static str,
+    critical: bool,
+    timeout: Duration,
+    check: F,
+) -> DependencyCheck
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let started = Instant::now();
+    let result = tokio::time::timeout(timeout, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {}ms", timeout.as_millis())));
+
+    DependencyCheck {
+        name,
+        critical,
+        status: if result.is_ok() { "ok" } else { "failed" },
+        response_time_ms: started.elapsed().as_millis() as u64,
+        error: result.err(),
+    }
+}
+
+/// Overall readiness status for the checks
+fn readiness_status(draining: bool, checks: &[DependencyCheck]) -> &'static str {
+    if draining {
+        "draining"
+    } else if checks.iter().any(|c| c.critical && c.failed()) {
+        "not_ready"
+    } else {
+        "ready"
+    }
+}
+
+/// GET /health/ready - Readiness probe with per-dependency checks
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "system",
+    security(()),
+    responses(
+        (status = 200, description = "Every critical dependency is available", body = ReadinessResponse),
+        (status = 503, description = "A critical dependency failed, or the service is draining", body = ReadinessResponse)
+    )
+)]
+pub async fn readiness(state: web::Data<AppState>) -> HttpResponse {
+    let timeout = state.settings.server.ready_check_timeout();
+
+    let templates = run_check("templates", true, timeout, async {
+        match state.template_manager.template_count() {
+            0 => Err("no templates loaded".to_string()),
+            _ => Ok(()),
+        }
+    });
+    let database = async {
+        match state.db_pool {
+            Some(ref pool) => Some(
+                run_check("database", true, timeout, async {
+                    pool.ping().await.map_err(|e| e.to_string())
+                })
+                .await,
+            ),
+            None => None,
+        }
+    };
+    let storage = async {
+        match state.storage {
+            Some(ref storage) => Some(
+                run_check(
+                    state.settings.storage.backend.as_str(),
+                    state.settings.server.ready_requires_r2,
+                    timeout,
+                    async {
+                        storage
+                            .exists(STORAGE_PROBE_KEY)
+                            .await
+                            .map(drop)
+                            .map_err(|e| e.to_string())
+                    },
+                )
+                .await,
+            ),
+            None => None,
+        }
+    };
+    let (templates, database, storage) = futures::join!(templates, database, storage);
+
+    let checks: Vec<DependencyCheck> = std::iter::once(templates)
+        .chain(database)
+        .chain(storage)
+        .collect();
+    let status = readiness_status(state.shutdown.is_draining(), &checks);
+    let response = ReadinessResponse {
+        status,
+        checks,
+        database_pool: state.db_pool.as_ref().map(|pool| pool.status()),
     };
 
-    HttpResponse::Ok().json(response)
+    if status == "ready" {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_fails_on_timeout() {
+        let check = run_check("slow", true, Duration::from_millis(10), async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        })
+        .await;
+        assert!(check.failed());
+        assert_eq!(check.error.as_deref(), Some("timed out after 10ms"));
+
+        let check = run_check("fast", true, Duration::from_secs(1), async { Ok(()) }).await;
+        assert_eq!(check.status, "ok");
+        assert!(check.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_only_critical_failures_fail_readiness() {
+        let timeout = Duration::from_secs(1);
+        let optional_down = vec![
+            run_check("templates", true, timeout, async { Ok(()) }).await,
+            run_check("r2", false, timeout, async {
+                Err("unreachable".to_string())
+            })
+            .await,
+        ];
+        assert_eq!(readiness_status(false, &optional_down), "ready");
+        assert_eq!(readiness_status(true, &optional_down), "draining");
+
+        let critical_down = vec![
+            run_check("templates", true, timeout, async { Ok(()) }).await,
+            run_check("database", true, timeout, async {
+                Err("refused".to_string())
+            })
+            .await,
+        ];
+        assert_eq!(readiness_status(false, &critical_down), "not_ready");
+    }
 }
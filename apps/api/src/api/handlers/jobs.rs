@@ -0,0 +1,112 @@
+//! Job output download and async render status endpoints
+
+use actix_web::{
+    http::header::{ContentDisposition, ContentEncoding, DispositionParam, DispositionType},
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
+use futures::StreamExt;
+use uuid::Uuid;
+
+use crate::api::middleware::ApiKeyAuth;
+use crate::jobs::RenderJob;
+use crate::storage::zip_stream;
+use crate::AppState;
+
+/// Stream a job's outputs as a ZIP archive
+/// GET /api/v1/jobs/{id}/download
+///
+/// Files are fetched and written one at a time. Content-Length is set when
+/// every file's size is known up front; otherwise the response is chunked.
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}/download",
+    tag = "mockups",
+    params(
+        ("id" = Uuid, Path, description = "Batch job ID")
+    ),
+    responses(
+        (status = 200, description = "ZIP archive of the job's outputs", content_type = "application/zip"),
+        (status = 404, description = "Job not found or its outputs have expired")
+    )
+)]
+pub async fn download_job(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+
+    // Jobs belong to the key that created them; anonymous jobs only exist without auth
+    let outputs = match state.jobs.get(id) {
+        Some(outputs) if outputs.api_key_id == api_key_id => outputs,
+        _ => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "not_found",
+                "message": "Job not found or its outputs have expired"
+            }));
+        }
+    };
+
+    let archive_len = outputs.archive_len();
+    let filename = format!("{}-{}.zip", outputs.kind, outputs.id);
+    let modified = outputs.created_at;
+
+    let jobs = state.jobs.clone();
+    let entries = futures::stream::iter(0..outputs.files.len())
+        .then(move |index| {
+            let jobs = jobs.clone();
+            let outputs = outputs.clone();
+            async move { jobs.open(&outputs.files[index]).await }
+        })
+        .boxed();
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type("application/zip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        // Entries are already-compressed images; keep the length exact
+        .insert_header(ContentEncoding::Identity);
+    if let Some(len) = archive_len {
+        response.no_chunking(len);
+    }
+
+    response.streaming(zip_stream(entries, modified))
+}
+
+/// GET /api/v1/mockups/jobs/{id} - Status of an async generation
+///
+/// Finished jobs carry the generate response or error until their retention
+/// window passes, and can be read any number of times until then.
+#[utoipa::path(
+    get,
+    path = "/api/v1/mockups/jobs/{id}",
+    tag = "mockups",
+    params(
+        ("id" = Uuid, Path, description = "Job ID returned by an async generate request")
+    ),
+    responses(
+        (status = 200, description = "Job status, with the result or error once finished", body = RenderJob),
+        (status = 404, description = "Job not found or its result has expired")
+    )
+)]
+pub async fn get_render_job(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+
+    // Jobs belong to the key that created them, like job outputs
+    match state.render_jobs.get(id, api_key_id).await {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "not_found",
+            "message": "Job not found or its result has expired"
+        })),
+    }
+}
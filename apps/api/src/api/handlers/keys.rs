@@ -5,15 +5,22 @@
 
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use crate::api::handlers::catalog::{default_page, default_per_page, PaginatedResponse};
 use crate::api::middleware::ApiKeyAuth;
-use crate::db::{ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest, DbPool};
+use crate::db::{
+    ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest, DbApiKey, DbPool, KeyEvent, KeyEventType,
+    PageRequest, UpdateApiKeyRequest,
+};
+use crate::webhooks::validate_webhook_url;
+use crate::AppState;
 
 /// Request to create a new API key
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateKeyRequest {
     pub name: String,
     pub owner_email: String,
@@ -21,7 +28,9 @@ pub struct CreateKeyRequest {
     pub owner_name: Option<String>,
     #[serde(default)]
     pub company: Option<String>,
+    /// free (default), starter, pro, or enterprise
     #[serde(default = "default_tier")]
+    #[schema(default = "free")]
     pub tier: String,
     #[serde(default)]
     pub rate_limit_per_minute: Option<i32>,
@@ -29,6 +38,9 @@ pub struct CreateKeyRequest {
     pub monthly_quota: Option<i32>,
     #[serde(default)]
     pub expires_at: Option<DateTime<Utc>>,
+    /// Domains design URLs must be hosted on, subdomains included; omit to allow any host
+    #[serde(default)]
+    pub allowed_design_domains: Option<Vec<String>>,
 }
 
 fn default_tier() -> String {
@@ -36,7 +48,7 @@ fn default_tier() -> String {
 }
 
 /// Response after creating a new API key
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CreateKeyResponse {
     pub id: Uuid,
     pub api_key: String, // Only shown once!
@@ -49,7 +61,7 @@ pub struct CreateKeyResponse {
 }
 
 /// API key info (without sensitive data)
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ApiKeyInfo {
     pub id: Uuid,
     pub key_prefix: String,
@@ -64,19 +76,51 @@ pub struct ApiKeyInfo {
     pub created_at: DateTime<Utc>,
     pub last_used_at: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
+    pub webhook_url: Option<String>,
+    /// Domains design URLs must be hosted on; null allows any host
+    pub allowed_design_domains: Option<Vec<String>>,
+    /// When the key was deleted; only deleted keys listed with `include_deleted` have one
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
-/// List of API keys response
-#[derive(Debug, Serialize)]
-pub struct ListKeysResponse {
-    pub keys: Vec<ApiKeyInfo>,
-    pub count: usize,
+impl From<DbApiKey> for ApiKeyInfo {
+    fn from(key: DbApiKey) -> Self {
+        Self {
+            id: key.id,
+            key_prefix: key.key_prefix,
+            name: key.name,
+            owner_email: key.owner_email,
+            owner_name: key.owner_name,
+            company: key.company,
+            tier: key.tier,
+            rate_limit_per_minute: key.rate_limit_per_minute,
+            monthly_quota: key.monthly_quota,
+            is_active: key.is_active,
+            created_at: key.created_at,
+            last_used_at: key.last_used_at,
+            expires_at: key.expires_at,
+            webhook_url: key.webhook_url,
+            allowed_design_domains: key.allowed_design_domains,
+            deleted_at: key.deleted_at,
+        }
+    }
 }
 
 /// Create a new API key
 /// POST /api/v1/keys
 ///
 /// Requires admin API key authentication
+#[utoipa::path(
+    post,
+    path = "/api/v1/keys",
+    tag = "keys",
+    request_body = CreateKeyRequest,
+    responses(
+        (status = 201, description = "Key created; the secret is only returned here", body = CreateKeyResponse),
+        (status = 401, description = "API key required"),
+        (status = 403, description = "Not an enterprise key")
+    )
+)]
 pub async fn create_api_key(
     req: HttpRequest,
     pool: web::Data<DbPool>,
@@ -99,6 +143,21 @@ pub async fn create_api_key(
         }
     };
 
+    let allowed_design_domains = match body
+        .allowed_design_domains
+        .as_deref()
+        .map(design_domains)
+        .transpose()
+    {
+        Ok(domains) => domains.flatten(),
+        Err(message) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "invalid_request",
+                "message": message
+            }));
+        }
+    };
+
     let repo = ApiKeyRepository::new(pool.get_ref().clone());
 
     let tier = ApiKeyTier::from_str(&body.tier);
@@ -112,6 +171,7 @@ pub async fn create_api_key(
         rate_limit_per_minute: body.rate_limit_per_minute,
         monthly_quota: body.monthly_quota,
         expires_at: body.expires_at,
+        allowed_design_domains,
     };
 
     match repo.create(request).await {
@@ -123,6 +183,11 @@ pub async fn create_api_key(
                 created_by = %auth.key_id,
                 "API key created"
             );
+            let details = serde_json::json!({
+                "key_prefix": response.key_prefix,
+                "tier": response.tier,
+            });
+            record_event(&repo, response.id, KeyEventType::Created, &auth, details).await;
 
             HttpResponse::Created().json(CreateKeyResponse {
                 id: response.id,
@@ -147,6 +212,15 @@ pub async fn create_api_key(
 
 /// Get current API key info
 /// GET /api/v1/keys/me
+#[utoipa::path(
+    get,
+    path = "/api/v1/keys/me",
+    tag = "keys",
+    responses(
+        (status = 200, description = "The calling key", body = ApiKeyInfo),
+        (status = 401, description = "API key required")
+    )
+)]
 pub async fn get_my_key(req: HttpRequest, pool: web::Data<DbPool>) -> HttpResponse {
     let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
         Some(auth) => auth,
@@ -161,21 +235,7 @@ pub async fn get_my_key(req: HttpRequest, pool: web::Data<DbPool>) -> HttpRespon
     let repo = ApiKeyRepository::new(pool.get_ref().clone());
 
     match repo.get_by_id(auth.key_id).await {
-        Ok(Some(key)) => HttpResponse::Ok().json(ApiKeyInfo {
-            id: key.id,
-            key_prefix: key.key_prefix,
-            name: key.name,
-            owner_email: key.owner_email,
-            owner_name: key.owner_name,
-            company: key.company,
-            tier: key.tier,
-            rate_limit_per_minute: key.rate_limit_per_minute,
-            monthly_quota: key.monthly_quota,
-            is_active: key.is_active,
-            created_at: key.created_at,
-            last_used_at: key.last_used_at,
-            expires_at: key.expires_at,
-        }),
+        Ok(Some(key)) => HttpResponse::Ok().json(ApiKeyInfo::from(key)),
         Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
             "error": "not_found",
             "message": "API key not found"
@@ -192,6 +252,19 @@ pub async fn get_my_key(req: HttpRequest, pool: web::Data<DbPool>) -> HttpRespon
 
 /// Get API key by ID (admin only)
 /// GET /api/v1/keys/{id}
+#[utoipa::path(
+    get,
+    path = "/api/v1/keys/{id}",
+    tag = "keys",
+    params(
+        ("id" = Uuid, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "API key details", body = ApiKeyInfo),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 404, description = "API key not found")
+    )
+)]
 pub async fn get_key_by_id(
     req: HttpRequest,
     pool: web::Data<DbPool>,
@@ -217,21 +290,7 @@ pub async fn get_key_by_id(
     let repo = ApiKeyRepository::new(pool.get_ref().clone());
 
     match repo.get_by_id(key_id).await {
-        Ok(Some(key)) => HttpResponse::Ok().json(ApiKeyInfo {
-            id: key.id,
-            key_prefix: key.key_prefix,
-            name: key.name,
-            owner_email: key.owner_email,
-            owner_name: key.owner_name,
-            company: key.company,
-            tier: key.tier,
-            rate_limit_per_minute: key.rate_limit_per_minute,
-            monthly_quota: key.monthly_quota,
-            is_active: key.is_active,
-            created_at: key.created_at,
-            last_used_at: key.last_used_at,
-            expires_at: key.expires_at,
-        }),
+        Ok(Some(key)) => HttpResponse::Ok().json(ApiKeyInfo::from(key)),
         Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
             "error": "not_found",
             "message": "API key not found"
@@ -247,12 +306,26 @@ pub async fn get_key_by_id(
 }
 
 /// List API keys by owner email (admin only)
-/// GET /api/v1/keys?owner_email=xxx
+/// GET /api/v1/keys?owner_email=xxx&page=1&per_page=50
+///
+/// Keys other than enterprise ones always get their own owner's keys, without
+/// deleted ones. Keys are listed newest first, at most 100 per page.
+#[utoipa::path(
+    get,
+    path = "/api/v1/keys",
+    tag = "keys",
+    params(ListKeysQuery),
+    responses(
+        (status = 200, description = "One page of the owner's keys", body = PaginatedResponse<ApiKeyInfo>),
+        (status = 401, description = "API key required")
+    )
+)]
 pub async fn list_keys(
     req: HttpRequest,
     pool: web::Data<DbPool>,
     query: web::Query<ListKeysQuery>,
 ) -> HttpResponse {
+    let page = PageRequest::new(query.page, query.per_page);
     let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
         Some(auth) if auth.tier == "enterprise" => auth,
         Some(auth) => {
@@ -260,32 +333,10 @@ pub async fn list_keys(
             let owner_email = auth.owner_email.clone();
             let repo = ApiKeyRepository::new(pool.get_ref().clone());
 
-            match repo.list_by_owner(&owner_email).await {
+            match repo.list_by_owner(&owner_email, false, page).await {
                 Ok(keys) => {
-                    let key_infos: Vec<ApiKeyInfo> = keys
-                        .into_iter()
-                        .map(|key| ApiKeyInfo {
-                            id: key.id,
-                            key_prefix: key.key_prefix,
-                            name: key.name,
-                            owner_email: key.owner_email,
-                            owner_name: key.owner_name,
-                            company: key.company,
-                            tier: key.tier,
-                            rate_limit_per_minute: key.rate_limit_per_minute,
-                            monthly_quota: key.monthly_quota,
-                            is_active: key.is_active,
-                            created_at: key.created_at,
-                            last_used_at: key.last_used_at,
-                            expires_at: key.expires_at,
-                        })
-                        .collect();
-
-                    let count = key_infos.len();
-                    return HttpResponse::Ok().json(ListKeysResponse {
-                        keys: key_infos,
-                        count,
-                    });
+                    return HttpResponse::Ok()
+                        .json(PaginatedResponse::from(keys.map(ApiKeyInfo::from)));
                 }
                 Err(e) => {
                     warn!(error = %e, "Failed to list API keys");
@@ -312,33 +363,11 @@ pub async fn list_keys(
         .unwrap_or(&auth.owner_email);
     let repo = ApiKeyRepository::new(pool.get_ref().clone());
 
-    match repo.list_by_owner(owner_email).await {
-        Ok(keys) => {
-            let key_infos: Vec<ApiKeyInfo> = keys
-                .into_iter()
-                .map(|key| ApiKeyInfo {
-                    id: key.id,
-                    key_prefix: key.key_prefix,
-                    name: key.name,
-                    owner_email: key.owner_email,
-                    owner_name: key.owner_name,
-                    company: key.company,
-                    tier: key.tier,
-                    rate_limit_per_minute: key.rate_limit_per_minute,
-                    monthly_quota: key.monthly_quota,
-                    is_active: key.is_active,
-                    created_at: key.created_at,
-                    last_used_at: key.last_used_at,
-                    expires_at: key.expires_at,
-                })
-                .collect();
-
-            let count = key_infos.len();
-            HttpResponse::Ok().json(ListKeysResponse {
-                keys: key_infos,
-                count,
-            })
-        }
+    match repo
+        .list_by_owner(owner_email, query.include_deleted, page)
+        .await
+    {
+        Ok(keys) => HttpResponse::Ok().json(PaginatedResponse::from(keys.map(ApiKeyInfo::from))),
         Err(e) => {
             warn!(error = %e, "Failed to list API keys");
             HttpResponse::InternalServerError().json(serde_json::json!({
@@ -349,15 +378,379 @@ pub async fn list_keys(
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListKeysQuery {
+    /// Owner whose keys to list, enterprise keys only; the caller's owner when omitted
     pub owner_email: Option<String>,
+    /// Include deleted keys, enterprise keys only
+    #[serde(default)]
+    pub include_deleted: bool,
+    /// Page number (1-based)
+    #[serde(default = "default_page")]
+    #[param(default = 1)]
+    pub page: u32,
+    /// Keys per page, at most 100
+    #[serde(default = "default_per_page")]
+    #[param(default = 50)]
+    pub per_page: u32,
+}
+
+/// Tiers a key can be moved to
+const TIERS: [&str; 4] = ["free", "starter", "pro", "enterprise"];
+
+/// Request to change an API key; omitted fields are kept
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct UpdateKeyRequest {
+    pub name: Option<String>,
+    /// New tier; limits not given with it are reset to the tier's defaults
+    pub tier: Option<String>,
+    pub rate_limit_per_minute: Option<i32>,
+    pub monthly_quota: Option<i32>,
+    /// New expiry, or null to make the key never expire
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    pub is_active: Option<bool>,
+    /// URL for quota threshold alerts, or null to stop them
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    pub webhook_url: Option<Option<String>>,
+    /// Domains design URLs must be hosted on, or null or [] to allow any host
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub allowed_design_domains: Option<Option<Vec<String>>>,
+}
+
+/// Most domains a key's design allowlist may hold
+const MAX_DESIGN_DOMAINS: usize = 50;
+
+/// Normalize a design domain allowlist; an empty list is no restriction
+///
+/// Entries are lowercased host names, so `*.` prefixes and trailing dots are
+/// dropped. IP addresses, ports, and paths are rejected.
+fn design_domains(domains: &[String]) -> Result<Option<Vec<String>>, String> {
+    if domains.len() > MAX_DESIGN_DOMAINS {
+        return Err(format!(
+            "allowed_design_domains may list at most {} domains",
+            MAX_DESIGN_DOMAINS
+        ));
+    }
+    let mut normalized: Vec<String> = Vec::with_capacity(domains.len());
+    for domain in domains {
+        let trimmed = domain.trim();
+        let name = trimmed
+            .strip_prefix("*.")
+            .unwrap_or(trimmed)
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        let is_domain = matches!(url::Host::parse(&name), Ok(url::Host::Domain(_)))
+            && !name.contains(['/', ':', '@', '*']);
+        if !is_domain {
+            return Err(format!(
+                "allowed_design_domains entry '{}' is not a domain name",
+                domain
+            ));
+        }
+        if !normalized.contains(&name) {
+            normalized.push(name);
+        }
+    }
+    Ok((!normalized.is_empty()).then_some(normalized))
+}
+
+/// Deserialize a field that is present, even as null, into `Some`
+fn present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl UpdateKeyRequest {
+    /// Names of the fields the request changes, for the audit trail
+    fn fields(&self) -> Vec<&'static str> {
+        [
+            ("name", self.name.is_some()),
+            ("tier", self.tier.is_some()),
+            (
+                "rate_limit_per_minute",
+                self.rate_limit_per_minute.is_some(),
+            ),
+            ("monthly_quota", self.monthly_quota.is_some()),
+            ("expires_at", self.expires_at.is_some()),
+            ("is_active", self.is_active.is_some()),
+            ("webhook_url", self.webhook_url.is_some()),
+            (
+                "allowed_design_domains",
+                self.allowed_design_domains.is_some(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(field, changed)| changed.then_some(field))
+        .collect()
+    }
+
+    /// Check the changes and convert them for the repository
+    fn validate(&self) -> Result<UpdateApiKeyRequest, String> {
+        if self
+            .name
+            .as_deref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err("name must not be empty".to_string());
+        }
+        let tier = match self.tier.as_deref().map(str::to_lowercase) {
+            Some(tier) if !TIERS.contains(&tier.as_str()) => {
+                return Err(format!("tier must be one of {}", TIERS.join(", ")));
+            }
+            tier => tier.map(|tier| ApiKeyTier::from_str(&tier)),
+        };
+        if self.rate_limit_per_minute.is_some_and(|limit| limit < 1) {
+            return Err("rate_limit_per_minute must be at least 1".to_string());
+        }
+        if self.monthly_quota.is_some_and(|quota| quota < 0) {
+            return Err("monthly_quota must not be negative".to_string());
+        }
+        if let Some(Some(url)) = &self.webhook_url {
+            validate_webhook_url(url)?;
+        }
+
+        let update = UpdateApiKeyRequest {
+            name: self.name.clone(),
+            tier,
+            rate_limit_per_minute: self.rate_limit_per_minute,
+            monthly_quota: self.monthly_quota,
+            expires_at: self.expires_at,
+            is_active: self.is_active,
+            webhook_url: self.webhook_url.clone(),
+            allowed_design_domains: self
+                .allowed_design_domains
+                .as_ref()
+                .map(|domains| domains.as_deref().map(design_domains).transpose())
+                .transpose()?
+                .map(Option::flatten),
+        };
+        if update.is_empty() {
+            return Err("No fields to update".to_string());
+        }
+        Ok(update)
+    }
+}
+
+/// Update an API key's tier, limits, expiry, status, name, webhook URL or design domains (admin only)
+/// PATCH /api/v1/keys/{id}
+///
+/// Changes apply from the key's next request.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/keys/{id}",
+    tag = "keys",
+    params(
+        ("id" = Uuid, Path, description = "API key ID")
+    ),
+    request_body = UpdateKeyRequest,
+    responses(
+        (status = 200, description = "The updated key", body = ApiKeyInfo),
+        (status = 400, description = "Invalid or empty update"),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 404, description = "API key not found or deleted")
+    )
+)]
+pub async fn update_key(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateKeyRequest>,
+) -> HttpResponse {
+    let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
+        Some(auth) if auth.tier == "enterprise" => auth,
+        Some(_) => {
+            return HttpResponse::Forbidden().json(serde_json::json!({
+                "error": "forbidden",
+                "message": "Only enterprise tier keys can update API keys"
+            }));
+        }
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "unauthorized",
+                "message": "API key required"
+            }));
+        }
+    };
+
+    let update = match body.validate() {
+        Ok(update) => update,
+        Err(message) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "invalid_request",
+                "message": message
+            }));
+        }
+    };
+
+    let key_id = path.into_inner();
+    let repo = ApiKeyRepository::new(pool.get_ref().clone());
+
+    match repo.update(key_id, &update).await {
+        Ok(Some(key)) => {
+            state.key_cache.invalidate(key_id);
+            info!(key_id = %key_id, updated_by = %auth.key_id, "API key updated");
+            let details = serde_json::json!({ "fields": body.fields() });
+            record_event(&repo, key_id, KeyEventType::Updated, &auth, details).await;
+            HttpResponse::Ok().json(ApiKeyInfo::from(key))
+        }
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "not_found",
+            "message": "API key not found or deleted"
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to update API key");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to update API key"
+            }))
+        }
+    }
+}
+
+/// Longest grace period a rotated key's old secret can keep working (7 days)
+const MAX_GRACE_PERIOD_MINUTES: i32 = 7 * 24 * 60;
+
+/// Request to rotate an API key's secret
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RotateKeyRequest {
+    /// Minutes the old secret keeps working, 0 (the default) to stop it at once
+    #[serde(default)]
+    pub grace_period_minutes: i32,
 }
 
-/// Revoke an API key
+/// Rotate an API key, issuing a new secret for the same key
+/// POST /api/v1/keys/{id}/rotate
+///
+/// The key keeps its ID, so usage history and quotas carry over.
+#[utoipa::path(
+    post,
+    path = "/api/v1/keys/{id}/rotate",
+    tag = "keys",
+    params(
+        ("id" = Uuid, Path, description = "API key ID")
+    ),
+    request_body(content = RotateKeyRequest, description = "Optional; without a body the old secret stops working at once"),
+    responses(
+        (status = 200, description = "New secret for the key; only returned here", body = CreateKeyResponse),
+        (status = 400, description = "Grace period out of range"),
+        (status = 403, description = "Key belongs to another owner"),
+        (status = 404, description = "API key not found, revoked or deleted")
+    )
+)]
+pub async fn rotate_key(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<RotateKeyRequest>>,
+) -> HttpResponse {
+    let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
+        Some(auth) => auth,
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "unauthorized",
+                "message": "API key required"
+            }));
+        }
+    };
+
+    let grace_period_minutes = body.map(|b| b.grace_period_minutes).unwrap_or_default();
+    if !(0..=MAX_GRACE_PERIOD_MINUTES).contains(&grace_period_minutes) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "invalid_request",
+            "message": format!(
+                "grace_period_minutes must be between 0 and {}",
+                MAX_GRACE_PERIOD_MINUTES
+            )
+        }));
+    }
+
+    let key_id = path.into_inner();
+    let repo = ApiKeyRepository::new(pool.get_ref().clone());
+
+    if let Err(response) = ensure_owner(&repo, &auth, key_id, "rotate").await {
+        return response;
+    }
+
+    match repo.rotate(key_id, grace_period_minutes, auth.key_id).await {
+        Ok(Some(response)) => {
+            state.key_cache.invalidate(key_id);
+            info!(
+                key_id = %key_id,
+                key_prefix = %response.key_prefix,
+                rotated_by = %auth.key_id,
+                grace_period_minutes,
+                "API key rotated"
+            );
+            let details = serde_json::json!({
+                "key_prefix": response.key_prefix,
+                "grace_period_minutes": grace_period_minutes,
+            });
+            record_event(&repo, key_id, KeyEventType::Rotated, &auth, details).await;
+
+            let message = if grace_period_minutes > 0 {
+                format!(
+                    "API key rotated. The old key keeps working for {} minutes. \
+                     Save the api_key value - it won't be shown again!",
+                    grace_period_minutes
+                )
+            } else {
+                "API key rotated and the old key revoked. \
+                 Save the api_key value - it won't be shown again!"
+                    .to_string()
+            };
+            HttpResponse::Ok().json(CreateKeyResponse {
+                id: response.id,
+                api_key: response.api_key,
+                key_prefix: response.key_prefix,
+                name: response.name,
+                tier: response.tier,
+                rate_limit_per_minute: response.rate_limit_per_minute,
+                monthly_quota: response.monthly_quota,
+                message,
+            })
+        }
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "not_found",
+            "message": "API key not found, revoked or deleted"
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to rotate API key");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to rotate API key"
+            }))
+        }
+    }
+}
+
+/// Revoke an API key, keeping it listed
 /// DELETE /api/v1/keys/{id}
+#[utoipa::path(
+    delete,
+    path = "/api/v1/keys/{id}",
+    tag = "keys",
+    params(
+        ("id" = Uuid, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "Key revoked"),
+        (status = 403, description = "Key belongs to another owner"),
+        (status = 404, description = "API key not found or deleted")
+    )
+)]
 pub async fn revoke_key(
     req: HttpRequest,
+    state: web::Data<AppState>,
     pool: web::Data<DbPool>,
     path: web::Path<Uuid>,
 ) -> HttpResponse {
@@ -374,37 +767,16 @@ pub async fn revoke_key(
     let key_id = path.into_inner();
     let repo = ApiKeyRepository::new(pool.get_ref().clone());
 
-    // Check if user owns the key or is admin
-    if auth.tier != "enterprise" {
-        match repo.get_by_id(key_id).await {
-            Ok(Some(key)) if key.owner_email == auth.owner_email => {
-                // Owner can revoke their own key
-            }
-            Ok(Some(_)) => {
-                return HttpResponse::Forbidden().json(serde_json::json!({
-                    "error": "forbidden",
-                    "message": "You can only revoke your own API keys"
-                }));
-            }
-            Ok(None) => {
-                return HttpResponse::NotFound().json(serde_json::json!({
-                    "error": "not_found",
-                    "message": "API key not found"
-                }));
-            }
-            Err(e) => {
-                warn!(error = %e, "Failed to check API key ownership");
-                return HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "internal_error",
-                    "message": "Failed to revoke API key"
-                }));
-            }
-        }
+    if let Err(response) = ensure_owner(&repo, &auth, key_id, "revoke").await {
+        return response;
     }
 
     match repo.revoke(key_id).await {
         Ok(true) => {
+            state.key_cache.invalidate(key_id);
             info!(key_id = %key_id, revoked_by = %auth.key_id, "API key revoked");
+            let details = serde_json::json!({});
+            record_event(&repo, key_id, KeyEventType::Revoked, &auth, details).await;
             HttpResponse::Ok().json(serde_json::json!({
                 "message": "API key revoked successfully",
                 "key_id": key_id
@@ -412,7 +784,7 @@ pub async fn revoke_key(
         }
         Ok(false) => HttpResponse::NotFound().json(serde_json::json!({
             "error": "not_found",
-            "message": "API key not found"
+            "message": "API key not found or deleted"
         })),
         Err(e) => {
             warn!(error = %e, "Failed to revoke API key");
@@ -423,3 +795,469 @@ pub async fn revoke_key(
         }
     }
 }
+
+/// Delete an API key
+/// POST /api/v1/keys/{id}/delete
+///
+/// The key stops working at once and is hidden from listings, but its row,
+/// usage and billing history are kept. `DELETE /api/v1/keys/{id}` stays the
+/// revoke it has always been.
+#[utoipa::path(
+    post,
+    path = "/api/v1/keys/{id}/delete",
+    tag = "keys",
+    params(
+        ("id" = Uuid, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "Key deleted"),
+        (status = 403, description = "Key belongs to another owner"),
+        (status = 404, description = "API key not found or already deleted")
+    )
+)]
+pub async fn delete_key(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
+        Some(auth) => auth,
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "unauthorized",
+                "message": "API key required"
+            }));
+        }
+    };
+
+    let key_id = path.into_inner();
+    let repo = ApiKeyRepository::new(pool.get_ref().clone());
+
+    if let Err(response) = ensure_owner(&repo, &auth, key_id, "delete").await {
+        return response;
+    }
+
+    match repo.delete(key_id).await {
+        Ok(true) => {
+            state.key_cache.invalidate(key_id);
+            info!(key_id = %key_id, deleted_by = %auth.key_id, "API key deleted");
+            let details = serde_json::json!({});
+            record_event(&repo, key_id, KeyEventType::Deleted, &auth, details).await;
+            HttpResponse::Ok().json(serde_json::json!({
+                "message": "API key deleted successfully",
+                "key_id": key_id
+            }))
+        }
+        Ok(false) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "not_found",
+            "message": "API key not found or already deleted"
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to delete API key");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to delete API key"
+            }))
+        }
+    }
+}
+
+/// One entry of a key's audit trail
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KeyEventInfo {
+    pub id: Uuid,
+    /// created, revoked, rotated, updated, or deleted
+    pub event_type: String,
+    /// Key whose request made the change
+    pub actor_key_id: Option<Uuid>,
+    /// Event specifics, such as the fields an update changed
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<KeyEvent> for KeyEventInfo {
+    fn from(event: KeyEvent) -> Self {
+        Self {
+            id: event.id,
+            event_type: event.event_type,
+            actor_key_id: event.actor_key_id,
+            details: event.details,
+            created_at: event.created_at,
+        }
+    }
+}
+
+/// A key's audit trail
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KeyEventsResponse {
+    pub key_id: Uuid,
+    pub events: Vec<KeyEventInfo>,
+    pub count: usize,
+}
+
+/// List an API key's audit trail, oldest first (admin only)
+/// GET /api/v1/keys/{id}/events
+#[utoipa::path(
+    get,
+    path = "/api/v1/keys/{id}/events",
+    tag = "keys",
+    params(
+        ("id" = Uuid, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "The key's events", body = KeyEventsResponse),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 404, description = "API key not found")
+    )
+)]
+pub async fn list_key_events(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    match req.extensions().get::<ApiKeyAuth>() {
+        Some(auth) if auth.tier == "enterprise" => {}
+        Some(_) => {
+            return HttpResponse::Forbidden().json(serde_json::json!({
+                "error": "forbidden",
+                "message": "Only enterprise tier keys can view key events"
+            }));
+        }
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "unauthorized",
+                "message": "API key required"
+            }));
+        }
+    }
+
+    let key_id = path.into_inner();
+    let repo = ApiKeyRepository::new(pool.get_ref().clone());
+
+    let events = match repo.get_by_id(key_id).await {
+        Ok(Some(_)) => repo.events(key_id).await,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "not_found",
+                "message": "API key not found"
+            }));
+        }
+        Err(e) => Err(e),
+    };
+    match events {
+        Ok(events) => {
+            let events: Vec<KeyEventInfo> = events.into_iter().map(KeyEventInfo::from).collect();
+            let count = events.len();
+            HttpResponse::Ok().json(KeyEventsResponse {
+                key_id,
+                events,
+                count,
+            })
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to list API key events");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to list API key events"
+            }))
+        }
+    }
+}
+
+/// Check that the caller may `action` the key: its owner or an enterprise key
+async fn ensure_owner(
+    repo: &ApiKeyRepository,
+    auth: &ApiKeyAuth,
+    key_id: Uuid,
+    action: &str,
+) -> Result<(), HttpResponse> {
+    if auth.tier == "enterprise" {
+        return Ok(());
+    }
+    match repo.get_by_id(key_id).await {
+        Ok(Some(key)) if key.owner_email == auth.owner_email => Ok(()),
+        Ok(Some(_)) => Err(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "forbidden",
+            "message": format!("You can only {} your own API keys", action)
+        }))),
+        Ok(None) => Err(HttpResponse::NotFound().json(serde_json::json!({
+            "error": "not_found",
+            "message": "API key not found"
+        }))),
+        Err(e) => {
+            warn!(error = %e, "Failed to check API key ownership");
+            Err(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": format!("Failed to {} API key", action)
+            })))
+        }
+    }
+}
+
+/// Add an event to a key's audit trail
+///
+/// The change it records has already been made, so a failure is logged
+/// rather than failing the request.
+async fn record_event(
+    repo: &ApiKeyRepository,
+    key_id: Uuid,
+    event_type: KeyEventType,
+    actor: &ApiKeyAuth,
+    details: serde_json::Value,
+) {
+    if let Err(e) = repo
+        .record_event(key_id, event_type, Some(actor.key_id), &details)
+        .await
+    {
+        warn!(
+            key_id = %key_id,
+            event_type = event_type.as_str(),
+            error = %e,
+            "Failed to record API key event"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::bare_state;
+    use actix_web::dev::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+
+    fn parse(json: &str) -> UpdateKeyRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_update_expiry_can_be_cleared() {
+        assert_eq!(parse("{}").expires_at, None);
+        assert_eq!(parse(r#"{"expires_at": null}"#).expires_at, Some(None));
+
+        let update = parse(r#"{"expires_at": "2027-01-01T00:00:00Z"}"#)
+            .validate()
+            .unwrap();
+        assert!(matches!(update.expires_at, Some(Some(_))));
+    }
+
+    #[test]
+    fn test_update_tier() {
+        let update = parse(r#"{"tier": "Pro"}"#).validate().unwrap();
+        assert_eq!(update.tier, Some(ApiKeyTier::Pro));
+
+        let error = parse(r#"{"tier": "platinum"}"#).validate().unwrap_err();
+        assert!(error.contains("free, starter, pro, enterprise"));
+    }
+
+    #[test]
+    fn test_invalid_updates_are_rejected() {
+        for json in [
+            "{}",
+            r#"{"name": " "}"#,
+            r#"{"rate_limit_per_minute": 0}"#,
+            r#"{"monthly_quota": -1}"#,
+            r#"{"webhook_url": "ftp://example.com/quota"}"#,
+            r#"{"webhook_url": "http://169.254.169.254/latest/meta-data/"}"#,
+        ] {
+            assert!(parse(json).validate().is_err(), "{} was accepted", json);
+        }
+        assert!(parse(r#"{"is_active": false}"#).validate().is_ok());
+    }
+
+    #[test]
+    fn test_update_webhook_url_can_be_cleared() {
+        let update = parse(r#"{"webhook_url": "https://example.com/quota"}"#)
+            .validate()
+            .unwrap();
+        assert_eq!(
+            update.webhook_url,
+            Some(Some("https://example.com/quota".to_string()))
+        );
+
+        let update = parse(r#"{"webhook_url": null}"#).validate().unwrap();
+        assert_eq!(update.webhook_url, Some(None));
+    }
+
+    #[test]
+    fn test_update_fields_are_named_for_the_audit_trail() {
+        let request = parse(r#"{"tier": "pro", "webhook_url": null}"#);
+        assert_eq!(request.fields(), ["tier", "webhook_url"]);
+        assert!(parse("{}").fields().is_empty());
+    }
+
+    #[test]
+    fn test_design_domains_are_normalized() {
+        let domains = design_domains(&[
+            " CDN.Acme.com ".to_string(),
+            "*.images.acme.com.".to_string(),
+            "cdn.acme.com".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            domains,
+            Some(vec![
+                "cdn.acme.com".to_string(),
+                "images.acme.com".to_string()
+            ])
+        );
+        assert_eq!(design_domains(&[]).unwrap(), None);
+
+        for domain in [
+            "",
+            "203.0.114.10",
+            "[::1]",
+            "cdn.acme.com:8080",
+            "acme.com/designs",
+        ] {
+            assert!(
+                design_domains(&[domain.to_string()]).is_err(),
+                "{} was accepted",
+                domain
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_design_domains_can_be_cleared() {
+        assert_eq!(parse("{}").allowed_design_domains, None);
+
+        let update = parse(r#"{"allowed_design_domains": ["Acme.com"]}"#)
+            .validate()
+            .unwrap();
+        assert_eq!(
+            update.allowed_design_domains,
+            Some(Some(vec!["acme.com".to_string()]))
+        );
+
+        // Null and an empty list both lift the restriction
+        for json in [
+            r#"{"allowed_design_domains": null}"#,
+            r#"{"allowed_design_domains": []}"#,
+        ] {
+            let update = parse(json).validate().unwrap();
+            assert_eq!(update.allowed_design_domains, Some(None));
+        }
+        assert!(parse(r#"{"allowed_design_domains": ["10.0.0.1"]}"#)
+            .validate()
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_key_lifecycle_events() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let repo = ApiKeyRepository::new(pool.clone());
+        // Events name their actor, so the caller has to be a real key
+        let admin = repo
+            .create(CreateApiKeyRequest {
+                name: "Lifecycle admin".to_string(),
+                owner_email: format!("admin-{}@example.com", Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Enterprise,
+                rate_limit_per_minute: None,
+                monthly_quota: None,
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+        let auth = ApiKeyAuth::from(&repo.get_by_id(admin.id).await.unwrap().unwrap());
+        // Stands in for the auth middleware
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(bare_state()))
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(auth.clone());
+                    srv.call(req)
+                })
+                .service(
+                    web::scope("/keys")
+                        .route("", web::post().to(create_api_key))
+                        .route("", web::get().to(list_keys))
+                        .route("/{id}", web::patch().to(update_key))
+                        .route("/{id}", web::delete().to(revoke_key))
+                        .route("/{id}/delete", web::post().to(delete_key))
+                        .route("/{id}/rotate", web::post().to(rotate_key))
+                        .route("/{id}/events", web::get().to(list_key_events)),
+                ),
+        )
+        .await;
+        let owner = format!("lifecycle-{}@example.com", Uuid::new_v4());
+        let listed = |query: &str| {
+            test::TestRequest::get()
+                .uri(&format!("/keys?owner_email={}{}", owner, query))
+                .to_request()
+        };
+
+        let request = test::TestRequest::post()
+            .uri("/keys")
+            .set_json(
+                serde_json::json!({"name": "Lifecycle", "owner_email": owner, "tier": "starter"}),
+            )
+            .to_request();
+        let created: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let id = created["id"].as_str().unwrap().to_string();
+        let key_id: Uuid = id.parse().unwrap();
+
+        for request in [
+            test::TestRequest::patch()
+                .uri(&format!("/keys/{}", id))
+                .set_json(serde_json::json!({"name": "Renamed"})),
+            test::TestRequest::post().uri(&format!("/keys/{}/rotate", id)),
+            test::TestRequest::delete().uri(&format!("/keys/{}", id)),
+        ] {
+            let response = test::call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // DELETE revokes: the key is still listed, inactive
+        let page: serde_json::Value = test::call_and_read_body_json(&app, listed("")).await;
+        assert_eq!(page["items"][0]["id"], id.as_str());
+        assert_eq!(page["items"][0]["is_active"], false);
+
+        let delete = || {
+            test::TestRequest::post()
+                .uri(&format!("/keys/{}/delete", id))
+                .to_request()
+        };
+        let response = test::call_service(&app, delete()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = test::call_service(&app, delete()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Deleted keys only list when asked for
+        let page: serde_json::Value = test::call_and_read_body_json(&app, listed("")).await;
+        assert_eq!(page["total"], 0);
+        let page: serde_json::Value =
+            test::call_and_read_body_json(&app, listed("&include_deleted=true")).await;
+        assert_eq!(page["total"], 1);
+        assert!(page["items"][0]["deleted_at"].is_string());
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/keys/{}/events", id))
+            .to_request();
+        let trail: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let events = trail["events"].as_array().unwrap();
+        let types: Vec<_> = events
+            .iter()
+            .map(|e| e["event_type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            ["created", "updated", "rotated", "revoked", "deleted"]
+        );
+        assert_eq!(events[1]["details"]["fields"][0], "name");
+        assert!(events
+            .iter()
+            .all(|e| e["actor_key_id"] == admin.id.to_string().as_str()));
+
+        repo.purge(key_id).await.unwrap();
+        repo.purge(admin.id).await.unwrap();
+    }
+}
@@ -0,0 +1,303 @@
+//! Prometheus metrics endpoint
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use std::fmt::Write;
+
+use crate::api::middleware::ApiKeyCache;
+use crate::db::DbPool;
+use crate::engine::TemplateManager;
+use crate::metrics::{Metrics, METRIC_PREFIX};
+use crate::storage::mirror_stats;
+use crate::AppState;
+
+/// GET /metrics - Prometheus text exposition
+///
+/// Restricted to `metrics.bearer_token` or `metrics.allowed_ips` when either
+/// is configured. The allowlist is checked against the connecting peer, not
+/// forwarded headers, which clients can set freely.
+pub async fn metrics(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    if !state.settings.metrics.allows(peer, bearer) {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "forbidden",
+            "message": "Metrics are restricted to configured scrapers"
+        }));
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(render_metrics(
+            &state.metrics,
+            &state.template_manager,
+            &state.key_cache,
+            state.db_pool.as_ref(),
+        ))
+}
+
+/// The shared registry followed by gauges read on demand
+fn render_metrics(
+    metrics: &Metrics,
+    templates: &TemplateManager,
+    key_cache: &ApiKeyCache,
+    db_pool: Option<&DbPool>,
+) -> String {
+    let stats = templates.memory_stats();
+
+    let mut body = metrics.render();
+    write_metric(
+        &mut body,
+        "templates_loaded",
+        "gauge",
+        "Templates with metadata indexed",
+        stats.templates as u64,
+    );
+    write_metric(
+        &mut body,
+        "templates_failed",
+        "gauge",
+        "Template directories that failed to load or validate",
+        templates.load_report().failed.len() as u64,
+    );
+    write_metric(
+        &mut body,
+        "template_resident_templates",
+        "gauge",
+        "Templates with decoded images held in memory",
+        stats.resident_templates as u64,
+    );
+    write_metric(
+        &mut body,
+        "template_resident_bytes",
+        "gauge",
+        "Bytes of decoded template images held in memory",
+        stats.resident_bytes,
+    );
+    write_metric(
+        &mut body,
+        "template_evictions_total",
+        "counter",
+        "Templates whose decoded images were evicted after idling or to stay within cache limits",
+        stats.evictions_total,
+    );
+    write_metric(
+        &mut body,
+        "template_reloads_total",
+        "counter",
+        "Template images decoded on demand, including first use",
+        stats.reloads_total,
+    );
+
+    let generations = templates.generation_load();
+    write_metric(
+        &mut body,
+        "generations_in_flight",
+        "gauge",
+        "Mockups being generated right now",
+        generations.in_flight as u64,
+    );
+    write_metric(
+        &mut body,
+        "generations_queued",
+        "gauge",
+        "Generations waiting for a free slot",
+        generations.queued as u64,
+    );
+
+    let mirror = mirror_stats();
+    write_metric(
+        &mut body,
+        "mirror_transfers_in_progress",
+        "gauge",
+        "Asset mirror downloads currently running",
+        mirror.transfers_in_progress,
+    );
+    write_metric(
+        &mut body,
+        "mirror_bytes_in_progress",
+        "gauge",
+        "Bytes received so far by running asset mirror downloads",
+        mirror.bytes_in_progress,
+    );
+    write_metric(
+        &mut body,
+        "mirror_retries_total",
+        "counter",
+        "Interrupted asset mirror downloads that were retried",
+        mirror.retries_total,
+    );
+    write_metric(
+        &mut body,
+        "mirror_resumed_bytes_total",
+        "counter",
+        "Bytes skipped by resuming asset mirror downloads instead of restarting",
+        mirror.resumed_bytes_total,
+    );
+    write_metric(
+        &mut body,
+        "mirror_failures_total",
+        "counter",
+        "Asset mirror downloads that failed after all retries",
+        mirror.failures_total,
+    );
+
+    let key_cache = key_cache.stats();
+    write_metric(
+        &mut body,
+        "api_key_cache_entries",
+        "gauge",
+        "Validated API keys held in memory",
+        key_cache.entries,
+    );
+    write_metric(
+        &mut body,
+        "api_key_cache_hits_total",
+        "counter",
+        "Requests authenticated from the API key cache",
+        key_cache.hits_total,
+    );
+    write_metric(
+        &mut body,
+        "api_key_cache_misses_total",
+        "counter",
+        "Requests whose API key was looked up in the database",
+        key_cache.misses_total,
+    );
+
+    if let Some(pool) = db_pool {
+        let status = pool.status();
+        write_metric(
+            &mut body,
+            "db_pool_max_connections",
+            "gauge",
+            "Most database connections the pool opens",
+            status.max_connections as u64,
+        );
+        write_metric(
+            &mut body,
+            "db_pool_connections",
+            "gauge",
+            "Database connections open, in use or idle",
+            status.size as u64,
+        );
+        write_metric(
+            &mut body,
+            "db_pool_available",
+            "gauge",
+            "Idle database connections ready to be handed out",
+            status.available as u64,
+        );
+        write_metric(
+            &mut body,
+            "db_pool_waiting",
+            "gauge",
+            "Requests waiting for a database connection",
+            status.waiting as u64,
+        );
+    }
+
+    body
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {}_{} {}", METRIC_PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", METRIC_PREFIX, name, kind);
+    let _ = writeln!(out, "{}_{} {}", METRIC_PREFIX, name, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::middleware::RequestMetrics;
+    use crate::engine::{write_starter_templates, GenerationLimits, MockupRequest, OutputSettings};
+    use actix_web::{test, App};
+    use std::sync::Arc;
+
+    /// Value of the sample line starting with `series`, or 0 when absent
+    fn sample(body: &str, series: &str) -> f64 {
+        body.lines()
+            .find_map(|line| line.strip_prefix(series)?.trim().parse().ok())
+            .unwrap_or(0.0)
+    }
+
+    #[actix_web::test]
+    async fn test_scrape_counts_generate_calls() {
+        let base = std::env::temp_dir().join(format!("metrics-{}", uuid::Uuid::new_v4()));
+        write_starter_templates(&base, false).unwrap();
+        let manager = Arc::new(TemplateManager::new(&base).unwrap());
+        manager.load_all().await.unwrap();
+        let template_id = manager.list_ids().into_iter().min().unwrap();
+
+        let metrics = Arc::new(Metrics::new());
+        manager.set_metrics(metrics.clone());
+        let key_cache = Arc::new(ApiKeyCache::default());
+
+        let generate = {
+            let manager = manager.clone();
+            let template_id = template_id.clone();
+            move || {
+                let manager = manager.clone();
+                let request = MockupRequest {
+                    designs: Vec::new(),
+                    template_id: template_id.clone(),
+                    apply_displacement: None,
+                    tint_color: None,
+                    remove_background: None,
+                    output: OutputSettings::default(),
+                    limits: GenerationLimits::default(),
+                };
+                async move {
+                    manager.generate_mockup(&request).await.unwrap();
+                    HttpResponse::Ok().finish()
+                }
+            }
+        };
+        let scrape = {
+            let (metrics, manager) = (metrics.clone(), manager.clone());
+            move || {
+                let body = render_metrics(&metrics, &manager, &key_cache, None);
+                async move { HttpResponse::Ok().body(body) }
+            }
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestMetrics::new(metrics.clone()))
+                .route("/api/v1/mockups/generate", web::post().to(generate))
+                .route("/metrics", web::get().to(scrape)),
+        )
+        .await;
+
+        let requests = r#"r_image_magic_http_requests_total{method="POST",route="/api/v1/mockups/generate",status="200"}"#;
+        let generations = format!(
+            r#"r_image_magic_generation_duration_seconds_count{{template_id="{}"}}"#,
+            template_id
+        );
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let before = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert_eq!(sample(&before, requests), 0.0);
+        assert_eq!(sample(&before, &generations), 0.0);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/mockups/generate")
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let after = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert_eq!(sample(&after, requests), 1.0);
+        assert_eq!(sample(&after, &generations), 1.0);
+        assert_eq!(
+            sample(
+                &after,
+                r#"r_image_magic_template_cache_requests_total{result="miss"}"#
+            ),
+            1.0
+        );
+        assert!(after.contains("r_image_magic_generations_in_flight 0"));
+        std::fs::remove_dir_all(&base).ok();
+    }
+}
@@ -1,10 +1,109 @@
 //! HTTP request handlers
 
+use actix_web::HttpResponse;
+
+pub mod admin;
+pub mod batch;
 pub mod catalog;
+pub mod designs;
+pub mod examples;
 pub mod generate;
 pub mod health;
+pub mod jobs;
 pub mod keys;
+pub mod metrics;
+pub mod providers;
+pub mod renders;
 pub mod sync;
 pub mod templates;
 pub mod usage;
+pub mod webhooks;
 pub mod tile;
+
+/// 503 for a request that found every database connection in use
+pub(crate) fn database_busy() -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .insert_header(("Retry-After", "1"))
+        .json(serde_json::json!({
+            "error": "database_busy",
+            "message": "Database busy. Try again shortly."
+        }))
+}
+
+/// State of a server with no database or storage, from the default config
+#[cfg(test)]
+pub(crate) fn bare_state() -> crate::AppState {
+    use crate::api::middleware::ApiKeyCache;
+    use crate::config::Settings;
+    use crate::engine::TemplateManager;
+    use crate::jobs::{JobStore, RenderJobs, JOB_OUTPUT_RETENTION};
+    use crate::metrics::Metrics;
+    use crate::providers::LiveCatalog;
+    use crate::shutdown::Shutdown;
+    use crate::storage::RenderCache;
+    use crate::sync::{OnDemandTemplates, ProductTemplates, SyncOrchestrator, SyncScheduler};
+    use crate::uploads::UploadQueue;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let settings = Settings::load().unwrap();
+    let cache_dir = std::env::temp_dir().join(format!("handlers-{}", uuid::Uuid::new_v4()));
+    let orchestrator = SyncOrchestrator::new(None, None);
+    let sync_jobs = orchestrator.job_store();
+    crate::AppState {
+        template_manager: Arc::new(TemplateManager::new(&settings.templates.path).unwrap()),
+        db_pool: None,
+        template_repo: None,
+        sync_scheduler: Arc::new(SyncScheduler::new(Arc::new(orchestrator), 1)),
+        sync_jobs,
+        sync_schedule: None,
+        webhooks: None,
+        on_demand_templates: Arc::new(OnDemandTemplates::new(None)),
+        product_templates: Arc::new(ProductTemplates::new(None, None, cache_dir)),
+        jobs: Arc::new(JobStore::new(None, JOB_OUTPUT_RETENTION)),
+        render_jobs: Arc::new(RenderJobs::new(None, Duration::from_secs(60))),
+        cloudinary: None,
+        storage: None,
+        uploads: Arc::new(UploadQueue::new(None, None)),
+        parity: None,
+        live_catalog: Arc::new(LiveCatalog::new()),
+        key_cache: Arc::new(ApiKeyCache::default()),
+        billing: Arc::new(RwLock::new(settings.billing.clone())),
+        shutdown: Arc::new(Shutdown::default()),
+        metrics: Arc::new(Metrics::new()),
+        render_cache: Arc::new(RenderCache::from_settings(&settings.render_cache, None)),
+        r2_usage: None,
+        settings,
+    }
+}
+
+/// Key of the given tier, as the auth middleware would attach it
+#[cfg(test)]
+pub(crate) fn test_key(tier: &str) -> crate::api::middleware::ApiKeyAuth {
+    crate::api::middleware::ApiKeyAuth {
+        key_id: uuid::Uuid::nil(),
+        tier: tier.to_string(),
+        rate_limit: 100,
+        monthly_quota: 10000,
+        owner_email: "handlers@example.com".to_string(),
+        allowed_design_domains: Vec::new(),
+    }
+}
+
+/// Stands in for the auth middleware, taking the key's tier from an `x-test-tier` header
+///
+/// Requests without the header go through unauthenticated.
+#[cfg(test)]
+pub(crate) async fn tier_from_header(
+    req: actix_web::dev::ServiceRequest,
+    next: actix_web::middleware::Next<impl actix_web::body::MessageBody>,
+) -> Result<actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>, actix_web::Error> {
+    use actix_web::HttpMessage;
+
+    if let Some(tier) = req.headers().get("x-test-tier") {
+        let auth = test_key(tier.to_str().unwrap());
+        req.extensions_mut().insert(auth);
+    }
+    next.call(req).await
+}
@@ -0,0 +1,434 @@
+//! Provider Management Handlers
+//!
+//! Enterprise endpoints for adding providers to `pod_providers` and changing
+//! their status, sync settings, and rate limits.
+
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use super::admin::require_enterprise;
+use super::catalog::ProviderResponse;
+use crate::api::middleware::ApiKeyAuth;
+use crate::db::{DbError, DbPool, NewProvider, ProviderRepository, UpdateProvider};
+use crate::providers::PROVIDER_CODES;
+
+/// How a provider's API authenticates requests
+const AUTH_TYPES: [&str; 3] = ["oauth2", "api_key", "recipe_id"];
+
+/// Request to add a provider
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateProviderRequest {
+    /// Lowercase letters, digits, `-` and `_`
+    pub code: String,
+    pub name: String,
+    pub api_base_url: String,
+    /// oauth2, api_key, or recipe_id
+    pub auth_type: String,
+    pub rate_limit_per_minute: i32,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+    #[serde(default)]
+    pub sync_enabled: bool,
+    #[serde(default = "default_sync_interval_hours")]
+    pub sync_interval_hours: i32,
+    /// Allow a code with no built-in client; such providers are listed but can't sync
+    #[serde(default)]
+    pub external_only: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_sync_interval_hours() -> i32 {
+    24
+}
+
+impl CreateProviderRequest {
+    /// Check the provider and convert it for the repository
+    fn validate(&self) -> Result<NewProvider, String> {
+        let code = self.code.trim();
+        let valid_code = !code.is_empty()
+            && code.len() <= 50
+            && code
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid_code {
+            return Err("code must be 1-50 lowercase letters, digits, '-' or '_'".to_string());
+        }
+        let implemented = PROVIDER_CODES.contains(&code);
+        if !implemented && !self.external_only {
+            return Err(format!(
+                "code must be one of {}, or set external_only for a provider without a client",
+                PROVIDER_CODES.join(", ")
+            ));
+        }
+        if !implemented && self.sync_enabled {
+            return Err("External-only providers can't sync".to_string());
+        }
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        let valid_url = url::Url::parse(&self.api_base_url)
+            .map(|u| matches!(u.scheme(), "http" | "https"))
+            .unwrap_or(false);
+        if !valid_url {
+            return Err("api_base_url must be an absolute http(s) URL".to_string());
+        }
+        if !AUTH_TYPES.contains(&self.auth_type.as_str()) {
+            return Err(format!(
+                "auth_type must be one of {}",
+                AUTH_TYPES.join(", ")
+            ));
+        }
+        validate_limits(
+            Some(self.rate_limit_per_minute),
+            Some(self.sync_interval_hours),
+        )?;
+
+        Ok(NewProvider {
+            code: code.to_string(),
+            name: self.name.trim().to_string(),
+            api_base_url: self.api_base_url.clone(),
+            auth_type: self.auth_type.clone(),
+            rate_limit_per_minute: self.rate_limit_per_minute,
+            is_active: self.is_active,
+            sync_enabled: self.sync_enabled,
+            sync_interval_hours: self.sync_interval_hours,
+        })
+    }
+}
+
+/// Changes to a provider; omitted fields are kept
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateProviderRequest {
+    pub is_active: Option<bool>,
+    pub sync_enabled: Option<bool>,
+    pub sync_interval_hours: Option<i32>,
+    pub rate_limit_per_minute: Option<i32>,
+}
+
+impl UpdateProviderRequest {
+    /// Check the changes to provider `code` and convert them for the repository
+    fn validate(&self, code: &str) -> Result<UpdateProvider, String> {
+        if self.sync_enabled == Some(true) && !PROVIDER_CODES.contains(&code) {
+            return Err(format!("Provider '{}' has no client to sync with", code));
+        }
+        validate_limits(self.rate_limit_per_minute, self.sync_interval_hours)?;
+
+        let update = UpdateProvider {
+            is_active: self.is_active,
+            sync_enabled: self.sync_enabled,
+            sync_interval_hours: self.sync_interval_hours,
+            rate_limit_per_minute: self.rate_limit_per_minute,
+        };
+        if update.is_empty() {
+            return Err("No fields to update".to_string());
+        }
+        Ok(update)
+    }
+}
+
+fn validate_limits(
+    rate_limit_per_minute: Option<i32>,
+    sync_interval_hours: Option<i32>,
+) -> Result<(), String> {
+    if rate_limit_per_minute.is_some_and(|limit| limit < 1) {
+        return Err("rate_limit_per_minute must be at least 1".to_string());
+    }
+    if sync_interval_hours.is_some_and(|hours| hours < 1) {
+        return Err("sync_interval_hours must be at least 1".to_string());
+    }
+    Ok(())
+}
+
+fn invalid_request(message: String) -> HttpResponse {
+    HttpResponse::BadRequest().json(serde_json::json!({
+        "error": "invalid_request",
+        "message": message
+    }))
+}
+
+fn provider_not_found(code: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "not_found",
+        "message": format!("Provider '{}' not found", code)
+    }))
+}
+
+fn database_error(e: DbError, action: &str) -> HttpResponse {
+    if e.is_busy() {
+        return super::database_busy();
+    }
+    warn!(error = %e, "Failed to {}", action);
+    HttpResponse::InternalServerError().json(serde_json::json!({
+        "error": "internal_error",
+        "message": format!("Failed to {}", action)
+    }))
+}
+
+/// Key making the change, for the logs
+fn actor(req: &HttpRequest) -> Option<uuid::Uuid> {
+    req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id)
+}
+
+/// Add a provider
+/// POST /api/v1/catalog/providers
+///
+/// The code must be one the sync has a client for, unless `external_only`
+/// is set; external-only providers can't have sync enabled.
+#[utoipa::path(
+    post,
+    path = "/api/v1/catalog/providers",
+    tag = "catalog",
+    request_body = CreateProviderRequest,
+    responses(
+        (status = 201, description = "Provider added", body = ProviderResponse),
+        (status = 400, description = "Invalid provider"),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 409, description = "A provider with this code already exists")
+    )
+)]
+pub async fn create_provider(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    body: web::Json<CreateProviderRequest>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "add providers") {
+        return response;
+    }
+    let provider = match body.validate() {
+        Ok(provider) => provider,
+        Err(message) => return invalid_request(message),
+    };
+
+    let repo = ProviderRepository::new(pool.get_ref().clone());
+    match repo.create(&provider).await {
+        Ok(Some(created)) => {
+            info!(code = %created.code, added_by = ?actor(&req), "Provider added");
+            HttpResponse::Created().json(ProviderResponse::from(created))
+        }
+        Ok(None) => HttpResponse::Conflict().json(serde_json::json!({
+            "error": "conflict",
+            "message": format!("Provider '{}' already exists", provider.code)
+        })),
+        Err(e) => database_error(e, "add provider"),
+    }
+}
+
+/// Change a provider's status, sync settings, or rate limit
+/// PATCH /api/v1/catalog/providers/{code}
+///
+/// A new rate limit applies from the provider's next sync job.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/catalog/providers/{code}",
+    tag = "catalog",
+    params(
+        ("code" = String, Path, description = "Provider code, e.g. printful")
+    ),
+    request_body = UpdateProviderRequest,
+    responses(
+        (status = 200, description = "Provider updated", body = ProviderResponse),
+        (status = 400, description = "Invalid or empty update"),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 404, description = "Provider not found")
+    )
+)]
+pub async fn update_provider(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+    body: web::Json<UpdateProviderRequest>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "update providers") {
+        return response;
+    }
+    let code = path.into_inner();
+    let update = match body.validate(&code) {
+        Ok(update) => update,
+        Err(message) => return invalid_request(message),
+    };
+
+    let repo = ProviderRepository::new(pool.get_ref().clone());
+    match repo.update(&code, &update).await {
+        Ok(Some(updated)) => {
+            info!(code = %code, updated_by = ?actor(&req), "Provider updated");
+            HttpResponse::Ok().json(ProviderResponse::from(updated))
+        }
+        Ok(None) => provider_not_found(&code),
+        Err(e) => database_error(e, "update provider"),
+    }
+}
+
+/// Deactivate a provider
+/// DELETE /api/v1/catalog/providers/{code}
+///
+/// The provider's row and synced products are kept; it just stops syncing
+/// and being accepted by sync endpoints. Reactivate it with a PATCH.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/catalog/providers/{code}",
+    tag = "catalog",
+    params(
+        ("code" = String, Path, description = "Provider code, e.g. printful")
+    ),
+    responses(
+        (status = 200, description = "Provider deactivated", body = ProviderResponse),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 404, description = "Provider not found")
+    )
+)]
+pub async fn delete_provider(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "remove providers") {
+        return response;
+    }
+    let code = path.into_inner();
+
+    let repo = ProviderRepository::new(pool.get_ref().clone());
+    match repo.deactivate(&code).await {
+        Ok(Some(provider)) => {
+            info!(code = %code, removed_by = ?actor(&req), "Provider deactivated");
+            HttpResponse::Ok().json(ProviderResponse::from(provider))
+        }
+        Ok(None) => provider_not_found(&code),
+        Err(e) => database_error(e, "deactivate provider"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::tier_from_header;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+
+    fn create(json: serde_json::Value) -> CreateProviderRequest {
+        let mut body = serde_json::json!({
+            "code": "printful",
+            "name": "Printful",
+            "api_base_url": "https://api.printful.com",
+            "auth_type": "oauth2",
+            "rate_limit_per_minute": 120
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(json.as_object().unwrap().clone());
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_create_defaults() {
+        let provider = create(serde_json::json!({})).validate().unwrap();
+        assert!(provider.is_active);
+        assert!(!provider.sync_enabled);
+        assert_eq!(provider.sync_interval_hours, 24);
+    }
+
+    #[test]
+    fn test_unknown_codes_must_be_external_only() {
+        let error = create(serde_json::json!({"code": "teespring"}))
+            .validate()
+            .unwrap_err();
+        assert!(error.contains("external_only"));
+
+        let external = create(serde_json::json!({"code": "teespring", "external_only": true}));
+        assert_eq!(external.validate().unwrap().code, "teespring");
+
+        let syncing = create(serde_json::json!({
+            "code": "teespring", "external_only": true, "sync_enabled": true
+        }));
+        assert!(syncing.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_providers_are_rejected() {
+        for json in [
+            serde_json::json!({"code": "Print Ful", "external_only": true}),
+            serde_json::json!({"name": " "}),
+            serde_json::json!({"api_base_url": "api.printful.com"}),
+            serde_json::json!({"auth_type": "basic"}),
+            serde_json::json!({"rate_limit_per_minute": 0}),
+            serde_json::json!({"sync_interval_hours": 0}),
+        ] {
+            assert!(create(json.clone()).validate().is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn test_update_validation() {
+        let parse = |json: &str| serde_json::from_str::<UpdateProviderRequest>(json).unwrap();
+
+        let update = parse(r#"{"rate_limit_per_minute": 60}"#)
+            .validate("printful")
+            .unwrap();
+        assert_eq!(update.rate_limit_per_minute, Some(60));
+        assert_eq!(update.is_active, None);
+
+        assert!(parse("{}").validate("printful").is_err());
+        assert!(parse(r#"{"rate_limit_per_minute": 0}"#)
+            .validate("printful")
+            .is_err());
+        assert!(parse(r#"{"sync_enabled": true}"#)
+            .validate("teespring")
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_provider_changes_need_an_enterprise_key() {
+        // Never connected to: rejected requests don't reach the database
+        let pool = DbPool::new("postgres://nobody@127.0.0.1:1/none").unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .wrap(from_fn(tier_from_header))
+                .route("/providers", web::post().to(create_provider))
+                .route("/providers/{code}", web::patch().to(update_provider))
+                .route("/providers/{code}", web::delete().to(delete_provider)),
+        )
+        .await;
+
+        let requests = || {
+            [
+                test::TestRequest::post()
+                    .uri("/providers")
+                    .set_json(serde_json::json!({
+                        "code": "printful",
+                        "name": "Printful",
+                        "api_base_url": "https://api.printful.com",
+                        "auth_type": "oauth2",
+                        "rate_limit_per_minute": 120
+                    })),
+                test::TestRequest::patch()
+                    .uri("/providers/printful")
+                    .set_json(serde_json::json!({"rate_limit_per_minute": 60})),
+                test::TestRequest::delete().uri("/providers/printful"),
+            ]
+        };
+        for request in requests() {
+            let response = test::call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        for request in requests() {
+            let request = request.insert_header(("x-test-tier", "pro")).to_request();
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+
+        // Enterprise keys get as far as validation
+        let request = test::TestRequest::patch()
+            .uri("/providers/printful")
+            .insert_header(("x-test-tier", "enterprise"))
+            .set_json(serde_json::json!({}))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,60 @@
+//! Render history endpoints for deferred result uploads
+
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+use crate::api::middleware::ApiKeyAuth;
+use crate::uploads::RenderUploads;
+use crate::AppState;
+
+/// List recent renders whose uploads were deferred, newest first
+/// GET /api/v1/renders
+#[utoipa::path(
+    get,
+    path = "/api/v1/renders",
+    tag = "renders",
+    responses(
+        (status = 200, description = "The caller's recent renders as `renders`, with their `total`")
+    )
+)]
+pub async fn list_renders(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+    let renders = state.uploads.list(api_key_id);
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "renders": renders,
+        "total": renders.len()
+    }))
+}
+
+/// Upload status of a single render
+/// GET /api/v1/renders/{id}
+#[utoipa::path(
+    get,
+    path = "/api/v1/renders/{id}",
+    tag = "renders",
+    params(
+        ("id" = Uuid, Path, description = "Render ID from a generate response")
+    ),
+    responses(
+        (status = 200, description = "Upload status of each of the render's files", body = RenderUploads),
+        (status = 404, description = "Render not found or its record has expired")
+    )
+)]
+pub async fn get_render(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let api_key_id = req.extensions().get::<ApiKeyAuth>().map(|auth| auth.key_id);
+
+    // Renders belong to the key that created them, like job outputs
+    match state.uploads.get(id, api_key_id) {
+        Some(render) => HttpResponse::Ok().json(render),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "not_found",
+            "message": "Render not found or its record has expired"
+        })),
+    }
+}
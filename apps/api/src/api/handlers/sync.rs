@@ -2,18 +2,31 @@
 //!
 //! Endpoints for triggering and monitoring POD catalog synchronization.
 
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use super::admin::require_enterprise;
 use crate::db::DbPool;
-use crate::storage::{AssetPath, R2Client};
+use crate::providers::{ProviderCredentials, PROVIDER_CODES};
+use crate::storage::{AssetPath, R2Client, TemplateBackup, UsageReport};
+use crate::sync::{
+    CleanupSummary, SyncJob, SyncJobStatus, SyncJobType, SyncOrchestratorError, UmbrellaJob,
+    UmbrellaJobSummary,
+};
+use crate::AppState;
 
 /// Helper macro to get database client
 macro_rules! get_client {
     ($pool:expr) => {
         match $pool.get().await {
             Ok(c) => c,
+            Err(e) if e.is_busy() => {
+                tracing::warn!("Database connection wait timed out: {}", e);
+                return crate::api::handlers::database_busy();
+            }
             Err(e) => {
                 tracing::error!("Failed to get database connection: {}", e);
                 return HttpResponse::InternalServerError().json(serde_json::json!({
@@ -25,81 +38,144 @@ macro_rules! get_client {
 }
 
 /// Request to start a sync job
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct StartSyncRequest {
-    /// Type of sync to perform
+    /// Type of sync to perform: full_catalog, incremental, single_product, or assets_only
     #[serde(default = "default_job_type")]
+    #[schema(default = "full_catalog")]
     pub job_type: String,
     /// Optional product ID for single product sync
     pub product_id: Option<String>,
+    /// Continue from the cursor of the provider's last interrupted job
+    #[serde(default)]
+    pub resume: bool,
 }
 
 fn default_job_type() -> String {
     "full_catalog".to_string()
 }
 
+/// Query parameters for an orphaned asset cleanup
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CleanupQuery {
+    /// Only report orphans; pass false to delete them
+    #[serde(default = "default_dry_run")]
+    #[param(default = true)]
+    pub dry_run: bool,
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
 /// R2 storage status response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct R2StatusResponse {
     pub configured: bool,
     pub bucket_name: Option<String>,
     pub account_id: Option<String>,
 }
 
-/// List all sync jobs
-pub async fn list_jobs(pool: web::Data<DbPool>) -> HttpResponse {
-    let client = get_client!(pool);
+/// Longest prefix accepted by the usage report, the longest R2 key
+const MAX_USAGE_PREFIX_BYTES: usize = 1024;
 
-    let sql = r#"
-        SELECT
-            j.id, pr.code as provider_code, j.job_type, j.status,
-            j.total_items, j.processed_items, j.failed_items,
-            j.started_at, j.completed_at, j.error_message
-        FROM pod_sync_jobs j
-        JOIN pod_providers pr ON j.provider_id = pr.id
-        ORDER BY j.started_at DESC NULLS LAST
-        LIMIT 50
-    "#;
+/// Query parameters for the R2 usage report
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct R2UsageQuery {
+    /// Only count objects whose keys start with this, e.g. `printful/`
+    pub prefix: Option<String>,
+    /// Walk the bucket again even when the cached report is current
+    #[serde(default)]
+    pub force_refresh: bool,
+}
+
+/// R2 storage used by each provider's assets
+#[derive(Debug, Serialize, ToSchema)]
+pub struct R2UsageResponse {
+    /// `ready`, `stale` when the report is older than the cache TTL, or
+    /// `refreshing` while a forced or first walk runs
+    pub status: String,
+    pub prefix: String,
+    /// When the report's walk finished; absent until the first one does
+    pub as_of: Option<DateTime<Utc>>,
+    /// A walk of the bucket is running in the background
+    pub refreshing: bool,
+    /// Why the last walk failed, if it did
+    pub error: Option<String>,
+    /// Usage by provider and asset type, with totals
+    pub report: Option<UsageReport>,
+}
+
+/// API view of a sync job
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncJobResponse {
+    pub id: Uuid,
+    pub provider_code: String,
+    pub job_type: SyncJobType,
+    pub status: SyncJobStatus,
+    pub total_items: u32,
+    pub processed_items: u32,
+    pub failed_items: u32,
+    pub skipped_items: u32,
+    pub progress_percent: f32,
+    /// RFC 3339 timestamps
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+    /// Last time the worker running the job reported in
+    pub heartbeat_at: Option<String>,
+    pub duration_secs: Option<i64>,
+    /// Next catalog page to fetch, for resuming an interrupted job
+    pub cursor: Option<String>,
+    pub product_id: Option<String>,
+    /// Umbrella job from POST /sync/all this job belongs to
+    pub parent_job_id: Option<Uuid>,
+    pub error_message: Option<String>,
+    /// What an asset cleanup found and removed
+    pub cleanup: Option<CleanupSummary>,
+}
 
-    match client.query(sql, &[]).await {
-        Ok(rows) => {
-            let jobs: Vec<serde_json::Value> = rows
-                .iter()
-                .map(|row| {
-                    let started_at: Option<chrono::DateTime<chrono::Utc>> = row.get("started_at");
-                    let completed_at: Option<chrono::DateTime<chrono::Utc>> =
-                        row.get("completed_at");
-                    let total: i32 = row.get("total_items");
-                    let processed: i32 = row.get("processed_items");
-                    let progress = if total > 0 {
-                        (processed as f32 / total as f32) * 100.0
-                    } else {
-                        0.0
-                    };
-
-                    let duration = match (started_at, completed_at) {
-                        (Some(start), Some(end)) => Some((end - start).num_seconds()),
-                        (Some(start), None) => Some((chrono::Utc::now() - start).num_seconds()),
-                        _ => None,
-                    };
-
-                    serde_json::json!({
-                        "id": row.get::<_, Uuid>("id"),
-                        "provider_code": row.get::<_, String>("provider_code"),
-                        "job_type": row.get::<_, String>("job_type"),
-                        "status": row.get::<_, String>("status"),
-                        "total_items": total,
-                        "processed_items": processed,
-                        "failed_items": row.get::<_, i32>("failed_items"),
-                        "progress_percent": progress,
-                        "started_at": started_at.map(|dt| dt.to_rfc3339()),
-                        "completed_at": completed_at.map(|dt| dt.to_rfc3339()),
-                        "duration_secs": duration,
-                        "error_message": row.get::<_, Option<String>>("error_message"),
-                    })
-                })
-                .collect();
+impl From<&SyncJob> for SyncJobResponse {
+    fn from(job: &SyncJob) -> Self {
+        Self {
+            id: job.id,
+            provider_code: job.provider_code.clone(),
+            job_type: job.job_type,
+            status: job.status,
+            total_items: job.total_items,
+            processed_items: job.processed_items,
+            failed_items: job.failed_items,
+            skipped_items: job.skipped_items,
+            progress_percent: job.progress(),
+            created_at: job.created_at.to_rfc3339(),
+            started_at: job.started_at.map(|dt| dt.to_rfc3339()),
+            completed_at: job.completed_at.map(|dt| dt.to_rfc3339()),
+            heartbeat_at: job.heartbeat_at.map(|dt| dt.to_rfc3339()),
+            duration_secs: job.duration_secs(),
+            cursor: job.cursor.clone(),
+            product_id: job.product_id.clone(),
+            parent_job_id: job.parent_job_id,
+            error_message: job.error_message.clone(),
+            cleanup: job.cleanup,
+        }
+    }
+}
 
+/// List all sync jobs
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync/jobs",
+    tag = "sync",
+    responses(
+        (status = 200, description = "The 50 most recent sync jobs", body = Vec<SyncJobResponse>)
+    )
+)]
+pub async fn list_jobs(state: web::Data<AppState>) -> HttpResponse {
+    match state.sync_jobs.list(50).await {
+        Ok(jobs) => {
+            let jobs: Vec<SyncJobResponse> = jobs.iter().map(SyncJobResponse::from).collect();
             HttpResponse::Ok().json(jobs)
         }
         Err(e) => {
@@ -112,53 +188,21 @@ pub async fn list_jobs(pool: web::Data<DbPool>) -> HttpResponse {
 }
 
 /// Get sync job by ID
-pub async fn get_job(pool: web::Data<DbPool>, path: web::Path<Uuid>) -> HttpResponse {
-    let client = get_client!(pool);
-    let job_id = path.into_inner();
-
-    let sql = r#"
-        SELECT
-            j.id, pr.code as provider_code, j.job_type, j.status,
-            j.total_items, j.processed_items, j.failed_items,
-            j.started_at, j.completed_at, j.error_message
-        FROM pod_sync_jobs j
-        JOIN pod_providers pr ON j.provider_id = pr.id
-        WHERE j.id = $1
-    "#;
-
-    match client.query_opt(sql, &[&job_id]).await {
-        Ok(Some(row)) => {
-            let started_at: Option<chrono::DateTime<chrono::Utc>> = row.get("started_at");
-            let completed_at: Option<chrono::DateTime<chrono::Utc>> = row.get("completed_at");
-            let total: i32 = row.get("total_items");
-            let processed: i32 = row.get("processed_items");
-            let progress = if total > 0 {
-                (processed as f32 / total as f32) * 100.0
-            } else {
-                0.0
-            };
-
-            let duration = match (started_at, completed_at) {
-                (Some(start), Some(end)) => Some((end - start).num_seconds()),
-                (Some(start), None) => Some((chrono::Utc::now() - start).num_seconds()),
-                _ => None,
-            };
-
-            HttpResponse::Ok().json(serde_json::json!({
-                "id": row.get::<_, Uuid>("id"),
-                "provider_code": row.get::<_, String>("provider_code"),
-                "job_type": row.get::<_, String>("job_type"),
-                "status": row.get::<_, String>("status"),
-                "total_items": total,
-                "processed_items": processed,
-                "failed_items": row.get::<_, i32>("failed_items"),
-                "progress_percent": progress,
-                "started_at": started_at.map(|dt| dt.to_rfc3339()),
-                "completed_at": completed_at.map(|dt| dt.to_rfc3339()),
-                "duration_secs": duration,
-                "error_message": row.get::<_, Option<String>>("error_message"),
-            }))
-        }
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync/jobs/{id}",
+    tag = "sync",
+    params(
+        ("id" = Uuid, Path, description = "Sync job ID")
+    ),
+    responses(
+        (status = 200, description = "The sync job", body = SyncJobResponse),
+        (status = 404, description = "Sync job not found")
+    )
+)]
+pub async fn get_job(state: web::Data<AppState>, path: web::Path<Uuid>) -> HttpResponse {
+    match state.sync_jobs.get(path.into_inner()).await {
+        Ok(Some(job)) => HttpResponse::Ok().json(SyncJobResponse::from(&job)),
         Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
             "error": "Sync job not found"
         })),
@@ -171,82 +215,562 @@ pub async fn get_job(pool: web::Data<DbPool>, path: web::Path<Uuid>) -> HttpResp
     }
 }
 
+/// Cancel a pending or running sync job
+/// POST /api/v1/sync/jobs/{id}/cancel
+///
+/// Responds with the cancelled job, or 409 with the status of a job that already finished.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync/jobs/{id}/cancel",
+    tag = "sync",
+    params(
+        ("id" = Uuid, Path, description = "Sync job ID")
+    ),
+    responses(
+        (status = 200, description = "The cancelled job", body = SyncJobResponse),
+        (status = 404, description = "Sync job not found"),
+        (status = 409, description = "Job already finished")
+    )
+)]
+pub async fn cancel_job(state: web::Data<AppState>, path: web::Path<Uuid>) -> HttpResponse {
+    match state.sync_scheduler.cancel_job(path.into_inner()).await {
+        Ok(job) => HttpResponse::Ok().json(SyncJobResponse::from(&job)),
+        Err(SyncOrchestratorError::JobNotFound(_)) => {
+            HttpResponse::NotFound().json(serde_json::json!({
+                "error": "Sync job not found"
+            }))
+        }
+        Err(SyncOrchestratorError::JobFinished(_, status)) => {
+            HttpResponse::Conflict().json(serde_json::json!({
+                "error": "Sync job already finished",
+                "status": status
+            }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to cancel sync job: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to cancel sync job"
+            }))
+        }
+    }
+}
+
 /// Start a sync job for a provider
+///
+/// `job_type` is `full_catalog` (the default), `incremental`, which skips
+/// products unchanged since the last sync, `single_product`, which needs a
+/// `product_id`, or `assets_only`, which retries failed asset downloads. With
+/// `resume`, a catalog sync continues from the cursor of the provider's last
+/// failed or cancelled job instead of the first catalog page.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync/{provider}/start",
+    tag = "sync",
+    params(
+        ("provider" = String, Path, description = "Provider code, e.g. printful")
+    ),
+    request_body = StartSyncRequest,
+    responses(
+        (status = 202, description = "Sync job started; poll it under /api/v1/sync/jobs/{id}"),
+        (status = 400, description = "Unknown job type or missing product_id"),
+        (status = 404, description = "Provider not found or inactive"),
+        (status = 409, description = "A sync job is already running for this provider")
+    )
+)]
 pub async fn start_sync(
     pool: web::Data<DbPool>,
+    state: web::Data<AppState>,
     path: web::Path<String>,
     body: web::Json<StartSyncRequest>,
 ) -> HttpResponse {
-    let client = get_client!(pool);
     let provider_code = path.into_inner();
+    if let Err(response) = check_provider(&pool, &provider_code).await {
+        return response;
+    }
 
-    // Get provider ID
-    let provider_sql = "SELECT id FROM pod_providers WHERE code = $1 AND is_active = true";
-    let provider_id: Uuid = match client.query_opt(provider_sql, &[&provider_code]).await {
-        Ok(Some(row)) => row.get("id"),
-        Ok(None) => {
-            return HttpResponse::NotFound().json(serde_json::json!({
-                "error": format!("Provider '{}' not found or not active", provider_code)
+    let job_type = match SyncJobType::parse(&body.job_type) {
+        Some(SyncJobType::AssetCleanup) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Asset cleanups are started through POST /api/v1/sync/{provider}/cleanup"
             }));
         }
-        Err(e) => {
-            tracing::error!("Failed to get provider: {}", e);
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to get provider"
+        Some(job_type) => job_type,
+        None => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Unknown sync job type '{}'", body.job_type)
             }));
         }
     };
 
-    // Check for running jobs
-    let running_sql = r#"
-        SELECT id FROM pod_sync_jobs
-        WHERE provider_id = $1 AND status = 'running'
-        LIMIT 1
-    "#;
-    if let Ok(rows) = client.query(running_sql, &[&provider_id]).await {
-        if !rows.is_empty() {
-            return HttpResponse::Conflict().json(serde_json::json!({
+    let mut job = SyncJob::new(&provider_code, job_type);
+    if job_type == SyncJobType::SingleProduct {
+        match body.product_id.as_deref().map(str::trim) {
+            Some(product_id) if !product_id.is_empty() => {
+                job = SyncJob::single_product(&provider_code, product_id);
+            }
+            _ => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": "product_id is required for single_product syncs"
+                }));
+            }
+        }
+    } else if body.resume && job_type != SyncJobType::AssetsOnly {
+        match state.sync_jobs.latest(&provider_code).await {
+            Ok(Some(previous)) if previous.is_resumable() => job = job.resume_from(&previous),
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Failed to look up last sync job: {}", e);
+                return HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": "Failed to start sync job"
+                }));
+            }
+        }
+    }
+
+    match state.sync_scheduler.schedule_provider(job).await {
+        Ok(job) => {
+            tracing::info!("Started sync job {} for provider {}", job.id, provider_code);
+
+            HttpResponse::Accepted().json(serde_json::json!({
+                "message": "Sync job started",
+                "job_id": job.id,
+                "provider": provider_code,
+                "job_type": job.job_type,
+                "status": job.status,
+                "product_id": job.product_id,
+                "cursor": job.cursor
+            }))
+        }
+        Err(SyncOrchestratorError::JobAlreadyRunning(_)) => {
+            let running = state.sync_jobs.latest(&provider_code).await.ok().flatten();
+            HttpResponse::Conflict().json(serde_json::json!({
                 "error": "A sync job is already running for this provider",
-                "job_id": rows[0].get::<_, Uuid>("id")
-            }));
+                "job_id": running.map(|job| job.id)
+            }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to create sync job: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to start sync job"
+            }))
         }
     }
+}
 
-    // Create new job
-    let job_id = Uuid::new_v4();
-    let job_type = &body.job_type;
+/// Sync one product and its assets, for fixing a single broken catalog entry
+/// POST /api/v1/sync/{provider}/products/{external_id}
+///
+/// Runs the sync before responding. A product the provider doesn't know
+/// fails the job, which is returned with a 502 and the provider's error.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync/{provider}/products/{external_id}",
+    tag = "sync",
+    params(
+        ("provider" = String, Path, description = "Provider code, e.g. printful"),
+        ("external_id" = String, Path, description = "The provider's product ID")
+    ),
+    responses(
+        (status = 200, description = "The completed sync job", body = SyncJobResponse),
+        (status = 404, description = "Provider not found or inactive"),
+        (status = 409, description = "A sync job is already running for this provider"),
+        (status = 502, description = "Product sync failed; the failed job is included")
+    )
+)]
+pub async fn sync_product(
+    pool: web::Data<DbPool>,
+    state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (provider_code, external_id) = path.into_inner();
+    if let Err(response) = check_provider(&pool, &provider_code).await {
+        return response;
+    }
 
-    let insert_sql = r#"
-        INSERT INTO pod_sync_jobs (id, provider_id, job_type, status, started_at)
-        VALUES ($1, $2, $3, 'running', NOW())
-        RETURNING id
-    "#;
+    match state
+        .sync_scheduler
+        .sync_product(&provider_code, &external_id)
+        .await
+    {
+        Ok(job) if job.status == SyncJobStatus::Completed => {
+            HttpResponse::Ok().json(SyncJobResponse::from(&job))
+        }
+        Ok(job) => HttpResponse::BadGateway().json(serde_json::json!({
+            "error": "Product sync failed",
+            "job": SyncJobResponse::from(&job)
+        })),
+        Err(SyncOrchestratorError::JobAlreadyRunning(_)) => {
+            let running = state.sync_jobs.latest(&provider_code).await.ok().flatten();
+            HttpResponse::Conflict().json(serde_json::json!({
+                "error": "A sync job is already running for this provider",
+                "job_id": running.map(|job| job.id)
+            }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to sync product {}: {}", external_id, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to sync product"
+            }))
+        }
+    }
+}
 
+/// Remove a provider's R2 objects that no synced asset points at
+/// POST /api/v1/sync/{provider}/cleanup
+///
+/// Lists the keys under `{provider}/` and checks them against the catalog's
+/// asset rows in batches. By default only reports the orphans; with
+/// `dry_run=false` deletes them. Runs before responding and is recorded as
+/// an `asset_cleanup` sync job, whose `cleanup` field holds the summary.
+/// Needs an enterprise key; inactive providers can be cleaned up too.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync/{provider}/cleanup",
+    tag = "sync",
+    params(
+        ("provider" = String, Path, description = "Provider code, e.g. printful"),
+        CleanupQuery
+    ),
+    responses(
+        (status = 200, description = "The completed cleanup job", body = SyncJobResponse),
+        (status = 400, description = "Provider code names a protected folder"),
+        (status = 403, description = "Only enterprise tier keys can clean up assets"),
+        (status = 404, description = "Provider not found"),
+        (status = 409, description = "A sync job is already running for this provider"),
+        (status = 502, description = "Cleanup failed; the failed job is included"),
+        (status = 503, description = "Storage not configured")
+    )
+)]
+pub async fn cleanup_assets(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<CleanupQuery>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "clean up assets") {
+        return response;
+    }
+    let provider_code = path.into_inner();
+    if state.storage.is_none() {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "not_configured",
+            "message": "Storage not configured"
+        }));
+    }
+    // A deactivated provider's objects are still worth cleaning up
+    if let Err(response) = find_provider(&pool, &provider_code, false).await {
+        return response;
+    }
+
+    match state
+        .sync_scheduler
+        .cleanup_orphaned_assets(&provider_code, query.dry_run)
+        .await
+    {
+        Ok(job) if job.status == SyncJobStatus::Completed => {
+            HttpResponse::Ok().json(SyncJobResponse::from(&job))
+        }
+        Ok(job) => HttpResponse::BadGateway().json(serde_json::json!({
+            "error": "Asset cleanup failed",
+            "job": SyncJobResponse::from(&job)
+        })),
+        Err(e @ SyncOrchestratorError::ProtectedPrefix(_)) => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e.to_string()
+            }))
+        }
+        Err(SyncOrchestratorError::JobAlreadyRunning(_)) => {
+            let running = state.sync_jobs.latest(&provider_code).await.ok().flatten();
+            HttpResponse::Conflict().json(serde_json::json!({
+                "error": "A sync job is already running for this provider",
+                "job_id": running.map(|job| job.id)
+            }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to clean up assets for {}: {}", provider_code, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to clean up assets"
+            }))
+        }
+    }
+}
+
+/// Ensure a provider exists and is active
+async fn check_provider(pool: &DbPool, provider_code: &str) -> Result<(), HttpResponse> {
+    find_provider(pool, provider_code, true).await
+}
+
+/// Ensure a provider exists, and with `active_only` that it is active
+async fn find_provider(
+    pool: &DbPool,
+    provider_code: &str,
+    active_only: bool,
+) -> Result<(), HttpResponse> {
+    let client = match pool.get().await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::error!("Failed to get database connection: {}", e);
+            return Err(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Database connection failed"
+            })));
+        }
+    };
+
+    let provider_sql = "SELECT id FROM pod_providers WHERE code = $1 AND (is_active OR NOT $2)";
     match client
-        .query_one(insert_sql, &[&job_id, &provider_id, job_type])
+        .query_opt(provider_sql, &[&provider_code, &active_only])
         .await
     {
-        Ok(_) => {
-            tracing::info!("Started sync job {} for provider {}", job_id, provider_code);
+        Ok(Some(_)) => Ok(()),
+        Ok(None) if active_only => Err(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("Provider '{}' not found or not active", provider_code)
+        }))),
+        Ok(None) => Err(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("Provider '{}' not found", provider_code)
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to get provider: {}", e);
+            Err(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to get provider"
+            })))
+        }
+    }
+}
 
-            HttpResponse::Accepted().json(serde_json::json!({
-                "message": "Sync job started",
-                "job_id": job_id,
-                "provider": provider_code,
-                "job_type": job_type,
-                "status": "running"
+/// 409 for a sync of all providers while `running` is unfinished
+fn sync_all_running(running: &UmbrellaJob) -> HttpResponse {
+    HttpResponse::Conflict().json(serde_json::json!({
+        "error": "A sync of all providers is already running",
+        "job_id": running.id
+    }))
+}
+
+/// Start a full catalog sync for every enabled provider
+/// POST /api/v1/sync/all
+///
+/// Providers run under the global sync concurrency limit and are reported
+/// through a single umbrella job aggregating one child job per provider.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync/all",
+    tag = "sync",
+    responses(
+        (status = 202, description = "Umbrella job scheduled, with the providers it covers and those skipped"),
+        (status = 400, description = "No sync-enabled provider has credentials configured"),
+        (status = 409, description = "A sync of all providers is already running")
+    )
+)]
+pub async fn start_sync_all(pool: web::Data<DbPool>, state: web::Data<AppState>) -> HttpResponse {
+    let scheduler = &state.sync_scheduler;
+
+    if let Some(running) = scheduler.running_umbrella() {
+        return sync_all_running(&running);
+    }
+
+    let client = get_client!(pool);
+
+    let sql = r#"
+        SELECT code FROM pod_providers
+        WHERE is_active = true AND sync_enabled = true
+        ORDER BY code
+    "#;
+    let enabled: Vec<String> = match client.query(sql, &[]).await {
+        Ok(rows) => rows.iter().map(|row| row.get("code")).collect(),
+        Err(e) => {
+            tracing::error!("Failed to list sync-enabled providers: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to list providers"
+            }));
+        }
+    };
+
+    // Only providers we have an implementation and credentials for can sync
+    let (providers, skipped): (Vec<String>, Vec<String>) = enabled.into_iter().partition(|code| {
+        PROVIDER_CODES.contains(&code.as_str())
+            && ProviderCredentials::from_env(code).is_configured()
+    });
+
+    if providers.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "No sync-enabled providers have credentials configured",
+            "skipped": skipped
+        }));
+    }
+
+    // Checked again as the job is scheduled, in case another request started one meanwhile
+    let umbrella = match scheduler.schedule_all(providers.clone()) {
+        Ok(umbrella) => umbrella,
+        Err(running) => return sync_all_running(&running),
+    };
+
+    HttpResponse::Accepted().json(serde_json::json!({
+        "message": "Sync scheduled for all enabled providers",
+        "job_id": umbrella.id,
+        "providers": providers,
+        "skipped": skipped,
+        "job": umbrella.summary()
+    }))
+}
+
+/// Next scheduled sync of each sync-enabled provider
+/// GET /api/v1/sync/schedule
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync/schedule",
+    tag = "sync",
+    responses(
+        (status = 200, description = "Whether scheduled syncs are enabled, and each provider's next run")
+    )
+)]
+pub async fn get_schedule(state: web::Data<AppState>) -> HttpResponse {
+    match state.sync_schedule {
+        Some(ref schedule) => {
+            let snapshot = schedule.snapshot();
+            HttpResponse::Ok().json(serde_json::json!({
+                "enabled": true,
+                "tick_secs": snapshot.tick_secs,
+                "checked_at": snapshot.checked_at,
+                "next_check_at": snapshot.next_check_at,
+                "providers": snapshot.providers
             }))
         }
+        None => HttpResponse::Ok().json(serde_json::json!({
+            "enabled": false,
+            "providers": []
+        })),
+    }
+}
+
+/// List umbrella jobs started by POST /sync/all
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync/all",
+    tag = "sync",
+    responses(
+        (status = 200, description = "Umbrella jobs with their per-provider child jobs", body = Vec<UmbrellaJobSummary>)
+    )
+)]
+pub async fn list_sync_all_jobs(state: web::Data<AppState>) -> HttpResponse {
+    let jobs: Vec<_> = state
+        .sync_scheduler
+        .list_umbrellas()
+        .iter()
+        .map(|job| job.summary())
+        .collect();
+
+    HttpResponse::Ok().json(jobs)
+}
+
+/// Get an umbrella job with its per-provider child jobs
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync/all/{id}",
+    tag = "sync",
+    params(
+        ("id" = Uuid, Path, description = "Umbrella job ID")
+    ),
+    responses(
+        (status = 200, description = "The umbrella job", body = UmbrellaJobSummary),
+        (status = 404, description = "Sync job not found")
+    )
+)]
+pub async fn get_sync_all_job(state: web::Data<AppState>, path: web::Path<Uuid>) -> HttpResponse {
+    match state.sync_scheduler.get_umbrella(path.into_inner()) {
+        Some(job) => HttpResponse::Ok().json(job.summary()),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Sync job not found"
+        })),
+    }
+}
+
+/// Back up the local templates directory to R2
+/// POST /api/v1/sync/templates/backup
+///
+/// Only new or changed files are uploaded; the manifest records checksums.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync/templates/backup",
+    tag = "sync",
+    responses(
+        (status = 200, description = "Backup summary; status is partial when some files failed"),
+        (status = 503, description = "R2 not configured")
+    )
+)]
+pub async fn backup_templates(state: web::Data<AppState>) -> HttpResponse {
+    let client = match R2Client::from_env().await {
+        Ok(client) => client,
         Err(e) => {
-            tracing::error!("Failed to create sync job: {}", e);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "status": "not_configured",
+                "message": format!("R2 not configured: {}", e)
+            }));
+        }
+    };
+
+    let backup = TemplateBackup::new(client, &state.settings.templates.path);
+    match backup.backup().await {
+        Ok(summary) => HttpResponse::Ok().json(serde_json::json!({
+            "status": if summary.failed.is_empty() { "ok" } else { "partial" },
+            "summary": summary
+        })),
+        Err(e) => {
+            tracing::error!("Template backup failed: {}", e);
             HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to start sync job"
+                "status": "error",
+                "message": format!("Template backup failed: {}", e)
+            }))
+        }
+    }
+}
+
+/// Compare local template checksums against the R2 backup
+/// GET /api/v1/sync/templates/drift
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync/templates/drift",
+    tag = "sync",
+    responses(
+        (status = 200, description = "Files added, changed, or missing since the last backup"),
+        (status = 503, description = "R2 not configured")
+    )
+)]
+pub async fn template_drift(state: web::Data<AppState>) -> HttpResponse {
+    let client = match R2Client::from_env().await {
+        Ok(client) => client,
+        Err(e) => {
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "status": "not_configured",
+                "message": format!("R2 not configured: {}", e)
+            }));
+        }
+    };
+
+    let backup = TemplateBackup::new(client, &state.settings.templates.path);
+    match backup.drift().await {
+        Ok(drift) => HttpResponse::Ok().json(serde_json::json!({
+            "status": if drift.has_drift() { "drift" } else { "in_sync" },
+            "drift": drift
+        })),
+        Err(e) => {
+            tracing::error!("Template drift check failed: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "status": "error",
+                "message": format!("Template drift check failed: {}", e)
             }))
         }
     }
 }
 
 /// Get R2 storage status
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync/r2/status",
+    tag = "sync",
+    responses(
+        (status = 200, description = "Whether R2 credentials are configured", body = R2StatusResponse)
+    )
+)]
 pub async fn get_r2_status() -> HttpResponse {
     let account_id = std::env::var("R2_ACCOUNT_ID")
         .or_else(|_| std::env::var("MOCKUP_R2__ACCOUNT_ID"))
@@ -273,7 +797,81 @@ pub async fn get_r2_status() -> HttpResponse {
     })
 }
 
+/// Report R2 storage usage by provider and asset type
+///
+/// Walking the bucket takes a while, so reports are computed in the
+/// background and served until `sync.usage_report_ttl_secs` passes. An
+/// expired report is still returned while a new walk runs.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sync/r2/usage",
+    tag = "sync",
+    params(R2UsageQuery),
+    responses(
+        (status = 200, description = "The latest report", body = R2UsageResponse),
+        (status = 202, description = "A walk was started; the previous report, if any, is included", body = R2UsageResponse),
+        (status = 400, description = "Prefix is too long"),
+        (status = 503, description = "R2 not configured")
+    )
+)]
+pub async fn get_r2_usage(
+    state: web::Data<AppState>,
+    query: web::Query<R2UsageQuery>,
+) -> HttpResponse {
+    let Some(ref reports) = state.r2_usage else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "not_configured",
+            "message": "R2 not configured"
+        }));
+    };
+
+    let prefix = query
+        .prefix
+        .as_deref()
+        .unwrap_or("")
+        .trim_start_matches('/');
+    if prefix.len() > MAX_USAGE_PREFIX_BYTES {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("prefix must be at most {} bytes", MAX_USAGE_PREFIX_BYTES)
+        }));
+    }
+
+    let snapshot = reports.get(prefix, query.force_refresh);
+    let report = snapshot.report.as_deref().cloned();
+    let accepted = query.force_refresh || report.is_none();
+    let status = if accepted {
+        "refreshing"
+    } else if snapshot.stale {
+        "stale"
+    } else {
+        "ready"
+    };
+    let body = R2UsageResponse {
+        status: status.to_string(),
+        prefix: prefix.to_string(),
+        as_of: report.as_ref().map(|report| report.as_of),
+        refreshing: snapshot.refreshing,
+        error: snapshot.last_error,
+        report,
+    };
+
+    if accepted {
+        HttpResponse::Accepted().json(body)
+    } else {
+        HttpResponse::Ok().json(body)
+    }
+}
+
 /// Test R2 connectivity
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync/r2/test",
+    tag = "sync",
+    responses(
+        (status = 200, description = "Bucket reachable, with a sample key"),
+        (status = 503, description = "R2 not configured or unreachable")
+    )
+)]
 pub async fn test_r2() -> HttpResponse {
     match R2Client::from_env().await {
         Ok(client) => {
@@ -300,6 +898,15 @@ pub async fn test_r2() -> HttpResponse {
 }
 
 /// Upload test file to R2
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync/r2/test-upload",
+    tag = "sync",
+    responses(
+        (status = 200, description = "Test object uploaded, with its key and public URL"),
+        (status = 503, description = "R2 not configured or the upload failed")
+    )
+)]
 pub async fn test_r2_upload() -> HttpResponse {
     match R2Client::from_env().await {
         Ok(client) => {
@@ -338,3 +945,47 @@ pub async fn test_r2_upload() -> HttpResponse {
         })),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::{bare_state, tier_from_header};
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn test_cleanup_needs_an_enterprise_key() {
+        // Never connected to: rejected requests don't reach the database
+        let pool = DbPool::new("postgres://nobody@127.0.0.1:1/none").unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(bare_state()))
+                .wrap(from_fn(tier_from_header))
+                .route("/sync/{provider}/cleanup", web::post().to(cleanup_assets)),
+        )
+        .await;
+
+        let request = test::TestRequest::post().uri("/sync/printful/cleanup?dry_run=false");
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        for tier in ["free", "pro"] {
+            let request = test::TestRequest::post()
+                .uri("/sync/printful/cleanup?dry_run=false")
+                .insert_header(("x-test-tier", tier))
+                .to_request();
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{tier}");
+        }
+
+        // Enterprise keys get as far as the storage check
+        let request = test::TestRequest::post()
+            .uri("/sync/printful/cleanup")
+            .insert_header(("x-test-tier", "enterprise"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -1,11 +1,25 @@
 //! Template management endpoints
 
-use actix_web::{web, HttpResponse};
-use serde::Serialize;
+use actix_multipart::Multipart;
+use actix_web::http::header::{self, ETag, EntityTag, IfNoneMatch};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder};
+use futures::StreamExt;
+use image::{DynamicImage, ImageError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 use tracing::{error, info};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::db::models::TemplateInfo;
+use super::admin::require_enterprise;
+use super::generate::read_field;
+use crate::db::models::{NewTemplate, TemplateInfo};
+use crate::domain::{PlacementPreset, PlacementSpec};
+use crate::engine::{
+    geometry_test_pattern, AnchorPoint, DesignLayer, DesignSource, GenerationLimits,
+    InstalledTemplate, JpegPreset, MockupRequest, MockupResult, OutputFormat, OutputSettings,
+    PrintArea, TemplateError, TemplateGeometry, TemplateImages, TemplateLoadReport,
+    TemplateMetadata, TemplateReloadSummary, TemplateUpload,
+};
 use crate::AppState;
 
 /// Response for listing templates
@@ -159,6 +173,187 @@ pub async fn get_template(state: web::Data<AppState>, path: web::Path<String>) -
     }
 }
 
+/// Query parameters for a template preview
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    /// `jpeg` (default) or `webp`
+    #[serde(default)]
+    pub format: Option<OutputFormat>,
+}
+
+/// How long clients may reuse a preview before revalidating it
+const PREVIEW_CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// GET /api/v1/templates/{template_id}/preview - Downscaled base image of a template
+#[utoipa::path(
+    get,
+    path = "/api/v1/templates/{template_id}/preview",
+    tag = "templates",
+    params(
+        ("template_id" = String, Path, description = "Template identifier (e.g., 'white-tshirt-front')"),
+        ("format" = Option<OutputFormat>, Query, description = "Image format: jpeg (default) or webp")
+    ),
+    responses(
+        (status = 200, description = "Preview image as image/jpeg or image/webp"),
+        (status = 304, description = "Preview unchanged since the given ETag"),
+        (status = 400, description = "Unsupported format", body = TemplateErrorResponse),
+        (status = 404, description = "Template not found", body = TemplateErrorResponse),
+        (status = 503, description = "Template files missing on disk", body = TemplateErrorResponse)
+    )
+)]
+pub async fn get_template_preview(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<PreviewQuery>,
+) -> HttpResponse {
+    let template_id = path.into_inner();
+    let format = query.format.unwrap_or(OutputFormat::Jpeg);
+    if format == OutputFormat::Png {
+        return template_error(
+            HttpResponse::BadRequest(),
+            "INVALID_FORMAT",
+            "preview format must be jpeg or webp".to_string(),
+        );
+    }
+
+    let Some(template) = state.template_manager.get(&template_id) else {
+        return template_error(
+            HttpResponse::NotFound(),
+            "TEMPLATE_NOT_FOUND",
+            format!("Template '{}' does not exist", template_id),
+        );
+    };
+
+    let max_dimension = state.settings.templates.preview_max_dimension;
+    let etag = EntityTag::new_strong(format!(
+        "{}-v{}-{}.{}",
+        template.metadata.id,
+        template.metadata.version,
+        max_dimension,
+        format.extension()
+    ));
+    let unchanged = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+    if unchanged {
+        return HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header((header::CACHE_CONTROL, PREVIEW_CACHE_CONTROL))
+            .finish();
+    }
+
+    match state
+        .template_manager
+        .preview(&template, format, max_dimension)
+        .await
+    {
+        Ok(preview) => HttpResponse::Ok()
+            .content_type(preview.format.content_type())
+            .insert_header(ETag(etag))
+            .insert_header((header::CACHE_CONTROL, PREVIEW_CACHE_CONTROL))
+            .body(preview.bytes.clone()),
+        Err(
+            e @ (TemplateError::FilesMissing(_) | TemplateError::ImageLoad(ImageError::IoError(_))),
+        ) => {
+            error!(error = %e, template_id = %template_id, "Template files unavailable for preview");
+            template_error(
+                HttpResponse::ServiceUnavailable(),
+                "TEMPLATE_FILES_MISSING",
+                e.to_string(),
+            )
+        }
+        Err(e) => {
+            error!(error = %e, template_id = %template_id, "Failed to render template preview");
+            template_error(
+                HttpResponse::InternalServerError(),
+                "PREVIEW_FAILED",
+                e.to_string(),
+            )
+        }
+    }
+}
+
+/// Presets of a template with the placements they compute
+#[derive(Serialize, ToSchema)]
+pub struct TemplatePresetsResponse {
+    pub success: bool,
+    pub template_id: String,
+    pub data: Vec<PresetInfo>,
+}
+
+/// One placement preset and where it puts a design
+#[derive(Serialize, ToSchema)]
+pub struct PresetInfo {
+    /// Name to pass as `preset` when generating
+    pub name: String,
+    pub preset: PlacementPreset,
+    /// Print space placement the preset resolves to
+    pub placement: PlacementSpec,
+    /// The design's bounding box in template image pixels, for drawing guides
+    pub bounds: PrintArea,
+}
+
+/// GET /api/v1/templates/{template_id}/presets - Placement presets and their pixel boxes
+#[utoipa::path(
+    get,
+    path = "/api/v1/templates/{template_id}/presets",
+    tag = "templates",
+    params(
+        ("template_id" = String, Path, description = "Template identifier (e.g., 'white-tshirt-front')")
+    ),
+    responses(
+        (status = 200, description = "Built-in presets overlaid with the template's own", body = TemplatePresetsResponse),
+        (status = 404, description = "Template not found", body = TemplateErrorResponse)
+    )
+)]
+pub async fn get_template_presets(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let template_id = path.into_inner();
+    let Some(template) = state.template_manager.get(&template_id) else {
+        return template_error(
+            HttpResponse::NotFound(),
+            "TEMPLATE_NOT_FOUND",
+            format!("Template '{}' does not exist", template_id),
+        );
+    };
+
+    HttpResponse::Ok().json(TemplatePresetsResponse {
+        success: true,
+        template_id,
+        data: preset_infos(&template.metadata),
+    })
+}
+
+/// Every effective preset of a template, by name
+fn preset_infos(metadata: &TemplateMetadata) -> Vec<PresetInfo> {
+    let area = &metadata.print_area;
+    metadata
+        .placement_presets()
+        .into_iter()
+        .filter_map(|(name, preset)| {
+            let placement = metadata.preset_placement(&name)?;
+            let (x, y) = placement.get_absolute_position();
+            let (width, height) = placement.get_rotated_dimensions();
+            Some(PresetInfo {
+                name,
+                preset,
+                bounds: PrintArea {
+                    x: area.x + x,
+                    y: area.y + y,
+                    width,
+                    height,
+                },
+                placement,
+            })
+        })
+        .collect()
+}
+
 /// GET /api/v1/templates/product-types - List product types with counts
 #[utoipa::path(
     get,
@@ -270,3 +465,807 @@ pub async fn get_by_product_type(
         }
     }
 }
+
+/// Geometry changes; omitted fields keep their current values
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GeometryPatch {
+    pub print_area: Option<PrintArea>,
+    pub anchor_point: Option<AnchorPoint>,
+    #[serde(default)]
+    pub displacement: DisplacementPatch,
+    /// Also write the geometry to the template's metadata.json and bump its version
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// Displacement settings to change
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct DisplacementPatch {
+    pub enabled: Option<bool>,
+    pub strength_default: Option<f64>,
+    /// `[min, max]`
+    #[schema(value_type = Option<[f64; 2]>)]
+    pub strength_range: Option<(f64, f64)>,
+}
+
+impl GeometryPatch {
+    fn apply(&self, mut geometry: TemplateGeometry) -> TemplateGeometry {
+        if let Some(print_area) = &self.print_area {
+            geometry.print_area = print_area.clone();
+        }
+        if let Some(anchor_point) = &self.anchor_point {
+            geometry.anchor_point = anchor_point.clone();
+        }
+        let displacement = &mut geometry.displacement;
+        if let Some(enabled) = self.displacement.enabled {
+            displacement.enabled = enabled;
+        }
+        if let Some(strength_default) = self.displacement.strength_default {
+            displacement.strength_default = strength_default;
+        }
+        if let Some(strength_range) = self.displacement.strength_range {
+            displacement.strength_range = strength_range;
+        }
+        geometry
+    }
+}
+
+/// Response for a geometry update
+#[derive(Serialize, ToSchema)]
+pub struct GeometryResponse {
+    pub success: bool,
+    pub template_id: String,
+    /// Metadata version; bumped when the geometry is confirmed
+    pub version: u32,
+    /// Whether the geometry was written to metadata.json
+    pub persisted: bool,
+    pub geometry: TemplateGeometry,
+    pub preview: GeometryPreview,
+}
+
+/// Test pattern rendered with the new geometry
+#[derive(Serialize, ToSchema)]
+pub struct GeometryPreview {
+    /// JPEG data URI
+    pub mockup_url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// PATCH /api/v1/templates/{template_id}/geometry - Adjust print area, anchor, and displacement
+///
+/// Applies the change in memory (and to the database print area) and returns a
+/// preview of the calibration test pattern. The metadata file is only
+/// rewritten, with a bumped version, when `confirm` is true.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/templates/{template_id}/geometry",
+    tag = "templates",
+    params(
+        ("template_id" = String, Path, description = "Template identifier (e.g., 'white-tshirt-front')")
+    ),
+    request_body = GeometryPatch,
+    responses(
+        (status = 200, description = "Geometry applied, with a test pattern preview", body = GeometryResponse),
+        (status = 400, description = "Geometry doesn't fit the template", body = TemplateErrorResponse),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 404, description = "Template not found", body = TemplateErrorResponse)
+    )
+)]
+pub async fn update_geometry(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<GeometryPatch>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "edit template geometry") {
+        return response;
+    }
+    let template_id = path.into_inner();
+
+    let Some(template) = state.template_manager.get(&template_id) else {
+        return template_error(
+            HttpResponse::NotFound(),
+            "TEMPLATE_NOT_FOUND",
+            format!("Template '{}' does not exist", template_id),
+        );
+    };
+
+    let geometry = body.apply(template.metadata.geometry());
+    if let Err(message) = geometry.validate(&template.metadata.dimensions) {
+        return template_error(HttpResponse::BadRequest(), "INVALID_GEOMETRY", message);
+    }
+
+    // Render against the candidate geometry first so a failed preview changes nothing
+    let images = match state.template_manager.images(&template).await {
+        Ok(images) => images,
+        Err(e) => {
+            error!(error = %e, template_id = %template_id, "Failed to load template images");
+            return template_error(
+                HttpResponse::InternalServerError(),
+                "TEMPLATE_LOAD_FAILED",
+                e.to_string(),
+            );
+        }
+    };
+    let candidate = template.metadata.with_geometry(geometry.clone());
+    let preview = match render_geometry_preview(&state, &candidate, &images).await {
+        Ok(preview) => preview,
+        Err(message) => {
+            error!(error = %message, template_id = %template_id, "Geometry preview failed");
+            return template_error(
+                HttpResponse::InternalServerError(),
+                "PREVIEW_FAILED",
+                message,
+            );
+        }
+    };
+
+    if let Some(repo) = &state.template_repo {
+        let area = &geometry.print_area;
+        let updated = repo
+            .update_print_area(
+                &template_id,
+                area.x as f64,
+                area.y as f64,
+                area.width as f64,
+                area.height as f64,
+            )
+            .await;
+        if let Err(e) = updated {
+            error!(error = %e, template_id = %template_id, "Failed to update template print area");
+            return template_error(
+                HttpResponse::InternalServerError(),
+                "DATABASE_ERROR",
+                format!("Failed to update template: {}", e),
+            );
+        }
+    }
+
+    let updated = match state
+        .template_manager
+        .update_geometry(&template_id, geometry)
+    {
+        Ok(updated) => updated,
+        Err(e) => {
+            return template_error(
+                HttpResponse::BadRequest(),
+                "INVALID_GEOMETRY",
+                e.to_string(),
+            );
+        }
+    };
+
+    // Renders against the old geometry would otherwise be served until the version changes
+    state.render_cache.invalidate_template(&template_id).await;
+
+    let mut version = updated.metadata.version;
+    if body.confirm {
+        match state.template_manager.persist_geometry(&template_id).await {
+            Ok(persisted) => version = persisted,
+            Err(e) => {
+                error!(error = %e, template_id = %template_id, "Failed to persist template geometry");
+                return template_error(
+                    HttpResponse::InternalServerError(),
+                    "PERSIST_FAILED",
+                    e.to_string(),
+                );
+            }
+        }
+    }
+
+    info!(template_id = %template_id, confirmed = body.confirm, "Template geometry updated");
+    HttpResponse::Ok().json(GeometryResponse {
+        success: true,
+        template_id,
+        version,
+        persisted: body.confirm,
+        geometry: updated.metadata.geometry(),
+        preview: GeometryPreview {
+            mockup_url: preview.data_uri(),
+            width: preview.width,
+            height: preview.height,
+        },
+    })
+}
+
+/// Response for a template reload
+#[derive(Serialize, ToSchema)]
+pub struct TemplateReloadResponse {
+    pub success: bool,
+    /// Templates loaded after the reload
+    pub template_count: usize,
+    #[serde(flatten)]
+    pub summary: TemplateReloadSummary,
+}
+
+/// POST /api/v1/templates/reload - Re-read every template directory from disk
+///
+/// The template map is swapped atomically, so in-flight renders are unaffected.
+/// Directories that fail to load keep their previous version and are listed
+/// under `failed`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/templates/reload",
+    tag = "templates",
+    responses(
+        (status = 200, description = "Templates added, updated, removed, and failed", body = TemplateReloadResponse),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 500, description = "Templates directory could not be read", body = TemplateErrorResponse)
+    )
+)]
+pub async fn reload_templates(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "reload templates") {
+        return response;
+    }
+
+    match state.template_manager.load_all().await {
+        Ok(summary) => reload_response(&state, summary).await,
+        Err(e) => {
+            error!(error = %e, "Template reload failed");
+            template_error(
+                HttpResponse::InternalServerError(),
+                "TEMPLATE_RELOAD_FAILED",
+                e.to_string(),
+            )
+        }
+    }
+}
+
+/// POST /api/v1/templates/{template_id}/reload - Re-read one template directory
+#[utoipa::path(
+    post,
+    path = "/api/v1/templates/{template_id}/reload",
+    tag = "templates",
+    params(
+        ("template_id" = String, Path, description = "Template identifier (e.g., 'white-tshirt-front')")
+    ),
+    responses(
+        (status = 200, description = "Whether the template was updated or failed", body = TemplateReloadResponse),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 404, description = "Template not found", body = TemplateErrorResponse)
+    )
+)]
+pub async fn reload_template(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "reload templates") {
+        return response;
+    }
+    let template_id = path.into_inner();
+
+    match state.template_manager.reload_one(&template_id).await {
+        Ok(summary) => reload_response(&state, summary).await,
+        Err(TemplateError::NotFound(_)) => template_error(
+            HttpResponse::NotFound(),
+            "TEMPLATE_NOT_FOUND",
+            format!("Template '{}' does not exist", template_id),
+        ),
+        Err(e) => {
+            error!(error = %e, template_id = %template_id, "Template reload failed");
+            template_error(
+                HttpResponse::InternalServerError(),
+                "TEMPLATE_RELOAD_FAILED",
+                e.to_string(),
+            )
+        }
+    }
+}
+
+/// Options for a template upload
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UploadTemplateQuery {
+    /// Replace a template that already has the uploaded ID
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Response for an uploaded template
+#[derive(Serialize, ToSchema)]
+pub struct TemplateUploadResponse {
+    pub success: bool,
+    pub template_id: String,
+    pub version: u32,
+    /// Whether a template with the same ID was replaced
+    pub replaced: bool,
+    /// Whether the templates table was updated; false without a database
+    pub database_updated: bool,
+}
+
+/// Largest accepted `metadata` part of a template upload
+const MAX_METADATA_PART_BYTES: usize = 256 * 1024;
+
+/// POST /api/v1/templates (multipart/form-data) - Add a template without a restart
+///
+/// Parts: `metadata` (metadata.json), `base` (PNG or JPEG), and optionally
+/// `displacement` and `mask`. Images must decode and match the metadata's
+/// dimensions, and the print area must lie within them. The template is
+/// served as soon as the response is sent; a rejected upload writes nothing.
+#[utoipa::path(
+    post,
+    path = "/api/v1/templates",
+    tag = "templates",
+    params(UploadTemplateQuery),
+    request_body(
+        content_type = "multipart/form-data",
+        description = "Parts `metadata` (metadata.json), `base` (PNG or JPEG), and optional `displacement` and `mask` images"
+    ),
+    responses(
+        (status = 201, description = "Template added", body = TemplateUploadResponse),
+        (status = 200, description = "Existing template replaced", body = TemplateUploadResponse),
+        (status = 400, description = "Malformed multipart body or missing part", body = TemplateErrorResponse),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 409, description = "Template ID exists and overwrite is not set", body = TemplateErrorResponse),
+        (status = 413, description = "A part exceeds its size limit"),
+        (status = 422, description = "Metadata or images failed validation", body = TemplateErrorResponse),
+        (status = 502, description = "Installed locally, but not published to the template source", body = TemplateErrorResponse)
+    )
+)]
+pub async fn upload_template(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<UploadTemplateQuery>,
+    payload: Multipart,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "upload templates") {
+        return response;
+    }
+
+    let upload = match read_template_upload(payload, state.settings.server.max_upload_bytes).await {
+        Ok(upload) => upload,
+        Err(response) => return response,
+    };
+    let installed = match state
+        .template_manager
+        .install(upload, query.overwrite)
+        .await
+    {
+        Ok(installed) => installed,
+        Err(TemplateError::AlreadyExists(id)) => {
+            return template_error(
+                HttpResponse::Conflict(),
+                "TEMPLATE_EXISTS",
+                format!(
+                    "Template '{}' already exists; set overwrite=true to replace it",
+                    id
+                ),
+            );
+        }
+        Err(e @ TemplateError::InvalidUpload(_)) => {
+            return template_error(
+                HttpResponse::UnprocessableEntity(),
+                "INVALID_TEMPLATE",
+                e.to_string(),
+            );
+        }
+        Err(TemplateError::Source(e)) => {
+            error!(error = %e, "Failed to publish uploaded template");
+            return template_error(
+                HttpResponse::BadGateway(),
+                "TEMPLATE_PUBLISH_FAILED",
+                format!(
+                    "Template is served by this instance only, as publishing it failed: {}; retry with overwrite=true",
+                    e
+                ),
+            );
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to install uploaded template");
+            return template_error(
+                HttpResponse::InternalServerError(),
+                "TEMPLATE_WRITE_FAILED",
+                e.to_string(),
+            );
+        }
+    };
+
+    let metadata = &installed.template.metadata;
+    if installed.replaced {
+        state.render_cache.invalidate_template(&metadata.id).await;
+    }
+    if let Some(repo) = &state.template_repo {
+        if let Err(e) = repo.upsert(&template_row(&installed)).await {
+            error!(error = %e, template_id = %metadata.id, "Failed to record uploaded template");
+            return template_error(
+                HttpResponse::InternalServerError(),
+                "DATABASE_ERROR",
+                format!(
+                    "Template '{}' is being served, but the templates table was not updated: {}",
+                    metadata.id, e
+                ),
+            );
+        }
+    }
+
+    info!(template_id = %metadata.id, replaced = installed.replaced, "Template uploaded");
+    let mut response = if installed.replaced {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::Created()
+    };
+    response.json(TemplateUploadResponse {
+        success: true,
+        template_id: metadata.id.clone(),
+        version: metadata.version,
+        replaced: installed.replaced,
+        database_updated: state.template_repo.is_some(),
+    })
+}
+
+/// Read the parts of a template upload
+async fn read_template_upload(
+    mut payload: Multipart,
+    max_upload_bytes: usize,
+) -> Result<TemplateUpload, HttpResponse> {
+    let (mut metadata, mut base_image, mut displacement_map, mut print_mask) =
+        (None, None, None, None);
+
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|e| {
+            template_error(
+                HttpResponse::BadRequest(),
+                "INVALID_MULTIPART",
+                e.to_string(),
+            )
+        })?;
+
+        match field.name() {
+            Some("metadata") => {
+                metadata = Some(read_field(&mut field, MAX_METADATA_PART_BYTES).await?);
+            }
+            Some("base") => base_image = Some(read_field(&mut field, max_upload_bytes).await?),
+            Some("displacement") => {
+                displacement_map = Some(read_field(&mut field, max_upload_bytes).await?);
+            }
+            Some("mask") => print_mask = Some(read_field(&mut field, max_upload_bytes).await?),
+            // Unknown parts are skipped when the next part is read
+            _ => {}
+        }
+    }
+
+    let missing = |part: &str| {
+        template_error(
+            HttpResponse::BadRequest(),
+            "MISSING_PART",
+            format!("Multipart body has no {} part", part),
+        )
+    };
+    Ok(TemplateUpload {
+        metadata: metadata.ok_or_else(|| missing("metadata"))?,
+        base_image: base_image.ok_or_else(|| missing("base"))?,
+        displacement_map,
+        print_mask,
+    })
+}
+
+/// Templates table row for an uploaded template
+fn template_row(installed: &InstalledTemplate) -> NewTemplate {
+    let metadata = &installed.template.metadata;
+    let area = &metadata.print_area;
+    let path = |path: &Path| path.display().to_string();
+    NewTemplate {
+        template_id: metadata.id.clone(),
+        name: metadata.name.clone().unwrap_or_else(|| metadata.id.clone()),
+        description: metadata.product.clone(),
+        product_type: metadata
+            .product_type
+            .clone()
+            .unwrap_or_else(|| metadata.category.clone()),
+        variant: Some(metadata.placement.clone()),
+        color: Some(metadata.color.clone()),
+        print_area_x: area.x as f64,
+        print_area_y: area.y as f64,
+        print_area_width: area.width as f64,
+        print_area_height: area.height as f64,
+        base_image_path: path(&installed.files.base_image),
+        displacement_map_path: installed.files.displacement_map.as_deref().map(path),
+        mask_path: installed.files.print_mask.as_deref().map(path),
+        width: metadata.dimensions.width as i32,
+        height: metadata.dimensions.height as i32,
+    }
+}
+
+/// DELETE /api/v1/templates/{template_id} - Remove a template
+///
+/// Stops serving the template, deletes its directory, and hides it from the
+/// database listings. With an R2 template source it is deleted there first.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/templates/{template_id}",
+    tag = "templates",
+    params(
+        ("template_id" = String, Path, description = "Template identifier (e.g., 'white-tshirt-front')")
+    ),
+    responses(
+        (status = 200, description = "Template removed"),
+        (status = 403, description = "Not an enterprise key"),
+        (status = 404, description = "Template not found", body = TemplateErrorResponse),
+        (status = 502, description = "Template source could not delete it; still served", body = TemplateErrorResponse)
+    )
+)]
+pub async fn delete_template(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "delete templates") {
+        return response;
+    }
+    let template_id = path.into_inner();
+
+    let removed = match state.template_manager.remove(&template_id).await {
+        Ok(()) => true,
+        // It may still be listed in the database
+        Err(TemplateError::NotFound(_)) => false,
+        Err(TemplateError::Source(e)) => {
+            error!(error = %e, template_id = %template_id, "Failed to unpublish template");
+            return template_error(
+                HttpResponse::BadGateway(),
+                "TEMPLATE_PUBLISH_FAILED",
+                format!("Template source could not delete the template: {}", e),
+            );
+        }
+        Err(e) => {
+            error!(error = %e, template_id = %template_id, "Failed to remove template");
+            return template_error(
+                HttpResponse::InternalServerError(),
+                "TEMPLATE_DELETE_FAILED",
+                e.to_string(),
+            );
+        }
+    };
+    state.render_cache.invalidate_template(&template_id).await;
+
+    let mut deactivated = false;
+    if let Some(repo) = &state.template_repo {
+        match repo.deactivate(&template_id).await {
+            Ok(matched) => deactivated = matched,
+            Err(e) => {
+                error!(error = %e, template_id = %template_id, "Failed to deactivate template");
+                return template_error(
+                    HttpResponse::InternalServerError(),
+                    "DATABASE_ERROR",
+                    format!("Failed to deactivate template: {}", e),
+                );
+            }
+        }
+    }
+    if !removed && !deactivated {
+        return template_error(
+            HttpResponse::NotFound(),
+            "TEMPLATE_NOT_FOUND",
+            format!("Template '{}' does not exist", template_id),
+        );
+    }
+
+    info!(template_id = %template_id, "Template deleted");
+    HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "template_id": template_id,
+    }))
+}
+
+/// Response listing broken templates
+#[derive(Serialize, ToSchema)]
+pub struct TemplateValidationResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub report: TemplateLoadReport,
+}
+
+/// GET /api/v1/templates/validation - Templates that failed to load or validate
+///
+/// `failed` lists directories left out (or kept at their last good version)
+/// with each metadata problem; `warnings` lists loaded templates whose
+/// metadata disagrees with their images.
+#[utoipa::path(
+    get,
+    path = "/api/v1/templates/validation",
+    tag = "templates",
+    responses(
+        (status = 200, description = "Templates that failed to load, and loaded ones with warnings", body = TemplateValidationResponse),
+        (status = 403, description = "Not an enterprise key")
+    )
+)]
+pub async fn template_validation(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Err(response) = require_enterprise(&req, "view template validation") {
+        return response;
+    }
+
+    HttpResponse::Ok().json(TemplateValidationResponse {
+        success: true,
+        report: state.template_manager.load_report(),
+    })
+}
+
+/// Drop cached renders of updated and removed templates, then report the reload
+async fn reload_response(state: &AppState, summary: TemplateReloadSummary) -> HttpResponse {
+    for template_id in summary.updated.iter().chain(&summary.removed) {
+        state.render_cache.invalidate_template(template_id).await;
+    }
+    info!(
+        added = summary.added.len(),
+        updated = summary.updated.len(),
+        removed = summary.removed.len(),
+        failed = summary.failed.len(),
+        "Templates reloaded"
+    );
+    HttpResponse::Ok().json(TemplateReloadResponse {
+        success: true,
+        template_count: state.template_manager.template_count(),
+        summary,
+    })
+}
+
+/// Render the calibration test pattern across the whole print area
+async fn render_geometry_preview(
+    state: &AppState,
+    metadata: &TemplateMetadata,
+    images: &TemplateImages,
+) -> Result<MockupResult, String> {
+    let pattern = DynamicImage::ImageRgba8(geometry_test_pattern(&metadata.print_area));
+    let mut png = Vec::new();
+    pattern
+        .write_to(
+            &mut std::io::Cursor::new(&mut png),
+            image::ImageOutputFormat::Png,
+        )
+        .map_err(|e| e.to_string())?;
+
+    let print_area = &metadata.print_area;
+    let request = MockupRequest {
+        designs: vec![DesignLayer {
+            design: DesignSource::Bytes(png.into()),
+            placement: PlacementSpec {
+                scale: 1.0,
+                offset_x: 0,
+                offset_y: 0,
+                print_area_width: print_area.width,
+                print_area_height: print_area.height,
+                ..PlacementSpec::default()
+            },
+            displacement_strength: metadata.displacement.strength_default,
+            blend_mode: None,
+        }],
+        template_id: metadata.id.clone(),
+        apply_displacement: None,
+        tint_color: None,
+        remove_background: None,
+        output: OutputSettings::with_preset(OutputFormat::Jpeg, JpegPreset::Web, None, None, None)
+            .unwrap_or_default(),
+        limits: GenerationLimits::default(),
+    };
+
+    state
+        .template_manager
+        .generate_with(&request, metadata, images)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn template_error(mut builder: HttpResponseBuilder, code: &str, message: String) -> HttpResponse {
+    builder.json(TemplateErrorResponse {
+        success: false,
+        error: TemplateApiError {
+            code: code.to_string(),
+            message,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geometry_patch_keeps_omitted_fields() {
+        let current = TemplateMetadata::from_provider_mockup(
+            "tee",
+            "front",
+            crate::engine::TemplateDimensions {
+                width: 800,
+                height: 800,
+            },
+            PrintArea {
+                x: 100,
+                y: 100,
+                width: 400,
+                height: 400,
+            },
+        )
+        .geometry();
+
+        let patch: GeometryPatch = serde_json::from_value(serde_json::json!({
+            "print_area": {"x": 120, "y": 90, "width": 380, "height": 420},
+            "displacement": {"enabled": true},
+        }))
+        .unwrap();
+        assert!(!patch.confirm);
+
+        let geometry = patch.apply(current);
+        assert_eq!(geometry.print_area.x, 120);
+        assert_eq!(geometry.print_area.height, 420);
+        assert_eq!(geometry.anchor_point.x, 300);
+        assert!(geometry.displacement.enabled);
+        assert_eq!(geometry.displacement.strength_range, (0.0, 30.0));
+    }
+
+    #[tokio::test]
+    async fn test_template_row_falls_back_to_metadata_basics() {
+        let base = std::env::temp_dir().join(format!("row-{}", uuid::Uuid::new_v4()));
+        let manager = crate::engine::TemplateManager::new(&base).unwrap();
+        let mut base_image = Vec::new();
+        DynamicImage::new_rgba8(16, 16)
+            .write_to(
+                &mut std::io::Cursor::new(&mut base_image),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        let metadata = serde_json::json!({
+            "id": "tee",
+            "version": 3,
+            "category": "tshirt",
+            "color": "black",
+            "placement": "front",
+            "dimensions": {"width": 16, "height": 16},
+            "print_area": {"x": 2, "y": 3, "width": 10, "height": 12},
+            "anchor_point": {"x": 7, "y": 9},
+            "displacement": {"enabled": false, "strength_default": 0.0, "strength_range": [0.0, 30.0]},
+            "blend_mode": "normal",
+            "default_opacity": 255,
+        });
+        let upload = TemplateUpload {
+            metadata: metadata.to_string().into(),
+            base_image: base_image.into(),
+            displacement_map: None,
+            print_mask: None,
+        };
+
+        let installed = manager.install(upload, false).await.unwrap();
+        let row = template_row(&installed);
+        std::fs::remove_dir_all(&base).ok();
+
+        assert_eq!(
+            (row.name.as_str(), row.product_type.as_str()),
+            ("tee", "tshirt")
+        );
+        assert_eq!(row.variant.as_deref(), Some("front"));
+        assert_eq!((row.print_area_x, row.print_area_height), (2.0, 12.0));
+        assert_eq!(
+            row.base_image_path,
+            base.join("tee").join("base.png").display().to_string()
+        );
+        assert_eq!((row.displacement_map_path, row.mask_path), (None, None));
+    }
+
+    #[test]
+    fn test_preset_bounds_are_in_template_pixels() {
+        let metadata = TemplateMetadata::from_provider_mockup(
+            "tee",
+            "front",
+            crate::engine::TemplateDimensions {
+                width: 2000,
+                height: 2600,
+            },
+            PrintArea {
+                x: 100,
+                y: 100,
+                width: 1800,
+                height: 2400,
+            },
+        );
+        let presets = preset_infos(&metadata);
+        assert_eq!(presets.len(), 5);
+
+        let left_chest = presets.iter().find(|p| p.name == "left_chest").unwrap();
+        let bounds = &left_chest.bounds;
+        assert_eq!(
+            (bounds.x, bounds.y, bounds.width, bounds.height),
+            (1180, 340, 360, 480)
+        );
+        let full_front = presets.iter().find(|p| p.name == "full_front").unwrap();
+        assert_eq!((full_front.bounds.x, full_front.bounds.width), (100, 1800));
+    }
+}
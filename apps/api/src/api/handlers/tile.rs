@@ -9,6 +9,7 @@ use std::io::Cursor;
 use utoipa::ToSchema;
 
 use crate::api::handlers::generate::{ApiError, ErrorResponse};
+use crate::api::middleware::RequestId;
 
 const DEFAULT_MAX_DIM: u32 = 4096;
 
@@ -106,6 +107,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
     if body.repeat_x == 0 || body.repeat_y == 0 {
         return HttpResponse::BadRequest().json(ErrorResponse {
             success: false,
+            request_id: RequestId::current(),
             error: ApiError {
                 code: "INVALID_REPEAT".to_string(),
                 message: "repeat_x and repeat_y must be greater than 0".to_string(),
@@ -116,6 +118,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
     if body.layout != "grid" {
         return HttpResponse::BadRequest().json(ErrorResponse {
             success: false,
+            request_id: RequestId::current(),
             error: ApiError {
                 code: "UNSUPPORTED_LAYOUT".to_string(),
                 message: "Only grid layout is currently supported".to_string(),
@@ -128,6 +131,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
         Err(msg) => {
             return HttpResponse::BadRequest().json(ErrorResponse {
                 success: false,
+                request_id: RequestId::current(),
                 error: ApiError {
                     code: "INVALID_PRESET".to_string(),
                     message: msg,
@@ -141,6 +145,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
         Err(e) => {
             return HttpResponse::InternalServerError().json(ErrorResponse {
                 success: false,
+                request_id: RequestId::current(),
                 error: ApiError {
                     code: "TILE_FETCH_FAILED".to_string(),
                     message: format!("Failed to fetch tile image: {}", e),
@@ -152,6 +157,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
     if !response.status().is_success() {
         return HttpResponse::BadRequest().json(ErrorResponse {
             success: false,
+            request_id: RequestId::current(),
             error: ApiError {
                 code: "TILE_FETCH_FAILED".to_string(),
                 message: format!("Tile URL returned HTTP {}", response.status()),
@@ -164,6 +170,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
         Err(e) => {
             return HttpResponse::InternalServerError().json(ErrorResponse {
                 success: false,
+                request_id: RequestId::current(),
                 error: ApiError {
                     code: "TILE_READ_FAILED".to_string(),
                     message: format!("Failed reading tile bytes: {}", e),
@@ -177,6 +184,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
         Err(e) => {
             return HttpResponse::BadRequest().json(ErrorResponse {
                 success: false,
+                request_id: RequestId::current(),
                 error: ApiError {
                     code: "TILE_DECODE_FAILED".to_string(),
                     message: format!("Failed to decode tile image: {}", e),
@@ -191,6 +199,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
     if tile_width == 0 || tile_height == 0 {
         return HttpResponse::BadRequest().json(ErrorResponse {
             success: false,
+            request_id: RequestId::current(),
             error: ApiError {
                 code: "INVALID_TILE".to_string(),
                 message: "Tile image has invalid dimensions".to_string(),
@@ -203,6 +212,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
         None => {
             return HttpResponse::BadRequest().json(ErrorResponse {
                 success: false,
+                request_id: RequestId::current(),
                 error: ApiError {
                     code: "OUTPUT_TOO_LARGE".to_string(),
                     message: "Computed output width is too large".to_string(),
@@ -216,6 +226,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
         None => {
             return HttpResponse::BadRequest().json(ErrorResponse {
                 success: false,
+                request_id: RequestId::current(),
                 error: ApiError {
                     code: "OUTPUT_TOO_LARGE".to_string(),
                     message: "Computed output height is too large".to_string(),
@@ -231,6 +242,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
             if let Err(e) = output.copy_from(&tile_img, x * tile_width, y * tile_height) {
                 return HttpResponse::InternalServerError().json(ErrorResponse {
                     success: false,
+                    request_id: RequestId::current(),
                     error: ApiError {
                         code: "TILE_COMPOSE_FAILED".to_string(),
                         message: format!("Failed composing tile image: {}", e),
@@ -262,6 +274,7 @@ pub async fn tile_pattern(body: web::Json<TileRequest>) -> HttpResponse {
     if let Err(e) = dyn_img.write_to(&mut cursor, ImageFormat::Png) {
         return HttpResponse::InternalServerError().json(ErrorResponse {
             success: false,
+            request_id: RequestId::current(),
             error: ApiError {
                 code: "PNG_ENCODE_FAILED".to_string(),
                 message: format!("Failed to encode PNG: {}", e),
@@ -3,24 +3,37 @@
 //! Endpoints for viewing API usage statistics, quotas, and billing info.
 
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{DateTime, Datelike, Utc};
 use serde::{Deserialize, Serialize};
 use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use crate::api::handlers::catalog::{default_page, default_per_page, PaginatedResponse};
 use crate::api::middleware::ApiKeyAuth;
-use crate::db::{DbPool, MonthlyUsageSummary, UsageRepository, UsageStats};
+use crate::config::pricing_url;
+use crate::db::{
+    parse_year_month, CategoryUsage, DailyUsage, DbPool, MonthlyUsageSummary, PageRequest,
+    ResourceKind, ResourceRepository, ResourceUsage, StatusClass, TemplateUsage, UsageLog,
+    UsageLogFilter, UsageRepository, UsageStats, MAX_DAILY_USAGE_DAYS,
+};
 
 /// Usage stats response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct UsageStatsResponse {
     pub api_key_id: Uuid,
     pub tier: String,
     pub current_month: MonthlyUsageResponse,
+    /// Render quota, the key's `monthly_quota`
     pub quota: QuotaInfo,
+    /// Usage per endpoint category, each with its own budget
+    pub categories: Vec<CategoryUsage>,
+    /// Stored resources against the tier's limits
+    pub resources: Vec<ResourceUsage>,
 }
 
 /// Monthly usage response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct MonthlyUsageResponse {
     pub year_month: String,
     pub total_requests: i32,
@@ -28,6 +41,7 @@ pub struct MonthlyUsageResponse {
     pub failed_requests: i32,
     pub billable_requests: i32,
     pub overage_requests: i32,
+    pub billable_units: i32,
 }
 
 impl From<MonthlyUsageSummary> for MonthlyUsageResponse {
@@ -39,12 +53,13 @@ impl From<MonthlyUsageSummary> for MonthlyUsageResponse {
             failed_requests: summary.failed_requests,
             billable_requests: summary.billable_requests,
             overage_requests: summary.overage_requests,
+            billable_units: summary.billable_units,
         }
     }
 }
 
 /// Quota information
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct QuotaInfo {
     pub monthly_quota: i32,
     pub used: i32,
@@ -54,7 +69,7 @@ pub struct QuotaInfo {
 }
 
 /// Usage history response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct UsageHistoryResponse {
     pub api_key_id: Uuid,
     pub months: Vec<MonthlyUsageResponse>,
@@ -62,6 +77,15 @@ pub struct UsageHistoryResponse {
 
 /// Get current usage stats
 /// GET /api/v1/usage
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage",
+    tag = "usage",
+    responses(
+        (status = 200, description = "This month's usage against the key's quotas and resource limits", body = UsageStatsResponse),
+        (status = 401, description = "API key required")
+    )
+)]
 pub async fn get_usage_stats(req: HttpRequest, pool: web::Data<DbPool>) -> HttpResponse {
     let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
         Some(auth) => auth,
@@ -74,9 +98,17 @@ pub async fn get_usage_stats(req: HttpRequest, pool: web::Data<DbPool>) -> HttpR
     };
 
     let repo = UsageRepository::new(pool.get_ref().clone());
+    let resource_repo = ResourceRepository::new(pool.get_ref().clone());
 
-    match repo.get_usage_stats(auth.key_id, auth.monthly_quota).await {
-        Ok(stats) => {
+    let stats = repo
+        .get_usage_stats(auth.key_id, |category| auth.category_quota(category))
+        .await;
+    let resource_usage = resource_repo
+        .all_usage(auth.key_id, |kind| auth.resource_limit(kind))
+        .await;
+
+    match (stats, resource_usage) {
+        (Ok(stats), Ok(resources)) => {
             let response = UsageStatsResponse {
                 api_key_id: stats.api_key_id,
                 tier: auth.tier.clone(),
@@ -88,11 +120,13 @@ pub async fn get_usage_stats(req: HttpRequest, pool: web::Data<DbPool>) -> HttpR
                     percentage_used: stats.quota_percentage_used,
                     is_exceeded: stats.quota_remaining <= 0,
                 },
+                categories: stats.categories,
+                resources,
             };
 
             HttpResponse::Ok().json(response)
         }
-        Err(e) => {
+        (Err(e), _) | (_, Err(e)) => {
             warn!(error = %e, "Failed to get usage stats");
             HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": "internal_error",
@@ -102,10 +136,56 @@ pub async fn get_usage_stats(req: HttpRequest, pool: web::Data<DbPool>) -> HttpR
     }
 }
 
+/// Check that `count` more resources of a kind fit under the key's tier limit
+///
+/// Answers 403 when they don't. Like the monthly quota check, a failed count
+/// lets the request through.
+pub(crate) async fn ensure_resource_capacity(
+    pool: &DbPool,
+    auth: &ApiKeyAuth,
+    kind: ResourceKind,
+    count: i64,
+) -> Result<(), HttpResponse> {
+    let repo = ResourceRepository::new(pool.clone());
+    let usage = match repo
+        .usage(auth.key_id, kind, auth.resource_limit(kind))
+        .await
+    {
+        Ok(usage) => usage,
+        Err(e) => {
+            warn!(error = %e, resource = kind.as_str(), "Resource limit check failed");
+            return Ok(());
+        }
+    };
+    if usage.allows(count) {
+        return Ok(());
+    }
+
+    Err(HttpResponse::Forbidden().json(serde_json::json!({
+        "error": "resource_limit_exceeded",
+        "message": format!(
+            "The {} tier allows {} {} and this key has {}. Remove some or upgrade your plan.",
+            auth.tier,
+            usage.limit,
+            kind.label(),
+            usage.used
+        ),
+        "resource": kind,
+        "limit": usage.limit,
+        "used": usage.used,
+        "requested": count,
+        "tier": auth.tier,
+        "upgrade_url": pricing_url()
+    })))
+}
+
 /// Query params for usage history
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct UsageHistoryQuery {
+    /// Months to return, 1 to 24
     #[serde(default = "default_months")]
+    #[param(default = 6)]
     pub months: i32,
 }
 
@@ -115,6 +195,16 @@ fn default_months() -> i32 {
 
 /// Get usage history
 /// GET /api/v1/usage/history?months=6
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage/history",
+    tag = "usage",
+    params(UsageHistoryQuery),
+    responses(
+        (status = 200, description = "Monthly totals, newest first", body = UsageHistoryResponse),
+        (status = 401, description = "API key required")
+    )
+)]
 pub async fn get_usage_history(
     req: HttpRequest,
     pool: web::Data<DbPool>,
@@ -155,8 +245,280 @@ pub async fn get_usage_history(
     }
 }
 
+/// Query params for the per-template breakdown
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TemplateUsageQuery {
+    /// Month as YYYY-MM, the current month when omitted
+    pub month: Option<String>,
+}
+
+/// Per-template usage response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TemplateUsageResponse {
+    pub api_key_id: Uuid,
+    pub year_month: String,
+    pub templates: Vec<TemplateUsage>,
+}
+
+/// Get requests and renders per template for a month
+/// GET /api/v1/usage/templates?month=2026-10
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage/templates",
+    tag = "usage",
+    params(TemplateUsageQuery),
+    responses(
+        (status = 200, description = "Requests and renders per template", body = TemplateUsageResponse),
+        (status = 400, description = "Month is not YYYY-MM"),
+        (status = 401, description = "API key required")
+    )
+)]
+pub async fn get_template_usage(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    query: web::Query<TemplateUsageQuery>,
+) -> HttpResponse {
+    let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
+        Some(auth) => auth,
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "unauthorized",
+                "message": "API key required"
+            }));
+        }
+    };
+
+    let (year, month) = match &query.month {
+        Some(value) => match parse_year_month(value) {
+            Some(parsed) => parsed,
+            None => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": "invalid_format",
+                    "message": "month must be in YYYY-MM format"
+                }));
+            }
+        },
+        None => {
+            let now = Utc::now();
+            (now.year(), now.month())
+        }
+    };
+
+    let repo = UsageRepository::new(pool.get_ref().clone());
+    match repo.get_template_usage(auth.key_id, year, month).await {
+        Ok(templates) => HttpResponse::Ok().json(TemplateUsageResponse {
+            api_key_id: auth.key_id,
+            year_month: format!("{:04}-{:02}", year, month),
+            templates,
+        }),
+        Err(e) => {
+            warn!(error = %e, "Failed to get template usage");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to get template usage"
+            }))
+        }
+    }
+}
+
+/// Query params for the daily breakdown
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DailyUsageQuery {
+    /// Days to return, 1 to 90
+    #[serde(default = "default_days")]
+    #[param(default = 30)]
+    pub days: u32,
+    /// Key to report on, enterprise keys only; the caller's own key when omitted
+    pub api_key_id: Option<Uuid>,
+}
+
+fn default_days() -> u32 {
+    30
+}
+
+/// Daily usage response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DailyUsageResponse {
+    pub api_key_id: Uuid,
+    pub days: Vec<DailyUsage>,
+}
+
+/// Get requests and response times per day, zero-filled for charts
+/// GET /api/v1/usage/daily?days=30
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage/daily",
+    tag = "usage",
+    params(DailyUsageQuery),
+    responses(
+        (status = 200, description = "One entry per UTC day, oldest first", body = DailyUsageResponse),
+        (status = 401, description = "API key required"),
+        (status = 403, description = "Another key's usage requested by a non-enterprise key")
+    )
+)]
+pub async fn get_daily_usage(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    query: web::Query<DailyUsageQuery>,
+) -> HttpResponse {
+    let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
+        Some(auth) => auth,
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "unauthorized",
+                "message": "API key required"
+            }));
+        }
+    };
+
+    let api_key_id = query.api_key_id.unwrap_or(auth.key_id);
+    if api_key_id != auth.key_id && auth.tier != "enterprise" {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "forbidden",
+            "message": "Only enterprise tier keys can view another key's usage"
+        }));
+    }
+
+    let days = query.days.clamp(1, MAX_DAILY_USAGE_DAYS);
+    let repo = UsageRepository::new(pool.get_ref().clone());
+
+    match repo.get_daily_usage(api_key_id, days).await {
+        Ok(days) => HttpResponse::Ok().json(DailyUsageResponse { api_key_id, days }),
+        Err(e) => {
+            warn!(error = %e, "Failed to get daily usage");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to get daily usage"
+            }))
+        }
+    }
+}
+
+/// Query params for the request log
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UsageLogsQuery {
+    /// Page number (1-based)
+    #[serde(default = "default_page")]
+    #[param(default = 1)]
+    pub page: u32,
+    /// Requests per page, at most 100
+    #[serde(default = "default_per_page")]
+    #[param(default = 50)]
+    pub per_page: u32,
+    /// Only responses of this class: 2xx, 4xx, or 5xx
+    pub status: Option<String>,
+    /// Only endpoints starting with this path, e.g. `/api/v1/mockups`
+    pub endpoint: Option<String>,
+    /// Only requests made at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only requests made before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Key to list requests of, enterprise keys only; the caller's own key when omitted
+    pub api_key_id: Option<Uuid>,
+}
+
+impl UsageLogsQuery {
+    fn filter(&self) -> Result<UsageLogFilter, HttpResponse> {
+        let status_class = match self.status.as_deref() {
+            None => None,
+            Some(status) => Some(StatusClass::parse(status).ok_or_else(|| {
+                HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": "invalid_status",
+                    "message": "status must be one of 2xx, 4xx, or 5xx"
+                }))
+            })?),
+        };
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            if since >= until {
+                return Err(HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": "invalid_range",
+                    "message": "since must be before until"
+                })));
+            }
+        }
+        Ok(UsageLogFilter {
+            status_class,
+            endpoint_prefix: self.endpoint.clone().filter(|e| !e.is_empty()),
+            since: self.since,
+            until: self.until,
+        })
+    }
+}
+
+/// List a key's requests, newest first
+/// GET /api/v1/usage/logs?page=1&per_page=50&status=5xx
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage/logs",
+    tag = "usage",
+    params(UsageLogsQuery),
+    responses(
+        (status = 200, description = "One page of logged requests", body = PaginatedResponse<UsageLog>),
+        (status = 400, description = "Unknown status class, or since not before until"),
+        (status = 401, description = "API key required"),
+        (status = 403, description = "Another key's requests asked for by a non-enterprise key")
+    )
+)]
+pub async fn get_usage_logs(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    query: web::Query<UsageLogsQuery>,
+) -> HttpResponse {
+    let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
+        Some(auth) => auth,
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "unauthorized",
+                "message": "API key required"
+            }));
+        }
+    };
+
+    let api_key_id = query.api_key_id.unwrap_or(auth.key_id);
+    if api_key_id != auth.key_id && auth.tier != "enterprise" {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "forbidden",
+            "message": "Only enterprise tier keys can view another key's usage"
+        }));
+    }
+
+    let filter = match query.filter() {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+    let page = PageRequest::new(query.page, query.per_page);
+    let repo = UsageRepository::new(pool.get_ref().clone());
+
+    match repo.get_logs(api_key_id, &filter, page).await {
+        Ok(logs) => HttpResponse::Ok().json(PaginatedResponse::from(logs)),
+        Err(e) => {
+            warn!(error = %e, "Failed to list usage logs");
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "internal_error",
+                "message": "Failed to list usage logs"
+            }))
+        }
+    }
+}
+
 /// Get specific month usage
 /// GET /api/v1/usage/month/{year_month}
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage/month/{year_month}",
+    tag = "usage",
+    params(
+        ("year_month" = String, Path, description = "Month as YYYY-MM")
+    ),
+    responses(
+        (status = 200, description = "Totals for the month, zero when the key made no requests", body = MonthlyUsageResponse),
+        (status = 400, description = "Month is not YYYY-MM"),
+        (status = 401, description = "API key required")
+    )
+)]
 pub async fn get_month_usage(
     req: HttpRequest,
     pool: web::Data<DbPool>,
@@ -198,6 +560,7 @@ pub async fn get_month_usage(
                     failed_requests: 0,
                     billable_requests: 0,
                     overage_requests: 0,
+                    billable_units: 0,
                 })
             }
         }
@@ -232,7 +595,7 @@ fn is_valid_year_month(s: &str) -> bool {
 }
 
 /// Billing summary response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct BillingSummaryResponse {
     pub api_key_id: Uuid,
     pub tier: String,
@@ -241,15 +604,19 @@ pub struct BillingSummaryResponse {
     pub pricing: PricingInfo,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct BillingMonthInfo {
     pub year_month: String,
     pub billable_requests: i32,
     pub overage_requests: i32,
+    /// Weighted units used this month, which the quota is counted in
+    pub billable_units: i32,
+    pub included_units: i32,
+    pub overage_units: i32,
     pub estimated_cost: f64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PricingInfo {
     pub tier_price: f64,
     pub overage_price_per_1k: f64,
@@ -258,6 +625,15 @@ pub struct PricingInfo {
 
 /// Get billing summary for current month
 /// GET /api/v1/usage/billing
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage/billing",
+    tag = "usage",
+    responses(
+        (status = 200, description = "Units used, overage, and estimated cost this month", body = BillingSummaryResponse),
+        (status = 401, description = "API key required")
+    )
+)]
 pub async fn get_billing_summary(req: HttpRequest, pool: web::Data<DbPool>) -> HttpResponse {
     let auth = match req.extensions().get::<ApiKeyAuth>().cloned() {
         Some(auth) => auth,
@@ -275,7 +651,8 @@ pub async fn get_billing_summary(req: HttpRequest, pool: web::Data<DbPool>) -> H
         Ok(usage) => {
             // Calculate tier pricing
             let (tier_price, overage_price) = get_tier_pricing(&auth.tier);
-            let overage_cost = (usage.overage_requests as f64 / 1000.0) * overage_price;
+            let overage_units = (usage.billable_units - auth.monthly_quota).max(0);
+            let overage_cost = (overage_units as f64 / 1000.0) * overage_price;
             let estimated_cost = tier_price + overage_cost;
 
             let response = BillingSummaryResponse {
@@ -286,6 +663,9 @@ pub async fn get_billing_summary(req: HttpRequest, pool: web::Data<DbPool>) -> H
                     year_month: usage.year_month,
                     billable_requests: usage.billable_requests,
                     overage_requests: usage.overage_requests,
+                    billable_units: usage.billable_units,
+                    included_units: auth.monthly_quota,
+                    overage_units,
                     estimated_cost,
                 },
                 pricing: PricingInfo {
@@ -0,0 +1,480 @@
+//! Webhook Subscription Handlers
+//!
+//! Endpoints for managing per-key webhook subscriptions, inspecting the
+//! delivery log, and replaying events.
+
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::usage::ensure_resource_capacity;
+use crate::api::middleware::ApiKeyAuth;
+use crate::db::{DbPool, DbWebhookSubscription, ResourceKind, WebhookDelivery, WebhookRepository};
+use crate::webhooks::{validate_filter, validate_webhook_url};
+use crate::AppState;
+
+/// Request to create a webhook subscription
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Event filters (`render.completed`, `sync.*`); empty subscribes to all
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+/// Request to update a webhook subscription
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateWebhookRequest {
+    #[serde(default)]
+    pub event_types: Option<Vec<String>>,
+    #[serde(default)]
+    pub is_active: Option<bool>,
+}
+
+/// Request to replay an event to a subscription
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RedeliverRequest {
+    pub event_id: Uuid,
+}
+
+/// Delivery log query parameters
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveriesQuery {
+    /// Filter by status: pending, delivered, failed
+    pub status: Option<String>,
+    /// Entries to return, 1 to 500
+    #[serde(default = "default_deliveries_limit")]
+    #[param(default = 50)]
+    pub limit: i64,
+}
+
+fn default_deliveries_limit() -> i64 {
+    50
+}
+
+/// Webhook subscription info (without the signing secret)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookInfo {
+    pub id: Uuid,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<DbWebhookSubscription> for WebhookInfo {
+    fn from(subscription: DbWebhookSubscription) -> Self {
+        Self {
+            id: subscription.id,
+            url: subscription.url,
+            event_types: subscription.event_types,
+            is_active: subscription.is_active,
+            created_at: subscription.created_at,
+            updated_at: subscription.updated_at,
+        }
+    }
+}
+
+/// Response after creating a webhook subscription
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: WebhookInfo,
+    pub secret: String, // Only shown once!
+    pub message: String,
+}
+
+/// List of webhook subscriptions response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListWebhooksResponse {
+    pub webhooks: Vec<WebhookInfo>,
+    pub count: usize,
+}
+
+/// Delivery log response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListDeliveriesResponse {
+    pub deliveries: Vec<WebhookDelivery>,
+    pub count: usize,
+}
+
+fn require_auth(req: &HttpRequest) -> Result<ApiKeyAuth, HttpResponse> {
+    req.extensions()
+        .get::<ApiKeyAuth>()
+        .cloned()
+        .ok_or_else(|| {
+            HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "unauthorized",
+                "message": "API key required"
+            }))
+        })
+}
+
+fn validate_filters(event_types: &[String]) -> Result<(), HttpResponse> {
+    for filter in event_types {
+        if let Err(message) = validate_filter(filter) {
+            return Err(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "invalid_event_type",
+                "message": message
+            })));
+        }
+    }
+    Ok(())
+}
+
+fn internal_error(message: &str) -> HttpResponse {
+    HttpResponse::InternalServerError().json(serde_json::json!({
+        "error": "internal_error",
+        "message": message
+    }))
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "not_found",
+        "message": "Webhook not found"
+    }))
+}
+
+/// Load a subscription owned by the requesting key
+async fn load_owned(
+    repo: &WebhookRepository,
+    id: Uuid,
+    auth: &ApiKeyAuth,
+) -> Result<DbWebhookSubscription, HttpResponse> {
+    match repo.get_subscription(id).await {
+        Ok(Some(subscription)) if subscription.api_key_id == auth.key_id => Ok(subscription),
+        Ok(_) => Err(not_found()),
+        Err(e) => {
+            warn!(error = %e, "Failed to get webhook");
+            Err(internal_error("Failed to get webhook"))
+        }
+    }
+}
+
+/// Create a webhook subscription for the requesting key
+/// POST /api/v1/webhooks
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks",
+    tag = "webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "Webhook created; the signing secret is only returned here", body = CreateWebhookResponse),
+        (status = 400, description = "Invalid URL or event filter"),
+        (status = 401, description = "API key required"),
+        (status = 403, description = "Webhook limit of the key's tier reached")
+    )
+)]
+pub async fn create_webhook(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    body: web::Json<CreateWebhookRequest>,
+) -> HttpResponse {
+    let auth = match require_auth(&req) {
+        Ok(auth) => auth,
+        Err(response) => return response,
+    };
+
+    if let Err(message) = validate_webhook_url(&body.url) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "invalid_url",
+            "message": message
+        }));
+    }
+    if let Err(response) = validate_filters(&body.event_types) {
+        return response;
+    }
+    if let Err(response) =
+        ensure_resource_capacity(pool.get_ref(), &auth, ResourceKind::Webhooks, 1).await
+    {
+        return response;
+    }
+
+    let repo = WebhookRepository::new(pool.get_ref().clone());
+
+    match repo
+        .create_subscription(auth.key_id, &body.url, &body.event_types)
+        .await
+    {
+        Ok(subscription) => {
+            let secret = subscription.secret.clone();
+            HttpResponse::Created().json(CreateWebhookResponse {
+                webhook: subscription.into(),
+                secret,
+                message: "Webhook created. Save the secret to verify X-Webhook-Signature - it won't be shown again!".to_string(),
+            })
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to create webhook");
+            internal_error("Failed to create webhook")
+        }
+    }
+}
+
+/// List the requesting key's webhook subscriptions
+/// GET /api/v1/webhooks
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "The caller's webhooks", body = ListWebhooksResponse),
+        (status = 401, description = "API key required")
+    )
+)]
+pub async fn list_webhooks(req: HttpRequest, pool: web::Data<DbPool>) -> HttpResponse {
+    let auth = match require_auth(&req) {
+        Ok(auth) => auth,
+        Err(response) => return response,
+    };
+
+    let repo = WebhookRepository::new(pool.get_ref().clone());
+
+    match repo.list_subscriptions(auth.key_id).await {
+        Ok(subscriptions) => {
+            let webhooks: Vec<WebhookInfo> = subscriptions.into_iter().map(Into::into).collect();
+            let count = webhooks.len();
+            HttpResponse::Ok().json(ListWebhooksResponse { webhooks, count })
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to list webhooks");
+            internal_error("Failed to list webhooks")
+        }
+    }
+}
+
+/// Update event filters or pause/resume a webhook
+/// PATCH /api/v1/webhooks/{id}
+#[utoipa::path(
+    patch,
+    path = "/api/v1/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID")
+    ),
+    request_body = UpdateWebhookRequest,
+    responses(
+        (status = 200, description = "The updated webhook", body = WebhookInfo),
+        (status = 400, description = "Invalid event filter"),
+        (status = 404, description = "Webhook not found")
+    )
+)]
+pub async fn update_webhook(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateWebhookRequest>,
+) -> HttpResponse {
+    let auth = match require_auth(&req) {
+        Ok(auth) => auth,
+        Err(response) => return response,
+    };
+    if let Some(ref event_types) = body.event_types {
+        if let Err(response) = validate_filters(event_types) {
+            return response;
+        }
+    }
+
+    let id = path.into_inner();
+    let repo = WebhookRepository::new(pool.get_ref().clone());
+    if let Err(response) = load_owned(&repo, id, &auth).await {
+        return response;
+    }
+
+    match repo
+        .update_subscription(id, body.event_types.as_deref(), body.is_active)
+        .await
+    {
+        Ok(Some(subscription)) => HttpResponse::Ok().json(WebhookInfo::from(subscription)),
+        Ok(None) => not_found(),
+        Err(e) => {
+            warn!(error = %e, "Failed to update webhook");
+            internal_error("Failed to update webhook")
+        }
+    }
+}
+
+/// Delete a webhook and its delivery log
+/// DELETE /api/v1/webhooks/{id}
+#[utoipa::path(
+    delete,
+    path = "/api/v1/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID")
+    ),
+    responses(
+        (status = 200, description = "Webhook deleted"),
+        (status = 404, description = "Webhook not found")
+    )
+)]
+pub async fn delete_webhook(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let auth = match require_auth(&req) {
+        Ok(auth) => auth,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    let repo = WebhookRepository::new(pool.get_ref().clone());
+    if let Err(response) = load_owned(&repo, id, &auth).await {
+        return response;
+    }
+
+    match repo.delete_subscription(id).await {
+        Ok(true) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Webhook deleted"
+        })),
+        Ok(false) => not_found(),
+        Err(e) => {
+            warn!(error = %e, "Failed to delete webhook");
+            internal_error("Failed to delete webhook")
+        }
+    }
+}
+
+/// Delivery log for a webhook, newest first
+/// GET /api/v1/webhooks/{id}/deliveries?status=failed&limit=50
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID"),
+        DeliveriesQuery
+    ),
+    responses(
+        (status = 200, description = "Delivery attempts, newest first", body = ListDeliveriesResponse),
+        (status = 404, description = "Webhook not found")
+    )
+)]
+pub async fn list_deliveries(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<DeliveriesQuery>,
+) -> HttpResponse {
+    let auth = match require_auth(&req) {
+        Ok(auth) => auth,
+        Err(response) => return response,
+    };
+
+    let id = path.into_inner();
+    let repo = WebhookRepository::new(pool.get_ref().clone());
+    if let Err(response) = load_owned(&repo, id, &auth).await {
+        return response;
+    }
+
+    match repo
+        .list_deliveries(id, query.status.as_deref(), query.limit.clamp(1, 500))
+        .await
+    {
+        Ok(deliveries) => {
+            let count = deliveries.len();
+            HttpResponse::Ok().json(ListDeliveriesResponse { deliveries, count })
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to list webhook deliveries");
+            internal_error("Failed to list webhook deliveries")
+        }
+    }
+}
+
+/// Replay a stored event to a webhook
+/// POST /api/v1/webhooks/{id}/redeliver
+///
+/// Delivers regardless of the subscription's event filters, so an event the
+/// caller explicitly asks for is never skipped.
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks/{id}/redeliver",
+    tag = "webhooks",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID")
+    ),
+    request_body = RedeliverRequest,
+    responses(
+        (status = 200, description = "Outcome of the delivery", body = WebhookDelivery),
+        (status = 404, description = "Webhook or event not found"),
+        (status = 409, description = "Webhook is paused"),
+        (status = 503, description = "Webhooks are not configured")
+    )
+)]
+pub async fn redeliver(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<RedeliverRequest>,
+) -> HttpResponse {
+    let auth = match require_auth(&req) {
+        Ok(auth) => auth,
+        Err(response) => return response,
+    };
+
+    let Some(dispatcher) = state.webhooks.clone() else {
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "service_unavailable",
+            "message": "Webhooks are not configured"
+        }));
+    };
+
+    let id = path.into_inner();
+    let repo = WebhookRepository::new(pool.get_ref().clone());
+    let subscription = match load_owned(&repo, id, &auth).await {
+        Ok(subscription) => subscription,
+        Err(response) => return response,
+    };
+    if !subscription.is_active {
+        return HttpResponse::Conflict().json(serde_json::json!({
+            "error": "webhook_inactive",
+            "message": "Reactivate the webhook before redelivering events"
+        }));
+    }
+
+    // Key-scoped events belong to their key; system events to enterprise keys
+    let event = match repo.get_event(body.event_id).await {
+        Ok(Some(event))
+            if event.api_key_id == Some(auth.key_id)
+                || (event.api_key_id.is_none() && auth.tier == "enterprise") =>
+        {
+            event
+        }
+        Ok(_) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "not_found",
+                "message": "Event not found"
+            }));
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to get webhook event");
+            return internal_error("Failed to get webhook event");
+        }
+    };
+
+    match dispatcher.redeliver(&subscription, &event).await {
+        Ok(delivery) => {
+            info!(
+                webhook_id = %id,
+                event_id = %event.id,
+                status = %delivery.status,
+                "Webhook event redelivered"
+            );
+            HttpResponse::Ok().json(delivery)
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to redeliver webhook event");
+            internal_error("Failed to redeliver webhook event")
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Access logging
+//!
+//! Root span builder for `TracingLogger` that skips excluded paths (load
+//! balancer health probes, metrics scrapes) and samples the rest, then emits
+//! one access log event per logged request. Server errors are always logged.
+//! The root span carries the request's `request_id`, so every event logged
+//! while handling it does too.
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    Error, HttpMessage,
+};
+use once_cell::sync::OnceCell;
+use rand::Rng;
+use std::time::Instant;
+use tracing::{field::Empty, info, info_span, warn, Span};
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpanBuilder};
+
+use super::request_id::RequestId;
+use crate::config::AccessLogSettings;
+
+/// Policy installed at startup; requests are logged unsampled until then
+static POLICY: OnceCell<AccessLogPolicy> = OnceCell::new();
+
+/// Which requests get an access log entry
+#[derive(Debug, Clone)]
+pub struct AccessLogPolicy {
+    exclude_paths: Vec<String>,
+    sample_rate: f64,
+    excluded_sample_rate: f64,
+}
+
+impl AccessLogPolicy {
+    pub fn from_settings(settings: &AccessLogSettings) -> Self {
+        Self {
+            exclude_paths: settings.exclude_paths.clone(),
+            sample_rate: settings.sample_rate.clamp(0.0, 1.0),
+            excluded_sample_rate: settings.excluded_sample_rate.clamp(0.0, 1.0),
+        }
+    }
+
+    fn is_excluded(&self, path: &str) -> bool {
+        self.exclude_paths.iter().any(|p| path.starts_with(p))
+    }
+
+    /// Decide whether to log a request, given a uniform roll in `[0, 1)`
+    pub fn should_log(&self, path: &str, roll: f64) -> bool {
+        let rate = if self.is_excluded(path) {
+            self.excluded_sample_rate
+        } else {
+            self.sample_rate
+        };
+        roll < rate
+    }
+}
+
+/// When a logged request started, stored in request extensions
+#[derive(Clone, Copy)]
+struct RequestStart(Instant);
+
+/// Root span for a request, with the fields `DefaultRootSpanBuilder` fills in
+/// when the request ends. The default span's `request_id` is one
+/// tracing-actix-web generates itself, so ours is recorded in its place.
+fn request_span(request: &ServiceRequest) -> Span {
+    let user_agent = request
+        .headers()
+        .get("User-Agent")
+        .and_then(|ua| ua.to_str().ok())
+        .unwrap_or("");
+    let route = request.match_pattern().unwrap_or_default();
+    let connection_info = request.connection_info();
+    let span = info_span!(
+        "HTTP request",
+        http.method = %request.method(),
+        http.route = %route,
+        http.target = %request.uri(),
+        http.host = %connection_info.host(),
+        http.client_ip = %connection_info.realip_remote_addr().unwrap_or(""),
+        http.user_agent = %user_agent,
+        otel.kind = "server",
+        request_id = Empty,
+        http.status_code = Empty,
+        otel.status_code = Empty,
+        exception.message = Empty,
+        exception.details = Empty,
+    );
+    if let Some(id) = request.extensions().get::<RequestId>() {
+        span.record("request_id", id.as_str());
+    }
+    span
+}
+
+/// `TracingLogger` root span builder applying the installed `AccessLogPolicy`
+pub struct AccessLogSpanBuilder;
+
+impl AccessLogSpanBuilder {
+    /// Install the policy used by every worker; later calls are ignored
+    pub fn install(policy: AccessLogPolicy) {
+        let _ = POLICY.set(policy);
+    }
+}
+
+impl RootSpanBuilder for AccessLogSpanBuilder {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let log = POLICY.get().map_or(true, |policy| {
+            policy.should_log(request.path(), rand::thread_rng().gen())
+        });
+        if !log {
+            return Span::none();
+        }
+
+        request
+            .extensions_mut()
+            .insert(RequestStart(Instant::now()));
+        request_span(request)
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        if span.is_disabled() {
+            // Not sampled, but server errors should never go unnoticed
+            if let Ok(response) = outcome {
+                if response.status().is_server_error() {
+                    warn!(
+                        method = %response.request().method(),
+                        path = %response.request().path(),
+                        status = response.status().as_u16(),
+                        "Request failed"
+                    );
+                }
+            }
+            return;
+        }
+
+        DefaultRootSpanBuilder::on_request_end(span.clone(), outcome);
+
+        if let Ok(response) = outcome {
+            let latency_ms = response
+                .request()
+                .extensions()
+                .get::<RequestStart>()
+                .map_or(0, |start| start.0.elapsed().as_millis() as u64);
+            span.in_scope(|| {
+                info!(
+                    status = response.status().as_u16(),
+                    latency_ms, "Request completed"
+                );
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(sample_rate: f64, excluded_sample_rate: f64) -> AccessLogPolicy {
+        AccessLogPolicy::from_settings(&AccessLogSettings {
+            exclude_paths: vec!["/health".to_string(), "/metrics".to_string()],
+            sample_rate,
+            excluded_sample_rate,
+        })
+    }
+
+    #[test]
+    fn test_excluded_paths_are_skipped() {
+        let policy = policy(1.0, 0.0);
+        assert!(!policy.should_log("/health", 0.0));
+        assert!(!policy.should_log("/metrics", 0.5));
+        assert!(policy.should_log("/api/v1/mockups/generate", 0.99));
+    }
+
+    #[test]
+    fn test_sampling() {
+        let policy = policy(0.25, 0.01);
+        assert!(policy.should_log("/api/v1/templates", 0.1));
+        assert!(!policy.should_log("/api/v1/templates", 0.3));
+        assert!(policy.should_log("/health", 0.005));
+        assert!(!policy.should_log("/health", 0.02));
+    }
+}
@@ -5,14 +5,17 @@
 
 use actix_web::{
     dev::{ServiceRequest, ServiceResponse},
-    error::ErrorUnauthorized,
+    error::{ErrorInternalServerError, ErrorServiceUnavailable, ErrorUnauthorized},
     http::header::{HeaderValue, AUTHORIZATION},
     Error, HttpMessage,
 };
+use chrono::Utc;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing::{info, warn};
 
-use crate::db::{ApiKeyRepository, DbApiKey};
+use super::key_cache::ApiKeyCache;
+use crate::db::{ApiKeyRepository, ApiKeyTier, DbApiKey, QuotaCategory, ResourceKind};
 
 /// Extension type for storing authenticated API key in request
 #[derive(Clone)]
@@ -23,6 +26,9 @@ pub struct AuthenticatedKey {
 /// Header name for API key
 pub const API_KEY_HEADER: &str = "X-API-Key";
 
+/// How stale a key's `last_used_at` may get before a request updates it
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
 /// Extract API key from request headers
 pub fn extract_api_key(req: &ServiceRequest) -> Option<String> {
     // First try X-API-Key header
@@ -45,9 +51,16 @@ pub fn extract_api_key(req: &ServiceRequest) -> Option<String> {
 }
 
 /// Validate API key and return authenticated key info
+///
+/// Keys found in `cache` skip the database lookup; keys looked up are added
+/// to it. Unknown keys aren't cached. `last_used_at` is written at most once
+/// a minute per key. Rejected keys are 401 errors, while a
+/// failed lookup is a 500 error so it isn't mistaken for a wrong key, or a
+/// 503 error when no database connection was free.
 pub async fn validate_api_key(
     api_key: &str,
     api_key_repo: &ApiKeyRepository,
+    cache: &ApiKeyCache,
 ) -> Result<DbApiKey, Error> {
     // Validate key format
     if !api_key.starts_with("rim_") || api_key.len() < 36 {
@@ -55,41 +68,61 @@ pub async fn validate_api_key(
         return Err(ErrorUnauthorized("Invalid API key format"));
     }
 
-    // Look up key in database
-    match api_key_repo.validate(api_key).await {
-        Ok(Some(key)) => {
-            // Check if key is valid (active and not expired)
-            if !key.is_valid() {
-                warn!(key_id = %key.id, "API key is inactive or expired");
-                return Err(ErrorUnauthorized("API key is inactive or expired"));
+    let key_hash = ApiKeyRepository::hash_api_key(api_key);
+    let looked_up_at = Instant::now();
+    let (key, cache_hit) = match cache.get(&key_hash) {
+        Some(key) => (key, true),
+        // Look up key in database
+        None => match api_key_repo.validate(api_key).await {
+            Ok(Some(key)) => {
+                cache.insert(key_hash.clone(), key.clone(), looked_up_at);
+                (key, false)
+            }
+            Ok(None) => {
+                warn!("API key not found");
+                return Err(ErrorUnauthorized("Invalid API key"));
+            }
+            Err(e) if e.is_busy() => {
+                warn!(error = %e, "No database connection to validate API key");
+                return Err(ErrorServiceUnavailable("Database busy"));
             }
+            Err(e) => {
+                warn!(error = %e, "Failed to validate API key");
+                return Err(ErrorInternalServerError("Authentication failed"));
+            }
+        },
+    };
 
-            // Update last used timestamp (fire and forget)
-            let key_id = key.id;
-            let repo_clone = api_key_repo.pool.clone();
-            tokio::spawn(async move {
-                let repo = ApiKeyRepository::new(repo_clone);
-                let _ = repo.touch(key_id).await;
-            });
-
-            info!(
-                key_id = %key.id,
-                key_prefix = %key.key_prefix,
-                tier = %key.tier,
-                "API key validated"
-            );
-
-            Ok(key)
-        }
-        Ok(None) => {
-            warn!("API key not found");
-            Err(ErrorUnauthorized("Invalid API key"))
-        }
-        Err(e) => {
-            warn!(error = %e, "Failed to validate API key");
-            Err(ErrorUnauthorized("Authentication failed"))
-        }
+    // Check if key is valid (active and not expired)
+    if !key.is_valid() {
+        warn!(key_id = %key.id, "API key is inactive or expired");
+        return Err(ErrorUnauthorized("API key is inactive or expired"));
+    }
+
+    // Update last used timestamp (fire and forget), unless it's recent
+    let now = Utc::now();
+    let stale = key
+        .last_used_at
+        .map_or(true, |at| (now - at).num_seconds() >= TOUCH_INTERVAL_SECS);
+    if stale {
+        cache.mark_used(&key_hash, now);
+        let key_id = key.id;
+        let repo_clone = api_key_repo.pool.clone();
+        tokio::spawn(async move {
+            let repo = ApiKeyRepository::new(repo_clone);
+            let _ = repo.touch(key_id).await;
+        });
     }
+
+    info!(
+        key_id = %key.id,
+        key_prefix = %key.key_prefix,
+        tier = %key.tier,
+        cache_hit,
+        "API key validated"
+    );
+
+    Ok(key)
 }
 
 /// Authentication result for use in handlers
@@ -100,6 +133,26 @@ pub struct ApiKeyAuth {
     pub rate_limit: i32,
     pub monthly_quota: i32,
     pub owner_email: String,
+    /// Domains design URLs must be hosted on; empty allows any host
+    pub allowed_design_domains: Vec<String>,
+}
+
+impl ApiKeyAuth {
+    /// Monthly budget for a quota category
+    ///
+    /// Renders use the key's own `monthly_quota`; other categories use the
+    /// tier's defaults.
+    pub fn category_quota(&self, category: QuotaCategory) -> i32 {
+        match category {
+            QuotaCategory::Render => self.monthly_quota,
+            _ => ApiKeyTier::from_str(&self.tier).default_category_quota(category),
+        }
+    }
+
+    /// Most resources of a kind this key may keep stored
+    pub fn resource_limit(&self, kind: ResourceKind) -> i64 {
+        ApiKeyTier::from_str(&self.tier).resource_limit(kind)
+    }
 }
 
 impl From<&DbApiKey> for ApiKeyAuth {
@@ -110,6 +163,7 @@ impl From<&DbApiKey> for ApiKeyAuth {
             rate_limit: key.rate_limit_per_minute,
             monthly_quota: key.monthly_quota,
             owner_email: key.owner_email.clone(),
+            allowed_design_domains: key.allowed_design_domains.clone().unwrap_or_default(),
         }
     }
 }
@@ -124,3 +178,153 @@ impl<T: HttpMessage> ApiKeyExt for T {
         self.extensions().get::<ApiKeyAuth>().cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::middleware::check_rate_limit;
+    use crate::db::{CreateApiKeyRequest, DbPool, UpdateApiKeyRequest, UsageRepository};
+
+    /// Repositories on the database in `TEST_DATABASE_URL`, migrations applied
+    ///
+    /// Tests that need it return early when the variable isn't set.
+    fn test_repos() -> Option<(ApiKeyRepository, UsageRepository)> {
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        Some((
+            ApiKeyRepository::new(pool.clone()),
+            UsageRepository::new(pool),
+        ))
+    }
+
+    async fn create_key(repo: &ApiKeyRepository, rate_limit: i32) -> (uuid::Uuid, String) {
+        let created = repo
+            .create(CreateApiKeyRequest {
+                name: "Auth test".to_string(),
+                owner_email: format!("auth-{}@example.com", uuid::Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Free,
+                rate_limit_per_minute: Some(rate_limit),
+                monthly_quota: None,
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+        (created.id, created.api_key)
+    }
+
+    /// Authenticate and count one request, as the middleware does
+    async fn request(
+        api_key: &str,
+        keys: &ApiKeyRepository,
+        usage: &UsageRepository,
+        cache: &ApiKeyCache,
+    ) -> Result<(), Error> {
+        let key = validate_api_key(api_key, keys, cache).await?;
+        check_rate_limit(&ApiKeyAuth::from(&key), usage).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raised_rate_limit_applies_to_next_request() {
+        let Some((keys, usage)) = test_repos() else {
+            return;
+        };
+        let (id, api_key) = create_key(&keys, 1).await;
+        let cache = ApiKeyCache::default();
+
+        request(&api_key, &keys, &usage, &cache).await.unwrap();
+        assert!(request(&api_key, &keys, &usage, &cache).await.is_err());
+
+        let raise = UpdateApiKeyRequest {
+            rate_limit_per_minute: Some(5),
+            ..Default::default()
+        };
+        keys.update(id, &raise).await.unwrap().unwrap();
+        // As the update handler does
+        cache.invalidate(id);
+        request(&api_key, &keys, &usage, &cache).await.unwrap();
+
+        keys.purge(id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_deactivated_key_is_rejected() {
+        let Some((keys, usage)) = test_repos() else {
+            return;
+        };
+        let (id, api_key) = create_key(&keys, 100).await;
+        let cache = ApiKeyCache::default();
+        request(&api_key, &keys, &usage, &cache).await.unwrap();
+
+        let deactivate = UpdateApiKeyRequest {
+            is_active: Some(false),
+            ..Default::default()
+        };
+        keys.update(id, &deactivate).await.unwrap().unwrap();
+        cache.invalidate(id);
+        assert!(request(&api_key, &keys, &usage, &cache).await.is_err());
+
+        keys.purge(id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_deleted_key_is_rejected() {
+        let Some((keys, usage)) = test_repos() else {
+            return;
+        };
+        let (id, api_key) = create_key(&keys, 100).await;
+        let cache = ApiKeyCache::default();
+        request(&api_key, &keys, &usage, &cache).await.unwrap();
+
+        assert!(keys.delete(id).await.unwrap());
+        cache.invalidate(id);
+        assert!(request(&api_key, &keys, &usage, &cache).await.is_err());
+        assert!(keys.validate(&api_key).await.unwrap().is_none());
+        // Still there for its usage history
+        let deleted = keys.get_by_id(id).await.unwrap().unwrap();
+        assert!(deleted.deleted_at.is_some());
+        assert!(!deleted.is_valid());
+
+        keys.purge(id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cached_key_skips_lookup_until_invalidated() {
+        let Some((keys, usage)) = test_repos() else {
+            return;
+        };
+        let (id, api_key) = create_key(&keys, 100).await;
+        let cache = ApiKeyCache::default();
+        request(&api_key, &keys, &usage, &cache).await.unwrap();
+
+        // Revoked in the database, but still trusted from the cache
+        keys.revoke(id).await.unwrap();
+        request(&api_key, &keys, &usage, &cache).await.unwrap();
+        assert_eq!(cache.stats().hits_total, 1);
+
+        // The revoke handler invalidates the key, which rejects it at once
+        cache.invalidate(id);
+        assert!(request(&api_key, &keys, &usage, &cache).await.is_err());
+
+        keys.purge(id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expired_cache_entry_is_looked_up_again() {
+        let Some((keys, usage)) = test_repos() else {
+            return;
+        };
+        let (id, api_key) = create_key(&keys, 100).await;
+        let cache = ApiKeyCache::new(std::time::Duration::ZERO);
+        request(&api_key, &keys, &usage, &cache).await.unwrap();
+
+        keys.revoke(id).await.unwrap();
+        assert!(request(&api_key, &keys, &usage, &cache).await.is_err());
+        assert_eq!(cache.stats().hits_total, 0);
+
+        keys.purge(id).await.unwrap();
+    }
+}
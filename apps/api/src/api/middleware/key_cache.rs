@@ -0,0 +1,242 @@
+//! API Key Validation Cache
+//!
+//! Keeps validated API keys in memory for a short time so authenticating a
+//! request doesn't query `api_keys`. Handlers that revoke, update or rotate
+//! a key invalidate it, so those changes apply from the key's next request.
+//! A lookup that was already running when the key was invalidated isn't
+//! cached, since it may have read the key as it was before the change.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::db::DbApiKey;
+
+/// How long a validated key is trusted before it is looked up again
+pub const API_KEY_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Cached keys before expired entries are swept on insert
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// How long an invalidation turns away lookups that started before it;
+/// far longer than a lookup can wait for a connection and run
+const INVALIDATION_MEMORY: Duration = Duration::from_secs(600);
+
+/// Validated API keys by the SHA-256 hash of the presented secret
+pub struct ApiKeyCache {
+    state: Mutex<CacheState>,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CachedKey {
+    key: DbApiKey,
+    cached_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CachedKey>,
+    /// When each recently changed key was last invalidated
+    invalidated: HashMap<Uuid, Instant>,
+}
+
+/// Snapshot of cache activity
+#[derive(Debug, Clone, Copy)]
+pub struct ApiKeyCacheStats {
+    /// Keys currently cached, including expired ones not yet swept
+    pub entries: u64,
+    pub hits_total: u64,
+    pub misses_total: u64,
+}
+
+impl Default for ApiKeyCache {
+    fn default() -> Self {
+        Self::new(API_KEY_CACHE_TTL)
+    }
+}
+
+impl ApiKeyCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            state: Mutex::new(CacheState::default()),
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The key validated for this secret hash, unless it has expired
+    pub fn get(&self, key_hash: &str) -> Option<DbApiKey> {
+        let entries = &mut self.state.lock().unwrap().entries;
+        let cached = match entries.get(key_hash) {
+            Some(cached) if cached.cached_at.elapsed() < self.ttl => Some(cached.key.clone()),
+            Some(_) => {
+                entries.remove(key_hash);
+                None
+            }
+            None => None,
+        };
+
+        let counter = if cached.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    /// Remember a key validated from the database by a lookup started at
+    /// `looked_up_at`
+    ///
+    /// Skipped when the key was invalidated since the lookup started.
+    pub fn insert(&self, key_hash: String, key: DbApiKey, looked_up_at: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .invalidated
+            .get(&key.id)
+            .is_some_and(|invalidated| *invalidated >= looked_up_at)
+        {
+            return;
+        }
+        let entries = &mut state.entries;
+        if entries.len() >= SWEEP_THRESHOLD {
+            entries.retain(|_, cached| cached.cached_at.elapsed() < self.ttl);
+        }
+        entries.insert(
+            key_hash,
+            CachedKey {
+                key,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Note that a cached key was used at `at`, so later hits see it
+    pub fn mark_used(&self, key_hash: &str, at: DateTime<Utc>) {
+        if let Some(cached) = self.state.lock().unwrap().entries.get_mut(key_hash) {
+            cached.key.last_used_at = Some(at);
+        }
+    }
+
+    /// Forget a key, under every secret it was validated with
+    ///
+    /// A rotated key can be cached under both its old and new secret.
+    pub fn invalidate(&self, key_id: Uuid) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.entries.retain(|_, cached| cached.key.id != key_id);
+        state
+            .invalidated
+            .retain(|_, invalidated| now.duration_since(*invalidated) < INVALIDATION_MEMORY);
+        state.invalidated.insert(key_id, now);
+    }
+
+    pub fn stats(&self) -> ApiKeyCacheStats {
+        ApiKeyCacheStats {
+            entries: self.state.lock().unwrap().entries.len() as u64,
+            hits_total: self.hits.load(Ordering::Relaxed),
+            misses_total: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn key(id: Uuid) -> DbApiKey {
+        DbApiKey {
+            id,
+            key_prefix: "rim_abcdefgh".to_string(),
+            key_hash: "hash".to_string(),
+            name: "Cache test".to_string(),
+            owner_email: "cache@example.com".to_string(),
+            owner_name: None,
+            company: None,
+            tier: "free".to_string(),
+            rate_limit_per_minute: 10,
+            monthly_quota: 100,
+            is_active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            last_used_at: None,
+            expires_at: None,
+            webhook_url: None,
+            allowed_design_domains: None,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_hits_and_misses_are_counted() {
+        let cache = ApiKeyCache::default();
+        let id = Uuid::new_v4();
+
+        assert!(cache.get("a").is_none());
+        cache.insert("a".to_string(), key(id), Instant::now());
+        assert_eq!(cache.get("a").unwrap().id, id);
+        assert_eq!(cache.get("a").unwrap().id, id);
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.hits_total, 2);
+        assert_eq!(stats.misses_total, 1);
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = ApiKeyCache::new(Duration::ZERO);
+        cache.insert("a".to_string(), key(Uuid::new_v4()), Instant::now());
+
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_invalidate_removes_every_secret_of_a_key() {
+        let cache = ApiKeyCache::default();
+        let rotated = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        cache.insert("old".to_string(), key(rotated), Instant::now());
+        cache.insert("new".to_string(), key(rotated), Instant::now());
+        cache.insert("other".to_string(), key(other), Instant::now());
+
+        cache.invalidate(rotated);
+        assert!(cache.get("old").is_none());
+        assert!(cache.get("new").is_none());
+        assert_eq!(cache.get("other").unwrap().id, other);
+    }
+
+    #[test]
+    fn test_lookup_started_before_invalidation_is_not_cached() {
+        let cache = ApiKeyCache::default();
+        let id = Uuid::new_v4();
+
+        // A lookup reads the key, the key is revoked, then the lookup returns
+        let looked_up_at = Instant::now();
+        cache.invalidate(id);
+        cache.insert("a".to_string(), key(id), looked_up_at);
+        assert!(cache.get("a").is_none());
+
+        // Lookups started after the change are cached as usual
+        std::thread::sleep(Duration::from_millis(1));
+        cache.insert("a".to_string(), key(id), Instant::now());
+        assert_eq!(cache.get("a").unwrap().id, id);
+    }
+
+    #[test]
+    fn test_mark_used_updates_the_cached_key() {
+        let cache = ApiKeyCache::default();
+        cache.insert("a".to_string(), key(Uuid::new_v4()), Instant::now());
+        let now = Utc::now();
+        cache.mark_used("a", now);
+        assert_eq!(cache.get("a").unwrap().last_used_at, Some(now));
+    }
+}
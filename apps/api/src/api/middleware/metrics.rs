@@ -0,0 +1,123 @@
+//! Request metrics
+//!
+//! Counts every request and records its latency under the route pattern it
+//! matched (`/api/v1/templates/{id}`), not the raw path, so the number of
+//! series stays bounded.
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::metrics::{Metrics, UNMATCHED_ROUTE};
+
+/// Middleware factory recording HTTP request metrics
+pub struct RequestMetrics {
+    metrics: Arc<Metrics>,
+}
+
+impl RequestMetrics {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestMetricsService {
+            service: Rc::new(service),
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+pub struct RequestMetricsService<S> {
+    service: Rc<S>,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &self,
+        ctx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let metrics = self.metrics.clone();
+        let method = req.method().to_string();
+        // Resolved from the resource map, so requests answered by middleware
+        // before routing still get their route
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+        Box::pin(async move {
+            let start = Instant::now();
+            let result = service.call(req).await;
+            let status = match &result {
+                Ok(res) => res.status().as_u16(),
+                Err(e) => e.as_response_error().status_code().as_u16(),
+            };
+            metrics.observe_request(&method, &route, status, start.elapsed());
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_requests_are_counted_by_route_pattern() {
+        let metrics = Arc::new(Metrics::new());
+        let app = test::init_service(App::new().wrap(RequestMetrics::new(metrics.clone())).route(
+            "/templates/{id}",
+            web::get().to(|| async { HttpResponse::Ok().finish() }),
+        ))
+        .await;
+
+        for id in ["tee", "mug"] {
+            let req = test::TestRequest::get()
+                .uri(&format!("/templates/{}", id))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+        let req = test::TestRequest::get().uri("/nope").to_request();
+        test::call_service(&app, req).await;
+
+        let body = metrics.render();
+        assert!(body.contains(
+            r#"r_image_magic_http_requests_total{method="GET",route="/templates/{id}",status="200"} 2"#
+        ));
+        assert!(body.contains(
+            r#"r_image_magic_http_requests_total{method="GET",route="unmatched",status="404"} 1"#
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+//! Failed Authentication Penalty Box
+//!
+//! Counts failed API key validations per client IP and key prefix. Once a
+//! client fails too often within a minute it is turned away with 429 until
+//! the minute is up, which keeps brute force guessing of a key slow. The
+//! number of clients tracked is capped, the oldest windows giving way first,
+//! so a flood of new clients can't grow it without bound.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Failed validations allowed per client and prefix within the window
+pub const MAX_AUTH_FAILURES: u32 = 10;
+
+/// How long failures are counted before a client starts over
+pub const AUTH_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Client and prefix pairs tracked at once
+const MAX_TRACKED: usize = 10_000;
+
+/// Failed validations by client IP and key prefix
+pub struct PenaltyBox {
+    failures: Mutex<Failures>,
+    max_failures: u32,
+    window: Duration,
+    capacity: usize,
+}
+
+type Client = (String, String);
+
+struct FailureWindow {
+    started: Instant,
+    failures: u32,
+}
+
+#[derive(Default)]
+struct Failures {
+    windows: HashMap<Client, FailureWindow>,
+    /// Each window's client, oldest window first
+    order: VecDeque<Client>,
+}
+
+impl Failures {
+    /// Drop the oldest window
+    fn pop_oldest(&mut self) {
+        if let Some(client) = self.order.pop_front() {
+            self.windows.remove(&client);
+        }
+    }
+
+    /// Drop the windows that ended by `now`; they are all at the front
+    fn expire(&mut self, now: Instant, window: Duration) {
+        while let Some(client) = self.order.front() {
+            let ended = self
+                .windows
+                .get(client)
+                .map_or(true, |oldest| now.duration_since(oldest.started) >= window);
+            if !ended {
+                break;
+            }
+            self.pop_oldest();
+        }
+    }
+}
+
+impl Default for PenaltyBox {
+    fn default() -> Self {
+        Self::new(MAX_AUTH_FAILURES, AUTH_FAILURE_WINDOW)
+    }
+}
+
+impl PenaltyBox {
+    pub fn new(max_failures: u32, window: Duration) -> Self {
+        Self {
+            failures: Mutex::new(Failures::default()),
+            max_failures,
+            window,
+            capacity: MAX_TRACKED,
+        }
+    }
+
+    /// Track at most `capacity` client and prefix pairs
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Time until a locked out client may try the prefix again
+    ///
+    /// None when the client hasn't reached the failure limit.
+    pub fn retry_after(&self, client: &str, prefix: &str) -> Option<Duration> {
+        let failures = self.failures.lock().unwrap();
+        let window = failures
+            .windows
+            .get(&(client.to_string(), prefix.to_string()))?;
+        let elapsed = window.started.elapsed();
+        if window.failures < self.max_failures || elapsed >= self.window {
+            return None;
+        }
+        Some(self.window - elapsed)
+    }
+
+    /// Count a failed validation, starting a new window if the last one ended
+    ///
+    /// A new client past the capacity evicts the oldest window. Windows start
+    /// in the order they are queued, so expiring and evicting only ever look
+    /// at the front of the queue.
+    pub fn record_failure(&self, client: &str, prefix: &str) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        failures.expire(now, self.window);
+
+        let key = (client.to_string(), prefix.to_string());
+        if let Some(window) = failures.windows.get_mut(&key) {
+            window.failures += 1;
+            return;
+        }
+        while failures.windows.len() >= self.capacity {
+            failures.pop_oldest();
+        }
+        failures.windows.insert(
+            key.clone(),
+            FailureWindow {
+                started: now,
+                failures: 1,
+            },
+        );
+        failures.order.push_back(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_after_max_failures() {
+        let penalty_box = PenaltyBox::new(3, AUTH_FAILURE_WINDOW);
+
+        for _ in 0..2 {
+            penalty_box.record_failure("203.0.113.7", "rim_abcdefgh");
+        }
+        assert!(penalty_box
+            .retry_after("203.0.113.7", "rim_abcdefgh")
+            .is_none());
+
+        penalty_box.record_failure("203.0.113.7", "rim_abcdefgh");
+        let retry_after = penalty_box
+            .retry_after("203.0.113.7", "rim_abcdefgh")
+            .unwrap();
+        assert!(retry_after <= AUTH_FAILURE_WINDOW);
+        assert!(retry_after > Duration::ZERO);
+
+        // Other clients and prefixes are counted separately
+        assert!(penalty_box
+            .retry_after("203.0.113.8", "rim_abcdefgh")
+            .is_none());
+        assert!(penalty_box
+            .retry_after("203.0.113.7", "rim_hgfedcba")
+            .is_none());
+    }
+
+    #[test]
+    fn test_lockout_expires_with_the_window() {
+        let penalty_box = PenaltyBox::new(2, Duration::from_millis(20));
+        penalty_box.record_failure("203.0.113.7", "rim_abcdefgh");
+        penalty_box.record_failure("203.0.113.7", "rim_abcdefgh");
+        assert!(penalty_box
+            .retry_after("203.0.113.7", "rim_abcdefgh")
+            .is_some());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(penalty_box
+            .retry_after("203.0.113.7", "rim_abcdefgh")
+            .is_none());
+
+        // A failure after the window starts a new count
+        penalty_box.record_failure("203.0.113.7", "rim_abcdefgh");
+        assert!(penalty_box
+            .retry_after("203.0.113.7", "rim_abcdefgh")
+            .is_none());
+    }
+
+    #[test]
+    fn test_tracked_clients_are_capped() {
+        let penalty_box = PenaltyBox::new(2, AUTH_FAILURE_WINDOW).with_capacity(3);
+        penalty_box.record_failure("203.0.113.1", "rim_abcdefgh");
+        penalty_box.record_failure("203.0.113.1", "rim_abcdefgh");
+        for n in 2..10 {
+            penalty_box.record_failure(&format!("203.0.113.{n}"), "rim_abcdefgh");
+            penalty_box.record_failure(&format!("203.0.113.{n}"), "rim_abcdefgh");
+        }
+
+        let failures = penalty_box.failures.lock().unwrap();
+        assert_eq!(failures.windows.len(), 3);
+        assert_eq!(failures.order.len(), 3);
+        drop(failures);
+        // The oldest clients gave way to the newest
+        assert!(penalty_box
+            .retry_after("203.0.113.1", "rim_abcdefgh")
+            .is_none());
+        assert!(penalty_box
+            .retry_after("203.0.113.9", "rim_abcdefgh")
+            .is_some());
+    }
+}
@@ -0,0 +1,223 @@
+//! Request IDs
+//!
+//! Every request gets an ID, taken from the caller's `X-Request-Id` header
+//! when it's a sane token and generated otherwise. It is stored in request
+//! extensions, scoped to the handler's task so error envelopes can quote it,
+//! echoed back in the response, and recorded on the access log span and the
+//! usage log row, so one ID ties a client report to the server's logs.
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    Error, HttpMessage, HttpRequest,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use std::fmt;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Header carrying the request ID in both directions
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest caller-supplied ID accepted; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+/// ID of one request, stored in request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Use the caller's ID if it's a plain token, otherwise generate one
+    pub fn from_header(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some(id) if is_valid_request_id(id) => Self(id.to_string()),
+            _ => Self::generate(),
+        }
+    }
+
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The ID assigned to a request, if the middleware ran
+    pub fn of(req: &HttpRequest) -> Option<RequestId> {
+        req.extensions().get::<RequestId>().cloned()
+    }
+
+    /// The ID of the request whose handler is running on this task
+    pub fn current() -> Option<String> {
+        CURRENT_REQUEST_ID.try_with(|id| id.0.clone()).ok()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// IDs end up in logs and headers, so only short tokens without spaces or
+/// control characters are taken as-is
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+/// Middleware factory assigning request IDs; wrap it outside `TracingLogger`
+/// so the access log span can pick the ID up
+pub struct AssignRequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for AssignRequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AssignRequestIdService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(AssignRequestIdService {
+            service: Rc::new(service),
+        })
+    }
+}
+
+pub struct AssignRequestIdService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AssignRequestIdService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &self,
+        ctx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let request_id = RequestId::from_header(
+            req.headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok()),
+        );
+        req.extensions_mut().insert(request_id.clone());
+        let http_req = req.request().clone();
+
+        Box::pin(async move {
+            let result = CURRENT_REQUEST_ID
+                .scope(request_id.clone(), service.call(req))
+                .await;
+            // Turn errors into responses here so they carry the header too
+            let mut res = match result {
+                Ok(res) => res.map_into_left_body(),
+                Err(e) => ServiceResponse::from_err(e, http_req).map_into_right_body(),
+            };
+            if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static("x-request-id"), value);
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    fn app_route() -> actix_web::Route {
+        web::get().to(|req: HttpRequest| async move {
+            HttpResponse::Ok().json(serde_json::json!({
+                "extension": RequestId::of(&req).map(|id| id.to_string()),
+                "current": RequestId::current(),
+            }))
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_provided_request_id_is_echoed() {
+        let app =
+            test::init_service(App::new().wrap(AssignRequestId).route("/", app_route())).await;
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "client-trace.42"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(
+            res.headers().get(REQUEST_ID_HEADER).unwrap(),
+            "client-trace.42"
+        );
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["extension"], "client-trace.42");
+        assert_eq!(body["current"], "client-trace.42");
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_generated_when_absent_or_invalid() {
+        let app =
+            test::init_service(App::new().wrap(AssignRequestId).route("/", app_route())).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        let generated = res
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(Uuid::parse_str(generated).is_ok());
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "has spaces; and=junk"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        let replaced = res
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(Uuid::parse_str(replaced).is_ok());
+
+        // Unmatched routes still get one
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/nope").to_request()).await;
+        assert!(res.headers().contains_key(REQUEST_ID_HEADER));
+    }
+
+    #[test]
+    fn test_request_id_validation() {
+        assert!(is_valid_request_id("9f1c2a3b-0000-4000-8000-000000000000"));
+        assert!(is_valid_request_id("trace:abc_1.2"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("line\nbreak"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+        assert_eq!(RequestId::current(), None);
+    }
+}
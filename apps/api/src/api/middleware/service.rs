@@ -10,19 +10,39 @@ use actix_web::{
     Error, HttpMessage, HttpResponse,
 };
 use futures::future::{ok, LocalBoxFuture, Ready};
+use parking_lot::RwLock;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::time::Instant;
 use tracing::{info, warn};
 
 use super::auth::{extract_api_key, validate_api_key, ApiKeyAuth};
+use super::key_cache::ApiKeyCache;
+use super::penalty_box::PenaltyBox;
 use super::rate_limit::{RATE_LIMIT_LIMIT, RATE_LIMIT_REMAINING, RATE_LIMIT_RESET};
-use crate::config::pricing_url;
-use crate::db::{ApiKeyRepository, DbPool, UsageLogEntry, UsageRepository};
+use super::request_id::RequestId;
+use super::usage::{QuotaExceededInfo, RenderUsage, UsageInfo};
+use crate::config::BillingSettings;
+use crate::db::{ApiKeyRepository, DbPool, QuotaCategory, UsageLogEntry, UsageRepository};
+use crate::metrics::Metrics;
+use crate::shutdown::Shutdown;
+use crate::webhooks::{reached_thresholds, QuotaAlert, QuotaAlerter, QuotaWebhook};
 
 /// Middleware factory for API authentication and rate limiting
 pub struct ApiMiddleware {
     pool: Option<DbPool>,
+    /// Validated keys, shared with the handlers that change keys
+    key_cache: Arc<ApiKeyCache>,
+    /// Failed validations by client, shared between workers
+    penalty_box: Arc<PenaltyBox>,
+    /// Unit weights charged per request, replaced on config reload
+    billing: Arc<RwLock<BillingSettings>>,
+    /// Sends quota threshold alerts to keys with a webhook URL
+    quota_alerter: Arc<QuotaAlerter>,
+    /// Tracks usage logging so shutdown waits for it
+    shutdown: Arc<Shutdown>,
+    /// Counts rate limit rejections
+    metrics: Arc<Metrics>,
     /// Paths that don't require authentication
     public_paths: Vec<String>,
 }
@@ -31,14 +51,58 @@ impl ApiMiddleware {
     pub fn new(pool: Option<DbPool>) -> Self {
         Self {
             pool,
+            key_cache: Arc::new(ApiKeyCache::default()),
+            penalty_box: Arc::new(PenaltyBox::default()),
+            billing: Arc::new(RwLock::new(BillingSettings::default())),
+            quota_alerter: Arc::new(QuotaAlerter::default()),
+            shutdown: Arc::new(Shutdown::default()),
+            metrics: Arc::new(Metrics::default()),
             public_paths: vec![
+                // Covers the /health/live and /health/ready probes
                 "/health".to_string(),
+                "/metrics".to_string(),
                 "/swagger-ui".to_string(),
                 "/api-docs".to_string(),
             ],
         }
     }
 
+    /// Share `cache` instead of keeping a cache of this middleware's own
+    pub fn with_key_cache(mut self, cache: Arc<ApiKeyCache>) -> Self {
+        self.key_cache = cache;
+        self
+    }
+
+    /// Share `penalty_box` instead of counting this middleware's failures alone
+    pub fn with_penalty_box(mut self, penalty_box: Arc<PenaltyBox>) -> Self {
+        self.penalty_box = penalty_box;
+        self
+    }
+
+    /// Charge requests with `billing`'s weights instead of the defaults
+    pub fn with_billing(mut self, billing: Arc<RwLock<BillingSettings>>) -> Self {
+        self.billing = billing;
+        self
+    }
+
+    /// Send quota alerts through `alerter`, shared with other workers
+    pub fn with_quota_alerter(mut self, alerter: Arc<QuotaAlerter>) -> Self {
+        self.quota_alerter = alerter;
+        self
+    }
+
+    /// Run usage logging as tasks `shutdown` waits for before exit
+    pub fn with_shutdown(mut self, shutdown: Arc<Shutdown>) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Record rejections in `metrics`, shared with the `/metrics` endpoint
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     pub fn with_public_paths(mut self, paths: Vec<String>) -> Self {
         self.public_paths.extend(paths);
         self
@@ -64,6 +128,12 @@ where
         ok(ApiMiddlewareService {
             service: Rc::new(service),
             pool: self.pool.clone(),
+            key_cache: self.key_cache.clone(),
+            penalty_box: self.penalty_box.clone(),
+            billing: self.billing.clone(),
+            quota_alerter: self.quota_alerter.clone(),
+            shutdown: self.shutdown.clone(),
+            metrics: self.metrics.clone(),
             public_paths: self.public_paths.clone(),
         })
     }
@@ -73,6 +143,12 @@ where
 pub struct ApiMiddlewareService<S> {
     service: Rc<S>,
     pool: Option<DbPool>,
+    key_cache: Arc<ApiKeyCache>,
+    penalty_box: Arc<PenaltyBox>,
+    billing: Arc<RwLock<BillingSettings>>,
+    quota_alerter: Arc<QuotaAlerter>,
+    shutdown: Arc<Shutdown>,
+    metrics: Arc<Metrics>,
     public_paths: Vec<String>,
 }
 
@@ -101,6 +177,12 @@ where
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let service = self.service.clone();
         let pool = self.pool.clone();
+        let key_cache = self.key_cache.clone();
+        let penalty_box = self.penalty_box.clone();
+        let billing = self.billing.clone();
+        let quota_alerter = self.quota_alerter.clone();
+        let shutdown = self.shutdown.clone();
+        let metrics = self.metrics.clone();
         let path = req.path().to_string();
         let method = req.method().to_string();
         let is_public = self.is_public_path(&path);
@@ -108,7 +190,7 @@ where
         Box::pin(async move {
             let start = Instant::now();
 
-            // Skip auth for public paths
+            // Skip auth and usage logging for public paths (health probes, scrapes)
             if is_public {
                 let res = service.call(req).await?;
                 return Ok(res.map_into_left_body());
@@ -137,29 +219,98 @@ where
                 }
             };
 
+            // Turn away clients that keep failing to guess a key. They are
+            // counted by the connecting peer: forwarding headers are the
+            // client's to set, and a fresh one per guess would start a new count
+            let client = req
+                .peer_addr()
+                .map(|addr| addr.ip().to_string())
+                .unwrap_or_default();
+            let prefix = ApiKeyRepository::key_prefix(&api_key).unwrap_or_default();
+            if let Some(retry_after) = penalty_box.retry_after(&client, prefix) {
+                let seconds = retry_after.as_secs().max(1);
+                warn!(client = %client, key_prefix = %prefix, "Too many failed API key attempts");
+                let response = HttpResponse::TooManyRequests()
+                    .insert_header(("Retry-After", seconds.to_string()))
+                    .json(serde_json::json!({
+                        "error": "too_many_failed_attempts",
+                        "message": "Too many invalid API key attempts. Try again later.",
+                        "retry_after_seconds": seconds
+                    }));
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
             // Validate API key
             let api_key_repo = ApiKeyRepository::new(pool.clone());
-            let db_key = match validate_api_key(&api_key, &api_key_repo).await {
+            let db_key = match validate_api_key(&api_key, &api_key_repo, &key_cache).await {
                 Ok(key) => key,
                 Err(e) => {
-                    let response = HttpResponse::Unauthorized().json(serde_json::json!({
-                        "error": "unauthorized",
-                        "message": e.to_string()
-                    }));
+                    let status = e.as_response_error().status_code();
+                    if status == StatusCode::SERVICE_UNAVAILABLE {
+                        let response = crate::api::handlers::database_busy();
+                        return Ok(req.into_response(response).map_into_right_body());
+                    }
+                    let response = if status == StatusCode::UNAUTHORIZED {
+                        penalty_box.record_failure(&client, prefix);
+                        HttpResponse::Unauthorized().json(serde_json::json!({
+                            "error": "unauthorized",
+                            "message": e.to_string()
+                        }))
+                    } else {
+                        HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": "internal_error",
+                            "message": e.to_string()
+                        }))
+                    };
                     return Ok(req.into_response(response).map_into_right_body());
                 }
             };
 
             // Create auth info
             let auth = ApiKeyAuth::from(&db_key);
+            let quota_webhook = db_key.webhook_url.clone().map(|url| QuotaWebhook {
+                url,
+                secret: db_key.key_hash.clone(),
+            });
             let key_id = auth.key_id;
             let rate_limit = auth.rate_limit;
-            let monthly_quota = auth.monthly_quota;
+            let category = QuotaCategory::for_path(&path);
+            let category_quota = auth.category_quota(category);
 
-            // Check rate limit
+            // Check the monthly budget for this endpoint group first, so requests
+            // turned away for quota don't use up the rate limit
             let usage_repo = UsageRepository::new(pool.clone());
+            let quota_used = match usage_repo
+                .check_quota(key_id, category, category_quota)
+                .await
+            {
+                Ok((true, used)) => Some(used), // Quota OK
+                Ok((false, used)) => {
+                    let info = QuotaExceededInfo {
+                        category,
+                        monthly_quota: category_quota,
+                        current_usage: used,
+                        tier: auth.tier.clone(),
+                    };
+                    let mut response = HttpResponse::PaymentRequired().json(info.to_json());
+                    UsageInfo::new(category_quota, used).add_headers(response.headers_mut());
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+                Err(e) => {
+                    warn!(error = %e, "Quota check failed");
+                    // Continue anyway - don't block on quota check failure
+                    None
+                }
+            };
+
+            // Check rate limit
             let rate_status = match usage_repo.check_rate_limit(key_id, rate_limit).await {
                 Ok(status) => status,
+                Err(e) if e.is_busy() => {
+                    warn!(error = %e, "No database connection for the rate limit check");
+                    let response = crate::api::handlers::database_busy();
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
                 Err(e) => {
                     warn!(error = %e, "Rate limit check failed");
                     let response = HttpResponse::InternalServerError().json(serde_json::json!({
@@ -171,6 +322,7 @@ where
             };
 
             if !rate_status.allowed {
+                metrics.record_rate_limited();
                 let seconds_until_reset = (rate_status.reset_at - chrono::Utc::now())
                     .num_seconds()
                     .max(1);
@@ -189,32 +341,13 @@ where
                 return Ok(req.into_response(response).map_into_right_body());
             }
 
-            // Check monthly quota
-            match usage_repo.check_quota(key_id, monthly_quota).await {
-                Ok(true) => {} // Quota OK
-                Ok(false) => {
-                    let upgrade_url = pricing_url();
-                    let response = HttpResponse::PaymentRequired()
-                        .json(serde_json::json!({
-                            "error": "quota_exceeded",
-                            "message": format!("Monthly quota of {} requests exceeded. Upgrade your plan for more requests.", monthly_quota),
-                            "quota": monthly_quota,
-                            "upgrade_url": upgrade_url
-                        }));
-                    return Ok(req.into_response(response).map_into_right_body());
-                }
-                Err(e) => {
-                    warn!(error = %e, "Quota check failed");
-                    // Continue anyway - don't block on quota check failure
-                }
-            }
-
             // Store auth info in request extensions
             req.extensions_mut().insert(auth.clone());
 
             // Extract info for usage logging
             let ip_address = super::usage::extract_client_ip(&req);
             let user_agent = super::usage::extract_user_agent(&req);
+            let request_id = req.extensions().get::<RequestId>().map(|id| id.to_string());
 
             // Call the actual service
             let res = service.call(req).await?;
@@ -236,24 +369,74 @@ where
             };
 
             let (error_code, error_message) = error_info.unzip();
+            let (template_id, render_count) = RenderUsage::of(&res)
+                .map(|usage| (usage.template_id, Some(usage.render_count)))
+                .unwrap_or_default();
+            let (billable_units, alert_thresholds) = {
+                let billing = billing.read();
+                let units = billing.units(&path, render_count);
+                (units, billing.alert_thresholds.clone())
+            };
+
+            // Usage including this request, and the alerts it may have triggered
+            let usage_info =
+                quota_used.map(|used| UsageInfo::new(category_quota, used + billable_units));
+            let alerts_due = match (&quota_webhook, &usage_info) {
+                (Some(_), Some(info)) => {
+                    reached_thresholds(&alert_thresholds, info.monthly_quota, info.monthly_used)
+                }
+                _ => Vec::new(),
+            };
+            let used_after = usage_info.as_ref().map_or(0, |info| info.monthly_used);
 
             let log_pool = pool.clone();
-            tokio::spawn(async move {
+            shutdown.spawn(async move {
                 let log_repo = UsageRepository::new(log_pool);
                 let entry = UsageLogEntry {
                     api_key_id: key_id,
                     endpoint: path,
+                    category,
                     method,
-                    template_id: None, // TODO: Extract from request body for generate endpoint
+                    template_id,
+                    render_count,
+                    billable_units,
                     status_code: status_code.as_u16() as i32,
                     response_time_ms: Some(response_time_ms),
                     error_code,
                     error_message,
                     ip_address,
                     user_agent,
+                    request_id,
                 };
                 if let Err(e) = log_repo.log_usage(entry).await {
                     warn!(error = %e, "Failed to log usage");
+                    return;
+                }
+
+                // Each threshold is claimed once per month, so only one request alerts
+                let Some(webhook) = quota_webhook else {
+                    return;
+                };
+                let year_month = chrono::Utc::now().format("%Y-%m").to_string();
+                for threshold in alerts_due {
+                    match log_repo
+                        .claim_quota_alert(key_id, &year_month, category, threshold)
+                        .await
+                    {
+                        Ok(true) => quota_alerter.send(
+                            webhook.clone(),
+                            QuotaAlert {
+                                api_key_id: key_id,
+                                category,
+                                year_month: year_month.clone(),
+                                threshold,
+                                quota: category_quota,
+                                used: used_after,
+                            },
+                        ),
+                        Ok(false) => {}
+                        Err(e) => warn!(error = %e, threshold, "Failed to claim quota alert"),
+                    }
                 }
             });
 
@@ -270,8 +453,104 @@ where
             if let Ok(reset) = rate_status.reset_at.timestamp().to_string().parse() {
                 headers.insert(RATE_LIMIT_RESET.parse().unwrap(), reset);
             }
+            if let Some(info) = &usage_info {
+                info.add_headers(headers);
+            }
 
             Ok(res)
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::middleware::{AssignRequestId, API_KEY_HEADER};
+    use crate::db::{ApiKeyTier, CreateApiKeyRequest};
+    use actix_web::{test, web, App};
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn test_usage_log_records_request_id() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let key = ApiKeyRepository::new(pool.clone())
+            .create(CreateApiKeyRequest {
+                name: "Request ID test".to_string(),
+                owner_email: format!("request-id-{}@example.com", uuid::Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Free,
+                rate_limit_per_minute: None,
+                monthly_quota: None,
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+
+        let shutdown = Arc::new(Shutdown::default());
+        let app = test::init_service(
+            App::new()
+                .wrap(ApiMiddleware::new(Some(pool.clone())).with_shutdown(shutdown.clone()))
+                .wrap(AssignRequestId)
+                .route(
+                    "/api/v1/templates",
+                    web::get().to(|| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/api/v1/templates")
+            .insert_header((API_KEY_HEADER, key.api_key.as_str()))
+            .insert_header(("X-Request-Id", "usage-row-check"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(shutdown.wait_for_tasks(Duration::from_secs(5)).await);
+
+        let client = pool.get().await.unwrap();
+        let row = client
+            .query_one(
+                "SELECT request_id FROM usage_logs WHERE api_key_id = $1",
+                &[&key.id],
+            )
+            .await
+            .unwrap();
+        let request_id: Option<String> = row.get("request_id");
+        assert_eq!(request_id.as_deref(), Some("usage-row-check"));
+    }
+
+    #[actix_web::test]
+    async fn test_rotating_forwarded_for_keeps_the_lockout() {
+        // Never connected to: malformed keys are refused before any lookup
+        let pool = DbPool::new("postgres://nobody@127.0.0.1:1/none").unwrap();
+        let penalty_box = Arc::new(PenaltyBox::new(3, Duration::from_secs(60)));
+        let app = test::init_service(
+            App::new()
+                .wrap(ApiMiddleware::new(Some(pool)).with_penalty_box(penalty_box))
+                .route(
+                    "/api/v1/templates",
+                    web::get().to(|| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+        let guess = |n: u32| {
+            test::TestRequest::get()
+                .uri("/api/v1/templates")
+                .peer_addr("203.0.113.7:40000".parse().unwrap())
+                .insert_header(("X-Forwarded-For", format!("198.51.100.{n}")))
+                .insert_header((API_KEY_HEADER, "rim_guessing"))
+                .to_request()
+        };
+
+        for n in 0..3 {
+            let res = test::call_service(&app, guess(n)).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+        let res = test::call_service(&app, guess(3)).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -3,7 +3,12 @@
 //! Records API usage for billing and analytics.
 //! Tracks request counts, response times, and errors per API key.
 
-use actix_web::{dev::ServiceRequest, http::StatusCode, HttpMessage};
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    http::StatusCode,
+    HttpMessage, HttpRequest,
+};
 use chrono::Utc;
 use std::net::IpAddr;
 use std::time::Instant;
@@ -11,7 +16,7 @@ use tracing::{info, warn};
 
 use super::auth::ApiKeyAuth;
 use crate::config::pricing_url;
-use crate::db::{UsageLogEntry, UsageRepository};
+use crate::db::{QuotaCategory, UsageLogEntry, UsageRepository};
 
 /// Request timing context
 #[derive(Clone, Debug)]
@@ -37,6 +42,38 @@ impl Default for RequestTiming {
     }
 }
 
+/// What a render request produced, attached by its handler for the usage log
+///
+/// The middleware can't read request bodies, so handlers that render record
+/// the template and the mockups produced in the request extensions, and the
+/// middleware picks them up once the handler returns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderUsage {
+    /// Template rendered, None for batches spanning several templates
+    pub template_id: Option<String>,
+    /// Mockups produced, or queued to render in the background
+    pub render_count: i32,
+}
+
+impl RenderUsage {
+    pub fn new(template_id: Option<String>, render_count: i32) -> Self {
+        Self {
+            template_id,
+            render_count,
+        }
+    }
+
+    /// Attach to the request for its usage log
+    pub fn record(self, req: &HttpRequest) {
+        req.extensions_mut().insert(self);
+    }
+
+    /// Usage recorded by the handler that produced `res`
+    pub fn of<B>(res: &ServiceResponse<B>) -> Option<Self> {
+        res.request().extensions().get::<RenderUsage>().cloned()
+    }
+}
+
 /// Extract client IP from request
 pub fn extract_client_ip(req: &ServiceRequest) -> Option<IpAddr> {
     // Try X-Forwarded-For first (for proxied requests)
@@ -78,27 +115,36 @@ pub fn log_usage_async(
     auth: ApiKeyAuth,
     endpoint: String,
     method: String,
-    template_id: Option<String>,
+    render_usage: Option<RenderUsage>,
+    billable_units: i32,
     status_code: StatusCode,
     response_time_ms: Option<i32>,
     error_info: Option<(String, String)>, // (error_code, error_message)
     ip_address: Option<IpAddr>,
     user_agent: Option<String>,
+    request_id: Option<String>,
 ) {
     tokio::spawn(async move {
         let (error_code, error_message) = error_info.unzip();
+        let (template_id, render_count) = render_usage
+            .map(|usage| (usage.template_id, Some(usage.render_count)))
+            .unwrap_or_default();
 
         let entry = UsageLogEntry {
             api_key_id: auth.key_id,
+            category: QuotaCategory::for_path(&endpoint),
             endpoint,
             method,
             template_id,
+            render_count,
+            billable_units,
             status_code: status_code.as_u16() as i32,
             response_time_ms,
             error_code,
             error_message,
             ip_address,
             user_agent,
+            request_id,
         };
 
         if let Err(e) = usage_repo.log_usage(entry).await {
@@ -107,17 +153,23 @@ pub fn log_usage_async(
     });
 }
 
-/// Check if API key has remaining quota
-pub async fn check_quota(auth: &ApiKeyAuth, usage_repo: &UsageRepository) -> Result<bool, String> {
+/// Check if API key has remaining quota in a category
+pub async fn check_quota(
+    auth: &ApiKeyAuth,
+    usage_repo: &UsageRepository,
+    category: QuotaCategory,
+) -> Result<bool, String> {
     usage_repo
-        .check_quota(auth.key_id, auth.monthly_quota)
+        .check_quota(auth.key_id, category, auth.category_quota(category))
         .await
+        .map(|(allowed, _)| allowed)
         .map_err(|e| format!("Quota check failed: {}", e))
 }
 
 /// Quota exceeded response details
 #[derive(Debug, Clone)]
 pub struct QuotaExceededInfo {
+    pub category: QuotaCategory,
     pub monthly_quota: i32,
     pub current_usage: i32,
     pub tier: String,
@@ -129,9 +181,10 @@ impl QuotaExceededInfo {
         serde_json::json!({
             "error": "quota_exceeded",
             "message": format!(
-                "Monthly quota of {} requests exceeded. Current usage: {}. Upgrade your plan for more requests.",
-                self.monthly_quota, self.current_usage
+                "Monthly {} quota of {} units exceeded. Current usage: {} units. Upgrade your plan for more.",
+                self.category.as_str(), self.monthly_quota, self.current_usage
             ),
+            "category": self.category,
             "quota": self.monthly_quota,
             "usage": self.current_usage,
             "tier": self.tier,
@@ -148,7 +201,79 @@ pub struct UsageInfo {
     pub monthly_remaining: i32,
 }
 
+impl UsageInfo {
+    pub fn new(monthly_quota: i32, monthly_used: i32) -> Self {
+        Self {
+            monthly_quota,
+            monthly_used,
+            monthly_remaining: (monthly_quota - monthly_used).max(0),
+        }
+    }
+
+    /// Set the X-Quota-* headers
+    pub fn add_headers(&self, headers: &mut HeaderMap) {
+        for (name, value) in [
+            (QUOTA_LIMIT, self.monthly_quota),
+            (QUOTA_USED, self.monthly_used),
+            (QUOTA_REMAINING, self.monthly_remaining),
+        ] {
+            if let Ok(name) = HeaderName::try_from(name) {
+                headers.insert(name, HeaderValue::from(value));
+            }
+        }
+    }
+}
+
 /// Headers for usage info
 pub const QUOTA_LIMIT: &str = "X-Quota-Limit";
 pub const QUOTA_USED: &str = "X-Quota-Used";
 pub const QUOTA_REMAINING: &str = "X-Quota-Remaining";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[test]
+    fn test_usage_info_headers() {
+        let mut response = HttpResponse::Ok().finish();
+        UsageInfo::new(100, 120).add_headers(response.headers_mut());
+
+        let header = |name: &str| response.headers().get(name).unwrap().to_str().unwrap();
+        assert_eq!(header("X-Quota-Limit"), "100");
+        assert_eq!(header("X-Quota-Used"), "120");
+        assert_eq!(header("X-Quota-Remaining"), "0");
+    }
+
+    #[actix_web::test]
+    async fn test_render_usage_reaches_the_middleware() {
+        let app = test::init_service(
+            App::new()
+                .route(
+                    "/api/v1/mockups/generate",
+                    web::post().to(|req: HttpRequest| async move {
+                        RenderUsage::new(Some("white_male_front".to_string()), 1).record(&req);
+                        HttpResponse::Ok().finish()
+                    }),
+                )
+                .route("/api/v1/templates", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/mockups/generate")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            RenderUsage::of(&res),
+            Some(RenderUsage::new(Some("white_male_front".to_string()), 1))
+        );
+
+        // Requests that don't render leave the template unset
+        let req = test::TestRequest::get()
+            .uri("/api/v1/templates")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(RenderUsage::of(&res), None);
+    }
+}
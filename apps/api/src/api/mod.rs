@@ -1,26 +1,67 @@
 //! API module - HTTP routes and handlers
 
+pub mod examples;
 pub mod handlers;
 pub mod middleware;
 pub mod openapi;
+pub mod validation;
 
-use actix_web::web;
+use actix_web::{guard, web};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+use crate::api::examples::RequestExamples;
 use crate::api::openapi::ApiDoc;
 
 /// Configure all API routes
-pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+///
+/// `examples` are embedded into the OpenAPI spec served to the Swagger UI.
+pub fn configure_routes(cfg: &mut web::ServiceConfig, examples: &RequestExamples) {
+    let mut openapi = ApiDoc::openapi();
+    examples.embed(&mut openapi);
+
     cfg.service(
         web::scope("/api/v1")
             .service(
                 web::scope("/tile").route("", web::post().to(handlers::tile::tile_pattern)),
             )
-            .service(web::scope("/mockups").route(
-                "/generate",
-                web::post().to(handlers::generate::generate_mockup),
-            ))
+            .service(
+                web::scope("/mockups")
+                    // Multipart uploads first; JSON bodies fall through
+                    .route(
+                        "/generate",
+                        web::post()
+                            .guard(guard::fn_guard(handlers::generate::is_multipart))
+                            .to(handlers::generate::generate_mockup_upload),
+                    )
+                    .route(
+                        "/generate",
+                        web::post().to(handlers::generate::generate_mockup),
+                    )
+                    .route(
+                        "/generate-from-catalog",
+                        web::post().to(handlers::generate::generate_from_catalog),
+                    )
+                    .route(
+                        "/generate-from-product",
+                        web::post().to(handlers::generate::generate_from_product),
+                    )
+                    .route(
+                        "/print-file",
+                        web::post().to(handlers::generate::generate_print_file),
+                    )
+                    .route(
+                        "/generate-batch",
+                        web::post()
+                            .guard(guard::fn_guard(handlers::generate::is_multipart))
+                            .to(handlers::batch::generate_batch_upload),
+                    )
+                    .route(
+                        "/generate-batch",
+                        web::post().to(handlers::batch::generate_batch),
+                    )
+                    .route("/jobs/{id}", web::get().to(handlers::jobs::get_render_job)),
+            )
             .service(
                 web::scope("/templates")
                     // More specific routes first
@@ -32,11 +73,40 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                         "/by-type/{product_type}",
                         web::get().to(handlers::templates::get_by_product_type),
                     )
+                    .route(
+                        "/reload",
+                        web::post().to(handlers::templates::reload_templates),
+                    )
+                    .route(
+                        "/validation",
+                        web::get().to(handlers::templates::template_validation),
+                    )
                     // General routes
                     .route("", web::get().to(handlers::templates::list_templates))
+                    .route("", web::post().to(handlers::templates::upload_template))
                     .route(
                         "/{template_id}",
                         web::get().to(handlers::templates::get_template),
+                    )
+                    .route(
+                        "/{template_id}",
+                        web::delete().to(handlers::templates::delete_template),
+                    )
+                    .route(
+                        "/{template_id}/preview",
+                        web::get().to(handlers::templates::get_template_preview),
+                    )
+                    .route(
+                        "/{template_id}/presets",
+                        web::get().to(handlers::templates::get_template_presets),
+                    )
+                    .route(
+                        "/{template_id}/geometry",
+                        web::patch().to(handlers::templates::update_geometry),
+                    )
+                    .route(
+                        "/{template_id}/reload",
+                        web::post().to(handlers::templates::reload_template),
                     ),
             )
             // API key management endpoints
@@ -46,7 +116,11 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                     .route("", web::get().to(handlers::keys::list_keys))
                     .route("/me", web::get().to(handlers::keys::get_my_key))
                     .route("/{id}", web::get().to(handlers::keys::get_key_by_id))
-                    .route("/{id}", web::delete().to(handlers::keys::revoke_key)),
+                    .route("/{id}", web::patch().to(handlers::keys::update_key))
+                    .route("/{id}", web::delete().to(handlers::keys::revoke_key))
+                    .route("/{id}/delete", web::post().to(handlers::keys::delete_key))
+                    .route("/{id}/rotate", web::post().to(handlers::keys::rotate_key))
+                    .route("/{id}/events", web::get().to(handlers::keys::list_key_events)),
             )
             // Usage statistics endpoints
             .service(
@@ -63,7 +137,16 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                     .route(
                         "/month/{year_month}",
                         web::get().to(handlers::usage::get_month_usage),
-                    ),
+                    )
+                    .route(
+                        "/templates",
+                        web::get().to(handlers::usage::get_template_usage),
+                    )
+                    .route(
+                        "/daily",
+                        web::get().to(handlers::usage::get_daily_usage),
+                    )
+                    .route("/logs", web::get().to(handlers::usage::get_usage_logs)),
             )
             // POD Catalog endpoints
             .service(
@@ -72,10 +155,24 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                         "/providers",
                         web::get().to(handlers::catalog::list_providers),
                     )
+                    // Enterprise provider management
+                    .route(
+                        "/providers",
+                        web::post().to(handlers::providers::create_provider),
+                    )
+                    .route(
+                        "/providers/{code}",
+                        web::patch().to(handlers::providers::update_provider),
+                    )
+                    .route(
+                        "/providers/{code}",
+                        web::delete().to(handlers::providers::delete_provider),
+                    )
                     .route(
                         "/categories",
                         web::get().to(handlers::catalog::list_categories),
                     )
+                    .route("/search", web::get().to(handlers::catalog::search_products))
                     .route("/products", web::get().to(handlers::catalog::list_products))
                     .route(
                         "/products/{id}",
@@ -84,6 +181,71 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                     .route(
                         "/products/{id}/print-areas",
                         web::get().to(handlers::catalog::get_print_areas),
+                    )
+                    // Provider catalogs read live, without a sync
+                    .route(
+                        "/live/{provider}/products",
+                        web::get().to(handlers::catalog::list_live_products),
+                    )
+                    .route(
+                        "/live/{provider}/products/{external_id}",
+                        web::get().to(handlers::catalog::get_live_product),
+                    )
+                    .route(
+                        "/live/{provider}/products/{external_id}/print-areas",
+                        web::get().to(handlers::catalog::get_live_print_areas),
+                    ),
+            )
+            // Design analysis endpoints
+            .service(
+                web::scope("/designs")
+                    .route("/fit-report", web::post().to(handlers::designs::fit_report)),
+            )
+            // Multi-result job downloads
+            .service(web::scope("/jobs").route(
+                "/{id}/download",
+                web::get().to(handlers::jobs::download_job),
+            ))
+            // Status of deferred result uploads
+            .service(
+                web::scope("/renders")
+                    .route("", web::get().to(handlers::renders::list_renders))
+                    .route("/{id}", web::get().to(handlers::renders::get_render)),
+            )
+            // Webhook subscription endpoints
+            .service(
+                web::scope("/webhooks")
+                    .route("", web::post().to(handlers::webhooks::create_webhook))
+                    .route("", web::get().to(handlers::webhooks::list_webhooks))
+                    .route("/{id}", web::patch().to(handlers::webhooks::update_webhook))
+                    .route(
+                        "/{id}",
+                        web::delete().to(handlers::webhooks::delete_webhook),
+                    )
+                    .route(
+                        "/{id}/deliveries",
+                        web::get().to(handlers::webhooks::list_deliveries),
+                    )
+                    .route(
+                        "/{id}/redeliver",
+                        web::post().to(handlers::webhooks::redeliver),
+                    ),
+            )
+            // Admin endpoints
+            .service(
+                web::scope("/admin")
+                    .route(
+                        "/config/reload",
+                        web::post().to(handlers::admin::reload_config),
+                    )
+                    .route(
+                        "/usage/rebuild",
+                        web::post().to(handlers::admin::rebuild_usage),
+                    )
+                    .route("/parity", web::get().to(handlers::admin::parity_report))
+                    .route(
+                        "/parity/runs",
+                        web::post().to(handlers::admin::start_parity_run),
                     ),
             )
             // Sync endpoints
@@ -91,11 +253,36 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                 web::scope("/sync")
                     .route("/jobs", web::get().to(handlers::sync::list_jobs))
                     .route("/jobs/{id}", web::get().to(handlers::sync::get_job))
+                    .route(
+                        "/jobs/{id}/cancel",
+                        web::post().to(handlers::sync::cancel_job),
+                    )
+                    .route("/all", web::post().to(handlers::sync::start_sync_all))
+                    .route("/all", web::get().to(handlers::sync::list_sync_all_jobs))
+                    .route("/all/{id}", web::get().to(handlers::sync::get_sync_all_job))
+                    .route("/schedule", web::get().to(handlers::sync::get_schedule))
                     .route(
                         "/{provider}/start",
                         web::post().to(handlers::sync::start_sync),
                     )
+                    .route(
+                        "/{provider}/products/{external_id}",
+                        web::post().to(handlers::sync::sync_product),
+                    )
+                    .route(
+                        "/{provider}/cleanup",
+                        web::post().to(handlers::sync::cleanup_assets),
+                    )
+                    .route(
+                        "/templates/backup",
+                        web::post().to(handlers::sync::backup_templates),
+                    )
+                    .route(
+                        "/templates/drift",
+                        web::get().to(handlers::sync::template_drift),
+                    )
                     .route("/r2/status", web::get().to(handlers::sync::get_r2_status))
+                    .route("/r2/usage", web::get().to(handlers::sync::get_r2_usage))
                     .route("/r2/test", web::post().to(handlers::sync::test_r2))
                     .route(
                         "/r2/test-upload",
@@ -104,6 +291,13 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
             ),
     )
     .route("/health", web::get().to(handlers::health::health_check))
+    .route("/health/live", web::get().to(handlers::health::liveness))
+    .route("/health/ready", web::get().to(handlers::health::readiness))
+    .route("/metrics", web::get().to(handlers::metrics::metrics))
+    .route(
+        "/api-docs/examples",
+        web::get().to(handlers::examples::request_examples),
+    )
     // Swagger UI and OpenAPI spec
-    .service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", ApiDoc::openapi()));
+    .service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", openapi));
 }
@@ -1,21 +1,86 @@
 //! OpenAPI 3.0 specification definition
 
-use utoipa::OpenApi;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 
 use crate::api::handlers::{
+    admin::StartParityRunRequest,
+    batch::{BatchItem, BatchItemResult, GenerateBatchRequest, GenerateBatchResponse},
+    catalog::{
+        AssetResponse, CatalogSearchResponse, CategoryResponse, PrintAreaResponse,
+        ProductDetailResponse, ProductOrdering, ProductSort, ProductSummaryResponse,
+        ProviderResponse, SortOrder, VariantResponse,
+    },
+    designs::{FitReportRequest, FitReportResponse, ProductFitResponse},
     generate::{
-        ApiError, Dimensions, ErrorResponse, GenerateMetadata, GenerateOptions, GenerateRequest,
-        GenerateResponse,
+        ApiError, CatalogTemplateSource, DesignInput, Dimensions, ErrorResponse,
+        GenerateFromCatalogRequest, GenerateFromCatalogResponse, GenerateFromProductRequest,
+        GenerateMetadata, GenerateOptions, GenerateRequest, GenerateResponse, PrintFileRequest,
+        PrintSize, RenderJobAccepted, ResponseMode,
+    },
+    health::{DependencyCheck, HealthResponse, LivenessResponse, ReadinessResponse},
+    keys::{
+        ApiKeyInfo, CreateKeyRequest, CreateKeyResponse, KeyEventInfo, KeyEventsResponse,
+        RotateKeyRequest, UpdateKeyRequest,
     },
-    health::HealthResponse,
+    providers::{CreateProviderRequest, UpdateProviderRequest},
+    sync::{R2StatusResponse, R2UsageResponse, StartSyncRequest, SyncJobResponse},
     templates::{
+        DisplacementPatch, GeometryPatch, GeometryPreview, GeometryResponse, PresetInfo,
         ProductTypeCount, ProductTypesResponse, TemplateApiError, TemplateErrorResponse,
-        TemplateResponse, TemplatesListResponse,
+        TemplatePresetsResponse, TemplateReloadResponse, TemplateResponse, TemplateUploadResponse,
+        TemplateValidationResponse, TemplatesListResponse,
     },
     tile::{TileMetadata, TileRequest, TileResponse},
+    usage::{
+        BillingMonthInfo, BillingSummaryResponse, DailyUsageResponse, MonthlyUsageResponse,
+        PricingInfo, QuotaInfo, TemplateUsageResponse, UsageHistoryResponse, UsageStatsResponse,
+    },
+    webhooks::{
+        CreateWebhookRequest, CreateWebhookResponse, ListDeliveriesResponse, ListWebhooksResponse,
+        RedeliverRequest, UpdateWebhookRequest, WebhookInfo,
+    },
 };
+use crate::api::middleware::API_KEY_HEADER;
+use crate::api::validation::{FieldError, ValidationError, ValidationErrorResponse};
 use crate::db::models::{DimensionsInfo, PrintAreaInfo, TemplateInfo};
-use crate::domain::{CoordinateSpace, PlacementSpec, PlacementType};
+use crate::db::{
+    CategoryUsage, DailyUsage, MatchedVariant, PoolStatus, ProductSearchHit, QuotaCategory,
+    ResourceKind, ResourceUsage, TemplateUsage, UsageLog, WebhookDelivery,
+};
+use crate::domain::{
+    CoordinateSpace, DesignProfile, FitAssessment, FitViolation, PhysicalPlacement,
+    PlacementPreset, PlacementSpec, PlacementType, PrintConstraints, PrintPlacement, ProductType,
+    UnifiedPrintArea, UnifiedProduct, UnifiedVariant,
+};
+use crate::engine::{
+    AnchorPoint, BackgroundRemoval, BackgroundRemovalMode, ChromaSubsampling, DisplacementConfig,
+    DisplacementMode, JpegPreset, OutputFormat, PrintArea, TemplateGeometry, TemplateLoadReport,
+    TemplateReloadSummary,
+};
+use crate::jobs::{RenderJob, RenderJobError, RenderJobStatus};
+use crate::storage::{ProviderUsage, StorageUsage, UsageReport};
+use crate::sync::{CleanupSummary, SyncJobStatus, SyncJobType, UmbrellaJobSummary};
+use crate::uploads::{RenderUploads, UploadState, UploadStatus, UploadTarget};
+
+/// Name of the security scheme every authenticated operation refers to
+const API_KEY_SCHEME: &str = "api_key";
+
+/// Registers the `X-API-Key` header so "Try it out" sends the key
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            API_KEY_SCHEME,
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                API_KEY_HEADER,
+                "API key, e.g. rim_...; `Authorization: Bearer <key>` is accepted too",
+            ))),
+        );
+    }
+}
 
 #[derive(OpenApi)]
 #[openapi(
@@ -38,36 +103,156 @@ use crate::domain::{CoordinateSpace, PlacementSpec, PlacementType};
     tags(
         (name = "system", description = "System health and status endpoints"),
         (name = "mockups", description = "Mockup generation endpoints"),
-        (name = "templates", description = "Template management endpoints")
+        (name = "templates", description = "Template management endpoints"),
+        (name = "renders", description = "Upload status of generated mockups"),
+        (name = "designs", description = "Design analysis endpoints"),
+        (name = "keys", description = "API key management endpoints"),
+        (name = "usage", description = "Usage, quota, and billing endpoints"),
+        (name = "catalog", description = "Print-on-demand product catalog endpoints"),
+        (name = "sync", description = "Provider catalog sync and R2 storage endpoints"),
+        (name = "webhooks", description = "Webhook subscription endpoints"),
+        (name = "admin", description = "Runtime operations for enterprise keys")
+    ),
+    modifiers(&SecurityAddon),
+    security(
+        ("api_key" = [])
     ),
     paths(
         crate::api::handlers::health::health_check,
+        crate::api::handlers::health::liveness,
+        crate::api::handlers::health::readiness,
         crate::api::handlers::generate::generate_mockup,
+        crate::api::handlers::generate::generate_from_catalog,
+        crate::api::handlers::generate::generate_from_product,
+        crate::api::handlers::generate::generate_print_file,
+        crate::api::handlers::batch::generate_batch,
+        crate::api::handlers::jobs::get_render_job,
+        crate::api::handlers::jobs::download_job,
+        crate::api::handlers::renders::list_renders,
+        crate::api::handlers::renders::get_render,
         crate::api::handlers::templates::list_templates,
         crate::api::handlers::templates::get_template,
+        crate::api::handlers::templates::get_template_preview,
+        crate::api::handlers::templates::get_template_presets,
         crate::api::handlers::templates::list_product_types,
         crate::api::handlers::templates::get_by_product_type,
+        crate::api::handlers::templates::update_geometry,
+        crate::api::handlers::templates::reload_templates,
+        crate::api::handlers::templates::reload_template,
+        crate::api::handlers::templates::template_validation,
+        crate::api::handlers::templates::upload_template,
+        crate::api::handlers::templates::delete_template,
         crate::api::handlers::tile::tile_pattern,
+        crate::api::handlers::designs::fit_report,
+        crate::api::handlers::keys::create_api_key,
+        crate::api::handlers::keys::list_keys,
+        crate::api::handlers::keys::get_my_key,
+        crate::api::handlers::keys::get_key_by_id,
+        crate::api::handlers::keys::update_key,
+        crate::api::handlers::keys::revoke_key,
+        crate::api::handlers::keys::delete_key,
+        crate::api::handlers::keys::rotate_key,
+        crate::api::handlers::keys::list_key_events,
+        crate::api::handlers::usage::get_usage_stats,
+        crate::api::handlers::usage::get_usage_history,
+        crate::api::handlers::usage::get_billing_summary,
+        crate::api::handlers::usage::get_month_usage,
+        crate::api::handlers::usage::get_template_usage,
+        crate::api::handlers::usage::get_daily_usage,
+        crate::api::handlers::usage::get_usage_logs,
+        crate::api::handlers::catalog::list_providers,
+        crate::api::handlers::providers::create_provider,
+        crate::api::handlers::providers::update_provider,
+        crate::api::handlers::providers::delete_provider,
+        crate::api::handlers::catalog::list_categories,
+        crate::api::handlers::catalog::list_products,
+        crate::api::handlers::catalog::search_products,
+        crate::api::handlers::catalog::get_product,
+        crate::api::handlers::catalog::get_print_areas,
+        crate::api::handlers::catalog::list_live_products,
+        crate::api::handlers::catalog::get_live_product,
+        crate::api::handlers::catalog::get_live_print_areas,
+        crate::api::handlers::sync::list_jobs,
+        crate::api::handlers::sync::get_job,
+        crate::api::handlers::sync::cancel_job,
+        crate::api::handlers::sync::start_sync,
+        crate::api::handlers::sync::sync_product,
+        crate::api::handlers::sync::cleanup_assets,
+        crate::api::handlers::sync::start_sync_all,
+        crate::api::handlers::sync::list_sync_all_jobs,
+        crate::api::handlers::sync::get_sync_all_job,
+        crate::api::handlers::sync::get_schedule,
+        crate::api::handlers::sync::backup_templates,
+        crate::api::handlers::sync::template_drift,
+        crate::api::handlers::sync::get_r2_status,
+        crate::api::handlers::sync::get_r2_usage,
+        crate::api::handlers::sync::test_r2,
+        crate::api::handlers::sync::test_r2_upload,
+        crate::api::handlers::webhooks::create_webhook,
+        crate::api::handlers::webhooks::list_webhooks,
+        crate::api::handlers::webhooks::update_webhook,
+        crate::api::handlers::webhooks::delete_webhook,
+        crate::api::handlers::webhooks::list_deliveries,
+        crate::api::handlers::webhooks::redeliver,
+        crate::api::handlers::admin::reload_config,
+        crate::api::handlers::admin::rebuild_usage,
+        crate::api::handlers::admin::start_parity_run,
+        crate::api::handlers::admin::parity_report,
     ),
     components(
         schemas(
             // Health schemas
             HealthResponse,
+            LivenessResponse,
+            ReadinessResponse,
+            PoolStatus,
+            DependencyCheck,
             // Tile schemas
             TileRequest,
             TileResponse,
             TileMetadata,
             // Generate schemas
             GenerateRequest,
+            DesignInput,
             GenerateOptions,
+            OutputFormat,
+            JpegPreset,
+            ChromaSubsampling,
+            BackgroundRemoval,
+            BackgroundRemovalMode,
+            ResponseMode,
             GenerateResponse,
             GenerateMetadata,
+            PrintSize,
+            PrintFileRequest,
+            GenerateFromCatalogRequest,
+            GenerateFromProductRequest,
+            GenerateFromCatalogResponse,
+            CatalogTemplateSource,
+            GenerateBatchRequest,
+            BatchItem,
+            GenerateBatchResponse,
+            BatchItemResult,
+            RenderJobAccepted,
+            RenderJob,
+            RenderJobStatus,
+            RenderJobError,
             Dimensions,
             ErrorResponse,
             ApiError,
+            ValidationErrorResponse,
+            ValidationError,
+            FieldError,
+            // Deferred upload schemas
+            RenderUploads,
+            UploadStatus,
+            UploadTarget,
+            UploadState,
             // Template schemas
             TemplatesListResponse,
             TemplateResponse,
+            TemplatePresetsResponse,
+            PresetInfo,
             ProductTypesResponse,
             ProductTypeCount,
             TemplateErrorResponse,
@@ -75,11 +260,146 @@ use crate::domain::{CoordinateSpace, PlacementSpec, PlacementType};
             TemplateInfo,
             DimensionsInfo,
             PrintAreaInfo,
+            TemplateGeometry,
+            PrintArea,
+            AnchorPoint,
+            DisplacementConfig,
+            DisplacementMode,
+            GeometryPatch,
+            DisplacementPatch,
+            GeometryResponse,
+            GeometryPreview,
+            TemplateReloadResponse,
+            TemplateReloadSummary,
+            TemplateValidationResponse,
+            TemplateLoadReport,
+            TemplateUploadResponse,
+            // Design analysis schemas
+            FitReportRequest,
+            FitReportResponse,
+            ProductFitResponse,
+            DesignProfile,
+            FitAssessment,
+            FitViolation,
+            // API key schemas
+            CreateKeyRequest,
+            CreateKeyResponse,
+            ApiKeyInfo,
+            UpdateKeyRequest,
+            RotateKeyRequest,
+            KeyEventInfo,
+            KeyEventsResponse,
+            // Usage schemas
+            UsageStatsResponse,
+            QuotaInfo,
+            CategoryUsage,
+            QuotaCategory,
+            ResourceUsage,
+            ResourceKind,
+            UsageHistoryResponse,
+            MonthlyUsageResponse,
+            TemplateUsageResponse,
+            TemplateUsage,
+            DailyUsageResponse,
+            DailyUsage,
+            UsageLog,
+            BillingSummaryResponse,
+            BillingMonthInfo,
+            PricingInfo,
+            // Catalog schemas; PaginatedResponse and CatalogPage are generic
+            // and collected from the paths that return them
+            ProviderResponse,
+            CreateProviderRequest,
+            UpdateProviderRequest,
+            CategoryResponse,
+            ProductSummaryResponse,
+            CatalogSearchResponse,
+            ProductSearchHit,
+            MatchedVariant,
+            ProductDetailResponse,
+            ProductOrdering,
+            ProductSort,
+            SortOrder,
+            VariantResponse,
+            PrintAreaResponse,
+            AssetResponse,
+            UnifiedProduct,
+            UnifiedVariant,
+            UnifiedPrintArea,
+            PrintConstraints,
+            ProductType,
+            PrintPlacement,
+            // Sync schemas
+            StartSyncRequest,
+            SyncJobResponse,
+            SyncJobType,
+            SyncJobStatus,
+            CleanupSummary,
+            UmbrellaJobSummary,
+            R2StatusResponse,
+            R2UsageResponse,
+            UsageReport,
+            ProviderUsage,
+            StorageUsage,
+            // Webhook schemas
+            CreateWebhookRequest,
+            CreateWebhookResponse,
+            UpdateWebhookRequest,
+            WebhookInfo,
+            ListWebhooksResponse,
+            ListDeliveriesResponse,
+            RedeliverRequest,
+            WebhookDelivery,
+            // Admin schemas
+            StartParityRunRequest,
             // Domain schemas
             PlacementSpec,
+            PhysicalPlacement,
+            PlacementPreset,
             PlacementType,
             CoordinateSpace,
         )
     )
 )]
 pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_covers_api_surface() {
+        let json = ApiDoc::openapi().to_json().unwrap();
+        let spec: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let paths = spec["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), 70);
+        for path in [
+            "/api/v1/mockups/generate",
+            "/api/v1/keys/{id}",
+            "/api/v1/usage",
+            "/api/v1/catalog/products",
+            "/api/v1/sync/{provider}/start",
+            "/api/v1/sync/r2/usage",
+            "/api/v1/sync/{provider}/cleanup",
+        ] {
+            assert!(paths.contains_key(path), "{} is not documented", path);
+        }
+
+        let schemas = &spec["components"]["schemas"];
+        assert!(schemas["PaginatedResponse_ProductSummaryResponse"].is_object());
+        assert!(schemas["PaginatedResponse_ApiKeyInfo"].is_object());
+        assert!(schemas["PaginatedResponse_UsageLog"].is_object());
+        assert!(schemas["CreateKeyRequest"].is_object());
+
+        let scheme = &spec["components"]["securitySchemes"][API_KEY_SCHEME];
+        assert_eq!(scheme["in"], "header");
+        assert_eq!(scheme["name"], API_KEY_HEADER);
+        assert_eq!(spec["security"][0][API_KEY_SCHEME], serde_json::json!([]));
+        // Probes stay callable without a key
+        assert_eq!(
+            spec["paths"]["/health/ready"]["get"]["security"],
+            serde_json::json!([{}])
+        );
+    }
+}
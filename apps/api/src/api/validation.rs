@@ -0,0 +1,286 @@
+//! Field-level validation of request bodies
+//!
+//! Checks collect every invalid field instead of stopping at the first, so a
+//! client can fix a request in one round trip. Invalid requests are answered
+//! with a 422 listing each field with a machine-readable code and the value
+//! that was sent.
+
+use actix_web::HttpResponse;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::api::middleware::RequestId;
+use crate::domain::{PhysicalPlacement, PlacementError, PlacementSpec};
+
+/// Error code of a 422 answered for invalid fields
+pub const VALIDATION_FAILED: &str = "VALIDATION_FAILED";
+
+/// One invalid field of a request body
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Path of the field, e.g. `designs[1].placement.scale`
+    pub field: String,
+    /// `required`, `conflict`, `too_many`, `invalid_url`, `invalid_value`,
+    /// `out_of_range`, or `out_of_bounds`
+    pub code: String,
+    pub message: String,
+    /// The value that was sent, omitted for missing fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+/// 422 response listing every invalid field
+#[derive(Serialize, ToSchema)]
+pub struct ValidationErrorResponse {
+    pub success: bool,
+    /// ID of the failed request, also sent as `X-Request-Id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub error: ValidationError,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ValidationError {
+    /// Always `VALIDATION_FAILED`
+    pub code: String,
+    pub message: String,
+    pub fields: Vec<FieldError>,
+}
+
+/// Invalid fields found while checking a request
+#[derive(Debug, Default)]
+pub struct FieldErrors {
+    pub fields: Vec<FieldError>,
+}
+
+impl FieldErrors {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Record an invalid field
+    pub fn add(
+        &mut self,
+        field: impl Into<String>,
+        code: &str,
+        message: impl Into<String>,
+        value: Option<Value>,
+    ) {
+        self.fields.push(FieldError {
+            field: field.into(),
+            code: code.to_string(),
+            message: message.into(),
+            value,
+        });
+    }
+
+    /// Record a missing field
+    pub fn required(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.add(field, "required", message, None);
+    }
+
+    /// Check that `url` is an absolute http(s) URL with a host
+    pub fn check_http_url(&mut self, field: impl Into<String>, url: &str) {
+        let valid = url::Url::parse(url)
+            .map(|u| matches!(u.scheme(), "http" | "https") && u.host_str().is_some())
+            .unwrap_or(false);
+        if !valid {
+            self.add(
+                field,
+                "invalid_url",
+                "Must be an absolute http or https URL",
+                Some(Value::from(url)),
+            );
+        }
+    }
+
+    /// Check scale, rotation, and, when the print area is known, the design's bounds
+    ///
+    /// `prefix` is the path of the placement's parent, e.g. `designs[1].`.
+    pub fn check_placement(
+        &mut self,
+        prefix: &str,
+        placement: &PlacementSpec,
+        print_area: Option<(i32, i32)>,
+    ) {
+        let sent = placement;
+        let placement = match print_area {
+            Some((width, height)) => placement.in_print_area(width, height),
+            None => placement.clone(),
+        };
+        for e in placement.errors() {
+            let (field, code, value) = match &e {
+                PlacementError::InvalidScale(scale) => {
+                    ("scale", "out_of_range", Value::from(*scale))
+                }
+                PlacementError::InvalidRotation(degrees) => {
+                    ("rotation_degrees", "out_of_range", Value::from(*degrees))
+                }
+                // Bounds are only meaningful against the template's print area
+                PlacementError::OutOfBoundsHorizontal(..) if print_area.is_some() => {
+                    ("offset_x", "out_of_bounds", Value::from(sent.offset_x))
+                }
+                PlacementError::OutOfBoundsVertical(..) if print_area.is_some() => {
+                    ("offset_y", "out_of_bounds", Value::from(sent.offset_y))
+                }
+                PlacementError::OutOfBoundsHorizontal(..)
+                | PlacementError::OutOfBoundsVertical(..) => continue,
+                // Only reported when converting a physical placement
+                PlacementError::InvalidPrintSize(..) | PlacementError::ExceedsPrintArea(..) => {
+                    continue
+                }
+            };
+            self.add(
+                format!("{}placement.{}", prefix, field),
+                code,
+                e.to_string(),
+                Some(value),
+            );
+        }
+    }
+
+    /// Check a physical placement converts to a valid placement in the print area
+    ///
+    /// Skipped without a print area, since inches only map to pixels in one.
+    pub fn check_physical(
+        &mut self,
+        prefix: &str,
+        physical: &PhysicalPlacement,
+        dpi: Option<u32>,
+        print_area: Option<(i32, i32)>,
+    ) {
+        let Some((width, height)) = print_area else {
+            return;
+        };
+        let field = |name: &str| format!("{}physical_placement.{}", prefix, name);
+        let placement = match PlacementSpec::from_physical(physical, dpi, width, height) {
+            Ok(placement) => placement,
+            Err(e) => {
+                let code = match e {
+                    PlacementError::ExceedsPrintArea(..) => "out_of_bounds",
+                    _ => "out_of_range",
+                };
+                let value = Value::from(physical.width_inches);
+                self.add(field("width_inches"), code, e.to_string(), Some(value));
+                return;
+            }
+        };
+        for e in placement.errors() {
+            let (name, code, value) = match &e {
+                // Under a tenth of the print area's width
+                PlacementError::InvalidScale(_) => {
+                    ("width_inches", "out_of_range", physical.width_inches)
+                }
+                PlacementError::InvalidRotation(degrees) => {
+                    ("rotation_degrees", "out_of_range", *degrees)
+                }
+                PlacementError::OutOfBoundsHorizontal(..) => {
+                    ("offset_x_inches", "out_of_bounds", physical.offset_x_inches)
+                }
+                PlacementError::OutOfBoundsVertical(..) => {
+                    ("offset_y_inches", "out_of_bounds", physical.offset_y_inches)
+                }
+                PlacementError::InvalidPrintSize(..) | PlacementError::ExceedsPrintArea(..) => {
+                    continue
+                }
+            };
+            self.add(field(name), code, e.to_string(), Some(Value::from(value)));
+        }
+    }
+
+    /// `Ok` when no field is invalid, otherwise the 422 listing them
+    pub fn into_result(self) -> Result<(), HttpResponse> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let message = match self.fields.len() {
+            1 => format!("{}: {}", self.fields[0].field, self.fields[0].message),
+            n => format!("{} fields are invalid", n),
+        };
+        Err(
+            HttpResponse::UnprocessableEntity().json(ValidationErrorResponse {
+                success: false,
+                request_id: RequestId::current(),
+                error: ValidationError {
+                    code: VALIDATION_FAILED.to_string(),
+                    message,
+                    fields: self.fields,
+                },
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::PlacementType;
+
+    #[test]
+    fn test_placement_bounds_need_a_print_area() {
+        let off_center = PlacementSpec::new(0.5, 900, -1200, PlacementType::Front);
+
+        let mut errors = FieldErrors::default();
+        errors.check_placement("", &off_center, None);
+        assert!(errors.is_empty());
+
+        errors.check_placement("designs[1].", &off_center, Some((1800, 2400)));
+        let fields: Vec<_> = errors.fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "designs[1].placement.offset_x",
+                "designs[1].placement.offset_y"
+            ]
+        );
+        assert_eq!(errors.fields[0].code, "out_of_bounds");
+        assert_eq!(errors.fields[0].value, Some(Value::from(900)));
+    }
+
+    #[test]
+    fn test_http_url_check() {
+        let mut errors = FieldErrors::default();
+        errors.check_http_url("design_url", "https://example.com/a.png");
+        assert!(errors.is_empty());
+
+        for url in [
+            "ftp://example.com/a.png",
+            "example.com/a.png",
+            "file:///etc/passwd",
+            "",
+        ] {
+            errors.check_http_url("design_url", url);
+        }
+        assert_eq!(errors.fields.len(), 4);
+        assert!(errors.fields.iter().all(|f| f.code == "invalid_url"));
+    }
+
+    #[test]
+    fn test_physical_placement_errors_name_physical_fields() {
+        let mut physical = PhysicalPlacement {
+            width_inches: 7.0,
+            offset_x_inches: 0.0,
+            offset_y_inches: 0.0,
+            dpi: None,
+            placement: PlacementType::Front,
+            rotation_degrees: 0.0,
+        };
+        let mut errors = FieldErrors::default();
+        errors.check_physical("", &physical, None, None);
+        assert!(errors.is_empty());
+
+        errors.check_physical("", &physical, None, Some((1800, 2400)));
+        assert_eq!(errors.fields[0].field, "physical_placement.width_inches");
+        assert_eq!(errors.fields[0].code, "out_of_bounds");
+        assert!(errors.fields[0].message.contains("6.00 in"));
+
+        physical.width_inches = 4.0;
+        physical.offset_y_inches = -4.0;
+        let mut errors = FieldErrors::default();
+        errors.check_physical("", &physical, None, Some((1800, 2400)));
+        assert_eq!(errors.fields.len(), 1);
+        assert_eq!(errors.fields[0].field, "physical_placement.offset_y_inches");
+        assert_eq!(errors.fields[0].value, Some(Value::from(-4.0)));
+    }
+}
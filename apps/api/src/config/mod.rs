@@ -2,8 +2,19 @@
 
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 
+use crate::db::PoolOptions;
+use crate::engine::{DesignLimits, JpegPreset};
+use crate::net::UrlPolicy;
+
+mod validation;
+
+pub use crate::net::{service_name, service_user_agent};
+pub use validation::{check_env_overrides, ConfigIssue, ConfigReport, IssueSeverity};
+
 /// Main application settings
 #[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
@@ -13,6 +24,20 @@ pub struct Settings {
     pub database: DatabaseSettings,
     #[serde(default)]
     pub r2: Option<R2Settings>,
+    #[serde(default)]
+    pub storage: StorageSettings,
+    #[serde(default)]
+    pub sync: SyncSettings,
+    #[serde(default)]
+    pub access_log: AccessLogSettings,
+    #[serde(default)]
+    pub output: OutputDefaults,
+    #[serde(default)]
+    pub billing: BillingSettings,
+    #[serde(default)]
+    pub metrics: MetricsSettings,
+    #[serde(default)]
+    pub render_cache: RenderCacheSettings,
 }
 
 /// HTTP server configuration
@@ -20,13 +45,317 @@ pub struct Settings {
 pub struct ServerSettings {
     pub host: String,
     pub port: u16,
+    /// Worker threads (defaults to twice the CPU count)
     pub workers: Option<usize>,
+    /// Seconds health checks report draining before the listeners close
+    #[serde(default = "default_drain_delay_secs")]
+    pub drain_delay_secs: u64,
+    /// Seconds in-flight requests, then background tasks, get to finish on shutdown
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+    /// Milliseconds each readiness dependency check may take before it counts as failed
+    #[serde(default = "default_ready_check_timeout_ms")]
+    pub ready_check_timeout_ms: u64,
+    /// Fail readiness while R2 is unreachable; leave off when no R2 features are used
+    #[serde(default)]
+    pub ready_requires_r2: bool,
+    /// Largest design image accepted as a multipart upload, in bytes
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_upload_bytes: usize,
+    /// Templates rendered at once by a batch request (defaults to the CPU count)
+    #[serde(default)]
+    pub batch_concurrency: Option<usize>,
+    /// Mockups generated at once across all requests (defaults to the CPU count)
+    #[serde(default)]
+    pub max_concurrent_generations: Option<usize>,
+    /// Seconds a generation waits for a free slot before the request gets a 503
+    #[serde(default = "default_generation_wait_secs")]
+    pub generation_wait_secs: u64,
+    /// Seconds an async generation's result stays available after it finishes
+    #[serde(default = "default_render_job_retention_secs")]
+    pub render_job_retention_secs: u64,
+    /// Longest a design fetch may take; requests can only ask for less
+    #[serde(default = "default_max_fetch_timeout_secs")]
+    pub max_fetch_timeout_secs: u64,
+    /// Seconds a generation may run, fetches included, before it fails with GENERATION_TIMEOUT
+    #[serde(default = "default_generation_timeout_secs")]
+    pub generation_timeout_secs: u64,
+    /// Largest design image downloaded from a URL, in bytes
+    #[serde(default = "default_max_design_bytes")]
+    pub max_design_bytes: u64,
+    /// Smallest width and height accepted for a design downloaded from a URL
+    #[serde(default = "default_min_design_dimension")]
+    pub min_design_dimension: u32,
+    /// Largest width and height accepted for a design downloaded from a URL
+    #[serde(default = "default_max_design_dimension")]
+    pub max_design_dimension: u32,
+    /// Hosts design URLs and mirror sources are limited to, subdomains included;
+    /// empty allows any public host
+    #[serde(default)]
+    pub allowed_fetch_hosts: Vec<String>,
+    /// Redirects followed when fetching a design URL or mirror source
+    #[serde(default = "default_max_fetch_redirects")]
+    pub max_fetch_redirects: usize,
+    /// More `ip:port` addresses to listen on, e.g. `[::]:8080` for IPv6
+    #[serde(default)]
+    pub extra_addresses: Vec<String>,
+    /// Unix domain socket to listen on as well, for a local reverse proxy
+    #[serde(default)]
+    pub unix_socket: Option<PathBuf>,
+    /// Serve HTTPS instead of HTTP on the TCP addresses
+    #[serde(default)]
+    pub tls: Option<TlsSettings>,
+}
+
+fn default_drain_delay_secs() -> u64 {
+    5
+}
+
+fn default_ready_check_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_upload_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_generation_wait_secs() -> u64 {
+    10
+}
+
+fn default_render_job_retention_secs() -> u64 {
+    60 * 60
+}
+
+fn default_max_fetch_timeout_secs() -> u64 {
+    30
+}
+
+fn default_generation_timeout_secs() -> u64 {
+    120
+}
+
+fn default_max_design_bytes() -> u64 {
+    DesignLimits::default().max_bytes
+}
+
+fn default_min_design_dimension() -> u32 {
+    DesignLimits::default().min_dimension
+}
+
+fn default_max_design_dimension() -> u32 {
+    DesignLimits::default().max_dimension
+}
+
+fn default_max_fetch_redirects() -> usize {
+    UrlPolicy::default().max_redirects
+}
+
+impl ServerSettings {
+    /// Worker threads to start
+    pub fn worker_count(&self) -> usize {
+        self.workers.unwrap_or_else(|| num_cpus::get() * 2).max(1)
+    }
+
+    /// How long the service drains before it stops accepting connections
+    pub fn drain_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.drain_delay_secs)
+    }
+
+    /// How long in-flight requests and background tasks get on shutdown
+    pub fn shutdown_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.shutdown_timeout_secs)
+    }
+
+    /// How long each readiness dependency check may take
+    pub fn ready_check_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.ready_check_timeout_ms)
+    }
+
+    /// Generations allowed to run at once
+    pub fn generation_limit(&self) -> usize {
+        self.max_concurrent_generations
+            .unwrap_or_else(num_cpus::get)
+            .max(1)
+    }
+
+    /// How long a generation waits for a slot
+    pub fn generation_wait(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.generation_wait_secs)
+    }
+
+    /// How long finished async generations stay available
+    pub fn render_job_retention(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.render_job_retention_secs)
+    }
+
+    /// Upper bound for a single design fetch
+    pub fn max_fetch_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.max_fetch_timeout_secs)
+    }
+
+    /// Overall time budget for one generation
+    pub fn generation_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.generation_timeout_secs)
+    }
+
+    /// Where design URLs and mirror sources may be fetched from
+    pub fn url_policy(&self) -> UrlPolicy {
+        UrlPolicy {
+            allowed_hosts: self
+                .allowed_fetch_hosts
+                .iter()
+                .map(|host| host.trim().to_string())
+                .collect(),
+            max_redirects: self.max_fetch_redirects,
+        }
+    }
+
+    /// Size and dimension limits for designs downloaded from a URL
+    pub fn design_limits(&self) -> DesignLimits {
+        DesignLimits {
+            max_bytes: self.max_design_bytes,
+            min_dimension: self.min_design_dimension,
+            max_dimension: self.max_design_dimension,
+        }
+    }
+
+    /// TCP addresses to bind: `host:port` first, then `extra_addresses`.
+    /// An empty host with a unix socket configured listens on the socket only.
+    pub fn tcp_addresses(&self) -> Vec<String> {
+        let primary = match self.host.trim() {
+            "" if self.unix_socket.is_some() => None,
+            host => Some(match host.parse::<IpAddr>() {
+                // Brackets IPv6 literals
+                Ok(ip) => SocketAddr::new(ip, self.port).to_string(),
+                Err(_) => format!("{}:{}", host, self.port),
+            }),
+        };
+        primary
+            .into_iter()
+            .chain(
+                self.extra_addresses
+                    .iter()
+                    .map(|addr| addr.trim().to_string()),
+            )
+            .collect()
+    }
+}
+
+/// Certificate and key for terminating TLS in the service itself
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsSettings {
+    /// PEM certificate chain, leaf first
+    pub cert_path: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path: PathBuf,
+}
+
+impl TlsSettings {
+    /// Read the certificate and key into a rustls server config
+    pub fn server_config(&self) -> std::io::Result<rustls::ServerConfig> {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+        let certs = CertificateDer::pem_file_iter(&self.cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| {
+                std::io::Error::other(format!(
+                    "TLS certificate '{}': {}",
+                    self.cert_path.display(),
+                    e
+                ))
+            })?;
+        let key = PrivateKeyDer::from_pem_file(&self.key_path).map_err(|e| {
+            std::io::Error::other(format!(
+                "TLS private key '{}': {}",
+                self.key_path.display(),
+                e
+            ))
+        })?;
+
+        rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| std::io::Error::other(format!("TLS configuration: {}", e)))
+    }
 }
 
 /// Template configuration
 #[derive(Debug, Clone, Deserialize)]
 pub struct TemplateSettings {
     pub path: PathBuf,
+    /// Where templates come from; with `r2`, `path` caches the shared library
+    #[serde(default)]
+    pub source: TemplateSourceKind,
+    /// Evict decoded images after this many idle seconds (0 disables eviction)
+    #[serde(default = "default_idle_eviction_secs")]
+    pub idle_eviction_secs: u64,
+    /// How often the eviction sweep runs, in seconds
+    #[serde(default = "default_eviction_interval_secs")]
+    pub eviction_interval_secs: u64,
+    /// Most templates kept decoded at once (0 for no limit)
+    #[serde(default)]
+    pub max_resident_templates: usize,
+    /// Most bytes of decoded template images kept at once (0 for no limit)
+    #[serde(default = "default_max_resident_bytes")]
+    pub max_resident_bytes: u64,
+    /// Longest side of template preview thumbnails, in pixels
+    #[serde(default = "default_preview_max_dimension")]
+    pub preview_max_dimension: u32,
+}
+
+/// Where the template library lives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateSourceKind {
+    /// The templates directory alone
+    #[default]
+    Filesystem,
+    /// R2's `templates/` prefix, pulled into the templates directory on
+    /// startup and reload; uploads are published to it
+    R2,
+}
+
+fn default_idle_eviction_secs() -> u64 {
+    900
+}
+
+fn default_eviction_interval_secs() -> u64 {
+    60
+}
+
+fn default_max_resident_bytes() -> u64 {
+    4 * 1024 * 1024 * 1024
+}
+
+fn default_preview_max_dimension() -> u32 {
+    512
+}
+
+impl TemplateSettings {
+    /// Idle timeout for decoded template images, `None` when eviction is disabled
+    pub fn idle_timeout(&self) -> Option<std::time::Duration> {
+        if self.idle_eviction_secs == 0 {
+            None
+        } else {
+            Some(std::time::Duration::from_secs(self.idle_eviction_secs))
+        }
+    }
+
+    /// Cap on decoded templates, `None` when unlimited
+    pub fn resident_template_limit(&self) -> Option<usize> {
+        (self.max_resident_templates > 0).then_some(self.max_resident_templates)
+    }
+
+    /// Cap on bytes of decoded template images, `None` when unlimited
+    pub fn resident_bytes_limit(&self) -> Option<u64> {
+        (self.max_resident_bytes > 0).then_some(self.max_resident_bytes)
+    }
 }
 
 /// Cloudinary configuration for uploading generated mockups
@@ -43,6 +372,41 @@ pub struct CloudinarySettings {
 pub struct DatabaseSettings {
     pub url: String,
     pub max_connections: Option<u32>,
+    /// Connections opened at startup and kept open while idle
+    #[serde(default)]
+    pub min_idle: u32,
+    /// Milliseconds opening a connection may take
+    #[serde(default = "default_db_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+    /// Milliseconds a request waits for a free connection before getting a 503
+    #[serde(default = "default_db_acquire_timeout_ms")]
+    pub acquire_timeout_ms: u64,
+    /// Milliseconds a statement may run before the server cancels it; `0` keeps the server's default
+    #[serde(default)]
+    pub statement_timeout_ms: u64,
+}
+
+fn default_db_connect_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_db_acquire_timeout_ms() -> u64 {
+    5000
+}
+
+impl DatabaseSettings {
+    /// Pool sizing and timeouts for `DbPool::with_options`
+    pub fn pool_options(&self) -> PoolOptions {
+        let max_connections = self.max_connections.unwrap_or(10).max(1) as usize;
+        PoolOptions {
+            max_connections,
+            min_idle: (self.min_idle as usize).min(max_connections),
+            connect_timeout: std::time::Duration::from_millis(self.connect_timeout_ms),
+            acquire_timeout: std::time::Duration::from_millis(self.acquire_timeout_ms),
+            statement_timeout: (self.statement_timeout_ms > 0)
+                .then(|| std::time::Duration::from_millis(self.statement_timeout_ms)),
+        }
+    }
 }
 
 /// Cloudflare R2 configuration for POD asset storage
@@ -60,6 +424,289 @@ pub struct R2Settings {
     pub public_url_prefix: Option<String>,
 }
 
+/// Where provider assets and generated mockups are stored
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StorageSettings {
+    /// Storage backend; `r2` uses the `[r2]` section, `s3` the `[storage.s3]` one
+    pub backend: StorageBackendKind,
+    /// Root directory of the `local` backend
+    pub local_path: PathBuf,
+    /// URL prefix the `local` backend's directory is served under, if any
+    pub local_public_url_prefix: Option<String>,
+    /// AWS S3 bucket for the `s3` backend
+    pub s3: Option<S3Settings>,
+}
+
+impl Default for StorageSettings {
+    fn default() -> Self {
+        StorageSettings {
+            backend: StorageBackendKind::default(),
+            local_path: PathBuf::from("./data/storage"),
+            local_public_url_prefix: None,
+            s3: None,
+        }
+    }
+}
+
+/// Object store or directory holding stored assets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackendKind {
+    /// Cloudflare R2
+    #[default]
+    R2,
+    /// AWS S3, or another S3-compatible service at `endpoint_url`
+    S3,
+    /// A directory on local disk
+    Local,
+}
+
+impl StorageBackendKind {
+    /// The `storage.backend` value, also the name of its readiness check
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageBackendKind::R2 => "r2",
+            StorageBackendKind::S3 => "s3",
+            StorageBackendKind::Local => "local",
+        }
+    }
+}
+
+/// AWS S3 configuration for the `s3` storage backend
+#[derive(Debug, Clone, Deserialize)]
+pub struct S3Settings {
+    /// AWS region of the bucket
+    pub region: String,
+    /// Bucket name for POD assets
+    pub bucket_name: String,
+    /// Access key ID; without both keys, the AWS environment's credentials are used
+    pub access_key_id: Option<String>,
+    /// Secret access key
+    pub secret_access_key: Option<String>,
+    /// Endpoint of an S3-compatible service other than AWS (optional)
+    pub endpoint_url: Option<String>,
+    /// Public URL prefix for assets (optional, for CDN)
+    pub public_url_prefix: Option<String>,
+}
+
+/// POD catalog sync scheduling limits
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SyncSettings {
+    /// Providers synced at the same time by POST /sync/all
+    pub max_concurrent_providers: usize,
+    /// Asset downloads in flight across all running provider syncs
+    pub max_concurrent_assets: usize,
+    /// Download attempts per asset before it is recorded as failed
+    pub max_asset_attempts: u32,
+    /// Store assets with identical bytes once, under a content-addressed key
+    pub dedup_assets: bool,
+    /// Store a WebP thumbnail of each mirrored image larger than `thumbnail_max_dimension`
+    pub thumbnails: bool,
+    /// Longest side of asset thumbnails in pixels
+    pub thumbnail_max_dimension: u32,
+    /// Run incremental syncs of providers whose `sync_interval_hours` has passed
+    pub scheduler_enabled: bool,
+    /// Seconds between checks for providers due a scheduled sync
+    pub scheduler_tick_secs: u64,
+    /// Seconds an R2 storage usage report is served before it is walked again
+    pub usage_report_ttl_secs: u64,
+}
+
+impl SyncSettings {
+    /// How often the sync scheduler looks for due providers
+    pub fn scheduler_tick(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.scheduler_tick_secs)
+    }
+
+    /// How long an R2 storage usage report is kept
+    pub fn usage_report_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.usage_report_ttl_secs)
+    }
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        SyncSettings {
+            max_concurrent_providers: 2,
+            max_concurrent_assets: 10,
+            max_asset_attempts: 3,
+            dedup_assets: false,
+            thumbnails: true,
+            thumbnail_max_dimension: 400,
+            scheduler_enabled: true,
+            scheduler_tick_secs: 300,
+            usage_report_ttl_secs: 3600,
+        }
+    }
+}
+
+/// Cache of encoded mockups for repeated identical generate requests
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RenderCacheSettings {
+    /// Serve repeated renders from the cache; requests can still opt out
+    pub enabled: bool,
+    /// Most bytes of encoded mockups kept in memory
+    pub max_bytes: u64,
+    /// Also keep cached renders in R2 under `generated/cache/`, when R2 is configured
+    pub r2: bool,
+}
+
+impl Default for RenderCacheSettings {
+    fn default() -> Self {
+        RenderCacheSettings {
+            enabled: true,
+            max_bytes: 256 * 1024 * 1024,
+            r2: false,
+        }
+    }
+}
+
+/// Access log filtering
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccessLogSettings {
+    /// Path prefixes logged only at `excluded_sample_rate` (health probes, scrapes)
+    pub exclude_paths: Vec<String>,
+    /// Fraction of other requests logged, 0.0-1.0
+    pub sample_rate: f64,
+    /// Fraction of excluded-path requests still logged, 0.0-1.0
+    pub excluded_sample_rate: f64,
+}
+
+impl Default for AccessLogSettings {
+    fn default() -> Self {
+        AccessLogSettings {
+            exclude_paths: vec!["/health".to_string(), "/metrics".to_string()],
+            sample_rate: 1.0,
+            excluded_sample_rate: 0.0,
+        }
+    }
+}
+
+/// Access to the `/metrics` endpoint
+///
+/// With neither a token nor an allowlist, anyone who can reach the service can
+/// scrape it. With both, a request passes if it meets either.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MetricsSettings {
+    /// Token scrapers send as `Authorization: Bearer <token>`
+    pub bearer_token: Option<String>,
+    /// Client IPs allowed to scrape without a token
+    pub allowed_ips: Vec<String>,
+}
+
+impl MetricsSettings {
+    /// Whether a scrape from `client` with `bearer` as its token is allowed
+    pub fn allows(&self, client: Option<IpAddr>, bearer: Option<&str>) -> bool {
+        let token = self.bearer_token.as_deref().filter(|t| !t.is_empty());
+        if token.is_none() && self.allowed_ips.is_empty() {
+            return true;
+        }
+
+        let token_ok = match (token, bearer) {
+            (Some(expected), Some(given)) => {
+                subtle::ConstantTimeEq::ct_eq(expected.as_bytes(), given.as_bytes()).into()
+            }
+            _ => false,
+        };
+        let ip_ok = client.is_some_and(|ip| {
+            self.allowed_ips
+                .iter()
+                .any(|allowed| allowed.trim().parse::<IpAddr>() == Ok(ip))
+        });
+        token_ok || ip_ok
+    }
+}
+
+/// Encoding defaults for requests that don't choose their own
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OutputDefaults {
+    /// JPEG preset used when a request sets no `jpeg_preset`
+    pub jpeg_preset: JpegPreset,
+}
+
+/// Billing units charged per endpoint
+///
+/// Usage and quotas are counted in units rather than requests, so a render
+/// costs more than a catalog read. Weights not set here use
+/// `DEFAULT_BILLING_WEIGHTS`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BillingSettings {
+    /// Units per request by billing endpoint; render endpoints charge per mockup
+    pub weights: HashMap<String, i32>,
+    /// Percentages of a category's quota that trigger a webhook alert
+    pub alert_thresholds: Vec<i32>,
+}
+
+impl Default for BillingSettings {
+    fn default() -> Self {
+        Self {
+            weights: HashMap::new(),
+            alert_thresholds: vec![80, 95, 100],
+        }
+    }
+}
+
+/// Units per request of each billing endpoint when not configured
+pub const DEFAULT_BILLING_WEIGHTS: [(&str, i32); 6] = [
+    ("generate", 5),
+    ("generate_batch", 5),
+    ("tile", 5),
+    ("catalog", 1),
+    ("templates", 1),
+    ("default", 1),
+];
+
+impl BillingSettings {
+    /// Units one request (or one mockup, for renders) of an endpoint costs
+    pub fn weight(&self, endpoint: &str) -> i32 {
+        let configured = |endpoint: &str| {
+            self.weights.get(endpoint).copied().or_else(|| {
+                DEFAULT_BILLING_WEIGHTS
+                    .iter()
+                    .find(|(name, _)| *name == endpoint)
+                    .map(|(_, units)| *units)
+            })
+        };
+        configured(endpoint)
+            .or_else(|| configured("default"))
+            .unwrap_or(1)
+    }
+
+    /// Units a request to `path` costs
+    ///
+    /// Render endpoints charge their weight per mockup the handler reported
+    /// producing, or once when it reported nothing (a failed render or the
+    /// tile endpoint).
+    pub fn units(&self, path: &str, render_count: Option<i32>) -> i32 {
+        let endpoint = billing_endpoint(path);
+        self.weight(endpoint) * render_count.unwrap_or(1)
+    }
+}
+
+/// Billing endpoint a request path is weighted as
+pub fn billing_endpoint(path: &str) -> &'static str {
+    let rest = path.strip_prefix("/api/v1/").unwrap_or("");
+    let mut segments = rest.split('/');
+    match (segments.next(), segments.next()) {
+        (Some("mockups"), Some("generate-batch")) => "generate_batch",
+        (
+            Some("mockups"),
+            Some("generate" | "generate-from-catalog" | "generate-from-product" | "print-file"),
+        ) => "generate",
+        (Some("tile"), _) => "tile",
+        (Some("catalog"), _) => "catalog",
+        (Some("templates"), _) => "templates",
+        _ => "default",
+    }
+}
+
 impl Settings {
     /// Load configuration from files and environment variables
     ///
@@ -82,7 +729,13 @@ impl Settings {
             .add_source(
                 Environment::with_prefix("MOCKUP")
                     .separator("__")
-                    .try_parsing(true),
+                    .try_parsing(true)
+                    .list_separator(",")
+                    .with_list_parse_key("access_log.exclude_paths")
+                    .with_list_parse_key("server.extra_addresses")
+                    .with_list_parse_key("server.allowed_fetch_hosts")
+                    .with_list_parse_key("billing.alert_thresholds")
+                    .with_list_parse_key("metrics.allowed_ips"),
             );
 
         let mut settings: Settings = builder.build()?.try_deserialize()?;
@@ -109,6 +762,18 @@ impl Settings {
 
         Ok(settings)
     }
+
+    /// Whether the storage backend is an R2 or S3 bucket with settings to reach it
+    ///
+    /// The R2 template library, the render cache's R2 tier, and usage
+    /// reports need a bucket; the `local` backend stores assets only.
+    pub fn object_store_configured(&self) -> bool {
+        match self.storage.backend {
+            StorageBackendKind::R2 => self.r2.is_some(),
+            StorageBackendKind::S3 => self.storage.s3.is_some(),
+            StorageBackendKind::Local => false,
+        }
+    }
 }
 
 impl Default for Settings {
@@ -118,9 +783,34 @@ impl Default for Settings {
                 host: "0.0.0.0".to_string(),
                 port: 8080,
                 workers: None,
+                drain_delay_secs: default_drain_delay_secs(),
+                shutdown_timeout_secs: default_shutdown_timeout_secs(),
+                ready_check_timeout_ms: default_ready_check_timeout_ms(),
+                ready_requires_r2: false,
+                max_upload_bytes: default_max_upload_bytes(),
+                batch_concurrency: None,
+                max_concurrent_generations: None,
+                generation_wait_secs: default_generation_wait_secs(),
+                render_job_retention_secs: default_render_job_retention_secs(),
+                max_fetch_timeout_secs: default_max_fetch_timeout_secs(),
+                generation_timeout_secs: default_generation_timeout_secs(),
+                max_design_bytes: default_max_design_bytes(),
+                min_design_dimension: default_min_design_dimension(),
+                max_design_dimension: default_max_design_dimension(),
+                allowed_fetch_hosts: Vec::new(),
+                max_fetch_redirects: default_max_fetch_redirects(),
+                extra_addresses: Vec::new(),
+                unix_socket: None,
+                tls: None,
             },
             templates: TemplateSettings {
                 path: PathBuf::from("assets/templates"),
+                source: TemplateSourceKind::default(),
+                idle_eviction_secs: default_idle_eviction_secs(),
+                eviction_interval_secs: default_eviction_interval_secs(),
+                max_resident_templates: 0,
+                max_resident_bytes: default_max_resident_bytes(),
+                preview_max_dimension: default_preview_max_dimension(),
             },
             cloudinary: CloudinarySettings {
                 cloud_name: String::new(),
@@ -131,8 +821,19 @@ impl Default for Settings {
             database: DatabaseSettings {
                 url: String::new(),
                 max_connections: Some(10),
+                min_idle: 0,
+                connect_timeout_ms: default_db_connect_timeout_ms(),
+                acquire_timeout_ms: default_db_acquire_timeout_ms(),
+                statement_timeout_ms: 0,
             },
             r2: None,
+            storage: StorageSettings::default(),
+            sync: SyncSettings::default(),
+            access_log: AccessLogSettings::default(),
+            output: OutputDefaults::default(),
+            billing: BillingSettings::default(),
+            metrics: MetricsSettings::default(),
+            render_cache: RenderCacheSettings::default(),
         }
     }
 }
@@ -180,22 +881,67 @@ fn resolve_r2_settings(existing: Option<R2Settings>) -> Option<R2Settings> {
     }
 }
 
-pub fn service_name() -> String {
-    first_env(&["MOCKUP_SERVICE__NAME", "SERVICE_NAME"])
-        .unwrap_or_else(|| "r-image-magic".to_string())
-}
-
 pub fn pricing_url() -> String {
     first_env(&["MOCKUP_SERVICE__PRICING_URL", "PRICING_URL"])
         .unwrap_or_else(|| "https://r-image-magic.com/pricing".to_string())
 }
 
-pub fn service_user_agent() -> String {
-    first_env(&["MOCKUP_SERVICE__USER_AGENT", "SERVICE_USER_AGENT"])
-        .unwrap_or_else(|| format!("{}/{}", service_name(), env!("CARGO_PKG_VERSION")))
-}
-
 pub fn default_r2_bucket_name() -> String {
     first_env(&["MOCKUP_SERVICE__R2_BUCKET_DEFAULT", "R2_BUCKET_DEFAULT"])
         .unwrap_or_else(|| "r-image-magic-pod-assets".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_billing_units_for_mixed_traffic() {
+        let billing = BillingSettings::default();
+        let traffic = [
+            ("/api/v1/mockups/generate", Some(1)),
+            ("/api/v1/mockups/generate-from-catalog", Some(2)),
+            ("/api/v1/mockups/generate-from-product", Some(1)),
+            ("/api/v1/mockups/print-file", Some(1)),
+            ("/api/v1/mockups/generate-batch", Some(4)),
+            ("/api/v1/tile", None),
+            ("/api/v1/catalog/products/42", None),
+            ("/api/v1/templates/tee", None),
+            ("/api/v1/usage", None),
+        ];
+        let units: i32 = traffic
+            .iter()
+            .map(|(path, renders)| billing.units(path, *renders))
+            .sum();
+        // 5 + 10 + 5 + 5 + 20 + 5 + 1 + 1 + 1
+        assert_eq!(units, 53);
+    }
+
+    #[test]
+    fn test_configured_billing_weights_override_defaults() {
+        let billing = BillingSettings {
+            weights: HashMap::from([("generate".to_string(), 2), ("default".to_string(), 0)]),
+            ..Default::default()
+        };
+        assert_eq!(billing.units("/api/v1/mockups/generate", Some(3)), 6);
+        assert_eq!(billing.units("/api/v1/tile", None), 5);
+        assert_eq!(billing.units("/api/v1/usage", None), 0);
+        assert_eq!(billing_endpoint("/health"), "default");
+    }
+
+    #[test]
+    fn test_metrics_access() {
+        let local: IpAddr = "10.0.0.5".parse().unwrap();
+        let other: IpAddr = "203.0.113.9".parse().unwrap();
+        assert!(MetricsSettings::default().allows(Some(other), None));
+
+        let settings = MetricsSettings {
+            bearer_token: Some("scrape-secret".to_string()),
+            allowed_ips: vec!["10.0.0.5".to_string()],
+        };
+        assert!(settings.allows(Some(local), None));
+        assert!(settings.allows(Some(other), Some("scrape-secret")));
+        assert!(!settings.allows(Some(other), Some("wrong")));
+        assert!(!settings.allows(None, None));
+    }
+}
@@ -0,0 +1,788 @@
+//! Startup validation for loaded settings
+//!
+//! Turns misconfiguration into a list of actionable issues, each naming the
+//! environment variable that fixes it, instead of panicking deep inside startup
+//! or silently running in a degraded mode.
+
+use serde::Serialize;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use tracing::{error, warn};
+
+use super::{Settings, StorageBackendKind, TemplateSourceKind, DEFAULT_BILLING_WEIGHTS};
+use crate::providers::PROVIDER_CODES;
+
+/// How serious a configuration issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    /// The service cannot run correctly; startup is refused
+    Error,
+    /// The service can run, but a feature is disabled or degraded
+    Warning,
+}
+
+/// A single configuration problem and the env var that fixes it
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigIssue {
+    pub severity: IssueSeverity,
+    /// Environment variable(s) to set or correct
+    pub env_var: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (fix: {})", self.message, self.env_var)
+    }
+}
+
+/// Result of validating settings
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ConfigReport {
+    fn error(&mut self, env_var: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ConfigIssue {
+            severity: IssueSeverity::Error,
+            env_var: env_var.into(),
+            message: message.into(),
+        });
+    }
+
+    fn warning(&mut self, env_var: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ConfigIssue {
+            severity: IssueSeverity::Warning,
+            env_var: env_var.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Hard errors that should block startup
+    pub fn errors(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == IssueSeverity::Error)
+    }
+
+    /// Non-fatal issues
+    pub fn warnings(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == IssueSeverity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Log every issue at the matching level
+    pub fn log(&self) {
+        for issue in &self.issues {
+            match issue.severity {
+                IssueSeverity::Error => {
+                    error!(env_var = %issue.env_var, "Config error: {}", issue.message)
+                }
+                IssueSeverity::Warning => {
+                    warn!(env_var = %issue.env_var, "Config warning: {}", issue.message)
+                }
+            }
+        }
+    }
+}
+
+/// Check that numeric env overrides parse before the config crate sees them.
+///
+/// The config crate reports a bad `MOCKUP_SERVER__PORT` as an opaque
+/// deserialization error, so this runs both on a failed load and during
+/// normal validation.
+pub fn check_env_overrides(lookup: &dyn Fn(&str) -> Option<String>) -> ConfigReport {
+    let mut report = ConfigReport::default();
+    check_parse::<u16>(
+        &mut report,
+        lookup,
+        "MOCKUP_SERVER__PORT",
+        "a port between 1 and 65535",
+    );
+    check_parse::<usize>(
+        &mut report,
+        lookup,
+        "MOCKUP_SERVER__WORKERS",
+        "a positive integer",
+    );
+    check_parse::<u32>(
+        &mut report,
+        lookup,
+        "MOCKUP_DATABASE__MAX_CONNECTIONS",
+        "a positive integer",
+    );
+    report
+}
+
+fn check_parse<T: FromStr>(
+    report: &mut ConfigReport,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    var: &str,
+    expected: &str,
+) {
+    if let Some(value) = lookup(var) {
+        if value.trim().parse::<T>().is_err() {
+            report.error(var, format!("{}='{}' is not {}", var, value, expected));
+        }
+    }
+}
+
+impl Settings {
+    /// Validate loaded settings against the process environment
+    pub fn validate(&self) -> ConfigReport {
+        self.validate_with(&|key| std::env::var(key).ok())
+    }
+
+    /// Validate loaded settings using a custom env lookup
+    pub fn validate_with(&self, lookup: &dyn Fn(&str) -> Option<String>) -> ConfigReport {
+        let mut report = check_env_overrides(lookup);
+
+        // Server
+        if self.server.host.trim().is_empty() && self.server.unix_socket.is_none() {
+            report.error("MOCKUP_SERVER__HOST", "server host is empty");
+        }
+        for addr in &self.server.extra_addresses {
+            if addr.trim().parse::<SocketAddr>().is_err() {
+                report.error(
+                    "MOCKUP_SERVER__EXTRA_ADDRESSES",
+                    format!(
+                        "'{}' is not an ip:port address (write IPv6 as [::1]:8080)",
+                        addr
+                    ),
+                );
+            }
+        }
+        if let Some(ref socket) = self.server.unix_socket {
+            if cfg!(not(unix)) {
+                report.error(
+                    "MOCKUP_SERVER__UNIX_SOCKET",
+                    "unix sockets are not supported on this platform",
+                );
+            }
+            let parent = socket.parent().filter(|dir| !dir.as_os_str().is_empty());
+            if parent.is_some_and(|dir| !dir.is_dir()) {
+                report.error(
+                    "MOCKUP_SERVER__UNIX_SOCKET",
+                    format!(
+                        "directory for unix socket '{}' does not exist",
+                        socket.display()
+                    ),
+                );
+            }
+        }
+        if let Some(ref tls) = self.server.tls {
+            for (var, path) in [
+                ("MOCKUP_SERVER__TLS__CERT_PATH", &tls.cert_path),
+                ("MOCKUP_SERVER__TLS__KEY_PATH", &tls.key_path),
+            ] {
+                if !path.is_file() {
+                    report.error(var, format!("TLS file '{}' does not exist", path.display()));
+                }
+            }
+            if self.server.tcp_addresses().is_empty() {
+                report.warning(
+                    "MOCKUP_SERVER__HOST",
+                    "TLS is configured but the service only listens on a unix socket, which is served without TLS",
+                );
+            }
+        }
+        if self.server.port == 0 {
+            report.error(
+                "MOCKUP_SERVER__PORT",
+                "server port must be between 1 and 65535",
+            );
+        }
+        if self.server.workers == Some(0) {
+            report.error("MOCKUP_SERVER__WORKERS", "worker count must be at least 1");
+        }
+        if self.server.shutdown_timeout_secs == 0 {
+            report.warning(
+                "MOCKUP_SERVER__SHUTDOWN_TIMEOUT_SECS",
+                "in-flight requests and background tasks are dropped on shutdown",
+            );
+        }
+        if self.server.ready_check_timeout_ms == 0 {
+            report.error(
+                "MOCKUP_SERVER__READY_CHECK_TIMEOUT_MS",
+                "readiness check timeout must be at least 1ms",
+            );
+        }
+        if self.server.ready_requires_r2
+            && self.storage.backend == StorageBackendKind::R2
+            && self.r2.is_none()
+        {
+            report.warning(
+                "MOCKUP_SERVER__READY_REQUIRES_R2",
+                "R2 is not configured, so readiness does not check it",
+            );
+        }
+        if self.server.max_upload_bytes == 0 {
+            report.error(
+                "MOCKUP_SERVER__MAX_UPLOAD_BYTES",
+                "design upload limit must be at least 1 byte",
+            );
+        }
+        if self.server.batch_concurrency == Some(0) {
+            report.error(
+                "MOCKUP_SERVER__BATCH_CONCURRENCY",
+                "batch concurrency must be at least 1",
+            );
+        }
+        if self.server.max_concurrent_generations == Some(0) {
+            report.error(
+                "MOCKUP_SERVER__MAX_CONCURRENT_GENERATIONS",
+                "generation concurrency must be at least 1",
+            );
+        }
+        if self.server.render_job_retention_secs == 0 {
+            report.error(
+                "MOCKUP_SERVER__RENDER_JOB_RETENTION_SECS",
+                "async generation results must be kept for at least 1 second",
+            );
+        }
+        if self.server.max_fetch_timeout_secs == 0 {
+            report.error(
+                "MOCKUP_SERVER__MAX_FETCH_TIMEOUT_SECS",
+                "design fetch timeout must be at least 1 second",
+            );
+        }
+        if self.server.generation_timeout_secs == 0 {
+            report.error(
+                "MOCKUP_SERVER__GENERATION_TIMEOUT_SECS",
+                "generation timeout must be at least 1 second",
+            );
+        }
+        if self.server.max_design_bytes == 0 {
+            report.error(
+                "MOCKUP_SERVER__MAX_DESIGN_BYTES",
+                "design download limit must be at least 1 byte",
+            );
+        }
+        if self.server.min_design_dimension > self.server.max_design_dimension {
+            report.error(
+                "MOCKUP_SERVER__MIN_DESIGN_DIMENSION",
+                format!(
+                    "smallest design dimension {} is larger than the largest, {}",
+                    self.server.min_design_dimension, self.server.max_design_dimension
+                ),
+            );
+        }
+        for host in &self.server.allowed_fetch_hosts {
+            let host = host.trim();
+            if host.is_empty() || host.contains(['/', ':', '@']) {
+                report.error(
+                    "MOCKUP_SERVER__ALLOWED_FETCH_HOSTS",
+                    format!("'{}' is not a bare host name like cdn.example.com", host),
+                );
+            }
+        }
+
+        // Sync
+        if self.sync.max_concurrent_providers == 0 {
+            report.error(
+                "MOCKUP_SYNC__MAX_CONCURRENT_PROVIDERS",
+                "sync provider concurrency must be at least 1",
+            );
+        }
+        if self.sync.max_concurrent_assets == 0 {
+            report.error(
+                "MOCKUP_SYNC__MAX_CONCURRENT_ASSETS",
+                "sync asset concurrency must be at least 1",
+            );
+        }
+        if self.sync.max_asset_attempts == 0 {
+            report.error(
+                "MOCKUP_SYNC__MAX_ASSET_ATTEMPTS",
+                "sync asset attempts must be at least 1",
+            );
+        }
+        if self.sync.thumbnail_max_dimension == 0 {
+            report.error(
+                "MOCKUP_SYNC__THUMBNAIL_MAX_DIMENSION",
+                "sync thumbnail size must be at least 1 pixel",
+            );
+        }
+        if self.sync.scheduler_tick_secs == 0 {
+            report.error(
+                "MOCKUP_SYNC__SCHEDULER_TICK_SECS",
+                "sync scheduler tick must be at least 1 second",
+            );
+        }
+
+        // Access log
+        for (var, rate) in [
+            (
+                "MOCKUP_ACCESS_LOG__SAMPLE_RATE",
+                self.access_log.sample_rate,
+            ),
+            (
+                "MOCKUP_ACCESS_LOG__EXCLUDED_SAMPLE_RATE",
+                self.access_log.excluded_sample_rate,
+            ),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                report.error(
+                    var,
+                    format!("sample rate {} must be between 0.0 and 1.0", rate),
+                );
+            }
+        }
+
+        // Billing
+        for (endpoint, &units) in &self.billing.weights {
+            let var = format!("MOCKUP_BILLING__WEIGHTS__{}", endpoint.to_uppercase());
+            if units < 0 {
+                report.error(
+                    &var,
+                    format!(
+                        "billing weight {} for '{}' must not be negative",
+                        units, endpoint
+                    ),
+                );
+            }
+            if !DEFAULT_BILLING_WEIGHTS
+                .iter()
+                .any(|(name, _)| name == endpoint)
+            {
+                report.warning(
+                    &var,
+                    format!("'{}' is not a billing endpoint and is ignored", endpoint),
+                );
+            }
+        }
+
+        if let Some(threshold) = self
+            .billing
+            .alert_thresholds
+            .iter()
+            .find(|t| !(1..=100).contains(*t))
+        {
+            report.error(
+                "MOCKUP_BILLING__ALERT_THRESHOLDS",
+                format!(
+                    "quota alert threshold {}% must be between 1 and 100",
+                    threshold
+                ),
+            );
+        }
+
+        // Metrics
+        for ip in &self.metrics.allowed_ips {
+            if ip.trim().parse::<IpAddr>().is_err() {
+                report.error(
+                    "MOCKUP_METRICS__ALLOWED_IPS",
+                    format!("'{}' is not an IP address", ip),
+                );
+            }
+        }
+
+        // Templates
+        if self.templates.eviction_interval_secs == 0 {
+            report.error(
+                "MOCKUP_TEMPLATES__EVICTION_INTERVAL_SECS",
+                "template eviction interval must be at least 1 second",
+            );
+        }
+        if self.templates.preview_max_dimension == 0 {
+            report.error(
+                "MOCKUP_TEMPLATES__PREVIEW_MAX_DIMENSION",
+                "template preview size must be at least 1 pixel",
+            );
+        }
+        if self.templates.source == TemplateSourceKind::R2 && !self.object_store_configured() {
+            report.error(
+                "MOCKUP_TEMPLATES__SOURCE",
+                "templates are sourced from R2, but no R2 or S3 bucket is configured",
+            );
+        }
+        // The R2 source creates the directory on its first pull
+        let from_disk = self.templates.source == TemplateSourceKind::Filesystem;
+        if from_disk && !self.templates.path.is_dir() {
+            report.warning(
+                "MOCKUP_TEMPLATES__PATH",
+                format!(
+                    "templates directory '{}' does not exist; no templates will be served",
+                    self.templates.path.display()
+                ),
+            );
+        }
+
+        if self.render_cache.enabled && self.render_cache.r2 && !self.object_store_configured() {
+            report.warning(
+                "MOCKUP_RENDER_CACHE__R2",
+                "no R2 or S3 bucket is configured, so cached renders are only kept in memory",
+            );
+        }
+
+        // Database
+        if self.database.url.is_empty() {
+            report.warning(
+                "DATABASE_URL",
+                "no database configured; API key auth, usage tracking and catalog endpoints are disabled",
+            );
+        } else {
+            match url::Url::parse(&self.database.url) {
+                Ok(url) if matches!(url.scheme(), "postgres" | "postgresql") => {
+                    if url.host_str().is_none() {
+                        report.error("DATABASE_URL", "database URL has no host");
+                    }
+                }
+                Ok(url) => report.error(
+                    "DATABASE_URL",
+                    format!(
+                        "database URL scheme '{}' is not supported; use postgres://",
+                        url.scheme()
+                    ),
+                ),
+                Err(e) => report.error("DATABASE_URL", format!("database URL is invalid: {}", e)),
+            }
+        }
+        if self.database.max_connections == Some(0) {
+            report.error(
+                "MOCKUP_DATABASE__MAX_CONNECTIONS",
+                "max_connections must be at least 1",
+            );
+        }
+        if self.database.min_idle > self.database.max_connections.unwrap_or(10) {
+            report.warning(
+                "MOCKUP_DATABASE__MIN_IDLE",
+                "min_idle is more than max_connections; only max_connections are opened",
+            );
+        }
+        if self.database.acquire_timeout_ms == 0 {
+            report.error(
+                "MOCKUP_DATABASE__ACQUIRE_TIMEOUT_MS",
+                "acquire_timeout_ms must be at least 1",
+            );
+        }
+
+        // R2: either all credentials or none
+        let r2_vars = [
+            ("R2_ACCOUNT_ID", "MOCKUP_R2__ACCOUNT_ID"),
+            ("R2_ACCESS_KEY_ID", "MOCKUP_R2__ACCESS_KEY_ID"),
+            ("R2_SECRET_ACCESS_KEY", "MOCKUP_R2__SECRET_ACCESS_KEY"),
+        ];
+        let present: Vec<bool> = r2_vars
+            .iter()
+            .map(|(a, b)| lookup(a).or_else(|| lookup(b)).is_some())
+            .collect();
+        let any_r2 = present.iter().any(|p| *p);
+        if any_r2 && self.r2.is_none() {
+            let missing: Vec<&str> = r2_vars
+                .iter()
+                .zip(&present)
+                .filter(|(_, p)| !**p)
+                .map(|((name, _), _)| *name)
+                .collect();
+            report.error(
+                missing.join(", "),
+                "R2 is partially configured; asset storage stays disabled until all credentials are set",
+            );
+        }
+        if let Some(ref r2) = self.r2 {
+            if r2.account_id.is_empty() {
+                report.error("R2_ACCOUNT_ID", "R2 account id is empty");
+            }
+            if r2.access_key_id.is_empty() {
+                report.error("R2_ACCESS_KEY_ID", "R2 access key id is empty");
+            }
+            if r2.secret_access_key.is_empty() {
+                report.error("R2_SECRET_ACCESS_KEY", "R2 secret access key is empty");
+            }
+            if r2.bucket_name.is_empty() {
+                report.error("R2_BUCKET_NAME", "R2 bucket name is empty");
+            }
+        }
+
+        // Storage backends other than R2
+        match self.storage.backend {
+            StorageBackendKind::S3 => match self.storage.s3 {
+                Some(ref s3) => {
+                    if s3.bucket_name.is_empty() {
+                        report.error("MOCKUP_STORAGE__S3__BUCKET_NAME", "S3 bucket name is empty");
+                    }
+                    if s3.region.is_empty() {
+                        report.error("MOCKUP_STORAGE__S3__REGION", "S3 region is empty");
+                    }
+                    if s3.access_key_id.is_some() != s3.secret_access_key.is_some() {
+                        report.error(
+                            "MOCKUP_STORAGE__S3__ACCESS_KEY_ID, MOCKUP_STORAGE__S3__SECRET_ACCESS_KEY",
+                            "set both S3 keys, or neither to use the AWS environment's credentials",
+                        );
+                    }
+                }
+                None => report.error(
+                    "MOCKUP_STORAGE__S3__BUCKET_NAME, MOCKUP_STORAGE__S3__REGION",
+                    "the s3 storage backend is selected, but no S3 bucket is configured",
+                ),
+            },
+            StorageBackendKind::Local if self.storage.local_path.as_os_str().is_empty() => {
+                report.error(
+                    "MOCKUP_STORAGE__LOCAL_PATH",
+                    "the local storage backend needs a directory",
+                );
+            }
+            _ => {}
+        }
+
+        // Providers configured for sync but nowhere to mirror assets
+        if self.storage.backend == StorageBackendKind::R2 && self.r2.is_none() {
+            let configured: Vec<&str> = PROVIDER_CODES
+                .iter()
+                .copied()
+                .filter(|code| {
+                    let prefix = code.to_uppercase();
+                    ["ACCESS_TOKEN", "API_KEY", "RECIPE_ID"]
+                        .iter()
+                        .any(|suffix| lookup(&format!("{}_{}", prefix, suffix)).is_some())
+                })
+                .collect();
+            if !configured.is_empty() {
+                report.warning(
+                    "R2_ACCOUNT_ID, R2_ACCESS_KEY_ID, R2_SECRET_ACCESS_KEY",
+                    format!(
+                        "provider credentials are set for {} but R2 is not configured; synced assets will not be mirrored",
+                        configured.join(", ")
+                    ),
+                );
+            }
+        }
+
+        // Cloudinary: a cloud name without credentials cannot upload
+        let cloudinary = &self.cloudinary;
+        if !cloudinary.cloud_name.is_empty()
+            && (cloudinary.api_key.is_empty() || cloudinary.api_secret.is_empty())
+        {
+            report.warning(
+                "MOCKUP_CLOUDINARY__API_KEY, MOCKUP_CLOUDINARY__API_SECRET",
+                "Cloudinary cloud name is set without API credentials; uploads are disabled",
+            );
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{R2Settings, S3Settings};
+    use std::collections::HashMap;
+
+    fn lookup_from(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| map.get(key).cloned()
+    }
+
+    #[test]
+    fn test_bad_port_env_is_reported() {
+        let lookup = lookup_from(&[("MOCKUP_SERVER__PORT", "80a")]);
+        let report = check_env_overrides(&lookup);
+        let errors: Vec<_> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].env_var, "MOCKUP_SERVER__PORT");
+    }
+
+    #[test]
+    fn test_partial_r2_names_missing_vars() {
+        let settings = Settings::default();
+        let lookup = lookup_from(&[("R2_ACCOUNT_ID", "abc")]);
+        let report = settings.validate_with(&lookup);
+        let r2_error = report
+            .errors()
+            .find(|i| i.env_var.contains("R2_ACCESS_KEY_ID"))
+            .expect("partial R2 config should be an error");
+        assert!(r2_error.env_var.contains("R2_SECRET_ACCESS_KEY"));
+        assert!(!r2_error.env_var.contains("R2_ACCOUNT_ID"));
+    }
+
+    #[test]
+    fn test_provider_without_r2_warns() {
+        let settings = Settings::default();
+        let lookup = lookup_from(&[("PRINTFUL_ACCESS_TOKEN", "token")]);
+        let report = settings.validate_with(&lookup);
+        assert!(!report.has_errors());
+        assert!(report.warnings().any(|w| w.message.contains("printful")));
+    }
+
+    #[test]
+    fn test_storage_backend_settings() {
+        let mut settings = Settings::default();
+        settings.storage.backend = StorageBackendKind::S3;
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report
+            .errors()
+            .any(|i| i.env_var.contains("MOCKUP_STORAGE__S3__BUCKET_NAME")));
+
+        settings.storage.s3 = Some(S3Settings {
+            region: "eu-west-1".to_string(),
+            bucket_name: "assets".to_string(),
+            access_key_id: Some("key".to_string()),
+            secret_access_key: None,
+            endpoint_url: None,
+            public_url_prefix: None,
+        });
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report
+            .errors()
+            .any(|i| i.env_var.contains("MOCKUP_STORAGE__S3__SECRET_ACCESS_KEY")));
+        assert!(settings.object_store_configured());
+
+        // Local storage mirrors assets without R2 credentials
+        settings.storage.backend = StorageBackendKind::Local;
+        let lookup = lookup_from(&[("PRINTFUL_ACCESS_TOKEN", "token")]);
+        let report = settings.validate_with(&lookup);
+        assert!(!report.has_errors());
+        assert!(!report.warnings().any(|w| w.message.contains("printful")));
+        assert!(!settings.object_store_configured());
+    }
+
+    #[test]
+    fn test_invalid_database_url_is_error() {
+        let mut settings = Settings::default();
+        settings.database.url = "mysql://localhost/db".to_string();
+        settings.r2 = Some(R2Settings {
+            account_id: "a".to_string(),
+            access_key_id: "b".to_string(),
+            secret_access_key: "c".to_string(),
+            bucket_name: "bucket".to_string(),
+            public_url_prefix: None,
+        });
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report.errors().any(|i| i.env_var == "DATABASE_URL"));
+    }
+
+    #[test]
+    fn test_database_pool_settings() {
+        let mut settings = Settings::default();
+        settings.database.min_idle = 20;
+        settings.database.acquire_timeout_ms = 0;
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report
+            .warnings()
+            .any(|w| w.env_var == "MOCKUP_DATABASE__MIN_IDLE"));
+        assert!(report
+            .errors()
+            .any(|e| e.env_var == "MOCKUP_DATABASE__ACQUIRE_TIMEOUT_MS"));
+
+        settings.database.statement_timeout_ms = 30_000;
+        let options = settings.database.pool_options();
+        assert_eq!(options.max_connections, 10);
+        // The warned-about min_idle is held to the pool's size
+        assert_eq!(options.min_idle, 10);
+        assert_eq!(
+            options.statement_timeout,
+            Some(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(
+            Settings::default()
+                .database
+                .pool_options()
+                .statement_timeout,
+            None
+        );
+    }
+
+    #[test]
+    fn test_template_eviction_settings() {
+        let mut settings = Settings::default();
+        assert!(settings.templates.idle_timeout().is_some());
+
+        settings.templates.idle_eviction_secs = 0;
+        assert!(settings.templates.idle_timeout().is_none());
+
+        settings.templates.eviction_interval_secs = 0;
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report
+            .errors()
+            .any(|i| i.env_var == "MOCKUP_TEMPLATES__EVICTION_INTERVAL_SECS"));
+
+        settings.templates.source = TemplateSourceKind::R2;
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report
+            .errors()
+            .any(|i| i.env_var == "MOCKUP_TEMPLATES__SOURCE"));
+    }
+
+    #[test]
+    fn test_allowed_fetch_hosts_must_be_bare_hosts() {
+        let mut settings = Settings::default();
+        settings.server.allowed_fetch_hosts = vec!["cdn.example.com".to_string()];
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(!report
+            .errors()
+            .any(|i| i.env_var == "MOCKUP_SERVER__ALLOWED_FETCH_HOSTS"));
+
+        settings.server.allowed_fetch_hosts = vec!["https://cdn.example.com/".to_string()];
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report
+            .errors()
+            .any(|i| i.env_var == "MOCKUP_SERVER__ALLOWED_FETCH_HOSTS"));
+    }
+
+    #[test]
+    fn test_bind_addresses() {
+        let mut settings = Settings::default();
+        settings.server.host = "::".to_string();
+        settings.server.extra_addresses = vec!["127.0.0.1:9090".to_string()];
+        assert_eq!(
+            settings.server.tcp_addresses(),
+            vec!["[::]:8080".to_string(), "127.0.0.1:9090".to_string()]
+        );
+
+        // Socket only
+        settings.server.host = String::new();
+        settings.server.extra_addresses.clear();
+        settings.server.unix_socket = Some(std::env::temp_dir().join("r-image-magic.sock"));
+        assert!(settings.server.tcp_addresses().is_empty());
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(!report
+            .errors()
+            .any(|i| i.env_var.starts_with("MOCKUP_SERVER")));
+
+        settings.server.extra_addresses = vec!["::1:8080".to_string()];
+        settings.server.tls = Some(crate::config::TlsSettings {
+            cert_path: "/nonexistent/cert.pem".into(),
+            key_path: "/nonexistent/key.pem".into(),
+        });
+        let report = settings.validate_with(&lookup_from(&[]));
+        for var in [
+            "MOCKUP_SERVER__EXTRA_ADDRESSES",
+            "MOCKUP_SERVER__TLS__CERT_PATH",
+            "MOCKUP_SERVER__TLS__KEY_PATH",
+        ] {
+            assert!(report.errors().any(|i| i.env_var == var), "{var}");
+        }
+    }
+
+    #[test]
+    fn test_billing_weights_are_checked() {
+        let mut settings = Settings::default();
+        settings.billing.weights.insert("generate".to_string(), -1);
+        settings.billing.weights.insert("thumbnails".to_string(), 2);
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report
+            .errors()
+            .any(|i| i.env_var == "MOCKUP_BILLING__WEIGHTS__GENERATE"));
+        assert!(report
+            .warnings()
+            .any(|i| i.env_var == "MOCKUP_BILLING__WEIGHTS__THUMBNAILS"));
+
+        settings.billing.alert_thresholds = vec![80, 120];
+        let report = settings.validate_with(&lookup_from(&[]));
+        assert!(report
+            .errors()
+            .any(|i| i.env_var == "MOCKUP_BILLING__ALERT_THRESHOLDS"));
+    }
+}
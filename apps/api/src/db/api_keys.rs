@@ -1,12 +1,24 @@
 //! API key database operations
 
+use super::page::{Page, PageRequest};
 use super::pool::{DbError, DbPool};
+use super::resources::ResourceKind;
+use super::usage::QuotaCategory;
 use chrono::{DateTime, Utc};
 use rand::Rng;
 use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
 use tracing::{info, warn};
 use uuid::Uuid;
 
+/// Columns selected for a `DbApiKey`
+const API_KEY_COLUMNS: &str = "id, key_prefix, key_hash, name, owner_email, owner_name, \
+    company, tier, rate_limit_per_minute, monthly_quota, is_active, created_at, updated_at, \
+    last_used_at, expires_at, webhook_url, \
+    allowed_design_domains::TEXT AS allowed_design_domains, deleted_at";
+
 /// API key tier with associated limits
 #[derive(Debug, Clone, PartialEq)]
 pub enum ApiKeyTier {
@@ -52,6 +64,38 @@ impl ApiKeyTier {
             ApiKeyTier::Enterprise => 1000000,
         }
     }
+
+    /// Default monthly budget for a quota category
+    ///
+    /// Renders draw on the key's `monthly_quota`, which starts at
+    /// `default_monthly_quota`; the cheaper categories get larger budgets.
+    pub fn default_category_quota(&self, category: QuotaCategory) -> i32 {
+        match (category, self) {
+            (QuotaCategory::Render, _) => self.default_monthly_quota(),
+            (QuotaCategory::Catalog | QuotaCategory::Other, ApiKeyTier::Free) => 1000,
+            (QuotaCategory::Catalog | QuotaCategory::Other, ApiKeyTier::Starter) => 10000,
+            (QuotaCategory::Catalog | QuotaCategory::Other, ApiKeyTier::Pro) => 100000,
+            (QuotaCategory::Catalog | QuotaCategory::Other, ApiKeyTier::Enterprise) => 10000000,
+            (QuotaCategory::Sync, ApiKeyTier::Free) => 10,
+            (QuotaCategory::Sync, ApiKeyTier::Starter) => 100,
+            (QuotaCategory::Sync, ApiKeyTier::Pro) => 1000,
+            (QuotaCategory::Sync, ApiKeyTier::Enterprise) => 100000,
+        }
+    }
+
+    /// Most resources of a kind a key on this tier may keep stored
+    pub fn resource_limit(&self, kind: ResourceKind) -> i64 {
+        match (kind, self) {
+            (ResourceKind::SavedRenders, ApiKeyTier::Free) => 100,
+            (ResourceKind::SavedRenders, ApiKeyTier::Starter) => 1000,
+            (ResourceKind::SavedRenders, ApiKeyTier::Pro) => 10000,
+            (ResourceKind::SavedRenders, ApiKeyTier::Enterprise) => 1000000,
+            (ResourceKind::Webhooks, ApiKeyTier::Free) => 1,
+            (ResourceKind::Webhooks, ApiKeyTier::Starter) => 5,
+            (ResourceKind::Webhooks, ApiKeyTier::Pro) => 25,
+            (ResourceKind::Webhooks, ApiKeyTier::Enterprise) => 100,
+        }
+    }
 }
 
 /// Database model for API key
@@ -72,12 +116,18 @@ pub struct DbApiKey {
     pub updated_at: DateTime<Utc>,
     pub last_used_at: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
+    /// Where quota threshold alerts are sent
+    pub webhook_url: Option<String>,
+    /// Domains design URLs must be hosted on; None or empty allows any host
+    pub allowed_design_domains: Option<Vec<String>>,
+    /// When the key was deleted; deleted keys are kept for their usage history
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl DbApiKey {
-    /// Check if the API key is valid (active and not expired)
+    /// Check if the API key is valid (active, not deleted and not expired)
     pub fn is_valid(&self) -> bool {
-        if !self.is_active {
+        if !self.is_active || self.deleted_at.is_some() {
             return false;
         }
         if let Some(expires) = self.expires_at {
@@ -104,6 +154,129 @@ pub struct CreateApiKeyRequest {
     pub rate_limit_per_minute: Option<i32>,
     pub monthly_quota: Option<i32>,
     pub expires_at: Option<DateTime<Utc>>,
+    pub allowed_design_domains: Option<Vec<String>>,
+}
+
+/// Owned query parameter
+type SqlValue = Box<dyn ToSql + Sync + Send>;
+
+fn sql_value<T: ToSql + Sync + Send + 'static>(value: T) -> SqlValue {
+    Box::new(value)
+}
+
+/// Changes to an API key; fields left as None are kept
+#[derive(Debug, Default)]
+pub struct UpdateApiKeyRequest {
+    pub name: Option<String>,
+    /// A new tier also resets limits not given here to the tier's defaults
+    pub tier: Option<ApiKeyTier>,
+    pub rate_limit_per_minute: Option<i32>,
+    pub monthly_quota: Option<i32>,
+    /// `Some(None)` removes the expiry
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    pub is_active: Option<bool>,
+    /// `Some(None)` stops quota alerts
+    pub webhook_url: Option<Option<String>>,
+    /// `Some(None)` lifts the restriction
+    pub allowed_design_domains: Option<Option<Vec<String>>>,
+}
+
+impl UpdateApiKeyRequest {
+    /// Whether the request changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.tier.is_none()
+            && self.rate_limit_per_minute.is_none()
+            && self.monthly_quota.is_none()
+            && self.expires_at.is_none()
+            && self.is_active.is_none()
+            && self.webhook_url.is_none()
+            && self.allowed_design_domains.is_none()
+    }
+
+    /// SET clause for the changed columns, with values bound from `$2`
+    ///
+    /// `$1` is left for the key's ID.
+    fn set_clause(&self) -> (String, Vec<SqlValue>) {
+        let rate_limit = self
+            .rate_limit_per_minute
+            .or_else(|| self.tier.as_ref().map(ApiKeyTier::default_rate_limit));
+        let monthly_quota = self
+            .monthly_quota
+            .or_else(|| self.tier.as_ref().map(ApiKeyTier::default_monthly_quota));
+
+        let tier = self.tier.as_ref().map(ApiKeyTier::as_str);
+        let changes = [
+            ("name", self.name.clone().map(sql_value)),
+            ("tier", tier.map(sql_value)),
+            ("rate_limit_per_minute", rate_limit.map(sql_value)),
+            ("monthly_quota", monthly_quota.map(sql_value)),
+            ("expires_at", self.expires_at.map(sql_value)),
+            ("is_active", self.is_active.map(sql_value)),
+            ("webhook_url", self.webhook_url.clone().map(sql_value)),
+            (
+                "allowed_design_domains",
+                self.allowed_design_domains
+                    .as_ref()
+                    .map(|domains| sql_value(domains.as_deref().map(domains_json))),
+            ),
+        ];
+
+        let mut assignments = Vec::new();
+        let mut params = Vec::new();
+        for (column, value) in changes {
+            if let Some(value) = value {
+                params.push(value);
+                // JSONB columns are bound as text
+                let cast = if column == "allowed_design_domains" {
+                    "::TEXT::JSONB"
+                } else {
+                    ""
+                };
+                assignments.push(format!("{} = ${}{}", column, params.len() + 1, cast));
+            }
+        }
+        (assignments.join(", "), params)
+    }
+}
+
+/// A domain list as the JSON stored in `allowed_design_domains`
+fn domains_json(domains: &[String]) -> String {
+    serde_json::to_string(domains).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Kinds of change recorded in a key's audit trail
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyEventType {
+    Created,
+    Revoked,
+    Rotated,
+    Updated,
+    Deleted,
+}
+
+impl KeyEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyEventType::Created => "created",
+            KeyEventType::Revoked => "revoked",
+            KeyEventType::Rotated => "rotated",
+            KeyEventType::Updated => "updated",
+            KeyEventType::Deleted => "deleted",
+        }
+    }
+}
+
+/// One entry of a key's audit trail
+#[derive(Debug, Clone)]
+pub struct KeyEvent {
+    pub id: Uuid,
+    pub key_id: Uuid,
+    pub event_type: String,
+    /// Key whose request made the change; None once that key is purged
+    pub actor_key_id: Option<Uuid>,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
 }
 
 /// Response containing the new API key (only returned once!)
@@ -145,12 +318,28 @@ impl ApiKeyRepository {
     }
 
     /// Hash an API key using SHA-256
-    fn hash_api_key(key: &str) -> String {
+    pub fn hash_api_key(key: &str) -> String {
         let mut hasher = Sha256::new();
         hasher.update(key.as_bytes());
         hex::encode(hasher.finalize())
     }
 
+    /// The prefix keys are looked up by ("rim_" + 8 chars)
+    ///
+    /// None for keys too short to have one, and for non-ASCII input, which no
+    /// generated key contains.
+    pub fn key_prefix(api_key: &str) -> Option<&str> {
+        if !api_key.is_ascii() || api_key.len() < 12 {
+            return None;
+        }
+        Some(&api_key[..12])
+    }
+
+    /// Compare two key hashes in constant time
+    fn hashes_match(stored: &str, presented: &str) -> bool {
+        stored.as_bytes().ct_eq(presented.as_bytes()).into()
+    }
+
     /// Create a new API key
     pub async fn create(
         &self,
@@ -160,7 +349,7 @@ impl ApiKeyRepository {
 
         // Generate key and hash
         let api_key = Self::generate_api_key();
-        let key_prefix = api_key[..12].to_string(); // "rim_" + 8 chars
+        let key_prefix = api_key[..12].to_string();
         let key_hash = Self::hash_api_key(&api_key);
 
         // Use tier defaults if not specified
@@ -176,8 +365,9 @@ impl ApiKeyRepository {
                 r#"
             INSERT INTO api_keys (
                 key_prefix, key_hash, name, owner_email, owner_name, company,
-                tier, rate_limit_per_minute, monthly_quota, expires_at
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                tier, rate_limit_per_minute, monthly_quota, expires_at,
+                allowed_design_domains
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11::TEXT::JSONB)
             RETURNING id
             "#,
                 &[
@@ -191,6 +381,7 @@ impl ApiKeyRepository {
                     &rate_limit,
                     &monthly_quota,
                     &request.expires_at,
+                    &request.allowed_design_domains.as_deref().map(domains_json),
                 ],
             )
             .await?;
@@ -217,49 +408,168 @@ impl ApiKeyRepository {
     }
 
     /// Validate an API key and return its details
+    ///
+    /// Keys sharing the prefix are fetched and their hashes compared here in
+    /// constant time, rather than by the database. A secret replaced by
+    /// `rotate` still validates until its grace period ends.
     pub async fn validate(&self, api_key: &str) -> Result<Option<DbApiKey>, DbError> {
+        let Some(key_prefix) = Self::key_prefix(api_key) else {
+            return Ok(None);
+        };
+        let key_hash = Self::hash_api_key(api_key);
+
         let client = self.pool.get().await?;
+        let sql = format!(
+            "SELECT {}, \
+                CASE WHEN old_key_expires_at > NOW() THEN old_key_hash END AS live_old_key_hash \
+             FROM api_keys \
+             WHERE deleted_at IS NULL \
+               AND (key_prefix = $1 OR (old_key_prefix = $1 AND old_key_expires_at > NOW()))",
+            API_KEY_COLUMNS
+        );
+        let rows = client.query(&sql, &[&key_prefix]).await?;
 
-        // Extract prefix for efficient lookup
-        if api_key.len() < 12 {
-            return Ok(None);
+        // Every candidate is compared, so timing doesn't reveal which matched
+        let mut found = None;
+        for row in &rows {
+            let stored: String = row.get("key_hash");
+            let old: Option<String> = row.get("live_old_key_hash");
+            let current = Self::hashes_match(&stored, &key_hash);
+            let previous = old.is_some_and(|old| Self::hashes_match(&old, &key_hash));
+            if (current | previous) && found.is_none() {
+                found = Some(api_key_from_row(row));
+            }
         }
-        let key_prefix = &api_key[..12];
-        let key_hash = Self::hash_api_key(api_key);
 
-        let row = client
+        Ok(found)
+    }
+
+    /// Replace a key's secret, keeping its ID, usage and quotas
+    ///
+    /// The old secret keeps working for `grace_period_minutes`, or stops at
+    /// once when that is zero. The rotation is recorded in `api_key_rotations`
+    /// along with the key that asked for it. Returns None for unknown, revoked
+    /// or deleted keys.
+    pub async fn rotate(
+        &self,
+        id: Uuid,
+        grace_period_minutes: i32,
+        rotated_by: Uuid,
+    ) -> Result<Option<CreateApiKeyResponse>, DbError> {
+        let mut client = self.pool.get().await?;
+        let tx = client.transaction().await?;
+
+        let old_key_prefix: String = match tx
             .query_opt(
+                "SELECT key_prefix FROM api_keys \
+                 WHERE id = $1 AND is_active AND deleted_at IS NULL FOR UPDATE",
+                &[&id],
+            )
+            .await?
+        {
+            Some(row) => row.get("key_prefix"),
+            None => return Ok(None),
+        };
+
+        let api_key = Self::generate_api_key();
+        let key_prefix = api_key[..12].to_string();
+        let key_hash = Self::hash_api_key(&api_key);
+
+        let row = tx
+            .query_one(
                 r#"
-            SELECT
-                id, key_prefix, key_hash, name, owner_email, owner_name, company,
-                tier, rate_limit_per_minute, monthly_quota, is_active,
-                created_at, updated_at, last_used_at, expires_at
-            FROM api_keys
-            WHERE key_prefix = $1 AND key_hash = $2
+            UPDATE api_keys SET
+                old_key_hash = CASE WHEN $4 > 0 THEN key_hash END,
+                old_key_prefix = CASE WHEN $4 > 0 THEN key_prefix END,
+                old_key_expires_at = CASE
+                    WHEN $4 > 0 THEN NOW() + make_interval(mins => $4)
+                END,
+                key_prefix = $2,
+                key_hash = $3
+            WHERE id = $1
+            RETURNING name, tier, rate_limit_per_minute, monthly_quota
             "#,
-                &[&key_prefix, &key_hash],
+                &[&id, &key_prefix, &key_hash, &grace_period_minutes],
             )
             .await?;
 
-        Ok(row.map(|row| DbApiKey {
-            id: row.get("id"),
-            key_prefix: row.get("key_prefix"),
-            key_hash: row.get("key_hash"),
+        tx.execute(
+            r#"
+            INSERT INTO api_key_rotations (
+                api_key_id, rotated_by, old_key_prefix, new_key_prefix, grace_period_minutes
+            ) VALUES ($1, $2, $3, $4, $5)
+            "#,
+            &[
+                &id,
+                &rotated_by,
+                &old_key_prefix,
+                &key_prefix,
+                &grace_period_minutes,
+            ],
+        )
+        .await?;
+        tx.commit().await?;
+
+        info!(
+            key_id = %id,
+            old_key_prefix = %old_key_prefix,
+            key_prefix = %key_prefix,
+            rotated_by = %rotated_by,
+            grace_period_minutes,
+            "Rotated API key"
+        );
+
+        Ok(Some(CreateApiKeyResponse {
+            id,
+            api_key,
+            key_prefix,
             name: row.get("name"),
-            owner_email: row.get("owner_email"),
-            owner_name: row.get("owner_name"),
-            company: row.get("company"),
             tier: row.get("tier"),
             rate_limit_per_minute: row.get("rate_limit_per_minute"),
             monthly_quota: row.get("monthly_quota"),
-            is_active: row.get("is_active"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-            last_used_at: row.get("last_used_at"),
-            expires_at: row.get("expires_at"),
         }))
     }
 
+    /// Apply changes to an API key, returning the updated key
+    ///
+    /// Returns None when no key has the ID or the key was deleted. Callers
+    /// invalidate the key cache so the changes apply from the key's next request.
+    pub async fn update(
+        &self,
+        id: Uuid,
+        request: &UpdateApiKeyRequest,
+    ) -> Result<Option<DbApiKey>, DbError> {
+        if request.is_empty() {
+            return self.get_by_id(id).await;
+        }
+
+        let client = self.pool.get().await?;
+        let (set_clause, values) = request.set_clause();
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&id];
+        params.extend(values.iter().map(|v| v.as_ref() as &(dyn ToSql + Sync)));
+
+        let sql = format!(
+            "UPDATE api_keys SET {} WHERE id = $1 AND deleted_at IS NULL RETURNING {}",
+            set_clause, API_KEY_COLUMNS
+        );
+        let key = client
+            .query_opt(&sql, &params)
+            .await?
+            .as_ref()
+            .map(api_key_from_row);
+
+        if let Some(key) = &key {
+            info!(
+                key_id = %id,
+                tier = %key.tier,
+                rate_limit = key.rate_limit_per_minute,
+                is_active = key.is_active,
+                "Updated API key"
+            );
+        }
+        Ok(key)
+    }
+
     /// Update last_used_at timestamp
     pub async fn touch(&self, key_id: Uuid) -> Result<(), DbError> {
         let client = self.pool.get().await?;
@@ -274,7 +584,7 @@ impl ApiKeyRepository {
         Ok(())
     }
 
-    /// Get API key by ID
+    /// Get API key by ID, deleted or not
     pub async fn get_by_id(&self, id: Uuid) -> Result<Option<DbApiKey>, DbError> {
         let client = self.pool.get().await?;
 
@@ -284,7 +594,8 @@ impl ApiKeyRepository {
             SELECT
                 id, key_prefix, key_hash, name, owner_email, owner_name, company,
                 tier, rate_limit_per_minute, monthly_quota, is_active,
-                created_at, updated_at, last_used_at, expires_at
+                created_at, updated_at, last_used_at, expires_at, webhook_url,
+                allowed_design_domains::TEXT AS allowed_design_domains, deleted_at
             FROM api_keys
             WHERE id = $1
             "#,
@@ -292,64 +603,54 @@ impl ApiKeyRepository {
             )
             .await?;
 
-        Ok(row.map(|row| DbApiKey {
-            id: row.get("id"),
-            key_prefix: row.get("key_prefix"),
-            key_hash: row.get("key_hash"),
-            name: row.get("name"),
-            owner_email: row.get("owner_email"),
-            owner_name: row.get("owner_name"),
-            company: row.get("company"),
-            tier: row.get("tier"),
-            rate_limit_per_minute: row.get("rate_limit_per_minute"),
-            monthly_quota: row.get("monthly_quota"),
-            is_active: row.get("is_active"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-            last_used_at: row.get("last_used_at"),
-            expires_at: row.get("expires_at"),
-        }))
+        Ok(row.as_ref().map(api_key_from_row))
     }
 
-    /// List all API keys for an owner
-    pub async fn list_by_owner(&self, owner_email: &str) -> Result<Vec<DbApiKey>, DbError> {
+    /// One page of an owner's API keys, newest first
+    ///
+    /// Deleted keys are only included when `include_deleted` is set. Keys
+    /// created in the same instant are ordered by ID, so pages never overlap.
+    pub async fn list_by_owner(
+        &self,
+        owner_email: &str,
+        include_deleted: bool,
+        page: PageRequest,
+    ) -> Result<Page<DbApiKey>, DbError> {
         let client = self.pool.get().await?;
 
+        let total: i64 = client
+            .query_one(
+                "SELECT COUNT(*) AS total FROM api_keys \
+                 WHERE owner_email = $1 AND ($2 OR deleted_at IS NULL)",
+                &[&owner_email, &include_deleted],
+            )
+            .await?
+            .get("total");
+
+        let sql = format!(
+            "SELECT {} FROM api_keys \
+             WHERE owner_email = $1 AND ($2 OR deleted_at IS NULL) \
+             ORDER BY created_at DESC, id DESC \
+             LIMIT $3 OFFSET $4",
+            API_KEY_COLUMNS
+        );
         let rows = client
             .query(
-                r#"
-            SELECT
-                id, key_prefix, key_hash, name, owner_email, owner_name, company,
-                tier, rate_limit_per_minute, monthly_quota, is_active,
-                created_at, updated_at, last_used_at, expires_at
-            FROM api_keys
-            WHERE owner_email = $1
-            ORDER BY created_at DESC
-            "#,
-                &[&owner_email],
+                &sql,
+                &[
+                    &owner_email,
+                    &include_deleted,
+                    &page.limit(),
+                    &page.offset(),
+                ],
             )
             .await?;
 
-        Ok(rows
-            .iter()
-            .map(|row| DbApiKey {
-                id: row.get("id"),
-                key_prefix: row.get("key_prefix"),
-                key_hash: row.get("key_hash"),
-                name: row.get("name"),
-                owner_email: row.get("owner_email"),
-                owner_name: row.get("owner_name"),
-                company: row.get("company"),
-                tier: row.get("tier"),
-                rate_limit_per_minute: row.get("rate_limit_per_minute"),
-                monthly_quota: row.get("monthly_quota"),
-                is_active: row.get("is_active"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                last_used_at: row.get("last_used_at"),
-                expires_at: row.get("expires_at"),
-            })
-            .collect())
+        Ok(Page::new(
+            rows.iter().map(api_key_from_row).collect(),
+            total,
+            page,
+        ))
     }
 
     /// Revoke (deactivate) an API key
@@ -358,7 +659,7 @@ impl ApiKeyRepository {
 
         let result = client
             .execute(
-                "UPDATE api_keys SET is_active = false WHERE id = $1",
+                "UPDATE api_keys SET is_active = false WHERE id = $1 AND deleted_at IS NULL",
                 &[&id],
             )
             .await?;
@@ -370,12 +671,18 @@ impl ApiKeyRepository {
         Ok(result > 0)
     }
 
-    /// Delete an API key permanently
+    /// Delete an API key, keeping the row for its usage and billing history
+    ///
+    /// The key stops authenticating and drops out of owner listings. Returns
+    /// false for unknown or already deleted keys.
     pub async fn delete(&self, id: Uuid) -> Result<bool, DbError> {
         let client = self.pool.get().await?;
 
         let result = client
-            .execute("DELETE FROM api_keys WHERE id = $1", &[&id])
+            .execute(
+                "UPDATE api_keys SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
+                &[&id],
+            )
             .await?;
 
         if result > 0 {
@@ -384,4 +691,499 @@ impl ApiKeyRepository {
 
         Ok(result > 0)
     }
+
+    /// Erase an API key, along with the usage and events that cascade from it
+    ///
+    /// Only for erasure requests; everything else uses `delete`.
+    pub async fn purge(&self, id: Uuid) -> Result<bool, DbError> {
+        let client = self.pool.get().await?;
+
+        let result = client
+            .execute("DELETE FROM api_keys WHERE id = $1", &[&id])
+            .await?;
+
+        if result > 0 {
+            warn!(key_id = %id, "API key purged");
+        }
+
+        Ok(result > 0)
+    }
+
+    /// Add an event to a key's audit trail
+    ///
+    /// `actor_key_id` is the key whose request made the change.
+    pub async fn record_event(
+        &self,
+        key_id: Uuid,
+        event_type: KeyEventType,
+        actor_key_id: Option<Uuid>,
+        details: &serde_json::Value,
+    ) -> Result<(), DbError> {
+        let client = self.pool.get().await?;
+
+        client
+            .execute(
+                r#"
+            INSERT INTO key_events (key_id, event_type, actor_key_id, details)
+            VALUES ($1, $2, $3, $4::TEXT::JSONB)
+            "#,
+                &[
+                    &key_id,
+                    &event_type.as_str(),
+                    &actor_key_id,
+                    &details.to_string(),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// A key's audit trail, oldest first
+    pub async fn events(&self, key_id: Uuid) -> Result<Vec<KeyEvent>, DbError> {
+        let client = self.pool.get().await?;
+
+        let rows = client
+            .query(
+                r#"
+            SELECT id, key_id, event_type, actor_key_id, details::TEXT AS details, created_at
+            FROM key_events
+            WHERE key_id = $1
+            ORDER BY created_at, id
+            "#,
+                &[&key_id],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| KeyEvent {
+                id: row.get("id"),
+                key_id: row.get("key_id"),
+                event_type: row.get("event_type"),
+                actor_key_id: row.get("actor_key_id"),
+                details: serde_json::from_str(row.get::<_, &str>("details"))
+                    .unwrap_or(serde_json::Value::Null),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+}
+
+fn api_key_from_row(row: &Row) -> DbApiKey {
+    DbApiKey {
+        id: row.get("id"),
+        key_prefix: row.get("key_prefix"),
+        key_hash: row.get("key_hash"),
+        name: row.get("name"),
+        owner_email: row.get("owner_email"),
+        owner_name: row.get("owner_name"),
+        company: row.get("company"),
+        tier: row.get("tier"),
+        rate_limit_per_minute: row.get("rate_limit_per_minute"),
+        monthly_quota: row.get("monthly_quota"),
+        is_active: row.get("is_active"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        last_used_at: row.get("last_used_at"),
+        expires_at: row.get("expires_at"),
+        webhook_url: row.get("webhook_url"),
+        allowed_design_domains: row
+            .get::<_, Option<&str>>("allowed_design_domains")
+            .and_then(|json| serde_json::from_str(json).ok()),
+        deleted_at: row.get("deleted_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Repository on the database in `TEST_DATABASE_URL`, migrations applied
+    ///
+    /// Tests that need it return early when the variable isn't set.
+    fn test_repo() -> Option<ApiKeyRepository> {
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        Some(ApiKeyRepository::new(pool))
+    }
+
+    async fn create_key(repo: &ApiKeyRepository) -> CreateApiKeyResponse {
+        repo.create(CreateApiKeyRequest {
+            name: "Rotation test".to_string(),
+            owner_email: format!("rotation-{}@example.com", Uuid::new_v4()),
+            owner_name: None,
+            company: None,
+            tier: ApiKeyTier::Starter,
+            rate_limit_per_minute: None,
+            monthly_quota: None,
+            expires_at: None,
+            allowed_design_domains: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Move the end of a key's grace period into the past
+    async fn expire_grace_period(repo: &ApiKeyRepository, id: Uuid) {
+        let client = repo.pool.get().await.unwrap();
+        client
+            .execute(
+                "UPDATE api_keys SET old_key_expires_at = NOW() - INTERVAL '1 minute' \
+                 WHERE id = $1",
+                &[&id],
+            )
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_generated_keys() {
+        let key = ApiKeyRepository::generate_api_key();
+        assert!(key.starts_with("rim_"));
+        assert_eq!(key.len(), 36);
+        assert_ne!(key, ApiKeyRepository::generate_api_key());
+        assert_eq!(ApiKeyRepository::hash_api_key(&key).len(), 64);
+    }
+
+    #[test]
+    fn test_key_prefix_rejects_short_and_non_ascii_keys() {
+        let key = ApiKeyRepository::generate_api_key();
+        assert_eq!(ApiKeyRepository::key_prefix(&key), Some(&key[..12]));
+
+        assert_eq!(ApiKeyRepository::key_prefix("rim_abc"), None);
+        // 12 bytes, but byte 12 falls inside a character
+        assert_eq!(ApiKeyRepository::key_prefix("rim_aéééé"), None);
+        assert_eq!(ApiKeyRepository::key_prefix("rim_日本語の鍵です"), None);
+    }
+
+    #[test]
+    fn test_hashes_match() {
+        let hash = ApiKeyRepository::hash_api_key("rim_a");
+        assert!(ApiKeyRepository::hashes_match(&hash, &hash.clone()));
+        assert!(!ApiKeyRepository::hashes_match(
+            &hash,
+            &ApiKeyRepository::hash_api_key("rim_b")
+        ));
+        assert!(!ApiKeyRepository::hashes_match(&hash, &hash[..63]));
+    }
+
+    #[tokio::test]
+    async fn test_validate_compares_the_whole_secret() {
+        let Some(repo) = test_repo() else { return };
+        let created = create_key(&repo).await;
+
+        let key = repo.validate(&created.api_key).await.unwrap().unwrap();
+        assert_eq!(key.id, created.id);
+
+        // Right prefix, wrong secret
+        let last = if created.api_key.ends_with('a') {
+            "b"
+        } else {
+            "a"
+        };
+        let guess = format!("{}{}", &created.api_key[..35], last);
+        assert!(repo.validate(&guess).await.unwrap().is_none());
+        assert!(repo.validate("rim_aéééé").await.unwrap().is_none());
+
+        repo.purge(created.id).await.unwrap();
+    }
+
+    #[test]
+    fn test_update_sets_only_given_fields() {
+        let request = UpdateApiKeyRequest {
+            name: Some("Renamed".to_string()),
+            is_active: Some(false),
+            ..Default::default()
+        };
+        let (clause, params) = request.set_clause();
+        assert_eq!(clause, "name = $2, is_active = $3");
+        assert_eq!(params.len(), 2);
+
+        assert!(UpdateApiKeyRequest::default().is_empty());
+        assert!(!request.is_empty());
+    }
+
+    #[test]
+    fn test_update_tier_applies_default_limits() {
+        let request = UpdateApiKeyRequest {
+            tier: Some(ApiKeyTier::Pro),
+            ..Default::default()
+        };
+        let (clause, _) = request.set_clause();
+        assert_eq!(
+            clause,
+            "tier = $2, rate_limit_per_minute = $3, monthly_quota = $4"
+        );
+
+        // Explicit limits win over the tier's
+        let request = UpdateApiKeyRequest {
+            tier: Some(ApiKeyTier::Pro),
+            monthly_quota: Some(50),
+            expires_at: Some(None),
+            ..Default::default()
+        };
+        let (clause, params) = request.set_clause();
+        assert_eq!(
+            clause,
+            "tier = $2, rate_limit_per_minute = $3, monthly_quota = $4, expires_at = $5"
+        );
+        assert_eq!(params.len(), 4);
+    }
+
+    #[test]
+    fn test_update_binds_design_domains_as_json() {
+        let request = UpdateApiKeyRequest {
+            allowed_design_domains: Some(Some(vec!["cdn.acme.com".to_string()])),
+            ..Default::default()
+        };
+        let (clause, params) = request.set_clause();
+        assert_eq!(clause, "allowed_design_domains = $2::TEXT::JSONB");
+        assert_eq!(params.len(), 1);
+        assert_eq!(
+            domains_json(&["cdn.acme.com".to_string()]),
+            r#"["cdn.acme.com"]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_design_domains_round_trip() {
+        let Some(repo) = test_repo() else { return };
+        let created = create_key(&repo).await;
+        assert_eq!(
+            repo.get_by_id(created.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .allowed_design_domains,
+            None
+        );
+
+        let domains = vec!["cdn.acme.com".to_string(), "images.acme.com".to_string()];
+        let request = UpdateApiKeyRequest {
+            allowed_design_domains: Some(Some(domains.clone())),
+            ..Default::default()
+        };
+        let key = repo.update(created.id, &request).await.unwrap().unwrap();
+        assert_eq!(key.allowed_design_domains, Some(domains));
+
+        let request = UpdateApiKeyRequest {
+            allowed_design_domains: Some(None),
+            ..Default::default()
+        };
+        let key = repo.update(created.id, &request).await.unwrap().unwrap();
+        assert_eq!(key.allowed_design_domains, None);
+        repo.purge(created.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_deleted_keys_are_kept_until_purged() {
+        let Some(repo) = test_repo() else { return };
+        let created = create_key(&repo).await;
+        let owner = repo
+            .get_by_id(created.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .owner_email;
+        repo.record_event(
+            created.id,
+            KeyEventType::Created,
+            None,
+            &serde_json::json!({}),
+        )
+        .await
+        .unwrap();
+        assert!(repo.delete(created.id).await.unwrap());
+
+        // Deleted keys can't change, and only list when asked for
+        let update = UpdateApiKeyRequest {
+            name: Some("Renamed".to_string()),
+            ..Default::default()
+        };
+        assert!(!repo.delete(created.id).await.unwrap());
+        assert!(!repo.revoke(created.id).await.unwrap());
+        assert!(repo.update(created.id, &update).await.unwrap().is_none());
+        let all = PageRequest::new(1, 10);
+        let listed = repo.list_by_owner(&owner, false, all).await.unwrap();
+        assert!(listed.items.is_empty());
+        assert_eq!(listed.total, 0);
+        let listed = repo.list_by_owner(&owner, true, all).await.unwrap();
+        assert_eq!((listed.items.len(), listed.total), (1, 1));
+        assert_eq!(repo.events(created.id).await.unwrap().len(), 1);
+
+        // Purging erases the key and its trail
+        assert!(repo.purge(created.id).await.unwrap());
+        assert!(repo.get_by_id(created.id).await.unwrap().is_none());
+        assert!(repo.events(created.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_by_owner_pages() {
+        let Some(repo) = test_repo() else { return };
+        let owner = format!("pages-{}@example.com", Uuid::new_v4());
+        let mut ids = Vec::new();
+        for n in 0..5 {
+            let created = repo
+                .create(CreateApiKeyRequest {
+                    name: format!("Page test {}", n),
+                    owner_email: owner.clone(),
+                    owner_name: None,
+                    company: None,
+                    tier: ApiKeyTier::Free,
+                    rate_limit_per_minute: None,
+                    monthly_quota: None,
+                    expires_at: None,
+                    allowed_design_domains: None,
+                })
+                .await
+                .unwrap();
+            ids.push(created.id);
+        }
+
+        let mut listed = Vec::new();
+        for number in 1..=3 {
+            let page = repo
+                .list_by_owner(&owner, false, PageRequest::new(number, 2))
+                .await
+                .unwrap();
+            assert_eq!((page.total, page.total_pages()), (5, 3));
+            assert_eq!(page.items.len(), if number < 3 { 2 } else { 1 });
+            listed.extend(page.items);
+        }
+        // Newest first, each key exactly once
+        assert!(listed
+            .windows(2)
+            .all(|pair| (pair[0].created_at, pair[0].id) > (pair[1].created_at, pair[1].id)));
+        let mut listed_ids: Vec<_> = listed.iter().map(|key| key.id).collect();
+        listed_ids.sort();
+        ids.sort();
+        assert_eq!(listed_ids, ids);
+
+        for id in ids {
+            repo.purge(id).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_key() {
+        let Some(repo) = test_repo() else { return };
+        let created = create_key(&repo).await;
+
+        let request = UpdateApiKeyRequest {
+            tier: Some(ApiKeyTier::Pro),
+            monthly_quota: Some(50),
+            expires_at: Some(Some(Utc::now() + chrono::Duration::days(30))),
+            ..Default::default()
+        };
+        let key = repo.update(created.id, &request).await.unwrap().unwrap();
+        assert_eq!(key.tier, "pro");
+        assert_eq!(
+            key.rate_limit_per_minute,
+            ApiKeyTier::Pro.default_rate_limit()
+        );
+        assert_eq!(key.monthly_quota, 50);
+        assert!(key.expires_at.is_some());
+        assert_eq!(key.name, "Rotation test");
+
+        let request = UpdateApiKeyRequest {
+            expires_at: Some(None),
+            ..Default::default()
+        };
+        let key = repo.update(created.id, &request).await.unwrap().unwrap();
+        assert!(key.expires_at.is_none());
+        assert_eq!(key.monthly_quota, 50);
+
+        assert!(repo
+            .update(Uuid::new_v4(), &request)
+            .await
+            .unwrap()
+            .is_none());
+        repo.purge(created.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rotation_without_grace_period() {
+        let Some(repo) = test_repo() else { return };
+        let created = create_key(&repo).await;
+
+        let rotated = repo
+            .rotate(created.id, 0, created.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rotated.id, created.id);
+        assert_ne!(rotated.api_key, created.api_key);
+        assert_eq!(rotated.key_prefix, &rotated.api_key[..12]);
+        assert_eq!(rotated.tier, created.tier);
+        assert_eq!(rotated.monthly_quota, created.monthly_quota);
+
+        assert!(repo.validate(&created.api_key).await.unwrap().is_none());
+        let key = repo.validate(&rotated.api_key).await.unwrap().unwrap();
+        assert_eq!(key.id, created.id);
+
+        repo.purge(created.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_old_key_works_until_grace_period_ends() {
+        let Some(repo) = test_repo() else { return };
+        let created = create_key(&repo).await;
+
+        let rotated = repo
+            .rotate(created.id, 60, created.id)
+            .await
+            .unwrap()
+            .unwrap();
+        for api_key in [&created.api_key, &rotated.api_key] {
+            let key = repo.validate(api_key).await.unwrap().unwrap();
+            assert_eq!(key.id, created.id);
+        }
+
+        expire_grace_period(&repo, created.id).await;
+        assert!(repo.validate(&created.api_key).await.unwrap().is_none());
+        assert!(repo.validate(&rotated.api_key).await.unwrap().is_some());
+
+        // Rotating again retires the secret the grace period was for
+        let again = repo
+            .rotate(created.id, 0, created.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(repo.validate(&rotated.api_key).await.unwrap().is_none());
+        assert!(repo.validate(&again.api_key).await.unwrap().is_some());
+
+        let client = repo.pool.get().await.unwrap();
+        let rotations: i64 = client
+            .query_one(
+                "SELECT COUNT(*) FROM api_key_rotations WHERE api_key_id = $1",
+                &[&created.id],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(rotations, 2);
+
+        repo.purge(created.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_revoked_keys_are_not_rotated() {
+        let Some(repo) = test_repo() else { return };
+        let created = create_key(&repo).await;
+        repo.revoke(created.id).await.unwrap();
+
+        assert!(repo
+            .rotate(created.id, 0, created.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .rotate(Uuid::new_v4(), 0, created.id)
+            .await
+            .unwrap()
+            .is_none());
+
+        repo.purge(created.id).await.unwrap();
+    }
 }
@@ -0,0 +1,1856 @@
+//! POD catalog database operations
+//!
+//! Synced provider products are upserted into `pod_products`,
+//! `pod_product_variants`, and `pod_print_areas`, keyed by the provider's IDs.
+
+use super::pool::{DbError, DbPool};
+use crate::domain::catalog::{
+    AssetType, DbPodPrintArea, DbPodProduct, DbPodProductVariant, MockupAsset, PrintPlacement,
+    UnifiedPrintArea, UnifiedProduct, UnifiedVariant,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Row, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const PRODUCT_COLUMNS: &str = "id, provider_id, external_product_id, category_id, name, \
+     description, brand, model, product_type, is_available, \
+     COALESCE(regions, '[]')::TEXT AS regions, base_price_cents, \
+     COALESCE(currency, 'USD') AS currency, \
+     COALESCE(provider_metadata, '{}')::TEXT AS provider_metadata, \
+     last_synced_at, sync_hash, created_at, updated_at";
+
+const VARIANT_COLUMNS: &str = "id, product_id, external_variant_id, sku, size, color_name, \
+     color_hex, is_available, price_cents, COALESCE(in_stock, true) AS in_stock, \
+     COALESCE(provider_metadata, '{}')::TEXT AS provider_metadata, created_at, updated_at";
+
+/// Matches the asset `a` with source URL `$3` of product `$2` from provider `$1`
+const ASSET_BY_SOURCE: &str = "FROM pod_products p JOIN pod_providers pr ON pr.id = p.provider_id \
+     WHERE a.product_id = p.id AND pr.code = $1 AND p.external_product_id = $2 \
+     AND a.source_url = $3";
+
+/// Creates or resets to pending the asset with source URL `$6` of product `$2`
+/// from provider `$1`
+const PENDING_ASSET_UPSERT: &str = r#"
+    INSERT INTO pod_mockup_assets (
+        product_id, variant_id, asset_type, placement, source_url, width_px, height_px,
+        status
+    )
+    SELECT p.id,
+        (SELECT v.id FROM pod_product_variants v
+         WHERE v.product_id = p.id AND v.external_variant_id = $3),
+        $4, $5, $6, $7, $8, 'pending'
+    FROM pod_products p
+    JOIN pod_providers pr ON pr.id = p.provider_id
+    WHERE pr.code = $1 AND p.external_product_id = $2
+    ON CONFLICT (product_id, source_url) DO UPDATE SET
+        variant_id = EXCLUDED.variant_id,
+        asset_type = EXCLUDED.asset_type,
+        placement = EXCLUDED.placement,
+        width_px = COALESCE(EXCLUDED.width_px, pod_mockup_assets.width_px),
+        height_px = COALESCE(EXCLUDED.height_px, pod_mockup_assets.height_px),
+        status = 'pending',
+        updated_at = NOW()
+"#;
+
+const PRINT_AREA_COLUMNS: &str = "id, product_id, external_print_area_id, placement, name, \
+     width_px, height_px, COALESCE(offset_x_px, 0) AS offset_x_px, \
+     COALESCE(offset_y_px, 0) AS offset_y_px, COALESCE(print_dpi, 300) AS print_dpi, \
+     COALESCE(file_format, 'PNG') AS file_format, \
+     COALESCE(constraints, '{}')::TEXT AS constraints, created_at";
+
+/// Rebuilds the `search_vector` of product `$1` from its name, brand, model,
+/// type, and the sizes and colors of its available variants
+///
+/// Must match the backfill in `020_catalog_search.sql`.
+const SEARCH_VECTOR_UPDATE: &str = r#"
+    UPDATE pod_products p SET search_vector =
+        setweight(to_tsvector('simple', COALESCE(p.name, '')), 'A') ||
+        setweight(to_tsvector('simple', concat_ws(' ', p.brand, p.model)), 'B') ||
+        setweight(to_tsvector('simple', COALESCE(p.product_type, '')), 'C') ||
+        setweight(to_tsvector('simple', COALESCE((
+            SELECT string_agg(DISTINCT concat_ws(' ', v.size, v.color_name), ' ')
+            FROM pod_product_variants v
+            WHERE v.product_id = p.id AND v.is_available
+        ), '')), 'D')
+    WHERE p.id = $1
+"#;
+
+/// Most words of a search query that are searched for
+const MAX_SEARCH_TERMS: usize = 8;
+
+/// Most matched variants listed per search result
+const MAX_MATCHED_VARIANTS: i64 = 5;
+
+/// JSONB column selected as text
+fn json_column(row: &Row, name: &str) -> serde_json::Value {
+    serde_json::from_str(row.get::<_, &str>(name)).unwrap_or(serde_json::Value::Null)
+}
+
+fn product_from_row(row: &Row) -> DbPodProduct {
+    DbPodProduct {
+        id: row.get("id"),
+        provider_id: row.get("provider_id"),
+        external_product_id: row.get("external_product_id"),
+        category_id: row.get("category_id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        brand: row.get("brand"),
+        model: row.get("model"),
+        product_type: row.get("product_type"),
+        is_available: row.get("is_available"),
+        regions: json_column(row, "regions"),
+        base_price_cents: row.get("base_price_cents"),
+        currency: row.get("currency"),
+        provider_metadata: json_column(row, "provider_metadata"),
+        last_synced_at: row.get("last_synced_at"),
+        sync_hash: row.get("sync_hash"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn variant_from_row(row: &Row) -> DbPodProductVariant {
+    DbPodProductVariant {
+        id: row.get("id"),
+        product_id: row.get("product_id"),
+        external_variant_id: row.get("external_variant_id"),
+        sku: row.get("sku"),
+        size: row.get("size"),
+        color_name: row.get("color_name"),
+        color_hex: row.get("color_hex"),
+        is_available: row.get("is_available"),
+        price_cents: row.get("price_cents"),
+        in_stock: row.get("in_stock"),
+        provider_metadata: json_column(row, "provider_metadata"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn print_area_from_row(row: &Row) -> DbPodPrintArea {
+    DbPodPrintArea {
+        id: row.get("id"),
+        product_id: row.get("product_id"),
+        external_print_area_id: row.get("external_print_area_id"),
+        placement: row.get("placement"),
+        name: row.get("name"),
+        width_px: row.get("width_px"),
+        height_px: row.get("height_px"),
+        offset_x_px: row.get("offset_x_px"),
+        offset_y_px: row.get("offset_y_px"),
+        print_dpi: row.get("print_dpi"),
+        file_format: row.get("file_format"),
+        constraints: json_column(row, "constraints"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Hex SHA-256 of the serialized product, stored as `sync_hash`
+pub fn sync_hash(product: &UnifiedProduct) -> String {
+    let serialized = serde_json::to_vec(product).unwrap_or_default();
+    hex::encode(Sha256::digest(serialized))
+}
+
+/// What storing a synced product did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoredProduct {
+    /// Product, variants, and print areas were written
+    Updated(Uuid),
+    /// Same `sync_hash` as last time; only `last_synced_at` was refreshed
+    Unchanged(Uuid),
+}
+
+impl StoredProduct {
+    pub fn product_id(self) -> Uuid {
+        match self {
+            StoredProduct::Updated(id) | StoredProduct::Unchanged(id) => id,
+        }
+    }
+}
+
+/// Parameters of `PENDING_ASSET_UPSERT`
+struct PendingAssetParams<'a> {
+    provider_code: &'a str,
+    external_product_id: &'a str,
+    asset: &'a MockupAsset,
+    asset_type: String,
+    placement: Option<&'a str>,
+}
+
+impl<'a> PendingAssetParams<'a> {
+    fn new(provider_code: &'a str, external_product_id: &'a str, asset: &'a MockupAsset) -> Self {
+        PendingAssetParams {
+            provider_code,
+            external_product_id,
+            asset,
+            asset_type: asset.asset_type.to_string(),
+            placement: asset.placement.as_ref().map(|p| p.as_str()),
+        }
+    }
+
+    fn as_params(&self) -> [&(dyn ToSql + Sync); 8] {
+        [
+            &self.provider_code,
+            &self.external_product_id,
+            &self.asset.variant_external_id,
+            &self.asset_type,
+            &self.placement,
+            &self.asset.source_url,
+            &self.asset.width_px,
+            &self.asset.height_px,
+        ]
+    }
+}
+
+/// A catalog search query, as `to_tsquery` input
+///
+/// Words are split on anything but letters and digits and lowercased, which
+/// also strips the query of tsquery operators. The last word matches as a
+/// prefix, so results show up while a name is still being typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// The query as typed, trimmed
+    pub text: String,
+    terms: Vec<String>,
+}
+
+impl SearchQuery {
+    /// `None` when `text` has no words to search for
+    pub fn parse(text: &str) -> Option<Self> {
+        let terms: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .take(MAX_SEARCH_TERMS)
+            .map(str::to_lowercase)
+            .collect();
+        if terms.is_empty() {
+            return None;
+        }
+        Some(SearchQuery {
+            text: text.trim().to_string(),
+            terms,
+        })
+    }
+
+    /// Products must match every word
+    pub fn all_terms(&self) -> String {
+        self.tsquery(" & ")
+    }
+
+    /// Variants are highlighted for matching any word
+    pub fn any_term(&self) -> String {
+        self.tsquery(" | ")
+    }
+
+    fn tsquery(&self, operator: &str) -> String {
+        let last = self.terms.len() - 1;
+        self.terms
+            .iter()
+            .enumerate()
+            .map(|(i, term)| {
+                if i == last {
+                    format!("{}:*", term)
+                } else {
+                    term.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(operator)
+    }
+}
+
+/// A product found by catalog search
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProductSearchHit {
+    pub id: Uuid,
+    pub provider_code: String,
+    pub external_product_id: String,
+    pub name: String,
+    pub brand: Option<String>,
+    pub model: Option<String>,
+    pub product_type: String,
+    pub category_slug: Option<String>,
+    pub is_available: bool,
+    /// Text search rank; higher is a better match
+    pub rank: f32,
+    /// Name, brand, and model with the matched words wrapped in `<mark>`
+    pub highlight: String,
+    /// Available variants whose size or color matched, best first
+    pub matched_variants: Vec<MatchedVariant>,
+}
+
+/// A variant of a search result whose size or color matched the query
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MatchedVariant {
+    pub id: Uuid,
+    pub external_variant_id: String,
+    pub size: Option<String>,
+    pub color_name: Option<String>,
+    pub color_hex: Option<String>,
+}
+
+/// Status change of a mirrored asset, stored in `pod_mockup_assets`
+#[derive(Debug, Clone)]
+pub enum AssetUpdate<'a> {
+    /// Queued for download; creates the asset's row if needed
+    Pending,
+    Downloading,
+    Downloaded {
+        bucket: &'a str,
+        r2_key: &'a str,
+        /// Size of a fresh download; `None` keeps what was stored
+        file_size_bytes: Option<i64>,
+        /// Content type of a fresh download; `None` keeps what was stored
+        content_type: Option<&'a str>,
+        /// Hex SHA-256 of a fresh download; `None` keeps what was stored
+        checksum: Option<&'a str>,
+        /// Attempts that failed before this download
+        retries: i32,
+        /// Thumbnail made from a fresh download; `None` keeps what was stored
+        thumbnail_r2_key: Option<&'a str>,
+    },
+    Failed {
+        error: String,
+        attempts: i32,
+    },
+}
+
+/// A catalog product's mirrored mockup image and print area for one placement
+#[derive(Debug, Clone)]
+pub struct ProductTemplate {
+    pub product_id: Uuid,
+    /// Whether the requested variant belongs to the product; true without one
+    pub variant_found: bool,
+    /// Best downloaded mockup template or base image, if any
+    pub asset: Option<ProductTemplateAsset>,
+    pub print_area: Option<ProductPrintArea>,
+}
+
+/// A mirrored asset image stored in R2
+#[derive(Debug, Clone)]
+pub struct ProductTemplateAsset {
+    pub source_url: String,
+    pub r2_key: String,
+    /// Hex SHA-256 of the stored image, when recorded
+    pub checksum: Option<String>,
+}
+
+/// Print area size in template pixels, and its position when the provider gave one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProductPrintArea {
+    pub width_px: i32,
+    pub height_px: i32,
+    pub offset: Option<(i32, i32)>,
+}
+
+/// Repository for synced catalog products
+pub struct CatalogRepository {
+    pub pool: DbPool,
+}
+
+impl CatalogRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store a product fetched from a provider, with its variants and print areas
+    pub async fn store_product(
+        &self,
+        provider_code: &str,
+        product: &UnifiedProduct,
+    ) -> Result<StoredProduct, DbError> {
+        self.store_product_with_assets(provider_code, product, &[])
+            .await
+    }
+
+    /// Store a product with its variants, print areas, and pending asset rows
+    ///
+    /// Products whose `sync_hash` matches the stored one are only marked as
+    /// synced, though their assets are still queued. Everything is written in
+    /// one transaction, so a failure part way leaves no trace of the product;
+    /// the assets are downloaded afterwards, outside it.
+    pub async fn store_product_with_assets(
+        &self,
+        provider_code: &str,
+        product: &UnifiedProduct,
+        assets: &[MockupAsset],
+    ) -> Result<StoredProduct, DbError> {
+        let provider_code = provider_code.to_string();
+        let product = product.clone();
+        let assets = assets.to_vec();
+        self.pool
+            .transaction(move |tx| {
+                Box::pin(async move {
+                    let stored = Self::write_product(tx, &provider_code, &product).await?;
+                    for asset in &assets {
+                        tx.execute(
+                            PENDING_ASSET_UPSERT,
+                            &PendingAssetParams::new(&provider_code, &product.external_id, asset)
+                                .as_params(),
+                        )
+                        .await?;
+                    }
+                    Ok(stored)
+                })
+            })
+            .await
+    }
+
+    /// Upsert a product, variants, and print areas unless `sync_hash` shows no change
+    async fn write_product(
+        tx: &Transaction<'_>,
+        provider_code: &str,
+        product: &UnifiedProduct,
+    ) -> Result<StoredProduct, DbError> {
+        let provider_id: Uuid = tx
+            .query_opt(
+                "SELECT id FROM pod_providers WHERE code = $1",
+                &[&provider_code],
+            )
+            .await?
+            .map(|row| row.get("id"))
+            .ok_or_else(|| DbError::Config(format!("Unknown provider: {}", provider_code)))?;
+
+        let hash = sync_hash(product);
+        let existing = tx
+            .query_opt(
+                r#"
+            SELECT id, sync_hash FROM pod_products
+            WHERE provider_id = $1 AND external_product_id = $2
+            FOR UPDATE
+            "#,
+                &[&provider_id, &product.external_id],
+            )
+            .await?;
+
+        if let Some(row) = existing {
+            if row.get::<_, Option<&str>>("sync_hash") == Some(hash.as_str()) {
+                let product_id: Uuid = row.get("id");
+                tx.execute(
+                    "UPDATE pod_products SET last_synced_at = NOW() WHERE id = $1",
+                    &[&product_id],
+                )
+                .await?;
+                return Ok(StoredProduct::Unchanged(product_id));
+            }
+        }
+
+        let stored = Self::upsert_product(tx, provider_id, product, &hash).await?;
+        Self::upsert_variants(tx, stored.id, &product.variants).await?;
+        Self::upsert_print_areas(tx, stored.id, &product.print_areas).await?;
+        tx.execute(SEARCH_VECTOR_UPDATE, &[&stored.id]).await?;
+
+        Ok(StoredProduct::Updated(stored.id))
+    }
+
+    /// Mark a product as synced if a sync would change nothing
+    ///
+    /// True when the stored product has `sync_hash` and none of its assets
+    /// still need mirroring; `last_synced_at` is refreshed in that case.
+    pub async fn skip_unchanged(
+        &self,
+        provider_code: &str,
+        external_id: &str,
+        sync_hash: &str,
+    ) -> Result<bool, DbError> {
+        let client = self.pool.get().await?;
+
+        let updated = client
+            .execute(
+                r#"
+            UPDATE pod_products p
+            SET last_synced_at = NOW()
+            FROM pod_providers pr
+            WHERE p.provider_id = pr.id AND pr.code = $1
+              AND p.external_product_id = $2 AND p.sync_hash = $3
+              AND NOT EXISTS (
+                  SELECT 1 FROM pod_mockup_assets a
+                  WHERE a.product_id = p.id AND a.status NOT IN ('downloaded', 'processed')
+              )
+            "#,
+                &[&provider_code, &external_id, &sync_hash],
+            )
+            .await?;
+
+        Ok(updated > 0)
+    }
+
+    /// Record that a catalog sync of a provider finished
+    pub async fn mark_provider_synced(&self, provider_code: &str) -> Result<(), DbError> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "UPDATE pod_providers SET last_sync_at = NOW(), updated_at = NOW() WHERE code = $1",
+                &[&provider_code],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Record the status of a product's asset, identified by its source URL
+    ///
+    /// `retry_count` counts failed attempts since the asset was last mirrored:
+    /// it grows while the asset keeps failing and starts over once it succeeds.
+    pub async fn record_asset(
+        &self,
+        provider_code: &str,
+        external_product_id: &str,
+        asset: &MockupAsset,
+        update: &AssetUpdate<'_>,
+    ) -> Result<(), DbError> {
+        let client = self.pool.get().await?;
+
+        match update {
+            AssetUpdate::Pending => {
+                client
+                    .execute(
+                        PENDING_ASSET_UPSERT,
+                        &PendingAssetParams::new(provider_code, external_product_id, asset)
+                            .as_params(),
+                    )
+                    .await?;
+            }
+            AssetUpdate::Downloading => {
+                client
+                    .execute(
+                        &format!(
+                            "UPDATE pod_mockup_assets a SET status = 'downloading', \
+                             updated_at = NOW() {}",
+                            ASSET_BY_SOURCE
+                        ),
+                        &[&provider_code, &external_product_id, &asset.source_url],
+                    )
+                    .await?;
+            }
+            AssetUpdate::Downloaded {
+                bucket,
+                r2_key,
+                file_size_bytes,
+                content_type,
+                checksum,
+                retries,
+                thumbnail_r2_key,
+            } => {
+                client
+                    .execute(
+                        &format!(
+                            r#"
+                UPDATE pod_mockup_assets a SET
+                    status = 'downloaded',
+                    r2_bucket = $4,
+                    r2_key = $5,
+                    file_size_bytes = COALESCE($6, a.file_size_bytes),
+                    content_type = COALESCE($7, a.content_type),
+                    checksum = COALESCE($8, a.checksum),
+                    thumbnail_r2_key = COALESCE($10, a.thumbnail_r2_key),
+                    retry_count = CASE WHEN a.error_message IS NOT NULL
+                        THEN COALESCE(a.retry_count, 0) + $9 ELSE $9 END,
+                    error_message = NULL,
+                    downloaded_at = CASE WHEN $6::BIGINT IS NOT NULL
+                        THEN NOW() ELSE COALESCE(a.downloaded_at, NOW()) END,
+                    updated_at = NOW()
+                {}
+                "#,
+                            ASSET_BY_SOURCE
+                        ),
+                        &[
+                            &provider_code,
+                            &external_product_id,
+                            &asset.source_url,
+                            bucket,
+                            r2_key,
+                            file_size_bytes,
+                            content_type,
+                            checksum,
+                            retries,
+                            thumbnail_r2_key,
+                        ],
+                    )
+                    .await?;
+            }
+            AssetUpdate::Failed { error, attempts } => {
+                client
+                    .execute(
+                        &format!(
+                            r#"
+                UPDATE pod_mockup_assets a SET
+                    status = 'failed',
+                    retry_count = CASE WHEN a.error_message IS NOT NULL
+                        THEN COALESCE(a.retry_count, 0) + $5 ELSE $5 END,
+                    error_message = $4,
+                    updated_at = NOW()
+                {}
+                "#,
+                            ASSET_BY_SOURCE
+                        ),
+                        &[
+                            &provider_code,
+                            &external_product_id,
+                            &asset.source_url,
+                            error,
+                            attempts,
+                        ],
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Failed assets of a provider with fewer than `retry_cap` failed attempts
+    ///
+    /// Each asset comes with its product's `external_product_id`.
+    pub async fn failed_assets(
+        &self,
+        provider_code: &str,
+        retry_cap: i32,
+    ) -> Result<Vec<(String, MockupAsset)>, DbError> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                r#"
+            SELECT p.external_product_id, a.asset_type, a.placement, a.source_url,
+                   a.width_px, a.height_px, v.external_variant_id
+            FROM pod_mockup_assets a
+            JOIN pod_products p ON p.id = a.product_id
+            JOIN pod_providers pr ON pr.id = p.provider_id
+            LEFT JOIN pod_product_variants v ON v.id = a.variant_id
+            WHERE pr.code = $1 AND a.status = 'failed' AND COALESCE(a.retry_count, 0) < $2
+            ORDER BY p.external_product_id, a.source_url
+            "#,
+                &[&provider_code, &retry_cap],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let asset_type = AssetType::parse(row.get("asset_type"))?;
+                let mut asset = MockupAsset::new(asset_type, row.get("source_url"));
+                asset.placement = row
+                    .get::<_, Option<String>>("placement")
+                    .map(|p| PrintPlacement::from_str(&p));
+                asset.width_px = row.get("width_px");
+                asset.height_px = row.get("height_px");
+                asset.variant_external_id = row.get("external_variant_id");
+                Some((row.get("external_product_id"), asset))
+            })
+            .collect())
+    }
+
+    /// Which of `keys` an asset, or its thumbnail, is stored under
+    ///
+    /// Rows are matched whatever their status or their product's
+    /// availability, so an asset being mirrored keeps its object, and so does
+    /// one of a product that comes back while its row still says downloaded.
+    pub async fn referenced_keys(&self, keys: &[String]) -> Result<HashSet<String>, DbError> {
+        if keys.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                r#"
+            SELECT r2_key AS key
+            FROM pod_mockup_assets
+            WHERE r2_key = ANY($1)
+            UNION
+            SELECT thumbnail_r2_key
+            FROM pod_mockup_assets
+            WHERE thumbnail_r2_key = ANY($1)
+            "#,
+                &[&keys],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| row.get("key")).collect())
+    }
+
+    /// Mockup image and print area to render `placement` of a catalog product
+    ///
+    /// Prefers an asset of the requested variant (or, without one, a
+    /// product-level asset), then one made for the placement, then mockup
+    /// templates over base images. `None` when the product doesn't exist.
+    pub async fn product_template(
+        &self,
+        product_id: Uuid,
+        variant_id: Option<Uuid>,
+        placement: &str,
+    ) -> Result<Option<ProductTemplate>, DbError> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_opt(
+                r#"
+            SELECT p.id,
+                   $2::UUID IS NULL OR EXISTS (
+                       SELECT 1 FROM pod_product_variants v WHERE v.id = $2 AND v.product_id = p.id
+                   ) AS variant_found,
+                   a.source_url, a.r2_key, a.checksum,
+                   pa.width_px, pa.height_px, pa.offset_x_px, pa.offset_y_px
+            FROM pod_products p
+            LEFT JOIN LATERAL (
+                SELECT a.source_url, a.r2_key, a.checksum
+                FROM pod_mockup_assets a
+                WHERE a.product_id = p.id
+                  AND a.asset_type IN ('mockup_template', 'base_image')
+                  AND a.status IN ('downloaded', 'processed')
+                  AND a.r2_key IS NOT NULL
+                  AND (a.placement IS NULL OR a.placement = $3)
+                  AND ($2::UUID IS NULL OR a.variant_id IS NULL OR a.variant_id = $2)
+                ORDER BY a.variant_id IS NOT DISTINCT FROM $2 DESC,
+                         a.placement IS NOT DISTINCT FROM $3 DESC,
+                         a.asset_type = 'mockup_template' DESC,
+                         a.created_at
+                LIMIT 1
+            ) a ON true
+            LEFT JOIN pod_print_areas pa ON pa.product_id = p.id AND pa.placement = $3
+            WHERE p.id = $1
+            "#,
+                &[&product_id, &variant_id, &placement],
+            )
+            .await?;
+
+        Ok(row.map(|row| {
+            let r2_key: Option<String> = row.get("r2_key");
+            let width_px: Option<i32> = row.get("width_px");
+            let height_px: Option<i32> = row.get("height_px");
+            let offset_x: Option<i32> = row.get("offset_x_px");
+            let offset_y: Option<i32> = row.get("offset_y_px");
+            ProductTemplate {
+                product_id: row.get("id"),
+                variant_found: row.get("variant_found"),
+                asset: r2_key.map(|r2_key| ProductTemplateAsset {
+                    source_url: row.get("source_url"),
+                    r2_key,
+                    checksum: row.get("checksum"),
+                }),
+                print_area: width_px.zip(height_px).map(|(width_px, height_px)| {
+                    ProductPrintArea {
+                        width_px,
+                        height_px,
+                        // Syncs store 0, 0 when the provider gives no position
+                        offset: offset_x.zip(offset_y).filter(|offset| *offset != (0, 0)),
+                    }
+                }),
+            }
+        }))
+    }
+
+    /// Products matching every word of `query`, best match first
+    ///
+    /// A product whose name is exactly the query ranks first, then products
+    /// by text search rank, where name matches outweigh brand and model,
+    /// then product type, then variant sizes and colors.
+    pub async fn search_products(
+        &self,
+        query: &SearchQuery,
+        limit: i64,
+    ) -> Result<Vec<ProductSearchHit>, DbError> {
+        let client = self.pool.get().await?;
+        let all_terms = query.all_terms();
+        let any_term = query.any_term();
+
+        let rows = client
+            .query(
+                r#"
+            SELECT p.id, pr.code AS provider_code, p.external_product_id, p.name, p.brand,
+                   p.model, p.product_type, c.slug AS category_slug, p.is_available,
+                   ts_rank_cd(p.search_vector, q.all_terms) AS rank,
+                   ts_headline('simple', concat_ws(' ', p.name, p.brand, p.model), q.any_term,
+                               'StartSel=<mark>, StopSel=</mark>, HighlightAll=true')
+                       AS highlight
+            FROM pod_products p
+            JOIN pod_providers pr ON pr.id = p.provider_id
+            LEFT JOIN product_categories c ON c.id = p.category_id
+            CROSS JOIN (
+                SELECT to_tsquery('simple', $1) AS all_terms,
+                       to_tsquery('simple', $2) AS any_term
+            ) q
+            WHERE p.search_vector @@ q.all_terms
+            ORDER BY lower(p.name) = lower($3) DESC, rank DESC, p.name, p.id
+            LIMIT $4
+            "#,
+                &[&all_terms, &any_term, &query.text, &limit],
+            )
+            .await?;
+
+        let ids: Vec<Uuid> = rows.iter().map(|row| row.get("id")).collect();
+        let mut matched: HashMap<Uuid, Vec<MatchedVariant>> = HashMap::new();
+        if !ids.is_empty() {
+            let variant_rows = client
+                .query(
+                    r#"
+                SELECT product_id, id, external_variant_id, size, color_name, color_hex
+                FROM (
+                    SELECT v.*, row_number() OVER (
+                        PARTITION BY v.product_id
+                        ORDER BY ts_rank(
+                            to_tsvector('simple', concat_ws(' ', v.size, v.color_name)),
+                            to_tsquery('simple', $2)
+                        ) DESC, v.external_variant_id
+                    ) AS variant_rank
+                    FROM pod_product_variants v
+                    WHERE v.product_id = ANY($1) AND v.is_available
+                      AND to_tsvector('simple', concat_ws(' ', v.size, v.color_name))
+                          @@ to_tsquery('simple', $2)
+                ) ranked
+                WHERE variant_rank <= $3
+                ORDER BY product_id, variant_rank
+                "#,
+                    &[&ids, &any_term, &MAX_MATCHED_VARIANTS],
+                )
+                .await?;
+            for row in &variant_rows {
+                matched
+                    .entry(row.get("product_id"))
+                    .or_default()
+                    .push(MatchedVariant {
+                        id: row.get("id"),
+                        external_variant_id: row.get("external_variant_id"),
+                        size: row.get("size"),
+                        color_name: row.get("color_name"),
+                        color_hex: row.get("color_hex"),
+                    });
+            }
+        }
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let id: Uuid = row.get("id");
+                ProductSearchHit {
+                    id,
+                    provider_code: row.get("provider_code"),
+                    external_product_id: row.get("external_product_id"),
+                    name: row.get("name"),
+                    brand: row.get("brand"),
+                    model: row.get("model"),
+                    product_type: row.get("product_type"),
+                    category_slug: row.get("category_slug"),
+                    is_available: row.get("is_available"),
+                    rank: row.get("rank"),
+                    highlight: row.get("highlight"),
+                    matched_variants: matched.remove(&id).unwrap_or_default(),
+                }
+            })
+            .collect())
+    }
+
+    /// Insert or update a product by `(provider_id, external_product_id)`
+    pub async fn upsert_product(
+        tx: &Transaction<'_>,
+        provider_id: Uuid,
+        product: &UnifiedProduct,
+        sync_hash: &str,
+    ) -> Result<DbPodProduct, DbError> {
+        let product_type = product.product_type.to_string();
+        let regions = serde_json::to_string(&product.regions).unwrap_or_else(|_| "[]".into());
+        let metadata = product.provider_metadata.to_string();
+
+        let row = tx
+            .query_one(
+                &format!(
+                    r#"
+            INSERT INTO pod_products (
+                provider_id, external_product_id, category_id, name, description, brand,
+                model, product_type, is_available, regions, base_price_cents, currency,
+                provider_metadata, last_synced_at, sync_hash
+            )
+            VALUES (
+                $1, $2, (SELECT id FROM product_categories WHERE slug = $3), $4, $5, $6,
+                $7, $8, $9, $10::TEXT::JSONB, $11, $12, $13::TEXT::JSONB, NOW(), $14
+            )
+            ON CONFLICT (provider_id, external_product_id) DO UPDATE SET
+                category_id = EXCLUDED.category_id,
+                name = EXCLUDED.name,
+                description = EXCLUDED.description,
+                brand = EXCLUDED.brand,
+                model = EXCLUDED.model,
+                product_type = EXCLUDED.product_type,
+                is_available = EXCLUDED.is_available,
+                regions = EXCLUDED.regions,
+                base_price_cents = EXCLUDED.base_price_cents,
+                currency = EXCLUDED.currency,
+                provider_metadata = EXCLUDED.provider_metadata,
+                last_synced_at = NOW(),
+                sync_hash = EXCLUDED.sync_hash,
+                updated_at = NOW()
+            RETURNING {}
+            "#,
+                    PRODUCT_COLUMNS
+                ),
+                &[
+                    &provider_id,
+                    &product.external_id,
+                    &product.category_slug,
+                    &product.name,
+                    &product.description,
+                    &product.brand,
+                    &product.model,
+                    &product_type,
+                    &product.is_available,
+                    &regions,
+                    &product.base_price_cents,
+                    &product.currency,
+                    &metadata,
+                    &sync_hash,
+                ],
+            )
+            .await?;
+
+        Ok(product_from_row(&row))
+    }
+
+    /// Insert or update a product's variants by `external_variant_id`
+    ///
+    /// Stored variants missing from `variants` are marked unavailable rather
+    /// than deleted, so assets and orders that reference them keep working.
+    pub async fn upsert_variants(
+        tx: &Transaction<'_>,
+        product_id: Uuid,
+        variants: &[UnifiedVariant],
+    ) -> Result<Vec<DbPodProductVariant>, DbError> {
+        let upsert = format!(
+            r#"
+            INSERT INTO pod_product_variants (
+                product_id, external_variant_id, sku, size, color_name, color_hex,
+                is_available, price_cents, in_stock, provider_metadata
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10::TEXT::JSONB)
+            ON CONFLICT (product_id, external_variant_id) DO UPDATE SET
+                sku = EXCLUDED.sku,
+                size = EXCLUDED.size,
+                color_name = EXCLUDED.color_name,
+                color_hex = EXCLUDED.color_hex,
+                is_available = EXCLUDED.is_available,
+                price_cents = EXCLUDED.price_cents,
+                in_stock = EXCLUDED.in_stock,
+                provider_metadata = EXCLUDED.provider_metadata,
+                updated_at = NOW()
+            RETURNING {}
+            "#,
+            VARIANT_COLUMNS
+        );
+
+        let mut stored = Vec::with_capacity(variants.len());
+        for variant in variants {
+            let metadata = variant.provider_metadata.to_string();
+            let row = tx
+                .query_one(
+                    &upsert,
+                    &[
+                        &product_id,
+                        &variant.external_id,
+                        &variant.sku,
+                        &variant.size,
+                        &variant.color_name,
+                        &variant.color_hex,
+                        &variant.is_available,
+                        &variant.price_cents,
+                        &variant.in_stock,
+                        &metadata,
+                    ],
+                )
+                .await?;
+            stored.push(variant_from_row(&row));
+        }
+
+        let current: Vec<&str> = variants.iter().map(|v| v.external_id.as_str()).collect();
+        tx.execute(
+            r#"
+            UPDATE pod_product_variants
+            SET is_available = false, updated_at = NOW()
+            WHERE product_id = $1 AND is_available
+              AND NOT (external_variant_id = ANY($2))
+            "#,
+            &[&product_id, &current],
+        )
+        .await?;
+
+        Ok(stored)
+    }
+
+    /// Insert or update a product's print areas by placement
+    pub async fn upsert_print_areas(
+        tx: &Transaction<'_>,
+        product_id: Uuid,
+        print_areas: &[UnifiedPrintArea],
+    ) -> Result<Vec<DbPodPrintArea>, DbError> {
+        let upsert = format!(
+            r#"
+            INSERT INTO pod_print_areas (
+                product_id, external_print_area_id, placement, name, width_px, height_px,
+                offset_x_px, offset_y_px, print_dpi, file_format, constraints
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11::TEXT::JSONB)
+            ON CONFLICT (product_id, placement) DO UPDATE SET
+                external_print_area_id = EXCLUDED.external_print_area_id,
+                name = EXCLUDED.name,
+                width_px = EXCLUDED.width_px,
+                height_px = EXCLUDED.height_px,
+                offset_x_px = EXCLUDED.offset_x_px,
+                offset_y_px = EXCLUDED.offset_y_px,
+                print_dpi = EXCLUDED.print_dpi,
+                file_format = EXCLUDED.file_format,
+                constraints = EXCLUDED.constraints
+            RETURNING {}
+            "#,
+            PRINT_AREA_COLUMNS
+        );
+
+        let mut stored = Vec::with_capacity(print_areas.len());
+        for area in print_areas {
+            let constraints =
+                serde_json::to_string(&area.constraints).unwrap_or_else(|_| "{}".into());
+            let row = tx
+                .query_one(
+                    &upsert,
+                    &[
+                        &product_id,
+                        &area.external_id,
+                        &area.placement.as_str(),
+                        &area.name,
+                        &area.width_px,
+                        &area.height_px,
+                        &area.offset_x_px,
+                        &area.offset_y_px,
+                        &area.print_dpi,
+                        &area.file_format,
+                        &constraints,
+                    ],
+                )
+                .await?;
+            stored.push(print_area_from_row(&row));
+        }
+
+        Ok(stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::catalog::ProductType;
+
+    /// Repository on the database in `TEST_DATABASE_URL`, migrations applied
+    ///
+    /// Tests that need it return early when the variable isn't set.
+    async fn test_repo() -> Option<CatalogRepository> {
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        Some(CatalogRepository::new(pool))
+    }
+
+    fn hoodie(external_id: &str) -> UnifiedProduct {
+        let mut product = UnifiedProduct::new(
+            external_id.to_string(),
+            "printful".to_string(),
+            "Unisex Hoodie".to_string(),
+            ProductType::Hoodie,
+        );
+        product.base_price_cents = Some(3500);
+        for (id, size) in [("v-s", "S"), ("v-m", "M")] {
+            let mut variant = UnifiedVariant::new(id.to_string());
+            variant.size = Some(size.to_string());
+            variant.color_name = Some("Black".to_string());
+            product.variants.push(variant);
+        }
+        product.print_areas.push(UnifiedPrintArea::new(
+            PrintPlacement::Front,
+            "Front Print".to_string(),
+            3600,
+            4800,
+        ));
+        product
+    }
+
+    async fn variants(repo: &CatalogRepository, product_id: Uuid) -> Vec<(String, bool, bool)> {
+        let client = repo.pool.get().await.unwrap();
+        client
+            .query(
+                r#"
+                SELECT external_variant_id, is_available, COALESCE(in_stock, true) AS in_stock
+                FROM pod_product_variants WHERE product_id = $1
+                ORDER BY external_variant_id
+                "#,
+                &[&product_id],
+            )
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect()
+    }
+
+    async fn delete_product(repo: &CatalogRepository, product_id: Uuid) {
+        let client = repo.pool.get().await.unwrap();
+        client
+            .execute("DELETE FROM pod_products WHERE id = $1", &[&product_id])
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_sync_hash_tracks_product_changes() {
+        let product = hoodie("hash");
+        assert_eq!(sync_hash(&product), sync_hash(&product.clone()));
+        assert_eq!(sync_hash(&product).len(), 64);
+
+        let mut repriced = product.clone();
+        repriced.variants[0].price_cents = Some(4000);
+        assert_ne!(sync_hash(&product), sync_hash(&repriced));
+    }
+
+    #[tokio::test]
+    async fn test_store_product_inserts_then_skips_unchanged() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let product = hoodie(&format!("test-{}", Uuid::new_v4()));
+
+        let stored = repo.store_product("printful", &product).await.unwrap();
+        assert!(matches!(stored, StoredProduct::Updated(_)));
+        let product_id = stored.product_id();
+
+        let client = repo.pool.get().await.unwrap();
+        let row = client
+            .query_one(
+                r#"
+                SELECT p.name, p.base_price_cents, p.sync_hash, c.slug,
+                       (SELECT COUNT(*) FROM pod_print_areas WHERE product_id = p.id) AS areas
+                FROM pod_products p
+                LEFT JOIN product_categories c ON p.category_id = c.id
+                WHERE p.id = $1
+                "#,
+                &[&product_id],
+            )
+            .await
+            .unwrap();
+        assert_eq!(row.get::<_, String>("name"), "Unisex Hoodie");
+        assert_eq!(row.get::<_, Option<i32>>("base_price_cents"), Some(3500));
+        assert_eq!(
+            row.get::<_, Option<String>>("sync_hash"),
+            Some(sync_hash(&product))
+        );
+        assert_eq!(
+            row.get::<_, Option<String>>("slug").as_deref(),
+            Some("hoodies")
+        );
+        assert_eq!(row.get::<_, i64>("areas"), 1);
+        assert_eq!(
+            variants(&repo, product_id).await,
+            [
+                ("v-m".to_string(), true, true),
+                ("v-s".to_string(), true, true)
+            ]
+        );
+
+        let again = repo.store_product("printful", &product).await.unwrap();
+        assert_eq!(again, StoredProduct::Unchanged(product_id));
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_store_product_updates_changed_product() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let mut product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let product_id = repo
+            .store_product("printful", &product)
+            .await
+            .unwrap()
+            .product_id();
+
+        product.name = "Heavyweight Hoodie".to_string();
+        product.variants[0].in_stock = false;
+        product.print_areas[0].width_px = 4000;
+        let stored = repo.store_product("printful", &product).await.unwrap();
+        assert_eq!(stored, StoredProduct::Updated(product_id));
+
+        let client = repo.pool.get().await.unwrap();
+        let row = client
+            .query_one(
+                r#"
+                SELECT p.name, a.width_px
+                FROM pod_products p JOIN pod_print_areas a ON a.product_id = p.id
+                WHERE p.id = $1
+                "#,
+                &[&product_id],
+            )
+            .await
+            .unwrap();
+        assert_eq!(row.get::<_, String>("name"), "Heavyweight Hoodie");
+        assert_eq!(row.get::<_, i32>("width_px"), 4000);
+        assert_eq!(
+            variants(&repo, product_id).await,
+            [
+                ("v-m".to_string(), true, true),
+                ("v-s".to_string(), true, false)
+            ]
+        );
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_variant_removed_upstream_marked_unavailable() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let mut product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let product_id = repo
+            .store_product("printful", &product)
+            .await
+            .unwrap()
+            .product_id();
+
+        product.variants.retain(|v| v.external_id != "v-m");
+        repo.store_product("printful", &product).await.unwrap();
+        assert_eq!(
+            variants(&repo, product_id).await,
+            [
+                ("v-m".to_string(), false, true),
+                ("v-s".to_string(), true, true)
+            ]
+        );
+
+        // Coming back upstream makes it available again
+        product.variants.push({
+            let mut variant = UnifiedVariant::new("v-m".to_string());
+            variant.size = Some("M".to_string());
+            variant
+        });
+        repo.store_product("printful", &product).await.unwrap();
+        assert!(variants(&repo, product_id)
+            .await
+            .iter()
+            .all(|(_, available, _)| *available));
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_product_skipped_once_assets_mirrored() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let hash = sync_hash(&product);
+        assert!(!repo
+            .skip_unchanged("printful", &product.external_id, &hash)
+            .await
+            .unwrap());
+
+        let product_id = repo
+            .store_product("printful", &product)
+            .await
+            .unwrap()
+            .product_id();
+        assert!(repo
+            .skip_unchanged("printful", &product.external_id, &hash)
+            .await
+            .unwrap());
+        assert!(!repo
+            .skip_unchanged("printful", &product.external_id, "stale")
+            .await
+            .unwrap());
+
+        let asset = MockupAsset::new(
+            AssetType::BaseImage,
+            "https://provider.example/front.png".to_string(),
+        );
+        let external_id = product.external_id.as_str();
+        repo.record_asset("printful", external_id, &asset, &AssetUpdate::Pending)
+            .await
+            .unwrap();
+        assert!(!repo
+            .skip_unchanged("printful", external_id, &hash)
+            .await
+            .unwrap());
+
+        let failed = AssetUpdate::Failed {
+            error: "HTTP error: 503".to_string(),
+            attempts: 3,
+        };
+        repo.record_asset("printful", external_id, &asset, &failed)
+            .await
+            .unwrap();
+        assert!(!repo
+            .skip_unchanged("printful", external_id, &hash)
+            .await
+            .unwrap());
+
+        let mirrored = AssetUpdate::Downloaded {
+            bucket: "pod-assets",
+            r2_key: "printful/front.png",
+            file_size_bytes: Some(2048),
+            content_type: Some("image/png"),
+            checksum: None,
+            retries: 0,
+            thumbnail_r2_key: None,
+        };
+        repo.record_asset("printful", external_id, &asset, &mirrored)
+            .await
+            .unwrap();
+        assert!(repo
+            .skip_unchanged("printful", external_id, &hash)
+            .await
+            .unwrap());
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_store_product_queues_assets_in_one_transaction() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let assets = [
+            MockupAsset::new(
+                AssetType::BaseImage,
+                "https://provider.example/front.png".to_string(),
+            ),
+            MockupAsset::new(
+                AssetType::MockupTemplate,
+                "https://provider.example/back.png".to_string(),
+            ),
+        ];
+        let product_id = repo
+            .store_product_with_assets("printful", &product, &assets)
+            .await
+            .unwrap()
+            .product_id();
+
+        let client = repo.pool.get().await.unwrap();
+        let statuses: Vec<String> = client
+            .query(
+                "SELECT status FROM pod_mockup_assets WHERE product_id = $1",
+                &[&product_id],
+            )
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(statuses, ["pending", "pending"]);
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_failure_after_product_upsert_leaves_no_product() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let front = MockupAsset::new(
+            AssetType::BaseImage,
+            "https://provider.example/front.png".to_string(),
+        );
+        // Too long for its column, so queueing it fails after the product,
+        // its variants, and the first asset are written
+        let mut oversized = MockupAsset::new(
+            AssetType::BaseImage,
+            "https://provider.example/back.png".to_string(),
+        );
+        oversized.placement = Some(PrintPlacement::Other("x".repeat(51)));
+
+        let result = repo
+            .store_product_with_assets("printful", &product, &[front, oversized])
+            .await;
+        assert!(result.is_err());
+
+        let client = repo.pool.get().await.unwrap();
+        let orphans: i64 = client
+            .query_one(
+                "SELECT COUNT(*) FROM pod_products WHERE external_product_id = $1",
+                &[&product.external_id],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(orphans, 0);
+    }
+
+    #[tokio::test]
+    async fn test_asset_retries_recorded() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let external_id = product.external_id.as_str();
+        let product_id = repo
+            .store_product("printful", &product)
+            .await
+            .unwrap()
+            .product_id();
+
+        let mut flaky = MockupAsset::new(
+            AssetType::MockupTemplate,
+            "https://provider.example/flaky.png".to_string(),
+        );
+        flaky.placement = Some(PrintPlacement::Front);
+        flaky.variant_external_id = Some("v-m".to_string());
+        let broken = MockupAsset::new(
+            AssetType::BaseImage,
+            "https://provider.example/broken.png".to_string(),
+        );
+        for asset in [&flaky, &broken] {
+            repo.record_asset("printful", external_id, asset, &AssetUpdate::Pending)
+                .await
+                .unwrap();
+            repo.record_asset("printful", external_id, asset, &AssetUpdate::Downloading)
+                .await
+                .unwrap();
+        }
+
+        // Failed twice, then downloaded
+        let checksum = "ab".repeat(32);
+        let downloaded = AssetUpdate::Downloaded {
+            bucket: "pod-assets",
+            r2_key: "printful/flaky.png",
+            file_size_bytes: Some(4),
+            content_type: Some("image/png"),
+            checksum: Some(&checksum),
+            retries: 2,
+            thumbnail_r2_key: Some("printful/products/19/thumbnails/thumb_flaky.webp"),
+        };
+        repo.record_asset("printful", external_id, &flaky, &downloaded)
+            .await
+            .unwrap();
+        let failed = AssetUpdate::Failed {
+            error: "HTTP error: 503".to_string(),
+            attempts: 3,
+        };
+        repo.record_asset("printful", external_id, &broken, &failed)
+            .await
+            .unwrap();
+
+        let client = repo.pool.get().await.unwrap();
+        let rows = client
+            .query(
+                r#"
+                SELECT status, retry_count, checksum, file_size_bytes, error_message,
+                       downloaded_at IS NOT NULL AS downloaded
+                FROM pod_mockup_assets WHERE product_id = $1 ORDER BY source_url
+                "#,
+                &[&product_id],
+            )
+            .await
+            .unwrap();
+        let summary: Vec<(
+            String,
+            i32,
+            Option<String>,
+            Option<i64>,
+            Option<String>,
+            bool,
+        )> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get(0),
+                    row.get(1),
+                    row.get(2),
+                    row.get(3),
+                    row.get(4),
+                    row.get(5),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "failed".to_string(),
+                    3,
+                    None,
+                    None,
+                    Some("HTTP error: 503".to_string()),
+                    false
+                ),
+                (
+                    "downloaded".to_string(),
+                    2,
+                    Some(checksum.clone()),
+                    Some(4),
+                    None,
+                    true
+                ),
+            ]
+        );
+
+        let thumbnail: Option<String> = client
+            .query_one(
+                "SELECT thumbnail_r2_key FROM pod_mockup_assets \
+                 WHERE product_id = $1 AND source_url = $2",
+                &[&product_id, &flaky.source_url],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(
+            thumbnail.as_deref(),
+            Some("printful/products/19/thumbnails/thumb_flaky.webp")
+        );
+
+        // Failed assets come back for retries until they reach the cap
+        let retryable = repo.failed_assets("printful", 10).await.unwrap();
+        let retryable: Vec<_> = retryable
+            .into_iter()
+            .filter(|(id, _)| id == external_id)
+            .collect();
+        assert_eq!(retryable.len(), 1);
+        assert_eq!(retryable[0].1.source_url, broken.source_url);
+        assert_eq!(retryable[0].1.asset_type, AssetType::BaseImage);
+        assert!(repo
+            .failed_assets("printful", 3)
+            .await
+            .unwrap()
+            .iter()
+            .all(|(id, _)| id != external_id));
+
+        // Further failures add to the count
+        repo.record_asset("printful", external_id, &broken, &AssetUpdate::Pending)
+            .await
+            .unwrap();
+        repo.record_asset("printful", external_id, &broken, &failed)
+            .await
+            .unwrap();
+        let retry_count: i32 = client
+            .query_one(
+                "SELECT retry_count FROM pod_mockup_assets \
+                 WHERE product_id = $1 AND source_url = $2",
+                &[&product_id, &broken.source_url],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(retry_count, 6);
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_referenced_keys_of_stored_assets() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let external_id = product.external_id.as_str();
+        let product_id = repo
+            .store_product("printful", &product)
+            .await
+            .unwrap()
+            .product_id();
+
+        let asset = MockupAsset::new(
+            AssetType::BaseImage,
+            "https://provider.example/hoodie.png".to_string(),
+        );
+        let r2_key = format!("printful/products/{}/base/hoodie.png", external_id);
+        let thumbnail = format!(
+            "printful/products/{}/thumbnails/thumb_hoodie.webp",
+            external_id
+        );
+        let downloaded = AssetUpdate::Downloaded {
+            bucket: "pod-assets",
+            r2_key: &r2_key,
+            file_size_bytes: Some(4),
+            content_type: Some("image/png"),
+            checksum: None,
+            retries: 0,
+            thumbnail_r2_key: Some(&thumbnail),
+        };
+        repo.record_asset("printful", external_id, &asset, &AssetUpdate::Pending)
+            .await
+            .unwrap();
+        repo.record_asset("printful", external_id, &asset, &downloaded)
+            .await
+            .unwrap();
+
+        let orphan = format!("printful/products/{}/base/old.png", external_id);
+        let keys = vec![r2_key.clone(), thumbnail.clone(), orphan];
+        assert_eq!(
+            repo.referenced_keys(&keys).await.unwrap(),
+            HashSet::from([r2_key.clone(), thumbnail.clone()])
+        );
+        assert!(repo.referenced_keys(&[]).await.unwrap().is_empty());
+
+        // A product the provider stops offering keeps its objects, so its
+        // downloaded assets are still there when it comes back
+        let mut withdrawn = product.clone();
+        withdrawn.is_available = false;
+        repo.store_product("printful", &withdrawn).await.unwrap();
+        assert_eq!(repo.referenced_keys(&keys).await.unwrap().len(), 2);
+
+        repo.store_product("printful", &product).await.unwrap();
+        let client = repo.pool.get().await.unwrap();
+        let row = client
+            .query_one(
+                "SELECT status, r2_key FROM pod_mockup_assets WHERE product_id = $1",
+                &[&product_id],
+            )
+            .await
+            .unwrap();
+        assert_eq!(row.get::<_, String>("status"), "downloaded");
+        assert_eq!(row.get::<_, Option<String>>("r2_key"), Some(r2_key.clone()));
+        assert_eq!(
+            repo.referenced_keys(&keys).await.unwrap(),
+            HashSet::from([r2_key, thumbnail])
+        );
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_product_template_prefers_variant_assets() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let external_id = product.external_id.as_str();
+        let product_id = repo
+            .store_product("printful", &product)
+            .await
+            .unwrap()
+            .product_id();
+
+        let none = repo
+            .product_template(product_id, None, "front")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(none.asset.is_none());
+        assert_eq!(
+            none.print_area,
+            Some(ProductPrintArea {
+                width_px: 3600,
+                height_px: 4800,
+                offset: None,
+            })
+        );
+
+        let mut generic = MockupAsset::new(
+            AssetType::BaseImage,
+            "https://provider.example/generic.png".to_string(),
+        );
+        generic.placement = Some(PrintPlacement::Front);
+        let mut medium = MockupAsset::new(
+            AssetType::MockupTemplate,
+            "https://provider.example/medium.png".to_string(),
+        );
+        medium.placement = Some(PrintPlacement::Front);
+        medium.variant_external_id = Some("v-m".to_string());
+        for (asset, key) in [
+            (&generic, "printful/generic.png"),
+            (&medium, "printful/m.png"),
+        ] {
+            let downloaded = AssetUpdate::Downloaded {
+                bucket: "pod-assets",
+                r2_key: key,
+                file_size_bytes: Some(4),
+                content_type: Some("image/png"),
+                checksum: None,
+                retries: 0,
+                thumbnail_r2_key: None,
+            };
+            for update in [AssetUpdate::Pending, downloaded] {
+                repo.record_asset("printful", external_id, asset, &update)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let client = repo.pool.get().await.unwrap();
+        let medium_id: Uuid = client
+            .query_one(
+                "SELECT id FROM pod_product_variants \
+                 WHERE product_id = $1 AND external_variant_id = 'v-m'",
+                &[&product_id],
+            )
+            .await
+            .unwrap()
+            .get(0);
+
+        let r2_key = |template: Option<ProductTemplate>| template.unwrap().asset.unwrap().r2_key;
+        let for_medium = repo.product_template(product_id, Some(medium_id), "front");
+        assert_eq!(r2_key(for_medium.await.unwrap()), "printful/m.png");
+        let for_product = repo.product_template(product_id, None, "front");
+        assert_eq!(r2_key(for_product.await.unwrap()), "printful/generic.png");
+
+        let unknown_variant = repo
+            .product_template(product_id, Some(Uuid::new_v4()), "front")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!unknown_variant.variant_found);
+        assert!(repo
+            .product_template(Uuid::new_v4(), None, "front")
+            .await
+            .unwrap()
+            .is_none());
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_unknown_provider_rejected() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let result = repo.store_product("no-such-provider", &hoodie("x")).await;
+        assert!(matches!(result, Err(DbError::Config(_))));
+    }
+
+    #[test]
+    fn test_search_query_terms() {
+        let query = SearchQuery::parse("  Bella+Canvas 3001 BLA ").unwrap();
+        assert_eq!(query.text, "Bella+Canvas 3001 BLA");
+        assert_eq!(query.all_terms(), "bella & canvas & 3001 & bla:*");
+        assert_eq!(query.any_term(), "bella | canvas | 3001 | bla:*");
+
+        // tsquery operators never reach Postgres
+        let query = SearchQuery::parse("tee's & !(hood:*)").unwrap();
+        assert_eq!(query.all_terms(), "tee & s & hood:*");
+
+        assert!(SearchQuery::parse("").is_none());
+        assert!(SearchQuery::parse(" & | ! ").is_none());
+    }
+
+    /// A word no other test product has, so searches only see this test's products
+    fn search_tag() -> String {
+        format!("tag{}", Uuid::new_v4().simple())
+    }
+
+    async fn search(repo: &CatalogRepository, text: &str) -> Vec<ProductSearchHit> {
+        repo.search_products(&SearchQuery::parse(text).unwrap(), 20)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_search_by_brand() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let tag = search_tag();
+        let mut product = hoodie(&format!("test-{}", Uuid::new_v4()));
+        product.brand = Some(format!("{} Apparel", tag));
+        let product_id = repo
+            .store_product("printful", &product)
+            .await
+            .unwrap()
+            .product_id();
+
+        let hits = search(&repo, &tag).await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, product_id);
+        assert_eq!(hits[0].provider_code, "printful");
+        assert_eq!(
+            hits[0].highlight,
+            format!("Unisex Hoodie <mark>{}</mark> Apparel", tag)
+        );
+        assert!(hits[0].matched_variants.is_empty());
+
+        // The last word matches as a prefix
+        let hits = search(&repo, &format!("apparel {}", &tag[..10])).await;
+        assert_eq!(hits.len(), 1);
+        assert!(search(&repo, &format!("{} mug", tag)).await.is_empty());
+
+        delete_product(&repo, product_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_search_by_size_and_color() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let tag = search_tag();
+        let mut black = hoodie(&format!("test-{}", Uuid::new_v4()));
+        let mut white = hoodie(&format!("test-{}", Uuid::new_v4()));
+        for (product, colors) in [
+            (&mut black, ["White", "Black"]),
+            (&mut white, ["White", "White"]),
+        ] {
+            product.brand = Some(tag.clone());
+            product.variants.clear();
+            for (color, size) in colors.iter().zip(["S", "XL"]) {
+                let mut variant = UnifiedVariant::new(format!("v-{}-{}", color, size));
+                variant.size = Some(size.to_string());
+                variant.color_name = Some(color.to_string());
+                product.variants.push(variant);
+            }
+        }
+        let black_id = repo
+            .store_product("printful", &black)
+            .await
+            .unwrap()
+            .product_id();
+        let white_id = repo
+            .store_product("printful", &white)
+            .await
+            .unwrap()
+            .product_id();
+
+        let hits = search(&repo, &format!("{} black xl", tag)).await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, black_id);
+        let best = &hits[0].matched_variants[0];
+        assert_eq!(
+            (best.color_name.as_deref(), best.size.as_deref()),
+            (Some("Black"), Some("XL"))
+        );
+
+        // Variants removed upstream are no longer searchable
+        black
+            .variants
+            .retain(|v| v.color_name.as_deref() != Some("Black"));
+        repo.store_product("printful", &black).await.unwrap();
+        assert!(search(&repo, &format!("{} black", tag)).await.is_empty());
+
+        delete_product(&repo, black_id).await;
+        delete_product(&repo, white_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_search_ranks_exact_name_first() {
+        let Some(repo) = test_repo().await else {
+            return;
+        };
+        let tag = search_tag();
+        let mut ids = Vec::new();
+        for name in [
+            format!("Classic {} Tee Long Sleeve Classic Tee", tag),
+            format!("Classic {} Tee", tag),
+        ] {
+            let mut product = hoodie(&format!("test-{}", Uuid::new_v4()));
+            product.name = name;
+            ids.push(
+                repo.store_product("printful", &product)
+                    .await
+                    .unwrap()
+                    .product_id(),
+            );
+        }
+
+        let hits = search(&repo, &format!("classic {} tee", tag)).await;
+        assert_eq!(
+            hits.iter().map(|hit| hit.id).collect::<Vec<_>>(),
+            vec![ids[1], ids[0]]
+        );
+
+        for id in ids {
+            delete_product(&repo, id).await;
+        }
+    }
+}
@@ -1,17 +1,38 @@
 //! Database module for PostgreSQL connectivity
 //!
 //! Provides connection pool management, template queries, API key management,
-//! and usage tracking for the r_image_magic database.
+//! paginated usage tracking, stored resource counts, webhooks, provider parity results,
+//! POD providers, and synced POD catalog products for the r_image_magic database.
 
 pub mod api_keys;
+pub mod catalog;
 pub mod models;
+pub mod page;
+pub mod parity;
 pub mod pool;
+pub mod providers;
 pub mod queries;
+pub mod resources;
 pub mod usage;
+pub mod webhooks;
 
 pub use api_keys::{
-    ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest, CreateApiKeyResponse, DbApiKey,
+    ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest, CreateApiKeyResponse, DbApiKey, KeyEvent,
+    KeyEventType, UpdateApiKeyRequest,
 };
-pub use pool::DbPool;
+pub use catalog::{
+    AssetUpdate, CatalogRepository, MatchedVariant, ProductPrintArea, ProductSearchHit,
+    ProductTemplate, ProductTemplateAsset, SearchQuery, StoredProduct,
+};
+pub use page::{Page, PageRequest, MAX_PER_PAGE};
+pub use parity::{NewParityResult, ParityRepository, ParityResult};
+pub use pool::{DbPool, PoolOptions, PoolStatus};
+pub use providers::{NewProvider, ProviderRepository, UpdateProvider};
 pub use queries::TemplateRepository;
-pub use usage::{MonthlyUsageSummary, RateLimitStatus, UsageLogEntry, UsageRepository, UsageStats};
+pub use resources::{ResourceKind, ResourceRepository, ResourceUsage};
+pub use usage::{
+    parse_year_month, CategoryUsage, DailyUsage, MonthlyUsageSummary, QuotaCategory,
+    RateLimitStatus, StatusClass, TemplateUsage, UsageLog, UsageLogEntry, UsageLogFilter,
+    UsageRepository, UsageStats, MAX_DAILY_USAGE_DAYS,
+};
+pub use webhooks::{DbWebhookEvent, DbWebhookSubscription, WebhookDelivery, WebhookRepository};
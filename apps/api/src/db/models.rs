@@ -29,6 +29,26 @@ pub struct DbTemplate {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Template row written when a template is uploaded
+#[derive(Debug, Clone)]
+pub struct NewTemplate {
+    pub template_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub product_type: String,
+    pub variant: Option<String>,
+    pub color: Option<String>,
+    pub print_area_x: f64,
+    pub print_area_y: f64,
+    pub print_area_width: f64,
+    pub print_area_height: f64,
+    pub base_image_path: String,
+    pub displacement_map_path: Option<String>,
+    pub mask_path: Option<String>,
+    pub width: i32,
+    pub height: i32,
+}
+
 /// Simplified template info for API responses
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TemplateInfo {
@@ -0,0 +1,88 @@
+//! Limit/offset pagination for repository listings
+
+/// Most rows a listing returns per page
+pub const MAX_PER_PAGE: u32 = 100;
+
+/// Which page of a listing to fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRequest {
+    /// 1-based page number
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl PageRequest {
+    /// Page `page` of `per_page` rows
+    ///
+    /// Page 0 is read as page 1, and `per_page` is clamped to `1..=MAX_PER_PAGE`.
+    pub fn new(page: u32, per_page: u32) -> Self {
+        Self {
+            page: page.max(1),
+            per_page: per_page.clamp(1, MAX_PER_PAGE),
+        }
+    }
+
+    pub fn limit(&self) -> i64 {
+        i64::from(self.per_page)
+    }
+
+    pub fn offset(&self) -> i64 {
+        i64::from(self.page - 1) * i64::from(self.per_page)
+    }
+}
+
+/// One page of a listing, with the number of rows across all pages
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, total: i64, request: PageRequest) -> Self {
+        Self {
+            items,
+            total,
+            page: request.page,
+            per_page: request.per_page,
+        }
+    }
+
+    pub fn total_pages(&self) -> u32 {
+        let per_page = i64::from(self.per_page.max(1));
+        u32::try_from((self.total.max(0) + per_page - 1) / per_page).unwrap_or(u32::MAX)
+    }
+
+    /// Convert the page's rows, keeping its position
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            per_page: self.per_page,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_request_is_clamped() {
+        assert_eq!(PageRequest::new(0, 0), PageRequest::new(1, 1));
+        let capped = PageRequest::new(3, 500);
+        assert_eq!(capped.per_page, MAX_PER_PAGE);
+        assert_eq!((capped.limit(), capped.offset()), (100, 200));
+    }
+
+    #[test]
+    fn test_total_pages() {
+        let page = |total| Page::<()>::new(Vec::new(), total, PageRequest::new(1, 50));
+        assert_eq!(page(0).total_pages(), 0);
+        assert_eq!(page(50).total_pages(), 1);
+        assert_eq!(page(120).total_pages(), 3);
+    }
+}
@@ -0,0 +1,161 @@
+//! Provider parity result database operations
+
+use super::pool::{DbError, DbPool};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+/// One product checked in a parity run
+#[derive(Debug, Clone, Serialize)]
+pub struct ParityResult {
+    pub id: Uuid,
+    pub run_id: Uuid,
+    pub provider_code: String,
+    pub product_external_id: String,
+    pub variant_external_id: Option<String>,
+    pub placement: String,
+    pub provider_mockup_url: Option<String>,
+    pub ssim: Option<f64>,
+    pub delta_e: Option<f64>,
+    /// 0-100, `None` when the comparison failed
+    pub score: Option<f64>,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl ParityResult {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            id: row.get("id"),
+            run_id: row.get("run_id"),
+            provider_code: row.get("provider_code"),
+            product_external_id: row.get("product_external_id"),
+            variant_external_id: row.get("variant_external_id"),
+            placement: row.get("placement"),
+            provider_mockup_url: row.get("provider_mockup_url"),
+            ssim: row.get("ssim"),
+            delta_e: row.get("delta_e"),
+            score: row.get("score"),
+            error: row.get("error"),
+            checked_at: row.get("checked_at"),
+        }
+    }
+}
+
+/// Outcome of checking one product, ready to be stored
+#[derive(Debug, Clone, Default)]
+pub struct NewParityResult {
+    pub provider_code: String,
+    pub product_external_id: String,
+    pub variant_external_id: Option<String>,
+    pub placement: String,
+    pub provider_mockup_url: Option<String>,
+    pub ssim: Option<f64>,
+    pub delta_e: Option<f64>,
+    pub score: Option<f64>,
+    pub error: Option<String>,
+}
+
+const RESULT_COLUMNS: &str =
+    "id, run_id, provider_code, product_external_id, variant_external_id, \
+     placement, provider_mockup_url, ssim, delta_e, score, error, checked_at";
+
+/// Repository for parity results
+pub struct ParityRepository {
+    pub pool: DbPool,
+}
+
+impl ParityRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store the result for one product
+    pub async fn record(&self, run_id: Uuid, result: &NewParityResult) -> Result<(), DbError> {
+        let client = self.pool.get().await?;
+
+        client
+            .execute(
+                r#"
+            INSERT INTO parity_results (
+                run_id, provider_code, product_external_id, variant_external_id, placement,
+                provider_mockup_url, ssim, delta_e, score, error
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+                &[
+                    &run_id,
+                    &result.provider_code,
+                    &result.product_external_id,
+                    &result.variant_external_id,
+                    &result.placement,
+                    &result.provider_mockup_url,
+                    &result.ssim,
+                    &result.delta_e,
+                    &result.score,
+                    &result.error,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Latest result per product, variant and placement, lowest scores first
+    pub async fn latest(
+        &self,
+        provider_code: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<ParityResult>, DbError> {
+        let client = self.pool.get().await?;
+
+        let rows = client
+            .query(
+                &format!(
+                    r#"
+            SELECT * FROM (
+                SELECT DISTINCT ON (provider_code, product_external_id, variant_external_id, placement)
+                    {}
+                FROM parity_results
+                WHERE ($1::TEXT IS NULL OR provider_code = $1)
+                ORDER BY provider_code, product_external_id, variant_external_id, placement,
+                         checked_at DESC
+            ) latest
+            ORDER BY score ASC NULLS FIRST, checked_at DESC
+            LIMIT $2
+            "#,
+                    RESULT_COLUMNS
+                ),
+                &[&provider_code, &limit],
+            )
+            .await?;
+
+        Ok(rows.iter().map(ParityResult::from_row).collect())
+    }
+
+    /// Random sample of available catalog products for a provider
+    pub async fn sample_products(
+        &self,
+        provider_code: &str,
+        count: i64,
+    ) -> Result<Vec<String>, DbError> {
+        let client = self.pool.get().await?;
+
+        let rows = client
+            .query(
+                r#"
+            SELECT p.external_product_id
+            FROM pod_products p
+            JOIN pod_providers pr ON p.provider_id = pr.id
+            WHERE pr.code = $1 AND p.is_available = true
+            ORDER BY random()
+            LIMIT $2
+            "#,
+                &[&provider_code, &count],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+}
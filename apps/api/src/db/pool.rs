@@ -1,9 +1,17 @@
 //! Database connection pool management
 
-use deadpool_postgres::{Config, ManagerConfig, Pool, RecyclingMethod, Runtime};
+use deadpool_postgres::{
+    Config, ManagerConfig, Pool, PoolConfig, PoolError, RecyclingMethod, Runtime, TimeoutType,
+    Timeouts,
+};
+use futures::future::BoxFuture;
 use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::time::Duration;
 use thiserror::Error;
+use tokio_postgres::Transaction;
 use tracing::info;
+use utoipa::ToSchema;
 
 /// Database-related errors
 #[derive(Debug, Error)]
@@ -16,12 +24,69 @@ pub enum DbError {
     PoolGet(#[from] deadpool_postgres::PoolError),
     #[error("Configuration error: {0}")]
     Config(String),
+    #[error("Database busy: no connection free after {}ms", .0.as_millis())]
+    Busy(Duration),
+}
+
+impl DbError {
+    /// Whether every connection stayed checked out for the whole acquire timeout
+    pub fn is_busy(&self) -> bool {
+        matches!(self, DbError::Busy(_))
+    }
+}
+
+/// Pool sizing and timeouts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolOptions {
+    pub max_connections: usize,
+    /// Connections opened by `warm_up` and kept open while idle
+    pub min_idle: usize,
+    /// How long opening a connection may take
+    pub connect_timeout: Duration,
+    /// How long `get` waits for a free connection before failing with `DbError::Busy`
+    pub acquire_timeout: Duration,
+    /// Longest a statement may run before the server cancels it; the server's
+    /// default when `None`
+    pub statement_timeout: Option<Duration>,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        PoolOptions {
+            max_connections: 10,
+            min_idle: 0,
+            connect_timeout: Duration::from_secs(5),
+            acquire_timeout: Duration::from_secs(5),
+            statement_timeout: None,
+        }
+    }
+}
+
+impl PoolOptions {
+    /// Startup options sent with every new connection
+    fn server_options(&self) -> Option<String> {
+        self.statement_timeout
+            .map(|timeout| format!("-c statement_timeout={}", timeout.as_millis().max(1)))
+    }
+}
+
+/// Connections in the pool right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PoolStatus {
+    pub max_connections: usize,
+    /// Connections open, in use or idle
+    pub size: usize,
+    /// Idle connections ready to be handed out
+    pub available: usize,
+    /// Requests waiting for a connection
+    pub waiting: usize,
 }
 
 /// Database connection pool wrapper
 #[derive(Clone)]
 pub struct DbPool {
     pool: Pool,
+    acquire_timeout: Duration,
 }
 
 /// Build a rustls TLS connector for PostgreSQL
@@ -35,8 +100,13 @@ fn make_tls_connector() -> tokio_postgres_rustls::MakeRustlsConnect {
 }
 
 impl DbPool {
-    /// Create a new database pool from a connection string
+    /// Create a new database pool from a connection string, with default options
     pub fn new(database_url: &str) -> Result<Self, DbError> {
+        Self::with_options(database_url, &PoolOptions::default())
+    }
+
+    /// Create a new database pool from a connection string
+    pub fn with_options(database_url: &str, options: &PoolOptions) -> Result<Self, DbError> {
         // Parse the connection URL
         let url = url::Url::parse(database_url)
             .map_err(|e| DbError::Config(format!("Invalid database URL: {}", e)))?;
@@ -65,6 +135,17 @@ impl DbPool {
         cfg.user = Some(user.to_string());
         cfg.password = Some(password.to_string());
         cfg.dbname = Some(dbname.to_string());
+        cfg.connect_timeout = Some(options.connect_timeout);
+        cfg.options = options.server_options();
+        cfg.pool = Some(PoolConfig {
+            max_size: options.max_connections.max(1),
+            timeouts: Timeouts {
+                wait: Some(options.acquire_timeout),
+                create: Some(options.connect_timeout),
+                recycle: Some(options.connect_timeout),
+            },
+            ..PoolConfig::default()
+        });
 
         cfg.manager = Some(ManagerConfig {
             recycling_method: RecyclingMethod::Fast,
@@ -72,14 +153,29 @@ impl DbPool {
 
         let pool = if use_tls {
             let tls = make_tls_connector();
-            info!(host = %host, port = %port, dbname = %dbname, "Database pool created (TLS)");
+            info!(
+                host = %host,
+                port = %port,
+                dbname = %dbname,
+                max_connections = options.max_connections,
+                "Database pool created (TLS)"
+            );
             cfg.create_pool(Some(Runtime::Tokio1), tls)?
         } else {
-            info!(host = %host, port = %port, dbname = %dbname, "Database pool created (NoTLS)");
+            info!(
+                host = %host,
+                port = %port,
+                dbname = %dbname,
+                max_connections = options.max_connections,
+                "Database pool created (NoTLS)"
+            );
             cfg.create_pool(Some(Runtime::Tokio1), tokio_postgres::NoTls)?
         };
 
-        Ok(DbPool { pool })
+        Ok(DbPool {
+            pool,
+            acquire_timeout: options.acquire_timeout,
+        })
     }
 
     /// Get the underlying pool reference
@@ -88,8 +184,55 @@ impl DbPool {
     }
 
     /// Get a connection from the pool
+    ///
+    /// Fails with `DbError::Busy` when none is free within the acquire timeout.
     pub async fn get(&self) -> Result<deadpool_postgres::Object, DbError> {
-        Ok(self.pool.get().await?)
+        self.pool.get().await.map_err(|e| match e {
+            PoolError::Timeout(TimeoutType::Wait) => DbError::Busy(self.acquire_timeout),
+            e => DbError::PoolGet(e),
+        })
+    }
+
+    /// Run `f` in a transaction, committed when it returns `Ok`
+    ///
+    /// If `f` fails, nothing it wrote is kept: the transaction is rolled
+    /// back when it's dropped. `f` only borrows the transaction, so anything
+    /// else it needs is moved into it.
+    pub async fn transaction<T, F>(&self, f: F) -> Result<T, DbError>
+    where
+        F: for<'t> FnOnce(&'t Transaction<'t>) -> BoxFuture<'t, Result<T, DbError>>,
+    {
+        let mut client = self.get().await?;
+        let tx = client.transaction().await?;
+        let value = f(&tx).await?;
+        tx.commit().await?;
+        Ok(value)
+    }
+
+    /// Open connections until `min_idle` are idle in the pool
+    ///
+    /// The pool never closes idle connections, so they stay ready for the
+    /// first requests after startup. `min_idle` past the pool's size opens
+    /// every connection; asking for more would wait out the acquire timeout.
+    pub async fn warm_up(&self, min_idle: usize) -> Result<(), DbError> {
+        let status = self.pool.status();
+        let missing = min_idle
+            .min(status.max_size)
+            .saturating_sub(status.available);
+        let connections = futures::future::try_join_all((0..missing).map(|_| self.get())).await?;
+        drop(connections);
+        Ok(())
+    }
+
+    /// Connections open, idle, and waited for
+    pub fn status(&self) -> PoolStatus {
+        let status = self.pool.status();
+        PoolStatus {
+            max_connections: status.max_size,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+        }
     }
 
     /// Test the database connection
@@ -99,4 +242,76 @@ impl DbPool {
         info!("Database connection test successful");
         Ok(())
     }
+
+    /// Run a trivial query, without logging, for health checks
+    pub async fn ping(&self) -> Result<(), DbError> {
+        let client = self.get().await?;
+        client.query_one("SELECT 1", &[]).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statement_timeout_is_sent_as_startup_option() {
+        assert_eq!(PoolOptions::default().server_options(), None);
+        let options = PoolOptions {
+            statement_timeout: Some(Duration::from_secs(30)),
+            ..PoolOptions::default()
+        };
+        assert_eq!(
+            options.server_options().as_deref(),
+            Some("-c statement_timeout=30000")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_saturated_pool_times_out_as_busy() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::with_options(
+            &url,
+            &PoolOptions {
+                max_connections: 1,
+                acquire_timeout: Duration::from_millis(200),
+                statement_timeout: Some(Duration::from_secs(10)),
+                ..PoolOptions::default()
+            },
+        )
+        .expect("invalid TEST_DATABASE_URL");
+
+        let held = pool.get().await.unwrap();
+        let timeout: String = held
+            .query_one("SHOW statement_timeout", &[])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(timeout, "10s");
+        let status = pool.status();
+        assert_eq!(
+            (status.max_connections, status.size, status.available),
+            (1, 1, 0)
+        );
+
+        let started = std::time::Instant::now();
+        let error = pool
+            .get()
+            .await
+            .err()
+            .expect("second connection was handed out");
+        assert!(error.is_busy(), "{error}");
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        // Returned connections are handed out again
+        drop(held);
+        assert!(pool.get().await.is_ok());
+
+        // Warming up past the pool's size fills it instead of timing out
+        pool.warm_up(5).await.unwrap();
+        assert_eq!(pool.status().available, 1);
+    }
 }
@@ -0,0 +1,316 @@
+//! POD provider database operations
+//!
+//! Providers are rows of `pod_providers`, keyed by their code. Removing one
+//! only deactivates it, since synced products and jobs still reference it.
+
+use super::pool::{DbError, DbPool};
+use crate::domain::catalog::DbPodProvider;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
+use tracing::info;
+
+const PROVIDER_COLUMNS: &str = "id, code, name, api_base_url, auth_type, rate_limit_per_minute, \
+     is_active, sync_enabled, last_sync_at, sync_interval_hours, created_at, updated_at";
+
+fn provider_from_row(row: &Row) -> DbPodProvider {
+    DbPodProvider {
+        id: row.get("id"),
+        code: row.get("code"),
+        name: row.get("name"),
+        api_base_url: row.get("api_base_url"),
+        auth_type: row.get("auth_type"),
+        rate_limit_per_minute: row.get("rate_limit_per_minute"),
+        is_active: row.get("is_active"),
+        sync_enabled: row.get("sync_enabled"),
+        last_sync_at: row.get("last_sync_at"),
+        sync_interval_hours: row.get("sync_interval_hours"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// A provider to add to `pod_providers`
+#[derive(Debug, Clone)]
+pub struct NewProvider {
+    pub code: String,
+    pub name: String,
+    pub api_base_url: String,
+    /// oauth2, api_key, or recipe_id
+    pub auth_type: String,
+    pub rate_limit_per_minute: i32,
+    pub is_active: bool,
+    pub sync_enabled: bool,
+    pub sync_interval_hours: i32,
+}
+
+/// Changes to a provider; fields left as None are kept
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateProvider {
+    pub is_active: Option<bool>,
+    pub sync_enabled: Option<bool>,
+    pub sync_interval_hours: Option<i32>,
+    pub rate_limit_per_minute: Option<i32>,
+}
+
+impl UpdateProvider {
+    /// Whether the request changes nothing
+    pub fn is_empty(&self) -> bool {
+        *self == UpdateProvider::default()
+    }
+
+    /// SET clause for the changed columns, with values bound from `$2`
+    ///
+    /// `$1` is left for the provider's code.
+    fn set_clause(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
+        fn param<T: ToSql + Sync>(value: &Option<T>) -> Option<&(dyn ToSql + Sync)> {
+            value.as_ref().map(|v| v as &(dyn ToSql + Sync))
+        }
+        let changes = [
+            ("is_active", param(&self.is_active)),
+            ("sync_enabled", param(&self.sync_enabled)),
+            ("sync_interval_hours", param(&self.sync_interval_hours)),
+            ("rate_limit_per_minute", param(&self.rate_limit_per_minute)),
+        ];
+
+        let mut assignments = Vec::new();
+        let mut params = Vec::new();
+        for (column, value) in changes {
+            if let Some(value) = value {
+                params.push(value);
+                assignments.push(format!("{} = ${}", column, params.len() + 1));
+            }
+        }
+        assignments.push("updated_at = NOW()".to_string());
+        (assignments.join(", "), params)
+    }
+}
+
+/// Repository for POD providers
+pub struct ProviderRepository {
+    pool: DbPool,
+}
+
+impl ProviderRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Every provider, active or not, by name
+    pub async fn list(&self) -> Result<Vec<DbPodProvider>, DbError> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT {} FROM pod_providers ORDER BY name",
+                    PROVIDER_COLUMNS
+                ),
+                &[],
+            )
+            .await?;
+        Ok(rows.iter().map(provider_from_row).collect())
+    }
+
+    pub async fn get(&self, code: &str) -> Result<Option<DbPodProvider>, DbError> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_opt(
+                &format!(
+                    "SELECT {} FROM pod_providers WHERE code = $1",
+                    PROVIDER_COLUMNS
+                ),
+                &[&code],
+            )
+            .await?;
+        Ok(row.as_ref().map(provider_from_row))
+    }
+
+    /// Add a provider; `None` when one with its code already exists
+    pub async fn create(&self, provider: &NewProvider) -> Result<Option<DbPodProvider>, DbError> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_opt(
+                &format!(
+                    r#"
+            INSERT INTO pod_providers (
+                code, name, api_base_url, auth_type, rate_limit_per_minute,
+                is_active, sync_enabled, sync_interval_hours
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (code) DO NOTHING
+            RETURNING {}
+            "#,
+                    PROVIDER_COLUMNS
+                ),
+                &[
+                    &provider.code,
+                    &provider.name,
+                    &provider.api_base_url,
+                    &provider.auth_type,
+                    &provider.rate_limit_per_minute,
+                    &provider.is_active,
+                    &provider.sync_enabled,
+                    &provider.sync_interval_hours,
+                ],
+            )
+            .await?;
+
+        let created = row.as_ref().map(provider_from_row);
+        if let Some(created) = &created {
+            info!(
+                code = %created.code,
+                rate_limit = created.rate_limit_per_minute,
+                sync_enabled = created.sync_enabled,
+                "Added provider"
+            );
+        }
+        Ok(created)
+    }
+
+    /// Apply `update` to a provider; `None` when no provider has `code`
+    pub async fn update(
+        &self,
+        code: &str,
+        update: &UpdateProvider,
+    ) -> Result<Option<DbPodProvider>, DbError> {
+        if update.is_empty() {
+            return self.get(code).await;
+        }
+
+        let client = self.pool.get().await?;
+        let (set_clause, values) = update.set_clause();
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&code];
+        params.extend(values);
+
+        let sql = format!(
+            "UPDATE pod_providers SET {} WHERE code = $1 RETURNING {}",
+            set_clause, PROVIDER_COLUMNS
+        );
+        let updated = client
+            .query_opt(&sql, &params)
+            .await?
+            .as_ref()
+            .map(provider_from_row);
+
+        if let Some(updated) = &updated {
+            info!(
+                code = %updated.code,
+                is_active = updated.is_active,
+                sync_enabled = updated.sync_enabled,
+                rate_limit = updated.rate_limit_per_minute,
+                "Updated provider"
+            );
+        }
+        Ok(updated)
+    }
+
+    /// Deactivate a provider, keeping its row and synced products
+    pub async fn deactivate(&self, code: &str) -> Result<Option<DbPodProvider>, DbError> {
+        self.update(
+            code,
+            &UpdateProvider {
+                is_active: Some(false),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// The requests per minute stored for a provider, if it has a row
+    pub async fn rate_limit(&self, code: &str) -> Result<Option<u32>, DbError> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_opt(
+                "SELECT rate_limit_per_minute FROM pod_providers WHERE code = $1",
+                &[&code],
+            )
+            .await?;
+        Ok(row
+            .map(|row| row.get::<_, i32>("rate_limit_per_minute"))
+            .and_then(|limit| u32::try_from(limit).ok())
+            .filter(|limit| *limit > 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_update_set_clause() {
+        let update = UpdateProvider {
+            sync_enabled: Some(true),
+            rate_limit_per_minute: Some(60),
+            ..Default::default()
+        };
+        let (clause, params) = update.set_clause();
+        assert_eq!(
+            clause,
+            "sync_enabled = $2, rate_limit_per_minute = $3, updated_at = NOW()"
+        );
+        assert_eq!(params.len(), 2);
+        assert!(!update.is_empty());
+        assert!(UpdateProvider::default().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_provider_crud() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let repo = ProviderRepository::new(pool.clone());
+
+        let code = format!("test-{}", &Uuid::new_v4().simple().to_string()[..12]);
+        let provider = NewProvider {
+            code: code.clone(),
+            name: "Test Provider".to_string(),
+            api_base_url: "https://api.provider.example/v1".to_string(),
+            auth_type: "api_key".to_string(),
+            rate_limit_per_minute: 90,
+            is_active: true,
+            sync_enabled: false,
+            sync_interval_hours: 12,
+        };
+
+        let created = repo.create(&provider).await.unwrap().unwrap();
+        assert_eq!(created.code, code);
+        assert_eq!(created.sync_interval_hours, Some(12));
+        assert!(repo.create(&provider).await.unwrap().is_none());
+        assert_eq!(repo.rate_limit(&code).await.unwrap(), Some(90));
+        assert!(repo.list().await.unwrap().iter().any(|p| p.code == code));
+
+        // Only the given fields change
+        let updated = repo
+            .update(
+                &code,
+                &UpdateProvider {
+                    rate_limit_per_minute: Some(30),
+                    sync_enabled: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.rate_limit_per_minute, 30);
+        assert!(updated.sync_enabled && updated.is_active);
+        assert_eq!(updated.sync_interval_hours, Some(12));
+        assert_eq!(updated.name, "Test Provider");
+        assert_eq!(repo.rate_limit(&code).await.unwrap(), Some(30));
+
+        // Deleting keeps the row, inactive
+        let deactivated = repo.deactivate(&code).await.unwrap().unwrap();
+        assert!(!deactivated.is_active);
+        assert!(!repo.get(&code).await.unwrap().unwrap().is_active);
+
+        assert!(repo.deactivate("no-such-provider").await.unwrap().is_none());
+        assert_eq!(repo.rate_limit("no-such-provider").await.unwrap(), None);
+
+        let client = pool.get().await.unwrap();
+        client
+            .execute("DELETE FROM pod_providers WHERE code = $1", &[&code])
+            .await
+            .unwrap();
+    }
+}
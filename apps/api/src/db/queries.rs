@@ -1,6 +1,6 @@
 //! Database queries for templates
 
-use super::models::DbTemplate;
+use super::models::{DbTemplate, NewTemplate};
 use super::pool::{DbError, DbPool};
 use tracing::info;
 
@@ -187,4 +187,104 @@ impl TemplateRepository {
             })
             .collect())
     }
+
+    /// Update a template's print area, returning whether a row matched
+    pub async fn update_print_area(
+        &self,
+        template_id: &str,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<bool, DbError> {
+        let client = self.pool.get().await?;
+
+        let updated = client
+            .execute(
+                r#"
+            UPDATE templates
+            SET print_area_x = $2, print_area_y = $3,
+                print_area_width = $4, print_area_height = $5,
+                updated_at = NOW()
+            WHERE template_id = $1
+            "#,
+                &[&template_id, &x, &y, &width, &height],
+            )
+            .await?;
+
+        Ok(updated > 0)
+    }
+
+    /// Insert a template, or replace and reactivate the row with its template_id
+    pub async fn upsert(&self, template: &NewTemplate) -> Result<(), DbError> {
+        let client = self.pool.get().await?;
+
+        client
+            .execute(
+                r#"
+            INSERT INTO templates (
+                template_id, name, description, product_type, variant, color,
+                print_area_x, print_area_y, print_area_width, print_area_height,
+                base_image_path, displacement_map_path, mask_path,
+                width, height, is_active
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, true)
+            ON CONFLICT (template_id) DO UPDATE SET
+                name = EXCLUDED.name,
+                description = EXCLUDED.description,
+                product_type = EXCLUDED.product_type,
+                variant = EXCLUDED.variant,
+                color = EXCLUDED.color,
+                print_area_x = EXCLUDED.print_area_x,
+                print_area_y = EXCLUDED.print_area_y,
+                print_area_width = EXCLUDED.print_area_width,
+                print_area_height = EXCLUDED.print_area_height,
+                base_image_path = EXCLUDED.base_image_path,
+                displacement_map_path = EXCLUDED.displacement_map_path,
+                mask_path = EXCLUDED.mask_path,
+                width = EXCLUDED.width,
+                height = EXCLUDED.height,
+                is_active = true,
+                updated_at = NOW()
+            "#,
+                &[
+                    &template.template_id,
+                    &template.name,
+                    &template.description,
+                    &template.product_type,
+                    &template.variant,
+                    &template.color,
+                    &template.print_area_x,
+                    &template.print_area_y,
+                    &template.print_area_width,
+                    &template.print_area_height,
+                    &template.base_image_path,
+                    &template.displacement_map_path,
+                    &template.mask_path,
+                    &template.width,
+                    &template.height,
+                ],
+            )
+            .await?;
+
+        info!(template_id = %template.template_id, "Upserted template");
+        Ok(())
+    }
+
+    /// Hide a template from listings, returning whether an active row matched
+    pub async fn deactivate(&self, template_id: &str) -> Result<bool, DbError> {
+        let client = self.pool.get().await?;
+
+        let updated = client
+            .execute(
+                r#"
+            UPDATE templates
+            SET is_active = false, updated_at = NOW()
+            WHERE template_id = $1 AND is_active = true
+            "#,
+                &[&template_id],
+            )
+            .await?;
+
+        Ok(updated > 0)
+    }
 }
@@ -0,0 +1,186 @@
+//! Stored resource counts for per-tier resource limits
+//!
+//! Monthly quotas limit requests; these limits cap what a key keeps around,
+//! such as mockups stored in R2 and webhook subscriptions.
+
+use super::pool::{DbError, DbPool};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Kind of resource a key keeps stored, each with a per-tier limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    /// Mockups stored in R2 with `store_in_r2` or a batch `upload`
+    SavedRenders,
+    /// Webhook subscriptions
+    Webhooks,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 2] = [ResourceKind::SavedRenders, ResourceKind::Webhooks];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResourceKind::SavedRenders => "saved_renders",
+            ResourceKind::Webhooks => "webhooks",
+        }
+    }
+
+    /// Human-readable plural for error messages
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResourceKind::SavedRenders => "saved renders",
+            ResourceKind::Webhooks => "webhook subscriptions",
+        }
+    }
+}
+
+/// Stored resources of one kind against the tier's limit
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResourceUsage {
+    pub resource: ResourceKind,
+    pub limit: i64,
+    pub used: i64,
+    pub remaining: i64,
+    pub is_exceeded: bool,
+}
+
+impl ResourceUsage {
+    pub fn new(resource: ResourceKind, limit: i64, used: i64) -> Self {
+        Self {
+            resource,
+            limit,
+            used,
+            remaining: (limit - used).max(0),
+            is_exceeded: used >= limit,
+        }
+    }
+
+    /// Whether `count` more resources fit under the limit
+    pub fn allows(&self, count: i64) -> bool {
+        self.used + count <= self.limit
+    }
+}
+
+/// Repository for stored resource counts
+#[derive(Clone)]
+pub struct ResourceRepository {
+    pub pool: DbPool,
+}
+
+impl ResourceRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Number of resources of a kind the key currently has stored
+    pub async fn count(&self, api_key_id: Uuid, kind: ResourceKind) -> Result<i64, DbError> {
+        let client = self.pool.get().await?;
+        let sql = match kind {
+            ResourceKind::SavedRenders => {
+                "SELECT COUNT(*) FROM stored_renders WHERE api_key_id = $1"
+            }
+            ResourceKind::Webhooks => {
+                "SELECT COUNT(*) FROM webhook_subscriptions WHERE api_key_id = $1"
+            }
+        };
+        let row = client.query_one(sql, &[&api_key_id]).await?;
+        Ok(row.get(0))
+    }
+
+    /// Usage of one kind against a limit
+    pub async fn usage(
+        &self,
+        api_key_id: Uuid,
+        kind: ResourceKind,
+        limit: i64,
+    ) -> Result<ResourceUsage, DbError> {
+        let used = self.count(api_key_id, kind).await?;
+        Ok(ResourceUsage::new(kind, limit, used))
+    }
+
+    /// Usage of every kind, with limits from `limit_for`
+    pub async fn all_usage(
+        &self,
+        api_key_id: Uuid,
+        limit_for: impl Fn(ResourceKind) -> i64,
+    ) -> Result<Vec<ResourceUsage>, DbError> {
+        let mut usage = Vec::with_capacity(ResourceKind::ALL.len());
+        for kind in ResourceKind::ALL {
+            usage.push(self.usage(api_key_id, kind, limit_for(kind)).await?);
+        }
+        Ok(usage)
+    }
+
+    /// Record a mockup stored in R2 for a key
+    pub async fn record_render(&self, api_key_id: Uuid, r2_key: &str) -> Result<(), DbError> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                r#"
+                INSERT INTO stored_renders (api_key_id, r2_key)
+                VALUES ($1, $2)
+                ON CONFLICT (r2_key) DO NOTHING
+                "#,
+                &[&api_key_id, &r2_key],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Forget renders stored before `cutoff` under the dated `generated/` folders
+    ///
+    /// Called after `prune-generated` deletes the same mockups from R2. Batch
+    /// outputs are not pruned and keep counting.
+    pub async fn delete_renders_before(&self, cutoff: DateTime<Utc>) -> Result<u64, DbError> {
+        let client = self.pool.get().await?;
+        let deleted = client
+            .execute(
+                r#"
+                DELETE FROM stored_renders
+                WHERE created_at < $1 AND r2_key NOT LIKE 'generated/batch/%'
+                "#,
+                &[&cutoff],
+            )
+            .await?;
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ApiKeyTier;
+
+    #[test]
+    fn test_resource_usage_remaining() {
+        let usage = ResourceUsage::new(ResourceKind::Webhooks, 5, 3);
+        assert_eq!(usage.remaining, 2);
+        assert!(!usage.is_exceeded);
+        assert!(usage.allows(2));
+        assert!(!usage.allows(3));
+
+        let usage = ResourceUsage::new(ResourceKind::SavedRenders, 100, 100);
+        assert_eq!(usage.remaining, 0);
+        assert!(usage.is_exceeded);
+        assert!(!usage.allows(1));
+        assert!(usage.allows(0));
+    }
+
+    #[test]
+    fn test_resource_limits_grow_with_tier() {
+        let tiers = [
+            ApiKeyTier::Free,
+            ApiKeyTier::Starter,
+            ApiKeyTier::Pro,
+            ApiKeyTier::Enterprise,
+        ];
+        for kind in ResourceKind::ALL {
+            let limits: Vec<i64> = tiers.iter().map(|t| t.resource_limit(kind)).collect();
+            assert!(limits.windows(2).all(|w| w[0] < w[1]), "{:?}", kind);
+        }
+    }
+}
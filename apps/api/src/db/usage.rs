@@ -1,29 +1,213 @@
 //! Usage tracking and rate limiting database operations
 
+use super::page::{Page, PageRequest};
 use super::pool::{DbError, DbPool};
-use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone, Timelike, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::IpAddr;
+use tokio_postgres::types::ToSql;
 use tracing::info;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// Endpoint group with its own monthly budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaCategory {
+    /// Mockup generation and pattern tiling
+    Render,
+    /// Catalog and template reads
+    Catalog,
+    /// Provider sync jobs and R2 maintenance
+    Sync,
+    /// Everything else (keys, usage, webhooks, admin, ...)
+    Other,
+}
+
+impl QuotaCategory {
+    pub const ALL: [QuotaCategory; 4] = [
+        QuotaCategory::Render,
+        QuotaCategory::Catalog,
+        QuotaCategory::Sync,
+        QuotaCategory::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaCategory::Render => "render",
+            QuotaCategory::Catalog => "catalog",
+            QuotaCategory::Sync => "sync",
+            QuotaCategory::Other => "other",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "render" => QuotaCategory::Render,
+            "catalog" => QuotaCategory::Catalog,
+            "sync" => QuotaCategory::Sync,
+            _ => QuotaCategory::Other,
+        }
+    }
+
+    /// Category of a request path, by its endpoint group
+    pub fn for_path(path: &str) -> Self {
+        let group = path
+            .strip_prefix("/api/v1/")
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or("");
+        match group {
+            "mockups" | "tile" => QuotaCategory::Render,
+            "catalog" | "templates" => QuotaCategory::Catalog,
+            "sync" => QuotaCategory::Sync,
+            _ => QuotaCategory::Other,
+        }
+    }
+}
+
+/// Billing units used in one category this month against its budget
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CategoryUsage {
+    pub category: QuotaCategory,
+    pub quota: i32,
+    pub used: i32,
+    pub remaining: i32,
+    pub is_exceeded: bool,
+}
+
+impl CategoryUsage {
+    pub fn new(category: QuotaCategory, quota: i32, used: i32) -> Self {
+        Self {
+            category,
+            quota,
+            used,
+            remaining: (quota - used).max(0),
+            is_exceeded: used >= quota,
+        }
+    }
+}
+
 /// Usage log entry for recording API requests
 #[derive(Debug)]
 pub struct UsageLogEntry {
     pub api_key_id: Uuid,
     pub endpoint: String,
+    pub category: QuotaCategory,
     pub method: String,
     pub template_id: Option<String>,
+    /// Mockups rendered, None for requests that don't render
+    pub render_count: Option<i32>,
+    /// Billing units the request cost
+    pub billable_units: i32,
     pub status_code: i32,
     pub response_time_ms: Option<i32>,
     pub error_code: Option<String>,
     pub error_message: Option<String>,
     pub ip_address: Option<IpAddr>,
     pub user_agent: Option<String>,
+    /// ID the request was answered under, see `X-Request-Id`
+    pub request_id: Option<String>,
+}
+
+/// HTTP status class of logged requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusClass {
+    /// 2xx
+    Success,
+    /// 4xx
+    ClientError,
+    /// 5xx
+    ServerError,
+}
+
+impl StatusClass {
+    /// Parse `2xx`, `4xx` or `5xx`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "2xx" => Some(StatusClass::Success),
+            "4xx" => Some(StatusClass::ClientError),
+            "5xx" => Some(StatusClass::ServerError),
+            _ => None,
+        }
+    }
+
+    /// Lowest status code in the class
+    fn floor(&self) -> i32 {
+        match self {
+            StatusClass::Success => 200,
+            StatusClass::ClientError => 400,
+            StatusClass::ServerError => 500,
+        }
+    }
+}
+
+/// Which of a key's usage logs to list; unset fields match every row
+#[derive(Debug, Clone, Default)]
+pub struct UsageLogFilter {
+    pub status_class: Option<StatusClass>,
+    /// Logged endpoint starts with this, e.g. `/api/v1/mockups`
+    pub endpoint_prefix: Option<String>,
+    /// Logged at or after
+    pub since: Option<DateTime<Utc>>,
+    /// Logged before
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl UsageLogFilter {
+    /// WHERE clause for the filter, with parameters bound from `$2`
+    ///
+    /// `$1` is left for the key's ID.
+    fn where_clause(&self) -> (String, Vec<Box<dyn ToSql + Sync + Send>>) {
+        let mut conditions = vec!["api_key_id = $1".to_string()];
+        let mut params: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
+        if let Some(class) = self.status_class {
+            params.push(Box::new(class.floor()));
+            conditions.push(format!(
+                "status_code >= ${0} AND status_code < ${0} + 100",
+                params.len() + 1
+            ));
+        }
+        if let Some(prefix) = &self.endpoint_prefix {
+            // left() rather than LIKE, so `_` and `%` in the prefix match literally
+            params.push(Box::new(prefix.clone()));
+            conditions.push(format!(
+                "left(endpoint, length(${0})) = ${0}",
+                params.len() + 1
+            ));
+        }
+        if let Some(since) = self.since {
+            params.push(Box::new(since));
+            conditions.push(format!("created_at >= ${}", params.len() + 1));
+        }
+        if let Some(until) = self.until {
+            params.push(Box::new(until));
+            conditions.push(format!("created_at < ${}", params.len() + 1));
+        }
+        (format!("WHERE {}", conditions.join(" AND ")), params)
+    }
+}
+
+/// One logged request
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UsageLog {
+    pub id: Uuid,
+    pub endpoint: String,
+    pub method: String,
+    pub category: String,
+    pub template_id: Option<String>,
+    pub status_code: i32,
+    pub response_time_ms: Option<i32>,
+    pub error_code: Option<String>,
+    pub render_count: Option<i32>,
+    pub billable_units: i32,
+    /// `X-Request-Id` the request was answered under
+    pub request_id: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 /// Monthly usage summary
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MonthlyUsageSummary {
     pub year_month: String,
     pub total_requests: i32,
@@ -31,6 +215,8 @@ pub struct MonthlyUsageSummary {
     pub failed_requests: i32,
     pub billable_requests: i32,
     pub overage_requests: i32,
+    /// Billing units used; renders cost more than reads
+    pub billable_units: i32,
 }
 
 /// Usage statistics for an API key
@@ -41,6 +227,102 @@ pub struct UsageStats {
     pub quota: i32,
     pub quota_remaining: i32,
     pub quota_percentage_used: f64,
+    /// Usage against each category's budget
+    pub categories: Vec<CategoryUsage>,
+}
+
+/// Requests and mockups rendered for one template in a month
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TemplateUsage {
+    pub template_id: String,
+    pub requests: i64,
+    pub renders: i64,
+}
+
+/// Longest series `get_daily_usage` returns
+pub const MAX_DAILY_USAGE_DAYS: u32 = 90;
+
+/// Requests and response times for one UTC day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DailyUsage {
+    pub date: NaiveDate,
+    pub requests: i64,
+    pub successful_requests: i64,
+    pub failed_requests: i64,
+    /// `None` on days without timed requests
+    pub avg_response_time_ms: Option<f64>,
+    pub p95_response_time_ms: Option<f64>,
+}
+
+impl DailyUsage {
+    fn empty(date: NaiveDate) -> Self {
+        Self {
+            date,
+            requests: 0,
+            successful_requests: 0,
+            failed_requests: 0,
+            avg_response_time_ms: None,
+            p95_response_time_ms: None,
+        }
+    }
+}
+
+/// One entry per day from `start`, with zeroed days where `rows` has none
+fn fill_daily_usage(start: NaiveDate, days: u32, rows: Vec<DailyUsage>) -> Vec<DailyUsage> {
+    let mut rows: HashMap<NaiveDate, DailyUsage> =
+        rows.into_iter().map(|day| (day.date, day)).collect();
+    start
+        .iter_days()
+        .take(days as usize)
+        .map(|date| {
+            rows.remove(&date)
+                .unwrap_or_else(|| DailyUsage::empty(date))
+        })
+        .collect()
+}
+
+/// A key's stored monthly aggregate next to the one recomputed from raw logs
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRebuild {
+    pub api_key_id: Uuid,
+    /// `None` when the month had no aggregate row
+    pub stored: Option<MonthlyUsageSummary>,
+    pub rebuilt: MonthlyUsageSummary,
+    /// `rebuilt.total_requests - stored.total_requests`
+    pub total_delta: i32,
+}
+
+impl UsageRebuild {
+    pub fn changed(&self) -> bool {
+        self.stored.as_ref() != Some(&self.rebuilt)
+    }
+}
+
+/// Parse a `YYYY-MM` month
+pub fn parse_year_month(value: &str) -> Option<(i32, u32)> {
+    let (year, month) = value.split_once('-')?;
+    if year.len() != 4 || month.len() != 2 {
+        return None;
+    }
+    let year = year.parse().ok()?;
+    let month = month.parse().ok()?;
+    NaiveDate::from_ymd_opt(year, month, 1)?;
+    Some((year, month))
+}
+
+/// Start of the month and start of the following month, in UTC
+fn month_bounds(year: i32, month: u32) -> (DateTime<Utc>, DateTime<Utc>) {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    let start = |y: i32, m: u32| {
+        Utc.with_ymd_and_hms(y, m, 1, 0, 0, 0)
+            .single()
+            .expect("first of the month is a valid UTC time")
+    };
+    (start(year, month), start(next_year, next_month))
 }
 
 /// Rate limit check result
@@ -75,8 +357,10 @@ impl UsageRepository {
             INSERT INTO usage_logs (
                 api_key_id, endpoint, method, template_id,
                 status_code, response_time_ms, error_code, error_message,
-                ip_address, user_agent
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NULLIF($9, '')::inet, $10)
+                ip_address, user_agent, category, render_count, billable_units, request_id
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, NULLIF($9, '')::inet, $10, $11, $12, $13, $14
+            )
             "#,
                 &[
                     &entry.api_key_id,
@@ -89,23 +373,90 @@ impl UsageRepository {
                     &entry.error_message,
                     &ip_str.unwrap_or_default(),
                     &entry.user_agent,
+                    &entry.category.as_str(),
+                    &entry.render_count,
+                    &entry.billable_units,
+                    &entry.request_id,
                 ],
             )
             .await?;
 
         // Also update monthly aggregation
         let success = entry.status_code >= 200 && entry.status_code < 400;
-        self.increment_monthly_usage(entry.api_key_id, success)
+        self.increment_monthly_usage(entry.api_key_id, success, entry.billable_units)
+            .await?;
+        self.increment_category_usage(entry.api_key_id, entry.category, entry.billable_units)
             .await?;
 
         Ok(())
     }
 
-    /// Increment monthly usage counter
+    /// Increment the monthly counters for one quota category
+    async fn increment_category_usage(
+        &self,
+        api_key_id: Uuid,
+        category: QuotaCategory,
+        units: i32,
+    ) -> Result<(), DbError> {
+        let client = self.pool.get().await?;
+
+        let now = Utc::now();
+        let year_month = format!("{:04}-{:02}", now.year(), now.month());
+
+        client
+            .execute(
+                r#"
+            INSERT INTO monthly_category_usage (
+                api_key_id, year_month, category, total_requests, billable_units
+            ) VALUES ($1, $2, $3, 1, $4)
+            ON CONFLICT (api_key_id, year_month, category) DO UPDATE SET
+                total_requests = monthly_category_usage.total_requests + 1,
+                billable_units = monthly_category_usage.billable_units + $4,
+                updated_at = NOW()
+            "#,
+                &[&api_key_id, &year_month, &category.as_str(), &units],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Billing units per quota category this month; categories without requests are omitted
+    pub async fn get_current_category_usage(
+        &self,
+        api_key_id: Uuid,
+    ) -> Result<HashMap<QuotaCategory, i32>, DbError> {
+        let client = self.pool.get().await?;
+
+        let now = Utc::now();
+        let year_month = format!("{:04}-{:02}", now.year(), now.month());
+
+        let rows = client
+            .query(
+                r#"
+            SELECT category, billable_units
+            FROM monthly_category_usage
+            WHERE api_key_id = $1 AND year_month = $2
+            "#,
+                &[&api_key_id, &year_month],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|r| {
+                let category: String = r.get("category");
+                (QuotaCategory::from_str(&category), r.get("billable_units"))
+            })
+            .collect())
+    }
+
+    /// Increment monthly usage counters
     async fn increment_monthly_usage(
         &self,
         api_key_id: Uuid,
         success: bool,
+        units: i32,
     ) -> Result<(), DbError> {
         let client = self.pool.get().await?;
 
@@ -125,13 +476,13 @@ impl UsageRepository {
         client.execute(
             r#"
             INSERT INTO monthly_usage (
-                api_key_id, year_month, total_requests,
-                successful_requests, failed_requests, billable_requests, overage_requests
+                api_key_id, year_month, total_requests, successful_requests,
+                failed_requests, billable_requests, overage_requests, billable_units
             ) VALUES (
                 $1, $2, 1,
                 CASE WHEN $3 THEN 1 ELSE 0 END,
                 CASE WHEN $3 THEN 0 ELSE 1 END,
-                1, 0
+                1, 0, $5
             )
             ON CONFLICT (api_key_id, year_month) DO UPDATE SET
                 total_requests = monthly_usage.total_requests + 1,
@@ -139,9 +490,10 @@ impl UsageRepository {
                 failed_requests = monthly_usage.failed_requests + CASE WHEN $3 THEN 0 ELSE 1 END,
                 billable_requests = LEAST(monthly_usage.billable_requests + 1, $4),
                 overage_requests = GREATEST(monthly_usage.total_requests + 1 - $4, 0),
+                billable_units = monthly_usage.billable_units + $5,
                 updated_at = NOW()
             "#,
-            &[&api_key_id, &year_month, &success, &quota]
+            &[&api_key_id, &year_month, &success, &quota, &units]
         ).await?;
 
         Ok(())
@@ -161,7 +513,7 @@ impl UsageRepository {
             .query_opt(
                 r#"
             SELECT year_month, total_requests, successful_requests, failed_requests,
-                   billable_requests, overage_requests
+                   billable_requests, overage_requests, billable_units
             FROM monthly_usage
             WHERE api_key_id = $1 AND year_month = $2
             "#,
@@ -177,6 +529,7 @@ impl UsageRepository {
                 failed_requests: r.get("failed_requests"),
                 billable_requests: r.get("billable_requests"),
                 overage_requests: r.get("overage_requests"),
+                billable_units: r.get("billable_units"),
             })
             .unwrap_or(MonthlyUsageSummary {
                 year_month,
@@ -185,26 +538,41 @@ impl UsageRepository {
                 failed_requests: 0,
                 billable_requests: 0,
                 overage_requests: 0,
+                billable_units: 0,
             }))
     }
 
     /// Get usage statistics for an API key
+    ///
+    /// `quota` gives each category's monthly budget; the top-level quota
+    /// figures describe renders.
     pub async fn get_usage_stats(
         &self,
         api_key_id: Uuid,
-        quota: i32,
+        quota: impl Fn(QuotaCategory) -> i32,
     ) -> Result<UsageStats, DbError> {
         let current_month = self.get_current_month_usage(api_key_id).await?;
+        let used = self.get_current_category_usage(api_key_id).await?;
 
-        let quota_remaining = quota - current_month.total_requests;
-        let quota_percentage = (current_month.total_requests as f64 / quota as f64) * 100.0;
+        let categories: Vec<CategoryUsage> = QuotaCategory::ALL
+            .iter()
+            .map(|&category| {
+                let used = used.get(&category).copied().unwrap_or(0);
+                CategoryUsage::new(category, quota(category), used)
+            })
+            .collect();
+
+        let render_quota = quota(QuotaCategory::Render);
+        let renders = used.get(&QuotaCategory::Render).copied().unwrap_or(0);
+        let quota_percentage = (renders as f64 / render_quota as f64) * 100.0;
 
         Ok(UsageStats {
             api_key_id,
             current_month,
-            quota,
-            quota_remaining: quota_remaining.max(0),
+            quota: render_quota,
+            quota_remaining: (render_quota - renders).max(0),
             quota_percentage_used: quota_percentage.min(100.0),
+            categories,
         })
     }
 
@@ -220,7 +588,7 @@ impl UsageRepository {
             .query(
                 r#"
             SELECT year_month, total_requests, successful_requests, failed_requests,
-                   billable_requests, overage_requests
+                   billable_requests, overage_requests, billable_units
             FROM monthly_usage
             WHERE api_key_id = $1
             ORDER BY year_month DESC
@@ -239,71 +607,415 @@ impl UsageRepository {
                 failed_requests: r.get("failed_requests"),
                 billable_requests: r.get("billable_requests"),
                 overage_requests: r.get("overage_requests"),
+                billable_units: r.get("billable_units"),
             })
             .collect())
     }
 
-    /// Check rate limit using sliding window in database
+    /// Requests and renders per template for a key in a month, most rendered first
+    ///
+    /// Only requests whose handler recorded a single template are counted.
+    pub async fn get_template_usage(
+        &self,
+        api_key_id: Uuid,
+        year: i32,
+        month: u32,
+    ) -> Result<Vec<TemplateUsage>, DbError> {
+        let client = self.pool.get().await?;
+        let (start, end) = month_bounds(year, month);
+
+        let rows = client
+            .query(
+                r#"
+            SELECT template_id,
+                   COUNT(*) AS requests,
+                   COALESCE(SUM(render_count), 0)::BIGINT AS renders
+            FROM usage_logs
+            WHERE api_key_id = $1 AND created_at >= $2 AND created_at < $3
+              AND template_id IS NOT NULL
+            GROUP BY template_id
+            ORDER BY renders DESC, requests DESC, template_id
+            "#,
+                &[&api_key_id, &start, &end],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|r| TemplateUsage {
+                template_id: r.get("template_id"),
+                requests: r.get("requests"),
+                renders: r.get("renders"),
+            })
+            .collect())
+    }
+
+    /// Daily usage for a key over the last `days` UTC days, oldest first
+    ///
+    /// Every day is present, zeroed when the key made no requests. `days` is
+    /// clamped to `1..=MAX_DAILY_USAGE_DAYS`. The range scan is served by
+    /// `idx_usage_logs_api_key_created`.
+    pub async fn get_daily_usage(
+        &self,
+        api_key_id: Uuid,
+        days: u32,
+    ) -> Result<Vec<DailyUsage>, DbError> {
+        let client = self.pool.get().await?;
+        let days = days.clamp(1, MAX_DAILY_USAGE_DAYS);
+        let today = Utc::now().date_naive();
+        let start = today
+            .checked_sub_days(Days::new(u64::from(days - 1)))
+            .unwrap_or(today);
+        let since = start
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .and_utc();
+
+        let rows = client
+            .query(
+                r#"
+            SELECT (created_at AT TIME ZONE 'UTC')::date AS day,
+                   COUNT(*) AS requests,
+                   COUNT(*) FILTER (WHERE status_code >= 200 AND status_code < 400) AS successful,
+                   AVG(response_time_ms)::FLOAT8 AS avg_response_time_ms,
+                   PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY response_time_ms)
+                       AS p95_response_time_ms
+            FROM usage_logs
+            WHERE api_key_id = $1 AND created_at >= $2
+            GROUP BY day
+            "#,
+                &[&api_key_id, &since],
+            )
+            .await?;
+
+        let usage = rows
+            .iter()
+            .map(|r| {
+                let requests: i64 = r.get("requests");
+                let successful: i64 = r.get("successful");
+                DailyUsage {
+                    date: r.get("day"),
+                    requests,
+                    successful_requests: successful,
+                    failed_requests: requests - successful,
+                    avg_response_time_ms: r.get("avg_response_time_ms"),
+                    p95_response_time_ms: r.get("p95_response_time_ms"),
+                }
+            })
+            .collect();
+
+        Ok(fill_daily_usage(start, days, usage))
+    }
+
+    /// One page of a key's logged requests, newest first
+    ///
+    /// Rows logged in the same instant are ordered by ID, so every row lands
+    /// on exactly one page. Requests logged while paging push older rows
+    /// back a page; an `until` filter pins the listing to a fixed window.
+    pub async fn get_logs(
+        &self,
+        api_key_id: Uuid,
+        filter: &UsageLogFilter,
+        page: PageRequest,
+    ) -> Result<Page<UsageLog>, DbError> {
+        let client = self.pool.get().await?;
+        let (where_clause, values) = filter.where_clause();
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&api_key_id];
+        params.extend(values.iter().map(|v| v.as_ref() as &(dyn ToSql + Sync)));
+
+        let count_sql = format!("SELECT COUNT(*) AS total FROM usage_logs {}", where_clause);
+        let total: i64 = client.query_one(&count_sql, &params).await?.get("total");
+
+        let (limit, offset) = (page.limit(), page.offset());
+        let data_sql = format!(
+            "SELECT id, endpoint, method, category, template_id, status_code, \
+                    response_time_ms, error_code, render_count, billable_units, \
+                    request_id, created_at \
+             FROM usage_logs {} \
+             ORDER BY created_at DESC, id DESC \
+             LIMIT ${} OFFSET ${}",
+            where_clause,
+            params.len() + 1,
+            params.len() + 2
+        );
+        params.push(&limit);
+        params.push(&offset);
+        let rows = client.query(&data_sql, &params).await?;
+
+        let logs = rows
+            .iter()
+            .map(|r| UsageLog {
+                id: r.get("id"),
+                endpoint: r.get("endpoint"),
+                method: r.get("method"),
+                category: r.get("category"),
+                template_id: r.get("template_id"),
+                status_code: r.get("status_code"),
+                response_time_ms: r.get("response_time_ms"),
+                error_code: r.get("error_code"),
+                render_count: r.get("render_count"),
+                billable_units: r.get("billable_units"),
+                request_id: r.get("request_id"),
+                created_at: r.get("created_at"),
+            })
+            .collect();
+        Ok(Page::new(logs, total, page))
+    }
+
+    /// Take a slot in the key's rate limit window for this minute
+    ///
+    /// Denied requests don't count against the window.
     pub async fn check_rate_limit(
         &self,
         api_key_id: Uuid,
         limit: i32,
     ) -> Result<RateLimitStatus, DbError> {
-        let client = self.pool.get().await?;
-
         let now = Utc::now();
-        // Round down to minute boundary for sliding window
+        // Round down to the minute the window covers
         let window_start = now.with_nanosecond(0).unwrap().with_second(0).unwrap();
+        self.take_rate_limit_slot(api_key_id, limit, window_start)
+            .await
+    }
 
-        // Count requests in current window
-        let count_row = client
-            .query_one(
+    /// Count a request in `window_start`'s window unless it is full
+    ///
+    /// The check and the increment are one statement: the conflicting row is
+    /// locked before its count is compared to the limit, so concurrent
+    /// requests can't overshoot it.
+    async fn take_rate_limit_slot(
+        &self,
+        api_key_id: Uuid,
+        limit: i32,
+        window_start: DateTime<Utc>,
+    ) -> Result<RateLimitStatus, DbError> {
+        let client = self.pool.get().await?;
+
+        let row = client
+            .query_opt(
                 r#"
-            SELECT COALESCE(SUM(request_count), 0)::INTEGER as count
-            FROM rate_limit_windows
-            WHERE api_key_id = $1 AND window_start > NOW() - INTERVAL '1 minute'
+            INSERT INTO rate_limit_windows (api_key_id, window_start, request_count)
+            SELECT $1, $2, 1 WHERE $3 > 0
+            ON CONFLICT (api_key_id, window_start) DO UPDATE
+            SET request_count = rate_limit_windows.request_count + 1
+            WHERE rate_limit_windows.request_count < $3
+            RETURNING request_count
             "#,
-                &[&api_key_id],
+                &[&api_key_id, &window_start, &limit],
             )
             .await?;
 
-        let current_count: i32 = count_row.get("count");
-        let allowed = current_count < limit;
+        // No row means the window was already full
+        let (allowed, current_count) = match row {
+            Some(row) => (true, row.get("request_count")),
+            None => (false, limit.max(0)),
+        };
 
-        // If allowed, increment counter
-        if allowed {
-            client
-                .execute(
+        Ok(RateLimitStatus {
+            allowed,
+            current_count,
+            limit,
+            // Calculate reset time (next minute)
+            reset_at: window_start + chrono::Duration::minutes(1),
+        })
+    }
+
+    /// Check a category's monthly budget, returning the units used so far
+    /// and whether another request is allowed
+    pub async fn check_quota(
+        &self,
+        api_key_id: Uuid,
+        category: QuotaCategory,
+        quota: i32,
+    ) -> Result<(bool, i32), DbError> {
+        let used = self
+            .get_current_category_usage(api_key_id)
+            .await?
+            .get(&category)
+            .copied()
+            .unwrap_or(0);
+        Ok((used < quota, used))
+    }
+
+    /// Record that a quota threshold alert is being sent
+    ///
+    /// Returns false when the threshold was already alerted for the key,
+    /// category and month, so concurrent requests send it only once.
+    pub async fn claim_quota_alert(
+        &self,
+        api_key_id: Uuid,
+        year_month: &str,
+        category: QuotaCategory,
+        threshold: i32,
+    ) -> Result<bool, DbError> {
+        let client = self.pool.get().await?;
+        let inserted = client
+            .execute(
+                r#"
+            INSERT INTO quota_alerts (api_key_id, year_month, category, threshold)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT DO NOTHING
+            "#,
+                &[&api_key_id, &year_month, &category.as_str(), &threshold],
+            )
+            .await?;
+        Ok(inserted == 1)
+    }
+
+    /// Recompute a month's aggregates from `usage_logs`, one transaction per key
+    ///
+    /// Only keys with logs in the month are rebuilt, so months whose logs were
+    /// already removed by retention keep their aggregates. With `dry_run` the
+    /// recomputed values are returned but nothing is written.
+    pub async fn rebuild_monthly_usage(
+        &self,
+        year: i32,
+        month: u32,
+        dry_run: bool,
+    ) -> Result<Vec<UsageRebuild>, DbError> {
+        let mut client = self.pool.get().await?;
+        let year_month = format!("{:04}-{:02}", year, month);
+        let (start, end) = month_bounds(year, month);
+
+        let key_rows = client
+            .query(
+                r#"
+            SELECT DISTINCT api_key_id
+            FROM usage_logs
+            WHERE created_at >= $1 AND created_at < $2
+            "#,
+                &[&start, &end],
+            )
+            .await?;
+
+        let mut rebuilds = Vec::with_capacity(key_rows.len());
+        for key_row in key_rows {
+            let api_key_id: Uuid = key_row.get("api_key_id");
+            let tx = client.transaction().await?;
+
+            // Lock the aggregate so live increments wait for the rebuilt value
+            let stored = tx
+                .query_opt(
                     r#"
-                INSERT INTO rate_limit_windows (api_key_id, window_start, request_count)
-                VALUES ($1, $2, 1)
-                ON CONFLICT (api_key_id, window_start) DO UPDATE
-                SET request_count = rate_limit_windows.request_count + 1
+                SELECT year_month, total_requests, successful_requests, failed_requests,
+                       billable_requests, overage_requests, billable_units
+                FROM monthly_usage
+                WHERE api_key_id = $1 AND year_month = $2
+                FOR UPDATE
                 "#,
-                    &[&api_key_id, &window_start],
+                    &[&api_key_id, &year_month],
+                )
+                .await?
+                .map(|r| MonthlyUsageSummary {
+                    year_month: r.get("year_month"),
+                    total_requests: r.get("total_requests"),
+                    successful_requests: r.get("successful_requests"),
+                    failed_requests: r.get("failed_requests"),
+                    billable_requests: r.get("billable_requests"),
+                    overage_requests: r.get("overage_requests"),
+                    billable_units: r.get("billable_units"),
+                });
+
+            let counts = tx
+                .query_one(
+                    r#"
+                SELECT COUNT(*)::INTEGER AS total,
+                       COUNT(*) FILTER (WHERE status_code >= 200 AND status_code < 400)::INTEGER
+                           AS successful,
+                       COALESCE(SUM(billable_units), 0)::INTEGER AS units,
+                       (SELECT monthly_quota FROM api_keys WHERE id = $1) AS quota
+                FROM usage_logs
+                WHERE api_key_id = $1 AND created_at >= $2 AND created_at < $3
+                "#,
+                    &[&api_key_id, &start, &end],
                 )
                 .await?;
-        }
+            let total: i32 = counts.get("total");
+            let successful: i32 = counts.get("successful");
+            let quota: i32 = counts.get("quota");
+            let units: i32 = counts.get("units");
 
-        // Calculate reset time (next minute)
-        let reset_at = window_start + chrono::Duration::minutes(1);
+            let rebuilt = MonthlyUsageSummary {
+                year_month: year_month.clone(),
+                total_requests: total,
+                successful_requests: successful,
+                failed_requests: total - successful,
+                billable_requests: total.min(quota),
+                overage_requests: (total - quota).max(0),
+                billable_units: units,
+            };
+            let rebuild = UsageRebuild {
+                api_key_id,
+                total_delta: total - stored.as_ref().map_or(0, |s| s.total_requests),
+                stored,
+                rebuilt,
+            };
 
-        Ok(RateLimitStatus {
-            allowed,
-            current_count: if allowed {
-                current_count + 1
+            if dry_run || !rebuild.changed() {
+                tx.rollback().await?;
             } else {
-                current_count
-            },
-            limit,
-            reset_at,
-        })
-    }
+                let r = &rebuild.rebuilt;
+                tx.execute(
+                    r#"
+                INSERT INTO monthly_usage (
+                    api_key_id, year_month, total_requests, successful_requests,
+                    failed_requests, billable_requests, overage_requests, billable_units
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (api_key_id, year_month) DO UPDATE SET
+                    total_requests = EXCLUDED.total_requests,
+                    successful_requests = EXCLUDED.successful_requests,
+                    failed_requests = EXCLUDED.failed_requests,
+                    billable_requests = EXCLUDED.billable_requests,
+                    overage_requests = EXCLUDED.overage_requests,
+                    billable_units = EXCLUDED.billable_units,
+                    updated_at = NOW()
+                "#,
+                    &[
+                        &api_key_id,
+                        &year_month,
+                        &r.total_requests,
+                        &r.successful_requests,
+                        &r.failed_requests,
+                        &r.billable_requests,
+                        &r.overage_requests,
+                        &r.billable_units,
+                    ],
+                )
+                .await?;
+
+                // Category counters follow the same logs
+                tx.execute(
+                    "DELETE FROM monthly_category_usage WHERE api_key_id = $1 AND year_month = $2",
+                    &[&api_key_id, &year_month],
+                )
+                .await?;
+                tx.execute(
+                    r#"
+                INSERT INTO monthly_category_usage (
+                    api_key_id, year_month, category, total_requests, billable_units
+                )
+                SELECT $1, $2, category, COUNT(*)::INTEGER, SUM(billable_units)::INTEGER
+                FROM usage_logs
+                WHERE api_key_id = $1 AND created_at >= $3 AND created_at < $4
+                GROUP BY category
+                "#,
+                    &[&api_key_id, &year_month, &start, &end],
+                )
+                .await?;
+                tx.commit().await?;
+            }
 
-    /// Check if API key has exceeded monthly quota
-    pub async fn check_quota(&self, api_key_id: Uuid, quota: i32) -> Result<bool, DbError> {
-        let current = self.get_current_month_usage(api_key_id).await?;
-        Ok(current.total_requests < quota)
+            rebuilds.push(rebuild);
+        }
+
+        info!(
+            year_month = %year_month,
+            keys = rebuilds.len(),
+            changed = rebuilds.iter().filter(|r| r.changed()).count(),
+            dry_run,
+            "Rebuilt monthly usage aggregates"
+        );
+
+        Ok(rebuilds)
     }
 
     /// Clean up old rate limit windows (call periodically)
@@ -345,3 +1057,574 @@ impl UsageRepository {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BillingSettings;
+
+    #[test]
+    fn test_parse_year_month() {
+        assert_eq!(parse_year_month("2026-09"), Some((2026, 9)));
+        assert_eq!(parse_year_month("2026-13"), None);
+        assert_eq!(parse_year_month("2026-9"), None);
+        assert_eq!(parse_year_month("26-09"), None);
+        assert_eq!(parse_year_month("september"), None);
+    }
+
+    #[test]
+    fn test_quota_category_for_path() {
+        let cases = [
+            ("/api/v1/mockups/generate", QuotaCategory::Render),
+            ("/api/v1/mockups/generate-batch", QuotaCategory::Render),
+            ("/api/v1/tile", QuotaCategory::Render),
+            ("/api/v1/catalog/products/42", QuotaCategory::Catalog),
+            ("/api/v1/templates", QuotaCategory::Catalog),
+            ("/api/v1/sync/printful/start", QuotaCategory::Sync),
+            ("/api/v1/usage", QuotaCategory::Other),
+            ("/api/v1/admin/usage/rebuild", QuotaCategory::Other),
+            ("/unknown", QuotaCategory::Other),
+        ];
+        for (path, category) in cases {
+            assert_eq!(QuotaCategory::for_path(path), category, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_category_usage_remaining() {
+        let usage = CategoryUsage::new(QuotaCategory::Catalog, 100, 40);
+        assert_eq!(usage.remaining, 60);
+        assert!(!usage.is_exceeded);
+
+        let usage = CategoryUsage::new(QuotaCategory::Render, 100, 120);
+        assert_eq!(usage.remaining, 0);
+        assert!(usage.is_exceeded);
+    }
+
+    #[test]
+    fn test_month_bounds_roll_over_the_year() {
+        let (start, end) = month_bounds(2026, 12);
+        assert_eq!(start, Utc.with_ymd_and_hms(2026, 12, 1, 0, 0, 0).unwrap());
+        assert_eq!(end, Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_daily_usage_fills_missing_days() {
+        let start = NaiveDate::from_ymd_opt(2026, 9, 29).unwrap();
+        let mut busy = DailyUsage::empty(NaiveDate::from_ymd_opt(2026, 10, 1).unwrap());
+        busy.requests = 3;
+        let days = fill_daily_usage(start, 4, vec![busy.clone()]);
+
+        let dates: Vec<String> = days.iter().map(|d| d.date.to_string()).collect();
+        assert_eq!(
+            dates,
+            ["2026-09-29", "2026-09-30", "2026-10-01", "2026-10-02"]
+        );
+        assert_eq!(days[2], busy);
+        assert!(days
+            .iter()
+            .filter(|d| d.date != busy.date)
+            .all(|d| d.requests == 0 && d.p95_response_time_ms.is_none()));
+    }
+
+    fn entry(api_key_id: Uuid, endpoint: &str, render: Option<(&str, i32)>) -> UsageLogEntry {
+        UsageLogEntry {
+            api_key_id,
+            endpoint: endpoint.to_string(),
+            category: QuotaCategory::for_path(endpoint),
+            method: "POST".to_string(),
+            template_id: render.map(|(template_id, _)| template_id.to_string()),
+            render_count: render.map(|(_, count)| count),
+            billable_units: BillingSettings::default()
+                .units(endpoint, render.map(|(_, count)| count)),
+            status_code: 200,
+            response_time_ms: Some(5),
+            error_code: None,
+            error_message: None,
+            ip_address: None,
+            user_agent: None,
+            request_id: None,
+        }
+    }
+
+    #[test]
+    fn test_usage_log_filter_binds_from_two() {
+        let (clause, params) = UsageLogFilter::default().where_clause();
+        assert_eq!(clause, "WHERE api_key_id = $1");
+        assert!(params.is_empty());
+
+        let filter = UsageLogFilter {
+            status_class: StatusClass::parse("5XX"),
+            endpoint_prefix: Some("/api/v1/mockups".to_string()),
+            since: Some(Utc::now()),
+            until: None,
+        };
+        let (clause, params) = filter.where_clause();
+        assert_eq!(
+            clause,
+            "WHERE api_key_id = $1 AND status_code >= $2 AND status_code < $2 + 100 \
+             AND left(endpoint, length($3)) = $3 AND created_at >= $4"
+        );
+        assert_eq!(params.len(), 3);
+        assert_eq!(StatusClass::parse("3xx"), None);
+    }
+
+    #[tokio::test]
+    async fn test_usage_logs_pages_and_filters() {
+        use crate::db::{ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest, MAX_PER_PAGE};
+        use std::collections::HashSet;
+
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let keys = ApiKeyRepository::new(pool.clone());
+        let usage = UsageRepository::new(pool.clone());
+        let key = keys
+            .create(CreateApiKeyRequest {
+                name: "Usage log test".to_string(),
+                owner_email: format!("logs-{}@example.com", Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Free,
+                rate_limit_per_minute: None,
+                monthly_quota: None,
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+
+        // 120 rows: statuses cycle 200/404/500, endpoints alternate, and
+        // pairs of rows share a timestamp to exercise the ID tiebreaker
+        let base = Utc::now() - chrono::Duration::hours(1);
+        let client = pool.get().await.unwrap();
+        client
+            .execute(
+                r#"
+            INSERT INTO usage_logs (api_key_id, endpoint, method, status_code, created_at)
+            SELECT $1,
+                   CASE WHEN n % 2 = 0 THEN '/api/v1/mockups/generate' ELSE '/api/v1/catalog/products' END,
+                   'GET',
+                   (ARRAY[200, 404, 500])[n % 3 + 1],
+                   $2::TIMESTAMPTZ - make_interval(secs => n / 2)
+            FROM generate_series(0, 119) AS n
+            "#,
+                &[&key.id, &base],
+            )
+            .await
+            .unwrap();
+
+        let all = UsageLogFilter::default();
+        let mut seen = HashSet::new();
+        let mut previous: Option<(DateTime<Utc>, Uuid)> = None;
+        for number in 1..=3 {
+            let page = usage
+                .get_logs(key.id, &all, PageRequest::new(number, 50))
+                .await
+                .unwrap();
+            assert_eq!((page.total, page.total_pages()), (120, 3));
+            assert_eq!(page.items.len(), if number < 3 { 50 } else { 20 });
+            for log in page.items {
+                let position = (log.created_at, log.id);
+                assert!(previous.map_or(true, |p| p > position), "not newest first");
+                previous = Some(position);
+                assert!(seen.insert(log.id), "row on two pages");
+            }
+        }
+        assert_eq!(seen.len(), 120);
+        let past_end = usage
+            .get_logs(key.id, &all, PageRequest::new(4, 50))
+            .await
+            .unwrap();
+        assert!(past_end.items.is_empty());
+
+        // per_page is capped
+        let capped = usage
+            .get_logs(key.id, &all, PageRequest::new(1, 500))
+            .await
+            .unwrap();
+        assert_eq!(capped.per_page, MAX_PER_PAGE);
+        assert_eq!(capped.items.len(), MAX_PER_PAGE as usize);
+
+        let count = |filter: UsageLogFilter| {
+            let usage = &usage;
+            async move {
+                let page = usage
+                    .get_logs(key.id, &filter, PageRequest::new(1, 100))
+                    .await
+                    .unwrap();
+                (page.total, page.items)
+            }
+        };
+        let (total, errors) = count(UsageLogFilter {
+            status_class: Some(StatusClass::ServerError),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(total, 40);
+        assert!(errors.iter().all(|log| log.status_code == 500));
+
+        let (total, catalog) = count(UsageLogFilter {
+            endpoint_prefix: Some("/api/v1/catalog".to_string()),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(total, 60);
+        assert!(catalog
+            .iter()
+            .all(|log| log.endpoint.starts_with("/api/v1/catalog")));
+        // `_` matches itself, not any character
+        let (total, _) = count(UsageLogFilter {
+            endpoint_prefix: Some("/api/v1/catalog_".to_string()),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(total, 0);
+
+        // Rows 0-19 were logged in the 10 seconds up to `base`
+        let (total, _) = count(UsageLogFilter {
+            since: Some(base - chrono::Duration::seconds(9)),
+            until: Some(base + chrono::Duration::seconds(1)),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(total, 20);
+
+        let (total, _) = count(UsageLogFilter {
+            status_class: Some(StatusClass::Success),
+            endpoint_prefix: Some("/api/v1/mockups".to_string()),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(total, 20);
+
+        keys.purge(key.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_template_usage_breakdown() {
+        use crate::db::{ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest};
+
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let keys = ApiKeyRepository::new(pool.clone());
+        let usage = UsageRepository::new(pool.clone());
+        let key = keys
+            .create(CreateApiKeyRequest {
+                name: "Template usage test".to_string(),
+                owner_email: format!("usage-{}@example.com", Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Free,
+                rate_limit_per_minute: None,
+                monthly_quota: None,
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+
+        let generate = "/api/v1/mockups/generate";
+        for render in [
+            Some(("tee", 1)),
+            Some(("tee", 1)),
+            Some(("hoodie", 3)),
+            None,
+        ] {
+            let endpoint = if render.is_some() {
+                generate
+            } else {
+                "/api/v1/templates"
+            };
+            usage
+                .log_usage(entry(key.id, endpoint, render))
+                .await
+                .unwrap();
+        }
+
+        let client = pool.get().await.unwrap();
+        let rows = client
+            .query(
+                "SELECT endpoint, template_id, render_count FROM usage_logs WHERE api_key_id = $1",
+                &[&key.id],
+            )
+            .await
+            .unwrap();
+        for row in rows {
+            let endpoint: String = row.get("endpoint");
+            let template_id: Option<String> = row.get("template_id");
+            let render_count: Option<i32> = row.get("render_count");
+            assert_eq!(template_id.is_some(), endpoint == generate);
+            assert_eq!(render_count.is_some(), endpoint == generate);
+        }
+
+        let now = Utc::now();
+        let templates = usage
+            .get_template_usage(key.id, now.year(), now.month())
+            .await
+            .unwrap();
+        assert_eq!(
+            templates,
+            vec![
+                TemplateUsage {
+                    template_id: "hoodie".to_string(),
+                    requests: 1,
+                    renders: 3,
+                },
+                TemplateUsage {
+                    template_id: "tee".to_string(),
+                    requests: 2,
+                    renders: 2,
+                },
+            ]
+        );
+
+        keys.purge(key.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_quota_trips_on_units() {
+        use crate::db::{ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest};
+
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let keys = ApiKeyRepository::new(pool.clone());
+        let usage = UsageRepository::new(pool.clone());
+        let key = keys
+            .create(CreateApiKeyRequest {
+                name: "Billing units test".to_string(),
+                owner_email: format!("units-{}@example.com", Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Free,
+                rate_limit_per_minute: None,
+                monthly_quota: None,
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+
+        // 5 + 15 = 20 render units from only two requests
+        let generate = "/api/v1/mockups/generate";
+        for render in [("tee", 1), ("hoodie", 3)] {
+            usage
+                .log_usage(entry(key.id, generate, Some(render)))
+                .await
+                .unwrap();
+        }
+        usage
+            .log_usage(entry(key.id, "/api/v1/templates", None))
+            .await
+            .unwrap();
+
+        let (allowed, used) = usage
+            .check_quota(key.id, QuotaCategory::Render, 21)
+            .await
+            .unwrap();
+        assert!(allowed);
+        assert_eq!(used, 20);
+        let (allowed, _) = usage
+            .check_quota(key.id, QuotaCategory::Render, 20)
+            .await
+            .unwrap();
+        assert!(!allowed);
+
+        let month = usage.get_current_month_usage(key.id).await.unwrap();
+        assert_eq!(month.total_requests, 3);
+        assert_eq!(month.billable_units, 21);
+
+        keys.purge(key.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_daily_usage_across_day_boundaries() {
+        use crate::db::{ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest};
+
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let keys = ApiKeyRepository::new(pool.clone());
+        let usage = UsageRepository::new(pool.clone());
+        let key = keys
+            .create(CreateApiKeyRequest {
+                name: "Daily usage test".to_string(),
+                owner_email: format!("daily-{}@example.com", Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Free,
+                rate_limit_per_minute: None,
+                monthly_quota: None,
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+
+        // Three days ago just before midnight, two days ago just after it,
+        // nothing yesterday, and two requests today (one failed)
+        let today = Utc::now().date_naive();
+        let at = |days_ago: u64, h: u32, m: u32, s: u32| {
+            (today - Days::new(days_ago))
+                .and_hms_opt(h, m, s)
+                .unwrap()
+                .and_utc()
+        };
+        let seeded = [
+            ("/api/v1/seed/a", at(3, 23, 59, 59), 200, 10),
+            ("/api/v1/seed/b", at(2, 0, 0, 1), 200, 20),
+            ("/api/v1/seed/c", at(0, 0, 0, 0), 200, 30),
+            ("/api/v1/seed/d", at(0, 0, 0, 0), 500, 50),
+        ];
+        let client = pool.get().await.unwrap();
+        for (endpoint, created_at, status, elapsed) in seeded {
+            usage
+                .log_usage(entry(key.id, endpoint, None))
+                .await
+                .unwrap();
+            client
+                .execute(
+                    "UPDATE usage_logs SET created_at = $3, status_code = $4, \
+                     response_time_ms = $5 WHERE api_key_id = $1 AND endpoint = $2",
+                    &[&key.id, &endpoint, &created_at, &status, &elapsed],
+                )
+                .await
+                .unwrap();
+        }
+
+        let days = usage.get_daily_usage(key.id, 4).await.unwrap();
+        let requests: Vec<i64> = days.iter().map(|d| d.requests).collect();
+        assert_eq!(requests, [1, 1, 0, 2]);
+        assert_eq!(days[0].date, today - Days::new(3));
+        assert_eq!(days[3].date, today);
+        assert_eq!(days[2].avg_response_time_ms, None);
+        assert_eq!(days[3].successful_requests, 1);
+        assert_eq!(days[3].failed_requests, 1);
+        assert_eq!(days[3].avg_response_time_ms, Some(40.0));
+        assert_eq!(days[3].p95_response_time_ms, Some(49.0));
+
+        // The window starts at midnight, so the oldest day drops out
+        let days = usage.get_daily_usage(key.id, 3).await.unwrap();
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].requests, 1);
+
+        keys.purge(key.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_quota_alert_is_claimed_once() {
+        use crate::db::{ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest};
+        use crate::webhooks::reached_thresholds;
+
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let keys = ApiKeyRepository::new(pool.clone());
+        let usage = UsageRepository::new(pool.clone());
+        let key = keys
+            .create(CreateApiKeyRequest {
+                name: "Quota alert test".to_string(),
+                owner_email: format!("alerts-{}@example.com", Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Free,
+                rate_limit_per_minute: None,
+                monthly_quota: Some(12),
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+
+        // Each render is 5 units of 12: 10 reaches 80%, 15 passes 95% and 100%
+        // together, and 20 alerts nothing new
+        let thresholds = [80, 95, 100];
+        let mut fired = Vec::new();
+        for _ in 0..4 {
+            usage
+                .log_usage(entry(key.id, "/api/v1/mockups/generate", Some(("tee", 1))))
+                .await
+                .unwrap();
+            let (_, used) = usage
+                .check_quota(key.id, QuotaCategory::Render, 12)
+                .await
+                .unwrap();
+            for threshold in reached_thresholds(&thresholds, 12, used) {
+                if usage
+                    .claim_quota_alert(key.id, "2026-10", QuotaCategory::Render, threshold)
+                    .await
+                    .unwrap()
+                {
+                    fired.push((used, threshold));
+                }
+            }
+        }
+        assert_eq!(fired, vec![(10, 80), (15, 95), (15, 100)]);
+
+        keys.purge(key.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_holds_under_concurrency() {
+        use crate::db::{ApiKeyRepository, ApiKeyTier, CreateApiKeyRequest};
+
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let pool = DbPool::new(&url).expect("invalid TEST_DATABASE_URL");
+        let keys = ApiKeyRepository::new(pool.clone());
+        let key = keys
+            .create(CreateApiKeyRequest {
+                name: "Rate limit test".to_string(),
+                owner_email: format!("rate-{}@example.com", Uuid::new_v4()),
+                owner_name: None,
+                company: None,
+                tier: ApiKeyTier::Free,
+                rate_limit_per_minute: Some(10),
+                monthly_quota: None,
+                expires_at: None,
+                allowed_design_domains: None,
+            })
+            .await
+            .unwrap();
+
+        // A fixed window, so a minute rolling over can't split the burst
+        let window_start = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+        let requests = (0..50).map(|_| {
+            let usage = UsageRepository::new(pool.clone());
+            tokio::spawn(async move {
+                usage
+                    .take_rate_limit_slot(key.id, 10, window_start)
+                    .await
+                    .unwrap()
+            })
+        });
+        let statuses: Vec<RateLimitStatus> = futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(statuses.iter().filter(|s| s.allowed).count(), 10);
+        let mut counts: Vec<i32> = statuses
+            .iter()
+            .filter(|s| s.allowed)
+            .map(|s| s.current_count)
+            .collect();
+        counts.sort_unstable();
+        assert_eq!(counts, (1..=10).collect::<Vec<_>>());
+        assert!(statuses
+            .iter()
+            .filter(|s| !s.allowed)
+            .all(|s| s.current_count == 10));
+
+        keys.purge(key.id).await.unwrap();
+    }
+}
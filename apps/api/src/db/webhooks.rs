@@ -0,0 +1,418 @@
+//! Webhook subscription, event, and delivery log database operations
+
+use super::pool::{DbError, DbPool};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use tokio_postgres::Row;
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Database model for a webhook subscription
+#[derive(Debug, Clone)]
+pub struct DbWebhookSubscription {
+    pub id: Uuid,
+    pub api_key_id: Uuid,
+    pub url: String,
+    pub secret: String,
+    /// Event filters; empty means every event
+    pub event_types: Vec<String>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl DbWebhookSubscription {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            id: row.get("id"),
+            api_key_id: row.get("api_key_id"),
+            url: row.get("url"),
+            secret: row.get("secret"),
+            event_types: row.get("event_types"),
+            is_active: row.get("is_active"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+}
+
+/// Database model for an emitted webhook event
+#[derive(Debug, Clone)]
+pub struct DbWebhookEvent {
+    pub id: Uuid,
+    /// Owning API key, `None` for system events
+    pub api_key_id: Option<Uuid>,
+    pub event_type: String,
+    /// JSON payload as text
+    pub payload: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DbWebhookEvent {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            id: row.get("id"),
+            api_key_id: row.get("api_key_id"),
+            event_type: row.get("event_type"),
+            payload: row.get("payload"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+/// Delivery log entry
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    /// pending, delivered, failed
+    pub status: String,
+    pub response_status: Option<i32>,
+    pub error_message: Option<String>,
+    pub duration_ms: Option<i32>,
+    pub is_redelivery: bool,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookDelivery {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            id: row.get("id"),
+            subscription_id: row.get("subscription_id"),
+            event_id: row.get("event_id"),
+            event_type: row.get("event_type"),
+            status: row.get("status"),
+            response_status: row.get("response_status"),
+            error_message: row.get("error_message"),
+            duration_ms: row.get("duration_ms"),
+            is_redelivery: row.get("is_redelivery"),
+            created_at: row.get("created_at"),
+            completed_at: row.get("completed_at"),
+        }
+    }
+}
+
+const SUBSCRIPTION_COLUMNS: &str =
+    "id, api_key_id, url, secret, event_types, is_active, created_at, updated_at";
+
+/// Repository for webhook operations
+pub struct WebhookRepository {
+    pub pool: DbPool,
+}
+
+impl WebhookRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Generate a signing secret
+    /// Format: whsec_<32 random alphanumeric chars>
+    fn generate_secret() -> String {
+        const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+        let mut rng = rand::thread_rng();
+
+        let body: String = (0..32)
+            .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+            .collect();
+
+        format!("whsec_{}", body)
+    }
+
+    /// Create a subscription for an API key
+    pub async fn create_subscription(
+        &self,
+        api_key_id: Uuid,
+        url: &str,
+        event_types: &[String],
+    ) -> Result<DbWebhookSubscription, DbError> {
+        let client = self.pool.get().await?;
+        let secret = Self::generate_secret();
+
+        let row = client
+            .query_one(
+                &format!(
+                    r#"
+            INSERT INTO webhook_subscriptions (api_key_id, url, secret, event_types)
+            VALUES ($1, $2, $3, $4)
+            RETURNING {}
+            "#,
+                    SUBSCRIPTION_COLUMNS
+                ),
+                &[&api_key_id, &url, &secret, &event_types],
+            )
+            .await?;
+
+        let subscription = DbWebhookSubscription::from_row(&row);
+        info!(
+            subscription_id = %subscription.id,
+            key_id = %api_key_id,
+            "Created webhook subscription"
+        );
+
+        Ok(subscription)
+    }
+
+    /// Get a subscription by ID
+    pub async fn get_subscription(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<DbWebhookSubscription>, DbError> {
+        let client = self.pool.get().await?;
+
+        let row = client
+            .query_opt(
+                &format!(
+                    "SELECT {} FROM webhook_subscriptions WHERE id = $1",
+                    SUBSCRIPTION_COLUMNS
+                ),
+                &[&id],
+            )
+            .await?;
+
+        Ok(row.as_ref().map(DbWebhookSubscription::from_row))
+    }
+
+    /// List subscriptions owned by an API key
+    pub async fn list_subscriptions(
+        &self,
+        api_key_id: Uuid,
+    ) -> Result<Vec<DbWebhookSubscription>, DbError> {
+        let client = self.pool.get().await?;
+
+        let rows = client
+            .query(
+                &format!(
+                    r#"
+            SELECT {} FROM webhook_subscriptions
+            WHERE api_key_id = $1
+            ORDER BY created_at DESC
+            "#,
+                    SUBSCRIPTION_COLUMNS
+                ),
+                &[&api_key_id],
+            )
+            .await?;
+
+        Ok(rows.iter().map(DbWebhookSubscription::from_row).collect())
+    }
+
+    /// Active subscriptions that may receive an event
+    ///
+    /// Key-scoped events go to the key's own subscriptions; system events
+    /// (no key) go to subscriptions owned by enterprise keys. Event type
+    /// filtering happens in the caller.
+    pub async fn candidate_subscriptions(
+        &self,
+        api_key_id: Option<Uuid>,
+    ) -> Result<Vec<DbWebhookSubscription>, DbError> {
+        let client = self.pool.get().await?;
+
+        let rows = client
+            .query(
+                r#"
+            SELECT s.id, s.api_key_id, s.url, s.secret, s.event_types, s.is_active,
+                   s.created_at, s.updated_at
+            FROM webhook_subscriptions s
+            JOIN api_keys k ON s.api_key_id = k.id
+            WHERE s.is_active = true AND k.is_active = true
+              AND (($1::UUID IS NOT NULL AND s.api_key_id = $1)
+                OR ($1::UUID IS NULL AND k.tier = 'enterprise'))
+            "#,
+                &[&api_key_id],
+            )
+            .await?;
+
+        Ok(rows.iter().map(DbWebhookSubscription::from_row).collect())
+    }
+
+    /// Update a subscription's event filters and/or active flag
+    pub async fn update_subscription(
+        &self,
+        id: Uuid,
+        event_types: Option<&[String]>,
+        is_active: Option<bool>,
+    ) -> Result<Option<DbWebhookSubscription>, DbError> {
+        let client = self.pool.get().await?;
+
+        let row = client
+            .query_opt(
+                &format!(
+                    r#"
+            UPDATE webhook_subscriptions
+            SET event_types = COALESCE($2, event_types),
+                is_active = COALESCE($3, is_active)
+            WHERE id = $1
+            RETURNING {}
+            "#,
+                    SUBSCRIPTION_COLUMNS
+                ),
+                &[&id, &event_types, &is_active],
+            )
+            .await?;
+
+        Ok(row.as_ref().map(DbWebhookSubscription::from_row))
+    }
+
+    /// Delete a subscription and its delivery log
+    pub async fn delete_subscription(&self, id: Uuid) -> Result<bool, DbError> {
+        let client = self.pool.get().await?;
+
+        let result = client
+            .execute("DELETE FROM webhook_subscriptions WHERE id = $1", &[&id])
+            .await?;
+
+        Ok(result > 0)
+    }
+
+    /// Store an emitted event and return its ID
+    pub async fn record_event(
+        &self,
+        api_key_id: Option<Uuid>,
+        event_type: &str,
+        payload: &str,
+    ) -> Result<Uuid, DbError> {
+        let client = self.pool.get().await?;
+
+        let row = client
+            .query_one(
+                r#"
+            INSERT INTO webhook_events (api_key_id, event_type, payload)
+            VALUES ($1, $2, $3::TEXT::JSONB)
+            RETURNING id
+            "#,
+                &[&api_key_id, &event_type, &payload],
+            )
+            .await?;
+
+        Ok(row.get("id"))
+    }
+
+    /// Get an event by ID
+    pub async fn get_event(&self, id: Uuid) -> Result<Option<DbWebhookEvent>, DbError> {
+        let client = self.pool.get().await?;
+
+        let row = client
+            .query_opt(
+                r#"
+            SELECT id, api_key_id, event_type, payload::TEXT as payload, created_at
+            FROM webhook_events
+            WHERE id = $1
+            "#,
+                &[&id],
+            )
+            .await?;
+
+        Ok(row.as_ref().map(DbWebhookEvent::from_row))
+    }
+
+    /// Open a delivery log entry in the pending state
+    pub async fn start_delivery(
+        &self,
+        subscription_id: Uuid,
+        event_id: Uuid,
+        is_redelivery: bool,
+    ) -> Result<Uuid, DbError> {
+        let client = self.pool.get().await?;
+
+        let row = client
+            .query_one(
+                r#"
+            INSERT INTO webhook_deliveries (subscription_id, event_id, is_redelivery)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+                &[&subscription_id, &event_id, &is_redelivery],
+            )
+            .await?;
+
+        Ok(row.get("id"))
+    }
+
+    /// Record the outcome of a delivery attempt
+    pub async fn finish_delivery(
+        &self,
+        delivery_id: Uuid,
+        delivered: bool,
+        response_status: Option<i32>,
+        error_message: Option<&str>,
+        duration_ms: i32,
+    ) -> Result<(), DbError> {
+        let client = self.pool.get().await?;
+        let status = if delivered { "delivered" } else { "failed" };
+
+        client
+            .execute(
+                r#"
+            UPDATE webhook_deliveries
+            SET status = $2, response_status = $3, error_message = $4,
+                duration_ms = $5, completed_at = NOW()
+            WHERE id = $1
+            "#,
+                &[
+                    &delivery_id,
+                    &status,
+                    &response_status,
+                    &error_message,
+                    &duration_ms,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a delivery log entry by ID
+    pub async fn get_delivery(&self, id: Uuid) -> Result<Option<WebhookDelivery>, DbError> {
+        let client = self.pool.get().await?;
+
+        let row = client
+            .query_opt(
+                r#"
+            SELECT d.id, d.subscription_id, d.event_id, e.event_type, d.status,
+                   d.response_status, d.error_message, d.duration_ms, d.is_redelivery,
+                   d.created_at, d.completed_at
+            FROM webhook_deliveries d
+            JOIN webhook_events e ON d.event_id = e.id
+            WHERE d.id = $1
+            "#,
+                &[&id],
+            )
+            .await?;
+
+        Ok(row.as_ref().map(WebhookDelivery::from_row))
+    }
+
+    /// Delivery log for a subscription, newest first
+    pub async fn list_deliveries(
+        &self,
+        subscription_id: Uuid,
+        status: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, DbError> {
+        let client = self.pool.get().await?;
+
+        let rows = client
+            .query(
+                r#"
+            SELECT d.id, d.subscription_id, d.event_id, e.event_type, d.status,
+                   d.response_status, d.error_message, d.duration_ms, d.is_redelivery,
+                   d.created_at, d.completed_at
+            FROM webhook_deliveries d
+            JOIN webhook_events e ON d.event_id = e.id
+            WHERE d.subscription_id = $1
+              AND ($2::TEXT IS NULL OR d.status = $2)
+            ORDER BY d.created_at DESC
+            LIMIT $3
+            "#,
+                &[&subscription_id, &status, &limit],
+            )
+            .await?;
+
+        Ok(rows.iter().map(WebhookDelivery::from_row).collect())
+    }
+}
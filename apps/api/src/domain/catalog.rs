@@ -6,6 +6,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 // ============================================================================
@@ -13,7 +14,7 @@ use uuid::Uuid;
 // ============================================================================
 
 /// Product type enumeration (unified across providers)
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ProductType {
     Tshirt,
@@ -120,7 +121,7 @@ impl std::fmt::Display for ProductType {
 // ============================================================================
 
 /// Print placement types (unified across providers)
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum PrintPlacement {
     Front,
@@ -197,7 +198,7 @@ impl std::fmt::Display for PrintPlacement {
 // ============================================================================
 
 /// Unified product representation across all providers
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UnifiedProduct {
     /// Provider's product ID
     pub external_id: String,
@@ -279,7 +280,7 @@ impl UnifiedProduct {
 // ============================================================================
 
 /// Unified variant representation (size/color combination)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UnifiedVariant {
     /// Provider's variant ID
     pub external_id: String,
@@ -331,7 +332,8 @@ impl UnifiedVariant {
 // ============================================================================
 
 /// Print constraints (technique-specific requirements)
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
 pub struct PrintConstraints {
     /// Maximum number of colors (for screen printing)
     pub max_colors: Option<i32>,
@@ -350,7 +352,7 @@ pub struct PrintConstraints {
 }
 
 /// Unified print area representation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UnifiedPrintArea {
     /// Provider's print area ID (if available)
     pub external_id: Option<String>,
@@ -417,6 +419,8 @@ pub enum AssetType {
     PrintfilePreview,
     /// Small thumbnail image
     Thumbnail,
+    /// Mockup rendered by this service, stored under `generated/`
+    GeneratedMockup,
 }
 
 impl std::fmt::Display for AssetType {
@@ -426,6 +430,21 @@ impl std::fmt::Display for AssetType {
             AssetType::MockupTemplate => write!(f, "mockup_template"),
             AssetType::PrintfilePreview => write!(f, "printfile_preview"),
             AssetType::Thumbnail => write!(f, "thumbnail"),
+            AssetType::GeneratedMockup => write!(f, "generated_mockup"),
+        }
+    }
+}
+
+impl AssetType {
+    /// Parse the stored form written by `Display`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "base_image" => Some(AssetType::BaseImage),
+            "mockup_template" => Some(AssetType::MockupTemplate),
+            "printfile_preview" => Some(AssetType::PrintfilePreview),
+            "thumbnail" => Some(AssetType::Thumbnail),
+            "generated_mockup" => Some(AssetType::GeneratedMockup),
+            _ => None,
         }
     }
 }
@@ -450,6 +469,19 @@ pub struct MockupAsset {
 
     /// Associated variant ID (for variant-specific mockups)
     pub variant_external_id: Option<String>,
+
+    /// Where designs go on the template, relative to `width_px` x `height_px`
+    #[serde(default)]
+    pub template_print_area: Option<TemplatePrintArea>,
+}
+
+/// Print area rectangle inside a provider mockup template
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TemplatePrintArea {
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
 }
 
 impl MockupAsset {
@@ -462,6 +494,7 @@ impl MockupAsset {
             width_px: None,
             height_px: None,
             variant_external_id: None,
+            template_print_area: None,
         }
     }
 }
@@ -612,6 +645,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_asset_type_parse_round_trips() {
+        for asset_type in [
+            AssetType::BaseImage,
+            AssetType::MockupTemplate,
+            AssetType::PrintfilePreview,
+            AssetType::Thumbnail,
+            AssetType::GeneratedMockup,
+        ] {
+            assert_eq!(AssetType::parse(&asset_type.to_string()), Some(asset_type));
+        }
+        assert_eq!(AssetType::parse("poster"), None);
+    }
+
     #[test]
     fn test_product_type_category_slug() {
         assert_eq!(ProductType::Tshirt.category_slug(), "t-shirts");
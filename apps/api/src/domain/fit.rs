@@ -0,0 +1,260 @@
+//! Design fit scoring against product print areas
+//!
+//! Evaluates how well a design suits a print area: the DPI it reaches when
+//! stretched to the full print width, how far its aspect ratio is from the
+//! area's, and which print constraints it breaks.
+
+use serde::Serialize;
+use std::collections::HashSet;
+use utoipa::ToSchema;
+
+use super::catalog::UnifiedPrintArea;
+
+/// DPI assumed when a print area does not specify one
+const DEFAULT_PRINT_DPI: i32 = 300;
+
+/// Score deducted per constraint violation
+const VIOLATION_PENALTY: f64 = 20.0;
+
+/// Properties of a design relevant to print fit
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DesignProfile {
+    pub width: u32,
+    pub height: u32,
+    pub file_size_bytes: u64,
+    /// Uppercase format name (PNG, JPEG, ...)
+    pub format: Option<String>,
+    /// Distinct opaque colors, capped by `count_colors`
+    pub color_count: Option<u32>,
+}
+
+/// A print constraint the design does not meet
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FitViolation {
+    LowDpi {
+        achieved: f64,
+        required: i32,
+    },
+    FileTooLarge {
+        size_mb: f64,
+        max_mb: i32,
+    },
+    UnsupportedFormat {
+        format: String,
+        allowed: Vec<String>,
+    },
+    TooManyColors {
+        colors: u32,
+        max: i32,
+    },
+}
+
+/// Fit of one design on one print area
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FitAssessment {
+    /// DPI achieved when the design spans the full print width
+    pub dpi_at_full_width: f64,
+    /// DPI the print area requires
+    pub required_dpi: i32,
+    /// 0.0 = identical aspect ratios, approaching 1.0 = very different
+    pub aspect_mismatch: f64,
+    /// Whether the design overflows the area height at full width
+    pub overflows_height: bool,
+    pub violations: Vec<FitViolation>,
+    /// 0-100, higher is better
+    pub score: f64,
+}
+
+impl FitAssessment {
+    pub fn is_printable(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Assess a design against a print area
+pub fn assess_fit(design: &DesignProfile, area: &UnifiedPrintArea) -> FitAssessment {
+    let print_dpi = if area.print_dpi > 0 {
+        area.print_dpi
+    } else {
+        DEFAULT_PRINT_DPI
+    };
+    let required_dpi = area.constraints.min_dpi.unwrap_or(print_dpi);
+
+    let area_width = area.width_px.max(1) as f64;
+    let area_height = area.height_px.max(1) as f64;
+    let design_width = design.width.max(1) as f64;
+    let design_height = design.height.max(1) as f64;
+
+    // Physical print width in inches is width_px / print_dpi
+    let dpi_at_full_width = design_width * print_dpi as f64 / area_width;
+    let overflows_height = design_height * (area_width / design_width) > area_height;
+
+    let design_ratio = design_width / design_height;
+    let area_ratio = area_width / area_height;
+    let aspect_mismatch = 1.0 - design_ratio.min(area_ratio) / design_ratio.max(area_ratio);
+
+    let mut violations = Vec::new();
+
+    if dpi_at_full_width < required_dpi as f64 {
+        violations.push(FitViolation::LowDpi {
+            achieved: round1(dpi_at_full_width),
+            required: required_dpi,
+        });
+    }
+
+    if let Some(max_mb) = area.constraints.max_file_size_mb {
+        let size_mb = design.file_size_bytes as f64 / (1024.0 * 1024.0);
+        if size_mb > max_mb as f64 {
+            violations.push(FitViolation::FileTooLarge {
+                size_mb: round1(size_mb),
+                max_mb,
+            });
+        }
+    }
+
+    if let Some(ref format) = design.format {
+        let allowed = &area.constraints.file_formats;
+        if !allowed.is_empty() && !allowed.iter().any(|f| same_format(f, format)) {
+            violations.push(FitViolation::UnsupportedFormat {
+                format: format.clone(),
+                allowed: allowed.clone(),
+            });
+        }
+    }
+
+    if let (Some(max), Some(colors)) = (area.constraints.max_colors, design.color_count) {
+        if max > 0 && colors > max as u32 {
+            violations.push(FitViolation::TooManyColors { colors, max });
+        }
+    }
+
+    let dpi_ratio = (dpi_at_full_width / required_dpi.max(1) as f64).min(1.0);
+    let score = 100.0 * (0.6 * dpi_ratio + 0.4 * (1.0 - aspect_mismatch))
+        - VIOLATION_PENALTY * violations.len() as f64;
+
+    FitAssessment {
+        dpi_at_full_width: round1(dpi_at_full_width),
+        required_dpi,
+        aspect_mismatch: (aspect_mismatch * 1000.0).round() / 1000.0,
+        overflows_height,
+        violations,
+        score: round1(score.clamp(0.0, 100.0)),
+    }
+}
+
+/// Count distinct opaque RGB colors, stopping once `limit` is exceeded
+///
+/// Returns `limit + 1` for designs with more colors than `limit`.
+pub fn count_colors(rgba: &[u8], limit: u32) -> u32 {
+    let mut colors = HashSet::new();
+    for pixel in rgba.chunks_exact(4) {
+        if pixel[3] == 0 {
+            continue;
+        }
+        colors.insert([pixel[0], pixel[1], pixel[2]]);
+        if colors.len() as u32 > limit {
+            break;
+        }
+    }
+    colors.len() as u32
+}
+
+fn same_format(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| {
+        let upper = s.trim().trim_start_matches('.').to_ascii_uppercase();
+        if upper == "JPG" {
+            "JPEG".to_string()
+        } else {
+            upper
+        }
+    };
+    normalize(a) == normalize(b)
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::catalog::PrintPlacement;
+
+    fn design(width: u32, height: u32) -> DesignProfile {
+        DesignProfile {
+            width,
+            height,
+            file_size_bytes: 1024,
+            format: Some("PNG".to_string()),
+            color_count: Some(3),
+        }
+    }
+
+    fn area(width_px: i32, height_px: i32) -> UnifiedPrintArea {
+        UnifiedPrintArea::new(
+            PrintPlacement::Front,
+            "Front".to_string(),
+            width_px,
+            height_px,
+        )
+    }
+
+    #[test]
+    fn test_perfect_fit() {
+        let fit = assess_fit(&design(3600, 4800), &area(3600, 4800));
+        assert_eq!(fit.dpi_at_full_width, 300.0);
+        assert_eq!(fit.aspect_mismatch, 0.0);
+        assert!(!fit.overflows_height);
+        assert!(fit.is_printable());
+        assert_eq!(fit.score, 100.0);
+    }
+
+    #[test]
+    fn test_low_dpi_violation() {
+        let fit = assess_fit(&design(1800, 2400), &area(3600, 4800));
+        assert_eq!(fit.dpi_at_full_width, 150.0);
+        assert_eq!(
+            fit.violations,
+            vec![FitViolation::LowDpi {
+                achieved: 150.0,
+                required: 300
+            }]
+        );
+        assert!(fit.score < 60.0);
+    }
+
+    #[test]
+    fn test_constraint_violations() {
+        let mut area = area(3600, 3600);
+        area.constraints.max_colors = Some(2);
+        area.constraints.file_formats = vec!["jpg".to_string()];
+
+        let mut jpeg = design(3600, 3600);
+        jpeg.format = Some("JPEG".to_string());
+        jpeg.color_count = Some(2);
+        assert!(assess_fit(&jpeg, &area).is_printable());
+
+        let fit = assess_fit(&design(3600, 3600), &area);
+        assert_eq!(fit.violations.len(), 2);
+    }
+
+    #[test]
+    fn test_aspect_mismatch_and_overflow() {
+        let fit = assess_fit(&design(3000, 6000), &area(3000, 3000));
+        assert_eq!(fit.aspect_mismatch, 0.5);
+        assert!(fit.overflows_height);
+    }
+
+    #[test]
+    fn test_count_colors_caps_and_skips_transparent() {
+        let pixels = [
+            255, 0, 0, 255, //
+            255, 0, 0, 255, //
+            0, 255, 0, 255, //
+            0, 0, 255, 0, //
+        ];
+        assert_eq!(count_colors(&pixels, 10), 2);
+        assert_eq!(count_colors(&pixels, 1), 2);
+    }
+}
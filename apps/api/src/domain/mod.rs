@@ -1,11 +1,18 @@
 //! Domain types and models
 
 pub mod catalog;
+mod fit;
 mod placement;
+mod presets;
 
 pub use catalog::{
     AssetType, DbPodMockupAsset, DbPodPrintArea, DbPodProduct, DbPodProductVariant, DbPodProvider,
     DbPodSyncJob, MockupAsset, PrintConstraints, PrintPlacement, ProductType, UnifiedPrintArea,
     UnifiedProduct, UnifiedVariant,
 };
-pub use placement::{CoordinateSpace, PlacementSpec, PlacementType};
+pub use fit::{assess_fit, count_colors, DesignProfile, FitAssessment, FitViolation};
+pub use placement::{
+    CoordinateSpace, PhysicalPlacement, PlacementError, PlacementSpec, PlacementType,
+    DEFAULT_PRINT_DPI,
+};
+pub use presets::{default_presets, PlacementPreset};
@@ -15,15 +15,24 @@ pub const DISPLAY_TEMPLATE_HEIGHT: i32 = 1400;
 pub const PRINT_TEMPLATE_WIDTH: i32 = 1800;
 pub const PRINT_TEMPLATE_HEIGHT: i32 = 2400;
 
+/// Print resolution assumed for templates that don't specify one
+pub const DEFAULT_PRINT_DPI: u32 = 300;
+
 /// Placement errors
 #[derive(Debug, Error)]
 pub enum PlacementError {
     #[error("Scale must be between 0.1 and 1.0, got {0}")]
     InvalidScale(f64),
+    #[error("Rotation must be between -180 and 180 degrees, got {0}")]
+    InvalidRotation(f64),
     #[error("Design extends outside print area: left={0}, right={1}, print_width={2}")]
     OutOfBoundsHorizontal(i32, i32, i32),
     #[error("Design extends outside print area: top={0}, bottom={1}, print_height={2}")]
     OutOfBoundsVertical(i32, i32, i32),
+    #[error("Print width must be positive at a positive DPI, got {0} in at {1} DPI")]
+    InvalidPrintSize(f64, u32),
+    #[error("Design is {0} in wide but the print area fits at most {1:.2} in at {2} DPI")]
+    ExceedsPrintArea(f64, f64, u32),
 }
 
 /// Coordinate space for placement calculations
@@ -83,9 +92,45 @@ pub struct PlacementSpec {
     #[serde(default = "default_print_height")]
     pub print_area_height: i32,
 
-    /// Coordinate space (display or print)
+    /// Coordinate space (display or print); display offsets are converted to the
+    /// template's print area on each axis before rendering
     #[serde(default)]
     pub coordinate_space: CoordinateSpace,
+
+    /// Clockwise rotation around the design's center in degrees (-180 to 180)
+    #[serde(default)]
+    pub rotation_degrees: f64,
+}
+
+/// Placement given as a physical print size instead of a scale
+///
+/// Inches are converted to pixels at `dpi`, so print operators can ask for
+/// "10 inches wide, 2 inches above center" without knowing the template's
+/// pixel dimensions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PhysicalPlacement {
+    /// Printed width of the design in inches
+    pub width_inches: f64,
+
+    /// Horizontal offset of the design's center from the print area's center in inches
+    #[serde(default)]
+    pub offset_x_inches: f64,
+
+    /// Vertical offset in inches (negative = up, positive = down)
+    #[serde(default)]
+    pub offset_y_inches: f64,
+
+    /// Print resolution; defaults to the template's `print_dpi`, then 300
+    #[serde(default)]
+    pub dpi: Option<u32>,
+
+    /// Placement type (front, back, etc.)
+    #[serde(default)]
+    pub placement: PlacementType,
+
+    /// Clockwise rotation around the design's center in degrees (-180 to 180)
+    #[serde(default)]
+    pub rotation_degrees: f64,
 }
 
 fn default_print_width() -> i32 {
@@ -106,18 +151,79 @@ impl PlacementSpec {
             print_area_width: PRINT_TEMPLATE_WIDTH,
             print_area_height: PRINT_TEMPLATE_HEIGHT,
             coordinate_space: CoordinateSpace::Print,
+            rotation_degrees: 0.0,
+        }
+    }
+
+    /// Convert a physical print size to a print space placement in a print area
+    ///
+    /// `dpi` is the template's print resolution, used when the placement names
+    /// none; inches are rounded to the nearest pixel. Fails when the design is
+    /// wider than the print area at that resolution.
+    pub fn from_physical(
+        physical: &PhysicalPlacement,
+        dpi: Option<u32>,
+        print_width: i32,
+        print_height: i32,
+    ) -> Result<PlacementSpec, PlacementError> {
+        let dpi = physical.dpi.or(dpi).unwrap_or(DEFAULT_PRINT_DPI);
+        // Also rejects NaN
+        if !(physical.width_inches > 0.0) || dpi == 0 {
+            return Err(PlacementError::InvalidPrintSize(physical.width_inches, dpi));
+        }
+        let to_pixels = |inches: f64| (inches * dpi as f64).round();
+
+        let width = to_pixels(physical.width_inches);
+        if width > print_width as f64 {
+            return Err(PlacementError::ExceedsPrintArea(
+                physical.width_inches,
+                print_width as f64 / dpi as f64,
+                dpi,
+            ));
         }
+
+        Ok(PlacementSpec {
+            scale: width / print_width as f64,
+            offset_x: to_pixels(physical.offset_x_inches) as i32,
+            offset_y: to_pixels(physical.offset_y_inches) as i32,
+            placement: physical.placement.clone(),
+            print_area_width: print_width,
+            print_area_height: print_height,
+            coordinate_space: CoordinateSpace::Print,
+            rotation_degrees: physical.rotation_degrees,
+        })
     }
 
     /// Validate the placement specification
     pub fn validate(&self) -> Result<(), PlacementError> {
+        match self.errors().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Every problem with the placement, in the order `validate` reports them
+    ///
+    /// Bounds are only checked once scale and rotation are valid, since the
+    /// design's extent depends on both.
+    pub fn errors(&self) -> Vec<PlacementError> {
+        let mut errors = Vec::new();
+
         // Validate scale
         if self.scale < 0.1 || self.scale > 1.0 {
-            return Err(PlacementError::InvalidScale(self.scale));
+            errors.push(PlacementError::InvalidScale(self.scale));
         }
 
-        // Calculate design dimensions
-        let (design_width, design_height) = self.get_design_dimensions();
+        // Validate rotation (NaN falls outside the range too)
+        if !(-180.0..=180.0).contains(&self.rotation_degrees) {
+            errors.push(PlacementError::InvalidRotation(self.rotation_degrees));
+        }
+        if !errors.is_empty() {
+            return errors;
+        }
+
+        // Bounds apply to the rotated design's bounding box
+        let (design_width, design_height) = self.get_rotated_dimensions();
 
         // Calculate absolute position
         let (abs_x, abs_y) = self.get_absolute_position();
@@ -126,7 +232,7 @@ impl PlacementSpec {
         let left_edge = abs_x;
         let right_edge = abs_x + design_width;
         if left_edge < 0 || right_edge > self.print_area_width {
-            return Err(PlacementError::OutOfBoundsHorizontal(
+            errors.push(PlacementError::OutOfBoundsHorizontal(
                 left_edge,
                 right_edge,
                 self.print_area_width,
@@ -137,26 +243,51 @@ impl PlacementSpec {
         let top_edge = abs_y;
         let bottom_edge = abs_y + design_height;
         if top_edge < 0 || bottom_edge > self.print_area_height {
-            return Err(PlacementError::OutOfBoundsVertical(
+            errors.push(PlacementError::OutOfBoundsVertical(
                 top_edge,
                 bottom_edge,
                 self.print_area_height,
             ));
         }
 
-        Ok(())
+        errors
     }
 
     /// Get design dimensions based on scale and print area
     pub fn get_design_dimensions(&self) -> (i32, i32) {
-        let width = (self.print_area_width as f64 * self.scale) as i32;
-        let height = (self.print_area_height as f64 * self.scale) as i32;
+        // Trim float noise so a scale derived from a pixel width maps back to it
+        let extent = |v: f64| (v + 1e-6) as i32;
+        let width = extent(self.print_area_width as f64 * self.scale);
+        let height = extent(self.print_area_height as f64 * self.scale);
         (width, height)
     }
 
-    /// Get absolute position (top-left corner) of the design
+    /// Get the bounding box of the design after rotation
+    ///
+    /// Equal to `get_design_dimensions` when the design is not rotated.
+    pub fn get_rotated_dimensions(&self) -> (i32, i32) {
+        let (width, height) = self.get_design_dimensions();
+        if self.rotation_degrees == 0.0 {
+            return (width, height);
+        }
+
+        let (sin, cos) = self.rotation_degrees.to_radians().sin_cos();
+        let (sin, cos) = (sin.abs(), cos.abs());
+        let (width, height) = (width as f64, height as f64);
+        // Trim float noise so 90 degrees doesn't grow the box by a pixel
+        let extent = |v: f64| (v - 1e-6).ceil() as i32;
+        (
+            extent(width * cos + height * sin),
+            extent(width * sin + height * cos),
+        )
+    }
+
+    /// Get absolute position (top-left corner) of the design's bounding box
+    ///
+    /// The box is centered on the print area center plus the offsets, so a
+    /// rotated design keeps its visual center in place.
     pub fn get_absolute_position(&self) -> (i32, i32) {
-        let (design_width, design_height) = self.get_design_dimensions();
+        let (design_width, design_height) = self.get_rotated_dimensions();
 
         // Center of print area
         let center_x = self.print_area_width / 2;
@@ -170,44 +301,114 @@ impl PlacementSpec {
     }
 
     /// Convert to display space coordinates
+    ///
+    /// Assumes the classic shirt print area; use `to_display_for_template` for others.
     pub fn to_display_space(&self) -> PlacementSpec {
         if self.coordinate_space == CoordinateSpace::Display {
             return self.clone();
         }
-
-        let scale_factor = DISPLAY_TEMPLATE_WIDTH as f64 / PRINT_TEMPLATE_WIDTH as f64;
-
-        PlacementSpec {
-            scale: self.scale,
-            offset_x: (self.offset_x as f64 * scale_factor) as i32,
-            offset_y: (self.offset_y as f64 * scale_factor) as i32,
-            placement: self.placement.clone(),
-            print_area_width: DISPLAY_TEMPLATE_WIDTH,
-            print_area_height: DISPLAY_TEMPLATE_HEIGHT,
-            coordinate_space: CoordinateSpace::Display,
-        }
+        self.rescale(
+            (PRINT_TEMPLATE_WIDTH, PRINT_TEMPLATE_HEIGHT),
+            (DISPLAY_TEMPLATE_WIDTH, DISPLAY_TEMPLATE_HEIGHT),
+            CoordinateSpace::Display,
+        )
     }
 
     /// Convert to print space coordinates
+    ///
+    /// Assumes the classic shirt print area; use `from_display_for_template` for others.
     pub fn to_print_space(&self) -> PlacementSpec {
         if self.coordinate_space == CoordinateSpace::Print {
             return self.clone();
         }
+        self.rescale(
+            (DISPLAY_TEMPLATE_WIDTH, DISPLAY_TEMPLATE_HEIGHT),
+            (PRINT_TEMPLATE_WIDTH, PRINT_TEMPLATE_HEIGHT),
+            CoordinateSpace::Print,
+        )
+    }
 
-        let scale_factor = PRINT_TEMPLATE_WIDTH as f64 / DISPLAY_TEMPLATE_WIDTH as f64;
+    /// Convert a print space placement to the display space of a template's print area
+    pub fn to_display_for_template(&self, print_width: i32, print_height: i32) -> PlacementSpec {
+        if self.coordinate_space == CoordinateSpace::Display {
+            return self.clone();
+        }
+        self.rescale(
+            (print_width, print_height),
+            display_dimensions(print_width, print_height),
+            CoordinateSpace::Display,
+        )
+    }
+
+    /// Convert a display space placement to print space for a template's print area
+    pub fn from_display_for_template(
+        display: &PlacementSpec,
+        print_width: i32,
+        print_height: i32,
+    ) -> PlacementSpec {
+        display.rescale(
+            display_dimensions(print_width, print_height),
+            (print_width, print_height),
+            CoordinateSpace::Print,
+        )
+    }
+
+    /// The placement in print space, sized to a template's print area
+    ///
+    /// Display space offsets are converted first; print space offsets are kept.
+    pub fn in_print_area(&self, print_width: i32, print_height: i32) -> PlacementSpec {
+        match self.coordinate_space {
+            CoordinateSpace::Display => {
+                Self::from_display_for_template(self, print_width, print_height)
+            }
+            CoordinateSpace::Print => PlacementSpec {
+                print_area_width: print_width,
+                print_area_height: print_height,
+                ..self.clone()
+            },
+        }
+    }
+
+    /// Scale offsets from one area to another, each by its own axis ratio
+    ///
+    /// Offsets are rounded to the nearest pixel, so a round trip lands within
+    /// one pixel of where it started.
+    fn rescale(&self, from: (i32, i32), to: (i32, i32), space: CoordinateSpace) -> PlacementSpec {
+        let scale_axis = |offset: i32, from: i32, to: i32| {
+            if from == 0 {
+                return offset;
+            }
+            (offset as f64 * to as f64 / from as f64).round() as i32
+        };
 
         PlacementSpec {
             scale: self.scale,
-            offset_x: (self.offset_x as f64 * scale_factor) as i32,
-            offset_y: (self.offset_y as f64 * scale_factor) as i32,
+            offset_x: scale_axis(self.offset_x, from.0, to.0),
+            offset_y: scale_axis(self.offset_y, from.1, to.1),
             placement: self.placement.clone(),
-            print_area_width: PRINT_TEMPLATE_WIDTH,
-            print_area_height: PRINT_TEMPLATE_HEIGHT,
-            coordinate_space: CoordinateSpace::Print,
+            print_area_width: to.0,
+            print_area_height: to.1,
+            coordinate_space: space,
+            rotation_degrees: self.rotation_degrees,
         }
     }
 }
 
+/// Display space size of a print area
+///
+/// Previews scale each axis by the classic display/print pair (1000/1800
+/// horizontally, 1400/2400 vertically), so the shirt print area maps to
+/// exactly 1000x1400 and other print areas keep their proportions to it.
+pub fn display_dimensions(print_width: i32, print_height: i32) -> (i32, i32) {
+    let scale_axis = |print: i32, display: i32, classic: i32| {
+        (print as f64 * display as f64 / classic as f64).round() as i32
+    };
+    (
+        scale_axis(print_width, DISPLAY_TEMPLATE_WIDTH, PRINT_TEMPLATE_WIDTH),
+        scale_axis(print_height, DISPLAY_TEMPLATE_HEIGHT, PRINT_TEMPLATE_HEIGHT),
+    )
+}
+
 impl Default for PlacementSpec {
     fn default() -> Self {
         PlacementSpec {
@@ -218,6 +419,7 @@ impl Default for PlacementSpec {
             print_area_width: PRINT_TEMPLATE_WIDTH,
             print_area_height: PRINT_TEMPLATE_HEIGHT,
             coordinate_space: CoordinateSpace::Print,
+            rotation_degrees: 0.0,
         }
     }
 }
@@ -242,6 +444,75 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_zero_rotation_matches_unrotated() {
+        let spec = PlacementSpec::default();
+        assert_eq!(spec.get_rotated_dimensions(), spec.get_design_dimensions());
+        assert_eq!(spec.get_absolute_position(), (450, 550));
+    }
+
+    #[test]
+    fn test_quarter_turn_swaps_dimensions() {
+        let mut spec = PlacementSpec::default();
+        spec.rotation_degrees = 90.0;
+        assert_eq!(spec.get_design_dimensions(), (900, 1200));
+        assert_eq!(spec.get_rotated_dimensions(), (1200, 900));
+
+        // Visual center stays at the print area center plus the offsets
+        let (x, y) = spec.get_absolute_position();
+        assert_eq!((x + 600, y + 450), (900, 1150));
+        assert!(spec.validate().is_ok());
+    }
+
+    #[test]
+    fn test_rotation_grows_bounds() {
+        // Fills the print area exactly, so any tilt pushes the corners out
+        let mut spec = PlacementSpec::new(1.0, 0, 0, PlacementType::Front);
+        assert!(spec.validate().is_ok());
+
+        spec.rotation_degrees = 7.0;
+        let (width, height) = spec.get_rotated_dimensions();
+        assert!(width > 1800 && height > 2400);
+        assert!(matches!(
+            spec.validate(),
+            Err(PlacementError::OutOfBoundsHorizontal(..))
+        ));
+
+        spec.scale = 0.5;
+        assert!(spec.validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_rotation() {
+        let mut spec = PlacementSpec::default();
+        for degrees in [180.5, -270.0, f64::NAN] {
+            spec.rotation_degrees = degrees;
+            assert!(matches!(
+                spec.validate(),
+                Err(PlacementError::InvalidRotation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_errors_lists_both_bounds() {
+        let spec = PlacementSpec::new(0.5, 900, -1200, PlacementType::Front);
+        let errors = spec.errors();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            errors[0],
+            PlacementError::OutOfBoundsHorizontal(..)
+        ));
+        assert!(matches!(errors[1], PlacementError::OutOfBoundsVertical(..)));
+
+        // An invalid scale makes the bounds meaningless
+        let spec = PlacementSpec::new(5.0, 900, -1200, PlacementType::Front);
+        assert!(matches!(
+            spec.errors().as_slice(),
+            [PlacementError::InvalidScale(_)]
+        ));
+    }
+
     #[test]
     fn test_coordinate_conversion() {
         let print_spec = PlacementSpec::new(0.5, 100, -50, PlacementType::Front);
@@ -250,4 +521,174 @@ mod tests {
         assert_eq!(display_spec.coordinate_space, CoordinateSpace::Display);
         assert_eq!(display_spec.print_area_width, DISPLAY_TEMPLATE_WIDTH);
     }
+
+    #[test]
+    fn test_display_conversion_scales_each_axis() {
+        let print_spec = PlacementSpec::new(0.5, 180, -240, PlacementType::Front);
+        let display_spec = print_spec.to_display_space();
+        assert_eq!((display_spec.offset_x, display_spec.offset_y), (100, -140));
+        assert_eq!(
+            (
+                display_spec.print_area_width,
+                display_spec.print_area_height
+            ),
+            (DISPLAY_TEMPLATE_WIDTH, DISPLAY_TEMPLATE_HEIGHT)
+        );
+
+        let back = display_spec.to_print_space();
+        assert_eq!((back.offset_x, back.offset_y), (180, -240));
+    }
+
+    #[test]
+    fn test_from_display_for_template() {
+        // Square mug wrap, classic shirt, and wide poster print areas
+        for ((width, height), display_offsets, print_offsets) in [
+            ((1800, 1800), (100, -100), (180, -171)),
+            ((1800, 2400), (100, -140), (180, -240)),
+            ((3600, 1200), (500, 70), (900, 120)),
+        ] {
+            let mut display = PlacementSpec::new(
+                0.3,
+                display_offsets.0,
+                display_offsets.1,
+                PlacementType::Front,
+            );
+            display.coordinate_space = CoordinateSpace::Display;
+
+            let print = PlacementSpec::from_display_for_template(&display, width, height);
+            assert_eq!(print.coordinate_space, CoordinateSpace::Print);
+            assert_eq!(
+                (print.print_area_width, print.print_area_height),
+                (width, height)
+            );
+            assert_eq!(
+                (print.offset_x, print.offset_y),
+                print_offsets,
+                "{}x{}",
+                width,
+                height
+            );
+            assert_eq!(
+                display.in_print_area(width, height).offset_y,
+                print.offset_y
+            );
+        }
+    }
+
+    #[test]
+    fn test_template_round_trip_is_stable() {
+        for (width, height) in [(1800, 1800), (1800, 2400), (3600, 1200)] {
+            for offset in (-900..=900).step_by(7) {
+                let print = PlacementSpec::new(0.2, offset, -offset, PlacementType::Front)
+                    .in_print_area(width, height);
+                let display = print.to_display_for_template(width, height);
+                let back = PlacementSpec::from_display_for_template(&display, width, height);
+                assert!(
+                    (back.offset_x - offset).abs() <= 1,
+                    "{} in {}x{}",
+                    offset,
+                    width,
+                    height
+                );
+                assert!(
+                    (back.offset_y + offset).abs() <= 1,
+                    "{} in {}x{}",
+                    offset,
+                    width,
+                    height
+                );
+
+                // Converting again changes nothing
+                let again = PlacementSpec::from_display_for_template(
+                    &back.to_display_for_template(width, height),
+                    width,
+                    height,
+                );
+                assert_eq!(
+                    (again.offset_x, again.offset_y),
+                    (back.offset_x, back.offset_y)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_print_space_keeps_offsets_in_print_area() {
+        let spec =
+            PlacementSpec::new(0.5, 120, -80, PlacementType::Front).in_print_area(1000, 1000);
+        assert_eq!((spec.offset_x, spec.offset_y), (120, -80));
+        assert_eq!(
+            (spec.print_area_width, spec.print_area_height),
+            (1000, 1000)
+        );
+    }
+
+    fn physical(
+        width_inches: f64,
+        offset_x_inches: f64,
+        offset_y_inches: f64,
+    ) -> PhysicalPlacement {
+        PhysicalPlacement {
+            width_inches,
+            offset_x_inches,
+            offset_y_inches,
+            dpi: None,
+            placement: PlacementType::Front,
+            rotation_degrees: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_from_physical_exact_fit() {
+        // 6 x 8 in at 300 DPI
+        let spec =
+            PlacementSpec::from_physical(&physical(6.0, 0.0, 0.0), None, 1800, 2400).unwrap();
+        assert_eq!(spec.scale, 1.0);
+        assert_eq!(spec.get_design_dimensions(), (1800, 2400));
+        assert!(spec.validate().is_ok());
+
+        // The placement's own DPI wins over the template's
+        let mut at_150 = physical(12.0, 1.0, -2.0);
+        at_150.dpi = Some(150);
+        let spec = PlacementSpec::from_physical(&at_150, Some(300), 1800, 2400).unwrap();
+        assert_eq!(spec.scale, 1.0);
+        assert_eq!((spec.offset_x, spec.offset_y), (150, -300));
+    }
+
+    #[test]
+    fn test_from_physical_overflow_names_the_maximum() {
+        let err =
+            PlacementSpec::from_physical(&physical(6.5, 0.0, 0.0), None, 1800, 2400).unwrap_err();
+        assert!(
+            matches!(err, PlacementError::ExceedsPrintArea(w, max, 300) if w == 6.5 && max == 6.0)
+        );
+        assert!(err.to_string().contains("at most 6.00 in"));
+
+        // The template's DPI decides how many inches fit
+        assert!(
+            PlacementSpec::from_physical(&physical(4.0, 0.0, 0.0), Some(600), 1800, 2400).is_err()
+        );
+
+        for width in [0.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                PlacementSpec::from_physical(&physical(width, 0.0, 0.0), None, 1800, 2400),
+                Err(PlacementError::InvalidPrintSize(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_from_physical_rounds_to_whole_pixels() {
+        // 4.1234 in = 1237.02 px, 0.0017 in = 0.51 px
+        let spec =
+            PlacementSpec::from_physical(&physical(4.1234, 0.0017, -0.0016), None, 1800, 2400)
+                .unwrap();
+        assert_eq!(spec.get_design_dimensions().0, 1237);
+        assert_eq!((spec.offset_x, spec.offset_y), (1, 0));
+
+        // Just over the edge in inches, but not by a whole pixel
+        let spec =
+            PlacementSpec::from_physical(&physical(6.001, 0.0, 0.0), None, 1800, 2400).unwrap();
+        assert_eq!(spec.scale, 1.0);
+    }
 }
@@ -0,0 +1,139 @@
+//! Named placement presets
+//!
+//! Presets let designers ask for "left chest" instead of computing offsets.
+//! Each preset is a scale and an offset from the template's anchor point in
+//! fractions of the print area, so one table fits every print area size.
+//! Templates can override or add presets in their metadata.json.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+use super::placement::{PlacementSpec, PlacementType};
+
+/// A placement relative to a template's print area and anchor point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PlacementPreset {
+    /// Scale factor (0.1 to 1.0) - percentage of print area width
+    pub scale: f64,
+
+    /// Horizontal offset of the design's center from the anchor point, as a
+    /// fraction of the print area width
+    #[serde(default)]
+    pub offset_x: f64,
+
+    /// Vertical offset from the anchor point as a fraction of the print area
+    /// height (negative = up)
+    #[serde(default)]
+    pub offset_y: f64,
+
+    /// Placement type (front, back, etc.)
+    #[serde(default)]
+    pub placement: PlacementType,
+}
+
+impl PlacementPreset {
+    const fn new(scale: f64, offset_x: f64, offset_y: f64, placement: PlacementType) -> Self {
+        PlacementPreset {
+            scale,
+            offset_x,
+            offset_y,
+            placement,
+        }
+    }
+
+    /// Print space placement in a print area
+    ///
+    /// `anchor_offset` is the anchor point's offset from the print area center.
+    /// Offsets are nudged so the design stays inside the print area, which lets
+    /// `full_front` fill it even when the anchor is off center.
+    pub fn to_placement(
+        &self,
+        print_width: i32,
+        print_height: i32,
+        anchor_offset: (i32, i32),
+    ) -> PlacementSpec {
+        let mut spec = PlacementSpec::new(self.scale, 0, 0, self.placement.clone());
+        spec.print_area_width = print_width;
+        spec.print_area_height = print_height;
+
+        let (design_width, design_height) = spec.get_design_dimensions();
+        let offset = |anchor: i32, fraction: f64, area: i32, design: i32| {
+            let max = ((area - design) / 2).max(0);
+            (anchor + (fraction * area as f64).round() as i32).clamp(-max, max)
+        };
+        spec.offset_x = offset(anchor_offset.0, self.offset_x, print_width, design_width);
+        spec.offset_y = offset(anchor_offset.1, self.offset_y, print_height, design_height);
+        spec
+    }
+}
+
+/// Built-in presets, available on every template unless it overrides them
+///
+/// Chest and pocket prints sit on the wearer's left, which is the viewer's right.
+pub fn default_presets() -> BTreeMap<String, PlacementPreset> {
+    [
+        (
+            "center_chest",
+            PlacementPreset::new(0.5, 0.0, -0.15, PlacementType::Front),
+        ),
+        (
+            "left_chest",
+            PlacementPreset::new(0.2, 0.2, -0.3, PlacementType::Front),
+        ),
+        (
+            "full_front",
+            PlacementPreset::new(1.0, 0.0, 0.0, PlacementType::Front),
+        ),
+        (
+            "back_center",
+            PlacementPreset::new(0.45, 0.0, -0.2, PlacementType::Back),
+        ),
+        (
+            "pocket",
+            PlacementPreset::new(0.12, 0.2, -0.28, PlacementType::Front),
+        ),
+    ]
+    .into_iter()
+    .map(|(name, preset)| (name.to_string(), preset))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Classic shirt print area with the anchor at its center
+    fn positions(anchor_offset: (i32, i32)) -> BTreeMap<String, (i32, i32)> {
+        default_presets()
+            .into_iter()
+            .map(|(name, preset)| {
+                let spec = preset.to_placement(1800, 2400, anchor_offset);
+                assert!(spec.validate().is_ok(), "{}", name);
+                (name, spec.get_absolute_position())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_default_preset_positions() {
+        let positions = positions((0, 0));
+        assert_eq!(positions["center_chest"], (450, 240));
+        assert_eq!(positions["left_chest"], (1080, 240));
+        assert_eq!(positions["full_front"], (0, 0));
+        assert_eq!(positions["back_center"], (495, 180));
+        assert_eq!(positions["pocket"], (1152, 384));
+    }
+
+    #[test]
+    fn test_presets_follow_the_anchor_but_stay_inside() {
+        let positions = positions((0, 100));
+        assert_eq!(positions["center_chest"], (450, 340));
+        // Already fills the print area, so it can't move
+        assert_eq!(positions["full_front"], (0, 0));
+
+        let spec = default_presets()["left_chest"].to_placement(1800, 2400, (900, 0));
+        assert_eq!(spec.offset_x, 720);
+        assert!(spec.validate().is_ok());
+    }
+}
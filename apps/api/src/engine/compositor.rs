@@ -4,110 +4,670 @@
 //! and blend modes for photorealistic mockups.
 
 use base64::Engine;
-use bytes::Bytes;
-use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
-use std::net::IpAddr;
+use bytes::{Bytes, BytesMut};
+use image::codecs::webp::{WebPEncoder, WebPQuality};
+use image::{
+    ColorType, DynamicImage, GenericImageView, GrayImage, ImageFormat, Luma, Rgb, RgbImage, Rgba,
+    RgbaImage,
+};
+use jpeg_encoder::{Encoder as JpegEncoder, SamplingFactor};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tracing::{debug, info};
-use url::{Host, Url};
+use utoipa::ToSchema;
 
-use super::displacement::{apply_displacement, apply_opacity};
-use super::template::Template;
-use crate::config::service_user_agent;
+use super::displacement::{apply_displacement, displaces_by_default};
+use super::template::{TemplateImages, TemplateMetadata};
 use crate::domain::PlacementSpec;
+use crate::metrics::GenerationStage;
+use crate::net::{service_user_agent, url_in_domains, UrlGuard, UrlGuardError, UrlPolicy};
 
 /// Compositing errors
 #[derive(Debug, Error)]
 pub enum CompositorError {
     #[error("Invalid design image URL: {0}")]
     InvalidDesignUrl(String),
+    #[error("Design image URL is not allowed: {0}")]
+    DesignUrlForbidden(String),
+    #[error("Design image URL is outside the key's allowed domains: {0}")]
+    DesignDomainNotAllowed(String),
     #[error("Design image is too large: {0} bytes")]
     DesignTooLarge(u64),
+    #[error("Design image is {width}x{height}; the largest accepted is {max}x{max}")]
+    DesignDimensionsTooLarge { width: u32, height: u32, max: u32 },
+    #[error("Design image is {width}x{height}; the smallest accepted is {min}x{min}")]
+    DesignTooSmall { width: u32, height: u32, min: u32 },
+    #[error("Unsupported design image format: {0}")]
+    UnsupportedDesignFormat(String),
     #[error("Failed to fetch design image: {0}")]
     FetchFailed(String),
     #[error("Failed to decode image: {0}")]
     DecodeFailed(#[from] image::ImageError),
     #[error("HTTP error: {0}")]
     HttpError(#[from] reqwest::Error),
+    #[error("Failed to encode image: {0}")]
+    EncodeFailed(String),
+    /// A design of a multi-design request failed; `index` is its position in `designs`
+    #[error("designs[{index}]: {source}")]
+    Design {
+        index: usize,
+        source: Box<CompositorError>,
+    },
+    #[error("Generation timed out: {0}")]
+    TimedOut(String),
+    #[error("Generation cancelled: nobody is waiting for the result")]
+    Cancelled,
+}
+
+impl CompositorError {
+    /// Error code for a design rejected by the `DesignLimits` checks, looking
+    /// through the index of a multi-design request
+    pub fn design_code(&self) -> Option<&'static str> {
+        match self {
+            CompositorError::DesignTooLarge(_)
+            | CompositorError::DesignDimensionsTooLarge { .. } => Some("DESIGN_TOO_LARGE"),
+            CompositorError::DesignTooSmall { .. } => Some("DESIGN_TOO_SMALL"),
+            CompositorError::UnsupportedDesignFormat(_) => Some("DESIGN_UNSUPPORTED_FORMAT"),
+            CompositorError::DesignUrlForbidden(_) => Some("DESIGN_URL_FORBIDDEN"),
+            CompositorError::DesignDomainNotAllowed(_) => Some("DESIGN_DOMAIN_NOT_ALLOWED"),
+            CompositorError::Design { source, .. } => source.design_code(),
+            _ => None,
+        }
+    }
+}
+
+impl From<UrlGuardError> for CompositorError {
+    fn from(e: UrlGuardError) -> Self {
+        match e {
+            UrlGuardError::Invalid(message) => CompositorError::InvalidDesignUrl(message),
+            UrlGuardError::Forbidden(message) => CompositorError::DesignUrlForbidden(message),
+            e @ UrlGuardError::Resolve { .. } => CompositorError::FetchFailed(e.to_string()),
+            UrlGuardError::OutsideDomains(message) => {
+                CompositorError::DesignDomainNotAllowed(message)
+            }
+        }
+    }
+}
+
+/// Where the design image comes from
+#[derive(Clone)]
+pub enum DesignSource {
+    /// Fetched over HTTP(S) with the URL and size checks applied
+    Url(String),
+    /// Raw image bytes uploaded with the request
+    Bytes(Bytes),
+}
+
+impl fmt::Debug for DesignSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DesignSource::Url(url) => f.debug_tuple("Url").field(url).finish(),
+            DesignSource::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+        }
+    }
+}
+
+/// Encoded output format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Lossless, keeps transparency
+    #[default]
+    Png,
+    /// Lossy, no alpha channel: transparent pixels are flattened onto a background
+    #[serde(alias = "jpg")]
+    Jpeg,
+    /// Lossy, keeps transparency
+    Webp,
+}
+
+impl OutputFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Webp => "image/webp",
+        }
+    }
+
+    /// File extension for saved outputs
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Default quality for lossy formats
+pub const DEFAULT_OUTPUT_QUALITY: u8 = 85;
+
+/// How JPEG chroma is sampled relative to luma
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum ChromaSubsampling {
+    /// Full-resolution color; sharpest edges on saturated artwork
+    #[serde(rename = "4:4:4")]
+    Yuv444,
+    /// Half horizontal color resolution
+    #[serde(rename = "4:2:2")]
+    Yuv422,
+    /// Quarter color resolution; smallest files
+    #[serde(rename = "4:2:0")]
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    fn sampling_factor(&self) -> SamplingFactor {
+        match self {
+            ChromaSubsampling::Yuv444 => SamplingFactor::R_4_4_4,
+            ChromaSubsampling::Yuv422 => SamplingFactor::R_4_2_2,
+            ChromaSubsampling::Yuv420 => SamplingFactor::R_4_2_0,
+        }
+    }
+}
+
+/// Named JPEG quality and chroma subsampling combinations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JpegPreset {
+    /// Thumbnails and listing grids
+    Web,
+    /// Product pages
+    #[default]
+    Standard,
+    /// Zoomable product images
+    High,
+    /// Print proofs; near-lossless
+    Print,
+}
+
+impl JpegPreset {
+    pub fn quality(&self) -> u8 {
+        match self {
+            JpegPreset::Web => 75,
+            JpegPreset::Standard => DEFAULT_OUTPUT_QUALITY,
+            JpegPreset::High => 92,
+            JpegPreset::Print => 98,
+        }
+    }
+
+    pub fn chroma_subsampling(&self) -> ChromaSubsampling {
+        match self {
+            JpegPreset::Web | JpegPreset::Standard => ChromaSubsampling::Yuv420,
+            JpegPreset::High | JpegPreset::Print => ChromaSubsampling::Yuv444,
+        }
+    }
+}
+
+/// How the finished mockup is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputSettings {
+    pub format: OutputFormat,
+    /// Quality for lossy formats (1-100)
+    pub quality: u8,
+    /// Chroma subsampling for JPEG output
+    pub chroma_subsampling: ChromaSubsampling,
+    /// Background that JPEG output is flattened onto
+    pub background: (u8, u8, u8),
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettings {
+            format: OutputFormat::Png,
+            quality: DEFAULT_OUTPUT_QUALITY,
+            chroma_subsampling: JpegPreset::default().chroma_subsampling(),
+            background: (255, 255, 255),
+        }
+    }
+}
+
+impl OutputSettings {
+    /// Build output settings from request options, validating quality and color
+    pub fn new(
+        format: OutputFormat,
+        quality: Option<u8>,
+        background_color: Option<&str>,
+    ) -> Result<Self, String> {
+        Self::with_preset(
+            format,
+            JpegPreset::default(),
+            quality,
+            None,
+            background_color,
+        )
+    }
+
+    /// Like [`OutputSettings::new`], with JPEG defaults taken from `preset`
+    ///
+    /// An explicit `quality` or `chroma_subsampling` overrides the preset.
+    pub fn with_preset(
+        format: OutputFormat,
+        preset: JpegPreset,
+        quality: Option<u8>,
+        chroma_subsampling: Option<ChromaSubsampling>,
+        background_color: Option<&str>,
+    ) -> Result<Self, String> {
+        let default_quality = match format {
+            OutputFormat::Jpeg => preset.quality(),
+            _ => DEFAULT_OUTPUT_QUALITY,
+        };
+        let quality = quality.unwrap_or(default_quality);
+        if !(1..=100).contains(&quality) {
+            return Err(format!("quality {} must be between 1 and 100", quality));
+        }
+        let background = match background_color {
+            Some(hex) => parse_hex_color(hex)
+                .ok_or_else(|| format!("background_color '{}' is not a hex color", hex))?,
+            None => (255, 255, 255),
+        };
+
+        Ok(OutputSettings {
+            format,
+            quality,
+            chroma_subsampling: chroma_subsampling.unwrap_or(preset.chroma_subsampling()),
+            background,
+        })
+    }
+}
+
+/// Blend modes a design can be composited with
+pub const BLEND_MODES: &[&str] = &["normal", "multiply", "screen", "overlay"];
+
+/// One design composited onto the template
+#[derive(Debug, Clone)]
+pub struct DesignLayer {
+    pub design: DesignSource,
+    pub placement: PlacementSpec,
+    pub displacement_strength: f64,
+    /// Overrides the template's blend mode for this design
+    pub blend_mode: Option<String>,
+}
+
+/// How white design backgrounds are found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundRemovalMode {
+    /// Leave the design untouched
+    None,
+    /// Every white or near-white pixel, wherever it is
+    #[default]
+    Luminance,
+    /// Only white regions connected to the image border; enclosed white areas
+    /// such as letter counters and highlights stay opaque
+    BorderFlood,
+}
+
+/// Thresholds for turning a white design background transparent, on pixel
+/// luminance (0-255). Only near-neutral pixels are affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct BackgroundRemoval {
+    /// Which white pixels are removed; set from `background_removal_mode`
+    #[serde(skip)]
+    pub mode: BackgroundRemovalMode,
+    /// Pixels at or above this luminance become fully transparent (default 245)
+    pub white_threshold: u8,
+    /// Pixels from here up to `white_threshold` are partially transparent (default 230)
+    pub light_threshold: u8,
+    /// Luminance range below `light_threshold` feathered for smooth edges (default 25)
+    pub feather: u8,
+}
+
+impl Default for BackgroundRemoval {
+    fn default() -> Self {
+        BackgroundRemoval {
+            mode: BackgroundRemovalMode::default(),
+            white_threshold: 245,
+            light_threshold: 230,
+            feather: 25,
+        }
+    }
+}
+
+impl BackgroundRemoval {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.light_threshold > self.white_threshold {
+            return Err(format!(
+                "light_threshold ({}) must not exceed white_threshold ({})",
+                self.light_threshold, self.white_threshold
+            ));
+        }
+        if self.feather > self.light_threshold {
+            return Err(format!(
+                "feather ({}) must not exceed light_threshold ({})",
+                self.feather, self.light_threshold
+            ));
+        }
+        Ok(())
+    }
+
+    /// Alpha for a pixel: 0 for white background, 255 for design content,
+    /// partial for off-white and the feathered edge between them
+    fn alpha_for(&self, [r, g, b, _]: [u8; 4]) -> u8 {
+        let BackgroundRemoval {
+            white_threshold,
+            light_threshold,
+            feather,
+            ..
+        } = *self;
+
+        // Calculate luminance (human eye weighted)
+        let luminance = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) as u8;
+
+        // Check color variance (white has low variance between channels)
+        let max_channel = r.max(g).max(b);
+        let min_channel = r.min(g).min(b);
+        let variance = max_channel - min_channel;
+
+        // Detect white/near-white: high luminance + low color variance
+        if luminance >= white_threshold && variance <= 15 {
+            // Pure white - fully transparent
+            0
+        } else if luminance >= light_threshold && variance <= 25 {
+            // Light gray/off-white - gradual transparency based on how white
+            ((255 - luminance) as f32 / (255 - light_threshold) as f32 * 255.0).min(255.0) as u8
+        } else if luminance >= light_threshold.saturating_sub(feather) && variance <= 35 {
+            // Edge feathering zone
+            ((light_threshold - luminance.saturating_sub(feather)) as f32 / feather as f32 * 255.0)
+                .min(255.0) as u8
+        } else {
+            // Keep pixel fully opaque
+            255
+        }
+    }
+}
+
+/// Clear background pixels connected to the image border
+///
+/// Breadth-first over a queue with a visited mask, so the cost is linear in
+/// the pixel count and large designs can't overflow the stack. Pixels are
+/// marked when queued, which keeps the queue no longer than the image.
+fn flood_border_background(image: &mut RgbaImage, removal: &BackgroundRemoval) {
+    let (width, height) = image.dimensions();
+    let mut reached = vec![false; width as usize * height as usize];
+    let mut queue = VecDeque::new();
+
+    let mut visit = |image: &mut RgbaImage, queue: &mut VecDeque<(u32, u32)>, x: u32, y: u32| {
+        let index = y as usize * width as usize + x as usize;
+        if reached[index] {
+            return;
+        }
+        let pixel = image.get_pixel_mut(x, y);
+        let alpha = removal.alpha_for(pixel.0);
+        if alpha == 255 {
+            return;
+        }
+        reached[index] = true;
+        pixel.0[3] = alpha;
+        queue.push_back((x, y));
+    };
+
+    for x in 0..width {
+        visit(image, &mut queue, x, 0);
+        visit(image, &mut queue, x, height - 1);
+    }
+    for y in 0..height {
+        visit(image, &mut queue, 0, y);
+        visit(image, &mut queue, width - 1, y);
+    }
+
+    while let Some((x, y)) = queue.pop_front() {
+        if x > 0 {
+            visit(image, &mut queue, x - 1, y);
+        }
+        if x + 1 < width {
+            visit(image, &mut queue, x + 1, y);
+        }
+        if y > 0 {
+            visit(image, &mut queue, x, y - 1);
+        }
+        if y + 1 < height {
+            visit(image, &mut queue, x, y + 1);
+        }
+    }
 }
 
 /// Request for mockup generation
 #[derive(Debug, Clone)]
 pub struct MockupRequest {
-    pub design_url: String,
+    /// Designs composited in order onto the same template base
+    pub designs: Vec<DesignLayer>,
     pub template_id: String,
-    pub placement: PlacementSpec,
-    pub displacement_strength: f64,
+    /// Forces the displacement pass on or off; `None` follows the product type default
+    pub apply_displacement: Option<bool>,
     pub tint_color: Option<String>,
+    /// Remove white design backgrounds; designs with any transparency are left as-is
+    pub remove_background: Option<BackgroundRemoval>,
+    pub output: OutputSettings,
+    pub limits: GenerationLimits,
 }
 
-/// Result of mockup generation
-pub struct MockupResult {
-    pub url: String,
-    pub width: u32,
-    pub height: u32,
-    pub bytes: Bytes,
+/// Time limits and cancellation for one generation
+///
+/// Checked between pipeline stages: before fetching, before compositing each
+/// design, and before encoding. A stage already running finishes first.
+#[derive(Debug, Clone, Default)]
+pub struct GenerationLimits {
+    /// How long fetching each design may take; `None` keeps the HTTP client's default
+    pub fetch_timeout: Option<Duration>,
+    /// When the whole generation must be done
+    pub deadline: Option<Instant>,
+    /// Size and format limits for designs fetched from a URL
+    pub design: DesignLimits,
+    /// Domains design URLs and their redirects must stay on; empty allows any
+    pub design_domains: Vec<String>,
+    /// Set once nobody is waiting for the result
+    cancelled: Option<Arc<AtomicBool>>,
+}
+
+/// Limits a design fetched from a URL must meet
+#[derive(Debug, Clone, Copy)]
+pub struct DesignLimits {
+    /// Largest download, checked against Content-Length and while streaming
+    pub max_bytes: u64,
+    /// Smallest accepted width and height
+    pub min_dimension: u32,
+    /// Largest accepted width and height
+    pub max_dimension: u32,
 }
 
-const MAX_DESIGN_IMAGE_BYTES: u64 = 10 * 1024 * 1024;
+impl Default for DesignLimits {
+    fn default() -> Self {
+        DesignLimits {
+            max_bytes: 10 * 1024 * 1024,
+            min_dimension: 50,
+            max_dimension: 10_000,
+        }
+    }
+}
 
-fn validate_fetch_url(url: &str) -> Result<Url, CompositorError> {
-    let parsed = Url::parse(url)
-        .map_err(|_| CompositorError::InvalidDesignUrl("must be an absolute URL".to_string()))?;
+impl DesignLimits {
+    /// Reject a Content-Type other than PNG, JPEG, or WebP
+    fn check_content_type(&self, content_type: &str) -> Result<(), CompositorError> {
+        let media = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match media.as_str() {
+            "image/png" | "image/jpeg" | "image/jpg" | "image/webp" => Ok(()),
+            // Object stores often serve untyped bodies; the bytes are sniffed instead
+            "application/octet-stream" => Ok(()),
+            _ => Err(CompositorError::UnsupportedDesignFormat(format!(
+                "content type '{}' is not PNG, JPEG, or WebP",
+                media
+            ))),
+        }
+    }
 
-    match parsed.scheme() {
-        "http" | "https" => {}
-        scheme => {
-            return Err(CompositorError::InvalidDesignUrl(format!(
-                "scheme '{}' is not supported",
-                scheme
+    /// Check the sniffed format and the dimensions from the image header,
+    /// without decoding the pixels
+    fn check_image(&self, bytes: &[u8]) -> Result<(), CompositorError> {
+        let format = image::guess_format(bytes).map_err(|_| {
+            CompositorError::UnsupportedDesignFormat("body is not an image".to_string())
+        })?;
+        if !matches!(
+            format,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP
+        ) {
+            return Err(CompositorError::UnsupportedDesignFormat(format!(
+                "{:?} is not PNG, JPEG, or WebP",
+                format
             )));
         }
-    }
 
-    let host = parsed
-        .host()
-        .ok_or_else(|| CompositorError::InvalidDesignUrl("host is required".to_string()))?;
-    if is_blocked_host(host) {
-        return Err(CompositorError::InvalidDesignUrl(
-            "local and private network hosts are not allowed".to_string(),
-        ));
+        let (width, height) = image::io::Reader::with_format(std::io::Cursor::new(bytes), format)
+            .into_dimensions()?;
+        if width > self.max_dimension || height > self.max_dimension {
+            return Err(CompositorError::DesignDimensionsTooLarge {
+                width,
+                height,
+                max: self.max_dimension,
+            });
+        }
+        if width < self.min_dimension || height < self.min_dimension {
+            return Err(CompositorError::DesignTooSmall {
+                width,
+                height,
+                min: self.min_dimension,
+            });
+        }
+        Ok(())
     }
+}
 
-    Ok(parsed)
+/// Cancels its generation when dropped
+pub struct CancelGuard(Arc<AtomicBool>);
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
 }
 
-fn is_blocked_host(host: Host<&str>) -> bool {
-    match host {
-        Host::Domain(domain) => {
-            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
-            domain == "localhost" || domain.ends_with(".localhost")
+impl GenerationLimits {
+    /// Cancel the generation once the returned guard is dropped
+    ///
+    /// Handlers hold the guard, so a client disconnect, which drops the handler
+    /// future, also stops work still running on other threads.
+    pub fn cancel_on_drop(&mut self) -> CancelGuard {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.cancelled = Some(cancelled.clone());
+        CancelGuard(cancelled)
+    }
+
+    /// Fail if the generation was cancelled or is past its deadline
+    pub(super) fn check(&self, stage: &str) -> Result<(), CompositorError> {
+        if let Some(cancelled) = &self.cancelled {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(CompositorError::Cancelled);
+            }
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(CompositorError::TimedOut(
+                format!("deadline passed before {}", stage),
+            )),
+            _ => Ok(()),
         }
-        Host::Ipv4(ip) => is_blocked_ip(IpAddr::V4(ip)),
-        Host::Ipv6(ip) => is_blocked_ip(IpAddr::V6(ip)),
     }
-}
 
-fn is_blocked_ip(ip: IpAddr) -> bool {
-    match ip {
-        IpAddr::V4(ip) => {
-            ip.is_private()
-                || ip.is_loopback()
-                || ip.is_link_local()
-                || ip.is_broadcast()
-                || ip.is_documentation()
-                || ip.is_unspecified()
+    /// Time a design fetch may take: the fetch timeout, cut short by the deadline
+    fn fetch_budget(&self) -> Option<Duration> {
+        let remaining = self
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        match (self.fetch_timeout, remaining) {
+            (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+            (timeout, remaining) => timeout.or(remaining),
         }
-        IpAddr::V6(ip) => {
-            ip.is_loopback()
-                || ip.is_unspecified()
-                || ip.is_unique_local()
-                || ip.is_unicast_link_local()
+    }
+}
+
+/// Time one generation spent in each pipeline stage
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StageTimings {
+    pub fetch: Duration,
+    pub displacement: Duration,
+    /// Everything between fetching and encoding except displacement: tinting,
+    /// resizing, blending, and preserve zones
+    pub composite: Duration,
+    pub encode: Duration,
+}
+
+impl StageTimings {
+    pub fn stages(&self) -> [(GenerationStage, Duration); 4] {
+        [
+            (GenerationStage::Fetch, self.fetch),
+            (GenerationStage::Displacement, self.displacement),
+            (GenerationStage::Composite, self.composite),
+            (GenerationStage::Encode, self.encode),
+        ]
+    }
+}
+
+/// Result of mockup generation
+pub struct MockupResult {
+    pub width: u32,
+    pub height: u32,
+    pub content_type: &'static str,
+    pub bytes: Bytes,
+    pub timings: StageTimings,
+}
+
+impl MockupResult {
+    /// Encoded image as a base64 data URI
+    pub fn data_uri(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.content_type,
+            base64::engine::general_purpose::STANDARD.encode(&self.bytes)
+        )
+    }
+}
+
+/// Name the failing design of a multi-design request; deadlines and
+/// cancellation apply to the whole request
+fn design_error(index: usize, count: usize, e: CompositorError) -> CompositorError {
+    if count > 1 && !matches!(e, CompositorError::TimedOut(_) | CompositorError::Cancelled) {
+        CompositorError::Design {
+            index,
+            source: Box::new(e),
         }
+    } else {
+        e
+    }
+}
+
+/// sRGB-encoded channel to linear light (0.0-1.0)
+pub(super) fn srgb_to_linear(value: u8) -> f32 {
+    let v = value as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
     }
 }
 
+/// Linear light (0.0-1.0) back to an sRGB-encoded channel
+fn linear_to_srgb(value: f32) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let encoded = if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
 /// Parse a hex color string (with or without leading '#') into (r, g, b)
 fn parse_hex_color(hex: &str) -> Option<(u8, u8, u8)> {
     let hex = hex.strip_prefix('#').unwrap_or(hex);
@@ -120,103 +680,93 @@ fn parse_hex_color(hex: &str) -> Option<(u8, u8, u8)> {
     Some((r, g, b))
 }
 
+/// Separable per-channel blend function (channels in 0.0-1.0) for a blend mode name
+fn blend_function(blend_mode: &str) -> fn(f32, f32) -> f32 {
+    match blend_mode {
+        "multiply" => |b, o| b * o,
+        "screen" => |b, o| 1.0 - (1.0 - b) * (1.0 - o),
+        "overlay" => |b, o| {
+            if b < 0.5 {
+                2.0 * b * o
+            } else {
+                1.0 - 2.0 * (1.0 - b) * (1.0 - o)
+            }
+        },
+        _ => |_, o| o,
+    }
+}
+
+/// Composite `overlay` over the RGBA `base` pixel in place using the separable
+/// `blend` function, computed in premultiplied alpha.
+///
+/// Overlay coverage is scaled by the base alpha so designs follow the
+/// template's silhouette: fully transparent base pixels are left unchanged
+/// and opaque base pixels stay opaque. The math is f32, so a channel can land
+/// ±1 from an f64 evaluation where the result sits on a rounding boundary.
+fn composite_pixel(base: &mut [u8], overlay: [u8; 4], blend: fn(f32, f32) -> f32) {
+    let base_alpha = base[3] as f32 / 255.0;
+    let src_alpha = overlay[3] as f32 / 255.0 * base_alpha;
+    if src_alpha == 0.0 {
+        return;
+    }
+
+    let out_alpha = src_alpha + base_alpha * (1.0 - src_alpha);
+    for (channel, &o) in base[..3].iter_mut().zip(&overlay[..3]) {
+        let b = *channel as f32 / 255.0;
+        let o = o as f32 / 255.0;
+        let premultiplied = src_alpha * (1.0 - base_alpha) * o
+            + src_alpha * base_alpha * blend(b, o)
+            + (1.0 - src_alpha) * base_alpha * b;
+        *channel = (premultiplied / out_alpha * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8;
+    }
+    base[3] = (out_alpha * 255.0).round() as u8;
+}
+
 /// Image compositor for generating mockups
 pub struct Compositor {
-    http_client: reqwest::Client,
+    /// Fetches design URLs, refusing internal addresses
+    urls: UrlGuard,
 }
 
 impl Compositor {
     /// Create a new compositor
     pub fn new() -> Self {
-        let http_client = reqwest::Client::builder()
+        Self::with_url_policy(UrlPolicy::default())
+    }
+
+    /// Create a compositor whose design fetches follow `policy`
+    pub fn with_url_policy(policy: UrlPolicy) -> Self {
+        let builder = reqwest::Client::builder()
             .timeout(std::time::Duration::from_secs(30))
-            .user_agent(service_user_agent())
-            .build()
-            .expect("Failed to create HTTP client");
+            .user_agent(service_user_agent());
+        let urls = UrlGuard::new(policy, builder).expect("Failed to create HTTP client");
 
-        Compositor { http_client }
+        Compositor { urls }
     }
 
     /// Generate a mockup from a request and template
     pub async fn generate(
         &self,
         request: &MockupRequest,
-        template: &Arc<Template>,
+        metadata: &TemplateMetadata,
+        images: &TemplateImages,
     ) -> Result<MockupResult, CompositorError> {
         debug!(
-            design_url = %request.design_url,
+            designs = request.designs.len(),
             template_id = %request.template_id,
-            displacement = request.displacement_strength,
             "Starting mockup generation"
         );
 
-        // 1. Fetch design image
-        let design = self.fetch_design(&request.design_url).await?;
-
-        // NOTE: White background removal is intentionally skipped for seamless/AOP patterns.
-        // Patterns fill the entire print area — removing white would punch holes in the design.
-        // Re-enable only for logo/artwork-on-white-bg use cases.
-        // let design = self.remove_white_background(&design);
-
-        // 2. Resize design according to placement
-        let (design_width, design_height) = request.placement.get_design_dimensions();
-        let resized_design = design.resize_exact(
-            design_width as u32,
-            design_height as u32,
-            image::imageops::FilterType::Lanczos3,
-        );
-
-        // 4. Composite position (needed before displacement crop)
-        let (rel_x, rel_y) = request.placement.get_absolute_position();
-        let abs_x = rel_x + template.metadata.print_area.x as i32;
-        let abs_y = rel_y + template.metadata.print_area.y as i32;
-
-        // Build an optional local print mask (same dimensions as design) from the full-canvas template mask.
-        // White/non-zero = printable pixel; zero = skip compositing.
-        let print_mask_region = template.print_mask.as_ref().map(|mask| {
-            Self::crop_mask_region(
-                mask,
-                abs_x,
-                abs_y,
-                design_width as u32,
-                design_height as u32,
-            )
-        });
-
-        // 3. Apply displacement mapping if available
-        // Crop the displacement map to the exact region where the design lands,
-        // so fabric wrinkles match the actual print position (not the whole shirt scaled down).
-        // If a print mask exists, displacement is only meaningful where the print mask is non-zero.
-        let mask_has_printable_pixels = print_mask_region
-            .as_ref()
-            .map_or(true, |m| Self::mask_has_nonzero(m));
-        let processed_design = if !mask_has_printable_pixels {
-            resized_design
-        } else if let Some(ref disp_map) = template.displacement_map {
-            if template.metadata.displacement.enabled {
-                let (disp_w, disp_h) = disp_map.dimensions();
-                let crop_x = (abs_x.max(0) as u32).min(disp_w.saturating_sub(1));
-                let crop_y = (abs_y.max(0) as u32).min(disp_h.saturating_sub(1));
-                let crop_w = (design_width as u32).min(disp_w.saturating_sub(crop_x));
-                let crop_h = (design_height as u32).min(disp_h.saturating_sub(crop_y));
-                let disp_crop = disp_map.crop_imm(crop_x, crop_y, crop_w, crop_h);
-                apply_displacement(&resized_design, &disp_crop, request.displacement_strength)
-            } else {
-                resized_design
-            }
-        } else {
-            resized_design
-        };
+        let mut timings = StageTimings::default();
 
-        debug!(
-            rel_x = rel_x,
-            rel_y = rel_y,
-            print_area_x = template.metadata.print_area.x,
-            print_area_y = template.metadata.print_area.y,
-            abs_x = abs_x,
-            abs_y = abs_y,
-            "Calculated design position"
-        );
+        // 1. Fetch or decode every design
+        request.limits.check("fetching designs")?;
+        let stage = Instant::now();
+        let designs = self.load_designs(&request.designs, &request.limits).await?;
+        timings.fetch = stage.elapsed();
+        let stage = Instant::now();
 
         // Apply product color tinting if requested (skip for white / no tint)
         let tinted_base;
@@ -231,29 +781,31 @@ impl Compositor {
         }) {
             Some((r, g, b)) => {
                 debug!(r, g, b, "Applying product tint");
-                tinted_base = Self::tint_template(&template.base_image, r, g, b);
+                tinted_base = Self::tint_template(&images.base_image, r, g, b);
                 &tinted_base
             }
-            None => &template.base_image,
+            None => &images.base_image,
         };
 
-        let mut composited = self.composite_design(
-            base_ref,
-            &processed_design,
-            abs_x,
-            abs_y,
-            template.metadata.default_opacity,
-            &template.metadata.blend_mode,
-            print_mask_region.as_ref(),
-        );
+        // Each design lands on the result of the previous one
+        let mut composited = None;
+        for (layer, design) in request.designs.iter().zip(designs) {
+            request.limits.check("compositing")?;
+            let base = composited.as_ref().unwrap_or(base_ref);
+            let (layer_image, displacement) =
+                self.composite_layer(request, layer, &design, base, metadata, images);
+            timings.displacement += displacement;
+            composited = Some(layer_image);
+        }
+        let mut composited = composited.unwrap_or_else(|| base_ref.clone());
 
         // 5. Preserve zones — restore original base pixels where preserve masks are white/non-zero.
         // If preserve masks are not configured, keep legacy collar_zone fallback behavior.
-        if !template.preserve_masks.is_empty() {
-            for preserve_mask in &template.preserve_masks {
+        if !images.preserve_masks.is_empty() {
+            for preserve_mask in &images.preserve_masks {
                 composited = Self::restore_from_mask(base_ref, &composited, preserve_mask);
             }
-        } else if let Some(ref cz) = template.metadata.collar_zone {
+        } else if let Some(ref cz) = metadata.collar_zone {
             debug!(
                 x = cz.x,
                 y = cz.y,
@@ -274,68 +826,314 @@ impl Compositor {
             composited = DynamicImage::ImageRgba8(comp_rgba);
         }
 
-        // 6. Encode to PNG (preserves RGBA transparency)
+        timings.composite = stage.elapsed().saturating_sub(timings.displacement);
+
+        // 6. Encode in the requested format
+        request.limits.check("encoding")?;
+        let stage = Instant::now();
         let (width, height) = composited.dimensions();
-        let png_bytes = self.encode_png(&composited)?;
+        let encoded = Self::encode(&composited, &request.output)?;
+        timings.encode = stage.elapsed();
+        let content_type = request.output.format.content_type();
 
         info!(
             width = width,
             height = height,
-            bytes = png_bytes.len(),
-            "Mockup generation complete (PNG with transparency)"
+            bytes = encoded.len(),
+            content_type = content_type,
+            "Mockup generation complete"
         );
 
-        // For now, return the bytes directly (Cloudinary upload can be added later)
         Ok(MockupResult {
-            url: format!(
-                "data:image/png;base64,{}",
-                base64::engine::general_purpose::STANDARD.encode(&png_bytes)
-            ),
             width,
             height,
-            bytes: Bytes::from(png_bytes),
+            content_type,
+            bytes: Bytes::from(encoded),
+            timings,
         })
     }
 
-    /// Fetch design image from URL
-    async fn fetch_design(&self, url: &str) -> Result<DynamicImage, CompositorError> {
-        debug!(url = %url, "Fetching design image");
+    /// Resize, rotate, displace, and composite one design onto `base`
+    ///
+    /// Also returns the time spent on displacement.
+    fn composite_layer(
+        &self,
+        request: &MockupRequest,
+        layer: &DesignLayer,
+        design: &DynamicImage,
+        base: &DynamicImage,
+        metadata: &TemplateMetadata,
+        images: &TemplateImages,
+    ) -> (DynamicImage, Duration) {
+        // 1. White background removal is opt-in: seamless/AOP patterns and light
+        // logos have genuine white fills that removal would punch holes in
+        let cleaned;
+        let design = match request.remove_background {
+            Some(ref removal) if !Self::has_transparency(design) => {
+                cleaned = self.remove_white_background(design, removal);
+                &cleaned
+            }
+            _ => design,
+        };
 
-        validate_fetch_url(url)?;
-        let response = self.http_client.get(url).send().await?;
+        // 2. Resize design according to placement
+        let (design_width, design_height) = layer.placement.get_design_dimensions();
+        let resized_design = design.resize_exact(
+            design_width as u32,
+            design_height as u32,
+            image::imageops::FilterType::Lanczos3,
+        );
 
-        if !response.status().is_success() {
-            return Err(CompositorError::FetchFailed(format!(
-                "HTTP {}: {}",
-                response.status(),
-                url
-            )));
-        }
+        // Rotate onto a canvas the size of the rotated bounding box; everything
+        // below works on that canvas
+        let (design_width, design_height) = layer.placement.get_rotated_dimensions();
+        let resized_design = if layer.placement.rotation_degrees != 0.0 {
+            Self::rotate_design(
+                &resized_design,
+                layer.placement.rotation_degrees,
+                design_width as u32,
+                design_height as u32,
+            )
+        } else {
+            resized_design
+        };
 
-        if let Some(content_length) = response.content_length() {
-            if content_length > MAX_DESIGN_IMAGE_BYTES {
-                return Err(CompositorError::DesignTooLarge(content_length));
-            }
-        }
+        // 3. Where the design lands on the base image; the mask and displacement
+        // map are both read from this region
+        let (rel_x, rel_y) = layer.placement.get_absolute_position();
+        let abs_x = rel_x + metadata.print_area.x;
+        let abs_y = rel_y + metadata.print_area.y;
 
-        let bytes = response.bytes().await?;
-        if bytes.len() as u64 > MAX_DESIGN_IMAGE_BYTES {
-            return Err(CompositorError::DesignTooLarge(bytes.len() as u64));
-        }
+        // Build an optional local print mask (same dimensions as design) from the full-canvas template mask.
+        // White/non-zero = printable pixel; zero = skip compositing.
+        let print_mask_region = images.print_mask.as_ref().map(|mask| {
+            Self::crop_mask_region(
+                mask,
+                abs_x,
+                abs_y,
+                design_width as u32,
+                design_height as u32,
+            )
+        });
 
-        let image = image::load_from_memory(&bytes)?;
+        // 4. Apply displacement mapping if available, sampling the map under the
+        // design so fabric wrinkles line up with the print position.
+        // If a print mask exists, displacement is only meaningful where the print mask is non-zero.
+        let mask_has_printable_pixels = print_mask_region
+            .as_ref()
+            .map_or(true, |m| Self::mask_has_nonzero(m));
+        let displacing = Instant::now();
+        let processed_design = match &images.displacement_map {
+            Some(disp_map)
+                if mask_has_printable_pixels
+                    && metadata.displacement.enabled
+                    && Self::should_displace(request, metadata) =>
+            {
+                let disp_region = Self::displacement_region(
+                    disp_map,
+                    images.base_image.dimensions(),
+                    abs_x,
+                    abs_y,
+                    design_width as u32,
+                    design_height as u32,
+                );
+                let strength_region = images.strength_map.as_ref().map(|strength_map| {
+                    DynamicImage::ImageLuma8(Self::displacement_region(
+                        strength_map,
+                        images.base_image.dimensions(),
+                        abs_x,
+                        abs_y,
+                        design_width as u32,
+                        design_height as u32,
+                    ))
+                });
+                apply_displacement(
+                    &resized_design,
+                    &DynamicImage::ImageLuma8(disp_region),
+                    strength_region.as_ref(),
+                    layer.displacement_strength,
+                )
+            }
+            _ => resized_design,
+        };
+        let displacement = displacing.elapsed();
 
         debug!(
-            width = image.width(),
-            height = image.height(),
-            "Design image loaded"
-        );
-
-        Ok(image)
+            rel_x = rel_x,
+            rel_y = rel_y,
+            print_area_x = metadata.print_area.x,
+            print_area_y = metadata.print_area.y,
+            abs_x = abs_x,
+            abs_y = abs_y,
+            "Calculated design position"
+        );
+
+        let composited = self.composite_design(
+            base,
+            &processed_design,
+            abs_x,
+            abs_y,
+            metadata.default_opacity,
+            layer.blend_mode.as_deref().unwrap_or(&metadata.blend_mode),
+            print_mask_region.as_ref(),
+        );
+        (composited, displacement)
+    }
+
+    /// Fetch or decode every design concurrently; a multi-design request
+    /// reports which design failed
+    pub(super) async fn load_designs(
+        &self,
+        designs: &[DesignLayer],
+        limits: &GenerationLimits,
+    ) -> Result<Vec<DynamicImage>, CompositorError> {
+        futures::future::try_join_all(designs.iter().enumerate().map(|(index, layer)| async move {
+            let design = match &layer.design {
+                DesignSource::Url(url) => self.fetch_design(url, limits).await,
+                DesignSource::Bytes(bytes) => Self::decode_design(bytes),
+            };
+            design.map_err(|e| design_error(index, designs.len(), e))
+        }))
+        .await
+    }
+
+    /// Replace URL designs with their downloaded bytes, so a request can be
+    /// fingerprinted by design content without fetching twice
+    pub async fn download_designs(
+        &self,
+        designs: &mut [DesignLayer],
+        limits: &GenerationLimits,
+    ) -> Result<(), CompositorError> {
+        limits.check("fetching designs")?;
+        let count = designs.len();
+        let downloaded = futures::future::try_join_all(designs.iter().enumerate().map(
+            |(index, layer)| async move {
+                match &layer.design {
+                    DesignSource::Url(url) => self
+                        .fetch_design_bytes(url, limits)
+                        .await
+                        .map(Some)
+                        .map_err(|e| design_error(index, count, e)),
+                    DesignSource::Bytes(_) => Ok(None),
+                }
+            },
+        ))
+        .await?;
+        for (layer, bytes) in designs.iter_mut().zip(downloaded) {
+            if let Some(bytes) = bytes {
+                layer.design = DesignSource::Bytes(bytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch design image from URL
+    async fn fetch_design(
+        &self,
+        url: &str,
+        limits: &GenerationLimits,
+    ) -> Result<DynamicImage, CompositorError> {
+        let bytes = self.fetch_design_bytes(url, limits).await?;
+        Self::decode_design(&bytes)
+    }
+
+    /// Decode design image bytes
+    fn decode_design(bytes: &[u8]) -> Result<DynamicImage, CompositorError> {
+        let image = image::load_from_memory(bytes)?;
+
+        debug!(
+            width = image.width(),
+            height = image.height(),
+            "Design image loaded"
+        );
+
+        Ok(image)
+    }
+
+    /// Fetch raw design bytes from URL, applying the same URL, domain, time,
+    /// and design limits
+    pub async fn fetch_design_bytes(
+        &self,
+        url: &str,
+        limits: &GenerationLimits,
+    ) -> Result<Bytes, CompositorError> {
+        debug!(url = %url, "Fetching design image");
+
+        if !url_in_domains(url, &limits.design_domains) {
+            return Err(CompositorError::DesignDomainNotAllowed(url.to_string()));
+        }
+        self.urls.check(url).await?;
+        self.urls
+            .within_domains(
+                limits.design_domains.clone(),
+                self.download_design(url, limits),
+            )
+            .await
+    }
+
+    /// Download design bytes from a validated URL, giving up at the fetch
+    /// budget (or the HTTP client's own timeout) and checking them against
+    /// `limits.design`
+    async fn download_design(
+        &self,
+        url: &str,
+        limits: &GenerationLimits,
+    ) -> Result<Bytes, CompositorError> {
+        let fetch_error = |e: reqwest::Error| {
+            if let Some(guard) = UrlGuardError::from_reqwest(&e) {
+                // A redirect hop or re-resolved address the guard refused
+                CompositorError::from(guard.clone())
+            } else if e.is_timeout() {
+                CompositorError::TimedOut(format!("design fetch took too long: {}", url))
+            } else {
+                CompositorError::HttpError(e)
+            }
+        };
+
+        let mut request = self.urls.client().get(url);
+        if let Some(timeout) = limits.fetch_budget() {
+            request = request.timeout(timeout);
+        }
+        let mut response = request.send().await.map_err(fetch_error)?;
+
+        if !response.status().is_success() {
+            return Err(CompositorError::FetchFailed(format!(
+                "HTTP {}: {}",
+                response.status(),
+                url
+            )));
+        }
+
+        let design = &limits.design;
+        if let Some(content_type) = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+        {
+            design.check_content_type(content_type)?;
+        }
+        if let Some(content_length) = response.content_length() {
+            if content_length > design.max_bytes {
+                return Err(CompositorError::DesignTooLarge(content_length));
+            }
+        }
+
+        // Content-Length may be missing or wrong, so count while streaming too
+        let mut bytes = BytesMut::new();
+        while let Some(chunk) = response.chunk().await.map_err(fetch_error)? {
+            let received = (bytes.len() + chunk.len()) as u64;
+            if received > design.max_bytes {
+                return Err(CompositorError::DesignTooLarge(received));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        design.check_image(&bytes)?;
+        Ok(bytes.freeze())
     }
 
     /// Composite design onto base template
-    fn composite_design(
+    pub fn composite_design(
         &self,
         base: &DynamicImage,
         design: &DynamicImage,
@@ -349,48 +1147,63 @@ impl Compositor {
         let design_rgba = design.to_rgba8();
         let (base_width, base_height) = base_rgba.dimensions();
         let (design_width, design_height) = design_rgba.dimensions();
+        let blend = blend_function(blend_mode);
+
+        // Only the base rows and columns the design covers are touched
+        let left = x_offset.max(0);
+        let top = y_offset.max(0);
+        let right = (x_offset + design_width as i32).min(base_width as i32);
+        let bottom = (y_offset + design_height as i32).min(base_height as i32);
+        if right <= left || bottom <= top {
+            return DynamicImage::ImageRgba8(base_rgba);
+        }
+        let (left, top, right, bottom) =
+            (left as usize, top as usize, right as usize, bottom as usize);
+
+        let base_stride = base_width as usize * 4;
+        let design_stride = design_width as usize * 4;
+        let design_raw = design_rgba.as_raw();
+        let mask_raw = print_mask_region.map(|mask| mask.as_raw());
+
+        // Rows are independent, so each one is blended on its own rayon task
+        base_rgba[top * base_stride..bottom * base_stride]
+            .par_chunks_exact_mut(base_stride)
+            .enumerate()
+            .for_each(|(row, base_row)| {
+                let dy = (top + row) as i32 - y_offset;
+                let design_row = &design_raw[dy as usize * design_stride..][..design_stride];
+                let mask_row = mask_raw.map(|mask| {
+                    &mask[dy as usize * design_width as usize..][..design_width as usize]
+                });
+
+                for x in left..right {
+                    let dx = (x as i32 - x_offset) as usize;
+                    let design_pixel = &design_row[dx * 4..dx * 4 + 4];
+
+                    // Apply opacity, then scale by the print mask so soft mask
+                    // edges fade the design out
+                    let mut alpha = design_pixel[3] as u32 * opacity as u32 / 255;
+                    if let Some(mask) = mask_row {
+                        alpha = alpha * mask[dx] as u32 / 255;
+                    }
 
-        // Apply opacity to design
-        let design_with_opacity = apply_opacity(&design_rgba, opacity);
-
-        // Composite based on blend mode
-        for dy in 0..design_height {
-            let y = y_offset + dy as i32;
-            if y < 0 || y >= base_height as i32 {
-                continue;
-            }
-
-            for dx in 0..design_width {
-                let x = x_offset + dx as i32;
-                if x < 0 || x >= base_width as i32 {
-                    continue;
-                }
-
-                let design_pixel = design_with_opacity.get_pixel(dx, dy);
-                let base_pixel = base_rgba.get_pixel(x as u32, y as u32);
-
-                // Skip fully transparent pixels
-                if design_pixel.0[3] == 0 {
-                    continue;
-                }
-
-                // Skip pixels outside print mask when provided
-                if let Some(mask) = print_mask_region {
-                    if mask.get_pixel(dx, dy).0[0] == 0 {
+                    // Skip fully transparent pixels, including those outside the print mask
+                    if alpha == 0 {
                         continue;
                     }
-                }
-
-                let blended = match blend_mode {
-                    "multiply" => self.blend_multiply_pixel(base_pixel, design_pixel),
-                    "screen" => self.blend_screen_pixel(base_pixel, design_pixel),
-                    "overlay" => self.blend_overlay_pixel(base_pixel, design_pixel),
-                    _ => self.blend_normal_pixel(base_pixel, design_pixel),
-                };
 
-                base_rgba.put_pixel(x as u32, y as u32, blended);
-            }
-        }
+                    composite_pixel(
+                        &mut base_row[x * 4..x * 4 + 4],
+                        [
+                            design_pixel[0],
+                            design_pixel[1],
+                            design_pixel[2],
+                            alpha as u8,
+                        ],
+                        blend,
+                    );
+                }
+            });
 
         DynamicImage::ImageRgba8(base_rgba)
     }
@@ -424,6 +1237,61 @@ impl Compositor {
         out
     }
 
+    /// The displacement map under a design placed at `(x_offset, y_offset)` on the base image
+    ///
+    /// Returns a `width` x `height` map aligned pixel for pixel with the design.
+    /// A map stored at a different size than the base image is scaled into
+    /// base coordinates, and parts of the design off the base image get neutral
+    /// gray so they are not displaced.
+    fn displacement_region(
+        map: &DynamicImage,
+        base_size: (u32, u32),
+        x_offset: i32,
+        y_offset: i32,
+        width: u32,
+        height: u32,
+    ) -> GrayImage {
+        let mut region = GrayImage::from_pixel(width, height, Luma([128]));
+        let (base_w, base_h) = base_size;
+        let left = x_offset.max(0);
+        let top = y_offset.max(0);
+        let right = (x_offset + width as i32).min(base_w as i32);
+        let bottom = (y_offset + height as i32).min(base_h as i32);
+        if right <= left || bottom <= top {
+            return region;
+        }
+        let (visible_w, visible_h) = ((right - left) as u32, (bottom - top) as u32);
+
+        let (map_w, map_h) = map.dimensions();
+        let scale_x = map_w as f64 / base_w as f64;
+        let scale_y = map_h as f64 / base_h as f64;
+        let map_x = ((left as f64 * scale_x) as u32).min(map_w.saturating_sub(1));
+        let map_y = ((top as f64 * scale_y) as u32).min(map_h.saturating_sub(1));
+        let map_crop_w = ((visible_w as f64 * scale_x).round() as u32).clamp(1, map_w - map_x);
+        let map_crop_h = ((visible_h as f64 * scale_y).round() as u32).clamp(1, map_h - map_y);
+
+        let visible = map
+            .crop_imm(map_x, map_y, map_crop_w, map_crop_h)
+            .to_luma8();
+        let visible = if visible.dimensions() == (visible_w, visible_h) {
+            visible
+        } else {
+            image::imageops::resize(
+                &visible,
+                visible_w,
+                visible_h,
+                image::imageops::FilterType::Triangle,
+            )
+        };
+        image::imageops::replace(
+            &mut region,
+            &visible,
+            (left - x_offset) as i64,
+            (top - y_offset) as i64,
+        );
+        region
+    }
+
     fn mask_has_nonzero(mask: &GrayImage) -> bool {
         mask.pixels().any(|p| p.0[0] > 0)
     }
@@ -453,70 +1321,6 @@ impl Compositor {
 
         DynamicImage::ImageRgba8(comp_rgba)
     }
-    /// Normal alpha blending
-    fn blend_normal_pixel(&self, base: &Rgba<u8>, overlay: &Rgba<u8>) -> Rgba<u8> {
-        let alpha = overlay.0[3] as f64 / 255.0;
-        let inv_alpha = 1.0 - alpha;
-
-        Rgba([
-            ((overlay.0[0] as f64 * alpha + base.0[0] as f64 * inv_alpha) as u8),
-            ((overlay.0[1] as f64 * alpha + base.0[1] as f64 * inv_alpha) as u8),
-            ((overlay.0[2] as f64 * alpha + base.0[2] as f64 * inv_alpha) as u8),
-            255,
-        ])
-    }
-
-    /// Multiply blend mode
-    fn blend_multiply_pixel(&self, base: &Rgba<u8>, overlay: &Rgba<u8>) -> Rgba<u8> {
-        let alpha = overlay.0[3] as f64 / 255.0;
-
-        let mut result = [0u8; 4];
-        for i in 0..3 {
-            let multiplied = (base.0[i] as u32 * overlay.0[i] as u32) / 255;
-            result[i] = (multiplied as f64 * alpha + base.0[i] as f64 * (1.0 - alpha)) as u8;
-        }
-        result[3] = 255;
-
-        Rgba(result)
-    }
-
-    /// Screen blend mode
-    fn blend_screen_pixel(&self, base: &Rgba<u8>, overlay: &Rgba<u8>) -> Rgba<u8> {
-        let alpha = overlay.0[3] as f64 / 255.0;
-
-        let mut result = [0u8; 4];
-        for i in 0..3 {
-            let screened = 255 - ((255 - base.0[i] as u32) * (255 - overlay.0[i] as u32)) / 255;
-            result[i] = (screened as f64 * alpha + base.0[i] as f64 * (1.0 - alpha)) as u8;
-        }
-        result[3] = 255;
-
-        Rgba(result)
-    }
-
-    /// Overlay blend mode
-    fn blend_overlay_pixel(&self, base: &Rgba<u8>, overlay: &Rgba<u8>) -> Rgba<u8> {
-        let alpha = overlay.0[3] as f64 / 255.0;
-
-        let mut result = [0u8; 4];
-        for i in 0..3 {
-            let b = base.0[i] as f64 / 255.0;
-            let o = overlay.0[i] as f64 / 255.0;
-
-            let overlayed = if b < 0.5 {
-                2.0 * b * o
-            } else {
-                1.0 - 2.0 * (1.0 - b) * (1.0 - o)
-            };
-
-            let blended = overlayed * alpha + b * (1.0 - alpha);
-            result[i] = (blended * 255.0).clamp(0.0, 255.0) as u8;
-        }
-        result[3] = 255;
-
-        Rgba(result)
-    }
-
     /// Apply a multiply-blend tint to a white-base template image.
     /// White pixels become the tint color; darker fabric texture pixels become proportionally darker.
     fn tint_template(base: &DynamicImage, r: u8, g: u8, b: u8) -> DynamicImage {
@@ -541,8 +1345,45 @@ impl Compositor {
         DynamicImage::ImageRgba8(output)
     }
 
+    /// Encode the finished mockup according to the output settings
+    pub(super) fn encode(
+        image: &DynamicImage,
+        output: &OutputSettings,
+    ) -> Result<Vec<u8>, CompositorError> {
+        match output.format {
+            OutputFormat::Png => Self::encode_png(image),
+            OutputFormat::Jpeg => {
+                let rgb = Self::flatten_alpha(&image.to_rgba8(), output.background);
+                let too_large = || {
+                    CompositorError::EncodeFailed(format!(
+                        "{}x{} exceeds the JPEG size limit",
+                        rgb.width(),
+                        rgb.height()
+                    ))
+                };
+                let width = u16::try_from(rgb.width()).map_err(|_| too_large())?;
+                let height = u16::try_from(rgb.height()).map_err(|_| too_large())?;
+
+                let mut buffer = Vec::new();
+                let mut encoder = JpegEncoder::new(&mut buffer, output.quality);
+                encoder.set_sampling_factor(output.chroma_subsampling.sampling_factor());
+                encoder
+                    .encode(rgb.as_raw(), width, height, jpeg_encoder::ColorType::Rgb)
+                    .map_err(|e| CompositorError::EncodeFailed(e.to_string()))?;
+                Ok(buffer)
+            }
+            OutputFormat::Webp => {
+                let rgba = image.to_rgba8();
+                let mut buffer = Vec::new();
+                WebPEncoder::new_with_quality(&mut buffer, WebPQuality::lossy(output.quality))
+                    .encode(rgba.as_raw(), rgba.width(), rgba.height(), ColorType::Rgba8)?;
+                Ok(buffer)
+            }
+        }
+    }
+
     /// Encode image to PNG bytes (preserves RGBA transparency)
-    fn encode_png(&self, image: &DynamicImage) -> Result<Vec<u8>, CompositorError> {
+    pub(super) fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, CompositorError> {
         let mut buffer = Vec::new();
         let encoder = image::codecs::png::PngEncoder::new(&mut buffer);
         encoder.encode(
@@ -554,64 +1395,167 @@ impl Compositor {
         Ok(buffer)
     }
 
-    /// Remove white/near-white background from an image by converting to transparency
-    /// Uses edge-aware algorithm to preserve design details while removing backgrounds
-    fn remove_white_background(&self, image: &DynamicImage) -> DynamicImage {
-        let rgba = image.to_rgba8();
-        let (width, height) = rgba.dimensions();
-        let mut output = RgbaImage::new(width, height);
+    /// Blend transparent pixels onto a solid background for formats without alpha
+    ///
+    /// Partially transparent pixels are mixed in linear light and converted back
+    /// to sRGB, so soft edges don't darken the way naive sRGB averaging does.
+    fn flatten_alpha(image: &RgbaImage, background: (u8, u8, u8)) -> RgbImage {
+        let (bg_r, bg_g, bg_b) = background;
+        let bg_linear = [
+            srgb_to_linear(bg_r),
+            srgb_to_linear(bg_g),
+            srgb_to_linear(bg_b),
+        ];
+        let mut output = RgbImage::new(image.width(), image.height());
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let [r, g, b, a] = pixel.0;
+            let rgb = match a {
+                0 => [bg_r, bg_g, bg_b],
+                255 => [r, g, b],
+                _ => {
+                    let alpha = a as f32 / 255.0;
+                    let blend = |fg: u8, bg: f32| {
+                        linear_to_srgb(srgb_to_linear(fg) * alpha + bg * (1.0 - alpha))
+                    };
+                    [
+                        blend(r, bg_linear[0]),
+                        blend(g, bg_linear[1]),
+                        blend(b, bg_linear[2]),
+                    ]
+                }
+            };
+            output.put_pixel(x, y, Rgb(rgb));
+        }
 
-        // Thresholds for "white-ish" detection
-        // Lower value = more aggressive removal (catches more off-white)
-        const WHITE_THRESHOLD: u8 = 245; // Pure white detection
-        const LIGHT_THRESHOLD: u8 = 230; // Light color detection
-        const EDGE_FEATHER: u8 = 25; // Feather range for smooth edges
+        output
+    }
 
-        for y in 0..height {
-            for x in 0..width {
-                let pixel = rgba.get_pixel(x, y);
-                let r = pixel.0[0];
-                let g = pixel.0[1];
-                let b = pixel.0[2];
-
-                // Calculate luminance (human eye weighted)
-                let luminance = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) as u8;
-
-                // Check color variance (white has low variance between channels)
-                let max_channel = r.max(g).max(b);
-                let min_channel = r.min(g).min(b);
-                let variance = max_channel - min_channel;
-
-                // Detect white/near-white: high luminance + low color variance
-                if luminance >= WHITE_THRESHOLD && variance <= 15 {
-                    // Pure white - fully transparent
-                    output.put_pixel(x, y, Rgba([r, g, b, 0]));
-                } else if luminance >= LIGHT_THRESHOLD && variance <= 25 {
-                    // Light gray/off-white - gradual transparency based on how white
-                    let alpha = ((255 - luminance) as f32 / (255 - LIGHT_THRESHOLD) as f32 * 255.0)
-                        .min(255.0) as u8;
-                    output.put_pixel(x, y, Rgba([r, g, b, alpha]));
-                } else if luminance >= LIGHT_THRESHOLD - EDGE_FEATHER && variance <= 35 {
-                    // Edge feathering zone
-                    let alpha = ((LIGHT_THRESHOLD - luminance.saturating_sub(EDGE_FEATHER)) as f32
-                        / EDGE_FEATHER as f32
-                        * 255.0)
-                        .min(255.0) as u8;
-                    output.put_pixel(x, y, Rgba([r, g, b, alpha]));
-                } else {
-                    // Keep pixel fully opaque
-                    output.put_pixel(x, y, Rgba([r, g, b, 255]));
+    /// Request override first, then the product type's default
+    fn should_displace(request: &MockupRequest, metadata: &TemplateMetadata) -> bool {
+        request.apply_displacement.unwrap_or_else(|| {
+            let product_type = metadata
+                .product_type
+                .as_deref()
+                .unwrap_or(&metadata.category);
+            displaces_by_default(product_type)
+        })
+    }
+
+    /// Rotate clockwise around the center onto a `width` x `height` canvas
+    ///
+    /// Samples bilinearly with premultiplied alpha so edges fade into the
+    /// transparent corners instead of picking up a dark fringe.
+    pub(super) fn rotate_design(
+        image: &DynamicImage,
+        degrees: f64,
+        width: u32,
+        height: u32,
+    ) -> DynamicImage {
+        let source = image.to_rgba8();
+        let (src_w, src_h) = source.dimensions();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (src_cx, src_cy) = (src_w as f64 / 2.0, src_h as f64 / 2.0);
+        let (dst_cx, dst_cy) = (width as f64 / 2.0, height as f64 / 2.0);
+
+        // Premultiplied source pixel, transparent outside the image
+        let texel = |x: i64, y: i64| -> [f64; 4] {
+            if x < 0 || y < 0 || x >= src_w as i64 || y >= src_h as i64 {
+                return [0.0; 4];
+            }
+            let [r, g, b, a] = source.get_pixel(x as u32, y as u32).0;
+            let alpha = a as f64 / 255.0;
+            [
+                r as f64 * alpha,
+                g as f64 * alpha,
+                b as f64 * alpha,
+                a as f64,
+            ]
+        };
+
+        RgbaImage::from_fn(width, height, |x, y| {
+            // Inverse-rotate the output pixel center into source coordinates
+            let dx = x as f64 + 0.5 - dst_cx;
+            let dy = y as f64 + 0.5 - dst_cy;
+            let sx = cos * dx + sin * dy + src_cx - 0.5;
+            let sy = -sin * dx + cos * dy + src_cy - 0.5;
+
+            let (x0, y0) = (sx.floor(), sy.floor());
+            let (fx, fy) = (sx - x0, sy - y0);
+            let (x0, y0) = (x0 as i64, y0 as i64);
+
+            let mut sum = [0.0; 4];
+            for (tx, ty, weight) in [
+                (x0, y0, (1.0 - fx) * (1.0 - fy)),
+                (x0 + 1, y0, fx * (1.0 - fy)),
+                (x0, y0 + 1, (1.0 - fx) * fy),
+                (x0 + 1, y0 + 1, fx * fy),
+            ] {
+                if weight > 0.0 {
+                    let t = texel(tx, ty);
+                    for (acc, value) in sum.iter_mut().zip(t) {
+                        *acc += value * weight;
+                    }
                 }
             }
+
+            let alpha = sum[3];
+            if alpha < 0.5 {
+                return Rgba([0, 0, 0, 0]);
+            }
+            let unpremultiply = |c: f64| (c * 255.0 / alpha).round().clamp(0.0, 255.0) as u8;
+            Rgba([
+                unpremultiply(sum[0]),
+                unpremultiply(sum[1]),
+                unpremultiply(sum[2]),
+                alpha.round().clamp(0.0, 255.0) as u8,
+            ])
+        })
+        .into()
+    }
+
+    /// Whether the design was exported with transparency; such designs already
+    /// carry their own cut-out and skip background removal
+    fn has_transparency(image: &DynamicImage) -> bool {
+        image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p.0[3] < 255)
+    }
+
+    /// Remove white/near-white background from an image by converting to transparency
+    ///
+    /// `Luminance` clears every white pixel; `BorderFlood` only those connected
+    /// to the border, so enclosed white areas of the design survive.
+    fn remove_white_background(
+        &self,
+        image: &DynamicImage,
+        removal: &BackgroundRemoval,
+    ) -> DynamicImage {
+        let mut rgba = image.to_rgba8();
+        let (width, height) = rgba.dimensions();
+
+        match removal.mode {
+            BackgroundRemovalMode::None => {}
+            BackgroundRemovalMode::Luminance => {
+                let stride = width as usize * 4;
+                rgba.par_chunks_exact_mut(stride.max(4)).for_each(|row| {
+                    for pixel in row.chunks_exact_mut(4) {
+                        pixel[3] = removal.alpha_for([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                    }
+                });
+            }
+            BackgroundRemovalMode::BorderFlood if width > 0 && height > 0 => {
+                flood_border_background(&mut rgba, removal);
+            }
+            BackgroundRemovalMode::BorderFlood => {}
         }
 
         debug!(
             width = width,
             height = height,
+            mode = ?removal.mode,
             "Removed white background from design"
         );
 
-        DynamicImage::ImageRgba8(output)
+        DynamicImage::ImageRgba8(rgba)
     }
 }
 
@@ -635,31 +1579,6 @@ mod tests {
         assert_eq!(parse_hex_color(""), None);
     }
 
-    #[test]
-    fn test_validate_fetch_url_allows_public_http_urls() {
-        assert!(validate_fetch_url("https://cdn.example.com/design.png").is_ok());
-        assert!(validate_fetch_url("http://203.0.114.10/design.png").is_ok());
-    }
-
-    #[test]
-    fn test_validate_fetch_url_rejects_non_http_urls() {
-        assert!(validate_fetch_url("file:///tmp/design.png").is_err());
-        assert!(validate_fetch_url("data:image/png;base64,abc").is_err());
-        assert!(validate_fetch_url("/samples/design.png").is_err());
-    }
-
-    #[test]
-    fn test_validate_fetch_url_rejects_local_targets() {
-        assert!(validate_fetch_url("http://localhost/design.png").is_err());
-        assert!(validate_fetch_url("http://api.localhost/design.png").is_err());
-        assert!(validate_fetch_url("http://127.0.0.1/design.png").is_err());
-        assert!(validate_fetch_url("http://10.1.2.3/design.png").is_err());
-        assert!(validate_fetch_url("http://172.16.0.1/design.png").is_err());
-        assert!(validate_fetch_url("http://192.168.0.1/design.png").is_err());
-        assert!(validate_fetch_url("http://[::1]/design.png").is_err());
-        assert!(validate_fetch_url("http://[fc00::1]/design.png").is_err());
-    }
-
     #[test]
     fn test_tint_white_pixel() {
         let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255])));
@@ -698,6 +1617,44 @@ mod tests {
         let px = out.to_rgba8();
         assert_eq!(px.get_pixel(0, 0).0, [255, 0, 0, 255]);
         assert_eq!(px.get_pixel(1, 0).0, [255, 255, 255, 255]);
+
+        // Gray mask values blend the design in partially
+        mask.put_pixel(1, 0, Luma([128]));
+        let out = c.composite_design(&base, &design, 0, 0, 255, "normal", Some(&mask));
+        let [r, g, b, _] = out.to_rgba8().get_pixel(1, 0).0;
+        assert_eq!(r, 255);
+        assert!((100..160).contains(&g) && g == b, "got {:?}", [r, g, b]);
+    }
+
+    #[test]
+    fn test_composite_design_keeps_template_transparency() {
+        let c = Compositor::new();
+        // Cut-out product shot: opaque fabric with a transparent hole and a soft edge
+        let mut base = RgbaImage::from_pixel(3, 1, Rgba([128, 128, 128, 255]));
+        base.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        base.put_pixel(2, 0, Rgba([128, 128, 128, 128]));
+        let base = DynamicImage::ImageRgba8(base);
+        let design = DynamicImage::ImageRgba8(RgbaImage::from_pixel(3, 1, Rgba([255, 0, 0, 255])));
+
+        for mode in BLEND_MODES {
+            let out = c
+                .composite_design(&base, &design, 0, 0, 255, mode, None)
+                .to_rgba8();
+            assert_eq!(out.get_pixel(0, 0).0[3], 255, "{mode}");
+            assert_eq!(out.get_pixel(1, 0).0, [0, 0, 0, 0], "{mode}: hole filled");
+            let edge = out.get_pixel(2, 0).0[3];
+            assert!((128..255).contains(&edge), "{mode}: edge alpha {edge}");
+        }
+
+        // Opaque templates composite as before
+        let out = c
+            .composite_design(&base, &design, 0, 0, 255, "normal", None)
+            .to_rgba8();
+        assert_eq!(out.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        let out = c
+            .composite_design(&base, &design, 0, 0, 255, "multiply", None)
+            .to_rgba8();
+        assert_eq!(out.get_pixel(0, 0).0, [128, 0, 0, 255]);
     }
 
     #[test]
@@ -714,4 +1671,940 @@ mod tests {
         assert_eq!(px.get_pixel(0, 0).0, [200, 100, 50, 255]);
         assert_eq!(px.get_pixel(1, 0).0, [10, 20, 30, 255]);
     }
+
+    #[test]
+    fn test_decode_uploaded_design() {
+        let design = DynamicImage::ImageRgba8(RgbaImage::from_pixel(3, 2, Rgba([1, 2, 3, 255])));
+        let mut png = Vec::new();
+        design
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+
+        let decoded = Compositor::decode_design(&png).unwrap();
+        assert_eq!(decoded.dimensions(), (3, 2));
+        assert!(Compositor::decode_design(b"not an image").is_err());
+
+        let source = DesignSource::Bytes(Bytes::from(png.clone()));
+        assert_eq!(
+            format!("{:?}", source),
+            format!("Bytes({} bytes)", png.len())
+        );
+    }
+
+    fn half_transparent() -> DynamicImage {
+        let mut image = RgbaImage::from_pixel(4, 4, Rgba([200, 40, 40, 255]));
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        image.put_pixel(1, 0, Rgba([0, 0, 0, 128]));
+        DynamicImage::ImageRgba8(image)
+    }
+
+    #[test]
+    fn test_encode_each_output_format() {
+        let image = half_transparent();
+        for (format, expected) in [
+            (OutputFormat::Png, image::ImageFormat::Png),
+            (OutputFormat::Jpeg, image::ImageFormat::Jpeg),
+            (OutputFormat::Webp, image::ImageFormat::WebP),
+        ] {
+            let output = OutputSettings::new(format, Some(80), None).unwrap();
+            let bytes = Compositor::encode(&image, &output).unwrap();
+            assert_eq!(image::guess_format(&bytes).unwrap(), expected);
+            assert_eq!(
+                image::load_from_memory(&bytes).unwrap().dimensions(),
+                (4, 4)
+            );
+        }
+    }
+
+    #[test]
+    fn test_flatten_alpha_onto_background() {
+        let rgba = half_transparent().to_rgba8();
+
+        let white = Compositor::flatten_alpha(&rgba, (255, 255, 255));
+        assert_eq!(white.get_pixel(0, 0).0, [255, 255, 255]);
+        // Half-covered black over white is 50% gray in linear light, not sRGB 127
+        assert_eq!(white.get_pixel(1, 0).0, [187, 187, 187]);
+        assert_eq!(white.get_pixel(2, 0).0, [200, 40, 40]);
+
+        let black = Compositor::flatten_alpha(&rgba, (0, 0, 0));
+        assert_eq!(black.get_pixel(0, 0).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_jpeg_output_flattens_transparency() {
+        let output = OutputSettings::new(OutputFormat::Jpeg, Some(100), Some("#00FF00")).unwrap();
+        let bytes = Compositor::encode(&half_transparent(), &output).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgb8();
+
+        // Fully transparent corner comes back as the background, within JPEG error
+        let [r, g, b] = decoded.get_pixel(0, 0).0;
+        assert!(r < 40 && g > 215 && b < 40, "got {:?}", [r, g, b]);
+    }
+
+    #[test]
+    fn test_displacement_follows_product_type_unless_overridden() {
+        use crate::engine::template::{PrintArea, TemplateDimensions};
+
+        let mut metadata = TemplateMetadata::from_provider_mockup(
+            "poster-front",
+            "front",
+            TemplateDimensions {
+                width: 100,
+                height: 100,
+            },
+            PrintArea {
+                x: 0,
+                y: 0,
+                width: 100,
+                height: 100,
+            },
+        );
+        let mut request = MockupRequest {
+            designs: vec![DesignLayer {
+                design: DesignSource::Url("https://example.com/design.png".to_string()),
+                placement: PlacementSpec::default(),
+                displacement_strength: 10.0,
+                blend_mode: None,
+            }],
+            template_id: metadata.id.clone(),
+            apply_displacement: None,
+            tint_color: None,
+            remove_background: None,
+            output: OutputSettings::default(),
+            limits: GenerationLimits::default(),
+        };
+
+        metadata.product_type = Some("poster".to_string());
+        assert!(!Compositor::should_displace(&request, &metadata));
+        request.apply_displacement = Some(true);
+        assert!(Compositor::should_displace(&request, &metadata));
+
+        metadata.product_type = Some("tshirt".to_string());
+        request.apply_displacement = Some(false);
+        assert!(!Compositor::should_displace(&request, &metadata));
+        request.apply_displacement = None;
+        assert!(Compositor::should_displace(&request, &metadata));
+    }
+
+    fn solid_png(color: [u8; 4]) -> Bytes {
+        let design = DynamicImage::ImageRgba8(RgbaImage::from_pixel(10, 10, Rgba(color)));
+        let mut png = Vec::new();
+        design
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        Bytes::from(png)
+    }
+
+    fn layer(design: Bytes, offset_x: i32, blend_mode: Option<&str>) -> DesignLayer {
+        DesignLayer {
+            design: DesignSource::Bytes(design),
+            placement: PlacementSpec {
+                scale: 0.2,
+                offset_x,
+                offset_y: 0,
+                print_area_width: 100,
+                print_area_height: 100,
+                ..PlacementSpec::default()
+            },
+            displacement_strength: 0.0,
+            blend_mode: blend_mode.map(str::to_string),
+        }
+    }
+
+    fn layered_request(designs: Vec<DesignLayer>) -> (MockupRequest, TemplateMetadata) {
+        use crate::engine::template::{PrintArea, TemplateDimensions};
+
+        let metadata = TemplateMetadata::from_provider_mockup(
+            "poster-front",
+            "front",
+            TemplateDimensions {
+                width: 100,
+                height: 100,
+            },
+            PrintArea {
+                x: 0,
+                y: 0,
+                width: 100,
+                height: 100,
+            },
+        );
+        let request = MockupRequest {
+            designs,
+            template_id: metadata.id.clone(),
+            apply_displacement: None,
+            tint_color: None,
+            remove_background: None,
+            output: OutputSettings::default(),
+            limits: GenerationLimits::default(),
+        };
+        (request, metadata)
+    }
+
+    #[tokio::test]
+    async fn test_designs_composite_in_order() {
+        let red = solid_png([255, 0, 0, 255]);
+        let blue = solid_png([0, 0, 255, 255]);
+        let (request, metadata) = layered_request(vec![
+            layer(red.clone(), -25, None),
+            layer(blue.clone(), 25, Some("normal")),
+            // Lands on the red design; normal blending replaces it
+            layer(blue, -25, Some("normal")),
+        ]);
+        let images = TemplateImages::from_base(DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            100,
+            100,
+            Rgba([255, 255, 255, 255]),
+        )));
+
+        let result = Compositor::new()
+            .generate(&request, &metadata, &images)
+            .await
+            .unwrap();
+        let mockup = image::load_from_memory(&result.bytes).unwrap().to_rgba8();
+
+        let [r, _, b, _] = mockup.get_pixel(25, 50).0;
+        assert!(r < 40 && b > 215, "got {:?}", mockup.get_pixel(25, 50));
+        let [r, _, b, _] = mockup.get_pixel(75, 50).0;
+        assert!(r < 40 && b > 215, "got {:?}", mockup.get_pixel(75, 50));
+        assert_eq!(mockup.get_pixel(50, 50).0, [255, 255, 255, 255]);
+
+        // The template has no displacement map, so every layer skips that stage
+        let timings = result.timings;
+        assert!(timings.composite > timings.displacement);
+        assert!(timings.encode > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_displacement_region_follows_design_position() {
+        // Horizontal gradient: the map value encodes the base column
+        let map = DynamicImage::ImageLuma8(GrayImage::from_fn(100, 10, |x, _| Luma([x as u8 * 2])));
+
+        let region = Compositor::displacement_region(&map, (100, 10), 30, 0, 10, 10);
+        assert_eq!(region.get_pixel(0, 0).0[0], 60);
+        let region = Compositor::displacement_region(&map, (100, 10), 50, 0, 10, 10);
+        assert_eq!(region.get_pixel(0, 0).0[0], 100);
+        assert_eq!(region.get_pixel(9, 5).0[0], 118);
+
+        // Off the left edge: neutral until the design reaches the base image
+        let region = Compositor::displacement_region(&map, (100, 10), -5, 0, 10, 10);
+        assert_eq!(region.get_pixel(4, 0).0[0], 128);
+        assert_eq!(region.get_pixel(5, 0).0[0], 0);
+        assert_eq!(region.get_pixel(9, 0).0[0], 8);
+
+        // A half-resolution map is read in base coordinates
+        let half = DynamicImage::ImageLuma8(GrayImage::from_fn(50, 5, |x, _| Luma([x as u8 * 4])));
+        let region = Compositor::displacement_region(&half, (100, 10), 40, 0, 10, 10);
+        assert_eq!(region.dimensions(), (10, 10));
+        let value = region.get_pixel(0, 0).0[0];
+        assert!((76..=84).contains(&value), "got {value}");
+    }
+
+    /// Vertical red and black stripes 3px wide, the size of the placed design
+    fn striped_png() -> Bytes {
+        let design = RgbaImage::from_fn(20, 20, |x, _| {
+            if (x / 3) % 2 == 0 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(design)
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        Bytes::from(png)
+    }
+
+    /// Render the stripes at `offset_x` and return the 20x20 patch they land on
+    async fn displaced_patch(offset_x: i32, displace: bool) -> RgbaImage {
+        let mut striped = layer(striped_png(), offset_x, Some("normal"));
+        striped.displacement_strength = 4.0;
+        let (mut request, mut metadata) = layered_request(vec![striped]);
+        request.apply_displacement = Some(displace);
+        metadata.displacement.enabled = true;
+        metadata.default_opacity = 255;
+
+        // Half-resolution map: flat on the left half of the base, folded on the right
+        let mut images = TemplateImages::from_base(DynamicImage::ImageRgba8(
+            RgbaImage::from_pixel(100, 100, Rgba([255, 255, 255, 255])),
+        ));
+        images.displacement_map = Some(DynamicImage::ImageLuma8(GrayImage::from_fn(
+            50,
+            50,
+            |x, _| Luma([if x < 25 { 128 } else { 255 }]),
+        )));
+
+        let result = Compositor::new()
+            .generate(&request, &metadata, &images)
+            .await
+            .unwrap();
+        let mockup = image::load_from_memory(&result.bytes).unwrap().to_rgba8();
+        let left = (50 + offset_x - 10) as u32;
+        image::imageops::crop_imm(&mockup, left, 40, 20, 20).to_image()
+    }
+
+    fn max_channel_diff(a: &RgbaImage, b: &RgbaImage) -> u8 {
+        a.pixels()
+            .zip(b.pixels())
+            .flat_map(|(pa, pb)| pa.0.into_iter().zip(pb.0).map(|(ca, cb)| ca.abs_diff(cb)))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn test_displacement_follows_placement_offset() {
+        // Over the flat half the design is untouched, give or take the
+        // fraction of a pixel that mid-gray 128 shifts by
+        let flat = displaced_patch(-25, false).await;
+        let displaced = displaced_patch(-25, true).await;
+        assert!(max_channel_diff(&flat, &displaced) <= 2);
+
+        // Moved over the folds, the same design is shifted by 2px
+        let flat = displaced_patch(25, false).await;
+        let folded = displaced_patch(25, true).await;
+        assert!(max_channel_diff(&flat, &folded) > 200);
+        assert_eq!(folded.get_pixel(0, 10), flat.get_pixel(2, 10));
+    }
+
+    /// White lettering on a navy badge, exported without transparency
+    fn white_artwork_png(alpha: u8) -> Bytes {
+        // Same size as the placed design, so resizing leaves pixels untouched
+        let mut design = RgbaImage::from_pixel(20, 20, Rgba([20, 30, 90, 255]));
+        for y in 5..15 {
+            for x in 5..15 {
+                design.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        design.put_pixel(0, 0, Rgba([20, 30, 90, alpha]));
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(design)
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        Bytes::from(png)
+    }
+
+    async fn render_on_black(design: Bytes, removal: Option<BackgroundRemoval>) -> RgbaImage {
+        let (mut request, metadata) = layered_request(vec![layer(design, 0, Some("normal"))]);
+        request.remove_background = removal;
+        let images = TemplateImages::from_base(DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            100,
+            100,
+            Rgba([0, 0, 0, 255]),
+        )));
+        let result = Compositor::new()
+            .generate(&request, &metadata, &images)
+            .await
+            .unwrap();
+        image::load_from_memory(&result.bytes).unwrap().to_rgba8()
+    }
+
+    #[tokio::test]
+    async fn test_white_artwork_survives_without_background_removal() {
+        let mockup = render_on_black(white_artwork_png(255), None).await;
+        assert_eq!(mockup.get_pixel(50, 50).0, [255, 255, 255, 255]);
+
+        // Opted in: the white fill is treated as background
+        let mockup =
+            render_on_black(white_artwork_png(255), Some(BackgroundRemoval::default())).await;
+        assert_eq!(mockup.get_pixel(50, 50).0, [0, 0, 0, 255]);
+
+        // A design with its own transparency is never cleaned
+        let mockup =
+            render_on_black(white_artwork_png(0), Some(BackgroundRemoval::default())).await;
+        assert_eq!(mockup.get_pixel(50, 50).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_background_removal_thresholds() {
+        let c = Compositor::new();
+        let gray = |value: u8| {
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                1,
+                1,
+                Rgba([value, value, value, 255]),
+            ))
+        };
+        let alpha = |value: u8, removal: &BackgroundRemoval| {
+            c.remove_white_background(&gray(value), removal)
+                .to_rgba8()
+                .get_pixel(0, 0)
+                .0[3]
+        };
+
+        let defaults = BackgroundRemoval::default();
+        assert_eq!(alpha(250, &defaults), 0);
+        assert_eq!(alpha(100, &defaults), 255);
+
+        // Only pure white goes once the thresholds are raised
+        let strict = BackgroundRemoval {
+            white_threshold: 254,
+            light_threshold: 254,
+            feather: 0,
+            ..defaults
+        };
+        assert_eq!(alpha(255, &strict), 0);
+        assert_eq!(alpha(250, &strict), 255);
+
+        assert!(defaults.validate().is_ok());
+        assert!(BackgroundRemoval {
+            light_threshold: 250,
+            ..defaults
+        }
+        .validate()
+        .is_err());
+    }
+
+    /// White logo on a white background: a navy ring whose inside is white too
+    fn white_logo_on_white() -> DynamicImage {
+        let mut logo = RgbaImage::from_pixel(40, 40, Rgba([255, 255, 255, 255]));
+        for y in 0..40i32 {
+            for x in 0..40i32 {
+                let distance = (((x - 20).pow(2) + (y - 20).pow(2)) as f64).sqrt();
+                if (8.0..14.0).contains(&distance) {
+                    logo.put_pixel(x as u32, y as u32, Rgba([20, 30, 90, 255]));
+                }
+            }
+        }
+        DynamicImage::ImageRgba8(logo)
+    }
+
+    #[test]
+    fn test_border_flood_keeps_enclosed_white() {
+        let c = Compositor::new();
+        let flood = BackgroundRemoval {
+            mode: BackgroundRemovalMode::BorderFlood,
+            ..BackgroundRemoval::default()
+        };
+        let cleaned = c
+            .remove_white_background(&white_logo_on_white(), &flood)
+            .to_rgba8();
+
+        // Outer background and corners are cleared
+        assert_eq!(cleaned.get_pixel(0, 0).0[3], 0);
+        assert_eq!(cleaned.get_pixel(39, 20).0[3], 0);
+        assert_eq!(cleaned.get_pixel(2, 2).0[3], 0);
+        // The ring and the white inside it stay opaque
+        assert_eq!(cleaned.get_pixel(20, 9).0, [20, 30, 90, 255]);
+        assert_eq!(cleaned.get_pixel(20, 20).0, [255, 255, 255, 255]);
+        assert_eq!(cleaned.get_pixel(16, 20).0[3], 255);
+
+        // Luminance mode punches the inside out as well
+        let cleaned = c
+            .remove_white_background(&white_logo_on_white(), &BackgroundRemoval::default())
+            .to_rgba8();
+        assert_eq!(cleaned.get_pixel(0, 0).0[3], 0);
+        assert_eq!(cleaned.get_pixel(20, 20).0[3], 0);
+    }
+
+    #[test]
+    fn test_border_flood_without_border_background() {
+        // A design that fills its canvas has nothing to flood from
+        let c = Compositor::new();
+        let flood = BackgroundRemoval {
+            mode: BackgroundRemovalMode::BorderFlood,
+            ..BackgroundRemoval::default()
+        };
+        let mut design = RgbaImage::from_pixel(10, 10, Rgba([20, 30, 90, 255]));
+        design.put_pixel(5, 5, Rgba([255, 255, 255, 255]));
+        let cleaned = c
+            .remove_white_background(&DynamicImage::ImageRgba8(design), &flood)
+            .to_rgba8();
+        assert!(cleaned.pixels().all(|p| p.0[3] == 255));
+    }
+
+    #[tokio::test]
+    async fn test_failed_design_reports_its_index() {
+        let (request, metadata) = layered_request(vec![
+            layer(solid_png([255, 0, 0, 255]), -25, None),
+            layer(Bytes::from_static(b"not an image"), 25, None),
+        ]);
+        let images = TemplateImages::from_base(DynamicImage::ImageRgba8(RgbaImage::new(100, 100)));
+
+        let error = Compositor::new()
+            .generate(&request, &metadata, &images)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CompositorError::Design { index: 1, .. }));
+        assert!(error.to_string().starts_with("designs[1]: "));
+    }
+
+    /// Address of a server that accepts connections but never responds
+    async fn hanging_server() -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_hanging_design_fetch_gives_up_at_timeout() {
+        let addr = hanging_server().await;
+        let started = Instant::now();
+
+        let error = Compositor::new()
+            .download_design(
+                &format!("http://{}/design.png", addr),
+                &GenerationLimits {
+                    fetch_timeout: Some(Duration::from_millis(200)),
+                    ..GenerationLimits::default()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CompositorError::TimedOut(_)), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_hanging_design_fetch_stops_at_deadline() {
+        let addr = hanging_server().await;
+        let started = Instant::now();
+        let limits = GenerationLimits {
+            fetch_timeout: Some(Duration::from_secs(30)),
+            deadline: Some(started + Duration::from_millis(200)),
+            ..GenerationLimits::default()
+        };
+
+        let error = Compositor::new()
+            .download_design(&format!("http://{}/design.png", addr), &limits)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CompositorError::TimedOut(_)), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_generation_past_deadline_times_out() {
+        let (mut request, metadata) =
+            layered_request(vec![layer(solid_png([255, 0, 0, 255]), 0, None)]);
+        request.limits.deadline = Some(Instant::now());
+        let images = TemplateImages::from_base(DynamicImage::ImageRgba8(RgbaImage::new(100, 100)));
+
+        let error = Compositor::new()
+            .generate(&request, &metadata, &images)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CompositorError::TimedOut(_)), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_generation_stops_once_cancelled() {
+        let (mut request, metadata) =
+            layered_request(vec![layer(solid_png([255, 0, 0, 255]), 0, None)]);
+        drop(request.limits.cancel_on_drop());
+        let images = TemplateImages::from_base(DynamicImage::ImageRgba8(RgbaImage::new(100, 100)));
+
+        let error = Compositor::new()
+            .generate(&request, &metadata, &images)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CompositorError::Cancelled));
+    }
+
+    /// Address of a server that answers every request with `head` and `body`
+    async fn fixed_response_server(head: String, body: Bytes) -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+        addr
+    }
+
+    async fn download_from(head: String, body: Bytes) -> Result<Bytes, CompositorError> {
+        let addr = fixed_response_server(head, body).await;
+        Compositor::new()
+            .download_design(
+                &format!("http://{}/design", addr),
+                &GenerationLimits::default(),
+            )
+            .await
+    }
+
+    fn png_response(content_length: usize) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            content_length
+        )
+    }
+
+    #[tokio::test]
+    async fn test_design_with_oversized_content_length_rejected() {
+        let error = download_from(png_response(200 * 1024 * 1024), Bytes::new())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, CompositorError::DesignTooLarge(n) if n == 200 * 1024 * 1024),
+            "{error}"
+        );
+        assert_eq!(error.design_code(), Some("DESIGN_TOO_LARGE"));
+    }
+
+    #[tokio::test]
+    async fn test_html_design_rejected() {
+        let body = Bytes::from_static(b"<html><body>Not found</body></html>");
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+
+        let error = download_from(head, body).await.unwrap_err();
+        assert!(
+            matches!(error, CompositorError::UnsupportedDesignFormat(_)),
+            "{error}"
+        );
+        assert_eq!(error.design_code(), Some("DESIGN_UNSUPPORTED_FORMAT"));
+    }
+
+    #[tokio::test]
+    async fn test_one_pixel_design_rejected() {
+        let design = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255])));
+        let mut png = Vec::new();
+        design
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+
+        let error = download_from(png_response(png.len()), Bytes::from(png))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                CompositorError::DesignTooSmall {
+                    width: 1,
+                    height: 1,
+                    min: 50
+                }
+            ),
+            "{error}"
+        );
+        assert_eq!(error.design_code(), Some("DESIGN_TOO_SMALL"));
+    }
+
+    #[test]
+    fn test_design_limits_check_sniffed_format_and_dimensions() {
+        let limits = DesignLimits::default();
+        let encode = |width: u32, height: u32, format: image::ImageOutputFormat| {
+            let design = DynamicImage::ImageRgb8(RgbImage::new(width, height));
+            let mut bytes = Vec::new();
+            design
+                .write_to(&mut std::io::Cursor::new(&mut bytes), format)
+                .unwrap();
+            bytes
+        };
+
+        assert!(limits
+            .check_image(&encode(64, 64, image::ImageOutputFormat::Png))
+            .is_ok());
+        assert!(matches!(
+            limits.check_image(&encode(64, 64, image::ImageOutputFormat::Gif)),
+            Err(CompositorError::UnsupportedDesignFormat(_))
+        ));
+        assert!(matches!(
+            limits.check_image(&encode(10_001, 60, image::ImageOutputFormat::Png)),
+            Err(CompositorError::DesignDimensionsTooLarge { .. })
+        ));
+        assert!(limits.check_content_type("image/webp").is_ok());
+        assert!(limits
+            .check_content_type("application/octet-stream")
+            .is_ok());
+        assert!(limits.check_content_type("image/tiff").is_err());
+    }
+
+    #[test]
+    fn test_rotate_design_quarter_turn() {
+        // 4x2 with a distinct color per pixel
+        let source = RgbaImage::from_fn(4, 2, |x, y| Rgba([x as u8 * 60, y as u8 * 200, 10, 255]));
+        let rotated =
+            Compositor::rotate_design(&DynamicImage::ImageRgba8(source.clone()), 90.0, 2, 4)
+                .to_rgba8();
+
+        assert_eq!(rotated.dimensions(), (2, 4));
+        // Clockwise: the bottom-left pixel becomes the top-left one
+        assert_eq!(rotated.get_pixel(0, 0), source.get_pixel(0, 1));
+        assert_eq!(rotated.get_pixel(1, 0), source.get_pixel(0, 0));
+        assert_eq!(rotated.get_pixel(0, 3), source.get_pixel(3, 1));
+        assert_eq!(rotated.get_pixel(1, 3), source.get_pixel(3, 0));
+    }
+
+    #[test]
+    fn test_rotate_design_leaves_transparent_corners() {
+        let source =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(20, 20, Rgba([250, 0, 0, 255])));
+        let rotated = Compositor::rotate_design(&source, 45.0, 29, 29).to_rgba8();
+
+        assert_eq!(rotated.get_pixel(0, 0).0[3], 0);
+        assert_eq!(rotated.get_pixel(28, 28).0[3], 0);
+        // Opaque center keeps its color; edge pixels fade without darkening
+        assert_eq!(rotated.get_pixel(14, 14).0, [250, 0, 0, 255]);
+        for pixel in rotated.pixels().filter(|p| p.0[3] > 0) {
+            assert!(pixel.0[0] >= 249, "fringe {:?}", pixel.0);
+        }
+    }
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for value in 0..=255u8 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+    }
+
+    /// Deterministic artwork with gradients, edges, and saturated detail
+    fn jpeg_test_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(256, 256, |x, y| {
+            let wave = ((x as f32 * 0.21).sin() * (y as f32 * 0.13).cos() * 60.0) as i32;
+            let stripe = if (x / 16 + y / 16) % 2 == 0 { 40 } else { 0 };
+            let channel = |base: i32| (base + wave + stripe).clamp(0, 255) as u8;
+            let alpha = if x < 8 { (x * 32) as u8 } else { 255 };
+            Rgba([
+                channel(x as i32),
+                channel(y as i32),
+                channel(200 - x as i32 / 2),
+                alpha,
+            ])
+        }))
+    }
+
+    #[test]
+    fn test_jpeg_presets_meet_size_targets() {
+        let image = jpeg_test_image();
+        let pixels = (image.width() * image.height()) as f64;
+
+        // Upper bound on bytes per pixel for each preset, smallest first
+        let targets = [
+            (JpegPreset::Web, 0.6),
+            (JpegPreset::Standard, 0.9),
+            (JpegPreset::High, 1.8),
+            (JpegPreset::Print, 3.0),
+        ];
+
+        let mut previous = 0;
+        for (preset, max_bytes_per_pixel) in targets {
+            let output =
+                OutputSettings::with_preset(OutputFormat::Jpeg, preset, None, None, None).unwrap();
+            let bytes = Compositor::encode(&image, &output).unwrap();
+
+            assert_eq!(
+                image::guess_format(&bytes).unwrap(),
+                image::ImageFormat::Jpeg
+            );
+            let bytes_per_pixel = bytes.len() as f64 / pixels;
+            assert!(
+                bytes_per_pixel <= max_bytes_per_pixel,
+                "{:?}: {:.2} bytes/pixel exceeds {}",
+                preset,
+                bytes_per_pixel,
+                max_bytes_per_pixel
+            );
+            assert!(
+                bytes.len() > previous,
+                "{:?} is not larger than the previous preset",
+                preset
+            );
+            previous = bytes.len();
+        }
+    }
+
+    #[test]
+    fn test_preset_overrides() {
+        let output = OutputSettings::with_preset(
+            OutputFormat::Jpeg,
+            JpegPreset::Web,
+            Some(90),
+            Some(ChromaSubsampling::Yuv444),
+            None,
+        )
+        .unwrap();
+        assert_eq!(output.quality, 90);
+        assert_eq!(output.chroma_subsampling, ChromaSubsampling::Yuv444);
+
+        // JPEG presets leave other formats at the default quality
+        let webp =
+            OutputSettings::with_preset(OutputFormat::Webp, JpegPreset::Print, None, None, None)
+                .unwrap();
+        assert_eq!(webp.quality, DEFAULT_OUTPUT_QUALITY);
+
+        let subsampling: ChromaSubsampling = serde_json::from_str("\"4:2:0\"").unwrap();
+        assert_eq!(subsampling, ChromaSubsampling::Yuv420);
+    }
+
+    #[test]
+    fn test_output_settings_validation() {
+        assert!(OutputSettings::new(OutputFormat::Jpeg, Some(0), None).is_err());
+        assert!(OutputSettings::new(OutputFormat::Webp, Some(101), None).is_err());
+        assert!(OutputSettings::new(OutputFormat::Jpeg, None, Some("nope")).is_err());
+        assert_eq!(
+            OutputSettings::new(OutputFormat::Png, None, None).unwrap(),
+            OutputSettings::default()
+        );
+    }
+
+    /// Deterministic pseudo-random RGBA image, with some transparent and soft pixels
+    fn noise_image(width: u32, height: u32, seed: u32) -> RgbaImage {
+        let mut state = seed.wrapping_mul(2_654_435_761).max(1);
+        RgbaImage::from_fn(width, height, |_, _| {
+            let mut next = || {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 24) as u8
+            };
+            let [r, g, b] = [next(), next(), next()];
+            let a = match next() {
+                0..=31 => 0,
+                32..=159 => 255,
+                a => a,
+            };
+            Rgba([r, g, b, a])
+        })
+    }
+
+    /// The original serial f64 composite, kept as the reference for the
+    /// parallel implementation
+    fn composite_design_reference(
+        base: &RgbaImage,
+        design: &RgbaImage,
+        x_offset: i32,
+        y_offset: i32,
+        opacity: u8,
+        blend_mode: &str,
+        mask: Option<&GrayImage>,
+    ) -> RgbaImage {
+        let blend: fn(f64, f64) -> f64 = match blend_mode {
+            "multiply" => |b, o| b * o,
+            "screen" => |b, o| 1.0 - (1.0 - b) * (1.0 - o),
+            "overlay" => |b, o| {
+                if b < 0.5 {
+                    2.0 * b * o
+                } else {
+                    1.0 - 2.0 * (1.0 - b) * (1.0 - o)
+                }
+            },
+            _ => |_, o| o,
+        };
+        let mut out = base.clone();
+        for (dx, dy, design_pixel) in design.enumerate_pixels() {
+            let (x, y) = (x_offset + dx as i32, y_offset + dy as i32);
+            if x < 0 || y < 0 || x >= base.width() as i32 || y >= base.height() as i32 {
+                continue;
+            }
+            let mut alpha = (design_pixel.0[3] as f64 * (opacity as f64 / 255.0)) as u32;
+            if let Some(mask) = mask {
+                alpha = alpha * mask.get_pixel(dx, dy).0[0] as u32 / 255;
+            }
+            let pixel = out.get_pixel_mut(x as u32, y as u32);
+            let base_alpha = pixel.0[3] as f64 / 255.0;
+            let src_alpha = alpha as f64 / 255.0 * base_alpha;
+            if src_alpha == 0.0 {
+                continue;
+            }
+            let out_alpha = src_alpha + base_alpha * (1.0 - src_alpha);
+            for i in 0..3 {
+                let b = pixel.0[i] as f64 / 255.0;
+                let o = design_pixel.0[i] as f64 / 255.0;
+                let premultiplied = src_alpha * (1.0 - base_alpha) * o
+                    + src_alpha * base_alpha * blend(b, o)
+                    + (1.0 - src_alpha) * base_alpha * b;
+                pixel.0[i] = (premultiplied / out_alpha * 255.0)
+                    .round()
+                    .clamp(0.0, 255.0) as u8;
+            }
+            pixel.0[3] = (out_alpha * 255.0).round() as u8;
+        }
+        out
+    }
+
+    fn max_pixel_diff(a: &RgbaImage, b: &RgbaImage) -> u8 {
+        a.as_raw()
+            .iter()
+            .zip(b.as_raw())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_composite_design_matches_serial_reference() {
+        let c = Compositor::new();
+        let base = noise_image(97, 61, 1);
+        let design = noise_image(40, 33, 2);
+        let mask = GrayImage::from_fn(40, 33, |x, y| Luma([((x * 7 + y * 13) % 256) as u8]));
+
+        // Inside the base, and hanging off each edge
+        for (x, y) in [(10, 5), (-12, -7), (70, 40), (-50, 0), (200, 10)] {
+            for mode in BLEND_MODES {
+                for opacity in [255, 200, 1] {
+                    for mask in [None, Some(&mask)] {
+                        let out = c
+                            .composite_design(
+                                &DynamicImage::ImageRgba8(base.clone()),
+                                &DynamicImage::ImageRgba8(design.clone()),
+                                x,
+                                y,
+                                opacity,
+                                mode,
+                                mask,
+                            )
+                            .to_rgba8();
+                        let expected =
+                            composite_design_reference(&base, &design, x, y, opacity, mode, mask);
+                        let diff = max_pixel_diff(&out, &expected);
+                        assert!(diff <= 1, "{mode} at ({x}, {y}) opacity {opacity}: {diff}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_luminance_removal_matches_per_pixel_alpha() {
+        let c = Compositor::new();
+        let mut design = noise_image(53, 29, 3);
+        // Mix in white and off-white background so every branch is exercised
+        for (x, y, pixel) in design.enumerate_pixels_mut() {
+            if (x + y) % 3 == 0 {
+                let v = 200 + ((x * 11 + y) % 56) as u8;
+                *pixel = Rgba([v, v, v.saturating_sub((x % 4) as u8), 255]);
+            }
+        }
+        let removal = BackgroundRemoval {
+            mode: BackgroundRemovalMode::Luminance,
+            ..BackgroundRemoval::default()
+        };
+
+        let out = c
+            .remove_white_background(&DynamicImage::ImageRgba8(design.clone()), &removal)
+            .to_rgba8();
+        for (x, y, pixel) in design.enumerate_pixels() {
+            let expected = removal.alpha_for(pixel.0);
+            assert_eq!(out.get_pixel(x, y).0[3], expected, "({x}, {y})");
+        }
+    }
 }
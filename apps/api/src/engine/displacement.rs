@@ -2,9 +2,29 @@
 //!
 //! Applies displacement effects to make designs follow fabric wrinkles and folds.
 
-use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use image::{DynamicImage, GenericImageView, GrayImage, Rgba, RgbaImage};
 use rayon::prelude::*;
 
+/// Rigid or flat products whose templates skip displacement unless a request asks for it
+pub const FLAT_PRODUCT_TYPES: &[&str] = &[
+    "poster",
+    "sticker",
+    "canvas",
+    "phone-case",
+    "airpods-case",
+    "acrylic-ornaments",
+    "luggage-tag",
+    "wrapping-paper",
+];
+
+/// Whether a product type runs the displacement pass when the request doesn't say
+///
+/// Product types are compared case-insensitively, with `_` and spaces read as `-`.
+pub fn displaces_by_default(product_type: &str) -> bool {
+    let normalized = product_type.trim().to_lowercase().replace(['_', ' '], "-");
+    !FLAT_PRODUCT_TYPES.contains(&normalized.as_str())
+}
+
 /// Apply displacement mapping to a design image
 ///
 /// The displacement map is a grayscale image where:
@@ -12,9 +32,16 @@ use rayon::prelude::*;
 /// - Gray (128) = no displacement
 /// - White (255) = push pixels right/down
 ///
+/// A strength map scales `strength` per pixel, from none at black to all of
+/// it at white.
+///
+/// Each output pixel is bilinearly interpolated from the four design pixels
+/// around its fractional source position, clamped to the design's edges.
+///
 /// # Arguments
 /// * `design` - The design image to displace
 /// * `displacement_map` - Grayscale displacement map
+/// * `strength_map` - Optional grayscale per-pixel strength multiplier
 /// * `strength` - Displacement strength in pixels (typical: 5-15)
 ///
 /// # Returns
@@ -22,93 +49,188 @@ use rayon::prelude::*;
 pub fn apply_displacement(
     design: &DynamicImage,
     displacement_map: &DynamicImage,
+    strength_map: Option<&DynamicImage>,
     strength: f64,
 ) -> DynamicImage {
     let (width, height) = design.dimensions();
-    let design_rgba = design.to_rgba8();
-    let disp_gray = displacement_map.to_luma8();
-
-    // Resize displacement map to match design if needed
-    let disp_resized = if disp_gray.dimensions() != (width, height) {
-        image::imageops::resize(
-            &disp_gray,
-            width,
-            height,
-            image::imageops::FilterType::Lanczos3,
-        )
-    } else {
-        disp_gray
-    };
+    if width == 0 || height == 0 {
+        return design.clone();
+    }
+    let source = PremultipliedImage::new(&design.to_rgba8());
+    let disp_resized = gray_at_size(displacement_map, width, height);
+    let strength_resized = strength_map.map(|map| gray_at_size(map, width, height));
+    let strength = strength as f32;
+    let (max_x, max_y) = ((width - 1) as f32, (height - 1) as f32);
+    let row_len = width as usize;
 
     let mut output = RgbaImage::new(width, height);
 
-    // Process rows in parallel using Rayon
-    let rows: Vec<_> = (0..height)
-        .into_par_iter()
-        .map(|y| {
-            let mut row = vec![Rgba([0u8, 0, 0, 0]); width as usize];
+    // Process rows in parallel using Rayon, writing straight into the output
+    output
+        .par_chunks_mut(row_len * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let row_range = y * row_len..(y + 1) * row_len;
+            let disp_row = &disp_resized.as_raw()[row_range.clone()];
+            let strength_row = strength_resized
+                .as_ref()
+                .map(|map| &map.as_raw()[row_range]);
 
-            for x in 0..width {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
                 // Get displacement value (0-255 normalized to -0.5 to 0.5)
-                let disp_value = disp_resized.get_pixel(x, y).0[0] as f64 / 255.0 - 0.5;
+                let disp_value = disp_row[x] as f32 / 255.0 - 0.5;
+                let scale = strength_row.map_or(1.0, |row| row[x] as f32 / 255.0);
+                let offset = disp_value * strength * scale;
 
-                // Calculate source coordinates with displacement
-                let src_x = (x as f64 + disp_value * strength).clamp(0.0, (width - 1) as f64);
-                let src_y = (y as f64 + disp_value * strength).clamp(0.0, (height - 1) as f64);
+                // Clamp to the design's edges rather than sampling past them
+                let src_x = (x as f32 + offset).clamp(0.0, max_x);
+                let src_y = (y as f32 + offset).clamp(0.0, max_y);
 
-                // Bilinear interpolation for smooth sampling
-                let pixel = bilinear_sample(&design_rgba, src_x, src_y);
-                row[x as usize] = pixel;
+                pixel.copy_from_slice(&source.sample(src_x, src_y));
             }
+        });
 
-            row
-        })
-        .collect();
+    DynamicImage::ImageRgba8(output)
+}
 
-    // Copy rows to output image
-    for (y, row) in rows.into_iter().enumerate() {
-        for (x, pixel) in row.into_iter().enumerate() {
-            output.put_pixel(x as u32, y as u32, pixel);
+/// A design with alpha-premultiplied `f32` channels, ready for resampling
+///
+/// Interpolating premultiplied colors keeps the color of transparent pixels
+/// from bleeding into the edges of opaque ones.
+struct PremultipliedImage {
+    pixels: Vec<[f32; 4]>,
+    width: usize,
+    height: usize,
+}
+
+impl PremultipliedImage {
+    fn new(image: &RgbaImage) -> Self {
+        let pixels = image
+            .as_raw()
+            .par_chunks_exact(4)
+            .map(|p| {
+                let alpha = p[3] as f32 / 255.0;
+                [
+                    p[0] as f32 * alpha,
+                    p[1] as f32 * alpha,
+                    p[2] as f32 * alpha,
+                    p[3] as f32,
+                ]
+            })
+            .collect();
+        PremultipliedImage {
+            pixels,
+            width: image.width() as usize,
+            height: image.height() as usize,
         }
     }
 
-    DynamicImage::ImageRgba8(output)
+    /// Bilinear interpolation of the four pixels around `(x, y)`, which must
+    /// lie within the image
+    ///
+    /// Where all four are fully transparent the result is transparent black.
+    fn sample(&self, x: f32, y: f32) -> [u8; 4] {
+        // Truncation is floor for the non-negative coordinates given
+        let (x0, y0) = (x as usize, y as usize);
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+        let p00 = self.pixels[y0 * self.width + x0];
+        let p10 = self.pixels[y0 * self.width + x1];
+        let p01 = self.pixels[y1 * self.width + x0];
+        let p11 = self.pixels[y1 * self.width + x1];
+
+        let value: [f32; 4] = std::array::from_fn(|i| {
+            let top = p00[i] + (p10[i] - p00[i]) * fx;
+            let bottom = p01[i] + (p11[i] - p01[i]) * fx;
+            top + (bottom - top) * fy
+        });
+
+        let alpha = value[3].round();
+        if alpha < 1.0 {
+            return [0, 0, 0, 0];
+        }
+        let unpremultiply = 255.0 / value[3];
+        [
+            (value[0] * unpremultiply).round().min(255.0) as u8,
+            (value[1] * unpremultiply).round().min(255.0) as u8,
+            (value[2] * unpremultiply).round().min(255.0) as u8,
+            alpha.min(255.0) as u8,
+        ]
+    }
 }
 
-/// Bilinear interpolation for smooth pixel sampling
-fn bilinear_sample(image: &RgbaImage, x: f64, y: f64) -> Rgba<u8> {
-    let (width, height) = image.dimensions();
-
-    let x0 = x.floor() as u32;
-    let y0 = y.floor() as u32;
-    let x1 = (x0 + 1).min(width - 1);
-    let y1 = (y0 + 1).min(height - 1);
-
-    let dx = x - x0 as f64;
-    let dy = y - y0 as f64;
-
-    let p00 = image.get_pixel(x0, y0);
-    let p10 = image.get_pixel(x1, y0);
-    let p01 = image.get_pixel(x0, y1);
-    let p11 = image.get_pixel(x1, y1);
-
-    let mut result = [0u8; 4];
-    for i in 0..4 {
-        let v00 = p00.0[i] as f64;
-        let v10 = p10.0[i] as f64;
-        let v01 = p01.0[i] as f64;
-        let v11 = p11.0[i] as f64;
-
-        // Bilinear interpolation formula
-        let value = v00 * (1.0 - dx) * (1.0 - dy)
-            + v10 * dx * (1.0 - dy)
-            + v01 * (1.0 - dx) * dy
-            + v11 * dx * dy;
-
-        result[i] = value.clamp(0.0, 255.0) as u8;
+/// A map as grayscale, resized to match the design if needed
+fn gray_at_size(map: &DynamicImage, width: u32, height: u32) -> GrayImage {
+    let gray = map.to_luma8();
+    if gray.dimensions() == (width, height) {
+        return gray;
     }
+    image::imageops::resize(&gray, width, height, image::imageops::FilterType::Lanczos3)
+}
+
+/// Pixel shift of a map's strongest folds at full realism
+const MAX_REALISM_SHIFT: f64 = 4.0;
 
-    Rgba(result)
+/// Intensity of a displacement map over a template's print area
+///
+/// Deviations are distances from neutral gray as a fraction of the full range,
+/// so 0.5 is pure black or white. At strength `s` a pixel deviating by `d`
+/// shifts by `d * s` pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplacementStats {
+    pub mean_deviation: f64,
+    pub p95_deviation: f64,
+}
+
+impl DisplacementStats {
+    /// Measure the map within a region, clipped to the map's bounds
+    ///
+    /// Returns `None` when the region misses the map entirely.
+    pub fn measure(map: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> Option<Self> {
+        let (map_w, map_h) = map.dimensions();
+        let width = width.min(map_w.saturating_sub(x));
+        let height = height.min(map_h.saturating_sub(y));
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let gray = map.crop_imm(x, y, width, height).to_luma8();
+        let mut histogram = [0u64; 129];
+        for pixel in gray.pixels() {
+            histogram[(pixel.0[0] as i32 - 128).unsigned_abs() as usize] += 1;
+        }
+
+        let count = gray.pixels().len() as u64;
+        let total: u64 = histogram
+            .iter()
+            .enumerate()
+            .map(|(deviation, n)| deviation as u64 * n)
+            .sum();
+        let p95_rank = count - count / 20;
+        let mut seen = 0;
+        let p95 = histogram
+            .iter()
+            .position(|n| {
+                seen += n;
+                seen >= p95_rank
+            })
+            .unwrap_or(128);
+
+        Some(DisplacementStats {
+            mean_deviation: total as f64 / count as f64 / 255.0,
+            p95_deviation: p95 as f64 / 255.0,
+        })
+    }
+
+    /// Strength at which the map's strongest folds (its 95th percentile) shift
+    /// pixels by `realism` of `MAX_REALISM_SHIFT`, before range clamping
+    ///
+    /// A map with no measurable folds gives `None`; any strength renders the same.
+    pub fn strength_for(&self, realism: f64) -> Option<f64> {
+        (self.p95_deviation > 0.0).then(|| realism * MAX_REALISM_SHIFT / self.p95_deviation)
+    }
 }
 
 /// Apply multiply blend mode
@@ -139,27 +261,18 @@ pub fn blend_multiply(base: &RgbaImage, overlay: &RgbaImage) -> RgbaImage {
     output
 }
 
-/// Apply opacity to an image
-pub fn apply_opacity(image: &RgbaImage, opacity: u8) -> RgbaImage {
-    let opacity_factor = opacity as f64 / 255.0;
-    let (width, height) = image.dimensions();
-    let mut output = RgbaImage::new(width, height);
-
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = image.get_pixel(x, y);
-            let new_alpha = (pixel.0[3] as f64 * opacity_factor) as u8;
-            output.put_pixel(x, y, Rgba([pixel.0[0], pixel.0[1], pixel.0[2], new_alpha]));
-        }
-    }
-
-    output
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_flat_products_skip_displacement() {
+        assert!(!displaces_by_default("poster"));
+        assert!(!displaces_by_default("Phone_Case"));
+        assert!(displaces_by_default("tshirt"));
+        assert!(displaces_by_default("pillow"));
+    }
+
     #[test]
     fn test_bilinear_sample_center() {
         let mut img = RgbaImage::new(2, 2);
@@ -168,8 +281,126 @@ mod tests {
         img.put_pixel(0, 1, Rgba([100, 100, 100, 255]));
         img.put_pixel(1, 1, Rgba([200, 200, 200, 255]));
 
-        let result = bilinear_sample(&img, 0.5, 0.5);
+        let result = PremultipliedImage::new(&img).sample(0.5, 0.5);
         // Should be average of all 4 pixels = 150
-        assert!((result.0[0] as i32 - 150).abs() < 5);
+        assert!((result[0] as i32 - 150).abs() < 5);
+    }
+
+    #[test]
+    fn test_transparent_neighbors_leave_no_halo() {
+        // An opaque square on a transparent black field
+        let design = DynamicImage::ImageRgba8(RgbaImage::from_fn(16, 16, |x, y| {
+            if (5..11).contains(&x) && (5..11).contains(&y) {
+                Rgba([255, 200, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        }));
+        // Shifts by about 2.02px, so the square's edges land between pixels
+        let map = DynamicImage::ImageLuma8(GrayImage::from_pixel(16, 16, image::Luma([192])));
+
+        let displaced = apply_displacement(&design, &map, None, 8.0).to_rgba8();
+        let mut partial = 0;
+        for (x, y, pixel) in displaced.enumerate_pixels() {
+            match pixel.0[3] {
+                0 => assert_eq!(pixel.0, [0, 0, 0, 0], "({x}, {y})"),
+                alpha => {
+                    assert_eq!(pixel.0[..3], [255, 200, 0], "({x}, {y})");
+                    partial += (alpha < 255) as u32;
+                }
+            }
+        }
+        assert!(partial > 0, "edges should be blended");
+        assert_eq!(displaced.get_pixel(0, 0).0, [0, 0, 0, 0]);
+        assert_eq!(displaced.get_pixel(15, 15).0, [0, 0, 0, 0]);
+    }
+
+    /// A checkerboard displaced along a horizontal gradient, which shifts the
+    /// left edge up and left and the right edge down and right by up to 3px
+    fn displaced_checkerboard() -> RgbaImage {
+        let design = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 32, |x, y| {
+            if (x / 4 + y / 4) % 2 == 0 {
+                Rgba([220, 40, 40, 255])
+            } else {
+                Rgba([30, 60, 200, 255])
+            }
+        }));
+        let map = DynamicImage::ImageLuma8(GrayImage::from_fn(32, 32, |x, _| {
+            image::Luma([(x * 255 / 31) as u8])
+        }));
+        apply_displacement(&design, &map, None, 6.0).to_rgba8()
+    }
+
+    /// Compare against the committed render; set `UPDATE_GOLDEN=1` to rewrite it
+    #[test]
+    fn test_checkerboard_matches_golden_image() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/engine/testdata/displacement_checkerboard.png"
+        );
+        let displaced = displaced_checkerboard();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            displaced.save(path).unwrap();
+        }
+
+        let golden = image::open(path).unwrap().to_rgba8();
+        assert_eq!(displaced.dimensions(), golden.dimensions());
+        for (x, y, pixel) in displaced.enumerate_pixels() {
+            let expected = golden.get_pixel(x, y);
+            let diff = pixel
+                .0
+                .iter()
+                .zip(expected.0)
+                .map(|(a, b)| a.abs_diff(b))
+                .max()
+                .unwrap_or(0);
+            assert!(diff <= 2, "({x}, {y}): {:?} vs {:?}", pixel.0, expected.0);
+        }
+    }
+
+    #[test]
+    fn test_zero_strength_pixels_stay_in_place() {
+        let design = DynamicImage::ImageRgba8(RgbaImage::from_fn(20, 20, |x, y| {
+            Rgba([(x * 12) as u8, (y * 12) as u8, 0, 255])
+        }));
+        // White pushes every pixel 5px right and down at strength 10
+        let map = DynamicImage::ImageLuma8(GrayImage::from_pixel(20, 20, image::Luma([255])));
+        // No strength on the left half, full strength on the right
+        let strength = DynamicImage::ImageLuma8(GrayImage::from_fn(20, 20, |x, _| {
+            image::Luma([if x < 10 { 0 } else { 255 }])
+        }));
+
+        let displaced = apply_displacement(&design, &map, Some(&strength), 10.0);
+        for (x, y, pixel) in displaced.pixels() {
+            if x < 10 {
+                assert_eq!(pixel, design.get_pixel(x, y), "({x}, {y})");
+            } else if x < 15 && y < 15 {
+                assert_eq!(pixel, design.get_pixel(x + 5, y + 5), "({x}, {y})");
+            }
+        }
+
+        let uniform = apply_displacement(&design, &map, None, 10.0);
+        assert_eq!(uniform.get_pixel(2, 2), design.get_pixel(7, 7));
+    }
+
+    #[test]
+    fn test_displacement_stats() {
+        // Left half neutral, right half deviating by 51 (0.2 of the range)
+        let map =
+            image::GrayImage::from_fn(20, 10, |x, _| image::Luma([if x < 10 { 128 } else { 179 }]));
+        let map = DynamicImage::ImageLuma8(map);
+
+        let stats = DisplacementStats::measure(&map, 0, 0, 20, 10).unwrap();
+        assert!((stats.mean_deviation - 0.1).abs() < 1e-9);
+        assert!((stats.p95_deviation - 0.2).abs() < 1e-9);
+        assert!((stats.strength_for(0.5).unwrap() - 10.0).abs() < 1e-9);
+
+        let flat = DisplacementStats::measure(&map, 0, 0, 10, 10).unwrap();
+        assert_eq!(flat.p95_deviation, 0.0);
+        assert_eq!(flat.strength_for(1.0), None);
+
+        // Clipped to the map, or missing it
+        assert!(DisplacementStats::measure(&map, 15, 5, 100, 100).is_some());
+        assert!(DisplacementStats::measure(&map, 20, 0, 5, 5).is_none());
     }
 }
@@ -0,0 +1,172 @@
+//! Admission control for mockup generation
+//!
+//! Each generation holds a decoded template and design in memory, so only a
+//! fixed number run at once. Callers wait a bounded time for a slot and are
+//! turned away when none frees up, instead of queueing without limit.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit, TryAcquireError};
+
+use super::template::TemplateError;
+
+/// Caps concurrent generations, with a bounded wait for a free slot
+pub struct GenerationLimiter {
+    slots: Semaphore,
+    max_concurrent: usize,
+    max_wait: Duration,
+    queued: AtomicUsize,
+}
+
+/// Generations running and waiting for a slot
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct GenerationLoad {
+    pub in_flight: usize,
+    pub queued: usize,
+    pub max_concurrent: usize,
+}
+
+/// Counts a caller as queued until it gets a slot, times out, or goes away
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl<'a> QueuedGuard<'a> {
+    fn new(queued: &'a AtomicUsize) -> Self {
+        queued.fetch_add(1, Ordering::Relaxed);
+        QueuedGuard(queued)
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl GenerationLimiter {
+    /// At most `max_concurrent` generations at once (minimum 1), each caller
+    /// waiting up to `max_wait` for a slot
+    pub fn new(max_concurrent: usize, max_wait: Duration) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        GenerationLimiter {
+            slots: Semaphore::new(max_concurrent),
+            max_concurrent,
+            max_wait,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Effectively unbounded, for managers that were never given a limit
+    pub fn unlimited() -> Self {
+        Self::new(Semaphore::MAX_PERMITS, Duration::ZERO)
+    }
+
+    /// Wait for a generation slot, held until the permit is dropped
+    ///
+    /// Fails with [`TemplateError::Saturated`] when every slot stays busy for
+    /// the whole wait, and with [`TemplateError::ShuttingDown`] once `close`
+    /// was called.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, TemplateError> {
+        match self.slots.try_acquire() {
+            Ok(permit) => return Ok(permit),
+            Err(TryAcquireError::Closed) => return Err(TemplateError::ShuttingDown),
+            Err(TryAcquireError::NoPermits) => {}
+        }
+
+        let _queued = QueuedGuard::new(&self.queued);
+        match tokio::time::timeout(self.max_wait, self.slots.acquire()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            Ok(Err(_)) => Err(TemplateError::ShuttingDown),
+            Err(_) => Err(TemplateError::Saturated {
+                max_concurrent: self.max_concurrent,
+                retry_after_secs: self.max_wait.as_secs().max(1),
+            }),
+        }
+    }
+
+    /// Refuse new generations, including callers waiting for a slot
+    ///
+    /// Generations already running keep their slot until they finish.
+    pub fn close(&self) {
+        self.slots.close();
+    }
+
+    /// Current in-flight and queued generations
+    pub fn load(&self) -> GenerationLoad {
+        GenerationLoad {
+            in_flight: self.max_concurrent - self.slots.available_permits(),
+            queued: self.queued.load(Ordering::Relaxed),
+            max_concurrent: self.max_concurrent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_times_out_when_saturated() {
+        let limiter = GenerationLimiter::new(1, Duration::from_millis(20));
+        let held = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.load().in_flight, 1);
+
+        let err = limiter.acquire().await.unwrap_err();
+        assert!(matches!(
+            err,
+            TemplateError::Saturated {
+                max_concurrent: 1,
+                retry_after_secs: 1
+            }
+        ));
+        assert_eq!(limiter.load().queued, 0);
+
+        drop(held);
+        assert!(limiter.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_waiting_callers_are_counted_as_queued() {
+        let limiter = std::sync::Arc::new(GenerationLimiter::new(1, Duration::from_secs(5)));
+        let held = limiter.acquire().await.unwrap();
+
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(drop) })
+        };
+        while limiter.load().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(limiter.load().in_flight, 1);
+
+        drop(held);
+        waiter.await.unwrap().unwrap();
+        let load = limiter.load();
+        assert_eq!((load.in_flight, load.queued), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_closed_limiter_refuses_new_generations() {
+        let limiter = std::sync::Arc::new(GenerationLimiter::new(1, Duration::from_secs(5)));
+        let held = limiter.acquire().await.unwrap();
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(drop) })
+        };
+        while limiter.load().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        limiter.close();
+        let err = waiter.await.unwrap().unwrap_err();
+        assert!(matches!(err, TemplateError::ShuttingDown));
+        assert!(matches!(
+            limiter.acquire().await.unwrap_err(),
+            TemplateError::ShuttingDown
+        ));
+
+        // The running generation keeps its slot until it finishes
+        assert_eq!(limiter.load().in_flight, 1);
+        drop(held);
+    }
+}
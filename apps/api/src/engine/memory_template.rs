@@ -0,0 +1,227 @@
+//! Templates built from images already in memory
+//!
+//! Catalog products have a mockup photo and a print area, but no template
+//! directory. This adapter turns the two into the metadata and images the
+//! compositor renders against, fitting the print area onto the photo.
+
+use image::{DynamicImage, GenericImageView};
+
+use super::template::{PrintArea, TemplateDimensions, TemplateImages, TemplateMetadata};
+
+/// A template with no directory, rendered with `TemplateManager::generate_with`
+pub struct MemoryTemplate {
+    pub metadata: TemplateMetadata,
+    pub images: TemplateImages,
+    /// How the print area had to be adjusted to fit the base image
+    pub warnings: Vec<String>,
+}
+
+impl MemoryTemplate {
+    /// A template of a single base photo with a print area of `size`
+    ///
+    /// `offset` is the print area's top-left corner; without one the print
+    /// area is centered. A print area larger than the photo is scaled down to
+    /// fit, and one hanging off its edge is moved inside. Each adjustment is
+    /// listed in `warnings`.
+    pub fn new(
+        id: &str,
+        placement: &str,
+        base_image: DynamicImage,
+        size: (i32, i32),
+        offset: Option<(i32, i32)>,
+    ) -> Self {
+        let (image_width, image_height) = base_image.dimensions();
+        let (print_area, warnings) =
+            fit_print_area((image_width as i32, image_height as i32), size, offset);
+        let metadata = TemplateMetadata::from_provider_mockup(
+            id,
+            placement,
+            TemplateDimensions {
+                width: image_width,
+                height: image_height,
+            },
+            print_area,
+        );
+
+        MemoryTemplate {
+            metadata,
+            images: TemplateImages::from_base(base_image),
+            warnings,
+        }
+    }
+}
+
+/// Place a `size` print area on an `image` sized template
+fn fit_print_area(
+    image: (i32, i32),
+    size: (i32, i32),
+    offset: Option<(i32, i32)>,
+) -> (PrintArea, Vec<String>) {
+    let mut warnings = Vec::new();
+    let (mut width, mut height) = size;
+    let mut offset = offset;
+
+    if width <= 0 || height <= 0 {
+        warnings.push(format!(
+            "Print area is {}x{}; using the whole template",
+            width, height
+        ));
+        (width, height) = image;
+        offset = Some((0, 0));
+    } else if width > image.0 || height > image.1 {
+        let scale = (image.0 as f64 / width as f64).min(image.1 as f64 / height as f64);
+        let scaled = |value: i32| (value as f64 * scale).round() as i32;
+        warnings.push(format!(
+            "Print area {}x{} is larger than the {}x{} template; scaled to {}x{}",
+            width,
+            height,
+            image.0,
+            image.1,
+            scaled(width).max(1),
+            scaled(height).max(1)
+        ));
+        (width, height) = (scaled(width).max(1), scaled(height).max(1));
+        offset = offset.map(|(x, y)| (scaled(x), scaled(y)));
+    }
+
+    let (x, y) = match offset {
+        Some((x, y)) => {
+            let fitted = (x.clamp(0, image.0 - width), y.clamp(0, image.1 - height));
+            if fitted != (x, y) {
+                warnings.push(format!(
+                    "Print area at ({}, {}) extends past the template; moved to ({}, {})",
+                    x, y, fitted.0, fitted.1
+                ));
+            }
+            fitted
+        }
+        None => {
+            warnings.push("Print area has no position; centered on the template".to_string());
+            ((image.0 - width) / 2, (image.1 - height) / 2)
+        }
+    };
+
+    (
+        PrintArea {
+            x,
+            y,
+            width,
+            height,
+        },
+        warnings,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::PlacementSpec;
+    use crate::engine::compositor::{
+        Compositor, DesignLayer, DesignSource, GenerationLimits, MockupRequest, OutputSettings,
+    };
+    use image::{Rgba, RgbaImage};
+
+    fn area(x: i32, y: i32, width: i32, height: i32) -> PrintArea {
+        PrintArea {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn assert_area(actual: &PrintArea, expected: PrintArea) {
+        assert_eq!(
+            (actual.x, actual.y, actual.width, actual.height),
+            (expected.x, expected.y, expected.width, expected.height)
+        );
+    }
+
+    #[test]
+    fn test_print_area_offsets_are_used_as_given() {
+        let (fitted, warnings) = fit_print_area((200, 300), (80, 100), Some((20, 40)));
+        assert_area(&fitted, area(20, 40, 80, 100));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_print_area_without_offsets_is_centered() {
+        let (fitted, warnings) = fit_print_area((200, 300), (80, 100), None);
+        assert_area(&fitted, area(60, 100, 80, 100));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("centered"));
+    }
+
+    #[test]
+    fn test_print_area_is_fitted_inside_the_template() {
+        // Twice the template's width: halved, offset included
+        let (fitted, warnings) = fit_print_area((200, 300), (400, 200), Some((0, 100)));
+        assert_area(&fitted, area(0, 50, 200, 100));
+        assert_eq!(warnings.len(), 1);
+
+        // Hanging off the bottom right corner
+        let (fitted, warnings) = fit_print_area((200, 300), (80, 100), Some((150, 250)));
+        assert_area(&fitted, area(120, 200, 80, 100));
+        assert!(warnings[0].contains("moved to (120, 200)"));
+
+        let (fitted, _) = fit_print_area((200, 300), (0, 100), None);
+        assert_area(&fitted, area(0, 0, 200, 300));
+    }
+
+    #[tokio::test]
+    async fn test_design_renders_inside_the_print_area() {
+        // A white product photo, and a solid red design filling the print area
+        let base = RgbaImage::from_pixel(60, 80, Rgba([255, 255, 255, 255]));
+        let template = MemoryTemplate::new(
+            "catalog:tee:front",
+            "front",
+            DynamicImage::ImageRgba8(base),
+            (20, 20),
+            Some((10, 30)),
+        );
+        assert!(template.warnings.is_empty());
+        assert!(!template.metadata.displacement.enabled);
+
+        let mut design = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(20, 20, Rgba([255, 0, 0, 255])))
+            .write_to(
+                &mut std::io::Cursor::new(&mut design),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        let request = MockupRequest {
+            designs: vec![DesignLayer {
+                design: DesignSource::Bytes(design.into()),
+                placement: PlacementSpec {
+                    scale: 1.0,
+                    offset_x: 0,
+                    offset_y: 0,
+                    print_area_width: 20,
+                    print_area_height: 20,
+                    ..PlacementSpec::default()
+                },
+                displacement_strength: 0.0,
+                blend_mode: None,
+            }],
+            template_id: template.metadata.id.clone(),
+            apply_displacement: None,
+            tint_color: None,
+            remove_background: None,
+            output: OutputSettings::default(),
+            limits: GenerationLimits::default(),
+        };
+
+        let result = Compositor::new()
+            .generate(&request, &template.metadata, &template.images)
+            .await
+            .unwrap();
+        let mockup = image::load_from_memory(&result.bytes).unwrap().to_rgba8();
+
+        assert_eq!(mockup.dimensions(), (60, 80));
+        // Multiplied onto white, the design stays red inside the print area
+        let inside = mockup.get_pixel(20, 40);
+        assert!(inside[0] > 200 && inside[1] < 60, "{inside:?}");
+        // and the photo is untouched outside it
+        assert_eq!(mockup.get_pixel(2, 2), &Rgba([255, 255, 255, 255]));
+    }
+}
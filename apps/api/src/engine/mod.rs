@@ -2,12 +2,40 @@
 //!
 //! This module contains the core mockup generation logic including:
 //! - Template loading and management
+//! - Templates built from in-memory catalog images
+//! - Template uploads and shared template libraries
 //! - Displacement mapping algorithm
 //! - Image compositing pipeline
+//! - Print-ready file export
+//! - Admission control for concurrent generations
+//! - Similarity scoring against provider renders
 
 mod compositor;
 mod displacement;
+mod limiter;
+mod memory_template;
+mod parity;
+mod print_file;
+mod starter;
 mod template;
+mod template_source;
+mod template_upload;
 
-pub use compositor::MockupRequest;
-pub use template::TemplateManager;
+pub use compositor::{
+    BackgroundRemoval, BackgroundRemovalMode, ChromaSubsampling, Compositor, DesignLayer,
+    DesignLimits, DesignSource, GenerationLimits, JpegPreset, MockupRequest, MockupResult,
+    OutputFormat, OutputSettings, StageTimings, BLEND_MODES,
+};
+pub use displacement::{apply_displacement, DisplacementStats};
+pub use memory_template::MemoryTemplate;
+pub use parity::{compare_renders, ParityMetrics};
+pub use print_file::{PrintFile, DEFAULT_MIN_DPI};
+pub use starter::write_starter_templates;
+pub use template::{
+    geometry_test_pattern, AnchorPoint, DisplacementConfig, DisplacementMode, EvictionPolicy,
+    PrintArea, TemplateDimensions, TemplateError, TemplateGeometry, TemplateImages,
+    TemplateLoadReport, TemplateManager, TemplateMemoryStats, TemplateMetadata,
+    TemplateReloadSummary,
+};
+pub use template_source::{TemplatePull, TemplateSource};
+pub use template_upload::{InstalledTemplate, TemplateFiles, TemplateUpload};
@@ -0,0 +1,215 @@
+//! Image similarity between our mockups and provider previews
+//!
+//! Both images are flattened onto white and scaled to a common size before
+//! comparing structure (SSIM on luma) and color (mean CIE76 delta E in Lab).
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, RgbImage};
+
+use super::compositor::srgb_to_linear;
+
+/// Longest side images are compared at
+const COMPARE_MAX_SIDE: u32 = 512;
+
+/// SSIM window size and step, in pixels
+const SSIM_WINDOW: u32 = 8;
+const SSIM_STEP: u32 = 4;
+
+/// Mean delta E at which the color component of the score reaches zero
+const DELTA_E_CEILING: f64 = 25.0;
+
+/// Weight of SSIM in the combined score; color makes up the rest
+const SSIM_WEIGHT: f64 = 0.7;
+
+/// How closely two renders of the same product match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParityMetrics {
+    /// Structural similarity of luma, -1.0 to 1.0 (1.0 is identical)
+    pub ssim: f64,
+    /// Mean CIE76 color difference; below ~2.3 is imperceptible
+    pub delta_e: f64,
+    /// Combined 0-100 score: 70% SSIM, 30% color, with color reaching zero at delta E 25
+    pub score: f64,
+}
+
+/// Compare `ours` against the provider's `reference` render
+///
+/// `ours` is resized to the reference's dimensions first, so differing output
+/// sizes don't count against the score.
+pub fn compare_renders(ours: &DynamicImage, reference: &DynamicImage) -> ParityMetrics {
+    let (width, height) = comparison_size(reference.width(), reference.height());
+    let ours = flatten(&ours.resize_exact(width, height, FilterType::Triangle));
+    let reference = flatten(&reference.resize_exact(width, height, FilterType::Triangle));
+
+    let ssim = ssim(&luma(&ours), &luma(&reference));
+    let delta_e = mean_delta_e(&ours, &reference);
+    let color = (1.0 - delta_e / DELTA_E_CEILING).clamp(0.0, 1.0);
+    let score = 100.0 * (SSIM_WEIGHT * ssim.max(0.0) + (1.0 - SSIM_WEIGHT) * color);
+
+    ParityMetrics {
+        ssim,
+        delta_e,
+        score,
+    }
+}
+
+fn comparison_size(width: u32, height: u32) -> (u32, u32) {
+    let longest = width.max(height).max(1);
+    if longest <= COMPARE_MAX_SIDE {
+        return (width.max(1), height.max(1));
+    }
+    let ratio = COMPARE_MAX_SIDE as f64 / longest as f64;
+    (
+        ((width as f64 * ratio).round() as u32).max(1),
+        ((height as f64 * ratio).round() as u32).max(1),
+    )
+}
+
+/// Composite onto white so transparent areas compare like provider previews
+fn flatten(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let alpha = a as u32;
+        let over_white = |c: u8| ((c as u32 * alpha + 255 * (255 - alpha)) / 255) as u8;
+        image::Rgb([over_white(r), over_white(g), over_white(b)])
+    })
+}
+
+fn luma(image: &RgbImage) -> GrayImage {
+    DynamicImage::ImageRgb8(image.clone()).to_luma8()
+}
+
+/// Mean SSIM over overlapping windows
+fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (width, height) = a.dimensions();
+    let window_w = SSIM_WINDOW.min(width);
+    let window_h = SSIM_WINDOW.min(height);
+    let mut total = 0.0;
+    let mut windows = 0u32;
+
+    let mut y = 0;
+    while y + window_h <= height {
+        let mut x = 0;
+        while x + window_w <= width {
+            let n = (window_w * window_h) as f64;
+            let (mut sum_a, mut sum_b) = (0.0, 0.0);
+            let (mut sq_a, mut sq_b, mut cross) = (0.0, 0.0, 0.0);
+            for dy in 0..window_h {
+                for dx in 0..window_w {
+                    let pa = a.get_pixel(x + dx, y + dy).0[0] as f64;
+                    let pb = b.get_pixel(x + dx, y + dy).0[0] as f64;
+                    sum_a += pa;
+                    sum_b += pb;
+                    sq_a += pa * pa;
+                    sq_b += pb * pb;
+                    cross += pa * pb;
+                }
+            }
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sq_a / n - mean_a * mean_a;
+            let var_b = sq_b / n - mean_b * mean_b;
+            let covariance = cross / n - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+            x += SSIM_STEP;
+        }
+        y += SSIM_STEP;
+    }
+
+    if windows == 0 {
+        1.0
+    } else {
+        total / windows as f64
+    }
+}
+
+fn mean_delta_e(a: &RgbImage, b: &RgbImage) -> f64 {
+    let pixels = (a.width() * a.height()).max(1) as f64;
+    let total: f64 = a
+        .pixels()
+        .zip(b.pixels())
+        .map(|(pa, pb)| {
+            let (l1, a1, b1) = srgb_to_lab(pa.0);
+            let (l2, a2, b2) = srgb_to_lab(pb.0);
+            ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+        })
+        .sum();
+    total / pixels
+}
+
+/// sRGB to CIELAB under D65
+fn srgb_to_lab([r, g, b]: [u8; 3]) -> (f64, f64, f64) {
+    let (r, g, b) = (
+        srgb_to_linear(r) as f64,
+        srgb_to_linear(g) as f64,
+        srgb_to_linear(b) as f64,
+    );
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.950_47;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.088_83;
+
+    let f = |t: f64| {
+        if t > 0.008_856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x * 255 / width) as u8, (y * 255 / height) as u8, 128, 255])
+        }))
+    }
+
+    #[test]
+    fn test_identical_images_score_full() {
+        let image = gradient(64, 48);
+        let metrics = compare_renders(&image, &image);
+        assert!((metrics.ssim - 1.0).abs() < 1e-9);
+        assert!(metrics.delta_e < 1e-9);
+        assert!((metrics.score - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_size_differences_are_ignored() {
+        let metrics = compare_renders(&gradient(128, 96), &gradient(64, 48));
+        assert!(metrics.score > 95.0, "{metrics:?}");
+    }
+
+    #[test]
+    fn test_color_shift_lowers_score() {
+        let reference = gradient(64, 48);
+        let shifted = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 48, |x, y| {
+            Rgba([(x * 255 / 64) as u8, (y * 255 / 48) as u8, 20, 255])
+        }));
+        let metrics = compare_renders(&shifted, &reference);
+        assert!(metrics.delta_e > 10.0, "{metrics:?}");
+        assert!(metrics.score < 90.0, "{metrics:?}");
+
+        let white = DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 48, Rgba([255; 4])));
+        assert!(compare_renders(&white, &reference).score < 50.0);
+    }
+
+    #[test]
+    fn test_srgb_to_lab_reference_colors() {
+        let (l, a, b) = srgb_to_lab([255, 255, 255]);
+        assert!((l - 100.0).abs() < 0.1 && a.abs() < 0.1 && b.abs() < 0.1);
+        let (l, _, _) = srgb_to_lab([0, 0, 0]);
+        assert!(l.abs() < 0.1);
+    }
+}
@@ -0,0 +1,212 @@
+//! Print-ready file rendering
+//!
+//! A print file is the design alone, placed in the print area's own pixel
+//! grid on a transparent canvas: no template, displacement, blending, or
+//! background removal. The PNG carries its DPI in a pHYs chunk so RIP
+//! software prints it at the intended physical size.
+
+use bytes::Bytes;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use tracing::info;
+
+use super::compositor::{Compositor, CompositorError, DesignLayer, GenerationLimits};
+use crate::domain::PlacementSpec;
+
+/// Lowest design resolution, at its printed size, accepted for templates that don't set one
+pub const DEFAULT_MIN_DPI: u32 = 150;
+
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// An encoded print file
+#[derive(Debug, Clone)]
+pub struct PrintFile {
+    pub width: u32,
+    pub height: u32,
+    /// Resolution recorded in the PNG
+    pub dpi: u32,
+    pub bytes: Bytes,
+    /// Each design's source resolution at its printed size, in request order
+    pub design_dpi: Vec<f64>,
+}
+
+impl Compositor {
+    /// Render designs onto a transparent print area canvas and encode it as PNG
+    ///
+    /// Placements must already be sized to the print area.
+    pub async fn print_file(
+        &self,
+        designs: &[DesignLayer],
+        dpi: u32,
+        limits: &GenerationLimits,
+    ) -> Result<PrintFile, CompositorError> {
+        limits.check("fetching designs")?;
+        let images = self.load_designs(designs, limits).await?;
+
+        limits.check("compositing")?;
+        let layers: Vec<_> = images
+            .iter()
+            .zip(designs)
+            .map(|(image, layer)| (image, &layer.placement))
+            .collect();
+        let design_dpi = layers
+            .iter()
+            .map(|(image, placement)| design_dpi(image.width(), placement, dpi))
+            .collect();
+        let canvas = render_print_file(&layers);
+
+        limits.check("encoding")?;
+        let bytes = encode_print_png(&canvas, dpi)?;
+        info!(
+            width = canvas.width(),
+            height = canvas.height(),
+            dpi,
+            bytes = bytes.len(),
+            "Print file complete"
+        );
+
+        Ok(PrintFile {
+            width: canvas.width(),
+            height: canvas.height(),
+            dpi,
+            bytes: Bytes::from(bytes),
+            design_dpi,
+        })
+    }
+}
+
+/// Draw each design at its placement on a transparent canvas the size of the
+/// first placement's print area
+pub fn render_print_file(layers: &[(&DynamicImage, &PlacementSpec)]) -> RgbaImage {
+    let (width, height) = layers.first().map_or((1, 1), |(_, placement)| {
+        (
+            placement.print_area_width.max(1) as u32,
+            placement.print_area_height.max(1) as u32,
+        )
+    });
+    let mut canvas = RgbaImage::new(width, height);
+
+    for (design, placement) in layers {
+        let (design_width, design_height) = placement.get_design_dimensions();
+        let resized = design.resize_exact(
+            design_width.max(1) as u32,
+            design_height.max(1) as u32,
+            FilterType::Lanczos3,
+        );
+        let placed = if placement.rotation_degrees != 0.0 {
+            let (width, height) = placement.get_rotated_dimensions();
+            Compositor::rotate_design(
+                &resized,
+                placement.rotation_degrees,
+                width.max(1) as u32,
+                height.max(1) as u32,
+            )
+        } else {
+            resized
+        };
+        let (x, y) = placement.get_absolute_position();
+        image::imageops::overlay(&mut canvas, &placed.to_rgba8(), x as i64, y as i64);
+    }
+    canvas
+}
+
+/// Resolution of a design `source_width` pixels wide when printed at its placement
+pub fn design_dpi(source_width: u32, placement: &PlacementSpec, dpi: u32) -> f64 {
+    let (printed_width, _) = placement.get_design_dimensions();
+    source_width as f64 * dpi as f64 / printed_width.max(1) as f64
+}
+
+/// Encode as PNG with a pHYs chunk recording `dpi`
+pub fn encode_print_png(image: &RgbaImage, dpi: u32) -> Result<Vec<u8>, CompositorError> {
+    let mut png = Compositor::encode_png(&DynamicImage::ImageRgba8(image.clone()))?;
+
+    let pixels_per_meter = (dpi as f64 / METERS_PER_INCH).round() as u32;
+    let mut chunk = Vec::with_capacity(21);
+    chunk.extend_from_slice(&9u32.to_be_bytes());
+    chunk.extend_from_slice(b"pHYs");
+    chunk.extend_from_slice(&pixels_per_meter.to_be_bytes());
+    chunk.extend_from_slice(&pixels_per_meter.to_be_bytes());
+    // Unit: meter
+    chunk.push(1);
+    let crc = crc32fast::hash(&chunk[4..]);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+
+    // Ancillary chunks like pHYs go after IHDR (8-byte signature + 25-byte chunk)
+    png.splice(IHDR_END..IHDR_END, chunk);
+    Ok(png)
+}
+
+/// End of the IHDR chunk, which every PNG starts with
+const IHDR_END: usize = 33;
+
+/// DPI recorded in a PNG's pHYs chunk, if it has one in meters
+pub fn png_dpi(png: &[u8]) -> Option<u32> {
+    let mut offset = 8;
+    while offset + 8 <= png.len() {
+        let length = u32::from_be_bytes(png[offset..offset + 4].try_into().ok()?) as usize;
+        let kind = &png[offset + 4..offset + 8];
+        let data = png.get(offset + 8..offset + 8 + length)?;
+        if kind == b"pHYs" && length == 9 && data[8] == 1 {
+            let pixels_per_meter = u32::from_be_bytes(data[0..4].try_into().ok()?);
+            return Some((pixels_per_meter as f64 * METERS_PER_INCH).round() as u32);
+        }
+        if kind == b"IDAT" {
+            return None;
+        }
+        // Length, type, data, and CRC
+        offset += 12 + length;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::PlacementType;
+    use image::Rgba;
+
+    fn red_design() -> DynamicImage {
+        RgbaImage::from_pixel(400, 400, Rgba([255, 0, 0, 255])).into()
+    }
+
+    #[test]
+    fn test_print_file_fills_only_the_placement() {
+        let design = red_design();
+        let mut placement = PlacementSpec::new(0.25, -100, 0, PlacementType::Front);
+        placement.print_area_width = 800;
+        placement.print_area_height = 600;
+
+        let canvas = render_print_file(&[(&design, &placement)]);
+        assert_eq!(canvas.dimensions(), (800, 600));
+
+        // 200x150 design centered 100 px left of the print area's center
+        assert_eq!(placement.get_absolute_position(), (200, 225));
+        assert_eq!(canvas.get_pixel(300, 300).0, [255, 0, 0, 255]);
+        for (x, y) in [(0, 0), (199, 300), (400, 300), (300, 224), (799, 599)] {
+            assert_eq!(canvas.get_pixel(x, y).0[3], 0, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn test_print_png_records_dpi() {
+        let canvas = RgbaImage::new(60, 40);
+        let png = encode_print_png(&canvas, 300).unwrap();
+        assert_eq!(png_dpi(&png), Some(300));
+
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(decoded.dimensions(), (60, 40));
+        assert!(decoded.to_rgba8().pixels().all(|p| p.0[3] == 0));
+
+        let plain = Compositor::encode_png(&DynamicImage::ImageRgba8(canvas)).unwrap();
+        assert_eq!(png_dpi(&plain), None);
+    }
+
+    #[test]
+    fn test_design_dpi_at_printed_size() {
+        // 900 px wide in a 1800 px print area printed at 300 DPI is 3 inches
+        let placement = PlacementSpec::new(0.5, 0, 0, PlacementType::Front);
+        assert_eq!(design_dpi(900, &placement, 300), 300.0);
+        assert_eq!(design_dpi(450, &placement, 300), 150.0);
+        assert_eq!(design_dpi(300, &placement, 300), 100.0);
+    }
+}
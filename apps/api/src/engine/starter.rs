@@ -0,0 +1,329 @@
+//! Synthetic starter templates
+//!
+//! Generates a small set of flat-color garment templates (base image,
+//! displacement map, and metadata) so a fresh install can render mockups
+//! without any proprietary assets. The shapes are simple silhouettes with
+//! soft shading and a few horizontal folds.
+
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use std::f64::consts::PI;
+use std::path::Path;
+use tracing::info;
+
+use super::template::TemplateError;
+
+/// Edge length of every starter template, in pixels
+const STARTER_SIZE: u32 = 1200;
+
+/// Garment silhouette drawn for a starter template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StarterGarment {
+    Tshirt,
+    Hoodie,
+    ToteBag,
+}
+
+impl StarterGarment {
+    fn product_type(&self) -> &'static str {
+        match self {
+            StarterGarment::Tshirt => "tshirt",
+            StarterGarment::Hoodie => "hoodie",
+            StarterGarment::ToteBag => "tote-bag",
+        }
+    }
+
+    /// Print area as fractions of the canvas: (left, top, width, height)
+    fn print_area(&self) -> (f64, f64, f64, f64) {
+        match self {
+            StarterGarment::Tshirt => (0.35, 0.30, 0.30, 0.38),
+            StarterGarment::Hoodie => (0.36, 0.34, 0.28, 0.24),
+            StarterGarment::ToteBag => (0.33, 0.42, 0.34, 0.38),
+        }
+    }
+
+    /// Whether the garment covers the point at canvas fractions (u, v)
+    fn covers(&self, u: f64, v: f64) -> bool {
+        let in_rect = |l: f64, t: f64, r: f64, b: f64| (l..=r).contains(&u) && (t..=b).contains(&v);
+        let in_ellipse = |cx: f64, cy: f64, rx: f64, ry: f64| {
+            ((u - cx) / rx).powi(2) + ((v - cy) / ry).powi(2) <= 1.0
+        };
+        // Sleeves widen towards the shoulder, running from `top` down to `bottom`
+        let in_sleeve = |top: f64, bottom: f64, reach: f64| {
+            if !(top..=bottom).contains(&v) {
+                return false;
+            }
+            let t = (v - top) / (bottom - top);
+            let outer = 0.28 - reach * (1.0 - 0.3 * t);
+            (outer..=0.28).contains(&u) || (0.72..=1.0 - outer).contains(&u)
+        };
+
+        match self {
+            StarterGarment::Tshirt => {
+                (in_rect(0.28, 0.20, 0.72, 0.92) || in_sleeve(0.20, 0.42, 0.14))
+                    && !in_ellipse(0.5, 0.19, 0.08, 0.05)
+            }
+            StarterGarment::Hoodie => {
+                in_rect(0.28, 0.22, 0.72, 0.92)
+                    || in_sleeve(0.22, 0.86, 0.12)
+                    || in_ellipse(0.5, 0.20, 0.14, 0.10)
+            }
+            StarterGarment::ToteBag => {
+                let handle = |cx: f64| {
+                    let d = ((u - cx) / 0.09).powi(2) + ((v - 0.30) / 0.16).powi(2);
+                    v <= 0.30 && (0.72..=1.0).contains(&d)
+                };
+                in_rect(0.25, 0.30, 0.75, 0.92) || handle(0.38) || handle(0.62)
+            }
+        }
+    }
+}
+
+/// A synthetic template written by `bootstrap-templates`
+#[derive(Debug, Clone, Copy)]
+pub struct StarterTemplate {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub color: &'static str,
+    pub color_hex: &'static str,
+    pub garment: StarterGarment,
+}
+
+/// Templates written by `bootstrap-templates`
+pub const STARTER_TEMPLATES: [StarterTemplate; 6] = [
+    StarterTemplate {
+        id: "starter-tshirt-white-front",
+        name: "Starter T-Shirt White (Front)",
+        color: "white",
+        color_hex: "F4F4F2",
+        garment: StarterGarment::Tshirt,
+    },
+    StarterTemplate {
+        id: "starter-tshirt-black-front",
+        name: "Starter T-Shirt Black (Front)",
+        color: "black",
+        color_hex: "1E1E1E",
+        garment: StarterGarment::Tshirt,
+    },
+    StarterTemplate {
+        id: "starter-tshirt-heather-front",
+        name: "Starter T-Shirt Heather Gray (Front)",
+        color: "heather-gray",
+        color_hex: "A7A9AC",
+        garment: StarterGarment::Tshirt,
+    },
+    StarterTemplate {
+        id: "starter-hoodie-white-front",
+        name: "Starter Hoodie White (Front)",
+        color: "white",
+        color_hex: "F4F4F2",
+        garment: StarterGarment::Hoodie,
+    },
+    StarterTemplate {
+        id: "starter-hoodie-navy-front",
+        name: "Starter Hoodie Navy (Front)",
+        color: "navy",
+        color_hex: "1F2A44",
+        garment: StarterGarment::Hoodie,
+    },
+    StarterTemplate {
+        id: "starter-tote-natural-front",
+        name: "Starter Tote Bag Natural (Front)",
+        color: "natural",
+        color_hex: "EFE6D2",
+        garment: StarterGarment::ToteBag,
+    },
+];
+
+/// Result of writing the starter templates
+#[derive(Debug, Default)]
+pub struct BootstrapSummary {
+    pub created: Vec<String>,
+    /// Templates whose directory already existed
+    pub skipped: Vec<String>,
+}
+
+/// Write every starter template under `base_path`
+///
+/// Existing template directories are left alone unless `overwrite` is set.
+pub fn write_starter_templates(
+    base_path: &Path,
+    overwrite: bool,
+) -> Result<BootstrapSummary, TemplateError> {
+    let mut summary = BootstrapSummary::default();
+
+    for template in &STARTER_TEMPLATES {
+        let dir = base_path.join(template.id);
+        if dir.exists() && !overwrite {
+            summary.skipped.push(template.id.to_string());
+            continue;
+        }
+
+        write_starter_template(&dir, template, STARTER_SIZE)?;
+        info!(id = template.id, path = %dir.display(), "Wrote starter template");
+        summary.created.push(template.id.to_string());
+    }
+
+    Ok(summary)
+}
+
+/// Render one template's base image, displacement map, and metadata into `dir`
+fn write_starter_template(
+    dir: &Path,
+    template: &StarterTemplate,
+    size: u32,
+) -> Result<(), TemplateError> {
+    std::fs::create_dir_all(dir)?;
+
+    let color = parse_hex(template.color_hex).ok_or_else(|| {
+        TemplateError::MetadataLoad(format!("Invalid starter color: {}", template.color_hex))
+    })?;
+    render_base(template.garment, color, size).save(dir.join("base.png"))?;
+    render_displacement(template.garment, size).save(dir.join("displacement.png"))?;
+
+    let metadata = starter_metadata(template, size);
+    std::fs::write(
+        dir.join("metadata.json"),
+        serde_json::to_vec_pretty(&metadata)?,
+    )?;
+
+    Ok(())
+}
+
+/// Shared fold pattern in -1.0..=1.0, driving both shading and displacement
+fn fold(u: f64, v: f64) -> f64 {
+    (v * PI * 7.0 + (u * PI * 2.0).sin() * 0.8).sin()
+}
+
+fn to_unit(x: u32, y: u32, size: u32) -> (f64, f64) {
+    let size = size as f64;
+    ((x as f64 + 0.5) / size, (y as f64 + 0.5) / size)
+}
+
+fn render_base(garment: StarterGarment, color: [u8; 3], size: u32) -> RgbaImage {
+    RgbaImage::from_fn(size, size, |x, y| {
+        let (u, v) = to_unit(x, y, size);
+        if !garment.covers(u, v) {
+            return Rgba([0, 0, 0, 0]);
+        }
+
+        // Darker towards the sides, with faint folds
+        let side = ((u - 0.5) / 0.35).powi(2);
+        let shade = (1.0 - 0.18 * side + 0.04 * fold(u, v)).clamp(0.0, 1.1);
+        let channel = |c: u8| (c as f64 * shade).round().clamp(0.0, 255.0) as u8;
+        Rgba([channel(color[0]), channel(color[1]), channel(color[2]), 255])
+    })
+}
+
+fn render_displacement(garment: StarterGarment, size: u32) -> GrayImage {
+    GrayImage::from_fn(size, size, |x, y| {
+        let (u, v) = to_unit(x, y, size);
+        if !garment.covers(u, v) {
+            return Luma([128]);
+        }
+        Luma([(128.0 + 48.0 * fold(u, v)).round() as u8])
+    })
+}
+
+fn starter_metadata(template: &StarterTemplate, size: u32) -> serde_json::Value {
+    let (left, top, width, height) = template.garment.print_area();
+    let px = |fraction: f64| (fraction * size as f64).round() as i32;
+    let print_area = (px(left), px(top), px(width), px(height));
+
+    // Designs would vanish when multiplied onto dark fabric
+    let dark = parse_hex(template.color_hex)
+        .map_or(false, |[r, g, b]| (r as u32 + g as u32 + b as u32) / 3 < 96);
+    let (blend_mode, opacity) = if dark {
+        ("normal", 235)
+    } else {
+        ("multiply", 240)
+    };
+
+    serde_json::json!({
+        "id": template.id,
+        "name": template.name,
+        "version": 1,
+        "category": template.garment.product_type(),
+        "color": template.color,
+        "color_hex": template.color_hex,
+        "placement": "front",
+        "product": template.name.trim_end_matches(" (Front)"),
+        "product_type": template.garment.product_type(),
+        "dimensions": { "width": size, "height": size },
+        "print_area": {
+            "x": print_area.0,
+            "y": print_area.1,
+            "width": print_area.2,
+            "height": print_area.3,
+        },
+        "anchor_point": {
+            "x": print_area.0 + print_area.2 / 2,
+            "y": print_area.1 + print_area.3 / 2,
+        },
+        "displacement": {
+            "enabled": true,
+            "strength_default": 8.0,
+            "strength_range": [4.0, 16.0],
+        },
+        "blend_mode": blend_mode,
+        "default_opacity": opacity,
+    })
+}
+
+fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::template::{Template, TemplateImages};
+
+    #[test]
+    fn test_starter_template_loads() {
+        let dir = std::env::temp_dir().join(format!("starter-{}", uuid::Uuid::new_v4()));
+        let starter = &STARTER_TEMPLATES[0];
+
+        write_starter_template(&dir, starter, 200).unwrap();
+        let template = Template::index(&dir).unwrap();
+        let images = TemplateImages::load(&dir, &template.metadata).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(template.metadata.id, starter.id);
+        assert_eq!(template.metadata.dimensions.width, 200);
+        assert!(images.displacement_map.is_some());
+        assert_eq!(images.base_image.width(), 200);
+    }
+
+    #[test]
+    fn test_print_areas_sit_on_the_garment() {
+        for template in &STARTER_TEMPLATES {
+            let (left, top, width, height) = template.garment.print_area();
+            for (u, v) in [
+                (left, top),
+                (left + width, top),
+                (left, top + height),
+                (left + width, top + height),
+            ] {
+                assert!(
+                    template.garment.covers(u, v),
+                    "{} at {},{}",
+                    template.id,
+                    u,
+                    v
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_dark_garments_use_normal_blend() {
+        let metadata = starter_metadata(&STARTER_TEMPLATES[1], 100);
+        assert_eq!(metadata["blend_mode"], "normal");
+        let metadata = starter_metadata(&STARTER_TEMPLATES[0], 100);
+        assert_eq!(metadata["blend_mode"], "multiply");
+    }
+}
@@ -1,15 +1,30 @@
 //! Template management and loading
 
-use image::{DynamicImage, ImageError};
-use parking_lot::RwLock;
-use serde::Deserialize;
-use std::collections::HashMap;
+use bytes::Bytes;
+use image::{DynamicImage, GenericImageView, GrayImage, ImageError, Luma, Rgba, RgbaImage};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
 
-use super::compositor::{Compositor, MockupRequest, MockupResult};
+use super::compositor::{
+    Compositor, CompositorError, DesignLayer, GenerationLimits, JpegPreset, MockupRequest,
+    MockupResult, OutputFormat, OutputSettings, BLEND_MODES,
+};
+use super::displacement::DisplacementStats;
+use super::limiter::{GenerationLimiter, GenerationLoad};
+use super::print_file::PrintFile;
+use super::template_source::{TemplatePull, TemplateSource};
+use super::template_upload::{InstalledTemplate, TemplateUpload};
+use crate::domain::{default_presets, PlacementPreset, PlacementSpec};
+use crate::metrics::Metrics;
+use crate::net::UrlPolicy;
 
 /// Template-related errors
 #[derive(Debug, Error)]
@@ -24,8 +39,71 @@ pub enum TemplateError {
     Io(#[from] std::io::Error),
     #[error("JSON parse error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Invalid geometry: {0}")]
+    InvalidGeometry(String),
+    #[error("Template files missing: {0}")]
+    FilesMissing(String),
+    #[error("Invalid metadata: {}", .0.join("; "))]
+    InvalidMetadata(Vec<String>),
+    #[error("Invalid template upload: {}", .0.join("; "))]
+    InvalidUpload(Vec<String>),
+    #[error("Template already exists: {0}")]
+    AlreadyExists(String),
+    #[error("Template source error: {0}")]
+    Source(String),
+    #[error("Print mask {file} is {mask_width}x{mask_height}, but the base image is {base_width}x{base_height}")]
+    MaskDimensions {
+        file: String,
+        mask_width: u32,
+        mask_height: u32,
+        base_width: u32,
+        base_height: u32,
+    },
+    #[error("Strength map {file} is {map_width}x{map_height}, but the displacement map is {displacement_width}x{displacement_height}")]
+    StrengthMapDimensions {
+        file: String,
+        map_width: u32,
+        map_height: u32,
+        displacement_width: u32,
+        displacement_height: u32,
+    },
+    #[error("All {max_concurrent} generation slots stayed busy; retry in {retry_after_secs}s")]
+    Saturated {
+        max_concurrent: usize,
+        retry_after_secs: u64,
+    },
+    #[error("Server is shutting down and not accepting new generations")]
+    ShuttingDown,
+    #[error("Generation timed out: {0}")]
+    TimedOut(String),
+    #[error("Generation cancelled")]
+    Cancelled,
+    /// A fetched design failed its size or format checks; `code` names which
+    #[error("{message}")]
+    InvalidDesign { code: &'static str, message: String },
 }
 
+/// Keep deadline and cancellation outcomes distinguishable from compositing failures
+fn compositor_error(e: CompositorError) -> TemplateError {
+    match e {
+        CompositorError::TimedOut(stage) => TemplateError::TimedOut(stage),
+        CompositorError::Cancelled => TemplateError::Cancelled,
+        e => match e.design_code() {
+            Some(code) => TemplateError::InvalidDesign {
+                code,
+                message: e.to_string(),
+            },
+            None => TemplateError::MetadataLoad(format!("Compositor error: {}", e)),
+        },
+    }
+}
+
+/// Print mask picked up when metadata names none: grayscale, white = printable
+pub(super) const DEFAULT_PRINT_MASK: &str = "mask.png";
+
+/// Per-pixel displacement strength picked up in `per_pixel` mode: grayscale, white = full strength
+const STRENGTH_MAP: &str = "strength.png";
+
 /// Template metadata loaded from metadata.json
 #[derive(Debug, Clone, Deserialize)]
 pub struct TemplateMetadata {
@@ -44,6 +122,16 @@ pub struct TemplateMetadata {
     pub displacement: DisplacementConfig,
     pub blend_mode: String,
     pub default_opacity: u8,
+    /// Resolution the print area is printed at; `DEFAULT_PRINT_DPI` when unset
+    #[serde(default)]
+    pub print_dpi: Option<u32>,
+    /// Lowest design resolution, at its printed size, a print file accepts;
+    /// `DEFAULT_MIN_DPI` when unset
+    #[serde(default)]
+    pub min_dpi: Option<u32>,
+    /// Placement presets overriding or adding to the built-in ones
+    #[serde(default)]
+    pub presets: BTreeMap<String, PlacementPreset>,
     // Printful sync fields
     #[serde(default)]
     pub name: Option<String>,
@@ -68,13 +156,64 @@ pub struct TemplateMetadata {
     pub zones: Option<HashMap<String, serde_json::Value>>,
 }
 
+impl TemplateMetadata {
+    /// Metadata for a provider mockup template that has no local directory
+    ///
+    /// Provider mockups ship without displacement maps or masks, so designs are
+    /// multiplied onto the photo inside the provider's print area.
+    pub fn from_provider_mockup(
+        id: &str,
+        placement: &str,
+        dimensions: TemplateDimensions,
+        print_area: PrintArea,
+    ) -> Self {
+        let anchor_point = AnchorPoint {
+            x: print_area.x + print_area.width / 2,
+            y: print_area.y + print_area.height / 2,
+        };
+
+        TemplateMetadata {
+            id: id.to_string(),
+            version: 1,
+            category: "provider".to_string(),
+            color: "original".to_string(),
+            color_hex: None,
+            placement: placement.to_string(),
+            gender: None,
+            dimensions,
+            print_area,
+            anchor_point,
+            displacement: DisplacementConfig {
+                enabled: false,
+                strength_default: 0.0,
+                strength_range: (0.0, 30.0),
+                mode: DisplacementMode::Uniform,
+            },
+            blend_mode: "multiply".to_string(),
+            default_opacity: 240,
+            print_dpi: None,
+            min_dpi: None,
+            presets: BTreeMap::new(),
+            name: None,
+            product: None,
+            product_type: None,
+            printful_product_id: None,
+            printful_template_id: None,
+            print_mask: None,
+            preserve_masks: Vec::new(),
+            collar_zone: None,
+            zones: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct TemplateDimensions {
     pub width: u32,
     pub height: u32,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PrintArea {
     pub x: i32,
     pub y: i32,
@@ -82,17 +221,287 @@ pub struct PrintArea {
     pub height: i32,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AnchorPoint {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DisplacementConfig {
     pub enabled: bool,
     pub strength_default: f64,
+    /// `[min, max]`
+    #[schema(value_type = [f64; 2])]
     pub strength_range: (f64, f64),
+    #[serde(default)]
+    pub mode: DisplacementMode,
+}
+
+/// How strongly each pixel of a displacement map displaces
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplacementMode {
+    /// Every pixel displaces at the requested strength
+    #[default]
+    Uniform,
+    /// The requested strength is scaled per pixel by `strength.png`, or else by
+    /// the map's green channel, with the offsets read from its red channel;
+    /// black holds pixels in place and white gives the full strength
+    PerPixel,
+}
+
+impl DisplacementConfig {
+    /// Strength to render with, and a warning when the request was clamped
+    ///
+    /// An omitted strength uses `strength_default`. A requested strength outside
+    /// `strength_range` is an error when `strict` and is clamped otherwise.
+    /// Templates with displacement disabled never displace, so any value passes.
+    pub fn resolve_strength(
+        &self,
+        requested: Option<f64>,
+        strict: bool,
+    ) -> Result<(f64, Option<String>), String> {
+        let Some(strength) = requested else {
+            return Ok((self.strength_default, None));
+        };
+        let (min, max) = self.strength_range;
+        if !self.enabled || (min..=max).contains(&strength) {
+            return Ok((strength, None));
+        }
+        if strict {
+            return Err(format!(
+                "displacement_strength {} is outside the template's range [{}, {}]",
+                strength, min, max
+            ));
+        }
+        let clamped = strength.clamp(min, max);
+        let warning = format!(
+            "displacement_strength {} clamped to {} (template range [{}, {}])",
+            strength, clamped, min, max
+        );
+        Ok((clamped, Some(warning)))
+    }
+
+    /// Strength for a 0-1 realism level, tuned to the template's map
+    ///
+    /// Maps with measured folds get the strength that shifts their strongest
+    /// folds in proportion to `realism`; without stats, or for a flat map,
+    /// realism is spread linearly over `strength_range`. Either way the result
+    /// lies within the range.
+    pub fn strength_for_realism(&self, realism: f64, stats: Option<&DisplacementStats>) -> f64 {
+        let (min, max) = self.strength_range;
+        stats
+            .and_then(|stats| stats.strength_for(realism))
+            .map_or(min + realism * (max - min), |strength| {
+                strength.clamp(min, max)
+            })
+    }
+}
+
+/// The editable placement geometry of a template
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TemplateGeometry {
+    pub print_area: PrintArea,
+    pub anchor_point: AnchorPoint,
+    pub displacement: DisplacementConfig,
+}
+
+impl TemplateGeometry {
+    /// Check the geometry fits a template of the given dimensions
+    pub fn validate(&self, dimensions: &TemplateDimensions) -> Result<(), String> {
+        match self.issues(dimensions).into_iter().next() {
+            Some(issue) => Err(issue),
+            None => Ok(()),
+        }
+    }
+
+    /// Every way the geometry fails to fit a template of the given dimensions
+    fn issues(&self, dimensions: &TemplateDimensions) -> Vec<String> {
+        let mut issues = Vec::new();
+        let (width, height) = (dimensions.width as i32, dimensions.height as i32);
+        let area = &self.print_area;
+        let area_fits = if area.width <= 0 || area.height <= 0 {
+            issues.push(format!(
+                "print_area width and height must be positive, got {}x{}",
+                area.width, area.height
+            ));
+            false
+        } else if area.x < 0
+            || area.y < 0
+            || area.x + area.width > width
+            || area.y + area.height > height
+        {
+            issues.push(format!(
+                "print_area must lie within the {}x{} template, but spans x {}..{}, y {}..{}",
+                width,
+                height,
+                area.x,
+                area.x + area.width,
+                area.y,
+                area.y + area.height
+            ));
+            false
+        } else {
+            true
+        };
+
+        let anchor = &self.anchor_point;
+        let anchor_inside = anchor.x >= area.x
+            && anchor.y >= area.y
+            && anchor.x < area.x + area.width
+            && anchor.y < area.y + area.height;
+        if area_fits && !anchor_inside {
+            issues.push(format!(
+                "anchor_point ({}, {}) must lie within the print area (x {}..{}, y {}..{})",
+                anchor.x,
+                anchor.y,
+                area.x,
+                area.x + area.width,
+                area.y,
+                area.y + area.height
+            ));
+        }
+
+        let displacement = &self.displacement;
+        let (min, max) = displacement.strength_range;
+        if min.is_nan() || max.is_nan() || min < 0.0 || min > max {
+            issues.push(format!(
+                "displacement.strength_range must be non-negative and ascending, got [{}, {}]",
+                min, max
+            ));
+        } else if !(min..=max).contains(&displacement.strength_default) {
+            issues.push(format!(
+                "displacement.strength_default {} must lie within strength_range [{}, {}]",
+                displacement.strength_default, min, max
+            ));
+        }
+        issues
+    }
+}
+
+impl TemplateMetadata {
+    /// Check the metadata is consistent, and with the base image's actual size
+    ///
+    /// Returns warnings for problems a template can still be served with, or
+    /// every error that keeps it from loading.
+    pub fn validate(&self, base_size: (u32, u32)) -> Result<Vec<String>, Vec<String>> {
+        let mut errors = self.geometry().issues(&self.dimensions);
+        if !BLEND_MODES.contains(&self.blend_mode.as_str()) {
+            errors.push(format!(
+                "blend_mode '{}' is not one of {}",
+                self.blend_mode,
+                BLEND_MODES.join(", ")
+            ));
+        }
+        for (name, preset) in &self.presets {
+            if !(0.1..=1.0).contains(&preset.scale) {
+                errors.push(format!(
+                    "preset '{}' scale {} is outside 0.1-1.0",
+                    name, preset.scale
+                ));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut warnings = Vec::new();
+        let dimensions = (self.dimensions.width, self.dimensions.height);
+        if base_size != dimensions {
+            // Geometry is in metadata pixels, so designs land off target
+            warnings.push(format!(
+                "base image is {}x{}, but dimensions are {}x{}",
+                base_size.0, base_size.1, dimensions.0, dimensions.1
+            ));
+        }
+        Ok(warnings)
+    }
+
+    /// Built-in placement presets overlaid with the template's own
+    pub fn placement_presets(&self) -> BTreeMap<String, PlacementPreset> {
+        let mut presets = default_presets();
+        presets.extend(self.presets.clone());
+        presets
+    }
+
+    /// Print space placement for a named preset, `None` for an unknown name
+    pub fn preset_placement(&self, name: &str) -> Option<PlacementSpec> {
+        let preset = match self.presets.get(name) {
+            Some(preset) => preset.clone(),
+            None => default_presets().remove(name)?,
+        };
+        Some(preset.to_placement(
+            self.print_area.width,
+            self.print_area.height,
+            self.anchor_offset(),
+        ))
+    }
+
+    /// Offset of the anchor point from the print area's center
+    pub fn anchor_offset(&self) -> (i32, i32) {
+        let area = &self.print_area;
+        (
+            self.anchor_point.x - (area.x + area.width / 2),
+            self.anchor_point.y - (area.y + area.height / 2),
+        )
+    }
+
+    /// Current print area, anchor point, and displacement settings
+    pub fn geometry(&self) -> TemplateGeometry {
+        TemplateGeometry {
+            print_area: self.print_area.clone(),
+            anchor_point: self.anchor_point.clone(),
+            displacement: self.displacement.clone(),
+        }
+    }
+
+    /// Copy of this metadata with `geometry` applied
+    pub fn with_geometry(&self, geometry: TemplateGeometry) -> Self {
+        TemplateMetadata {
+            print_area: geometry.print_area,
+            anchor_point: geometry.anchor_point,
+            displacement: geometry.displacement,
+            ..self.clone()
+        }
+    }
+}
+
+/// Calibration grid rendered into the print area for geometry previews
+///
+/// Alternating cells show scale and skew, a border marks the print area
+/// edges, and a crosshair marks its center. Sized to the print area's aspect
+/// ratio with the longer side at 600 px; the compositor scales it to fit.
+pub fn geometry_test_pattern(print_area: &PrintArea) -> RgbaImage {
+    const LONG_SIDE: f64 = 600.0;
+    const CELLS: u32 = 8;
+    let longest = print_area.width.max(print_area.height).max(1) as f64;
+    let width = ((print_area.width.max(1) as f64 / longest) * LONG_SIDE)
+        .round()
+        .max(1.0) as u32;
+    let height = ((print_area.height.max(1) as f64 / longest) * LONG_SIDE)
+        .round()
+        .max(1.0) as u32;
+    let cell = (width.max(height) / CELLS).max(1);
+    let border = (cell / 8).max(2);
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let on_border = x < border
+            || y < border
+            || x >= width.saturating_sub(border)
+            || y >= height.saturating_sub(border);
+        let on_crosshair =
+            x.abs_diff(width / 2) < border / 2 + 1 || y.abs_diff(height / 2) < border / 2 + 1;
+        if on_border {
+            Rgba([0, 170, 255, 255])
+        } else if on_crosshair {
+            Rgba([255, 60, 0, 255])
+        } else if (x / cell + y / cell) % 2 == 0 {
+            Rgba([230, 0, 140, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    })
 }
 
 /// Collar zone exclusion rectangle — preserves original blank pixels in this region
@@ -104,34 +513,37 @@ pub struct CollarZone {
     pub height: u32,
 }
 
-/// A loaded template with all assets in memory
-pub struct Template {
-    pub metadata: TemplateMetadata,
+/// Decoded template images, dropped when the template sits idle
+pub struct TemplateImages {
     pub base_image: DynamicImage,
     pub displacement_map: Option<DynamicImage>,
+    /// Per-pixel displacement strength, 0 to 1 over black to white, aligned
+    /// with the displacement map; only loaded in `per_pixel` mode
+    pub strength_map: Option<DynamicImage>,
     pub print_mask: Option<DynamicImage>,
     pub preserve_masks: Vec<DynamicImage>,
 }
 
-impl Template {
-    /// Load a template from a directory
-    pub fn load(path: &Path) -> Result<Self, TemplateError> {
-        // Load metadata
-        let metadata_path = path.join("metadata.json");
-        let metadata_content = std::fs::read_to_string(&metadata_path).map_err(|e| {
-            TemplateError::MetadataLoad(format!("{}: {}", metadata_path.display(), e))
-        })?;
-        let metadata: TemplateMetadata = serde_json::from_str(&metadata_content)?;
+impl TemplateImages {
+    /// Images for a template made of a single base photo
+    pub fn from_base(base_image: DynamicImage) -> Self {
+        TemplateImages {
+            base_image,
+            displacement_map: None,
+            strength_map: None,
+            print_mask: None,
+            preserve_masks: Vec::new(),
+        }
+    }
 
-        // Load base image
-        let base_path = path.join("base.png");
-        let base_image = if base_path.exists() {
-            image::open(&base_path)?
-        } else {
-            // Try jpg as fallback
-            let jpg_path = path.join("base.jpg");
-            image::open(&jpg_path)?
-        };
+    /// Decode the base image of a template directory, base.png or else base.jpg
+    fn load_base(path: &Path) -> Result<DynamicImage, TemplateError> {
+        Ok(image::open(base_image_path(path)?)?)
+    }
+
+    /// Decode all images for a template directory
+    pub fn load(path: &Path, metadata: &TemplateMetadata) -> Result<Self, TemplateError> {
+        let base_image = Self::load_base(path)?;
 
         // Load displacement map (optional)
         let displacement_map = {
@@ -148,9 +560,16 @@ impl Template {
                 }
             }
         };
+        let (displacement_map, strength_map) = match displacement_map {
+            Some(map) if metadata.displacement.mode == DisplacementMode::PerPixel => {
+                let (map, strength) = Self::split_strength(path, metadata, map)?;
+                (Some(map), strength)
+            }
+            map => (map, None),
+        };
 
-        // Load optional print mask (full-canvas mask image)
-        let print_mask = if let Some(mask_file) = metadata.print_mask.as_ref() {
+        // Load optional print mask (full-canvas mask image), falling back to mask.png
+        let print_mask_file = if let Some(mask_file) = metadata.print_mask.as_ref() {
             let mask_path = path.join(mask_file);
             if !mask_path.exists() {
                 return Err(TemplateError::MetadataLoad(format!(
@@ -158,9 +577,27 @@ impl Template {
                     mask_path.display()
                 )));
             }
-            Some(image::open(&mask_path)?)
+            Some(mask_file.as_str())
         } else {
-            None
+            path.join(DEFAULT_PRINT_MASK)
+                .exists()
+                .then_some(DEFAULT_PRINT_MASK)
+        };
+        let print_mask = match print_mask_file {
+            Some(mask_file) => {
+                let mask = image::open(path.join(mask_file))?;
+                if mask.dimensions() != base_image.dimensions() {
+                    return Err(TemplateError::MaskDimensions {
+                        file: mask_file.to_string(),
+                        mask_width: mask.width(),
+                        mask_height: mask.height(),
+                        base_width: base_image.width(),
+                        base_height: base_image.height(),
+                    });
+                }
+                Some(mask)
+            }
+            None => None,
         };
 
         // Load optional preserve masks (full-canvas mask image list)
@@ -176,30 +613,334 @@ impl Template {
             preserve_masks.push(image::open(&mask_path)?);
         }
 
-        info!(
-            id = %metadata.id,
-            dimensions = ?metadata.dimensions,
-            has_displacement = displacement_map.is_some(),
-            has_print_mask = print_mask.is_some(),
-            preserve_mask_count = preserve_masks.len(),
-            "Loaded template"
-        );
-
-        Ok(Template {
-            metadata,
+        Ok(TemplateImages {
             base_image,
             displacement_map,
+            strength_map,
             print_mask,
             preserve_masks,
         })
     }
+
+    /// Separate a `per_pixel` displacement map from its strength map
+    ///
+    /// `strength.png` must match the map's dimensions. Without one, a color
+    /// map carries offsets in its red channel and strength in its green; a
+    /// grayscale map has no strength to read and displaces uniformly.
+    fn split_strength(
+        path: &Path,
+        metadata: &TemplateMetadata,
+        map: DynamicImage,
+    ) -> Result<(DynamicImage, Option<DynamicImage>), TemplateError> {
+        let strength_path = path.join(STRENGTH_MAP);
+        if strength_path.exists() {
+            let strength = image::open(&strength_path)?;
+            if strength.dimensions() != map.dimensions() {
+                return Err(TemplateError::StrengthMapDimensions {
+                    file: STRENGTH_MAP.to_string(),
+                    map_width: strength.width(),
+                    map_height: strength.height(),
+                    displacement_width: map.width(),
+                    displacement_height: map.height(),
+                });
+            }
+            return Ok((map, Some(DynamicImage::ImageLuma8(strength.to_luma8()))));
+        }
+
+        if !map.color().has_color() {
+            warn!(
+                id = %metadata.id,
+                "Per-pixel displacement map is grayscale and there is no strength.png; displacing uniformly"
+            );
+            return Ok((map, None));
+        }
+        let rgb = map.to_rgb8();
+        let channel = |index: usize| {
+            DynamicImage::ImageLuma8(GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+                Luma([rgb.get_pixel(x, y).0[index]])
+            }))
+        };
+        Ok((channel(0), Some(channel(1))))
+    }
+
+    /// Intensity of the displacement map over the print area
+    pub fn displacement_stats(&self, metadata: &TemplateMetadata) -> Option<DisplacementStats> {
+        let map = self.displacement_map.as_ref()?;
+        let area = &metadata.print_area;
+        DisplacementStats::measure(
+            map,
+            area.x.max(0) as u32,
+            area.y.max(0) as u32,
+            area.width.max(0) as u32,
+            area.height.max(0) as u32,
+        )
+    }
+
+    /// Bytes held by the decoded pixel buffers
+    pub fn resident_bytes(&self) -> u64 {
+        std::iter::once(&self.base_image)
+            .chain(self.displacement_map.iter())
+            .chain(self.strength_map.iter())
+            .chain(self.print_mask.iter())
+            .chain(self.preserve_masks.iter())
+            .map(|image| image.as_bytes().len() as u64)
+            .sum()
+    }
+}
+
+/// A template's base image, downscaled and encoded for pickers
+pub struct TemplatePreview {
+    pub bytes: Bytes,
+    pub format: OutputFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TemplatePreview {
+    /// Fit `base` within `max_dimension` on both sides and encode it
+    ///
+    /// Images already within the bound keep their size.
+    fn render(
+        base: &DynamicImage,
+        format: OutputFormat,
+        max_dimension: u32,
+    ) -> Result<Self, TemplateError> {
+        let thumbnail = if base.width() <= max_dimension && base.height() <= max_dimension {
+            base.clone()
+        } else {
+            base.thumbnail(max_dimension, max_dimension)
+        };
+        let output = OutputSettings::with_preset(format, JpegPreset::Web, None, None, None)
+            .unwrap_or_default();
+        let bytes = Compositor::encode(&thumbnail, &output)
+            .map_err(|e| TemplateError::MetadataLoad(format!("Preview encoding failed: {}", e)))?;
+        Ok(TemplatePreview {
+            bytes: bytes.into(),
+            format,
+            width: thumbnail.width(),
+            height: thumbnail.height(),
+        })
+    }
+}
+
+/// A template: metadata always in memory, images decoded on first use
+pub struct Template {
+    pub metadata: TemplateMetadata,
+    /// Problems found when indexing that still let the template be served
+    pub warnings: Vec<String>,
+    dir: PathBuf,
+    images: Mutex<Option<Arc<TemplateImages>>>,
+    /// Measured on first decode, so it survives image eviction
+    displacement_stats: OnceLock<Option<DisplacementStats>>,
+    /// Held while decoding so concurrent first uses decode once
+    decoding: tokio::sync::Mutex<()>,
+    /// Encoded previews by format and size; small, so kept through image eviction
+    previews: Mutex<HashMap<(OutputFormat, u32), Arc<TemplatePreview>>>,
+    /// Milliseconds since the manager's epoch at last use
+    last_access_ms: AtomicU64,
+}
+
+impl Template {
+    /// The same template directory and resident images with different metadata
+    ///
+    /// Displacement stats are remeasured for the new print area when the images
+    /// are resident, and on the next decode otherwise.
+    fn with_metadata(&self, metadata: TemplateMetadata) -> Self {
+        let images = self.resident_images();
+        let displacement_stats = match &images {
+            Some(images) => OnceLock::from(images.displacement_stats(&metadata)),
+            None => OnceLock::new(),
+        };
+        Template {
+            metadata,
+            warnings: self.warnings.clone(),
+            dir: self.dir.clone(),
+            images: Mutex::new(images),
+            displacement_stats,
+            decoding: tokio::sync::Mutex::new(()),
+            previews: Mutex::new(self.previews.lock().clone()),
+            last_access_ms: AtomicU64::new(self.last_access_ms.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Index a template directory: read and validate its metadata without decoding images
+    ///
+    /// Fails when metadata.json is unreadable or invalid, or the base image is
+    /// missing or has an unreadable header. Other image problems surface when
+    /// the images are first decoded.
+    pub fn index(path: &Path) -> Result<Self, TemplateError> {
+        let metadata_path = path.join("metadata.json");
+        let metadata_content = std::fs::read_to_string(&metadata_path).map_err(|e| {
+            TemplateError::MetadataLoad(format!("{}: {}", metadata_path.display(), e))
+        })?;
+        let metadata: TemplateMetadata = serde_json::from_str(&metadata_content)?;
+
+        // Reads only the image header
+        let base_size = image::image_dimensions(base_image_path(path)?)?;
+        let warnings = metadata
+            .validate(base_size)
+            .map_err(TemplateError::InvalidMetadata)?;
+        if !warnings.is_empty() {
+            warn!(id = %metadata.id, issues = ?warnings, "Template metadata has problems");
+        }
+
+        debug!(id = %metadata.id, dimensions = ?metadata.dimensions, "Indexed template");
+
+        Ok(Template {
+            metadata,
+            warnings,
+            dir: path.to_path_buf(),
+            images: Mutex::new(None),
+            displacement_stats: OnceLock::new(),
+            decoding: tokio::sync::Mutex::new(()),
+            previews: Mutex::new(HashMap::new()),
+            last_access_ms: AtomicU64::new(0),
+        })
+    }
+
+    /// Decoded images, if currently resident
+    pub fn resident_images(&self) -> Option<Arc<TemplateImages>> {
+        self.images.lock().clone()
+    }
+
+    /// Displacement map intensity over the print area, once images have been decoded
+    pub fn displacement_stats(&self) -> Option<DisplacementStats> {
+        self.displacement_stats.get().copied().flatten()
+    }
+
+    /// Keep freshly decoded images, measuring displacement stats the first time
+    fn store_images(&self, images: Arc<TemplateImages>) {
+        let stats = *self
+            .displacement_stats
+            .get_or_init(|| images.displacement_stats(&self.metadata));
+        debug!(
+            id = %self.metadata.id,
+            has_displacement = images.displacement_map.is_some(),
+            has_print_mask = images.print_mask.is_some(),
+            preserve_mask_count = images.preserve_masks.len(),
+            displacement_p95 = ?stats.map(|stats| stats.p95_deviation),
+            resident_bytes = images.resident_bytes(),
+            "Decoded template images"
+        );
+        *self.images.lock() = Some(images);
+    }
+
+    /// Bytes held by decoded images (0 when evicted)
+    pub fn resident_bytes(&self) -> u64 {
+        self.images
+            .lock()
+            .as_ref()
+            .map_or(0, |images| images.resident_bytes())
+    }
+}
+
+/// Eviction settings for decoded images, tunable at runtime
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct EvictionPolicy {
+    /// Drop decoded images after this long without use; `None` disables eviction
+    pub idle_timeout: Option<Duration>,
+    /// Most templates with decoded images; least recently used are dropped first
+    pub max_resident_templates: Option<usize>,
+    /// Most bytes of decoded images; least recently used are dropped first
+    pub max_resident_bytes: Option<u64>,
+}
+
+impl EvictionPolicy {
+    fn over_limits(&self, templates: usize, bytes: u64) -> bool {
+        self.max_resident_templates
+            .is_some_and(|max| templates > max)
+            || self.max_resident_bytes.is_some_and(|max| bytes > max)
+    }
 }
 
-/// Manages all templates in memory
+/// Snapshot of template memory usage
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateMemoryStats {
+    pub templates: usize,
+    pub resident_templates: usize,
+    pub resident_bytes: u64,
+    pub evictions_total: u64,
+    /// Image decodes on demand, including each template's first use
+    pub reloads_total: u64,
+}
+
+/// A template directory that failed to load or validate
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TemplateLoadFailure {
+    /// ID of the template previously loaded from the directory, or the directory name
+    pub template_id: String,
+    pub error: String,
+    /// Each metadata problem, when validation failed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<String>,
+}
+
+impl TemplateLoadFailure {
+    fn new(template_id: String, error: &TemplateError) -> Self {
+        let issues = match error {
+            TemplateError::InvalidMetadata(issues) => issues.clone(),
+            _ => Vec::new(),
+        };
+        TemplateLoadFailure {
+            template_id,
+            error: error.to_string(),
+            issues,
+        }
+    }
+}
+
+/// A loaded template with problems it can still be served with
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TemplateLoadWarning {
+    pub template_id: String,
+    pub issues: Vec<String>,
+}
+
+/// Broken templates as of the latest reload of each directory
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TemplateLoadReport {
+    pub template_count: usize,
+    /// Directories that failed to load; a template already loaded from one
+    /// keeps its last good version
+    pub failed: Vec<TemplateLoadFailure>,
+    pub warnings: Vec<TemplateLoadWarning>,
+}
+
+/// Template IDs changed by a reload
+///
+/// A template whose directory fails to load keeps its previous version.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct TemplateReloadSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    pub failed: Vec<TemplateLoadFailure>,
+    /// What was pulled from the shared template library, when there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pulled: Option<TemplatePull>,
+}
+
+/// Indexes every template's metadata and caches decoded images on demand
 pub struct TemplateManager {
     templates: RwLock<HashMap<String, Arc<Template>>>,
     base_path: PathBuf,
-    compositor: Compositor,
+    /// Replaced by `set_url_policy`
+    compositor: RwLock<Arc<Compositor>>,
+    /// Reference point for template access timestamps
+    epoch: Instant,
+    eviction: RwLock<EvictionPolicy>,
+    evictions_total: AtomicU64,
+    reloads_total: AtomicU64,
+    /// Serializes reloads so their summaries don't interleave
+    reload_lock: tokio::sync::Mutex<()>,
+    /// Failures from the latest reload of each directory
+    load_failures: RwLock<Vec<TemplateLoadFailure>>,
+    /// Bounds generations composited at once; replaced by `set_generation_limit`
+    generations: RwLock<Arc<GenerationLimiter>>,
+    /// Where generation timings and cache lookups are recorded, once set
+    metrics: RwLock<Option<Arc<Metrics>>>,
+    /// Shared library the templates directory caches, once set
+    source: RwLock<Option<Arc<dyn TemplateSource>>>,
 }
 
 impl TemplateManager {
@@ -208,35 +949,115 @@ impl TemplateManager {
         Ok(TemplateManager {
             templates: RwLock::new(HashMap::new()),
             base_path: base_path.to_path_buf(),
-            compositor: Compositor::new(),
+            compositor: RwLock::new(Arc::new(Compositor::new())),
+            epoch: Instant::now(),
+            eviction: RwLock::new(EvictionPolicy::default()),
+            evictions_total: AtomicU64::new(0),
+            reloads_total: AtomicU64::new(0),
+            reload_lock: tokio::sync::Mutex::new(()),
+            load_failures: RwLock::new(Vec::new()),
+            generations: RwLock::new(Arc::new(GenerationLimiter::unlimited())),
+            metrics: RwLock::new(None),
+            source: RwLock::new(None),
         })
     }
 
-    /// Load all templates from the base directory
-    pub async fn load_all(&self) -> Result<(), TemplateError> {
+    /// Record generation timings and image cache lookups in `metrics`
+    pub fn set_metrics(&self, metrics: Arc<Metrics>) {
+        *self.metrics.write() = Some(metrics);
+    }
+
+    /// Record a finished generation's stage timings
+    fn observe_generation(&self, template_id: &str, result: &MockupResult) {
+        if let Some(ref metrics) = *self.metrics.read() {
+            metrics.observe_generation(template_id, &result.timings.stages());
+        }
+    }
+
+    fn record_cache_lookup(&self, hit: bool) {
+        if let Some(ref metrics) = *self.metrics.read() {
+            metrics.record_template_cache(hit);
+        }
+    }
+
+    /// Pull templates from `source` before every reload and publish uploads to it
+    pub fn set_source(&self, source: Arc<dyn TemplateSource>) {
+        info!(source = source.name(), "Template source set");
+        *self.source.write() = Some(source);
+    }
+
+    /// Pull the shared library, or one template of it, into the templates directory
+    ///
+    /// A failed pull is reported, and the local copy is served meanwhile.
+    async fn pull_source(&self, dir_name: Option<&str>) -> Option<TemplatePull> {
+        let source = self.source.read().clone()?;
+        let pulled = match source.pull(dir_name).await {
+            Ok(pulled) => pulled,
+            Err(e) => {
+                warn!(source = source.name(), error = %e, "Template pull failed; serving the local copy");
+                TemplatePull {
+                    source: source.name().to_string(),
+                    failed: vec![e.to_string()],
+                    ..TemplatePull::default()
+                }
+            }
+        };
+        Some(pulled)
+    }
+
+    /// Set the idle timeout and cache limits for decoded images
+    ///
+    /// A lower limit applies immediately.
+    pub fn set_eviction_policy(&self, policy: EvictionPolicy) {
+        info!(
+            idle_timeout_secs = ?policy.idle_timeout.map(|d| d.as_secs()),
+            max_resident_templates = ?policy.max_resident_templates,
+            max_resident_bytes = ?policy.max_resident_bytes,
+            "Template eviction policy updated"
+        );
+        *self.eviction.write() = policy;
+        self.evict_over_limits(None);
+    }
+
+    /// Current eviction policy
+    pub fn eviction_policy(&self) -> EvictionPolicy {
+        *self.eviction.read()
+    }
+
+    /// Index all templates in the base directory
+    ///
+    /// Only metadata is read; images are decoded on first use. Directories are
+    /// read before the map is touched, then the new map is
+    /// swapped in under one write lock, so concurrent renders see either the
+    /// old set or the new one. Safe to call again while serving requests.
+    pub async fn load_all(&self) -> Result<TemplateReloadSummary, TemplateError> {
+        let _reloading = self.reload_lock.lock().await;
+        let pulled = self.pull_source(None).await;
         let base_path = self.base_path.clone();
 
         // Spawn blocking task for file I/O
-        let templates = tokio::task::spawn_blocking(move || {
+        let (loaded, failures) = tokio::task::spawn_blocking(move || {
             let mut loaded = HashMap::new();
+            let mut failures = Vec::new();
 
             if !base_path.exists() {
                 warn!(
                     "Templates directory does not exist: {}",
                     base_path.display()
                 );
-                return Ok(loaded);
+                return Ok((loaded, failures));
             }
 
             for entry in std::fs::read_dir(&base_path)? {
                 let entry = entry?;
                 let path = entry.path();
 
-                if path.is_dir() {
+                // Hidden directories are uploads being staged or replaced
+                if path.is_dir() && !dir_name(&path).starts_with('.') {
                     // Check if this looks like a template directory
                     let metadata_path = path.join("metadata.json");
                     if metadata_path.exists() {
-                        match Template::load(&path) {
+                        match Template::index(&path) {
                             Ok(template) => {
                                 let id = template.metadata.id.clone();
                                 loaded.insert(id, Arc::new(template));
@@ -247,30 +1068,255 @@ impl TemplateManager {
                                     error = %e,
                                     "Failed to load template"
                                 );
+                                failures.push((path, e));
                             }
                         }
                     }
                 }
             }
 
-            Ok::<_, TemplateError>(loaded)
+            Ok::<_, TemplateError>((loaded, failures))
         })
         .await
         .map_err(|e| TemplateError::MetadataLoad(format!("Task join error: {}", e)))??;
 
-        // Update templates map
-        let mut guard = self.templates.write();
-        *guard = templates;
+        // Swap the whole map at once
+        let mut templates = self.templates.write();
+        let mut summary = TemplateReloadSummary {
+            pulled,
+            ..TemplateReloadSummary::default()
+        };
+        let mut next = loaded;
+
+        for (path, error) in failures {
+            let previous = templates.values().find(|t| t.dir == path);
+            let template_id = match previous {
+                Some(template) => {
+                    // Keep serving the last good version
+                    next.entry(template.metadata.id.clone())
+                        .or_insert_with(|| template.clone());
+                    template.metadata.id.clone()
+                }
+                None => dir_name(&path),
+            };
+            summary
+                .failed
+                .push(TemplateLoadFailure::new(template_id, &error));
+        }
+
+        for id in next.keys() {
+            if templates.contains_key(id) {
+                if !summary.failed.iter().any(|f| &f.template_id == id) {
+                    summary.updated.push(id.clone());
+                }
+            } else {
+                summary.added.push(id.clone());
+            }
+        }
+        summary.removed = templates
+            .keys()
+            .filter(|id| !next.contains_key(*id))
+            .cloned()
+            .collect();
+
+        *templates = next;
+        drop(templates);
+
+        summary.added.sort();
+        summary.updated.sort();
+        summary.removed.sort();
+        summary
+            .failed
+            .sort_by(|a, b| a.template_id.cmp(&b.template_id));
+        *self.load_failures.write() = summary.failed.clone();
+        Ok(summary)
+    }
+
+    /// Re-read a single template directory
+    ///
+    /// Looks in the directory the template was loaded from, or
+    /// `{base_path}/{template_id}` for a template that isn't loaded yet. A
+    /// template whose directory was deleted is removed; one that fails to load
+    /// keeps its previous version.
+    pub async fn reload_one(
+        &self,
+        template_id: &str,
+    ) -> Result<TemplateReloadSummary, TemplateError> {
+        let _reloading = self.reload_lock.lock().await;
+        let dir = match self.get(template_id) {
+            Some(template) => template.dir.clone(),
+            None => self.base_path.join(template_id),
+        };
+
+        let mut summary = TemplateReloadSummary {
+            pulled: self.pull_source(Some(&dir_name(&dir))).await,
+            ..TemplateReloadSummary::default()
+        };
+        if !dir.join("metadata.json").exists() {
+            if self.templates.write().remove(template_id).is_none() {
+                return Err(TemplateError::NotFound(template_id.to_string()));
+            }
+            summary.removed.push(template_id.to_string());
+            self.record_failures(template_id, &summary.failed);
+            return Ok(summary);
+        }
+
+        let loading = dir.clone();
+        let loaded = tokio::task::spawn_blocking(move || Template::index(&loading))
+            .await
+            .map_err(|e| TemplateError::MetadataLoad(format!("Task join error: {}", e)))?;
+
+        match loaded {
+            Ok(template) if template.metadata.id != template_id => {
+                summary.failed.push(TemplateLoadFailure {
+                    template_id: template_id.to_string(),
+                    error: format!(
+                        "{} declares template ID '{}'",
+                        dir.join("metadata.json").display(),
+                        template.metadata.id
+                    ),
+                    issues: Vec::new(),
+                });
+            }
+            Ok(template) => {
+                let previous = self
+                    .templates
+                    .write()
+                    .insert(template_id.to_string(), Arc::new(template));
+                match previous {
+                    Some(_) => summary.updated.push(template_id.to_string()),
+                    None => summary.added.push(template_id.to_string()),
+                }
+            }
+            Err(e) => {
+                warn!(template_id = %template_id, error = %e, "Failed to reload template");
+                summary
+                    .failed
+                    .push(TemplateLoadFailure::new(template_id.to_string(), &e));
+            }
+        }
+        self.record_failures(template_id, &summary.failed);
+        Ok(summary)
+    }
+
+    /// Validate and write an uploaded template, then serve it immediately
+    ///
+    /// An ID that is already loaded, or whose directory exists, is refused
+    /// with [`TemplateError::AlreadyExists`] unless `overwrite` is set. With a
+    /// template source, the template is then published to it; if that fails
+    /// with [`TemplateError::Source`], this instance still serves the upload.
+    pub async fn install(
+        &self,
+        upload: TemplateUpload,
+        overwrite: bool,
+    ) -> Result<InstalledTemplate, TemplateError> {
+        let _reloading = self.reload_lock.lock().await;
+        let upload = tokio::task::spawn_blocking(move || upload.validate())
+            .await
+            .map_err(|e| TemplateError::MetadataLoad(format!("Task join error: {}", e)))??;
+
+        let id = upload.metadata.id.clone();
+        let current = self.get(&id);
+        let dir = match &current {
+            Some(template) => template.dir.clone(),
+            None => self.base_path.join(&id),
+        };
+        if !overwrite && (current.is_some() || dir.exists()) {
+            return Err(TemplateError::AlreadyExists(id));
+        }
+
+        let installing = dir.clone();
+        let (template, files) = tokio::task::spawn_blocking(move || upload.install(&installing))
+            .await
+            .map_err(|e| TemplateError::MetadataLoad(format!("Task join error: {}", e)))??;
+        let template = Arc::new(template);
+        let replaced = self
+            .templates
+            .write()
+            .insert(id.clone(), template.clone())
+            .is_some();
+        self.record_failures(&id, &[]);
+        info!(template_id = %id, replaced, "Installed uploaded template");
+
+        let source = self.source.read().clone();
+        if let Some(source) = source {
+            source.publish(&dir_name(&dir)).await?;
+        }
+
+        Ok(InstalledTemplate {
+            template,
+            files,
+            replaced,
+        })
+    }
 
+    /// Stop serving a template and delete its directory, and its copy in
+    /// the template source first when there is one
+    pub async fn remove(&self, id: &str) -> Result<(), TemplateError> {
+        let _reloading = self.reload_lock.lock().await;
+        let template = self
+            .get(id)
+            .ok_or_else(|| TemplateError::NotFound(id.to_string()))?;
+        let source = self.source.read().clone();
+        if let Some(source) = source {
+            source.unpublish(&dir_name(&template.dir)).await?;
+        }
+
+        // Hide the directory in one step so a failed delete never leaves a
+        // partial template to be indexed
+        let dir = template.dir.clone();
+        let removed = self
+            .base_path
+            .join(format!(".removed-{}", uuid::Uuid::new_v4()));
+        let renamed = removed.clone();
+        tokio::task::spawn_blocking(move || std::fs::rename(&dir, &renamed))
+            .await
+            .map_err(|e| TemplateError::MetadataLoad(format!("Task join error: {}", e)))??;
+
+        self.templates.write().remove(id);
+        self.record_failures(id, &[]);
+        let deleted = tokio::task::spawn_blocking(move || std::fs::remove_dir_all(&removed)).await;
+        if let Ok(Err(e)) = deleted {
+            warn!(template_id = %id, error = %e, "Failed to delete removed template directory");
+        }
+        info!(template_id = %id, "Removed template");
         Ok(())
     }
 
+    /// Replace the recorded failures for one template with those of its latest reload
+    fn record_failures(&self, template_id: &str, failed: &[TemplateLoadFailure]) {
+        let mut failures = self.load_failures.write();
+        failures.retain(|failure| failure.template_id != template_id);
+        failures.extend(failed.iter().cloned());
+        failures.sort_by(|a, b| a.template_id.cmp(&b.template_id));
+    }
+
+    /// Templates that failed to load or validate, and loaded ones with warnings
+    pub fn load_report(&self) -> TemplateLoadReport {
+        let templates = self.templates.read();
+        let mut warnings: Vec<TemplateLoadWarning> = templates
+            .values()
+            .filter(|template| !template.warnings.is_empty())
+            .map(|template| TemplateLoadWarning {
+                template_id: template.metadata.id.clone(),
+                issues: template.warnings.clone(),
+            })
+            .collect();
+        warnings.sort_by(|a, b| a.template_id.cmp(&b.template_id));
+
+        TemplateLoadReport {
+            template_count: templates.len(),
+            failed: self.load_failures.read().clone(),
+            warnings,
+        }
+    }
+
     /// Get a template by ID
     pub fn get(&self, id: &str) -> Option<Arc<Template>> {
         self.templates.read().get(id).cloned()
     }
 
-    /// Get the number of loaded templates
+    /// Get the number of indexed templates
     pub fn template_count(&self) -> usize {
         self.templates.read().len()
     }
@@ -280,6 +1326,25 @@ impl TemplateManager {
         self.templates.read().keys().cloned().collect()
     }
 
+    /// Fetch a design image's raw bytes through the compositor's HTTP client
+    pub async fn fetch_design_bytes(
+        &self,
+        url: &str,
+        limits: &GenerationLimits,
+    ) -> Result<bytes::Bytes, CompositorError> {
+        let compositor = self.compositor.read().clone();
+        compositor.fetch_design_bytes(url, limits).await
+    }
+
+    /// Download a request's URL designs into memory, checked as a generation would check them
+    pub async fn download_designs(&self, request: &mut MockupRequest) -> Result<(), TemplateError> {
+        let compositor = self.compositor.read().clone();
+        compositor
+            .download_designs(&mut request.designs, &request.limits)
+            .await
+            .map_err(compositor_error)
+    }
+
     /// Generate a mockup using the compositor
     pub async fn generate_mockup(
         &self,
@@ -288,10 +1353,967 @@ impl TemplateManager {
         let template = self
             .get(&request.template_id)
             .ok_or_else(|| TemplateError::NotFound(request.template_id.clone()))?;
+        let generations = self.generations.read().clone();
+        let _slot = generations.acquire().await?;
+        let images = self.images(&template).await?;
+
+        let compositor = self.compositor.read().clone();
+        let result = compositor
+            .generate(request, &template.metadata, &images)
+            .await
+            .map_err(compositor_error)?;
+        self.observe_generation(&request.template_id, &result);
+        Ok(result)
+    }
+
+    /// Generate a mockup against a template that is not managed from disk
+    pub async fn generate_with(
+        &self,
+        request: &MockupRequest,
+        metadata: &TemplateMetadata,
+        images: &TemplateImages,
+    ) -> Result<MockupResult, TemplateError> {
+        let generations = self.generations.read().clone();
+        let _slot = generations.acquire().await?;
+        let compositor = self.compositor.read().clone();
+        let result = compositor
+            .generate(request, metadata, images)
+            .await
+            .map_err(compositor_error)?;
+        self.observe_generation(&request.template_id, &result);
+        Ok(result)
+    }
+
+    /// Render designs alone onto a transparent canvas the size of the print area
+    ///
+    /// Placements must already be sized to the print area. Shares the
+    /// generation slots with mockups since it fetches and resizes the same designs.
+    pub async fn print_file(
+        &self,
+        designs: &[DesignLayer],
+        dpi: u32,
+        limits: &GenerationLimits,
+    ) -> Result<PrintFile, TemplateError> {
+        let generations = self.generations.read().clone();
+        let _slot = generations.acquire().await?;
+        let compositor = self.compositor.read().clone();
+        compositor
+            .print_file(designs, dpi, limits)
+            .await
+            .map_err(compositor_error)
+    }
+
+    /// Decoded images for a template, decoding them from disk on first use or after eviction
+    ///
+    /// Concurrent calls for the same template share one decode. Decoding may
+    /// evict the least recently used images of other templates to stay within
+    /// the cache limits.
+    pub async fn images(
+        &self,
+        template: &Arc<Template>,
+    ) -> Result<Arc<TemplateImages>, TemplateError> {
+        self.touch(template);
+        if let Some(images) = template.resident_images() {
+            self.record_cache_lookup(true);
+            return Ok(images);
+        }
+
+        let _decoding = template.decoding.lock().await;
+        // Decoded by the call we waited on
+        if let Some(images) = template.resident_images() {
+            self.record_cache_lookup(true);
+            return Ok(images);
+        }
+        self.record_cache_lookup(false);
+
+        let dir = template.dir.clone();
+        let loading = template.clone();
+        let images =
+            tokio::task::spawn_blocking(move || TemplateImages::load(&dir, &loading.metadata))
+                .await
+                .map_err(|e| TemplateError::MetadataLoad(format!("Task join error: {}", e)))??;
+
+        let images = Arc::new(images);
+        template.store_images(images.clone());
+        self.reloads_total.fetch_add(1, Ordering::Relaxed);
+        self.evict_over_limits(Some(template));
+
+        Ok(images)
+    }
+
+    /// Downscaled base image of a template, encoded once per format and size
+    ///
+    /// Uses the resident base image when there is one; otherwise only the base
+    /// image is decoded, and it is not added to the image cache.
+    pub async fn preview(
+        &self,
+        template: &Arc<Template>,
+        format: OutputFormat,
+        max_dimension: u32,
+    ) -> Result<Arc<TemplatePreview>, TemplateError> {
+        let key = (format, max_dimension);
+        if let Some(preview) = template.previews.lock().get(&key) {
+            return Ok(preview.clone());
+        }
+
+        let _decoding = template.decoding.lock().await;
+        // Rendered by the call we waited on
+        if let Some(preview) = template.previews.lock().get(&key) {
+            return Ok(preview.clone());
+        }
+
+        let resident = template.resident_images();
+        let dir = template.dir.clone();
+        let preview = tokio::task::spawn_blocking(move || match resident {
+            Some(images) => TemplatePreview::render(&images.base_image, format, max_dimension),
+            None => {
+                TemplatePreview::render(&TemplateImages::load_base(&dir)?, format, max_dimension)
+            }
+        })
+        .await
+        .map_err(|e| TemplateError::MetadataLoad(format!("Task join error: {}", e)))??;
+
+        let preview = Arc::new(preview);
+        template.previews.lock().insert(key, preview.clone());
+        debug!(
+            id = %template.metadata.id,
+            format = ?format,
+            bytes = preview.bytes.len(),
+            "Rendered template preview"
+        );
+        Ok(preview)
+    }
+
+    /// Displacement stats for a template, decoding its images if they were never measured
+    pub async fn displacement_stats(
+        &self,
+        template: &Arc<Template>,
+    ) -> Result<Option<DisplacementStats>, TemplateError> {
+        if let Some(stats) = template.displacement_stats.get() {
+            return Ok(*stats);
+        }
+        self.images(template).await?;
+        Ok(template.displacement_stats())
+    }
+
+    fn touch(&self, template: &Template) {
+        let now = self.epoch.elapsed().as_millis() as u64;
+        template.last_access_ms.store(now, Ordering::Relaxed);
+    }
+
+    /// Drop decoded images for templates idle longer than the eviction policy allows
+    ///
+    /// Metadata stays loaded; images are decoded again on next use.
+    pub fn evict_idle(&self) -> usize {
+        let Some(idle_timeout) = self.eviction_policy().idle_timeout else {
+            return 0;
+        };
+        let now = self.epoch.elapsed().as_millis() as u64;
+        let idle_ms = idle_timeout.as_millis() as u64;
+
+        let mut evicted = 0;
+        for template in self.templates.read().values() {
+            let last_access = template.last_access_ms.load(Ordering::Relaxed);
+            if now.saturating_sub(last_access) < idle_ms {
+                continue;
+            }
+            if template.images.lock().take().is_some() {
+                evicted += 1;
+            }
+        }
+
+        if evicted > 0 {
+            self.evictions_total
+                .fetch_add(evicted as u64, Ordering::Relaxed);
+            info!(evicted, "Evicted idle template images");
+        }
+        evicted
+    }
+
+    /// Drop least recently used images until the cache fits the policy's limits
+    ///
+    /// `keep` is the template just decoded for a caller and is never dropped.
+    fn evict_over_limits(&self, keep: Option<&Arc<Template>>) -> usize {
+        let policy = self.eviction_policy();
+        if policy.max_resident_templates.is_none() && policy.max_resident_bytes.is_none() {
+            return 0;
+        }
+
+        let templates = self.templates.read();
+        let mut resident: Vec<(u64, u64, &Arc<Template>)> = templates
+            .values()
+            .filter_map(|template| {
+                let bytes = template.resident_bytes();
+                (bytes > 0).then(|| {
+                    let last_access = template.last_access_ms.load(Ordering::Relaxed);
+                    (last_access, bytes, template)
+                })
+            })
+            .collect();
+        let mut count = resident.len();
+        let mut bytes: u64 = resident.iter().map(|(_, bytes, _)| bytes).sum();
+        resident.sort_by_key(|(last_access, _, _)| *last_access);
+
+        let mut evicted = 0;
+        for (_, size, template) in resident {
+            if !policy.over_limits(count, bytes) {
+                break;
+            }
+            if keep.is_some_and(|keep| Arc::ptr_eq(keep, template)) {
+                continue;
+            }
+            if template.images.lock().take().is_some() {
+                count -= 1;
+                bytes -= size;
+                evicted += 1;
+            }
+        }
+
+        if evicted > 0 {
+            self.evictions_total
+                .fetch_add(evicted as u64, Ordering::Relaxed);
+            debug!(
+                evicted,
+                resident_templates = count,
+                resident_bytes = bytes,
+                "Evicted least recently used template images"
+            );
+        }
+        evicted
+    }
+
+    /// Periodically evict idle template images in the background
+    pub fn spawn_eviction_task(self: &Arc<Self>, interval: Duration) {
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match manager.upgrade() {
+                    Some(manager) => {
+                        manager.evict_idle();
+                    }
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// Replace a template's geometry in memory; renders pick it up immediately
+    ///
+    /// The metadata file is left untouched until `persist_geometry`.
+    pub fn update_geometry(
+        &self,
+        id: &str,
+        geometry: TemplateGeometry,
+    ) -> Result<Arc<Template>, TemplateError> {
+        let mut templates = self.templates.write();
+        let current = templates
+            .get(id)
+            .ok_or_else(|| TemplateError::NotFound(id.to_string()))?;
+        geometry
+            .validate(&current.metadata.dimensions)
+            .map_err(TemplateError::InvalidGeometry)?;
+
+        let updated = Arc::new(current.with_metadata(current.metadata.with_geometry(geometry)));
+        templates.insert(id.to_string(), updated.clone());
+        info!(template_id = %id, "Updated template geometry");
+        Ok(updated)
+    }
+
+    /// Write a template's in-memory geometry to its metadata.json, bumping the version
+    ///
+    /// Fields other than the geometry and version are preserved as written.
+    /// Returns the new version.
+    pub async fn persist_geometry(&self, id: &str) -> Result<u32, TemplateError> {
+        let template = self
+            .get(id)
+            .ok_or_else(|| TemplateError::NotFound(id.to_string()))?;
+        let version = template.metadata.version + 1;
+        let geometry = template.metadata.geometry();
+        let dir = template.dir.clone();
+
+        tokio::task::spawn_blocking(move || write_geometry(&dir, &geometry, version))
+            .await
+            .map_err(|e| TemplateError::MetadataLoad(format!("Task join error: {}", e)))??;
+
+        // Only bump the version if the template wasn't replaced meanwhile
+        let mut templates = self.templates.write();
+        if let Some(current) = templates.get(id) {
+            if Arc::ptr_eq(current, &template) {
+                let mut metadata = current.metadata.clone();
+                metadata.version = version;
+                templates.insert(id.to_string(), Arc::new(current.with_metadata(metadata)));
+            }
+        }
+        info!(template_id = %id, version, "Persisted template geometry");
+        Ok(version)
+    }
+
+    /// Allow at most `max_concurrent` generations at once, each waiting up to
+    /// `max_wait` for a slot before failing with [`TemplateError::Saturated`]
+    ///
+    /// Generations already running keep their slot under the previous limit.
+    pub fn set_generation_limit(&self, max_concurrent: usize, max_wait: Duration) {
+        *self.generations.write() = Arc::new(GenerationLimiter::new(max_concurrent, max_wait));
+    }
+
+    /// Refuse new generations while the server drains for shutdown
+    ///
+    /// Generations already running finish normally.
+    pub fn stop_generations(&self) {
+        self.generations.read().close();
+    }
+
+    /// Restrict design URL fetches to `policy`
+    ///
+    /// Generations already running keep fetching under the previous policy.
+    pub fn set_url_policy(&self, policy: UrlPolicy) {
+        *self.compositor.write() = Arc::new(Compositor::with_url_policy(policy));
+    }
+
+    /// Generations running and waiting for a slot
+    pub fn generation_load(&self) -> GenerationLoad {
+        self.generations.read().load()
+    }
+
+    /// Memory usage and eviction counters
+    pub fn memory_stats(&self) -> TemplateMemoryStats {
+        let templates = self.templates.read();
+        let resident: Vec<u64> = templates
+            .values()
+            .map(|t| t.resident_bytes())
+            .filter(|bytes| *bytes > 0)
+            .collect();
+
+        TemplateMemoryStats {
+            templates: templates.len(),
+            resident_templates: resident.len(),
+            resident_bytes: resident.iter().sum(),
+            evictions_total: self.evictions_total.load(Ordering::Relaxed),
+            reloads_total: self.reloads_total.load(Ordering::Relaxed),
+        }
+    }
+}
 
-        self.compositor
-            .generate(request, &template)
+/// base.png, or else base.jpg, in a template directory
+fn base_image_path(dir: &Path) -> Result<PathBuf, TemplateError> {
+    ["base.png", "base.jpg"]
+        .iter()
+        .map(|file| dir.join(file))
+        .find(|candidate| candidate.exists())
+        .ok_or_else(|| {
+            TemplateError::FilesMissing(format!("no base.png or base.jpg in {}", dir.display()))
+        })
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Rewrite the geometry and version of a metadata.json, keeping its other fields
+fn write_geometry(
+    dir: &Path,
+    geometry: &TemplateGeometry,
+    version: u32,
+) -> Result<(), TemplateError> {
+    let metadata_path = dir.join("metadata.json");
+    let content = std::fs::read_to_string(&metadata_path)?;
+    let mut metadata: serde_json::Value = serde_json::from_str(&content)?;
+    let fields = metadata.as_object_mut().ok_or_else(|| {
+        TemplateError::MetadataLoad(format!("{}: not a JSON object", metadata_path.display()))
+    })?;
+
+    fields.insert("version".to_string(), version.into());
+    // Merge into existing objects so keys the engine doesn't read (such as
+    // displacement.path) survive
+    let updates = [
+        ("print_area", serde_json::to_value(&geometry.print_area)?),
+        (
+            "anchor_point",
+            serde_json::to_value(&geometry.anchor_point)?,
+        ),
+        (
+            "displacement",
+            serde_json::to_value(&geometry.displacement)?,
+        ),
+    ];
+    for (key, value) in updates {
+        match fields.get_mut(key) {
+            Some(serde_json::Value::Object(existing)) => {
+                if let serde_json::Value::Object(updated) = value {
+                    existing.extend(updated);
+                }
+            }
+            _ => {
+                fields.insert(key.to_string(), value);
+            }
+        }
+    }
+
+    // Write beside the original and rename so a crash never leaves a torn file
+    let tmp_path = dir.join("metadata.json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec_pretty(&metadata)?)?;
+    std::fs::rename(&tmp_path, &metadata_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry() -> TemplateGeometry {
+        TemplateGeometry {
+            print_area: PrintArea {
+                x: 100,
+                y: 150,
+                width: 400,
+                height: 500,
+            },
+            anchor_point: AnchorPoint { x: 300, y: 400 },
+            displacement: DisplacementConfig {
+                enabled: true,
+                strength_default: 10.0,
+                strength_range: (0.0, 30.0),
+                mode: DisplacementMode::Uniform,
+            },
+        }
+    }
+
+    const DIMENSIONS: TemplateDimensions = TemplateDimensions {
+        width: 800,
+        height: 800,
+    };
+
+    #[test]
+    fn test_geometry_validation() {
+        assert!(geometry().validate(&DIMENSIONS).is_ok());
+
+        let mut outside = geometry();
+        outside.print_area.width = 800;
+        assert!(outside.validate(&DIMENSIONS).is_err());
+
+        let mut empty = geometry();
+        empty.print_area.height = 0;
+        assert!(empty.validate(&DIMENSIONS).is_err());
+
+        let mut anchor = geometry();
+        anchor.anchor_point.y = 800;
+        assert!(anchor.validate(&DIMENSIONS).is_err());
+
+        let mut strength = geometry();
+        strength.displacement.strength_default = 31.0;
+        assert!(strength.validate(&DIMENSIONS).is_err());
+        strength.displacement.strength_range = (20.0, 5.0);
+        assert!(strength.validate(&DIMENSIONS).is_err());
+    }
+
+    #[test]
+    fn test_resolve_strength() {
+        let displacement = geometry().displacement;
+        assert_eq!(displacement.resolve_strength(None, true), Ok((10.0, None)));
+        assert_eq!(
+            displacement.resolve_strength(Some(12.5), true),
+            Ok((12.5, None))
+        );
+
+        let (strength, warning) = displacement.resolve_strength(Some(50.0), false).unwrap();
+        assert_eq!(strength, 30.0);
+        assert!(warning.unwrap().contains("[0, 30]"));
+        let (strength, warning) = displacement.resolve_strength(Some(-4.0), false).unwrap();
+        assert_eq!(strength, 0.0);
+        assert!(warning.is_some());
+
+        let error = displacement.resolve_strength(Some(50.0), true).unwrap_err();
+        assert!(error.contains("[0, 30]"));
+        assert!(displacement.resolve_strength(Some(-4.0), true).is_err());
+    }
+
+    #[test]
+    fn test_resolve_strength_displacement_disabled() {
+        let mut displacement = geometry().displacement;
+        displacement.enabled = false;
+        assert_eq!(displacement.resolve_strength(None, true), Ok((10.0, None)));
+        assert_eq!(
+            displacement.resolve_strength(Some(50.0), true),
+            Ok((50.0, None))
+        );
+    }
+
+    #[test]
+    fn test_strength_for_realism() {
+        let displacement = geometry().displacement;
+        assert_eq!(displacement.strength_for_realism(0.5, None), 15.0);
+
+        let subtle = DisplacementStats {
+            mean_deviation: 0.02,
+            p95_deviation: 0.05,
+        };
+        assert_eq!(displacement.strength_for_realism(0.0, Some(&subtle)), 0.0);
+        assert!((displacement.strength_for_realism(0.25, Some(&subtle)) - 20.0).abs() < 1e-9);
+        assert_eq!(displacement.strength_for_realism(1.0, Some(&subtle)), 30.0);
+
+        let flat = DisplacementStats {
+            mean_deviation: 0.0,
+            p95_deviation: 0.0,
+        };
+        assert_eq!(displacement.strength_for_realism(1.0, Some(&flat)), 30.0);
+    }
+
+    /// A 40x40 gray template with a circular mask.png of radius 12 in the middle
+    fn masked_template_dir(mask_size: u32) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("masked-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let metadata = serde_json::json!({
+            "id": "masked",
+            "version": 1,
+            "category": "tshirt",
+            "color": "gray",
+            "placement": "front",
+            "dimensions": {"width": 40, "height": 40},
+            "print_area": {"x": 0, "y": 0, "width": 40, "height": 40},
+            "anchor_point": {"x": 20, "y": 20},
+            "displacement": {"enabled": false, "strength_default": 0.0, "strength_range": [0.0, 30.0]},
+            "blend_mode": "normal",
+            "default_opacity": 255,
+        });
+        std::fs::write(dir.join("metadata.json"), metadata.to_string()).unwrap();
+        RgbaImage::from_pixel(40, 40, Rgba([128, 128, 128, 255]))
+            .save(dir.join("base.png"))
+            .unwrap();
+        image::GrayImage::from_fn(mask_size, mask_size, |x, y| {
+            let (dx, dy) = (x as i32 - 20, y as i32 - 20);
+            image::Luma([if dx * dx + dy * dy <= 144 { 255 } else { 0 }])
+        })
+        .save(dir.join(DEFAULT_PRINT_MASK))
+        .unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_mask_confines_design_to_silhouette() {
+        use crate::domain::PlacementSpec;
+        use crate::engine::{DesignLayer, DesignSource, OutputSettings};
+
+        let dir = masked_template_dir(40);
+        let template = Template::index(&dir).unwrap();
+        let images = TemplateImages::load(&dir, &template.metadata).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(images.print_mask.is_some());
+
+        let mut design = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 40, Rgba([255, 0, 0, 255])))
+            .write_to(
+                &mut std::io::Cursor::new(&mut design),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        let request = MockupRequest {
+            designs: vec![DesignLayer {
+                design: DesignSource::Bytes(design.into()),
+                placement: PlacementSpec {
+                    scale: 1.0,
+                    print_area_width: 40,
+                    print_area_height: 40,
+                    ..PlacementSpec::default()
+                },
+                displacement_strength: 0.0,
+                blend_mode: None,
+            }],
+            template_id: "masked".to_string(),
+            apply_displacement: None,
+            tint_color: None,
+            remove_background: None,
+            output: OutputSettings::default(),
+            limits: GenerationLimits::default(),
+        };
+
+        let result = Compositor::new()
+            .generate(&request, &template.metadata, &images)
             .await
-            .map_err(|e| TemplateError::MetadataLoad(format!("Compositor error: {}", e)))
+            .unwrap();
+        let mockup = image::load_from_memory(&result.bytes).unwrap().to_rgba8();
+
+        assert_eq!(mockup.get_pixel(20, 20).0, [255, 0, 0, 255]);
+        for (x, y) in [(0, 0), (39, 0), (0, 39), (39, 39), (20, 2), (5, 20)] {
+            assert_eq!(mockup.get_pixel(x, y).0, [128, 128, 128, 255], "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn test_mask_dimensions_must_match_base() {
+        let dir = masked_template_dir(30);
+        let template = Template::index(&dir).unwrap();
+        let result = TemplateImages::load(&dir, &template.metadata);
+        std::fs::remove_dir_all(&dir).ok();
+
+        match result {
+            Err(e @ TemplateError::MaskDimensions { .. }) => {
+                assert_eq!(
+                    e.to_string(),
+                    "Print mask mask.png is 30x30, but the base image is 40x40"
+                );
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("mismatched mask loaded"),
+        }
+    }
+
+    #[test]
+    fn test_per_pixel_mode_loads_strength_map() {
+        let dir = masked_template_dir(40);
+        let mut metadata = Template::index(&dir).unwrap().metadata;
+        metadata.displacement.mode = DisplacementMode::PerPixel;
+        // Offsets in red, strength in green
+        image::RgbImage::from_pixel(40, 40, image::Rgb([200, 60, 0]))
+            .save(dir.join("displacement.png"))
+            .unwrap();
+        let split = TemplateImages::load(&dir, &metadata);
+
+        // strength.png takes over from the green channel and must match the map
+        GrayImage::from_pixel(20, 20, Luma([255]))
+            .save(dir.join(STRENGTH_MAP))
+            .unwrap();
+        let mismatched = TemplateImages::load(&dir, &metadata);
+        metadata.displacement.mode = DisplacementMode::Uniform;
+        let uniform = TemplateImages::load(&dir, &metadata);
+        std::fs::remove_dir_all(&dir).ok();
+
+        let split = split.unwrap();
+        let pixel = |map: &Option<DynamicImage>| map.as_ref().unwrap().to_luma8().get_pixel(5, 5).0;
+        assert_eq!(pixel(&split.displacement_map), [200]);
+        assert_eq!(pixel(&split.strength_map), [60]);
+
+        match mismatched {
+            Err(e @ TemplateError::StrengthMapDimensions { .. }) => {
+                assert_eq!(
+                    e.to_string(),
+                    "Strength map strength.png is 20x20, but the displacement map is 40x40"
+                );
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("mismatched strength map loaded"),
+        }
+        assert!(uniform.unwrap().strength_map.is_none());
+    }
+
+    fn write_template(base: &Path, id: &str) {
+        let dir = base.join(id);
+        std::fs::create_dir_all(&dir).unwrap();
+        let metadata = serde_json::json!({
+            "id": id,
+            "version": 1,
+            "category": "tshirt",
+            "color": "gray",
+            "placement": "front",
+            "dimensions": {"width": 8, "height": 8},
+            "print_area": {"x": 0, "y": 0, "width": 8, "height": 8},
+            "anchor_point": {"x": 4, "y": 4},
+            "displacement": {"enabled": false, "strength_default": 0.0, "strength_range": [0.0, 30.0]},
+            "blend_mode": "normal",
+            "default_opacity": 255,
+        });
+        std::fs::write(dir.join("metadata.json"), metadata.to_string()).unwrap();
+        RgbaImage::from_pixel(8, 8, Rgba([128, 128, 128, 255]))
+            .save(dir.join("base.png"))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_reports_changes_and_keeps_broken_templates() {
+        let base = std::env::temp_dir().join(format!("reload-{}", uuid::Uuid::new_v4()));
+        write_template(&base, "tee-a");
+        write_template(&base, "tee-b");
+        let manager = TemplateManager::new(&base).unwrap();
+
+        let summary = manager.load_all().await.unwrap();
+        assert_eq!(summary.added, vec!["tee-a", "tee-b"]);
+        assert!(summary.updated.is_empty() && summary.removed.is_empty());
+
+        std::fs::remove_dir_all(base.join("tee-b")).unwrap();
+        write_template(&base, "tee-c");
+        std::fs::write(base.join("tee-a").join("metadata.json"), "{").unwrap();
+        let before = manager.get("tee-a").unwrap();
+
+        let summary = manager.load_all().await.unwrap();
+        assert_eq!(summary.added, vec!["tee-c"]);
+        assert_eq!(summary.removed, vec!["tee-b"]);
+        assert!(summary.updated.is_empty());
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].template_id, "tee-a");
+        assert!(Arc::ptr_eq(&manager.get("tee-a").unwrap(), &before));
+        assert_eq!(manager.template_count(), 2);
+
+        write_template(&base, "tee-a");
+        let summary = manager.reload_one("tee-a").await.unwrap();
+        assert_eq!(summary.updated, vec!["tee-a"]);
+        assert!(!Arc::ptr_eq(&manager.get("tee-a").unwrap(), &before));
+
+        std::fs::remove_dir_all(base.join("tee-c")).unwrap();
+        let summary = manager.reload_one("tee-c").await.unwrap();
+        assert_eq!(summary.removed, vec!["tee-c"]);
+        assert!(matches!(
+            manager.reload_one("tee-c").await,
+            Err(TemplateError::NotFound(_))
+        ));
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_metadata_validation() {
+        let metadata = TemplateMetadata::from_provider_mockup(
+            "tee",
+            "front",
+            DIMENSIONS,
+            geometry().print_area,
+        );
+        assert_eq!(metadata.validate((800, 800)), Ok(Vec::new()));
+
+        let warnings = metadata.validate((400, 400)).unwrap();
+        assert_eq!(
+            warnings,
+            vec!["base image is 400x400, but dimensions are 800x800"]
+        );
+
+        let mut broken = metadata.clone();
+        broken.print_area.width = 750;
+        broken.blend_mode = "dodge".to_string();
+        broken.displacement.strength_range = (30.0, 0.0);
+        let errors = broken.validate((800, 800)).unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].contains("spans x 100..850"));
+        assert!(errors[1].contains("[30, 0]"));
+        assert!(errors[2].contains("'dodge'"));
+
+        let mut anchor = metadata;
+        anchor.anchor_point = AnchorPoint { x: 50, y: 400 };
+        let errors = anchor.validate((800, 800)).unwrap_err();
+        assert!(errors[0].starts_with("anchor_point (50, 400)"));
+    }
+
+    #[test]
+    fn test_template_presets_override_defaults() {
+        let mut metadata: TemplateMetadata = serde_json::from_value(serde_json::json!({
+            "id": "mug",
+            "version": 1,
+            "category": "mug",
+            "color": "white",
+            "placement": "front",
+            "dimensions": {"width": 2000, "height": 1000},
+            "print_area": {"x": 100, "y": 100, "width": 1800, "height": 800},
+            "anchor_point": {"x": 1000, "y": 500},
+            "displacement": {"enabled": false, "strength_default": 0.0, "strength_range": [0.0, 30.0]},
+            "blend_mode": "multiply",
+            "default_opacity": 255,
+            "presets": {
+                "center_chest": {"scale": 0.4},
+                "handle_side": {"scale": 0.25, "offset_x": 0.3},
+            },
+        }))
+        .unwrap();
+        assert_eq!(metadata.validate((2000, 1000)), Ok(Vec::new()));
+
+        let presets = metadata.placement_presets();
+        assert_eq!(presets.len(), 6);
+        assert_eq!(presets["center_chest"].scale, 0.4);
+
+        let handle = metadata.preset_placement("handle_side").unwrap();
+        assert_eq!(
+            (handle.print_area_width, handle.print_area_height),
+            (1800, 800)
+        );
+        assert_eq!(handle.get_absolute_position(), (1215, 300));
+        assert!(metadata.preset_placement("sleeve").is_none());
+
+        // Anchor 50 px left of the print area's center
+        metadata.anchor_point.x = 950;
+        let chest = metadata.preset_placement("center_chest").unwrap();
+        assert_eq!(chest.get_absolute_position(), (490, 240));
+
+        metadata.presets.get_mut("handle_side").unwrap().scale = 2.0;
+        let errors = metadata.validate((2000, 1000)).unwrap_err();
+        assert!(errors[0].contains("preset 'handle_side'"));
+    }
+
+    #[tokio::test]
+    async fn test_load_report_lists_invalid_templates() {
+        let base = std::env::temp_dir().join(format!("report-{}", uuid::Uuid::new_v4()));
+        write_template(&base, "tee-a");
+        write_template(&base, "tee-b");
+        let metadata_path = base.join("tee-b").join("metadata.json");
+        let mut metadata: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&metadata_path).unwrap()).unwrap();
+        metadata["print_area"]["width"] = 20.into();
+        std::fs::write(&metadata_path, metadata.to_string()).unwrap();
+
+        let manager = TemplateManager::new(&base).unwrap();
+        manager.load_all().await.unwrap();
+        let report = manager.load_report();
+        assert_eq!(report.template_count, 1);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].template_id, "tee-b");
+        assert_eq!(report.failed[0].issues.len(), 1);
+
+        write_template(&base, "tee-b");
+        manager.reload_one("tee-b").await.unwrap();
+        let report = manager.load_report();
+        assert_eq!(report.template_count, 2);
+        assert!(report.failed.is_empty());
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn test_generation_rejected_while_slots_stay_busy() {
+        let base = std::env::temp_dir().join(format!("busy-{}", uuid::Uuid::new_v4()));
+        write_template(&base, "tee-a");
+        let manager = TemplateManager::new(&base).unwrap();
+        manager.load_all().await.unwrap();
+        manager.set_generation_limit(1, Duration::from_millis(20));
+
+        // A slow generation holding the only slot
+        let generations = manager.generations.read().clone();
+        let slot = generations.acquire().await.unwrap();
+
+        let request = MockupRequest {
+            designs: Vec::new(),
+            template_id: "tee-a".to_string(),
+            apply_displacement: None,
+            tint_color: None,
+            remove_background: None,
+            output: OutputSettings::default(),
+            limits: GenerationLimits::default(),
+        };
+        let err = manager.generate_mockup(&request).await.err().unwrap();
+        assert!(matches!(err, TemplateError::Saturated { .. }), "{err}");
+        let load = manager.generation_load();
+        assert_eq!(
+            (load.in_flight, load.queued, load.max_concurrent),
+            (1, 0, 1)
+        );
+
+        // Nothing is decoded for a rejected generation
+        assert_eq!(manager.memory_stats().reloads_total, 0);
+        drop(slot);
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn test_images_decode_lazily_within_cache_limits() {
+        let base = std::env::temp_dir().join(format!("lazy-{}", uuid::Uuid::new_v4()));
+        for id in ["tee-a", "tee-b", "tee-c"] {
+            write_template(&base, id);
+        }
+        let manager = TemplateManager::new(&base).unwrap();
+        manager.load_all().await.unwrap();
+        assert_eq!(manager.template_count(), 3);
+        assert_eq!(manager.memory_stats().resident_templates, 0);
+
+        // Concurrent first uses share one decode
+        let a = manager.get("tee-a").unwrap();
+        let (first, second) = tokio::join!(manager.images(&a), manager.images(&a));
+        assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+        assert_eq!(manager.memory_stats().reloads_total, 1);
+
+        manager.set_eviction_policy(EvictionPolicy {
+            max_resident_templates: Some(2),
+            ..EvictionPolicy::default()
+        });
+        let b = manager.get("tee-b").unwrap();
+        let c = manager.get("tee-c").unwrap();
+        manager.images(&b).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        manager.images(&a).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        manager.images(&c).await.unwrap();
+
+        // tee-b was least recently used
+        let stats = manager.memory_stats();
+        assert_eq!(stats.resident_templates, 2);
+        assert_eq!(stats.evictions_total, 1);
+        assert!(b.resident_images().is_none());
+        assert!(a.resident_images().is_some() && c.resident_images().is_some());
+
+        // Evicted images decode again on next use
+        manager.images(&b).await.unwrap();
+        assert_eq!(manager.memory_stats().reloads_total, 4);
+        assert!(a.resident_images().is_none());
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn test_preview_is_memoized_without_caching_images() {
+        let base = std::env::temp_dir().join(format!("preview-{}", uuid::Uuid::new_v4()));
+        write_template(&base, "tee");
+        let manager = TemplateManager::new(&base).unwrap();
+        manager.load_all().await.unwrap();
+        let template = manager.get("tee").unwrap();
+
+        let preview = manager
+            .preview(&template, OutputFormat::Jpeg, 4)
+            .await
+            .unwrap();
+        assert_eq!((preview.width, preview.height), (4, 4));
+        let decoded = image::load_from_memory(&preview.bytes).unwrap();
+        assert_eq!(decoded.dimensions(), (4, 4));
+        assert!(template.resident_images().is_none());
+        assert_eq!(manager.memory_stats().reloads_total, 0);
+
+        // Served from memory once rendered, even with the files gone
+        std::fs::remove_file(base.join("tee").join("base.png")).unwrap();
+        let again = manager
+            .preview(&template, OutputFormat::Jpeg, 4)
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&preview, &again));
+        assert!(matches!(
+            manager.preview(&template, OutputFormat::Webp, 4).await,
+            Err(TemplateError::FilesMissing(_))
+        ));
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_write_geometry_keeps_other_fields() {
+        let dir = std::env::temp_dir().join(format!("geometry-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let original = serde_json::json!({
+            "id": "tee",
+            "version": 3,
+            "print_area": {"x": 0, "y": 0, "width": 10, "height": 10},
+            "anchor_point": {"x": 5, "y": 5},
+            "displacement": {
+                "enabled": false,
+                "strength_default": 0.0,
+                "strength_range": [0.0, 30.0],
+                "path": "wrinkles.png",
+            },
+            "zones": {"chest": {"x": 1}},
+        });
+        std::fs::write(dir.join("metadata.json"), original.to_string()).unwrap();
+
+        write_geometry(&dir, &geometry(), 4).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("metadata.json")).unwrap())
+                .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(written["version"], 4);
+        assert_eq!(written["print_area"]["width"], 400);
+        assert_eq!(written["anchor_point"]["x"], 300);
+        assert_eq!(written["displacement"]["enabled"], true);
+        assert_eq!(written["displacement"]["path"], "wrinkles.png");
+        assert_eq!(written["zones"]["chest"]["x"], 1);
+    }
+
+    #[test]
+    fn test_geometry_test_pattern_follows_aspect_ratio() {
+        let pattern = geometry_test_pattern(&geometry().print_area);
+        assert_eq!(pattern.dimensions(), (480, 600));
+        // Border, then checker cells inside it
+        assert_eq!(pattern.get_pixel(0, 0).0, [0, 170, 255, 255]);
+        assert_ne!(pattern.get_pixel(20, 20), pattern.get_pixel(100, 20));
     }
 }
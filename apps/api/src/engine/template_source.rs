@@ -0,0 +1,45 @@
+//! Shared template libraries the local templates directory caches
+//!
+//! With a source set, the manager pulls the library into its templates
+//! directory before every reload and publishes uploads to it, so every
+//! instance serves the same templates.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::template::TemplateError;
+
+/// Changes a pull made to the local templates directory
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct TemplatePull {
+    /// Name of the source pulled from
+    pub source: String,
+    /// Files downloaded because they were new or changed
+    pub downloaded: usize,
+    /// Files already up to date in the local cache
+    pub unchanged: usize,
+    /// Local files deleted because the library no longer has them
+    pub deleted: usize,
+    /// Files, or the whole pull, that failed; the local copy is served instead
+    pub failed: Vec<String>,
+}
+
+/// A template library shared between instances
+///
+/// Templates are addressed by their directory name under the templates directory.
+#[async_trait]
+pub trait TemplateSource: Send + Sync {
+    /// Short name for logs and reload summaries
+    fn name(&self) -> &'static str;
+
+    /// Bring the local templates directory, or just the template in
+    /// `dir_name`, up to date with the library, downloading only files that changed
+    async fn pull(&self, dir_name: Option<&str>) -> Result<TemplatePull, TemplateError>;
+
+    /// Copy one local template directory to the library
+    async fn publish(&self, dir_name: &str) -> Result<(), TemplateError>;
+
+    /// Delete one template directory from the library
+    async fn unpublish(&self, dir_name: &str) -> Result<(), TemplateError>;
+}
@@ -0,0 +1,428 @@
+//! Templates uploaded through the API
+//!
+//! An upload is validated in memory, written to a hidden staging directory
+//! beside the templates, and renamed into place, so a rejected or failed
+//! upload never leaves a partial template directory behind.
+
+use bytes::Bytes;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+use super::template::{Template, TemplateError, TemplateMetadata, DEFAULT_PRINT_MASK};
+
+/// Longest template ID accepted from an upload
+const MAX_ID_LEN: usize = 128;
+
+/// Files of a template uploaded through the API
+pub struct TemplateUpload {
+    /// Raw metadata.json, written as uploaded
+    pub metadata: Bytes,
+    pub base_image: Bytes,
+    pub displacement_map: Option<Bytes>,
+    pub print_mask: Option<Bytes>,
+}
+
+/// Where an installed template's images were written
+#[derive(Debug, Clone)]
+pub struct TemplateFiles {
+    pub base_image: PathBuf,
+    pub displacement_map: Option<PathBuf>,
+    pub print_mask: Option<PathBuf>,
+}
+
+/// A template written by an upload and now being served
+pub struct InstalledTemplate {
+    pub template: Arc<Template>,
+    pub files: TemplateFiles,
+    /// Whether it replaced a template with the same ID
+    pub replaced: bool,
+}
+
+/// An upload that passed validation, with the file name each image is written as
+pub(super) struct ValidatedUpload {
+    pub metadata: TemplateMetadata,
+    raw_metadata: Bytes,
+    base_image: (String, Bytes),
+    displacement_map: Option<(String, Bytes)>,
+    print_mask: Option<(String, Bytes)>,
+}
+
+impl TemplateUpload {
+    /// Check the metadata, that every image decodes, and that the images
+    /// match the metadata's dimensions
+    ///
+    /// Reports every problem found rather than stopping at the first.
+    pub(super) fn validate(self) -> Result<ValidatedUpload, TemplateError> {
+        let metadata: TemplateMetadata = serde_json::from_slice(&self.metadata)
+            .map_err(|e| TemplateError::InvalidUpload(vec![format!("metadata: {}", e)]))?;
+
+        let mut errors = Vec::new();
+        if !is_valid_id(&metadata.id) {
+            errors.push(format!(
+                "id '{}' must be 1-{} letters, digits, '-' or '_'",
+                metadata.id, MAX_ID_LEN
+            ));
+        }
+        if !metadata.preserve_masks.is_empty() {
+            errors.push("preserve_masks can't be uploaded; remove them from the metadata".into());
+        }
+        let dimensions = (metadata.dimensions.width, metadata.dimensions.height);
+        if let Err(issues) = metadata.validate(dimensions) {
+            errors.extend(issues);
+        }
+
+        let base = decode_part("base", &self.base_image, &mut errors);
+        if let Some((_, image)) = &base {
+            if image.dimensions() != dimensions {
+                errors.push(format!(
+                    "base image is {}x{}, but dimensions are {}x{}",
+                    image.width(),
+                    image.height(),
+                    dimensions.0,
+                    dimensions.1
+                ));
+            }
+        }
+        let base_size = base.as_ref().map(|(_, image)| image.dimensions());
+
+        let displacement = self.displacement_map.as_ref().and_then(|bytes| {
+            let (format, image) = decode_part("displacement", bytes, &mut errors)?;
+            check_size("displacement map", &image, base_size, &mut errors);
+            Some((file_name("displacement", format), bytes.clone()))
+        });
+
+        let mask_file = metadata.print_mask.as_deref().unwrap_or(DEFAULT_PRINT_MASK);
+        if Path::new(mask_file).file_name() != Some(OsStr::new(mask_file)) {
+            errors.push(format!(
+                "print_mask '{}' must be a file name in the template directory",
+                mask_file
+            ));
+        }
+        if metadata.print_mask.is_some() && self.print_mask.is_none() {
+            errors.push(format!(
+                "metadata names print_mask '{}', but no mask was uploaded",
+                mask_file
+            ));
+        }
+        let mask = self.print_mask.as_ref().and_then(|bytes| {
+            let (format, image) = decode_part("mask", bytes, &mut errors)?;
+            check_size("mask", &image, base_size, &mut errors);
+            // The loader picks the decoder from the file extension
+            if ImageFormat::from_path(mask_file).ok() != Some(format) {
+                errors.push(format!(
+                    "mask is {:?}, but would be written as {}",
+                    format, mask_file
+                ));
+            }
+            Some((mask_file.to_string(), bytes.clone()))
+        });
+
+        if !errors.is_empty() {
+            return Err(TemplateError::InvalidUpload(errors));
+        }
+        let (base_format, _) = base.expect("an undecodable base image is reported");
+        Ok(ValidatedUpload {
+            metadata,
+            raw_metadata: self.metadata,
+            base_image: (file_name("base", base_format), self.base_image),
+            displacement_map: displacement,
+            print_mask: mask,
+        })
+    }
+}
+
+impl ValidatedUpload {
+    /// Write the template to `dir`, replacing any directory already there, and index it
+    ///
+    /// The previous directory is only deleted once the new one indexes, and
+    /// is put back otherwise.
+    pub(super) fn install(&self, dir: &Path) -> Result<(Template, TemplateFiles), TemplateError> {
+        let parent = dir.parent().unwrap_or(Path::new("."));
+        std::fs::create_dir_all(parent)?;
+
+        let staging = parent.join(format!(".upload-{}", uuid::Uuid::new_v4()));
+        if let Err(e) = self.write(&staging) {
+            remove_dir(&staging);
+            return Err(e);
+        }
+
+        let previous = dir
+            .exists()
+            .then(|| parent.join(format!(".replaced-{}", uuid::Uuid::new_v4())));
+        if let Some(previous) = &previous {
+            if let Err(e) = std::fs::rename(dir, previous) {
+                remove_dir(&staging);
+                return Err(e.into());
+            }
+        }
+        let restore = |previous: Option<&PathBuf>| {
+            if let Some(previous) = previous {
+                if let Err(e) = std::fs::rename(previous, dir) {
+                    warn!(path = %previous.display(), error = %e, "Failed to restore template directory");
+                }
+            }
+        };
+        if let Err(e) = std::fs::rename(&staging, dir) {
+            remove_dir(&staging);
+            restore(previous.as_ref());
+            return Err(e.into());
+        }
+
+        match Template::index(dir) {
+            Ok(template) => {
+                if let Some(previous) = &previous {
+                    remove_dir(previous);
+                }
+                Ok((template, self.files(dir)))
+            }
+            Err(e) => {
+                remove_dir(dir);
+                restore(previous.as_ref());
+                Err(e)
+            }
+        }
+    }
+
+    /// Write every file into a new `staging` directory
+    fn write(&self, staging: &Path) -> Result<(), TemplateError> {
+        std::fs::create_dir(staging)?;
+        let images = std::iter::once(&self.base_image)
+            .chain(self.displacement_map.iter())
+            .chain(self.print_mask.iter());
+        for (name, bytes) in images {
+            std::fs::write(staging.join(name), bytes)?;
+        }
+        // Last, since a directory is only picked up once it has metadata.json
+        std::fs::write(staging.join("metadata.json"), &self.raw_metadata)?;
+        Ok(())
+    }
+
+    fn files(&self, dir: &Path) -> TemplateFiles {
+        TemplateFiles {
+            base_image: dir.join(&self.base_image.0),
+            displacement_map: self
+                .displacement_map
+                .as_ref()
+                .map(|(name, _)| dir.join(name)),
+            print_mask: self.print_mask.as_ref().map(|(name, _)| dir.join(name)),
+        }
+    }
+}
+
+/// IDs become directory names, so only plain names are accepted
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Decode a PNG or JPEG part, recording why it can't be used otherwise
+fn decode_part(
+    part: &str,
+    bytes: &[u8],
+    errors: &mut Vec<String>,
+) -> Option<(ImageFormat, DynamicImage)> {
+    let format = match image::guess_format(bytes) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg)) => format,
+        Ok(format) => {
+            errors.push(format!("{} must be PNG or JPEG, got {:?}", part, format));
+            return None;
+        }
+        Err(_) => {
+            errors.push(format!("{} is not a recognized image format", part));
+            return None;
+        }
+    };
+    match image::load_from_memory_with_format(bytes, format) {
+        Ok(image) => Some((format, image)),
+        Err(e) => {
+            errors.push(format!("{} could not be decoded: {}", part, e));
+            None
+        }
+    }
+}
+
+/// Record an image whose size differs from the base image's
+fn check_size(
+    part: &str,
+    image: &DynamicImage,
+    base_size: Option<(u32, u32)>,
+    errors: &mut Vec<String>,
+) {
+    if let Some((width, height)) = base_size {
+        if image.dimensions() != (width, height) {
+            errors.push(format!(
+                "{} is {}x{}, but the base image is {}x{}",
+                part,
+                image.width(),
+                image.height(),
+                width,
+                height
+            ));
+        }
+    }
+}
+
+fn file_name(stem: &str, format: ImageFormat) -> String {
+    match format {
+        ImageFormat::Png => format!("{}.png", stem),
+        _ => format!("{}.jpg", stem),
+    }
+}
+
+fn remove_dir(path: &Path) {
+    if let Err(e) = std::fs::remove_dir_all(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!(path = %path.display(), error = %e, "Failed to remove template directory");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TemplateManager;
+    use image::{ImageOutputFormat, Rgba, RgbaImage};
+
+    fn png(width: u32, height: u32) -> Bytes {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            width,
+            height,
+            Rgba([90, 90, 90, 255]),
+        ))
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            ImageOutputFormat::Png,
+        )
+        .unwrap();
+        bytes.into()
+    }
+
+    fn upload(id: &str, print_area_width: i32) -> TemplateUpload {
+        let metadata = serde_json::json!({
+            "id": id,
+            "version": 1,
+            "category": "tshirt",
+            "color": "gray",
+            "placement": "front",
+            "dimensions": {"width": 16, "height": 16},
+            "print_area": {"x": 2, "y": 2, "width": print_area_width, "height": 12},
+            "anchor_point": {"x": 8, "y": 8},
+            "displacement": {"enabled": true, "strength_default": 5.0, "strength_range": [0.0, 30.0]},
+            "blend_mode": "multiply",
+            "default_opacity": 240,
+        });
+        TemplateUpload {
+            metadata: metadata.to_string().into(),
+            base_image: png(16, 16),
+            displacement_map: Some(png(16, 16)),
+            print_mask: None,
+        }
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_upload_validation_reports_every_problem() {
+        let mut broken = upload("../escape", 40);
+        broken.displacement_map = Some(png(8, 8));
+        broken.print_mask = Some(Bytes::from_static(b"not an image"));
+
+        match broken.validate() {
+            Err(TemplateError::InvalidUpload(errors)) => {
+                assert_eq!(errors.len(), 4, "{:?}", errors);
+                assert!(errors[0].starts_with("id '../escape'"));
+                assert!(errors[1].starts_with("print_area must lie within"));
+                assert_eq!(
+                    errors[2],
+                    "displacement map is 8x8, but the base image is 16x16"
+                );
+                assert_eq!(errors[3], "mask is not a recognized image format");
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("broken upload validated"),
+        }
+
+        let mut missing_mask = upload("tee", 12);
+        missing_mask.base_image = png(20, 16);
+        missing_mask.displacement_map = None;
+        let metadata = String::from_utf8(missing_mask.metadata.to_vec()).unwrap();
+        missing_mask.metadata = metadata
+            .replacen('{', r#"{"print_mask":"silhouette.png","#, 1)
+            .into();
+        match missing_mask.validate() {
+            Err(TemplateError::InvalidUpload(errors)) => assert_eq!(
+                errors,
+                vec![
+                    "base image is 20x16, but dimensions are 16x16",
+                    "metadata names print_mask 'silhouette.png', but no mask was uploaded",
+                ]
+            ),
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("upload without its mask validated"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_install_registers_and_replaces_templates() {
+        let base = std::env::temp_dir().join(format!("upload-{}", uuid::Uuid::new_v4()));
+        let manager = TemplateManager::new(&base).unwrap();
+
+        let installed = manager.install(upload("tee", 12), false).await.unwrap();
+        assert!(!installed.replaced);
+        assert_eq!(
+            installed.files.base_image,
+            base.join("tee").join("base.png")
+        );
+        assert!(manager.get("tee").is_some());
+        assert_eq!(
+            entries(&base.join("tee")),
+            vec!["base.png", "displacement.png", "metadata.json"]
+        );
+
+        // Rejected uploads leave the served template and directory alone
+        assert!(matches!(
+            manager.install(upload("tee", 10), false).await,
+            Err(TemplateError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            manager.install(upload("tee", 40), true).await,
+            Err(TemplateError::InvalidUpload(_))
+        ));
+        assert_eq!(manager.get("tee").unwrap().metadata.print_area.width, 12);
+
+        let mut replacement = upload("tee", 10);
+        replacement.displacement_map = None;
+        let installed = manager.install(replacement, true).await.unwrap();
+        assert!(installed.replaced);
+        assert_eq!(manager.get("tee").unwrap().metadata.print_area.width, 10);
+        assert_eq!(
+            entries(&base.join("tee")),
+            vec!["base.png", "metadata.json"]
+        );
+        // No staging or replaced directories linger
+        assert_eq!(entries(&base), vec!["tee"]);
+
+        manager.remove("tee").await.unwrap();
+        assert!(manager.get("tee").is_none());
+        assert!(entries(&base).is_empty());
+        assert!(matches!(
+            manager.remove("tee").await,
+            Err(TemplateError::NotFound(_))
+        ));
+        std::fs::remove_dir_all(&base).ok();
+    }
+}
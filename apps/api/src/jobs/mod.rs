@@ -0,0 +1,11 @@
+//! Multi-result and async jobs
+//!
+//! Bundle, batch, and bulk-CSV endpoints register the files they produce
+//! here; `GET /api/v1/jobs/{id}/download` streams them back as one ZIP.
+//! Async generations are tracked here too until their results are collected.
+
+mod render;
+mod store;
+
+pub use render::{RenderJob, RenderJobError, RenderJobStatus, RenderJobs};
+pub use store::{JobFile, JobOutputs, JobStore, JOB_OUTPUT_RETENTION};
@@ -0,0 +1,420 @@
+//! Async mockup renders
+//!
+//! `POST /api/v1/mockups/generate?async=true` records a job here and renders
+//! in the background, and `GET /api/v1/mockups/jobs/{id}` polls it. Jobs are
+//! kept in memory and, with a database, in the `render_jobs` table as well, so
+//! finished results survive a restart. A finished job stays available for the
+//! retention window after it completes.
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_postgres::Row;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db::pool::DbError;
+use crate::db::DbPool;
+
+/// Unfinished jobs older than this were lost with the process that ran them
+const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Error recorded on jobs lost with the process that ran them
+const INTERRUPTED_MESSAGE: &str = "Interrupted: the render did not finish before a restart";
+
+/// Lifecycle of an async render
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderJobStatus {
+    Queued,
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl RenderJobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RenderJobStatus::Queued => "queued",
+            RenderJobStatus::Processing => "processing",
+            RenderJobStatus::Completed => "completed",
+            RenderJobStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "queued" => Some(RenderJobStatus::Queued),
+            "processing" => Some(RenderJobStatus::Processing),
+            "completed" => Some(RenderJobStatus::Completed),
+            "failed" => Some(RenderJobStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Why an async render failed, as the synchronous endpoint would report it
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RenderJobError {
+    pub code: String,
+    pub message: String,
+}
+
+/// An async render and, once finished, its result or error
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RenderJob {
+    pub job_id: Uuid,
+    /// Owning API key, `None` when auth is disabled
+    #[serde(skip)]
+    pub api_key_id: Option<Uuid>,
+    pub template_id: String,
+    pub status: RenderJobStatus,
+    /// Percent complete: 0 while queued, 50 while rendering, 100 once finished
+    pub progress: u8,
+    /// The synchronous generate response, once completed
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+    pub error: Option<RenderJobError>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When a finished job's result stops being available
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl RenderJob {
+    fn new(api_key_id: Option<Uuid>, template_id: &str) -> Self {
+        Self {
+            job_id: Uuid::new_v4(),
+            api_key_id,
+            template_id: template_id.to_string(),
+            status: RenderJobStatus::Queued,
+            progress: 0,
+            result: None,
+            error: None,
+            created_at: Utc::now(),
+            started_at: None,
+            completed_at: None,
+            expires_at: None,
+        }
+    }
+
+    fn finish(&mut self, expires_at: DateTime<Utc>) {
+        self.progress = 100;
+        self.completed_at = Some(Utc::now());
+        self.expires_at = Some(expires_at);
+    }
+
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= now)
+    }
+
+    fn from_row(row: &Row) -> Self {
+        let status: String = row.get("status");
+        let result: Option<String> = row.get("result");
+        let error_code: Option<String> = row.get("error_code");
+        let error_message: Option<String> = row.get("error_message");
+
+        Self {
+            job_id: row.get("id"),
+            api_key_id: row.get("api_key_id"),
+            template_id: row.get("template_id"),
+            status: RenderJobStatus::parse(&status).unwrap_or(RenderJobStatus::Failed),
+            progress: row.get::<_, i16>("progress").clamp(0, 100) as u8,
+            result: result.and_then(|json| serde_json::from_str(&json).ok()),
+            error: error_code.map(|code| RenderJobError {
+                code,
+                message: error_message.unwrap_or_default(),
+            }),
+            created_at: row.get("created_at"),
+            started_at: row.get("started_at"),
+            completed_at: row.get("completed_at"),
+            expires_at: row.get("expires_at"),
+        }
+    }
+}
+
+const JOB_COLUMNS: &str = "id, api_key_id, template_id, status, progress, result::TEXT AS result, \
+     error_code, error_message, created_at, started_at, completed_at, expires_at";
+
+/// Async render jobs, in memory and in the database when one is configured
+pub struct RenderJobs {
+    jobs: RwLock<HashMap<Uuid, RenderJob>>,
+    db_pool: Option<DbPool>,
+    retention: Duration,
+}
+
+impl RenderJobs {
+    /// Finished jobs stay available for `retention` after they complete
+    pub fn new(db_pool: Option<DbPool>, retention: Duration) -> Self {
+        Self {
+            jobs: RwLock::new(HashMap::new()),
+            db_pool,
+            retention,
+        }
+    }
+
+    /// Record a queued render, dropping expired jobs
+    pub async fn create(&self, api_key_id: Option<Uuid>, template_id: &str) -> RenderJob {
+        self.prune();
+        let job = RenderJob::new(api_key_id, template_id);
+        self.jobs.write().insert(job.job_id, job.clone());
+
+        if let Some(pool) = &self.db_pool {
+            if let Err(e) = insert_job(pool, &job).await {
+                warn!(job_id = %job.job_id, error = %e, "Failed to persist render job");
+            }
+        }
+        job
+    }
+
+    /// Mark a job as rendering
+    pub async fn start(&self, id: Uuid) {
+        self.transition(id, |job| {
+            job.status = RenderJobStatus::Processing;
+            job.progress = 50;
+            job.started_at = Some(Utc::now());
+        })
+        .await;
+    }
+
+    /// Record a finished render's response
+    pub async fn complete(&self, id: Uuid, result: serde_json::Value) {
+        let expires_at = self.expires_at();
+        self.transition(id, |job| {
+            job.status = RenderJobStatus::Completed;
+            job.result = Some(result);
+            job.finish(expires_at);
+        })
+        .await;
+    }
+
+    /// Record why a render failed
+    pub async fn fail(&self, id: Uuid, error: RenderJobError) {
+        let expires_at = self.expires_at();
+        self.transition(id, |job| {
+            job.status = RenderJobStatus::Failed;
+            job.error = Some(error);
+            job.finish(expires_at);
+        })
+        .await;
+    }
+
+    /// A job owned by `api_key_id` that has not expired
+    ///
+    /// Jobs started before a restart or by another instance are read from
+    /// the database.
+    pub async fn get(&self, id: Uuid, api_key_id: Option<Uuid>) -> Option<RenderJob> {
+        let local = self.jobs.read().get(&id).cloned();
+        let job = match (local, &self.db_pool) {
+            (Some(job), _) => Some(job),
+            (None, Some(pool)) => match fetch_job(pool, id).await {
+                Ok(job) => job,
+                Err(e) => {
+                    warn!(job_id = %id, error = %e, "Failed to load render job");
+                    None
+                }
+            },
+            (None, None) => None,
+        }?;
+
+        (job.api_key_id == api_key_id && !job.is_expired(Utc::now())).then_some(job)
+    }
+
+    /// Drop expired jobs from memory, returning how many were removed
+    pub fn prune(&self) -> usize {
+        let now = Utc::now();
+        let mut jobs = self.jobs.write();
+        let before = jobs.len();
+        jobs.retain(|_, job| !job.is_expired(now));
+        let removed = before - jobs.len();
+        if removed > 0 {
+            debug!(removed, "Pruned expired render jobs");
+        }
+        removed
+    }
+
+    /// Periodically drop expired jobs, and in the database fail jobs whose
+    /// process went away before they finished
+    pub fn spawn_cleanup_task(self: &Arc<Self>, interval: Duration) {
+        let jobs = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                jobs.prune();
+                if let Some(pool) = &jobs.db_pool {
+                    if let Err(e) = cleanup_jobs(pool, jobs.retention).await {
+                        warn!(error = %e, "Failed to clean up render jobs");
+                    }
+                }
+            }
+        });
+    }
+
+    fn expires_at(&self) -> DateTime<Utc> {
+        Utc::now() + chrono::Duration::from_std(self.retention).unwrap_or_default()
+    }
+
+    async fn transition(&self, id: Uuid, update: impl FnOnce(&mut RenderJob)) {
+        let job = {
+            let mut jobs = self.jobs.write();
+            let Some(job) = jobs.get_mut(&id) else {
+                return;
+            };
+            update(job);
+            job.clone()
+        };
+
+        if let Some(pool) = &self.db_pool {
+            if let Err(e) = update_job(pool, &job).await {
+                warn!(job_id = %id, error = %e, "Failed to persist render job");
+            }
+        }
+    }
+}
+
+async fn insert_job(pool: &DbPool, job: &RenderJob) -> Result<(), DbError> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            r#"
+        INSERT INTO render_jobs (id, api_key_id, template_id, status, progress, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+            &[
+                &job.job_id,
+                &job.api_key_id,
+                &job.template_id,
+                &job.status.as_str(),
+                &(job.progress as i16),
+                &job.created_at,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn update_job(pool: &DbPool, job: &RenderJob) -> Result<(), DbError> {
+    let client = pool.get().await?;
+    let result = job.result.as_ref().map(|result| result.to_string());
+    let error_code = job.error.as_ref().map(|error| error.code.as_str());
+    let error_message = job.error.as_ref().map(|error| error.message.as_str());
+    client
+        .execute(
+            r#"
+        UPDATE render_jobs
+        SET status = $2, progress = $3, result = $4::TEXT::JSONB, error_code = $5,
+            error_message = $6, started_at = $7, completed_at = $8, expires_at = $9
+        WHERE id = $1
+        "#,
+            &[
+                &job.job_id,
+                &job.status.as_str(),
+                &(job.progress as i16),
+                &result,
+                &error_code,
+                &error_message,
+                &job.started_at,
+                &job.completed_at,
+                &job.expires_at,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn fetch_job(pool: &DbPool, id: Uuid) -> Result<Option<RenderJob>, DbError> {
+    let client = pool.get().await?;
+    let sql = format!("SELECT {} FROM render_jobs WHERE id = $1", JOB_COLUMNS);
+    let row = client.query_opt(&sql, &[&id]).await?;
+    Ok(row.as_ref().map(RenderJob::from_row))
+}
+
+/// Delete expired rows and fail unfinished jobs that went stale
+async fn cleanup_jobs(pool: &DbPool, retention: Duration) -> Result<(), DbError> {
+    let client = pool.get().await?;
+    let deleted = client
+        .execute("DELETE FROM render_jobs WHERE expires_at <= NOW()", &[])
+        .await?;
+    let interrupted = client
+        .execute(
+            r#"
+        UPDATE render_jobs
+        SET status = 'failed', progress = 100, error_code = 'INTERRUPTED', error_message = $3,
+            completed_at = NOW(), expires_at = NOW() + make_interval(secs => $2)
+        WHERE status IN ('queued', 'processing')
+          AND created_at < NOW() - make_interval(secs => $1)
+        "#,
+            &[
+                &STALE_AFTER.as_secs_f64(),
+                &retention.as_secs_f64(),
+                &INTERRUPTED_MESSAGE,
+            ],
+        )
+        .await?;
+    if deleted > 0 || interrupted > 0 {
+        debug!(deleted, interrupted, "Cleaned up render jobs");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs_move_through_their_lifecycle() {
+        let jobs = RenderJobs::new(None, Duration::from_secs(60));
+        let owner = Some(Uuid::new_v4());
+        let job = jobs.create(owner, "white-tshirt-front").await;
+        assert_eq!(job.status, RenderJobStatus::Queued);
+
+        jobs.start(job.job_id).await;
+        let running = jobs.get(job.job_id, owner).await.unwrap();
+        assert_eq!(
+            (running.status, running.progress),
+            (RenderJobStatus::Processing, 50)
+        );
+
+        jobs.complete(job.job_id, serde_json::json!({ "success": true }))
+            .await;
+        let done = jobs.get(job.job_id, owner).await.unwrap();
+        assert_eq!(
+            (done.status, done.progress),
+            (RenderJobStatus::Completed, 100)
+        );
+        assert_eq!(done.result, Some(serde_json::json!({ "success": true })));
+        assert!(done.expires_at.unwrap() > done.completed_at.unwrap());
+
+        // Other keys can't see the job, and it stays readable more than once
+        assert!(jobs.get(job.job_id, None).await.is_none());
+        assert!(jobs.get(job.job_id, owner).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_finished_jobs_expire_after_retention() {
+        let jobs = RenderJobs::new(None, Duration::ZERO);
+        let failed = jobs.create(None, "tee").await;
+        let running = jobs.create(None, "tee").await;
+        jobs.start(running.job_id).await;
+        jobs.fail(
+            failed.job_id,
+            RenderJobError {
+                code: "TEMPLATE_NOT_FOUND".to_string(),
+                message: "Template 'tee' does not exist".to_string(),
+            },
+        )
+        .await;
+
+        assert!(jobs.get(failed.job_id, None).await.is_none());
+        assert_eq!(jobs.prune(), 1);
+        // Unfinished jobs never expire
+        assert!(jobs.get(running.job_id, None).await.is_some());
+    }
+}
@@ -0,0 +1,213 @@
+//! In-memory registry of finished job outputs
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::storage::{zip_content_length, R2Error, StorageBackend, ZipEntry};
+
+/// How long finished job outputs stay downloadable
+pub const JOB_OUTPUT_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Where a job output file's bytes live
+pub enum JobFileSource {
+    /// Held in memory
+    Memory(Bytes),
+    /// Stored in the storage backend under this key
+    R2 { key: String },
+}
+
+/// One file produced by a job
+pub struct JobFile {
+    /// Path inside the download archive
+    pub name: String,
+    /// Size in bytes, when known without fetching
+    pub size: Option<u64>,
+    pub source: JobFileSource,
+}
+
+impl JobFile {
+    /// A file held in memory
+    pub fn in_memory(name: impl Into<String>, data: Bytes) -> Self {
+        Self {
+            name: name.into(),
+            size: Some(data.len() as u64),
+            source: JobFileSource::Memory(data),
+        }
+    }
+}
+
+/// Files produced by a bundle, batch, or bulk-CSV job
+pub struct JobOutputs {
+    pub id: Uuid,
+    /// Job kind, e.g. "batch", used to name the archive
+    pub kind: String,
+    /// Owning API key, `None` when auth is disabled
+    pub api_key_id: Option<Uuid>,
+    pub files: Vec<JobFile>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl JobOutputs {
+    pub fn new(kind: &str, api_key_id: Option<Uuid>, files: Vec<JobFile>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            kind: kind.to_string(),
+            api_key_id,
+            files,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Exact ZIP size, when every file's size is known up front
+    pub fn archive_len(&self) -> Option<u64> {
+        let sizes: Option<Vec<(usize, u64)>> = self
+            .files
+            .iter()
+            .map(|f| f.size.map(|size| (f.name.len(), size)))
+            .collect();
+        sizes.map(zip_content_length)
+    }
+}
+
+/// Keeps job outputs downloadable until they expire
+pub struct JobStore {
+    jobs: RwLock<HashMap<Uuid, Arc<JobOutputs>>>,
+    storage: Option<Arc<dyn StorageBackend>>,
+    retention: Duration,
+}
+
+impl JobStore {
+    pub fn new(storage: Option<Arc<dyn StorageBackend>>, retention: Duration) -> Self {
+        Self {
+            jobs: RwLock::new(HashMap::new()),
+            storage,
+            retention,
+        }
+    }
+
+    /// Register a finished job's outputs, dropping expired jobs
+    pub fn insert(&self, outputs: JobOutputs) -> Uuid {
+        self.prune();
+        let id = outputs.id;
+        self.jobs.write().insert(id, Arc::new(outputs));
+        id
+    }
+
+    /// Outputs of a job that has not expired
+    pub fn get(&self, id: Uuid) -> Option<Arc<JobOutputs>> {
+        let outputs = self.jobs.read().get(&id).cloned()?;
+        (!self.is_expired(&outputs)).then_some(outputs)
+    }
+
+    /// Drop expired jobs, returning how many were removed
+    pub fn prune(&self) -> usize {
+        let mut jobs = self.jobs.write();
+        let before = jobs.len();
+        jobs.retain(|_, outputs| !self.is_expired(outputs));
+        let removed = before - jobs.len();
+        if removed > 0 {
+            debug!(removed, "Pruned expired job outputs");
+        }
+        removed
+    }
+
+    fn is_expired(&self, outputs: &JobOutputs) -> bool {
+        let age = Utc::now().signed_duration_since(outputs.created_at);
+        age.to_std().map_or(false, |age| age > self.retention)
+    }
+
+    /// Whether outputs can be stored
+    pub fn can_upload(&self) -> bool {
+        self.storage.is_some()
+    }
+
+    /// Store an output so the job keeps only its key
+    ///
+    /// Returns the file entry and the object's public URL, if one is configured.
+    pub async fn upload(
+        &self,
+        name: String,
+        key: String,
+        data: Bytes,
+        content_type: &str,
+    ) -> Result<(JobFile, Option<String>), R2Error> {
+        let storage = self.storage.as_ref().ok_or(R2Error::NotConfigured)?;
+        let uploaded = storage
+            .upload_key(&key, data.to_vec(), content_type)
+            .await?;
+
+        let file = JobFile {
+            name,
+            size: Some(uploaded.size),
+            source: JobFileSource::R2 { key: uploaded.key },
+        };
+        Ok((file, uploaded.public_url))
+    }
+
+    /// Load one file's bytes as an archive entry
+    pub async fn open(&self, file: &JobFile) -> io::Result<ZipEntry> {
+        let data = match &file.source {
+            JobFileSource::Memory(data) => data.clone(),
+            JobFileSource::R2 { key } => {
+                let storage = self.storage.as_ref().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "Storage is not configured")
+                })?;
+                storage
+                    .download(key)
+                    .await
+                    .map(Bytes::from)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+            }
+        };
+
+        Ok(ZipEntry {
+            name: file.name.clone(),
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_jobs_are_hidden_and_pruned() {
+        let store = JobStore::new(None, Duration::from_secs(60));
+
+        let fresh = store.insert(JobOutputs::new("batch", None, Vec::new()));
+        let mut old = JobOutputs::new("batch", None, Vec::new());
+        old.created_at = Utc::now() - chrono::Duration::minutes(5);
+        let old = store.insert(old);
+
+        assert!(store.get(fresh).is_some());
+        assert!(store.get(old).is_none());
+        assert_eq!(store.prune(), 1);
+    }
+
+    #[test]
+    fn test_archive_len_requires_known_sizes() {
+        let mut outputs = JobOutputs::new(
+            "bundle",
+            None,
+            vec![JobFile::in_memory("a.png", Bytes::from_static(b"abc"))],
+        );
+        assert_eq!(outputs.archive_len(), Some(zip_content_length([(5, 3)])));
+
+        outputs.files.push(JobFile {
+            name: "b.png".to_string(),
+            size: None,
+            source: JobFileSource::R2 {
+                key: "generated/b.png".to_string(),
+            },
+        });
+        assert_eq!(outputs.archive_len(), None);
+    }
+}
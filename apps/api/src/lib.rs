@@ -0,0 +1,10 @@
+//! R-Image-Magic engine
+//!
+//! The rendering core shared by the API server and the benchmarks: mockup
+//! generation, the domain types it renders against, outbound URL guarding,
+//! and the metrics it records.
+
+pub mod domain;
+pub mod engine;
+pub mod metrics;
+pub mod net;
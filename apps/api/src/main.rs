@@ -5,23 +5,52 @@
 //! Designed for 10K+ concurrent connections.
 
 use actix_web::{middleware, web, App, HttpServer};
+use chrono::{Days, Utc};
+use parking_lot::RwLock;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{error, info, warn};
 use tracing_actix_web::TracingLogger;
 
+use r_image_magic::{domain, engine, metrics, net};
+
 mod api;
 mod config;
 mod db;
-mod domain;
-mod engine;
+mod jobs;
+mod parity;
 mod providers;
+mod shutdown;
 mod storage;
 mod sync;
+mod uploads;
+mod webhooks;
 
-use crate::api::middleware::ApiMiddleware;
-use crate::config::{service_name, Settings};
-use crate::db::{DbPool, TemplateRepository};
-use crate::engine::TemplateManager;
+use crate::api::examples::RequestExamples;
+use crate::api::middleware::{
+    AccessLogPolicy, AccessLogSpanBuilder, ApiKeyCache, ApiMiddleware, AssignRequestId, PenaltyBox,
+    RequestMetrics,
+};
+use crate::config::{
+    check_env_overrides, service_name, BillingSettings, Settings, StorageBackendKind,
+    TemplateSourceKind,
+};
+use crate::db::{DbPool, ResourceRepository, TemplateRepository};
+use crate::engine::{write_starter_templates, EvictionPolicy, TemplateManager};
+use crate::jobs::{JobStore, RenderJobs, JOB_OUTPUT_RETENTION};
+use crate::metrics::Metrics;
+use crate::parity::ParityRunner;
+use crate::providers::LiveCatalog;
+use crate::shutdown::{termination_signal, Shutdown};
+use crate::storage::{
+    object_store_client, CloudinaryUploader, LocalDiskStorage, RenderCache, StorageBackend,
+    TemplateBackup, UsageReports,
+};
+use crate::sync::{
+    any_provider_configured, OnDemandTemplates, ProductTemplates, SyncJobStore, SyncOrchestrator,
+    SyncSchedule, SyncScheduler,
+};
+use crate::uploads::UploadQueue;
+use crate::webhooks::{QuotaAlerter, WebhookDispatcher};
 
 /// Application state shared across all handlers
 pub struct AppState {
@@ -29,6 +58,43 @@ pub struct AppState {
     pub template_manager: Arc<TemplateManager>,
     pub db_pool: Option<DbPool>,
     pub template_repo: Option<TemplateRepository>,
+    pub sync_scheduler: Arc<SyncScheduler>,
+    /// Provider sync jobs, in the database when one is configured
+    pub sync_jobs: Arc<dyn SyncJobStore>,
+    /// Interval-driven provider syncs, running when enabled with a database and credentials
+    pub sync_schedule: Option<Arc<SyncSchedule>>,
+    /// Webhook delivery, available when the database is configured
+    pub webhooks: Option<Arc<WebhookDispatcher>>,
+    /// Provider mockup templates fetched at render time, cached in R2
+    pub on_demand_templates: Arc<OnDemandTemplates>,
+    /// Synced catalog products rendered against their mirrored mockup images
+    pub product_templates: Arc<ProductTemplates>,
+    /// Outputs of multi-result jobs, downloadable as ZIP archives
+    pub jobs: Arc<JobStore>,
+    /// Async generations, also persisted when the database is configured
+    pub render_jobs: Arc<RenderJobs>,
+    /// Hosts generated mockups when Cloudinary credentials are configured
+    pub cloudinary: Option<Arc<CloudinaryUploader>>,
+    /// Stores generated mockups under `generated/` when storage is configured
+    pub storage: Option<Arc<dyn StorageBackend>>,
+    /// Cloudinary and R2 uploads that failed during a request, retried in the background
+    pub uploads: Arc<UploadQueue>,
+    /// Provider mockup parity runs, available when the database is configured
+    pub parity: Option<Arc<ParityRunner>>,
+    /// Provider catalogs read straight from their APIs
+    pub live_catalog: Arc<LiveCatalog>,
+    /// Validated API keys, invalidated by the handlers that change keys
+    pub key_cache: Arc<ApiKeyCache>,
+    /// Billing unit weights, shared with the usage middleware and swapped on reload
+    pub billing: Arc<RwLock<BillingSettings>>,
+    /// Draining flag checked by the health check, and background tasks awaited on exit
+    pub shutdown: Arc<Shutdown>,
+    /// Prometheus registry exported on `/metrics`
+    pub metrics: Arc<Metrics>,
+    /// Encoded mockups of repeated generate requests, dropped when their template reloads
+    pub render_cache: Arc<RenderCache>,
+    /// R2 storage usage by provider and asset type, when R2 is configured
+    pub r2_usage: Option<Arc<UsageReports>>,
 }
 
 #[actix_web::main]
@@ -51,32 +117,162 @@ async fn main() -> std::io::Result<()> {
         .json()
         .init();
 
+    // `--ignore-config-warnings` lets operators start despite hard config errors
+    let ignore_config_errors = std::env::args().any(|arg| arg == "--ignore-config-warnings");
+
     // Load configuration
-    let settings = Settings::load().expect("Failed to load configuration");
-    let bind_addr = format!("{}:{}", settings.server.host, settings.server.port);
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!(error = %e, "Failed to load configuration");
+            check_env_overrides(&|key| std::env::var(key).ok()).log();
+            std::process::exit(1);
+        }
+    };
+
+    // Validate configuration before touching any external resources
+    let config_report = settings.validate();
+    config_report.log();
+    if config_report.has_errors() {
+        if ignore_config_errors {
+            warn!("Starting despite configuration errors (--ignore-config-warnings)");
+        } else {
+            error!("Refusing to start with configuration errors; fix the variables above or pass --ignore-config-warnings");
+            std::process::exit(1);
+        }
+    }
+
+    // Synthetic starter templates need neither R2 nor a database
+    if std::env::args().any(|arg| arg == "bootstrap-templates") {
+        let overwrite = std::env::args().any(|arg| arg == "--force");
+        std::process::exit(run_bootstrap_command(&settings, overwrite));
+    }
+
+    // Retention for mockups stored with `store_in_r2`: prune-generated [days]
+    if let Some(position) = std::env::args().position(|arg| arg == "prune-generated") {
+        let days = std::env::args()
+            .nth(position + 1)
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(DEFAULT_GENERATED_RETENTION_DAYS);
+        std::process::exit(run_prune_command(&settings, days).await);
+    }
+
+    // One-shot template backup commands run instead of the server
+    if let Some(command) = std::env::args().find(|arg| TEMPLATE_COMMANDS.contains(&arg.as_str())) {
+        std::process::exit(run_template_command(&command, &settings).await);
+    }
+
+    let tcp_addresses = settings.server.tcp_addresses();
+    let unix_socket = settings.server.unix_socket.clone();
+    // Read certificates up front so a bad key fails before templates load
+    let tls_config = settings
+        .server
+        .tls
+        .as_ref()
+        .map(|tls| tls.server_config())
+        .transpose()?;
 
     info!(
-        "Starting R-Image-Magic v{} on {}",
-        env!("CARGO_PKG_VERSION"),
-        bind_addr
+        version = env!("CARGO_PKG_VERSION"),
+        addresses = ?tcp_addresses,
+        unix_socket = ?unix_socket,
+        tls = tls_config.is_some(),
+        "Starting R-Image-Magic"
     );
 
-    // Initialize template manager and load templates
+    // Shared by the request middleware, generation, sync, and R2 instrumentation
+    let metrics = Arc::new(Metrics::new());
+
+    // Initialize the R2 or S3 client for asset mirroring if configured
+    let r2_client = match object_store_client(&settings).await {
+        Ok(client) => client.map(|client| client.with_metrics(metrics.clone())),
+        Err(e) => {
+            warn!(
+                "Failed to create R2 client: {}. Sync will skip asset mirroring.",
+                e
+            );
+            None
+        }
+    };
+
+    // Provider assets and generated mockups go to that bucket, or a local directory
+    let storage: Option<Arc<dyn StorageBackend>> = match settings.storage.backend {
+        StorageBackendKind::Local => match LocalDiskStorage::new(&settings.storage.local_path) {
+            Ok(local) => {
+                info!(
+                    path = %settings.storage.local_path.display(),
+                    "Storing assets on local disk"
+                );
+                Some(Arc::new(local.with_public_url_prefix(
+                    settings.storage.local_public_url_prefix.clone(),
+                )))
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to open local storage: {}. Sync will skip asset mirroring.",
+                    e
+                );
+                None
+            }
+        },
+        _ => r2_client
+            .clone()
+            .map(|client| Arc::new(client) as Arc<dyn StorageBackend>),
+    };
+
+    // Initialize template manager and index templates
     let template_manager = Arc::new(
         TemplateManager::new(&settings.templates.path)
             .expect("Failed to initialize template manager"),
     );
 
-    // Load all templates into memory at startup
-    template_manager
+    // Serve the shared template library in R2, cached in the templates directory
+    if settings.templates.source == TemplateSourceKind::R2 {
+        match r2_client {
+            Some(ref client) => template_manager.set_source(Arc::new(TemplateBackup::new(
+                client.clone(),
+                &settings.templates.path,
+            ))),
+            None => warn!(
+                "Templates are sourced from R2, but no R2 client is available. Serving the local copy."
+            ),
+        }
+    }
+
+    // Index template metadata at startup; images are decoded on first use
+    let loaded = template_manager
         .load_all()
         .await
         .expect("Failed to load templates");
-    info!("Loaded {} templates", template_manager.template_count());
+    info!(
+        failed = loaded.failed.len(),
+        with_warnings = template_manager.load_report().warnings.len(),
+        "Indexed {} templates",
+        template_manager.template_count()
+    );
+
+    // Drop decoded images of idle or least recently used templates; metadata stays indexed
+    template_manager.set_eviction_policy(EvictionPolicy {
+        idle_timeout: settings.templates.idle_timeout(),
+        max_resident_templates: settings.templates.resident_template_limit(),
+        max_resident_bytes: settings.templates.resident_bytes_limit(),
+    });
+    template_manager.spawn_eviction_task(std::time::Duration::from_secs(
+        settings.templates.eviction_interval_secs.max(1),
+    ));
+
+    // Bound in-flight generations so a burst can't exhaust memory
+    template_manager.set_generation_limit(
+        settings.server.generation_limit(),
+        settings.server.generation_wait(),
+    );
+    template_manager.set_url_policy(settings.server.url_policy());
+    template_manager.set_metrics(metrics.clone());
 
     // Initialize database connection if DATABASE_URL is configured
     let (db_pool, template_repo) = if !settings.database.url.is_empty() {
-        match DbPool::new(&settings.database.url) {
+        let options = settings.database.pool_options();
+        match DbPool::with_options(&settings.database.url, &options) {
             Ok(pool) => {
                 // Test the connection
                 if let Err(e) = pool.test_connection().await {
@@ -86,6 +282,9 @@ async fn main() -> std::io::Result<()> {
                     );
                     (None, None)
                 } else {
+                    if let Err(e) = pool.warm_up(options.min_idle).await {
+                        tracing::warn!(error = %e, "Failed to open idle database connections");
+                    }
                     let repo = TemplateRepository::new(pool.clone());
                     info!("Database pool initialized successfully");
                     (Some(pool), Some(repo))
@@ -104,9 +303,100 @@ async fn main() -> std::io::Result<()> {
         (None, None)
     };
 
+    // Webhook subscriptions and delivery logs live in the database
+    let webhooks = db_pool
+        .clone()
+        .map(|pool| Arc::new(WebhookDispatcher::new(pool)));
+
+    // Unsynced products render against provider templates cached in R2
+    let on_demand_templates = Arc::new(OnDemandTemplates::new(r2_client.clone()));
+    // Hidden, so template indexing and the R2 template library skip it
+    let product_templates = Arc::new(ProductTemplates::new(
+        db_pool.clone(),
+        r2_client.clone(),
+        settings.templates.path.join(".catalog-cache"),
+    ));
+    let jobs = Arc::new(JobStore::new(storage.clone(), JOB_OUTPUT_RETENTION));
+    let render_jobs = Arc::new(RenderJobs::new(
+        db_pool.clone(),
+        settings.server.render_job_retention(),
+    ));
+    render_jobs.spawn_cleanup_task(std::time::Duration::from_secs(60));
+    let cloudinary = CloudinaryUploader::from_settings(&settings.cloudinary).map(Arc::new);
+    let uploads = Arc::new(UploadQueue::new(cloudinary.clone(), storage.clone()));
+    uploads.spawn_retry_task(std::time::Duration::from_secs(5));
+    // Repeated generate requests are answered from memory, then R2 when enabled
+    let render_cache = Arc::new(
+        RenderCache::from_settings(&settings.render_cache, r2_client.clone())
+            .with_metrics(metrics.clone()),
+    );
+    // Bucket walks for GET /sync/r2/usage, kept for the configured TTL
+    let r2_usage = r2_client.clone().map(|client| {
+        Arc::new(UsageReports::new(client, settings.sync.usage_report_ttl()))
+    });
+
+    // Parity results are stored in the database
+    let parity = db_pool.clone().map(|pool| {
+        Arc::new(ParityRunner::new(
+            pool,
+            template_manager.clone(),
+            on_demand_templates.clone(),
+        ))
+    });
+
+    // Usage logging and sync runs are awaited before the process exits
+    let shutdown = Arc::new(Shutdown::default());
+
+    // Sync scheduler shares provider and asset limits across all sync runs
+    let orchestrator = SyncOrchestrator::new(db_pool.clone(), storage.clone())
+        .with_asset_limit(settings.sync.max_concurrent_assets)
+        .with_asset_attempts(settings.sync.max_asset_attempts)
+        .with_asset_dedup(settings.sync.dedup_assets)
+        .with_asset_thumbnails(
+            settings.sync.thumbnails,
+            settings.sync.thumbnail_max_dimension,
+        )
+        .with_url_policy(settings.server.url_policy())
+        .with_metrics(metrics.clone());
+    let sync_jobs = orchestrator.job_store();
+    let sync_scheduler = Arc::new(
+        SyncScheduler::new(
+            Arc::new(orchestrator),
+            settings.sync.max_concurrent_providers,
+        )
+        .with_webhooks(webhooks.clone())
+        .with_shutdown(shutdown.clone()),
+    );
+
+    // Providers are synced again once their sync_interval_hours have passed
+    let sync_schedule = match db_pool.clone() {
+        Some(pool) if settings.sync.scheduler_enabled && any_provider_configured() => {
+            let schedule = Arc::new(SyncSchedule::new(
+                pool,
+                sync_scheduler.clone(),
+                settings.sync.scheduler_tick(),
+            ));
+            schedule.spawn();
+            info!(
+                "Sync scheduler checking providers every {}s",
+                settings.sync.scheduler_tick_secs
+            );
+            Some(schedule)
+        }
+        _ => None,
+    };
+
+    // Swagger examples name templates loaded at startup
+    let request_examples = Arc::new(RequestExamples::from_templates(&template_manager));
+
     // Clone pool for middleware and handlers (before moving into AppState)
     let middleware_pool = db_pool.clone();
     let pool_data = db_pool.clone().map(web::Data::new);
+    let key_cache = Arc::new(ApiKeyCache::default());
+    let penalty_box = Arc::new(PenaltyBox::default());
+    let billing = Arc::new(RwLock::new(settings.billing.clone()));
+    let quota_alerter = Arc::new(QuotaAlerter::default());
+    let generations = template_manager.clone();
 
     // Create shared application state
     let app_state = web::Data::new(AppState {
@@ -114,10 +404,33 @@ async fn main() -> std::io::Result<()> {
         template_manager,
         db_pool,
         template_repo,
+        sync_scheduler,
+        sync_jobs,
+        sync_schedule,
+        webhooks,
+        on_demand_templates,
+        product_templates,
+        jobs,
+        render_jobs,
+        cloudinary,
+        storage,
+        uploads,
+        parity,
+        live_catalog: Arc::new(LiveCatalog::new()),
+        key_cache: key_cache.clone(),
+        billing: billing.clone(),
+        shutdown: shutdown.clone(),
+        metrics: metrics.clone(),
+        render_cache,
+        r2_usage,
     });
 
+    // Access log exclusions and sampling apply to every worker
+    AccessLogSpanBuilder::install(AccessLogPolicy::from_settings(&settings.access_log));
+
     // Configure and start HTTP server
-    HttpServer::new(move || {
+    let middleware_shutdown = shutdown.clone();
+    let mut server = HttpServer::new(move || {
         let header_service_name = service_name();
         let mut app = App::new().app_data(app_state.clone());
 
@@ -129,9 +442,21 @@ async fn main() -> std::io::Result<()> {
         app
             // API middleware for auth, rate limiting, usage tracking
             // (handles missing DB gracefully by skipping auth)
-            .wrap(ApiMiddleware::new(middleware_pool.clone()))
+            .wrap(
+                ApiMiddleware::new(middleware_pool.clone())
+                    .with_key_cache(key_cache.clone())
+                    .with_penalty_box(penalty_box.clone())
+                    .with_billing(billing.clone())
+                    .with_quota_alerter(quota_alerter.clone())
+                    .with_shutdown(middleware_shutdown.clone())
+                    .with_metrics(metrics.clone()),
+            )
+            // Outside ApiMiddleware, so requests it rejects are counted too
+            .wrap(RequestMetrics::new(metrics.clone()))
             // Middleware (order matters - these wrap around ApiMiddleware)
-            .wrap(TracingLogger::default())
+            .wrap(TracingLogger::<AccessLogSpanBuilder>::new())
+            // Outside TracingLogger, so the access log span carries the ID
+            .wrap(AssignRequestId)
             .wrap(middleware::Compress::default())
             .wrap(
                 middleware::DefaultHeaders::new()
@@ -139,10 +464,203 @@ async fn main() -> std::io::Result<()> {
                     .add(("X-Version", env!("CARGO_PKG_VERSION"))),
             )
             // Routes
-            .configure(api::configure_routes)
+            .configure(|cfg| api::configure_routes(cfg, &request_examples))
     })
-    .workers(num_cpus::get() * 2) // 2 workers per CPU for async I/O
-    .bind(&bind_addr)?
-    .run()
-    .await
+    .workers(settings.server.worker_count())
+    .shutdown_timeout(settings.server.shutdown_timeout_secs)
+    // Signals are handled below so the instance drains before the listeners close
+    .disable_signals();
+
+    for addr in &tcp_addresses {
+        server = match tls_config {
+            Some(ref config) => server.bind_rustls_0_23(addr, config.clone())?,
+            None => server.bind(addr)?,
+        };
+    }
+    #[cfg(unix)]
+    if let Some(ref path) = unix_socket {
+        use std::os::unix::fs::FileTypeExt;
+
+        // A socket left behind by an unclean shutdown would block the bind
+        if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+        server = server.bind_uds(path)?;
+    }
+
+    let server = server.run();
+    let handle = server.handle();
+    let drain_delay = settings.server.drain_delay();
+    let drain = shutdown.clone();
+    actix_web::rt::spawn(async move {
+        termination_signal().await;
+        info!("Shutdown signal received");
+        generations.stop_generations();
+        drain.drain_and_stop(handle, drain_delay).await;
+    });
+
+    server.await?;
+
+    if !shutdown
+        .wait_for_tasks(settings.server.shutdown_timeout())
+        .await
+    {
+        warn!(
+            tasks = shutdown.tasks_running(),
+            "Background tasks still running at shutdown"
+        );
+    }
+    info!("Shutdown complete");
+    Ok(())
+}
+
+/// Write the starter templates and return the process exit code
+fn run_bootstrap_command(settings: &Settings, overwrite: bool) -> i32 {
+    match write_starter_templates(&settings.templates.path, overwrite) {
+        Ok(summary) => {
+            info!(
+                created = summary.created.len(),
+                skipped = summary.skipped.len(),
+                path = %settings.templates.path.display(),
+                "bootstrap-templates finished"
+            );
+            if !summary.skipped.is_empty() {
+                warn!(
+                    skipped = ?summary.skipped,
+                    "Existing templates were left untouched; pass --force to overwrite them"
+                );
+            }
+            0
+        }
+        Err(e) => {
+            error!(error = %e, "bootstrap-templates failed");
+            1
+        }
+    }
+}
+
+/// Days generated mockups are kept when `prune-generated` is given no count
+const DEFAULT_GENERATED_RETENTION_DAYS: u32 = 30;
+
+/// Delete generated mockups older than `days` and return the process exit code
+async fn run_prune_command(settings: &Settings, days: u32) -> i32 {
+    let client = match object_store_client(settings).await {
+        Ok(Some(client)) => client,
+        Ok(None) => {
+            error!("prune-generated requires an R2 or S3 bucket to be configured");
+            return 1;
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to create R2 client");
+            return 1;
+        }
+    };
+
+    match client.prune_generated(days).await {
+        Ok(deleted) => {
+            info!(deleted, days, "prune-generated finished");
+            forget_pruned_renders(settings, days).await;
+            0
+        }
+        Err(e) => {
+            error!(error = %e, "prune-generated failed");
+            1
+        }
+    }
+}
+
+/// Stop counting pruned mockups toward their keys' saved render limits
+async fn forget_pruned_renders(settings: &Settings, days: u32) {
+    if settings.database.url.is_empty() {
+        return;
+    }
+    let cutoff = (Utc::now().date_naive() - Days::new(days as u64))
+        .and_hms_opt(0, 0, 0)
+        .map(|midnight| midnight.and_utc());
+    let pool = DbPool::with_options(&settings.database.url, &settings.database.pool_options());
+    let (Some(cutoff), Ok(pool)) = (cutoff, pool) else {
+        warn!("Could not update saved render counts after pruning");
+        return;
+    };
+    match ResourceRepository::new(pool)
+        .delete_renders_before(cutoff)
+        .await
+    {
+        Ok(forgotten) => info!(forgotten, "Removed pruned mockups from saved render counts"),
+        Err(e) => warn!(error = %e, "Failed to update saved render counts after pruning"),
+    }
+}
+
+/// CLI subcommands for mirroring the templates directory to R2
+const TEMPLATE_COMMANDS: [&str; 3] = ["backup-templates", "restore-templates", "template-drift"];
+
+/// Run a template backup command and return the process exit code
+async fn run_template_command(command: &str, settings: &Settings) -> i32 {
+    let client = match object_store_client(settings).await {
+        Ok(Some(client)) => client,
+        Ok(None) => {
+            error!("{} requires an R2 or S3 bucket to be configured", command);
+            return 1;
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to create R2 client");
+            return 1;
+        }
+    };
+    let backup = TemplateBackup::new(client, &settings.templates.path);
+
+    match command {
+        "backup-templates" | "restore-templates" => {
+            let result = if command == "backup-templates" {
+                backup.backup().await
+            } else {
+                backup.restore().await
+            };
+            match result {
+                Ok(summary) if summary.failed.is_empty() => {
+                    info!(
+                        transferred = summary.transferred,
+                        unchanged = summary.unchanged,
+                        bytes = summary.bytes,
+                        "{} finished",
+                        command
+                    );
+                    0
+                }
+                Ok(summary) => {
+                    error!(
+                        failed = summary.failed.len(),
+                        "{} finished with failures", command
+                    );
+                    1
+                }
+                Err(e) => {
+                    error!(error = %e, "{} failed", command);
+                    1
+                }
+            }
+        }
+        _ => match backup.drift().await {
+            Ok(drift) => {
+                info!(
+                    in_sync = drift.in_sync,
+                    missing_remote = drift.missing_remote.len(),
+                    missing_local = drift.missing_local.len(),
+                    changed = drift.changed.len(),
+                    missing_objects = drift.missing_objects.len(),
+                    size_mismatch = drift.size_mismatch.len(),
+                    "Template drift check finished"
+                );
+                if drift.has_drift() {
+                    2
+                } else {
+                    0
+                }
+            }
+            Err(e) => {
+                error!(error = %e, "Template drift check failed");
+                1
+            }
+        },
+    }
 }
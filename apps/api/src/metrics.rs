@@ -0,0 +1,297 @@
+//! Prometheus instrumentation
+//!
+//! One registry is shared through `AppState` and handed to the components
+//! that record into it: the request metrics middleware, the template manager,
+//! the sync orchestrator and its asset syncers, the R2 client, and the render
+//! cache. Gauges that are cheap to read on demand, like template cache
+//! residency, are still written by the `/metrics` handler itself.
+
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts,
+    Registry, TextEncoder,
+};
+use std::time::Duration;
+
+/// Prefix shared by every exported metric
+pub const METRIC_PREFIX: &str = "r_image_magic";
+
+/// Route label for requests that matched no route, so probing stray paths
+/// doesn't add a series per path
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Stages of a mockup generation, timed separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationStage {
+    /// Downloading and decoding designs
+    Fetch,
+    /// Warping designs along the displacement map
+    Displacement,
+    /// Resizing, blending, and masking designs onto the template
+    Composite,
+    /// Encoding the output image
+    Encode,
+}
+
+impl GenerationStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GenerationStage::Fetch => "fetch",
+            GenerationStage::Displacement => "displacement",
+            GenerationStage::Composite => "composite",
+            GenerationStage::Encode => "encode",
+        }
+    }
+}
+
+/// Metrics recorded across the service
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    generation_duration: HistogramVec,
+    generation_stage_duration: HistogramVec,
+    template_cache: IntCounterVec,
+    render_cache: IntCounterVec,
+    rate_limit_rejections: IntCounter,
+    sync_products: IntCounterVec,
+    asset_stage_duration: HistogramVec,
+    r2_bytes: IntCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some(METRIC_PREFIX.to_string()), None)
+            .expect("metric prefix is valid");
+
+        // 1ms to ~65s, covering fast lookups through slow batch generations
+        let seconds = exponential_buckets(0.001, 2.0, 17).expect("bucket layout is valid");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .expect("metric is valid");
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route",
+            )
+            .buckets(seconds.clone()),
+            &["method", "route"],
+        )
+        .expect("metric is valid");
+        let generation_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "generation_duration_seconds",
+                "Mockup generation time by template, excluding the wait for a slot",
+            )
+            .buckets(seconds.clone()),
+            &["template_id"],
+        )
+        .expect("metric is valid");
+        let generation_stage_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "generation_stage_duration_seconds",
+                "Mockup generation time by template and pipeline stage",
+            )
+            .buckets(seconds.clone()),
+            &["template_id", "stage"],
+        )
+        .expect("metric is valid");
+        let template_cache = IntCounterVec::new(
+            Opts::new(
+                "template_cache_requests_total",
+                "Template image lookups served from memory (hit) or decoded from disk (miss)",
+            ),
+            &["result"],
+        )
+        .expect("metric is valid");
+        let render_cache = IntCounterVec::new(
+            Opts::new(
+                "render_cache_requests_total",
+                "Render cache lookups by result and the tier that answered (none for misses)",
+            ),
+            &["result", "tier"],
+        )
+        .expect("metric is valid");
+        let rate_limit_rejections = IntCounter::new(
+            "rate_limit_rejections_total",
+            "Requests rejected for exceeding their API key's rate limit",
+        )
+        .expect("metric is valid");
+        let sync_products = IntCounterVec::new(
+            Opts::new(
+                "sync_products_total",
+                "Products handled by provider syncs, by outcome",
+            ),
+            &["provider", "outcome"],
+        )
+        .expect("metric is valid");
+        let asset_stage_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "asset_sync_stage_duration_seconds",
+                "Asset mirroring time by provider and stage",
+            )
+            .buckets(seconds),
+            &["provider", "stage"],
+        )
+        .expect("metric is valid");
+        let r2_bytes = IntCounterVec::new(
+            Opts::new("r2_bytes_total", "Bytes uploaded to and downloaded from R2"),
+            &["direction"],
+        )
+        .expect("metric is valid");
+
+        for metric in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_duration.clone()),
+            Box::new(generation_duration.clone()),
+            Box::new(generation_stage_duration.clone()),
+            Box::new(template_cache.clone()),
+            Box::new(render_cache.clone()),
+            Box::new(rate_limit_rejections.clone()),
+            Box::new(sync_products.clone()),
+            Box::new(asset_stage_duration.clone()),
+            Box::new(r2_bytes.clone()),
+        ] {
+            registry.register(metric).expect("metric names are unique");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            generation_duration,
+            generation_stage_duration,
+            template_cache,
+            render_cache,
+            rate_limit_rejections,
+            sync_products,
+            asset_stage_duration,
+            r2_bytes,
+        }
+    }
+
+    /// Record a finished HTTP request under its route pattern
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Record a successful generation and the time spent in each stage
+    pub fn observe_generation(&self, template_id: &str, stages: &[(GenerationStage, Duration)]) {
+        let total: Duration = stages.iter().map(|(_, elapsed)| *elapsed).sum();
+        self.generation_duration
+            .with_label_values(&[template_id])
+            .observe(total.as_secs_f64());
+        for (stage, elapsed) in stages {
+            self.generation_stage_duration
+                .with_label_values(&[template_id, stage.as_str()])
+                .observe(elapsed.as_secs_f64());
+        }
+    }
+
+    /// Record whether a template's images were already decoded in memory
+    pub fn record_template_cache(&self, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.template_cache.with_label_values(&[result]).inc();
+    }
+
+    /// Record a render cache lookup, answered by `tier` or missed everywhere
+    pub fn record_render_cache(&self, tier: Option<&str>) {
+        let (result, tier) = match tier {
+            Some(tier) => ("hit", tier),
+            None => ("miss", "none"),
+        };
+        self.render_cache.with_label_values(&[result, tier]).inc();
+    }
+
+    pub fn record_rate_limited(&self) {
+        self.rate_limit_rejections.inc();
+    }
+
+    /// Record a product synced, skipped as unchanged, or failed by a provider sync
+    pub fn record_sync_product(&self, provider: &str, outcome: &str) {
+        self.sync_products
+            .with_label_values(&[provider, outcome])
+            .inc();
+    }
+
+    /// Record how long one stage of mirroring an asset took
+    pub fn observe_asset_stage(&self, provider: &str, stage: &str, elapsed: Duration) {
+        self.asset_stage_duration
+            .with_label_values(&[provider, stage])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_r2_upload(&self, bytes: u64) {
+        self.r2_bytes.with_label_values(&["upload"]).inc_by(bytes);
+    }
+
+    pub fn record_r2_download(&self, bytes: u64) {
+        self.r2_bytes.with_label_values(&["download"]).inc_by(bytes);
+    }
+
+    /// The registry's metrics in Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::warn!(error = %e, "Failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_labeled_series() {
+        let metrics = Metrics::new();
+        metrics.observe_request(
+            "GET",
+            "/api/v1/templates/{id}",
+            200,
+            Duration::from_millis(3),
+        );
+        metrics.observe_generation(
+            "tshirt-front",
+            &[
+                (GenerationStage::Fetch, Duration::from_millis(40)),
+                (GenerationStage::Encode, Duration::from_millis(10)),
+            ],
+        );
+        metrics.record_r2_upload(2048);
+        metrics.record_render_cache(Some("memory"));
+        metrics.record_render_cache(None);
+        metrics.record_r2_upload(1024);
+
+        let body = metrics.render();
+        assert!(body.contains(
+            r#"r_image_magic_http_requests_total{method="GET",route="/api/v1/templates/{id}",status="200"} 1"#
+        ));
+        assert!(body.contains(
+            r#"r_image_magic_generation_stage_duration_seconds_count{stage="fetch",template_id="tshirt-front"} 1"#
+        ));
+        assert!(body.contains(
+            r#"r_image_magic_generation_duration_seconds_sum{template_id="tshirt-front"} 0.05"#
+        ));
+        assert!(body.contains(r#"r_image_magic_r2_bytes_total{direction="upload"} 3072"#));
+        assert!(body.contains(
+            r#"r_image_magic_render_cache_requests_total{result="hit",tier="memory"} 1"#
+        ));
+        assert!(body
+            .contains(r#"r_image_magic_render_cache_requests_total{result="miss",tier="none"} 1"#));
+    }
+}
@@ -0,0 +1,634 @@
+//! SSRF protection for fetches of user-supplied URLs
+//!
+//! A URL is checked three times: its scheme, host, and any IP literal before
+//! the request is made; every address its host name resolves to, by the HTTP
+//! client's own resolver, so a name can't be rebound to an internal address
+//! between the check and the connect; and each redirect hop, which goes
+//! through the same checks before it is followed.
+
+use async_trait::async_trait;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use thiserror::Error;
+use url::{Host, Url};
+
+/// Redirects followed before a fetch is refused
+const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+tokio::task_local! {
+    /// Domains the fetch running in [`UrlGuard::within_domains`] may redirect to
+    static FETCH_DOMAINS: Vec<String>;
+}
+
+/// Why a URL may not be fetched
+#[derive(Debug, Clone, Error)]
+pub enum UrlGuardError {
+    /// Not an absolute URL with a host
+    #[error("{0}")]
+    Invalid(String),
+    /// Well-formed, but pointing somewhere fetches may not go
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("Failed to resolve {host}: {message}")]
+    Resolve { host: String, message: String },
+    /// A redirect hop left the domains the fetch was limited to
+    #[error("{0}")]
+    OutsideDomains(String),
+}
+
+impl UrlGuardError {
+    /// The guard's error behind a failed request, if the guard stopped it
+    pub fn from_reqwest(error: &reqwest::Error) -> Option<&UrlGuardError> {
+        let mut source = std::error::Error::source(error);
+        while let Some(error) = source {
+            if let Some(guard) = error.downcast_ref::<UrlGuardError>() {
+                return Some(guard);
+            }
+            source = error.source();
+        }
+        None
+    }
+}
+
+/// Where fetches may go, beyond the always-blocked internal ranges
+#[derive(Debug, Clone)]
+pub struct UrlPolicy {
+    /// Host names fetches are limited to, subdomains included; empty allows any public host
+    pub allowed_hosts: Vec<String>,
+    /// Redirects followed before giving up
+    pub max_redirects: usize,
+}
+
+impl Default for UrlPolicy {
+    fn default() -> Self {
+        UrlPolicy {
+            allowed_hosts: Vec::new(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+        }
+    }
+}
+
+impl UrlPolicy {
+    /// Parse `url` and check it without resolving its host
+    ///
+    /// Names that resolve to internal addresses pass; the guarded client
+    /// refuses them when it connects.
+    pub fn check(&self, url: &str) -> Result<Url, UrlGuardError> {
+        let parsed = Url::parse(url)
+            .map_err(|_| UrlGuardError::Invalid("must be an absolute URL".to_string()))?;
+        self.check_url(&parsed)?;
+        Ok(parsed)
+    }
+
+    /// Check the scheme, host, and any IP literal, without resolving names
+    fn check_url(&self, url: &Url) -> Result<(), UrlGuardError> {
+        match url.scheme() {
+            "http" | "https" => {}
+            scheme => {
+                return Err(UrlGuardError::Forbidden(format!(
+                    "scheme '{}' is not supported",
+                    scheme
+                )));
+            }
+        }
+
+        let host = url
+            .host()
+            .ok_or_else(|| UrlGuardError::Invalid("host is required".to_string()))?;
+        if is_blocked_host(&host) {
+            return Err(UrlGuardError::Forbidden(
+                "local and private network hosts are not allowed".to_string(),
+            ));
+        }
+        if !self.allows_host(&host.to_string()) {
+            return Err(UrlGuardError::Forbidden(format!(
+                "host '{}' is not in the allowed fetch hosts",
+                host
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the allowlist admits `host`; an empty allowlist admits every host
+    fn allows_host(&self, host: &str) -> bool {
+        if self.allowed_hosts.is_empty() {
+            return true;
+        }
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| host_in_domain(host, allowed))
+    }
+}
+
+/// Whether `host` is `domain` or one of its subdomains, ignoring case and a trailing dot
+fn host_in_domain(host: &str, domain: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+}
+
+/// Whether `url` is hosted on one of `domains`, subdomains included
+///
+/// An empty list admits every URL. IP literals never match, since a domain
+/// list can't vouch for an address, and neither does a URL that doesn't parse.
+pub fn url_in_domains(url: &str, domains: &[String]) -> bool {
+    if domains.is_empty() {
+        return true;
+    }
+    match Url::parse(url).ok().as_ref().and_then(Url::host) {
+        Some(Host::Domain(host)) => domains.iter().any(|domain| host_in_domain(host, domain)),
+        Some(Host::Ipv4(_) | Host::Ipv6(_)) | None => false,
+    }
+}
+
+/// Resolves host names to addresses
+#[async_trait]
+pub trait HostResolver: Send + Sync {
+    async fn lookup(&self, host: &str) -> std::io::Result<Vec<IpAddr>>;
+}
+
+/// The operating system's resolver
+pub struct SystemResolver;
+
+#[async_trait]
+impl HostResolver for SystemResolver {
+    async fn lookup(&self, host: &str) -> std::io::Result<Vec<IpAddr>> {
+        let addrs = tokio::net::lookup_host((host, 0)).await?;
+        Ok(addrs.map(|addr| addr.ip()).collect())
+    }
+}
+
+/// Resolve `host`, failing if any of its addresses is blocked
+async fn resolve_public(
+    resolver: &dyn HostResolver,
+    host: &str,
+) -> Result<Vec<IpAddr>, UrlGuardError> {
+    let ips = resolver
+        .lookup(host)
+        .await
+        .map_err(|e| UrlGuardError::Resolve {
+            host: host.to_string(),
+            message: e.to_string(),
+        })?;
+    // One internal address is enough to refuse: the client may pick any of them
+    if let Some(ip) = ips.iter().find(|ip| is_blocked_ip(**ip)) {
+        return Err(UrlGuardError::Forbidden(format!(
+            "host '{}' resolves to the non-public address {}",
+            host, ip
+        )));
+    }
+    Ok(ips)
+}
+
+/// reqwest resolver refusing names that resolve to internal addresses
+struct GuardedResolver(Arc<dyn HostResolver>);
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve_addrs(self.0.clone(), name))
+    }
+}
+
+async fn resolve_addrs(
+    resolver: Arc<dyn HostResolver>,
+    name: Name,
+) -> Result<Addrs, Box<dyn std::error::Error + Send + Sync>> {
+    let ips = resolve_public(resolver.as_ref(), name.as_str()).await?;
+    // reqwest fills in the port
+    Ok(Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0))))
+}
+
+/// An HTTP client for user-supplied URLs, with the checks to run before using it
+pub struct UrlGuard {
+    policy: UrlPolicy,
+    resolver: Arc<dyn HostResolver>,
+    client: reqwest::Client,
+}
+
+impl UrlGuard {
+    /// Build the client from `builder`, using the system resolver
+    pub fn new(policy: UrlPolicy, builder: reqwest::ClientBuilder) -> reqwest::Result<Self> {
+        Self::with_resolver(policy, builder, Arc::new(SystemResolver))
+    }
+
+    /// Build the client from `builder`, resolving names with `resolver`
+    pub fn with_resolver(
+        policy: UrlPolicy,
+        builder: reqwest::ClientBuilder,
+        resolver: Arc<dyn HostResolver>,
+    ) -> reqwest::Result<Self> {
+        let redirects = policy.clone();
+        let client = builder
+            .dns_resolver(Arc::new(GuardedResolver(resolver.clone())))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                // `previous` holds the original URL and every hop followed so far
+                if attempt.previous().len() > redirects.max_redirects {
+                    let error = UrlGuardError::Forbidden(format!(
+                        "more than {} redirects",
+                        redirects.max_redirects
+                    ));
+                    return attempt.error(error);
+                }
+                if let Err(e) = redirects.check_url(attempt.url()) {
+                    return attempt.error(e);
+                }
+                // Redirects are followed while the request future is polled,
+                // inside the scope `within_domains` set up
+                let outside = FETCH_DOMAINS
+                    .try_with(|domains| !url_in_domains(attempt.url().as_str(), domains))
+                    .unwrap_or(false);
+                if outside {
+                    let error = UrlGuardError::OutsideDomains(format!(
+                        "redirect to {} leaves the allowed design domains",
+                        attempt.url()
+                    ));
+                    return attempt.error(error);
+                }
+                attempt.follow()
+            }))
+            .build()?;
+        Ok(UrlGuard {
+            policy,
+            resolver,
+            client,
+        })
+    }
+
+    /// Client that re-checks resolved addresses and redirect hops
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Parse `url` and check it without resolving its host
+    pub fn check_url(&self, url: &str) -> Result<Url, UrlGuardError> {
+        self.policy.check(url)
+    }
+
+    /// Run `fetch`, a request made with [`client`](Self::client), refusing
+    /// redirects to hosts outside `domains`; an empty list allows any host
+    ///
+    /// The request URL itself is not checked; use [`url_in_domains`] first.
+    pub async fn within_domains<F: std::future::Future>(
+        &self,
+        domains: Vec<String>,
+        fetch: F,
+    ) -> F::Output {
+        FETCH_DOMAINS.scope(domains, fetch).await
+    }
+
+    /// Parse and check `url`, resolving a host name to make sure it is public
+    pub async fn check(&self, url: &str) -> Result<Url, UrlGuardError> {
+        let parsed = self.check_url(url)?;
+        if let Some(Host::Domain(domain)) = parsed.host() {
+            resolve_public(self.resolver.as_ref(), domain).await?;
+        }
+        Ok(parsed)
+    }
+}
+
+fn is_blocked_host(host: &Host<&str>) -> bool {
+    match host {
+        Host::Domain(domain) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Host::Ipv4(ip) => is_blocked_ip(IpAddr::V4(*ip)),
+        Host::Ipv6(ip) => is_blocked_ip(IpAddr::V6(*ip)),
+    }
+}
+
+fn is_blocked_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // 0.0.0.0/8, "this network"
+                || a == 0
+                // 100.64.0.0/10, carrier-grade NAT
+                || (a == 100 && (b & 0xc0) == 64)
+                // 198.18.0.0/15, benchmarking
+                || (a == 198 && (b & 0xfe) == 18)
+                // 240.0.0.0/4, reserved, broadcast included
+                || a >= 240
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(v4) => is_blocked_ip(IpAddr::V4(v4)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_multicast()
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 address carries and routes to, if any
+///
+/// Covers IPv4-mapped `::ffff:a.b.c.d`, IPv4-compatible `::a.b.c.d`, NAT64
+/// `64:ff9b::a.b.c.d`, and 6to4 `2002:aabb:ccdd::`.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let last_four = || {
+        let [.., a, b, c, d] = ip.octets();
+        Ipv4Addr::new(a, b, c, d)
+    };
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, ..] => Some(last_four()),
+        // `::` and `::1` are left to the IPv6 checks
+        [0, 0, 0, 0, 0, 0, ..] if !ip.is_unspecified() && !ip.is_loopback() => Some(last_four()),
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(last_four()),
+        [0x2002, high, low16, ..] => {
+            let [a, b] = high.to_be_bytes();
+            let [c, d] = low16.to_be_bytes();
+            Some(Ipv4Addr::new(a, b, c, d))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Resolves names from a fixed table
+    struct StaticResolver(HashMap<&'static str, IpAddr>);
+
+    #[async_trait]
+    impl HostResolver for StaticResolver {
+        async fn lookup(&self, host: &str) -> std::io::Result<Vec<IpAddr>> {
+            self.0
+                .get(host)
+                .map(|ip| vec![*ip])
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "unknown host"))
+        }
+    }
+
+    fn guard(policy: UrlPolicy) -> UrlGuard {
+        let resolver = StaticResolver(HashMap::from([
+            ("cdn.example.com", "93.184.216.34".parse().unwrap()),
+            ("internal.test", "127.0.0.1".parse().unwrap()),
+            ("metadata.test", "169.254.169.254".parse().unwrap()),
+        ]));
+        UrlGuard::with_resolver(policy, reqwest::Client::builder(), Arc::new(resolver)).unwrap()
+    }
+
+    /// Address of a server that redirects every request to `location`
+    async fn redirecting_server(location: String) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    location
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_check_url_allows_public_http_urls() {
+        let guard = guard(UrlPolicy::default());
+        assert!(guard
+            .check_url("https://cdn.example.com/design.png")
+            .is_ok());
+        assert!(guard.check_url("http://203.0.114.10/design.png").is_ok());
+        assert!(guard
+            .check_url("http://[2606:4700::1111]/design.png")
+            .is_ok());
+        // 6to4 and NAT64 addresses of a public IPv4 address
+        assert!(guard
+            .check_url("http://[2002:5db8:d822::]/design.png")
+            .is_ok());
+        assert!(guard
+            .check_url("http://[64:ff9b::5db8:d822]/design.png")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_check_url_rejects_non_http_urls() {
+        let guard = guard(UrlPolicy::default());
+        assert!(guard.check_url("file:///tmp/design.png").is_err());
+        assert!(guard.check_url("data:image/png;base64,abc").is_err());
+        assert!(guard.check_url("/samples/design.png").is_err());
+    }
+
+    #[test]
+    fn test_check_url_rejects_ip_literals_of_internal_ranges() {
+        let guard = guard(UrlPolicy::default());
+        for url in [
+            "http://localhost/design.png",
+            "http://api.localhost/design.png",
+            "http://127.0.0.1/design.png",
+            "http://10.1.2.3/design.png",
+            "http://172.16.0.1/design.png",
+            "http://192.168.0.1/design.png",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://100.64.0.1/design.png",
+            "http://0.0.0.0/design.png",
+            "http://[::1]/design.png",
+            "http://[fc00::1]/design.png",
+            "http://[fe80::1]/design.png",
+            "http://[::ffff:127.0.0.1]/design.png",
+            "http://0.1.2.3/design.png",
+            "http://198.18.0.1/design.png",
+            "http://198.19.255.254/design.png",
+            "http://240.0.0.1/design.png",
+            "http://255.255.255.255/design.png",
+            "http://224.0.0.1/design.png",
+            "http://[ff02::1]/design.png",
+            "http://[64:ff9b::a9fe:a9fe]/latest/meta-data/",
+            "http://[2002:7f00:1::]/design.png",
+            "http://[::10.0.0.1]/design.png",
+        ] {
+            assert!(
+                matches!(guard.check_url(url), Err(UrlGuardError::Forbidden(_))),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_allowlist_limits_hosts() {
+        let guard = guard(UrlPolicy {
+            allowed_hosts: vec!["example.com".to_string()],
+            ..UrlPolicy::default()
+        });
+        assert!(guard.check_url("https://example.com/design.png").is_ok());
+        assert!(guard
+            .check_url("https://cdn.example.com/design.png")
+            .is_ok());
+        assert!(matches!(
+            guard.check_url("https://example.com.evil.net/design.png"),
+            Err(UrlGuardError::Forbidden(_))
+        ));
+        assert!(matches!(
+            guard.check_url("https://notexample.com/design.png"),
+            Err(UrlGuardError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn test_url_in_domains() {
+        let domains = vec![
+            "cdn.acme.com".to_string(),
+            "Images.Example.org.".to_string(),
+        ];
+        // Exact domain, subdomains, and any case
+        assert!(url_in_domains("https://cdn.acme.com/a.png", &domains));
+        assert!(url_in_domains("https://eu.cdn.acme.com/a.png", &domains));
+        assert!(url_in_domains("https://CDN.Acme.COM/a.png", &domains));
+        assert!(url_in_domains(
+            "https://images.example.org./a.png",
+            &domains
+        ));
+
+        assert!(!url_in_domains("https://acme.com/a.png", &domains));
+        assert!(!url_in_domains("https://evilcdn.acme.com/a.png", &domains));
+        assert!(!url_in_domains(
+            "https://cdn.acme.com.evil.net/a.png",
+            &domains
+        ));
+        assert!(!url_in_domains("not a url", &domains));
+    }
+
+    #[test]
+    fn test_url_in_domains_rejects_ip_literals() {
+        let domains = vec!["cdn.acme.com".to_string(), "203.0.114.10".to_string()];
+        assert!(!url_in_domains("http://203.0.114.10/a.png", &domains));
+        assert!(!url_in_domains("http://[2001:db8::1]/a.png", &domains));
+    }
+
+    #[test]
+    fn test_url_in_domains_without_restriction() {
+        assert!(url_in_domains("https://anywhere.example/a.png", &[]));
+        assert!(url_in_domains("http://203.0.114.10/a.png", &[]));
+    }
+
+    #[tokio::test]
+    async fn test_check_rejects_names_resolving_to_internal_addresses() {
+        let guard = guard(UrlPolicy::default());
+        assert!(guard
+            .check("https://cdn.example.com/design.png")
+            .await
+            .is_ok());
+        assert!(matches!(
+            guard.check("http://internal.test/design.png").await,
+            Err(UrlGuardError::Forbidden(_))
+        ));
+        assert!(matches!(
+            guard.check("http://metadata.test/").await,
+            Err(UrlGuardError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_refuses_names_resolving_to_internal_addresses() {
+        // Skips `check`, as a name rebound after it would
+        let error = guard(UrlPolicy::default())
+            .client()
+            .get("http://internal.test/design.png")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                UrlGuardError::from_reqwest(&error),
+                Some(UrlGuardError::Forbidden(_))
+            ),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_internal_ip_refused() {
+        let addr = redirecting_server("http://169.254.169.254/latest/meta-data/".to_string()).await;
+        let error = guard(UrlPolicy::default())
+            .client()
+            .get(format!("http://{}/design.png", addr))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                UrlGuardError::from_reqwest(&error),
+                Some(UrlGuardError::Forbidden(_))
+            ),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_name_resolving_internally_refused() {
+        let addr = redirecting_server("http://internal.test/design.png".to_string()).await;
+        let error = guard(UrlPolicy::default())
+            .client()
+            .get(format!("http://{}/design.png", addr))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                UrlGuardError::from_reqwest(&error),
+                Some(UrlGuardError::Forbidden(_))
+            ),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_outside_fetch_domains_refused() {
+        let addr = redirecting_server("http://cdn.example.com/design.png".to_string()).await;
+        let guard = guard(UrlPolicy::default());
+        let fetch = guard
+            .client()
+            .get(format!("http://{}/design.png", addr))
+            .send();
+        let error = guard
+            .within_domains(vec!["designs.example.org".to_string()], fetch)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                UrlGuardError::from_reqwest(&error),
+                Some(UrlGuardError::OutsideDomains(_))
+            ),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_chain_capped() {
+        // Redirects to itself, which the loopback literal already forbids;
+        // with no redirects allowed the cap trips first
+        let addr = redirecting_server("/again".to_string()).await;
+        let error = guard(UrlPolicy {
+            max_redirects: 0,
+            ..UrlPolicy::default()
+        })
+        .client()
+        .get(format!("http://{}/design.png", addr))
+        .send()
+        .await
+        .unwrap_err();
+        match UrlGuardError::from_reqwest(&error) {
+            Some(UrlGuardError::Forbidden(message)) => {
+                assert!(message.contains("redirects"), "{message}")
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
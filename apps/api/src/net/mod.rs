@@ -0,0 +1,11 @@
+//! Outbound HTTP to user-supplied URLs
+//!
+//! Design URLs and asset mirror sources come from API callers, so every
+//! fetch of them goes through a [`UrlGuard`] that keeps requests away from
+//! loopback, private, and link-local addresses.
+
+mod guard;
+mod user_agent;
+
+pub use guard::{url_in_domains, HostResolver, SystemResolver, UrlGuard, UrlGuardError, UrlPolicy};
+pub use user_agent::{service_name, service_user_agent};
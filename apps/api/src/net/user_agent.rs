@@ -0,0 +1,15 @@
+//! How the service names itself on outbound requests
+
+fn first_env(keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| std::env::var(key).ok())
+}
+
+pub fn service_name() -> String {
+    first_env(&["MOCKUP_SERVICE__NAME", "SERVICE_NAME"])
+        .unwrap_or_else(|| "r-image-magic".to_string())
+}
+
+pub fn service_user_agent() -> String {
+    first_env(&["MOCKUP_SERVICE__USER_AGENT", "SERVICE_USER_AGENT"])
+        .unwrap_or_else(|| format!("{}/{}", service_name(), env!("CARGO_PKG_VERSION")))
+}
@@ -0,0 +1,10 @@
+//! Provider mockup parity
+//!
+//! Renders sampled catalog products with the same design and placement as
+//! the provider's own mockup generator, scores how closely the two images
+//! match, and stores one result per product so regressions in our templates
+//! show up in the admin parity report.
+
+mod runner;
+
+pub use runner::{ParityError, ParityRunner};
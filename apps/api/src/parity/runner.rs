@@ -0,0 +1,373 @@
+//! Parity runs
+//!
+//! A run checks each product in turn: the provider renders the design through
+//! its mockup generator, we render the same design with the same placement on
+//! our resolved template, and the two images are scored. Failures are stored
+//! alongside scores so a product that stops rendering is as visible as one
+//! that renders differently.
+
+use image::DynamicImage;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::service_user_agent;
+use crate::db::pool::DbError;
+use crate::db::{DbPool, NewParityResult, ParityRepository};
+use crate::domain::catalog::PrintPlacement;
+use crate::domain::{PlacementSpec, PlacementType};
+use crate::engine::{
+    compare_renders, DesignLayer, DesignSource, GenerationLimits, MockupRequest, OutputSettings,
+    ParityMetrics, TemplateManager,
+};
+use crate::providers::{PodProvider, ProviderCredentials, ProviderFactory, PROVIDER_CODES};
+use crate::sync::OnDemandTemplates;
+
+/// Timeout for downloading a provider mockup
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest provider mockup accepted for comparison
+const MAX_MOCKUP_BYTES: usize = 25 * 1024 * 1024;
+
+/// Errors starting a parity run
+#[derive(Debug, Error)]
+pub enum ParityError {
+    #[error("A parity run is already in progress")]
+    AlreadyRunning,
+    #[error("Unknown provider: {0}")]
+    ProviderNotFound(String),
+    #[error("No catalog products to sample for {0}")]
+    NoProducts(String),
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+}
+
+/// Runs parity checks one at a time and records their results
+pub struct ParityRunner {
+    repo: ParityRepository,
+    template_manager: Arc<TemplateManager>,
+    on_demand_templates: Arc<OnDemandTemplates>,
+    http_client: reqwest::Client,
+    running: AtomicBool,
+}
+
+impl ParityRunner {
+    pub fn new(
+        pool: DbPool,
+        template_manager: Arc<TemplateManager>,
+        on_demand_templates: Arc<OnDemandTemplates>,
+    ) -> Self {
+        let http_client = reqwest::Client::builder()
+            .timeout(DOWNLOAD_TIMEOUT)
+            .user_agent(service_user_agent())
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            repo: ParityRepository::new(pool),
+            template_manager,
+            on_demand_templates,
+            http_client,
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Stored results
+    pub fn repo(&self) -> &ParityRepository {
+        &self.repo
+    }
+
+    /// Start a run in the background and return its ID and product list
+    ///
+    /// With no `product_ids`, `sample_size` available catalog products are
+    /// picked at random.
+    pub async fn start(
+        self: &Arc<Self>,
+        provider_code: &str,
+        design_url: String,
+        product_ids: Vec<String>,
+        sample_size: i64,
+    ) -> Result<(Uuid, Vec<String>), ParityError> {
+        let provider_code = provider_code.to_lowercase();
+        if !PROVIDER_CODES.contains(&provider_code.as_str()) {
+            return Err(ParityError::ProviderNotFound(provider_code));
+        }
+
+        if self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(ParityError::AlreadyRunning);
+        }
+
+        let products = if product_ids.is_empty() {
+            match self.repo.sample_products(&provider_code, sample_size).await {
+                Ok(products) if !products.is_empty() => products,
+                Ok(_) => {
+                    self.running.store(false, Ordering::SeqCst);
+                    return Err(ParityError::NoProducts(provider_code));
+                }
+                Err(e) => {
+                    self.running.store(false, Ordering::SeqCst);
+                    return Err(e.into());
+                }
+            }
+        } else {
+            product_ids
+        };
+
+        let run_id = Uuid::new_v4();
+        let runner = self.clone();
+        let run_products = products.clone();
+        tokio::spawn(async move {
+            runner
+                .run(run_id, &provider_code, &design_url, &run_products)
+                .await;
+            runner.running.store(false, Ordering::SeqCst);
+        });
+
+        Ok((run_id, products))
+    }
+
+    async fn run(&self, run_id: Uuid, provider_code: &str, design_url: &str, products: &[String]) {
+        info!(
+            %run_id,
+            provider = provider_code,
+            products = products.len(),
+            "Parity run started"
+        );
+
+        let provider = match self.connect(provider_code).await {
+            Ok(provider) => provider,
+            Err(e) => {
+                warn!(
+                    %run_id,
+                    provider = provider_code,
+                    error = %e,
+                    "Parity run could not reach provider"
+                );
+                for product_id in products {
+                    let result = NewParityResult {
+                        provider_code: provider_code.to_string(),
+                        product_external_id: product_id.clone(),
+                        placement: PrintPlacement::Front.as_str().to_string(),
+                        error: Some(e.clone()),
+                        ..Default::default()
+                    };
+                    self.record(run_id, &result).await;
+                }
+                return;
+            }
+        };
+
+        let mut scores = Vec::new();
+        for product_id in products {
+            let result = self
+                .check_product(provider.as_ref(), product_id, design_url)
+                .await;
+            if let Some(score) = result.score {
+                scores.push(score);
+            }
+            self.record(run_id, &result).await;
+        }
+
+        let average = scores.iter().sum::<f64>() / scores.len().max(1) as f64;
+        info!(
+            %run_id,
+            provider = provider_code,
+            scored = scores.len(),
+            failed = products.len() - scores.len(),
+            average_score = average,
+            "Parity run finished"
+        );
+    }
+
+    async fn connect(&self, provider_code: &str) -> Result<Box<dyn PodProvider>, String> {
+        let credentials = ProviderCredentials::from_env(provider_code);
+        let mut provider = ProviderFactory::create(provider_code, credentials)
+            .ok_or_else(|| format!("Unknown provider: {}", provider_code))?;
+        provider.authenticate().await.map_err(|e| e.to_string())?;
+        Ok(provider)
+    }
+
+    async fn record(&self, run_id: Uuid, result: &NewParityResult) {
+        if let Err(e) = self.repo.record(run_id, result).await {
+            warn!(
+                %run_id,
+                product = %result.product_external_id,
+                error = %e,
+                "Failed to store parity result"
+            );
+        }
+    }
+
+    /// Score one product, recording any failure on the result
+    async fn check_product(
+        &self,
+        provider: &dyn PodProvider,
+        product_id: &str,
+        design_url: &str,
+    ) -> NewParityResult {
+        let mut result = NewParityResult {
+            provider_code: provider.code().to_string(),
+            product_external_id: product_id.to_string(),
+            placement: PrintPlacement::Front.as_str().to_string(),
+            ..Default::default()
+        };
+
+        match self
+            .compare_product(provider, design_url, &mut result)
+            .await
+        {
+            Ok(metrics) => {
+                result.ssim = Some(metrics.ssim);
+                result.delta_e = Some(metrics.delta_e);
+                result.score = Some(metrics.score);
+            }
+            Err(e) => {
+                warn!(
+                    provider = provider.code(),
+                    product = product_id,
+                    error = %e,
+                    "Parity check failed"
+                );
+                result.error = Some(e);
+            }
+        }
+
+        result
+    }
+
+    async fn compare_product(
+        &self,
+        provider: &dyn PodProvider,
+        design_url: &str,
+        result: &mut NewParityResult,
+    ) -> Result<ParityMetrics, String> {
+        let variants = provider
+            .get_variants(&result.product_external_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        let variant_id = variants
+            .into_iter()
+            .next()
+            .map(|variant| variant.external_id)
+            .ok_or("Product has no variants")?;
+        result.variant_external_id = Some(variant_id.clone());
+
+        let mockup_url = provider
+            .generate_preview(
+                &result.product_external_id,
+                &variant_id,
+                &result.placement,
+                design_url,
+            )
+            .await
+            .map_err(|e| format!("Provider mockup failed: {}", e))?;
+        result.provider_mockup_url = Some(mockup_url.clone());
+
+        let reference = self.download(&mockup_url).await?;
+        let ours = self
+            .render(
+                provider.code(),
+                &result.product_external_id,
+                &variant_id,
+                design_url,
+            )
+            .await?;
+
+        tokio::task::spawn_blocking(move || compare_renders(&ours, &reference))
+            .await
+            .map_err(|e| format!("Task join error: {}", e))
+    }
+
+    /// Render the design across the full front print area, as the provider does
+    async fn render(
+        &self,
+        provider_code: &str,
+        product_id: &str,
+        variant_id: &str,
+        design_url: &str,
+    ) -> Result<DynamicImage, String> {
+        let template = self
+            .on_demand_templates
+            .resolve(
+                provider_code,
+                product_id,
+                Some(variant_id),
+                &PrintPlacement::Front,
+                true,
+            )
+            .await
+            .map_err(|e| format!("Template unavailable: {}", e))?;
+
+        let mut placement = PlacementSpec::new(1.0, 0, 0, PlacementType::Front);
+        placement.print_area_width = template.metadata.print_area.width;
+        placement.print_area_height = template.metadata.print_area.height;
+
+        let request = MockupRequest {
+            designs: vec![DesignLayer {
+                design: DesignSource::Url(design_url.to_string()),
+                placement,
+                displacement_strength: template.metadata.displacement.strength_default,
+                blend_mode: None,
+            }],
+            template_id: template.metadata.id.clone(),
+            apply_displacement: Some(true),
+            tint_color: None,
+            remove_background: None,
+            output: OutputSettings::default(),
+            limits: GenerationLimits::default(),
+        };
+
+        let rendered = self
+            .template_manager
+            .generate_with(&request, &template.metadata, &template.images)
+            .await
+            .map_err(|e| format!("Render failed: {}", e))?;
+
+        decode(rendered.bytes.to_vec()).await
+    }
+
+    async fn download(&self, url: &str) -> Result<DynamicImage, String> {
+        let response = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| format!("Mockup download failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Mockup download failed: HTTP {} from {}",
+                response.status(),
+                url
+            ));
+        }
+
+        let data = response
+            .bytes()
+            .await
+            .map_err(|e| format!("Mockup download failed: {}", e))?;
+        if data.len() > MAX_MOCKUP_BYTES {
+            return Err(format!(
+                "Provider mockup is {} bytes (max {})",
+                data.len(),
+                MAX_MOCKUP_BYTES
+            ));
+        }
+
+        decode(data.to_vec()).await
+    }
+}
+
+async fn decode(data: Vec<u8>) -> Result<DynamicImage, String> {
+    tokio::task::spawn_blocking(move || image::load_from_memory(&data))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+        .map_err(|e| format!("Image decode failed: {}", e))
+}
@@ -98,7 +98,11 @@ impl PodProvider for GootenProvider {
     }
 
     fn rate_limit(&self) -> u32 {
-        300
+        self.client.rate_limit_per_minute()
+    }
+
+    fn set_rate_limit(&mut self, per_minute: u32) {
+        self.client.set_rate_limit(per_minute);
     }
 
     async fn authenticate(&mut self) -> ProviderResult<()> {
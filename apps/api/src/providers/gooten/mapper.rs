@@ -84,6 +84,7 @@ impl GootenMapper {
                     width_px: None,
                     height_px: None,
                     variant_external_id: Some(template.sku.clone()),
+                    template_print_area: None,
                 }
             })
             .collect()
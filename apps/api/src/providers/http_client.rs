@@ -38,12 +38,7 @@ impl RateLimitedClient {
     /// # Arguments
     /// * `rate_limit_per_minute` - Maximum requests allowed per minute
     pub fn new(rate_limit_per_minute: u32) -> Self {
-        // Ensure at least 1 request per minute
-        let rate = NonZeroU32::new(rate_limit_per_minute.max(1)).unwrap();
-
-        // Create quota: rate requests per 60 seconds
-        let quota = Quota::per_minute(rate);
-        let limiter = RateLimiter::direct(quota);
+        let limiter = Self::limiter(rate_limit_per_minute);
 
         // Create HTTP client with reasonable defaults
         let client = Client::builder()
@@ -70,6 +65,28 @@ impl RateLimitedClient {
         client
     }
 
+    /// Limiter allowing `rate_limit_per_minute` requests per 60 seconds
+    fn limiter(
+        rate_limit_per_minute: u32,
+    ) -> RateLimiter<NotKeyed, governor::state::InMemoryState, DefaultClock, NoOpMiddleware> {
+        // Ensure at least 1 request per minute
+        let rate = NonZeroU32::new(rate_limit_per_minute.max(1)).unwrap();
+        RateLimiter::direct(Quota::per_minute(rate))
+    }
+
+    /// Configured rate limit (requests per minute)
+    pub fn rate_limit_per_minute(&self) -> u32 {
+        self.rate_limit_per_minute
+    }
+
+    /// Change the rate limit, starting a fresh window
+    pub fn set_rate_limit(&mut self, rate_limit_per_minute: u32) {
+        self.limiter = Self::limiter(rate_limit_per_minute);
+        self.rate_limit_per_minute = rate_limit_per_minute;
+        self.remaining_requests
+            .store(rate_limit_per_minute, Ordering::Relaxed);
+    }
+
     /// Get remaining requests in current rate limit window
     pub fn remaining_requests(&self) -> Option<u32> {
         let remaining = self.remaining_requests.load(Ordering::Relaxed);
@@ -234,6 +251,14 @@ mod tests {
         assert_eq!(client.rate_limit_per_minute, 120);
     }
 
+    #[test]
+    fn test_rate_limit_can_be_changed() {
+        let mut client = RateLimitedClient::new(120);
+        client.set_rate_limit(30);
+        assert_eq!(client.rate_limit_per_minute(), 30);
+        assert_eq!(client.remaining_requests(), Some(30));
+    }
+
     #[test]
     fn test_remaining_requests() {
         let client = RateLimitedClient::new(100);
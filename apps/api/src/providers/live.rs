@@ -0,0 +1,334 @@
+//! Live Provider Catalogs
+//!
+//! Reads products straight from a provider's API, for browsing a catalog
+//! before it is synced. Provider clients are kept between requests so their
+//! rate limiters apply across requests, and product lists are cached briefly.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+use tracing::info;
+
+use crate::domain::catalog::{UnifiedPrintArea, UnifiedProduct};
+use crate::providers::traits::{
+    CatalogPage, PodProvider, ProviderCredentials, ProviderError, ProviderFactory,
+};
+
+/// How long a fetched product list is served before asking the provider again
+pub const LIVE_PRODUCTS_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Live catalog errors
+#[derive(Debug, Error)]
+pub enum LiveCatalogError {
+    #[error("Provider not configured: {0}")]
+    NotConfigured(String),
+
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+}
+
+/// Builds a provider client by code, None when it isn't configured
+type ProviderBuilder = Box<dyn Fn(&str) -> Option<Box<dyn PodProvider>> + Send + Sync>;
+
+/// A product list page, keyed by provider code, page and page size
+type PageKey = (String, u32, u32);
+
+/// Pass-through access to provider catalogs
+pub struct LiveCatalog {
+    build: ProviderBuilder,
+    /// Authenticated clients, reused so each keeps one rate limiter
+    providers: tokio::sync::Mutex<HashMap<String, Arc<dyn PodProvider>>>,
+    pages: Mutex<HashMap<PageKey, (Instant, CatalogPage<UnifiedProduct>)>>,
+    ttl: Duration,
+}
+
+impl Default for LiveCatalog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LiveCatalog {
+    /// Live catalogs of the providers with credentials in the environment
+    pub fn new() -> Self {
+        Self {
+            build: Box::new(|code: &str| {
+                let credentials = ProviderCredentials::from_env(code);
+                if !credentials.is_configured() {
+                    return None;
+                }
+                ProviderFactory::create(code, credentials)
+            }),
+            providers: tokio::sync::Mutex::new(HashMap::new()),
+            pages: Mutex::new(HashMap::new()),
+            ttl: LIVE_PRODUCTS_TTL,
+        }
+    }
+
+    /// Build provider clients with `build` instead of from the environment
+    #[cfg(test)]
+    fn with_providers(
+        mut self,
+        build: impl Fn(&str) -> Option<Box<dyn PodProvider>> + Send + Sync + 'static,
+    ) -> Self {
+        self.build = Box::new(build);
+        self
+    }
+
+    #[cfg(test)]
+    fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// One page of the provider's products, cached for the TTL
+    pub async fn products(
+        &self,
+        provider_code: &str,
+        page: u32,
+        per_page: u32,
+    ) -> Result<CatalogPage<UnifiedProduct>, LiveCatalogError> {
+        let key = (provider_code.to_string(), page, per_page);
+        if let Some((fetched_at, cached)) = self.pages.lock().unwrap().get(&key) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(cached.clone());
+            }
+        }
+
+        let provider = self.provider(provider_code).await?;
+        let fetched = provider.get_products(page, per_page).await?;
+
+        let mut pages = self.pages.lock().unwrap();
+        pages.retain(|_, (fetched_at, _)| fetched_at.elapsed() < self.ttl);
+        pages.insert(key, (Instant::now(), fetched.clone()));
+        Ok(fetched)
+    }
+
+    /// One product as the provider describes it now
+    pub async fn product(
+        &self,
+        provider_code: &str,
+        external_id: &str,
+    ) -> Result<UnifiedProduct, LiveCatalogError> {
+        let provider = self.provider(provider_code).await?;
+        Ok(provider.get_product(external_id).await?)
+    }
+
+    /// A product's print areas as the provider describes them now
+    pub async fn print_areas(
+        &self,
+        provider_code: &str,
+        external_id: &str,
+    ) -> Result<Vec<UnifiedPrintArea>, LiveCatalogError> {
+        let provider = self.provider(provider_code).await?;
+        Ok(provider.get_print_areas(external_id).await?)
+    }
+
+    /// The provider's authenticated client, created on first use
+    ///
+    /// A client that fails to authenticate isn't kept, so the next request
+    /// tries again.
+    async fn provider(
+        &self,
+        provider_code: &str,
+    ) -> Result<Arc<dyn PodProvider>, LiveCatalogError> {
+        let mut providers = self.providers.lock().await;
+        if let Some(provider) = providers.get(provider_code) {
+            if provider.is_authenticated() {
+                return Ok(provider.clone());
+            }
+        }
+
+        let mut provider = (self.build)(provider_code)
+            .ok_or_else(|| LiveCatalogError::NotConfigured(provider_code.to_string()))?;
+        provider.authenticate().await?;
+        info!(
+            provider = provider_code,
+            "Live catalog client authenticated"
+        );
+
+        let provider: Arc<dyn PodProvider> = Arc::from(provider);
+        providers.insert(provider_code.to_string(), provider.clone());
+        Ok(provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::domain::catalog::{MockupAsset, UnifiedVariant};
+    use crate::providers::traits::ProviderResult;
+
+    #[derive(Default)]
+    struct Calls {
+        authenticate: AtomicUsize,
+        products: AtomicUsize,
+    }
+
+    struct CountingProvider {
+        calls: Arc<Calls>,
+        authenticated: bool,
+        reject_auth: bool,
+    }
+
+    #[async_trait]
+    impl PodProvider for CountingProvider {
+        fn code(&self) -> &'static str {
+            "printful"
+        }
+
+        fn name(&self) -> &'static str {
+            "Printful"
+        }
+
+        fn base_url(&self) -> &str {
+            "http://live.invalid"
+        }
+
+        fn rate_limit(&self) -> u32 {
+            120
+        }
+
+        async fn authenticate(&mut self) -> ProviderResult<()> {
+            self.calls.authenticate.fetch_add(1, Ordering::SeqCst);
+            if self.reject_auth {
+                return Err(ProviderError::AuthFailed(
+                    "Invalid access token".to_string(),
+                ));
+            }
+            self.authenticated = true;
+            Ok(())
+        }
+
+        fn is_authenticated(&self) -> bool {
+            self.authenticated
+        }
+
+        async fn refresh_auth(&mut self) -> ProviderResult<()> {
+            Ok(())
+        }
+
+        async fn get_products(
+            &self,
+            page: u32,
+            per_page: u32,
+        ) -> ProviderResult<CatalogPage<UnifiedProduct>> {
+            self.calls.products.fetch_add(1, Ordering::SeqCst);
+            Ok(CatalogPage::new(Vec::new(), 0, page, per_page))
+        }
+
+        async fn get_product(&self, external_id: &str) -> ProviderResult<UnifiedProduct> {
+            Err(ProviderError::NotFound(external_id.to_string()))
+        }
+
+        async fn get_variants(&self, _: &str) -> ProviderResult<Vec<UnifiedVariant>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_print_areas(&self, _: &str) -> ProviderResult<Vec<UnifiedPrintArea>> {
+            Err(ProviderError::ApiError {
+                status: 500,
+                message: "Internal server error".to_string(),
+            })
+        }
+
+        async fn get_mockup_urls(
+            &self,
+            _: &str,
+            _: Option<&str>,
+        ) -> ProviderResult<Vec<MockupAsset>> {
+            Ok(Vec::new())
+        }
+
+        fn rate_limit_remaining(&self) -> Option<u32> {
+            None
+        }
+    }
+
+    fn catalog(calls: Arc<Calls>, reject_auth: bool) -> LiveCatalog {
+        LiveCatalog::new().with_providers(move |code| {
+            if code != "printful" {
+                return None;
+            }
+            Some(Box::new(CountingProvider {
+                calls: calls.clone(),
+                authenticated: false,
+                reject_auth,
+            }))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_product_pages_are_cached() {
+        let calls = Arc::new(Calls::default());
+        let live = catalog(calls.clone(), false);
+
+        live.products("printful", 1, 20).await.unwrap();
+        live.products("printful", 1, 20).await.unwrap();
+        assert_eq!(calls.products.load(Ordering::SeqCst), 1);
+
+        // Another page is fetched, through the same client
+        let page = live.products("printful", 2, 20).await.unwrap();
+        assert_eq!(page.page, 2);
+        assert_eq!(calls.products.load(Ordering::SeqCst), 2);
+        assert_eq!(calls.authenticate.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_pages_are_fetched_again() {
+        let calls = Arc::new(Calls::default());
+        let live = catalog(calls.clone(), false).with_ttl(Duration::ZERO);
+
+        live.products("printful", 1, 20).await.unwrap();
+        live.products("printful", 1, 20).await.unwrap();
+        assert_eq!(calls.products.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_provider() {
+        let live = catalog(Arc::new(Calls::default()), false);
+
+        let result = live.products("gelato", 1, 20).await;
+        assert!(matches!(result, Err(LiveCatalogError::NotConfigured(code)) if code == "gelato"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_client_is_not_kept() {
+        let calls = Arc::new(Calls::default());
+        let live = catalog(calls.clone(), true);
+
+        for _ in 0..2 {
+            let result = live.products("printful", 1, 20).await;
+            assert!(matches!(
+                result,
+                Err(LiveCatalogError::Provider(ProviderError::AuthFailed(_)))
+            ));
+        }
+        assert_eq!(calls.authenticate.load(Ordering::SeqCst), 2);
+        assert_eq!(calls.products.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_upstream_errors_pass_through() {
+        let live = catalog(Arc::new(Calls::default()), false);
+
+        let result = live.product("printful", "71").await;
+        assert!(matches!(
+            result,
+            Err(LiveCatalogError::Provider(ProviderError::NotFound(id))) if id == "71"
+        ));
+        let result = live.print_areas("printful", "71").await;
+        assert!(matches!(
+            result,
+            Err(LiveCatalogError::Provider(ProviderError::ApiError {
+                status: 500,
+                ..
+            }))
+        ));
+    }
+}
@@ -20,6 +20,8 @@
 pub mod gelato;
 pub mod gooten;
 pub mod http_client;
+pub mod live;
+pub mod oauth;
 pub mod printful;
 pub mod printify;
 pub mod spod;
@@ -27,6 +29,9 @@ pub mod traits;
 
 // Re-export commonly used types
 pub use http_client::RateLimitedClient;
+pub use live::LiveCatalog;
+pub use oauth::OAuthSession;
 pub use traits::{
     CatalogPage, PodProvider, ProviderCredentials, ProviderError, ProviderFactory, ProviderResult,
+    PROVIDER_CODES,
 };
@@ -0,0 +1,476 @@
+//! OAuth access tokens that expire
+//!
+//! `OAuthSession` holds a provider's access token. When a refresh token,
+//! client credentials and a token URL are configured, it exchanges the
+//! refresh token for a new access token shortly before the old one expires,
+//! or once when the API rejects a request with 401. A failed refresh leaves
+//! the session unauthenticated, so syncs stop instead of retrying a dead token.
+
+use chrono::{DateTime, Utc};
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::providers::http_client::RateLimitedRequestBuilder;
+use crate::providers::traits::{ProviderCredentials, ProviderError, ProviderResult};
+
+/// Tokens expiring within this many seconds are refreshed before use
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// An access token and when it stops working
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessToken {
+    pub value: String,
+    /// `None` for tokens that don't expire, or whose expiry is unknown
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl AccessToken {
+    /// Whether the token expires within the refresh margin of `now`
+    fn is_expiring(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at
+            .is_some_and(|at| at <= now + chrono::Duration::seconds(EXPIRY_MARGIN_SECS))
+    }
+}
+
+/// Where and as whom to exchange refresh tokens
+#[derive(Debug, Clone)]
+struct RefreshClient {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+}
+
+/// Token endpoint response (RFC 6749 section 5.1)
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// Seconds until the new token expires
+    expires_in: Option<i64>,
+    /// Unix time the new token expires, for endpoints that send it instead
+    expires_at: Option<i64>,
+    /// Replacement refresh token, for endpoints that rotate them
+    refresh_token: Option<String>,
+}
+
+impl TokenResponse {
+    fn into_token(self, now: DateTime<Utc>) -> AccessToken {
+        let expires_at = match (self.expires_in, self.expires_at) {
+            (Some(secs), _) => Some(now + chrono::Duration::seconds(secs)),
+            (None, Some(at)) => DateTime::from_timestamp(at, 0),
+            (None, None) => None,
+        };
+        AccessToken {
+            value: self.access_token,
+            expires_at,
+        }
+    }
+}
+
+/// A provider's access token, refreshed when it expires
+pub struct OAuthSession {
+    token: RwLock<Option<AccessToken>>,
+    refresh_token: RwLock<Option<String>>,
+    refresh_client: Option<RefreshClient>,
+    http: reqwest::Client,
+    /// Held while refreshing, so concurrent requests refresh once
+    refreshing: tokio::sync::Mutex<()>,
+    /// Set when a refresh fails or a refreshed token is still rejected
+    failed: AtomicBool,
+}
+
+impl OAuthSession {
+    /// Session for `credentials`, refreshing at their `token_url` or `default_token_url`
+    ///
+    /// Refreshing needs a refresh token, client ID and client secret as well.
+    pub fn new(credentials: &ProviderCredentials, default_token_url: Option<&str>) -> Self {
+        let token_url = credentials
+            .token_url
+            .clone()
+            .or_else(|| default_token_url.map(str::to_string));
+        let refresh_client = match (
+            token_url,
+            credentials.client_id.clone(),
+            credentials.client_secret.clone(),
+        ) {
+            (Some(token_url), Some(client_id), Some(client_secret))
+                if credentials.refresh_token.is_some() =>
+            {
+                Some(RefreshClient {
+                    token_url,
+                    client_id,
+                    client_secret,
+                })
+            }
+            _ => None,
+        };
+
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent("r-image-magic/1.0")
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            token: RwLock::new(credentials.access_token.clone().map(|value| AccessToken {
+                value,
+                expires_at: credentials.expires_at,
+            })),
+            refresh_token: RwLock::new(credentials.refresh_token.clone()),
+            refresh_client,
+            http,
+            refreshing: tokio::sync::Mutex::new(()),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether there's a token, or the means to get one
+    pub fn is_configured(&self) -> bool {
+        self.current().is_some() || self.can_refresh()
+    }
+
+    /// Whether expired or rejected tokens can be replaced
+    pub fn can_refresh(&self) -> bool {
+        self.refresh_client.is_some()
+    }
+
+    /// False once a refresh has failed, until one succeeds
+    pub fn is_valid(&self) -> bool {
+        !self.failed.load(Ordering::Relaxed) && self.is_configured()
+    }
+
+    /// The token as it stands
+    pub fn current(&self) -> Option<AccessToken> {
+        self.token.read().unwrap().clone()
+    }
+
+    /// A token to send, refreshed first if it's missing or about to expire
+    pub async fn access_token(&self) -> ProviderResult<String> {
+        match self.current() {
+            Some(token) if !(self.can_refresh() && token.is_expiring(Utc::now())) => {
+                Ok(token.value)
+            }
+            Some(token) => self.refresh_stale(Some(&token.value)).await,
+            None if self.can_refresh() => self.refresh_stale(None).await,
+            None => Err(ProviderError::AuthFailed(
+                "No access token configured".to_string(),
+            )),
+        }
+    }
+
+    /// Exchange the refresh token for a new access token now
+    pub async fn refresh(&self) -> ProviderResult<String> {
+        let stale = self.current().map(|token| token.value);
+        self.refresh_stale(stale.as_deref()).await
+    }
+
+    /// Send a request built around the access token, refreshing it once on a 401
+    ///
+    /// Without a way to refresh, a 401 response is returned as it is.
+    pub async fn send<'a, F>(&self, request: F) -> ProviderResult<Response>
+    where
+        F: Fn(&str) -> RateLimitedRequestBuilder<'a>,
+    {
+        let token = self.access_token().await?;
+        let response = request(&token).send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED || !self.can_refresh() {
+            return Ok(response);
+        }
+
+        debug!("Access token rejected; refreshing");
+        let token = self.refresh_stale(Some(&token)).await?;
+        let response = request(&token).send().await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            self.failed.store(true, Ordering::Relaxed);
+            return Err(ProviderError::AuthFailed(
+                "Access token rejected after refresh".to_string(),
+            ));
+        }
+        Ok(response)
+    }
+
+    /// Replace `stale`, unless another request already has
+    async fn refresh_stale(&self, stale: Option<&str>) -> ProviderResult<String> {
+        let _refreshing = self.refreshing.lock().await;
+        if let Some(token) = self.current() {
+            if Some(token.value.as_str()) != stale && !token.is_expiring(Utc::now()) {
+                return Ok(token.value);
+            }
+        }
+
+        match self.exchange().await {
+            Ok(token) => {
+                info!(expires_at = ?token.expires_at, "Refreshed access token");
+                let value = token.value.clone();
+                *self.token.write().unwrap() = Some(token);
+                self.failed.store(false, Ordering::Relaxed);
+                Ok(value)
+            }
+            Err(reason) => {
+                warn!(reason = %reason, "Access token refresh failed");
+                self.failed.store(true, Ordering::Relaxed);
+                Err(ProviderError::AuthFailed(format!(
+                    "Token refresh failed: {}",
+                    reason
+                )))
+            }
+        }
+    }
+
+    /// POST the refresh token to the token endpoint
+    async fn exchange(&self) -> Result<AccessToken, String> {
+        let client = self
+            .refresh_client
+            .as_ref()
+            .ok_or_else(|| "no refresh token or client credentials configured".to_string())?;
+        let refresh_token = self
+            .refresh_token
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| "no refresh token configured".to_string())?;
+
+        let response = self
+            .http
+            .post(&client.token_url)
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token.as_str()),
+                ("client_id", client.client_id.as_str()),
+                ("client_secret", client.client_secret.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let status = response.status();
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!(
+                "HTTP {}: {}",
+                status.as_u16(),
+                &body[..body.len().min(200)]
+            ));
+        }
+
+        let tokens: TokenResponse =
+            serde_json::from_str(&body).map_err(|e| format!("invalid token response: {}", e))?;
+        if let Some(ref rotated) = tokens.refresh_token {
+            *self.refresh_token.write().unwrap() = Some(rotated.clone());
+        }
+        Ok(tokens.into_token(Utc::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::RateLimitedClient;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `responses` to one connection each, returning the requests received
+    fn serve(
+        listener: TcpListener,
+        responses: Vec<String>,
+    ) -> tokio::task::JoinHandle<Vec<String>> {
+        tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                // Read the headers, then as much body as they announce
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let Some(end) = text.find("\r\n\r\n") else {
+                        if n == 0 {
+                            break;
+                        }
+                        continue;
+                    };
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            if name.eq_ignore_ascii_case("content-length") {
+                                value.trim().parse::<usize>().ok()
+                            } else {
+                                None
+                            }
+                        })
+                        .unwrap_or(0);
+                    if n == 0 || request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                requests.push(String::from_utf8_lossy(&request).to_string());
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        })
+    }
+
+    fn respond(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    fn credentials(token_url: &str, expires_at: Option<DateTime<Utc>>) -> ProviderCredentials {
+        ProviderCredentials {
+            access_token: Some("old-token".to_string()),
+            refresh_token: Some("refresh-1".to_string()),
+            client_id: Some("client".to_string()),
+            client_secret: Some("secret".to_string()),
+            token_url: Some(token_url.to_string()),
+            expires_at,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_valid_token_used_without_refreshing() {
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        let session = OAuthSession::new(
+            &credentials("http://127.0.0.1:9/token", Some(expires_at)),
+            None,
+        );
+
+        assert!(session.can_refresh());
+        assert_eq!(session.access_token().await.unwrap(), "old-token");
+        assert!(session.is_valid());
+    }
+
+    #[tokio::test]
+    async fn test_expiring_token_refreshed_before_use() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let token_url = format!("http://{}/oauth/token", listener.local_addr().unwrap());
+        let server = serve(
+            listener,
+            vec![respond(
+                "200 OK",
+                r#"{"access_token":"new-token","expires_in":3600,"refresh_token":"refresh-2"}"#,
+            )],
+        );
+
+        let expired = Utc::now() - chrono::Duration::minutes(5);
+        let session = OAuthSession::new(&credentials(&token_url, Some(expired)), None);
+        assert_eq!(session.access_token().await.unwrap(), "new-token");
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /oauth/token"));
+        assert!(requests[0].contains("grant_type=refresh_token"));
+        assert!(requests[0].contains("refresh_token=refresh-1"));
+        assert!(requests[0].contains("client_id=client"));
+
+        let token = session.current().unwrap();
+        assert!(token.expires_at.unwrap() > Utc::now() + chrono::Duration::minutes(59));
+        assert_eq!(
+            session.refresh_token.read().unwrap().as_deref(),
+            Some("refresh-2")
+        );
+        // Fresh now, so it's used as it is
+        assert_eq!(session.access_token().await.unwrap(), "new-token");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_request_retried_after_refresh() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = serve(
+            listener,
+            vec![
+                respond("401 Unauthorized", r#"{"error":"expired"}"#),
+                respond(
+                    "200 OK",
+                    r#"{"access_token":"new-token","expires_at":4102444800}"#,
+                ),
+                respond("200 OK", r#"{"ok":true}"#),
+            ],
+        );
+
+        let session =
+            OAuthSession::new(&credentials(&format!("http://{}/token", addr), None), None);
+        let client = RateLimitedClient::new(600);
+        let url = format!("http://{}/articles", addr);
+        let response = session
+            .send(|token| client.get(&url).bearer_auth(token))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].contains("Bearer old-token"));
+        assert!(requests[1].starts_with("POST /token"));
+        assert!(requests[2].contains("Bearer new-token"));
+        assert_eq!(
+            session.current().unwrap().expires_at,
+            DateTime::from_timestamp(4102444800, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_marks_session_invalid() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = serve(
+            listener,
+            vec![
+                respond("401 Unauthorized", r#"{"error":"expired"}"#),
+                respond("400 Bad Request", r#"{"error":"invalid_grant"}"#),
+            ],
+        );
+
+        let session =
+            OAuthSession::new(&credentials(&format!("http://{}/token", addr), None), None);
+        let client = RateLimitedClient::new(600);
+        let url = format!("http://{}/articles", addr);
+        let result = session
+            .send(|token| client.get(&url).bearer_auth(token))
+            .await;
+
+        match result {
+            Err(ProviderError::AuthFailed(message)) => assert!(message.contains("invalid_grant")),
+            other => panic!("expected AuthFailed, got {:?}", other.map(|r| r.status())),
+        }
+        assert!(!session.is_valid());
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_returned_without_refresh_credentials() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = serve(listener, vec![respond("401 Unauthorized", "{}")]);
+
+        let session = OAuthSession::new(
+            &ProviderCredentials {
+                access_token: Some("static-token".to_string()),
+                ..Default::default()
+            },
+            Some("http://127.0.0.1:9/token"),
+        );
+        assert!(!session.can_refresh());
+
+        let client = RateLimitedClient::new(600);
+        let url = format!("http://{}/articles", addr);
+        let response = session
+            .send(|token| client.get(&url).bearer_auth(token))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(server.await.unwrap().len(), 1);
+        assert!(session.is_valid());
+    }
+}
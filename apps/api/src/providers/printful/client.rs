@@ -6,23 +6,34 @@
 //! API Docs: https://developers.printful.com/docs/
 
 use async_trait::async_trait;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
 use super::mapper::PrintfulMapper;
 use super::models::*;
 use crate::domain::catalog::{MockupAsset, UnifiedPrintArea, UnifiedProduct, UnifiedVariant};
 use crate::providers::http_client::RateLimitedClient;
+use crate::providers::oauth::OAuthSession;
 use crate::providers::traits::{
     CatalogPage, PodProvider, ProviderCredentials, ProviderError, ProviderResult,
 };
 
+/// Delay between polls of a mockup generation task
+const MOCKUP_TASK_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Polls before a mockup generation task is abandoned
+const MOCKUP_TASK_MAX_POLLS: u32 = 40;
+
+/// Where Printful OAuth apps exchange refresh tokens
+const TOKEN_URL: &str = "https://www.printful.com/oauth/token";
+
 /// Printful API client
 pub struct PrintfulProvider {
     /// Rate-limited HTTP client
     client: RateLimitedClient,
 
-    /// OAuth access token
-    access_token: Option<String>,
+    /// OAuth access token, refreshed when it expires
+    auth: OAuthSession,
 
     /// API base URL
     base_url: String,
@@ -36,7 +47,7 @@ impl PrintfulProvider {
     pub fn new(credentials: ProviderCredentials) -> Self {
         PrintfulProvider {
             client: RateLimitedClient::new(120), // 120 req/min
-            access_token: credentials.access_token,
+            auth: OAuthSession::new(&credentials, Some(TOKEN_URL)),
             base_url: "https://api.printful.com".to_string(),
             authenticated: false,
         }
@@ -44,24 +55,47 @@ impl PrintfulProvider {
 
     /// Make an authenticated GET request
     async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> ProviderResult<T> {
-        let token = self
-            .access_token
-            .as_ref()
-            .ok_or_else(|| ProviderError::AuthFailed("No access token configured".to_string()))?;
+        let url = format!("{}{}", self.base_url, path);
+        debug!(url = %url, "Printful API request");
+
+        let response = self
+            .auth
+            .send(|token| self.client.get(&url).bearer_auth(token))
+            .await?;
+        Self::parse_response(response).await
+    }
 
+    /// Make an authenticated POST request with a JSON body
+    async fn post<B: serde::Serialize, T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> ProviderResult<T> {
         let url = format!("{}{}", self.base_url, path);
         debug!(url = %url, "Printful API request");
 
-        let response = self.client.get(&url).bearer_auth(token).send().await?;
+        let response = self
+            .auth
+            .send(|token| self.client.post(&url).bearer_auth(token).json(body))
+            .await?;
+        Self::parse_response(response).await
+    }
 
-        // Check for error status
+    async fn parse_response<T: serde::de::DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> ProviderResult<T> {
+        // Map Printful's error envelope to a provider error
         let status = response.status();
         if !status.is_success() {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok());
             let body = response.text().await.unwrap_or_default();
-            return Err(ProviderError::ApiError {
-                status: status.as_u16(),
-                message: body,
-            });
+            let error = PrintfulMapper::map_error(status.as_u16(), retry_after, &body);
+            warn!(status = status.as_u16(), error = %error, "Printful request failed");
+            return Err(error);
         }
 
         // Parse JSON response
@@ -91,11 +125,15 @@ impl PodProvider for PrintfulProvider {
     }
 
     fn rate_limit(&self) -> u32 {
-        120
+        self.client.rate_limit_per_minute()
+    }
+
+    fn set_rate_limit(&mut self, per_minute: u32) {
+        self.client.set_rate_limit(per_minute);
     }
 
     async fn authenticate(&mut self) -> ProviderResult<()> {
-        if self.access_token.is_none() {
+        if !self.auth.is_configured() {
             return Err(ProviderError::NotConfigured(
                 "PRINTFUL_ACCESS_TOKEN environment variable not set".to_string(),
             ));
@@ -110,20 +148,22 @@ impl PodProvider for PrintfulProvider {
                 info!("Printful authentication successful");
                 Ok(())
             }
-            Err(ProviderError::ApiError { status: 401, .. }) => Err(ProviderError::AuthFailed(
-                "Invalid access token".to_string(),
-            )),
             Err(e) => Err(e),
         }
     }
 
     fn is_authenticated(&self) -> bool {
-        self.authenticated && self.access_token.is_some()
+        self.authenticated && self.auth.is_valid()
     }
 
     async fn refresh_auth(&mut self) -> ProviderResult<()> {
-        // Printful tokens don't expire, but we can re-validate
-        self.authenticate().await
+        if !self.auth.can_refresh() {
+            // Private tokens don't expire, so re-validate the one we have
+            return self.authenticate().await;
+        }
+        self.auth.refresh().await?;
+        self.authenticated = true;
+        Ok(())
     }
 
     async fn get_products(
@@ -199,6 +239,87 @@ impl PodProvider for PrintfulProvider {
         ))
     }
 
+    async fn generate_preview(
+        &self,
+        product_external_id: &str,
+        variant_external_id: &str,
+        placement: &str,
+        design_url: &str,
+    ) -> ProviderResult<String> {
+        let variant_id: i64 = variant_external_id.parse().map_err(|_| {
+            ProviderError::NotFound(format!("Printful variant '{}'", variant_external_id))
+        })?;
+
+        // Stretch the design over the whole printfile, as a scale 1.0 placement does
+        let areas = self.get_print_areas(product_external_id).await?;
+        let area = areas
+            .iter()
+            .find(|area| area.placement.as_str() == placement)
+            .or_else(|| areas.first())
+            .ok_or_else(|| {
+                ProviderError::NotFound(format!(
+                    "print area for Printful product {}",
+                    product_external_id
+                ))
+            })?;
+        let request = PrintfulMockupTaskRequest {
+            variant_ids: vec![variant_id],
+            format: "png".to_string(),
+            files: vec![PrintfulMockupFile {
+                placement: placement.to_string(),
+                image_url: design_url.to_string(),
+                position: PrintfulTemplatePositions {
+                    area_width: area.width_px,
+                    area_height: area.height_px,
+                    width: area.width_px,
+                    height: area.height_px,
+                    top: 0,
+                    left: 0,
+                },
+            }],
+        };
+
+        let path = format!("/mockup-generator/create-task/{}", product_external_id);
+        let created: PrintfulResponse<PrintfulMockupTask> = self.post(&path, &request).await?;
+        let task_key = created.result.task_key;
+        let task_path = format!("/mockup-generator/task?task_key={}", task_key);
+
+        for _ in 0..MOCKUP_TASK_MAX_POLLS {
+            tokio::time::sleep(MOCKUP_TASK_POLL_INTERVAL).await;
+            let task: PrintfulResponse<PrintfulMockupTask> = self.get(&task_path).await?;
+            match task.result.status.as_str() {
+                "completed" => {
+                    let mut mockups = task.result.mockups;
+                    let index = mockups
+                        .iter()
+                        .position(|mockup| mockup.placement == placement)
+                        .unwrap_or(0);
+                    if index >= mockups.len() {
+                        return Err(ProviderError::ParseError(format!(
+                            "Mockup task {} completed without mockups",
+                            task_key
+                        )));
+                    }
+                    return Ok(mockups.swap_remove(index).mockup_url);
+                }
+                "failed" => {
+                    return Err(ProviderError::Internal(format!(
+                        "Mockup task {} failed: {}",
+                        task_key,
+                        task.result.error.unwrap_or_default()
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        Err(ProviderError::Internal(format!(
+            "Mockup task {} did not finish within {} seconds",
+            task_key,
+            MOCKUP_TASK_MAX_POLLS as u64 * MOCKUP_TASK_POLL_INTERVAL.as_secs()
+        )))
+    }
+
     fn rate_limit_remaining(&self) -> Option<u32> {
         self.client.remaining_requests()
     }
@@ -219,6 +340,13 @@ mod tests {
         assert!(!provider.is_authenticated());
     }
 
+    #[test]
+    fn test_stored_rate_limit_replaces_default() {
+        let mut provider = PrintfulProvider::new(ProviderCredentials::default());
+        provider.set_rate_limit(60);
+        assert_eq!(provider.rate_limit(), 60);
+    }
+
     #[test]
     fn test_provider_with_token() {
         let creds = ProviderCredentials {
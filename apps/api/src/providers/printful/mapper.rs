@@ -4,9 +4,13 @@
 
 use super::models::*;
 use crate::domain::catalog::{
-    AssetType, MockupAsset, PrintConstraints, PrintPlacement, ProductType, UnifiedPrintArea,
-    UnifiedProduct, UnifiedVariant,
+    AssetType, MockupAsset, PrintConstraints, PrintPlacement, ProductType, TemplatePrintArea,
+    UnifiedPrintArea, UnifiedProduct, UnifiedVariant,
 };
+use crate::providers::traits::ProviderError;
+
+/// Seconds to wait after a 429 without a usable Retry-After header
+const DEFAULT_RETRY_AFTER_SECS: u64 = 60;
 
 /// Mapper for Printful API responses
 pub struct PrintfulMapper;
@@ -103,6 +107,17 @@ impl PrintfulMapper {
             None => (template.template_width, template.template_height),
         };
 
+        let template_print_area =
+            template
+                .template_positions
+                .as_ref()
+                .map(|pos| TemplatePrintArea {
+                    left: pos.left,
+                    top: pos.top,
+                    width: pos.width,
+                    height: pos.height,
+                });
+
         MockupAsset {
             asset_type: AssetType::MockupTemplate,
             placement: Some(PrintPlacement::Front), // Default, may need context
@@ -110,6 +125,7 @@ impl PrintfulMapper {
             width_px: width,
             height_px: height,
             variant_external_id: None,
+            template_print_area,
         }
     }
 
@@ -177,6 +193,54 @@ impl PrintfulMapper {
             })
             .collect()
     }
+
+    /// Map a failed response to a provider error
+    ///
+    /// Printful's error envelope becomes a readable message. Error pages
+    /// that aren't JSON, such as a proxy's HTML 502, become a parse error
+    /// unless the status alone says what went wrong.
+    pub fn map_error(status: u16, retry_after: Option<u64>, body: &str) -> ProviderError {
+        let description = match serde_json::from_str::<PrintfulErrorResponse>(body) {
+            Ok(envelope) => Ok(envelope.to_string()),
+            Err(_) => Err(error_page_summary(body)),
+        };
+
+        match (status, description) {
+            (429, _) => ProviderError::RateLimited {
+                retry_after_secs: retry_after.unwrap_or(DEFAULT_RETRY_AFTER_SECS),
+            },
+            (401 | 403, Ok(message) | Err(message)) => ProviderError::AuthFailed(message),
+            (404, Ok(message) | Err(message)) => ProviderError::NotFound(message),
+            (_, Ok(message)) => ProviderError::ApiError { status, message },
+            (_, Err(summary)) if serde_json::from_str::<serde_json::Value>(body).is_ok() => {
+                ProviderError::ApiError {
+                    status,
+                    message: summary,
+                }
+            }
+            (_, Err(summary)) => ProviderError::ParseError(format!(
+                "Printful returned a non-JSON error page (HTTP {}): {}",
+                status, summary
+            )),
+        }
+    }
+}
+
+/// Short description of an error body Printful's envelope didn't fit
+///
+/// Uses an HTML page's title, otherwise the start of the body.
+fn error_page_summary(body: &str) -> String {
+    let lower = body.to_ascii_lowercase();
+    let title = lower.find("<title>").and_then(|start| {
+        let start = start + "<title>".len();
+        let end = start + lower[start..].find("</title>")?;
+        Some(body[start..end].trim())
+    });
+
+    match title {
+        Some(title) if !title.is_empty() => title.to_string(),
+        _ => body.trim().chars().take(200).collect(),
+    }
 }
 
 #[cfg(test)]
@@ -234,4 +298,133 @@ mod tests {
         assert_eq!(unified.price_cents, Some(1250));
         assert!(unified.in_stock);
     }
+
+    const NOT_FOUND: &str = r#"{
+        "code": 404,
+        "result": "Not Found",
+        "error": {"reason": "NotFound", "message": "Product not found"}
+    }"#;
+
+    const UNAUTHORIZED: &str = r#"{
+        "code": 401,
+        "result": "The access token provided is invalid.",
+        "error": {"reason": "Unauthorized", "message": "The access token provided is invalid."}
+    }"#;
+
+    const VALIDATION: &str = r#"{
+        "code": 400,
+        "result": "Invalid request",
+        "error": {
+            "reason": "BadRequest",
+            "message": "Invalid request",
+            "details": {"variant_ids": ["Must not be empty"], "format": "Unknown format"}
+        }
+    }"#;
+
+    const VALIDATION_LIST: &str = r#"{
+        "code": 400,
+        "result": "Invalid request",
+        "error": {
+            "reason": "BadRequest",
+            "message": "Invalid request",
+            "details": [{"field": "files[0].placement", "message": "Unknown placement"}]
+        }
+    }"#;
+
+    const RESULT_ONLY: &str = r#"{"code": 500, "result": "Internal server error"}"#;
+
+    const HTML_PAGE: &str = "<!DOCTYPE html>\n<html><head>\
+        <title>502 Bad Gateway</title></head>\
+        <body><center><h1>502 Bad Gateway</h1></center></body></html>";
+
+    #[test]
+    fn test_map_error_not_found() {
+        let error = PrintfulMapper::map_error(404, None, NOT_FOUND);
+        assert!(matches!(
+            &error,
+            ProviderError::NotFound(message) if message == "Product not found (NotFound)"
+        ));
+        assert_eq!(error.to_string(), "Not found: Product not found (NotFound)");
+    }
+
+    #[test]
+    fn test_map_error_auth() {
+        for status in [401, 403] {
+            let error = PrintfulMapper::map_error(status, None, UNAUTHORIZED);
+            assert!(matches!(
+                error,
+                ProviderError::AuthFailed(message)
+                    if message == "The access token provided is invalid. (Unauthorized)"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_map_error_rate_limited() {
+        let error = PrintfulMapper::map_error(429, Some(17), RESULT_ONLY);
+        assert!(matches!(
+            error,
+            ProviderError::RateLimited {
+                retry_after_secs: 17
+            }
+        ));
+
+        let error = PrintfulMapper::map_error(429, None, "");
+        assert!(matches!(
+            error,
+            ProviderError::RateLimited {
+                retry_after_secs: DEFAULT_RETRY_AFTER_SECS
+            }
+        ));
+    }
+
+    #[test]
+    fn test_map_error_field_details() {
+        let error = PrintfulMapper::map_error(400, None, VALIDATION);
+        assert!(matches!(
+            error,
+            ProviderError::ApiError { status: 400, message }
+                if message == "Invalid request (BadRequest) - \
+                    format: Unknown format; variant_ids: Must not be empty"
+        ));
+
+        let error = PrintfulMapper::map_error(400, None, VALIDATION_LIST);
+        assert!(matches!(
+            error,
+            ProviderError::ApiError { status: 400, message }
+                if message == "Invalid request (BadRequest) - files[0].placement: Unknown placement"
+        ));
+    }
+
+    #[test]
+    fn test_map_error_result_only() {
+        let error = PrintfulMapper::map_error(500, None, RESULT_ONLY);
+        assert!(matches!(
+            error,
+            ProviderError::ApiError { status: 500, message } if message == "Internal server error"
+        ));
+    }
+
+    #[test]
+    fn test_map_error_html_page() {
+        let error = PrintfulMapper::map_error(502, None, HTML_PAGE);
+        assert!(matches!(
+            error,
+            ProviderError::ParseError(message)
+                if message == "Printful returned a non-JSON error page (HTTP 502): 502 Bad Gateway"
+        ));
+
+        // The status still says what a 404 page means
+        let error = PrintfulMapper::map_error(404, None, "<html><body>Gone</body></html>");
+        assert!(matches!(error, ProviderError::NotFound(_)));
+    }
+
+    #[test]
+    fn test_map_error_unknown_json() {
+        let error = PrintfulMapper::map_error(500, None, r#"{"message": "boom"}"#);
+        assert!(matches!(
+            error,
+            ProviderError::ApiError { status: 500, message } if message == r#"{"message": "boom"}"#
+        ));
+    }
 }
@@ -143,8 +143,8 @@ pub struct PrintfulMockupTemplate {
     pub template_height: Option<i32>,
 }
 
-/// Template print area positions
-#[derive(Debug, Clone, Deserialize)]
+/// Template print area positions, also used to position files in mockup tasks
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrintfulTemplatePositions {
     pub area_width: i32,
     pub area_height: i32,
@@ -154,6 +154,46 @@ pub struct PrintfulTemplatePositions {
     pub left: i32,
 }
 
+// ============================================================================
+// Mockup Generation Tasks
+// ============================================================================
+
+/// Request body for POST /mockup-generator/create-task/{id}
+#[derive(Debug, Serialize)]
+pub struct PrintfulMockupTaskRequest {
+    pub variant_ids: Vec<i64>,
+    pub format: String,
+    pub files: Vec<PrintfulMockupFile>,
+}
+
+/// Design file placed in a mockup task
+#[derive(Debug, Serialize)]
+pub struct PrintfulMockupFile {
+    pub placement: String,
+    pub image_url: String,
+    pub position: PrintfulTemplatePositions,
+}
+
+/// Mockup task state (from create-task and /mockup-generator/task)
+#[derive(Debug, Deserialize)]
+pub struct PrintfulMockupTask {
+    pub task_key: String,
+    /// pending, completed, or failed
+    pub status: String,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub mockups: Vec<PrintfulGeneratedMockup>,
+}
+
+/// Mockup rendered by a completed task
+#[derive(Debug, Deserialize)]
+pub struct PrintfulGeneratedMockup {
+    pub placement: String,
+    #[serde(default)]
+    pub variant_ids: Vec<i64>,
+    pub mockup_url: String,
+}
+
 // ============================================================================
 // Categories
 // ============================================================================
@@ -167,6 +207,32 @@ pub struct PrintfulCategory {
     pub image_url: Option<String>,
 }
 
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Envelope of a failed Printful request
+#[derive(Debug, Deserialize)]
+pub struct PrintfulErrorResponse {
+    pub code: u16,
+    /// Short description of the failure, usually a string
+    #[serde(default)]
+    pub result: serde_json::Value,
+    #[serde(default)]
+    pub error: Option<PrintfulErrorDetail>,
+}
+
+/// Reason and message of a failed request
+#[derive(Debug, Deserialize)]
+pub struct PrintfulErrorDetail {
+    /// Machine-readable reason, e.g. `NotFound`
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    /// Per-field validation errors, keyed by field or as a list
+    #[serde(default)]
+    pub details: Option<serde_json::Value>,
+}
+
 // ============================================================================
 // Serialization Helpers
 // ============================================================================
@@ -191,3 +257,83 @@ impl PrintfulVariant {
             .and_then(|p| p.parse::<f64>().ok().map(|f| (f * 100.0) as i32))
     }
 }
+
+impl PrintfulErrorResponse {
+    /// Machine-readable reason, when Printful gave one
+    pub fn reason(&self) -> Option<&str> {
+        self.error.as_ref().and_then(|e| e.reason.as_deref())
+    }
+
+    /// Human-readable description of the failure
+    pub fn message(&self) -> String {
+        self.error
+            .as_ref()
+            .and_then(|e| e.message.clone())
+            .or_else(|| self.result.as_str().map(str::to_string))
+            .unwrap_or_else(|| format!("HTTP {}", self.code))
+    }
+
+    /// Validation errors as (field, message) pairs
+    pub fn field_errors(&self) -> Vec<(String, String)> {
+        let details = match self.error.as_ref().and_then(|e| e.details.as_ref()) {
+            Some(details) => details,
+            None => return Vec::new(),
+        };
+
+        match details {
+            serde_json::Value::Object(fields) => fields
+                .iter()
+                .flat_map(|(field, messages)| {
+                    detail_messages(messages)
+                        .into_iter()
+                        .map(move |message| (field.clone(), message))
+                })
+                .collect(),
+            serde_json::Value::Array(items) => items
+                .iter()
+                .filter_map(|item| {
+                    let field = item.get("field")?.as_str()?;
+                    let message = item.get("message")?.as_str()?;
+                    Some((field.to_string(), message.to_string()))
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for PrintfulErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = self.message();
+        write!(f, "{}", message)?;
+
+        // "Not Found" with reason "NotFound" says nothing new
+        if let Some(reason) = self.reason() {
+            if !message.replace(' ', "").eq_ignore_ascii_case(reason) {
+                write!(f, " ({})", reason)?;
+            }
+        }
+
+        let fields = self.field_errors();
+        if !fields.is_empty() {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(field, message)| format!("{}: {}", field, message))
+                .collect();
+            write!(f, " - {}", fields.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Messages of one field's validation errors, a string or a list of them
+fn detail_messages(value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::String(message) => vec![message.clone()],
+        serde_json::Value::Array(messages) => messages
+            .iter()
+            .filter_map(|m| m.as_str().map(str::to_string))
+            .collect(),
+        other => vec![other.to_string()],
+    }
+}
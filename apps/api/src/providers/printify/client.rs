@@ -89,7 +89,11 @@ impl PodProvider for PrintifyProvider {
     }
 
     fn rate_limit(&self) -> u32 {
-        600
+        self.client.rate_limit_per_minute()
+    }
+
+    fn set_rate_limit(&mut self, per_minute: u32) {
+        self.client.set_rate_limit(per_minute);
     }
 
     async fn authenticate(&mut self) -> ProviderResult<()> {
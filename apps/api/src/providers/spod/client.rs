@@ -13,6 +13,7 @@ use super::mapper::SpodMapper;
 use super::models::*;
 use crate::domain::catalog::{MockupAsset, UnifiedPrintArea, UnifiedProduct, UnifiedVariant};
 use crate::providers::http_client::RateLimitedClient;
+use crate::providers::oauth::OAuthSession;
 use crate::providers::traits::{
     CatalogPage, PodProvider, ProviderCredentials, ProviderError, ProviderResult,
 };
@@ -22,8 +23,8 @@ pub struct SpodProvider {
     /// Rate-limited HTTP client
     client: RateLimitedClient,
 
-    /// Bearer access token
-    access_token: Option<String>,
+    /// Bearer access token, refreshed when it expires
+    auth: OAuthSession,
 
     /// API base URL
     base_url: String,
@@ -37,23 +38,24 @@ impl SpodProvider {
     pub fn new(credentials: ProviderCredentials) -> Self {
         SpodProvider {
             client: RateLimitedClient::new(200), // 200 req/min
-            access_token: credentials.access_token,
+            // SPOD has no public token endpoint; set SPOD_TOKEN_URL to refresh
+            auth: OAuthSession::new(&credentials, None),
             base_url: "https://api.spod.com/api/v1".to_string(),
             authenticated: false,
         }
     }
 
     /// Make an authenticated GET request
+    ///
+    /// A rejected token is refreshed once, when refreshing is configured.
     async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> ProviderResult<T> {
-        let token = self
-            .access_token
-            .as_ref()
-            .ok_or_else(|| ProviderError::AuthFailed("No access token configured".to_string()))?;
-
         let url = format!("{}{}", self.base_url, path);
         debug!(url = %url, "SPOD API request");
 
-        let response = self.client.get(&url).bearer_auth(token).send().await?;
+        let response = self
+            .auth
+            .send(|token| self.client.get(&url).bearer_auth(token))
+            .await?;
 
         let status = response.status();
         if !status.is_success() {
@@ -90,11 +92,15 @@ impl PodProvider for SpodProvider {
     }
 
     fn rate_limit(&self) -> u32 {
-        200
+        self.client.rate_limit_per_minute()
+    }
+
+    fn set_rate_limit(&mut self, per_minute: u32) {
+        self.client.set_rate_limit(per_minute);
     }
 
     async fn authenticate(&mut self) -> ProviderResult<()> {
-        if self.access_token.is_none() {
+        if !self.auth.is_configured() {
             return Err(ProviderError::NotConfigured(
                 "SPOD_ACCESS_TOKEN environment variable not set".to_string(),
             ));
@@ -118,12 +124,17 @@ impl PodProvider for SpodProvider {
     }
 
     fn is_authenticated(&self) -> bool {
-        self.authenticated && self.access_token.is_some()
+        self.authenticated && self.auth.is_valid()
     }
 
     async fn refresh_auth(&mut self) -> ProviderResult<()> {
-        // SPOD tokens don't auto-expire, re-validate
-        self.authenticate().await
+        if !self.auth.can_refresh() {
+            // Without a refresh token, re-validate the one we have
+            return self.authenticate().await;
+        }
+        self.auth.refresh().await?;
+        self.authenticated = true;
+        Ok(())
     }
 
     async fn get_products(
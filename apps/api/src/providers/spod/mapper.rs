@@ -152,6 +152,7 @@ impl SpodMapper {
                 width_px: image.width,
                 height_px: image.height,
                 variant_external_id: Some(appearance.id.to_string()),
+                template_print_area: None,
             })
             .collect()
     }
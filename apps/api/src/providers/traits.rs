@@ -5,8 +5,10 @@
 //! provide a unified interface for catalog access.
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::ToSchema;
 
 use crate::domain::catalog::{MockupAsset, UnifiedPrintArea, UnifiedProduct, UnifiedVariant};
 
@@ -38,6 +40,9 @@ pub enum ProviderError {
     #[error("Provider not configured: {0}")]
     NotConfigured(String),
 
+    #[error("Not supported: {0}")]
+    Unsupported(String),
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
@@ -50,7 +55,7 @@ pub type ProviderResult<T> = Result<T, ProviderError>;
 // ============================================================================
 
 /// Catalog page for paginated results
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CatalogPage<T> {
     pub items: Vec<T>,
     pub total: u64,
@@ -108,6 +113,12 @@ pub struct ProviderCredentials {
 
     /// OAuth client secret (for refreshing tokens)
     pub client_secret: Option<String>,
+
+    /// OAuth token endpoint, for providers without a default one
+    pub token_url: Option<String>,
+
+    /// When `access_token` expires, if it does
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 impl ProviderCredentials {
@@ -121,12 +132,22 @@ impl ProviderCredentials {
             refresh_token: std::env::var(format!("{}_REFRESH_TOKEN", prefix)).ok(),
             client_id: std::env::var(format!("{}_CLIENT_ID", prefix)).ok(),
             client_secret: std::env::var(format!("{}_CLIENT_SECRET", prefix)).ok(),
+            token_url: std::env::var(format!("{}_TOKEN_URL", prefix)).ok(),
+            expires_at: std::env::var(format!("{}_TOKEN_EXPIRES_AT", prefix))
+                .ok()
+                .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+                .map(|at| at.with_timezone(&Utc)),
         }
     }
 
     /// Check if any credentials are configured
+    ///
+    /// A refresh token counts, since it can be exchanged for an access token.
     pub fn is_configured(&self) -> bool {
-        self.access_token.is_some() || self.api_key.is_some() || self.recipe_id.is_some()
+        self.access_token.is_some()
+            || self.api_key.is_some()
+            || self.recipe_id.is_some()
+            || self.refresh_token.is_some()
     }
 }
 
@@ -139,6 +160,8 @@ impl Default for ProviderCredentials {
             refresh_token: None,
             client_id: None,
             client_secret: None,
+            token_url: None,
+            expires_at: None,
         }
     }
 }
@@ -165,6 +188,9 @@ pub trait PodProvider: Send + Sync {
     /// Rate limit (requests per minute)
     fn rate_limit(&self) -> u32;
 
+    /// Override the built-in rate limit, e.g. with the one stored for the provider
+    fn set_rate_limit(&mut self, _per_minute: u32) {}
+
     /// Authenticate with the provider
     ///
     /// This should validate credentials and obtain any necessary tokens.
@@ -219,6 +245,29 @@ pub trait PodProvider: Send + Sync {
         variant_external_id: Option<&str>,
     ) -> ProviderResult<Vec<MockupAsset>>;
 
+    /// Have the provider's mockup generator render `design_url` over the whole
+    /// print area of a placement, returning the URL of the rendered preview
+    ///
+    /// Providers without a mockup generator API return `Unsupported`.
+    ///
+    /// # Arguments
+    /// * `product_external_id` - Provider's product ID
+    /// * `variant_external_id` - Provider's variant ID
+    /// * `placement` - Placement name (e.g., "front")
+    /// * `design_url` - Publicly reachable design image
+    async fn generate_preview(
+        &self,
+        _product_external_id: &str,
+        _variant_external_id: &str,
+        _placement: &str,
+        _design_url: &str,
+    ) -> ProviderResult<String> {
+        Err(ProviderError::Unsupported(format!(
+            "{} has no mockup generator API",
+            self.name()
+        )))
+    }
+
     /// Get rate limit status (remaining requests in current window)
     fn rate_limit_remaining(&self) -> Option<u32>;
 }
@@ -227,6 +276,9 @@ pub trait PodProvider: Send + Sync {
 // Provider Factory
 // ============================================================================
 
+/// Codes of every provider the factory knows how to build
+pub const PROVIDER_CODES: [&str; 5] = ["printful", "printify", "gelato", "spod", "gooten"];
+
 /// Provider factory for creating provider instances
 pub struct ProviderFactory;
 
@@ -262,10 +314,9 @@ impl ProviderFactory {
 
     /// Create all configured providers from environment variables
     pub fn create_all_from_env() -> Vec<Box<dyn PodProvider>> {
-        let provider_codes = ["printful", "printify", "gelato", "spod", "gooten"];
         let mut providers = Vec::new();
 
-        for code in provider_codes {
+        for code in PROVIDER_CODES {
             let credentials = ProviderCredentials::from_env(code);
             if credentials.is_configured() {
                 if let Some(provider) = Self::create(code, credentials) {
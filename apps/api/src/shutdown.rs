@@ -0,0 +1,183 @@
+//! Graceful shutdown
+//!
+//! On SIGTERM or SIGINT the service drains before it stops. Health checks
+//! return 503 so the load balancer takes the instance out of rotation, and new
+//! generations are refused. After the drain delay the listeners close and
+//! in-flight requests get the server's shutdown timeout to finish. Background
+//! work started through [`Shutdown::spawn`], such as usage logging and sync
+//! runs, is then awaited before the process exits.
+
+use actix_web::dev::ServerHandle;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// Drain state and the background tasks to wait for on exit
+#[derive(Default)]
+pub struct Shutdown {
+    draining: AtomicBool,
+    tasks: AtomicUsize,
+    idle: Notify,
+}
+
+/// Counts a tracked task as running until it finishes or is dropped
+struct TaskGuard(Arc<Shutdown>);
+
+impl TaskGuard {
+    fn new(shutdown: Arc<Shutdown>) -> Self {
+        shutdown.tasks.fetch_add(1, Ordering::SeqCst);
+        TaskGuard(shutdown)
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if self.0.tasks.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl Shutdown {
+    /// Whether a shutdown has started
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Mark the service as draining
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    /// Background tasks still running
+    pub fn tasks_running(&self) -> usize {
+        self.tasks.load(Ordering::SeqCst)
+    }
+
+    /// Spawn a background task the process waits for before exiting
+    pub fn spawn<F>(self: &Arc<Self>, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let guard = TaskGuard::new(self.clone());
+        tokio::spawn(async move {
+            let _guard = guard;
+            task.await
+        })
+    }
+
+    /// Wait up to `timeout` for background tasks to finish
+    ///
+    /// Returns false if some were still running when the time ran out.
+    pub async fn wait_for_tasks(&self, timeout: Duration) -> bool {
+        let all_done = async {
+            loop {
+                // Registered before the check, so a task finishing in between still wakes it
+                let idle = self.idle.notified();
+                if self.tasks_running() == 0 {
+                    return;
+                }
+                idle.await;
+            }
+        };
+        tokio::time::timeout(timeout, all_done).await.is_ok()
+    }
+
+    /// Drain for `drain_delay`, then stop the server once in-flight requests finish
+    ///
+    /// Requests still running after the server's shutdown timeout are dropped.
+    pub async fn drain_and_stop(&self, server: ServerHandle, drain_delay: Duration) {
+        self.start_draining();
+        info!(
+            drain_delay_secs = drain_delay.as_secs(),
+            "Draining before shutdown"
+        );
+        tokio::time::sleep(drain_delay).await;
+        server.stop(true).await;
+    }
+}
+
+/// Resolve on SIGTERM or SIGINT
+pub async fn termination_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate =
+            signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    #[tokio::test]
+    async fn test_wait_for_tasks() {
+        let shutdown = Arc::new(Shutdown::default());
+        assert!(shutdown.wait_for_tasks(Duration::ZERO).await);
+
+        shutdown.spawn(tokio::time::sleep(Duration::from_millis(20)));
+        let stuck = shutdown.spawn(std::future::pending::<()>());
+        assert_eq!(shutdown.tasks_running(), 2);
+        assert!(!shutdown.wait_for_tasks(Duration::from_millis(50)).await);
+        assert_eq!(shutdown.tasks_running(), 1);
+
+        // An aborted task no longer holds up the exit
+        stuck.abort();
+        assert!(shutdown.wait_for_tasks(Duration::from_secs(1)).await);
+    }
+
+    #[actix_web::test]
+    async fn test_in_flight_request_finishes_during_shutdown() {
+        let server = HttpServer::new(|| {
+            App::new().route(
+                "/slow",
+                web::get().to(|| async {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    HttpResponse::Ok().body("done")
+                }),
+            )
+        })
+        .workers(1)
+        .disable_signals()
+        .shutdown_timeout(5)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        let running = actix_web::rt::spawn(server);
+
+        let request = tokio::spawn(async move {
+            reqwest::get(format!("http://{}/slow", addr))
+                .await?
+                .text()
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let shutdown = Shutdown::default();
+        shutdown.drain_and_stop(handle, Duration::ZERO).await;
+        assert!(shutdown.is_draining());
+        assert_eq!(request.await.unwrap().unwrap(), "done");
+        running.await.unwrap().unwrap();
+
+        // The listener is closed once the server has stopped
+        assert!(reqwest::get(format!("http://{}/slow", addr)).await.is_err());
+    }
+}
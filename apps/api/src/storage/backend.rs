@@ -0,0 +1,134 @@
+//! Pluggable object storage
+//!
+//! Provider assets and generated mockups are stored through `StorageBackend`.
+//! `R2Client` implements it for Cloudflare R2 and AWS S3, and
+//! `LocalDiskStorage` for a directory on the host. `storage.backend` picks one.
+
+use async_trait::async_trait;
+
+use super::r2::{AssetPath, R2Client, R2Error, UploadResult};
+use super::usage::ObjectPage;
+use crate::config::{Settings, StorageBackendKind};
+
+/// Where objects are stored, addressed by `/`-separated keys
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Name of the bucket, or directory, objects are stored in
+    fn bucket(&self) -> &str;
+
+    /// Store bytes under a raw object key
+    async fn upload_key(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<UploadResult, R2Error>;
+
+    /// Store bytes under a raw object key, rejecting them unless they match `sha256`
+    async fn upload_key_verified(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        sha256: &[u8; 32],
+    ) -> Result<UploadResult, R2Error>;
+
+    /// Read an object, `R2Error::NotFound` when there is none
+    async fn download(&self, key: &str) -> Result<Vec<u8>, R2Error>;
+
+    async fn exists(&self, key: &str) -> Result<bool, R2Error>;
+
+    /// Remove an object; removing a missing one succeeds
+    async fn delete(&self, key: &str) -> Result<(), R2Error>;
+
+    /// One page of objects under `prefix`, continuing from `token`
+    async fn list_page(&self, prefix: &str, token: Option<String>) -> Result<ObjectPage, R2Error>;
+
+    /// URL an object is publicly served at, when a prefix is configured
+    fn public_url(&self, key: &str) -> Option<String>;
+
+    /// Store bytes under an asset path
+    async fn upload(
+        &self,
+        path: &AssetPath,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<UploadResult, R2Error> {
+        self.upload_key(&path.to_key(), data, content_type).await
+    }
+
+    /// Store bytes under an asset path, rejecting them unless they match `sha256`
+    async fn upload_verified(
+        &self,
+        path: &AssetPath,
+        data: Vec<u8>,
+        content_type: &str,
+        sha256: &[u8; 32],
+    ) -> Result<UploadResult, R2Error> {
+        self.upload_key_verified(&path.to_key(), data, content_type, sha256)
+            .await
+    }
+}
+
+#[async_trait]
+impl StorageBackend for R2Client {
+    fn bucket(&self) -> &str {
+        R2Client::bucket(self)
+    }
+
+    async fn upload_key(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<UploadResult, R2Error> {
+        R2Client::upload_key(self, key, data, content_type).await
+    }
+
+    async fn upload_key_verified(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        sha256: &[u8; 32],
+    ) -> Result<UploadResult, R2Error> {
+        R2Client::upload_key_verified(self, key, data, content_type, sha256).await
+    }
+
+    async fn download(&self, key: &str) -> Result<Vec<u8>, R2Error> {
+        R2Client::download(self, key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, R2Error> {
+        R2Client::exists(self, key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), R2Error> {
+        R2Client::delete(self, key).await
+    }
+
+    async fn list_page(&self, prefix: &str, token: Option<String>) -> Result<ObjectPage, R2Error> {
+        R2Client::list_page(self, prefix, token).await
+    }
+
+    fn public_url(&self, key: &str) -> Option<String> {
+        R2Client::public_url(self, key)
+    }
+}
+
+/// Client of the R2 or S3 bucket `storage.backend` selects
+///
+/// `None` for the `local` backend, or when the bucket has no settings.
+pub async fn object_store_client(settings: &Settings) -> Result<Option<R2Client>, R2Error> {
+    match settings.storage.backend {
+        StorageBackendKind::R2 => match settings.r2 {
+            Some(ref r2) => R2Client::new(r2).await.map(Some),
+            None => Ok(None),
+        },
+        StorageBackendKind::S3 => match settings.storage.s3 {
+            Some(ref s3) => R2Client::s3(s3).await.map(Some),
+            None => Ok(None),
+        },
+        StorageBackendKind::Local => Ok(None),
+    }
+}
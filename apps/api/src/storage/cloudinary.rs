@@ -0,0 +1,175 @@
+//! Cloudinary uploads for generated mockups
+//!
+//! Uses the signed upload API: the parameters are sorted, joined, suffixed with
+//! the API secret, and hashed. The image is sent as a base64 data URI so no
+//! multipart body is needed.
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, instrument};
+
+use crate::config::CloudinarySettings;
+
+/// Upload requests give up after this long
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Errors that can occur during Cloudinary uploads
+#[derive(Error, Debug)]
+pub enum CloudinaryError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Cloudinary rejected the upload ({status}): {message}")]
+    Rejected { status: u16, message: String },
+}
+
+/// Where an uploaded mockup ended up
+#[derive(Debug, Clone, Deserialize)]
+pub struct CloudinaryUpload {
+    pub secure_url: String,
+    pub public_id: String,
+}
+
+#[derive(Deserialize)]
+struct CloudinaryErrorBody {
+    error: CloudinaryErrorMessage,
+}
+
+#[derive(Deserialize)]
+struct CloudinaryErrorMessage {
+    message: String,
+}
+
+/// Signed uploader for a single Cloudinary cloud
+pub struct CloudinaryUploader {
+    client: reqwest::Client,
+    cloud_name: String,
+    api_key: String,
+    api_secret: String,
+    upload_preset: Option<String>,
+}
+
+impl CloudinaryUploader {
+    /// Build an uploader, or `None` when the cloud name or credentials are missing
+    pub fn from_settings(settings: &CloudinarySettings) -> Option<Self> {
+        if settings.cloud_name.is_empty()
+            || settings.api_key.is_empty()
+            || settings.api_secret.is_empty()
+        {
+            return None;
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(UPLOAD_TIMEOUT)
+            .build()
+            .ok()?;
+
+        Some(Self {
+            client,
+            cloud_name: settings.cloud_name.clone(),
+            api_key: settings.api_key.clone(),
+            api_secret: settings.api_secret.clone(),
+            upload_preset: settings
+                .upload_preset
+                .clone()
+                .filter(|preset| !preset.is_empty()),
+        })
+    }
+
+    /// Upload an encoded image given as a data URI
+    #[instrument(skip(self, data_uri), fields(cloud = %self.cloud_name))]
+    pub async fn upload(&self, data_uri: String) -> Result<CloudinaryUpload, CloudinaryError> {
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+
+        let mut params = vec![("timestamp", timestamp)];
+        if let Some(preset) = &self.upload_preset {
+            params.push(("upload_preset", preset.clone()));
+        }
+        let signature = sign(&params, &self.api_secret);
+
+        params.push(("file", data_uri));
+        params.push(("api_key", self.api_key.clone()));
+        params.push(("signature", signature));
+        params.push(("signature_algorithm", "sha256".to_string()));
+
+        let url = format!(
+            "https://api.cloudinary.com/v1_1/{}/image/upload",
+            self.cloud_name
+        );
+        let response = self.client.post(&url).form(&params).send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let message = serde_json::from_str::<CloudinaryErrorBody>(&body)
+                .map(|b| b.error.message)
+                .unwrap_or(body);
+            return Err(CloudinaryError::Rejected {
+                status: status.as_u16(),
+                message,
+            });
+        }
+
+        let upload: CloudinaryUpload = response.json().await?;
+        debug!(public_id = %upload.public_id, "Uploaded mockup to Cloudinary");
+        Ok(upload)
+    }
+}
+
+/// Hex SHA-256 of the `&`-joined, name-sorted parameters followed by the secret
+fn sign(params: &[(&str, String)], api_secret: &str) -> String {
+    let mut sorted: Vec<_> = params.iter().collect();
+    sorted.sort_by_key(|(name, _)| *name);
+    let joined = sorted
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    hex::encode(Sha256::digest(format!("{}{}", joined, api_secret)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> CloudinarySettings {
+        CloudinarySettings {
+            cloud_name: "demo".to_string(),
+            api_key: "1234".to_string(),
+            api_secret: "secret".to_string(),
+            upload_preset: None,
+        }
+    }
+
+    #[test]
+    fn test_sign_sorts_parameters() {
+        let params = vec![
+            ("timestamp", "1315060510".to_string()),
+            ("public_id", "sample".to_string()),
+        ];
+        let expected = hex::encode(Sha256::digest(
+            "public_id=sample&timestamp=1315060510secret",
+        ));
+        assert_eq!(sign(&params, "secret"), expected);
+    }
+
+    #[test]
+    fn test_from_settings_requires_credentials() {
+        assert!(CloudinaryUploader::from_settings(&settings()).is_some());
+
+        let mut missing = settings();
+        missing.api_secret.clear();
+        assert!(CloudinaryUploader::from_settings(&missing).is_none());
+    }
+
+    #[test]
+    fn test_empty_upload_preset_is_ignored() {
+        let mut with_preset = settings();
+        with_preset.upload_preset = Some(String::new());
+        let uploader = CloudinaryUploader::from_settings(&with_preset).unwrap();
+        assert!(uploader.upload_preset.is_none());
+    }
+}
@@ -0,0 +1,452 @@
+//! Resumable HTTP downloads for asset mirroring
+//!
+//! Interrupted transfers are retried with backoff and resumed with a `Range`
+//! request instead of starting over. `If-Range` carries the first response's
+//! validator, so a file that changed between attempts is downloaded again in
+//! full rather than stitched together from two versions.
+
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// Log progress every time this many more bytes have arrived
+const PROGRESS_LOG_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Errors that end a download
+#[derive(Debug, Error)]
+pub enum DownloadError {
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Rate limited, retry after {retry_after_secs} seconds")]
+    RateLimited { retry_after_secs: u64 },
+
+    #[error("{0}")]
+    Http(String),
+
+    #[error("Gave up after {attempts} attempts: {reason}")]
+    Exhausted { attempts: u32, reason: String },
+
+    #[error("Size mismatch: expected {expected} bytes, got {actual}")]
+    SizeMismatch { expected: u64, actual: u64 },
+}
+
+/// How often and how patiently to retry an interrupted download
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the attempt after `attempt`, doubling each time
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// A completed, size-verified download
+#[derive(Debug)]
+pub struct Downloaded {
+    pub data: Vec<u8>,
+    pub content_type: String,
+    /// SHA-256 of `data`, for verifying the upload
+    pub sha256: [u8; 32],
+    pub attempts: u32,
+}
+
+/// Download `url`, resuming from the last received byte after interruptions
+pub async fn download_resumable(
+    client: &reqwest::Client,
+    url: &str,
+    policy: &RetryPolicy,
+) -> Result<Downloaded, DownloadError> {
+    let mut transfer = Transfer::new(url);
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+        match transfer.attempt(client).await {
+            Ok(()) => break,
+            Err(AttemptError::Fatal(e)) => {
+                MIRROR_STATS.failures_total.fetch_add(1, Ordering::Relaxed);
+                return Err(e);
+            }
+            Err(AttemptError::Retry(reason)) => {
+                if attempt >= policy.max_attempts.max(1) {
+                    MIRROR_STATS.failures_total.fetch_add(1, Ordering::Relaxed);
+                    return Err(DownloadError::Exhausted {
+                        attempts: attempt,
+                        reason,
+                    });
+                }
+                MIRROR_STATS.retries_total.fetch_add(1, Ordering::Relaxed);
+                let delay = policy.backoff(attempt);
+                warn!(
+                    url,
+                    attempt,
+                    downloaded = transfer.data.len(),
+                    error = %reason,
+                    delay_ms = delay.as_millis() as u64,
+                    "Download interrupted; retrying"
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    let actual = transfer.data.len() as u64;
+    if let Some(expected) = transfer.expected_len.filter(|&expected| expected != actual) {
+        MIRROR_STATS.failures_total.fetch_add(1, Ordering::Relaxed);
+        return Err(DownloadError::SizeMismatch { expected, actual });
+    }
+
+    let sha256 = Sha256::digest(&transfer.data).into();
+    Ok(Downloaded {
+        content_type: transfer
+            .content_type
+            .take()
+            .unwrap_or_else(|| "image/png".to_string()),
+        data: std::mem::take(&mut transfer.data),
+        sha256,
+        attempts: attempt,
+    })
+}
+
+enum AttemptError {
+    /// Worth retrying; keep what has been received
+    Retry(String),
+    Fatal(DownloadError),
+}
+
+/// State carried across attempts of one download
+struct Transfer<'a> {
+    url: &'a str,
+    data: Vec<u8>,
+    /// Total size announced by the server
+    expected_len: Option<u64>,
+    /// ETag or Last-Modified of the first response, sent as `If-Range`
+    validator: Option<String>,
+    content_type: Option<String>,
+    progress: Progress,
+}
+
+impl<'a> Transfer<'a> {
+    fn new(url: &'a str) -> Self {
+        Self {
+            url,
+            data: Vec::new(),
+            expected_len: None,
+            validator: None,
+            content_type: None,
+            progress: Progress::start(),
+        }
+    }
+
+    async fn attempt(&mut self, client: &reqwest::Client) -> Result<(), AttemptError> {
+        let resume_from = self.data.len() as u64;
+        let mut request = client.get(self.url);
+        if resume_from > 0 {
+            request = request.header(RANGE, format!("bytes={}-", resume_from));
+            if let Some(validator) = &self.validator {
+                request = request.header(IF_RANGE, validator.as_str());
+            }
+        }
+
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| AttemptError::Retry(e.to_string()))?;
+        let status = response.status();
+
+        match status {
+            StatusCode::PARTIAL_CONTENT => {
+                let range = response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_content_range);
+                match range {
+                    Some((start, total)) if start == resume_from => {
+                        if total.is_some() {
+                            self.expected_len = total;
+                        }
+                        if self.validator.is_none() {
+                            self.validator = validator(response.headers());
+                        }
+                        if resume_from > 0 {
+                            MIRROR_STATS
+                                .resumed_bytes_total
+                                .fetch_add(resume_from, Ordering::Relaxed);
+                            debug!(url = self.url, resume_from, "Resuming download");
+                        }
+                    }
+                    _ => {
+                        self.restart();
+                        return Err(AttemptError::Retry(
+                            "Server returned an unexpected range".to_string(),
+                        ));
+                    }
+                }
+            }
+            status if status.is_success() => {
+                // A full body: the server ignored the range or the file changed
+                self.restart();
+                self.expected_len = response.content_length();
+                self.validator = validator(response.headers());
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE if self.expected_len == Some(resume_from) => {
+                return Ok(());
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                self.restart();
+                return Err(AttemptError::Retry("Range not satisfiable".to_string()));
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after_secs = response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(60);
+                return Err(AttemptError::Fatal(DownloadError::RateLimited {
+                    retry_after_secs,
+                }));
+            }
+            StatusCode::NOT_FOUND => {
+                return Err(AttemptError::Fatal(DownloadError::NotFound(
+                    self.url.to_string(),
+                )));
+            }
+            status if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT => {
+                return Err(AttemptError::Retry(format!("HTTP {}", status)));
+            }
+            status => {
+                return Err(AttemptError::Fatal(DownloadError::Http(format!(
+                    "HTTP {} from {}",
+                    status, self.url
+                ))));
+            }
+        }
+
+        if self.content_type.is_none() {
+            self.content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+        }
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AttemptError::Retry(e.to_string()))?
+        {
+            self.data.extend_from_slice(&chunk);
+            self.progress
+                .advance(chunk.len() as u64, self.url, self.expected_len);
+        }
+
+        Ok(())
+    }
+
+    /// Drop received bytes so the next attempt starts from zero
+    fn restart(&mut self) {
+        self.data.clear();
+        self.expected_len = None;
+        self.progress.rewind();
+    }
+}
+
+/// Strong validator for `If-Range`; weak ETags are not allowed there
+fn validator(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let etag = headers
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"));
+    etag.or_else(|| headers.get(LAST_MODIFIED).and_then(|v| v.to_str().ok()))
+        .map(String::from)
+}
+
+/// Parse `bytes start-end/total` into the start offset and total size
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-')?.0.trim().parse().ok()?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((start, total))
+}
+
+/// Process-wide mirror download counters, exported on `/metrics`
+struct MirrorStats {
+    transfers_in_progress: AtomicU64,
+    bytes_in_progress: AtomicU64,
+    retries_total: AtomicU64,
+    resumed_bytes_total: AtomicU64,
+    failures_total: AtomicU64,
+}
+
+static MIRROR_STATS: MirrorStats = MirrorStats {
+    transfers_in_progress: AtomicU64::new(0),
+    bytes_in_progress: AtomicU64::new(0),
+    retries_total: AtomicU64::new(0),
+    resumed_bytes_total: AtomicU64::new(0),
+    failures_total: AtomicU64::new(0),
+};
+
+/// Snapshot of mirror download activity
+#[derive(Debug, Clone, Copy)]
+pub struct MirrorStatsSnapshot {
+    /// Downloads currently running
+    pub transfers_in_progress: u64,
+    /// Bytes received so far by running downloads
+    pub bytes_in_progress: u64,
+    pub retries_total: u64,
+    /// Bytes not downloaded again thanks to resuming
+    pub resumed_bytes_total: u64,
+    pub failures_total: u64,
+}
+
+pub fn mirror_stats() -> MirrorStatsSnapshot {
+    MirrorStatsSnapshot {
+        transfers_in_progress: MIRROR_STATS.transfers_in_progress.load(Ordering::Relaxed),
+        bytes_in_progress: MIRROR_STATS.bytes_in_progress.load(Ordering::Relaxed),
+        retries_total: MIRROR_STATS.retries_total.load(Ordering::Relaxed),
+        resumed_bytes_total: MIRROR_STATS.resumed_bytes_total.load(Ordering::Relaxed),
+        failures_total: MIRROR_STATS.failures_total.load(Ordering::Relaxed),
+    }
+}
+
+/// One running download's share of the in-progress gauges
+struct Progress {
+    bytes: u64,
+    next_log: u64,
+}
+
+impl Progress {
+    fn start() -> Self {
+        MIRROR_STATS
+            .transfers_in_progress
+            .fetch_add(1, Ordering::Relaxed);
+        Self {
+            bytes: 0,
+            next_log: PROGRESS_LOG_BYTES,
+        }
+    }
+
+    fn advance(&mut self, bytes: u64, url: &str, expected_len: Option<u64>) {
+        self.bytes += bytes;
+        MIRROR_STATS
+            .bytes_in_progress
+            .fetch_add(bytes, Ordering::Relaxed);
+        if self.bytes >= self.next_log {
+            self.next_log = self.bytes + PROGRESS_LOG_BYTES;
+            debug!(url, downloaded = self.bytes, expected = ?expected_len, "Download progress");
+        }
+    }
+
+    fn rewind(&mut self) {
+        MIRROR_STATS
+            .bytes_in_progress
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+        self.bytes = 0;
+        self.next_log = PROGRESS_LOG_BYTES;
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.rewind();
+        MIRROR_STATS
+            .transfers_in_progress
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range("bytes 4-9/10"), Some((4, Some(10))));
+        assert_eq!(parse_content_range("bytes 0-99/*"), Some((0, None)));
+        assert_eq!(parse_content_range("bytes */10"), None);
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+    }
+
+    #[tokio::test]
+    async fn test_resumes_after_interrupted_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/asset.png", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in [
+                // Promises 10 bytes, sends 4, then drops the connection
+                &b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nETag: \"v1\"\r\n\r\n0123"[..],
+                &b"HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\nContent-Range: bytes 4-9/10\r\n\r\n456789"[..],
+            ] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+                socket.write_all(response).await.unwrap();
+            }
+            requests
+        });
+
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let downloaded = download_resumable(&reqwest::Client::new(), &url, &policy)
+            .await
+            .unwrap();
+
+        assert_eq!(downloaded.data, b"0123456789");
+        assert_eq!(downloaded.attempts, 2);
+        assert_eq!(
+            downloaded.sha256,
+            <[u8; 32]>::from(Sha256::digest(b"0123456789"))
+        );
+
+        let requests = server.await.unwrap();
+        assert!(requests[1].contains("range: bytes=4-"));
+        assert!(requests[1].contains("if-range: \"v1\""));
+    }
+}
@@ -0,0 +1,484 @@
+//! Local disk storage backend
+//!
+//! Stores objects as files under a root directory, for self-hosted setups
+//! without an object store. A key is a path relative to the root; keys with
+//! empty, `.` or `..` segments, backslashes, or anything else that could
+//! resolve outside the root are refused. Segments may not start with a dot
+//! either, which keeps the temporary files uploads are written to out of
+//! listings.
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info, instrument};
+use uuid::Uuid;
+
+use super::backend::StorageBackend;
+use super::r2::{R2Error, UploadResult};
+use super::usage::{ListedObject, ObjectPage};
+
+/// Objects per listed page, as S3 returns
+const DEFAULT_PAGE_SIZE: usize = 1000;
+
+/// Objects stored in a directory on the host
+pub struct LocalDiskStorage {
+    root: PathBuf,
+    /// The root as text, reported as the bucket
+    name: String,
+    public_url_prefix: Option<String>,
+    page_size: usize,
+}
+
+impl LocalDiskStorage {
+    /// Store objects under `root`, creating the directory if needed
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self, R2Error> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            name: root.display().to_string(),
+            root,
+            public_url_prefix: None,
+            page_size: DEFAULT_PAGE_SIZE,
+        })
+    }
+
+    /// Report objects as served under `prefix`, e.g. by a reverse proxy
+    pub fn with_public_url_prefix(mut self, prefix: Option<String>) -> Self {
+        self.public_url_prefix = prefix;
+        self
+    }
+
+    /// List at most `page_size` objects per page
+    #[cfg(test)]
+    pub(crate) fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// File holding `key`, refusing keys that could leave the root
+    fn object_path(&self, key: &str) -> Result<PathBuf, R2Error> {
+        check_key(key)?;
+        Ok(self.root.join(key))
+    }
+
+    /// Write `data` beside the object and rename it into place
+    ///
+    /// Readers see the old file or the new one, never half of it.
+    async fn write(&self, key: &str, data: &[u8]) -> Result<(), R2Error> {
+        let path = self.object_path(key)?;
+        let dir = path.parent().unwrap_or(&self.root);
+        tokio::fs::create_dir_all(dir).await?;
+
+        let temp = dir.join(format!(".{}.tmp", Uuid::new_v4()));
+        let written = match tokio::fs::write(&temp, data).await {
+            Ok(()) => tokio::fs::rename(&temp, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+/// Refuse keys that aren't a plain relative path of visible segments
+fn check_key(key: &str) -> Result<(), R2Error> {
+    let invalid = |reason: &str| Err(R2Error::InvalidPath(format!("{}: {:?}", reason, key)));
+
+    if key.is_empty() {
+        return invalid("Empty key");
+    }
+    if key.contains(['\\', '\0']) {
+        return invalid("Backslash or NUL in key");
+    }
+    if key.split('/').any(|segment| segment.is_empty()) {
+        return invalid("Empty path segment in key");
+    }
+    if key.split('/').any(|segment| segment.starts_with('.')) {
+        return invalid("Dot segment in key");
+    }
+    // Drive prefixes and the like, on platforms that have them
+    if !Path::new(key)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return invalid("Key is not a relative path");
+    }
+    Ok(())
+}
+
+/// The keys one listed page wants: those starting with `prefix` that sort
+/// after `after`, up to `limit` of them
+struct KeyRange<'a> {
+    prefix: &'a str,
+    after: Option<&'a str>,
+    limit: usize,
+}
+
+impl KeyRange<'_> {
+    fn wants(&self, key: &str) -> bool {
+        key.starts_with(self.prefix) && self.after.map_or(true, |after| key > after)
+    }
+
+    /// Whether any key under the folder `folder_key`, which ends in '/', can
+    /// be wanted
+    fn may_contain(&self, folder_key: &str) -> bool {
+        let overlaps = folder_key.starts_with(self.prefix) || self.prefix.starts_with(folder_key);
+        overlaps
+            && self.after.map_or(true, |after| {
+                folder_key > after || after.starts_with(folder_key)
+            })
+    }
+}
+
+/// Add the regular files under `dir` that `range` wants to `objects`, in key
+/// order, keyed under `key_prefix`
+///
+/// Hidden entries and symlinks are skipped, as are names that aren't UTF-8.
+/// Folders holding no wanted keys aren't read, and the walk stops once
+/// `objects` holds `range.limit` entries.
+fn walk(
+    dir: &Path,
+    key_prefix: &str,
+    range: &KeyRange,
+    objects: &mut Vec<ListedObject>,
+) -> io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut children = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            children.push((format!("{}{}/", key_prefix, name), entry));
+        } else if file_type.is_file() {
+            children.push((format!("{}{}", key_prefix, name), entry));
+        }
+    }
+    // Folder keys keep their trailing '/', so a folder sorts where its
+    // files' keys do: "a-b.png" before "a/x.png"
+    children.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (key, entry) in children {
+        if objects.len() >= range.limit {
+            break;
+        }
+        if key.ends_with('/') {
+            if range.may_contain(&key) {
+                walk(&entry.path(), &key, range, objects)?;
+            }
+        } else if range.wants(&key) {
+            objects.push(ListedObject {
+                key,
+                size: entry.metadata()?.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl StorageBackend for LocalDiskStorage {
+    fn bucket(&self) -> &str {
+        &self.name
+    }
+
+    #[instrument(skip(self, data), fields(size = data.len()))]
+    async fn upload_key(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<UploadResult, R2Error> {
+        let size = data.len() as u64;
+        self.write(key, &data).await?;
+        info!("Stored on disk: {} ({} bytes)", key, size);
+
+        Ok(UploadResult {
+            key: key.to_string(),
+            size,
+            content_type: content_type.to_string(),
+            etag: None,
+            public_url: self.public_url(key),
+        })
+    }
+
+    async fn upload_key_verified(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        sha256: &[u8; 32],
+    ) -> Result<UploadResult, R2Error> {
+        if Sha256::digest(&data).as_slice() != sha256.as_slice() {
+            return Err(R2Error::ChecksumMismatch(key.to_string()));
+        }
+        self.upload_key(key, data, content_type).await
+    }
+
+    #[instrument(skip(self))]
+    async fn download(&self, key: &str) -> Result<Vec<u8>, R2Error> {
+        let path = self.object_path(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(data) => {
+                debug!("Read {} bytes from disk: {}", data.len(), key);
+                Ok(data)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Err(R2Error::NotFound(key.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, R2Error> {
+        let path = self.object_path(key)?;
+        match tokio::fs::metadata(&path).await {
+            Ok(metadata) => Ok(metadata.is_file()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, key: &str) -> Result<(), R2Error> {
+        let path = self.object_path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {
+                info!("Deleted from disk: {}", key);
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Objects under `prefix` in key order, after the key `token` names
+    ///
+    /// Each page walks the prefix's folder again, in key order, skipping
+    /// folders that sort before the token and stopping one object past the
+    /// page to learn whether another follows.
+    async fn list_page(&self, prefix: &str, token: Option<String>) -> Result<ObjectPage, R2Error> {
+        let (dir, key_prefix) = match prefix.rsplit_once('/') {
+            Some((dir, _)) => {
+                check_key(dir)?;
+                (self.root.join(dir), format!("{}/", dir))
+            }
+            None => (self.root.clone(), String::new()),
+        };
+
+        let prefix = prefix.to_string();
+        let page_size = self.page_size;
+        let walked = tokio::task::spawn_blocking(move || {
+            let range = KeyRange {
+                prefix: &prefix,
+                after: token.as_deref(),
+                limit: page_size + 1,
+            };
+            let mut objects = Vec::new();
+            walk(&dir, &key_prefix, &range, &mut objects).map(|_| objects)
+        })
+        .await
+        .map_err(|e| R2Error::ListFailed(e.to_string()))?;
+        let mut objects = walked?;
+
+        let next_token = if objects.len() > page_size {
+            objects.truncate(page_size);
+            objects.last().map(|object| object.key.clone())
+        } else {
+            None
+        };
+        Ok(ObjectPage {
+            objects,
+            next_token,
+        })
+    }
+
+    fn public_url(&self, key: &str) -> Option<String> {
+        self.public_url_prefix
+            .as_ref()
+            .map(|prefix| format!("{}/{}", prefix.trim_end_matches('/'), key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage() -> LocalDiskStorage {
+        let root = std::env::temp_dir().join(format!("storage-{}", Uuid::new_v4()));
+        LocalDiskStorage::new(root).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_keys_cannot_escape_root() {
+        let storage = storage();
+        let escapes = [
+            "",
+            "../outside.png",
+            "printful/../../outside.png",
+            "/etc/passwd",
+            "printful//a.png",
+            "printful/",
+            "./a.png",
+            "printful/./a.png",
+            "..\\outside.png",
+            "printful\\..\\..\\outside.png",
+            ".hidden",
+            "printful/.a.png.tmp",
+            "a\0.png",
+        ];
+
+        for key in escapes {
+            assert!(
+                matches!(
+                    storage.upload_key(key, b"x".to_vec(), "image/png").await,
+                    Err(R2Error::InvalidPath(_))
+                ),
+                "{:?} was stored",
+                key
+            );
+            assert!(matches!(
+                storage.download(key).await,
+                Err(R2Error::InvalidPath(_))
+            ));
+            assert!(matches!(
+                storage.exists(key).await,
+                Err(R2Error::InvalidPath(_))
+            ));
+            assert!(matches!(
+                storage.delete(key).await,
+                Err(R2Error::InvalidPath(_))
+            ));
+        }
+        assert!(matches!(
+            storage.list_page("../", None).await,
+            Err(R2Error::InvalidPath(_))
+        ));
+
+        // Nothing was written, inside the root or next to it
+        assert!(std::fs::read_dir(&storage.root).unwrap().next().is_none());
+        assert!(!storage.root.join("../outside.png").exists());
+        std::fs::remove_dir_all(&storage.root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_objects_round_trip() {
+        let storage = storage().with_public_url_prefix(Some("https://cdn.example/".to_string()));
+        let key = "printful/products/19/base/mug.png";
+
+        let uploaded = storage
+            .upload_key(key, b"PNG!".to_vec(), "image/png")
+            .await
+            .unwrap();
+        assert_eq!(uploaded.size, 4);
+        assert_eq!(
+            uploaded.public_url.as_deref(),
+            Some("https://cdn.example/printful/products/19/base/mug.png")
+        );
+        assert!(storage.exists(key).await.unwrap());
+        assert_eq!(storage.download(key).await.unwrap(), b"PNG!");
+
+        // Folders aren't objects
+        assert!(!storage.exists("printful/products").await.unwrap());
+
+        // Overwrites replace the whole file
+        storage
+            .upload_key(key, b"JPEG".to_vec(), "image/jpeg")
+            .await
+            .unwrap();
+        assert_eq!(storage.download(key).await.unwrap(), b"JPEG");
+
+        storage.delete(key).await.unwrap();
+        assert!(!storage.exists(key).await.unwrap());
+        assert!(matches!(
+            storage.download(key).await,
+            Err(R2Error::NotFound(_))
+        ));
+        storage.delete(key).await.unwrap();
+        std::fs::remove_dir_all(&storage.root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_verified_upload_checks_sha256() {
+        let storage = storage();
+        let sha256: [u8; 32] = Sha256::digest(b"PNG!").into();
+
+        storage
+            .upload_key_verified("a/b.png", b"PNG!".to_vec(), "image/png", &sha256)
+            .await
+            .unwrap();
+        assert!(matches!(
+            storage
+                .upload_key_verified("a/c.png", b"PNG?".to_vec(), "image/png", &sha256)
+                .await,
+            Err(R2Error::ChecksumMismatch(_))
+        ));
+        assert!(!storage.exists("a/c.png").await.unwrap());
+        std::fs::remove_dir_all(&storage.root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_list_pages_in_key_order() {
+        let storage = storage().with_page_size(2);
+        for key in [
+            "printful/variants/4011/front.png",
+            "printful/products/19/base/mug.png",
+            "printful/blobs/ab12.png",
+            "printful/blobs-2025.png",
+            "printify/products/5/base/tee.png",
+            "generated/2026-10-16/render.png",
+        ] {
+            storage
+                .upload_key(key, key.as_bytes().to_vec(), "image/png")
+                .await
+                .unwrap();
+        }
+        // Left behind by an interrupted upload
+        std::fs::write(storage.root.join("printful/blobs/.stale.tmp"), b"x").unwrap();
+
+        let mut keys = Vec::new();
+        let mut pages = 0;
+        let mut token = None;
+        loop {
+            let page = storage.list_page("printful/", token.take()).await.unwrap();
+            pages += 1;
+            for object in page.objects {
+                assert_eq!(object.size, object.key.len() as u64);
+                keys.push(object.key);
+            }
+            match page.next_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 2);
+        assert_eq!(
+            keys,
+            vec![
+                "printful/blobs-2025.png",
+                "printful/blobs/ab12.png",
+                "printful/products/19/base/mug.png",
+                "printful/variants/4011/front.png",
+            ]
+        );
+
+        // Prefixes needn't end at a folder
+        let page = storage.list_page("print", None).await.unwrap();
+        assert_eq!(page.objects[0].key, "printify/products/5/base/tee.png");
+        let page = storage.list_page("missing/", None).await.unwrap();
+        assert!(page.objects.is_empty() && page.next_token.is_none());
+        std::fs::remove_dir_all(&storage.root).ok();
+    }
+}
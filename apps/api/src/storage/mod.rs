@@ -1,8 +1,36 @@
 //! Storage module for POD asset management
 //!
 //! Provides Cloudflare R2 integration for storing and retrieving POD mockup assets.
-//! R2 is S3-compatible, so we use the AWS SDK.
+//! R2 is S3-compatible, so we use the AWS SDK. Generated mockups can also be
+//! uploaded to Cloudinary, and encoded mockups are cached for repeat requests.
+//! Usage reports total the storage each provider's assets take up.
+//! Assets go through the `StorageBackend` trait, so AWS S3 or a local
+//! directory can stand in for R2.
 
+mod backend;
+mod cloudinary;
+mod download;
+mod local;
 mod r2;
+mod render_cache;
+mod template_backup;
+mod usage;
+mod zip;
 
+pub use backend::{object_store_client, StorageBackend};
+pub use cloudinary::CloudinaryUploader;
+pub use download::{download_resumable, mirror_stats, DownloadError, Downloaded, RetryPolicy};
+pub use local::LocalDiskStorage;
+pub(crate) use r2::GENERATED_PREFIX;
 pub use r2::{AssetPath, R2Client, R2Error, UploadResult};
+pub use render_cache::{
+    CacheStatus, MemoryRenderStore, R2RenderStore, RenderCache, RenderCacheKey, RenderCacheStore,
+};
+pub use template_backup::{
+    BackupStore, DriftReport, TemplateBackup, TemplateManifest, TransferSummary,
+    TEMPLATE_BACKUP_PREFIX,
+};
+pub use usage::{
+    ListedObject, ObjectLister, ObjectPage, ProviderUsage, StorageUsage, UsageReport, UsageReports,
+};
+pub use zip::{zip_content_length, zip_stream, ZipEntry};
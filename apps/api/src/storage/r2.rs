@@ -1,6 +1,8 @@
 //! Cloudflare R2 storage client for POD assets
 //!
 //! R2 is S3-compatible, so we use aws-sdk-s3 with custom endpoint configuration.
+//! The same client talks to AWS S3, or another S3-compatible service, when
+//! built with `R2Client::s3`.
 //!
 //! ## Folder Structure
 //! ```text
@@ -17,14 +19,18 @@
 //! │   └── variants/
 //! │       └── {variant_id}/
 //! │           └── {placement}.png     # Variant-specific mockups
-//! └── generated/                      # User-generated mockups (optional cache)
-//!     └── {date}/
-//!         └── {uuid}.png
+//! ├── generated/                      # User-generated mockups (optional cache)
+//! │   └── {date}/
+//! │       └── {uuid}.{ext}            # png, jpg, or webp
+//! └── templates/                      # Backup of the local templates directory
+//!     ├── manifest.json               # SHA-256 checksums of every file
+//!     └── {template_id}/
+//!         └── {filename}
 //! ```
 
 use aws_sdk_s3::{
     config::{Builder, Credentials, Region},
-    error::SdkError,
+    error::{DisplayErrorContext, ProvideErrorMetadata, SdkError},
     operation::delete_object::DeleteObjectError,
     operation::get_object::GetObjectError,
     operation::head_object::HeadObjectError,
@@ -33,12 +39,19 @@ use aws_sdk_s3::{
     primitives::ByteStream,
     Client as S3Client,
 };
-use std::fmt;
+use base64::Engine;
+use chrono::{Days, NaiveDate, Utc};
+use std::sync::Arc;
 use thiserror::Error;
 use tracing::{debug, info, instrument, warn};
+use uuid::Uuid;
 
-use crate::config::{default_r2_bucket_name, R2Settings};
+use super::download::{download_resumable, RetryPolicy};
+use super::usage::{walk_usage, ListedObject, ObjectPage, UsageReport};
+use crate::config::{default_r2_bucket_name, R2Settings, S3Settings};
 use crate::domain::catalog::{AssetType, PrintPlacement};
+use crate::metrics::Metrics;
+use crate::net::{UrlGuard, UrlGuardError};
 
 /// Errors that can occur during R2 operations
 #[derive(Error, Debug)]
@@ -64,22 +77,58 @@ pub enum R2Error {
     #[error("Invalid path: {0}")]
     InvalidPath(String),
 
+    #[error("Checksum mismatch: {0}")]
+    ChecksumMismatch(String),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+
+    #[error("Source URL rejected: {0}")]
+    SourceUrlRejected(#[from] UrlGuardError),
+
+    /// R2 was throttling, failing, or unreachable; the request may succeed later
+    #[error("R2 temporarily unavailable: {0}")]
+    Transient(String),
+
+    /// A conditional write found the object changed since it was read
+    #[error("Object changed since it was read: {0}")]
+    PreconditionFailed(String),
 }
 
-impl<E: fmt::Debug> From<SdkError<E>> for R2Error {
-    fn from(err: SdkError<E>) -> Self {
-        R2Error::UploadFailed(format!("{:?}", err))
+/// What a conditional upload expects to replace
+#[derive(Debug, Clone, Copy)]
+pub enum WriteCondition<'a> {
+    /// No object under the key yet
+    Absent,
+    /// The object with this ETag
+    ETag(&'a str),
+}
+
+impl R2Error {
+    /// Whether retrying the operation could succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, R2Error::Transient(_))
     }
 }
 
+/// Top-level folder for mockups rendered by this service
+pub(crate) const GENERATED_PREFIX: &str = "generated";
+
+/// Folder of the render cache's R2 tier; sorts after every dated folder
+pub(crate) const RENDER_CACHE_PREFIX: &str = "generated/cache/";
+
+/// Date format of the `generated/{date}/` folders
+const GENERATED_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Keys fetched per listing while pruning generated mockups
+const PRUNE_PAGE_SIZE: i32 = 1000;
+
 /// Represents a path to an asset in R2
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AssetPath {
     /// Provider code (printful, printify, etc.)
     pub provider: String,
-    /// Product ID from the provider
+    /// Product ID from the provider (the `YYYY-MM-DD` folder for generated mockups)
     pub product_id: String,
     /// Optional variant ID
     pub variant_id: Option<String>,
@@ -152,6 +201,26 @@ impl AssetPath {
         }
     }
 
+    /// Create a path for a mockup rendered by this service
+    pub fn generated(date: NaiveDate, id: Uuid, ext: &str) -> Self {
+        Self {
+            provider: GENERATED_PREFIX.to_string(),
+            product_id: date.format(GENERATED_DATE_FORMAT).to_string(),
+            variant_id: None,
+            asset_type: AssetType::GeneratedMockup,
+            placement: None,
+            filename: format!("{}.{}", id, ext),
+        }
+    }
+
+    /// Day a generated mockup was stored; `None` for provider assets
+    pub fn generated_date(&self) -> Option<NaiveDate> {
+        if self.asset_type != AssetType::GeneratedMockup {
+            return None;
+        }
+        NaiveDate::parse_from_str(&self.product_id, GENERATED_DATE_FORMAT).ok()
+    }
+
     /// Convert to R2 object key
     pub fn to_key(&self) -> String {
         let asset_folder = match self.asset_type {
@@ -159,6 +228,10 @@ impl AssetPath {
             AssetType::MockupTemplate => "mockups",
             AssetType::Thumbnail => "thumbnails",
             AssetType::PrintfilePreview => "printfiles",
+            AssetType::GeneratedMockup => {
+                // Generated path: generated/{date}/{filename}
+                return format!("{}/{}/{}", GENERATED_PREFIX, self.product_id, self.filename);
+            }
         };
 
         if let Some(ref variant_id) = self.variant_id {
@@ -177,59 +250,128 @@ impl AssetPath {
     }
 
     /// Parse an R2 key back into an AssetPath
+    ///
+    /// Filenames are kept as they are, so `to_key` gives back the same key.
+    /// Mockup placements are read from `{placement}.{ext}` and
+    /// `{placement}_{variant_id}.{ext}` filenames. Variant keys don't include
+    /// the product, so `product_id` comes back empty for them.
     pub fn from_key(key: &str) -> Result<Self, R2Error> {
         let parts: Vec<&str> = key.split('/').collect();
 
+        if parts[0] == GENERATED_PREFIX {
+            // Generated path: generated/{date}/{filename}
+            return match parts.as_slice() {
+                [_, date, filename]
+                    if !filename.is_empty()
+                        && NaiveDate::parse_from_str(date, GENERATED_DATE_FORMAT).is_ok() =>
+                {
+                    Ok(Self {
+                        provider: GENERATED_PREFIX.to_string(),
+                        product_id: date.to_string(),
+                        variant_id: None,
+                        asset_type: AssetType::GeneratedMockup,
+                        placement: None,
+                        filename: filename.to_string(),
+                    })
+                }
+                _ => Err(R2Error::InvalidPath(format!(
+                    "Invalid generated mockup key: {}",
+                    key
+                ))),
+            };
+        }
+
         if parts.len() < 4 {
             return Err(R2Error::InvalidPath(format!("Key too short: {}", key)));
         }
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(R2Error::InvalidPath(format!(
+                "Empty path segment in key: {}",
+                key
+            )));
+        }
 
-        let provider = parts[0].to_string();
-
-        if parts[1] == "variants" && parts.len() >= 4 {
+        match parts.as_slice() {
             // Variant path: {provider}/variants/{variant_id}/{filename}
-            let variant_id = parts[2].to_string();
-            let filename = parts[3..].join("/");
-
-            Ok(Self {
-                provider,
-                product_id: String::new(), // Not in variant path
-                variant_id: Some(variant_id),
-                asset_type: AssetType::MockupTemplate,
-                placement: None,
-                filename,
-            })
-        } else if parts[1] == "products" && parts.len() >= 5 {
+            [provider, "variants", variant_id, filename @ ..] => {
+                let filename = filename.join("/");
+                Ok(Self {
+                    provider: provider.to_string(),
+                    product_id: String::new(), // Not in variant path
+                    variant_id: Some(variant_id.to_string()),
+                    asset_type: AssetType::MockupTemplate,
+                    placement: placement_from_filename(&filename),
+                    filename,
+                })
+            }
             // Product path: {provider}/products/{product_id}/{asset_folder}/{filename}
-            let product_id = parts[2].to_string();
-            let asset_folder = parts[3];
-            let filename = parts[4..].join("/");
-
-            let asset_type = match asset_folder {
-                "base" => AssetType::BaseImage,
-                "mockups" => AssetType::MockupTemplate,
-                "thumbnails" => AssetType::Thumbnail,
-                "printfiles" => AssetType::PrintfilePreview,
-                _ => AssetType::BaseImage,
-            };
-
-            Ok(Self {
-                provider,
-                product_id,
-                variant_id: None,
-                asset_type,
-                placement: None,
-                filename,
-            })
-        } else {
-            Err(R2Error::InvalidPath(format!(
+            [provider, "products", product_id, asset_folder, filename @ ..]
+                if !filename.is_empty() =>
+            {
+                let asset_type = match *asset_folder {
+                    "base" => AssetType::BaseImage,
+                    "mockups" => AssetType::MockupTemplate,
+                    "thumbnails" => AssetType::Thumbnail,
+                    "printfiles" => AssetType::PrintfilePreview,
+                    _ => {
+                        return Err(R2Error::InvalidPath(format!(
+                            "Unknown asset folder in key: {}",
+                            key
+                        )))
+                    }
+                };
+                let filename = filename.join("/");
+                let placement = match asset_type {
+                    AssetType::MockupTemplate | AssetType::PrintfilePreview => {
+                        placement_from_filename(&filename)
+                    }
+                    _ => None,
+                };
+
+                Ok(Self {
+                    provider: provider.to_string(),
+                    product_id: product_id.to_string(),
+                    variant_id: None,
+                    asset_type,
+                    placement,
+                    filename,
+                })
+            }
+            _ => Err(R2Error::InvalidPath(format!(
                 "Unrecognized path structure: {}",
                 key
-            )))
+            ))),
         }
     }
 }
 
+/// A placement in the form `as_str` gives, e.g. `sleeve_left`
+fn known_placement(s: &str) -> Option<PrintPlacement> {
+    [
+        PrintPlacement::Front,
+        PrintPlacement::Back,
+        PrintPlacement::SleeveLeft,
+        PrintPlacement::SleeveRight,
+        PrintPlacement::Pocket,
+        PrintPlacement::Hood,
+        PrintPlacement::FullWrap,
+        PrintPlacement::AllOver,
+    ]
+    .into_iter()
+    .find(|placement| placement.as_str() == s)
+}
+
+/// Placement named by a `{placement}.{ext}` or `{placement}_{variant_id}.{ext}` filename
+fn placement_from_filename(filename: &str) -> Option<PrintPlacement> {
+    let stem = filename.rsplit_once('.').map_or(filename, |(stem, _)| stem);
+    known_placement(stem).or_else(|| {
+        // Longest prefix first, so `sleeve_left_4012` isn't cut short
+        stem.match_indices('_')
+            .rev()
+            .find_map(|(i, _)| known_placement(&stem[..i]))
+    })
+}
+
 /// Result of an upload operation
 #[derive(Debug, Clone)]
 pub struct UploadResult {
@@ -251,6 +393,8 @@ pub struct R2Client {
     client: S3Client,
     bucket: String,
     public_url_prefix: Option<String>,
+    /// Counts bytes transferred, when set
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl R2Client {
@@ -282,9 +426,54 @@ impl R2Client {
             client,
             bucket: settings.bucket_name.clone(),
             public_url_prefix: settings.public_url_prefix.clone(),
+            metrics: None,
+        })
+    }
+
+    /// Create a client of an AWS S3 bucket
+    ///
+    /// Without static keys, credentials come from the AWS environment:
+    /// variables, profiles, or the instance role.
+    pub async fn s3(settings: &S3Settings) -> Result<Self, R2Error> {
+        let region = Region::new(settings.region.clone());
+        let mut builder = match (&settings.access_key_id, &settings.secret_access_key) {
+            (Some(access_key_id), Some(secret_access_key)) => Builder::new()
+                .region(region)
+                .credentials_provider(Credentials::new(
+                    access_key_id,
+                    secret_access_key,
+                    None, // session token
+                    None, // expiry
+                    "s3-static-credentials",
+                )),
+            _ => {
+                let shared = aws_config::defaults(aws_config::BehaviorVersion::latest())
+                    .region(region)
+                    .load()
+                    .await;
+                Builder::from(&shared)
+            }
+        };
+        if let Some(ref endpoint) = settings.endpoint_url {
+            debug!("Creating S3 client with endpoint: {}", endpoint);
+            // Most S3-compatible services don't serve virtual-hosted buckets
+            builder = builder.endpoint_url(endpoint).force_path_style(true);
+        }
+
+        Ok(Self {
+            client: S3Client::from_conf(builder.build()),
+            bucket: settings.bucket_name.clone(),
+            public_url_prefix: settings.public_url_prefix.clone(),
+            metrics: None,
         })
     }
 
+    /// Count uploaded and downloaded bytes in `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Create from environment variables
     pub async fn from_env() -> Result<Self, R2Error> {
         let account_id = std::env::var("R2_ACCOUNT_ID")
@@ -320,30 +509,96 @@ impl R2Client {
     }
 
     /// Upload bytes to R2
-    #[instrument(skip(self, data), fields(key = %path.to_key(), size = data.len()))]
     pub async fn upload(
         &self,
         path: &AssetPath,
         data: Vec<u8>,
         content_type: &str,
     ) -> Result<UploadResult, R2Error> {
-        let key = path.to_key();
+        self.upload_key(&path.to_key(), data, content_type).await
+    }
+
+    /// Upload bytes to R2 under a raw object key
+    pub async fn upload_key(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<UploadResult, R2Error> {
+        self.put_object(key, data, content_type, None, None).await
+    }
+
+    /// Upload bytes under a raw object key only if the object there still
+    /// meets `condition`, failing with [`R2Error::PreconditionFailed`] if not
+    pub async fn upload_key_if(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        condition: WriteCondition<'_>,
+    ) -> Result<UploadResult, R2Error> {
+        self.put_object(key, data, content_type, None, Some(condition))
+            .await
+    }
+
+    /// Upload bytes along with their SHA-256, so R2 rejects a corrupted body
+    pub async fn upload_verified(
+        &self,
+        path: &AssetPath,
+        data: Vec<u8>,
+        content_type: &str,
+        sha256: &[u8; 32],
+    ) -> Result<UploadResult, R2Error> {
+        self.upload_key_verified(&path.to_key(), data, content_type, sha256)
+            .await
+    }
+
+    /// Upload bytes and their SHA-256 under a raw object key
+    pub async fn upload_key_verified(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        sha256: &[u8; 32],
+    ) -> Result<UploadResult, R2Error> {
+        let checksum = base64::engine::general_purpose::STANDARD.encode(sha256);
+        self.put_object(key, data, content_type, Some(checksum), None)
+            .await
+    }
+
+    #[instrument(skip(self, data, checksum_sha256), fields(size = data.len()))]
+    async fn put_object(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        checksum_sha256: Option<String>,
+        condition: Option<WriteCondition<'_>>,
+    ) -> Result<UploadResult, R2Error> {
+        let key = key.to_string();
         let size = data.len() as u64;
 
         debug!("Uploading {} bytes to R2: {}", size, key);
 
-        let result = self
+        let mut request = self
             .client
             .put_object()
             .bucket(&self.bucket)
             .key(&key)
             .body(ByteStream::from(data))
             .content_type(content_type)
-            .send()
-            .await
-            .map_err(|e| R2Error::UploadFailed(format!("{:?}", e)))?;
+            .set_checksum_sha256(checksum_sha256);
+        request = match condition {
+            Some(WriteCondition::Absent) => request.if_none_match("*"),
+            Some(WriteCondition::ETag(etag)) => request.if_match(etag),
+            None => request,
+        };
+        let result = request.send().await.map_err(upload_error)?;
 
         let etag = result.e_tag().map(String::from);
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_r2_upload(size);
+        }
 
         let public_url = self
             .public_url_prefix
@@ -376,6 +631,12 @@ impl R2Client {
     /// Download an object from R2
     #[instrument(skip(self))]
     pub async fn download(&self, key: &str) -> Result<Vec<u8>, R2Error> {
+        self.download_tagged(key).await.map(|(data, _)| data)
+    }
+
+    /// Download an object along with its ETag, for a later conditional upload
+    #[instrument(skip(self))]
+    pub async fn download_tagged(&self, key: &str) -> Result<(Vec<u8>, Option<String>), R2Error> {
         debug!("Downloading from R2: {}", key);
 
         let result = self
@@ -385,14 +646,9 @@ impl R2Client {
             .key(key)
             .send()
             .await
-            .map_err(|e| {
-                if is_not_found_error(&e) {
-                    R2Error::NotFound(key.to_string())
-                } else {
-                    R2Error::DownloadFailed(format!("{:?}", e))
-                }
-            })?;
+            .map_err(|e| download_error(key, e))?;
 
+        let etag = result.e_tag().map(String::from);
         let data = result
             .body
             .collect()
@@ -402,8 +658,11 @@ impl R2Client {
             .to_vec();
 
         debug!("Downloaded {} bytes from R2: {}", data.len(), key);
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_r2_download(data.len() as u64);
+        }
 
-        Ok(data)
+        Ok((data, etag))
     }
 
     /// Check if an object exists in R2
@@ -418,8 +677,10 @@ impl R2Client {
             .await
         {
             Ok(_) => Ok(true),
-            Err(e) if is_not_found_error(&e) => Ok(false),
-            Err(e) => Err(R2Error::DownloadFailed(format!("{:?}", e))),
+            Err(e) => match head_error(key, e) {
+                R2Error::NotFound(_) => Ok(false),
+                e => Err(e),
+            },
         }
     }
 
@@ -434,7 +695,7 @@ impl R2Client {
             .key(key)
             .send()
             .await
-            .map_err(|e| R2Error::DeleteFailed(format!("{:?}", e)))?;
+            .map_err(delete_error)?;
 
         info!("Deleted from R2: {}", key);
         Ok(())
@@ -461,10 +722,7 @@ impl R2Client {
                 request = request.continuation_token(token);
             }
 
-            let result = request
-                .send()
-                .await
-                .map_err(|e| R2Error::ListFailed(format!("{:?}", e)))?;
+            let result = request.send().await.map_err(list_error)?;
 
             if let Some(contents) = result.contents {
                 for object in contents {
@@ -491,6 +749,53 @@ impl R2Client {
         Ok(keys)
     }
 
+    /// One page of objects under `prefix` with their sizes, continuing from `token`
+    pub async fn list_page(
+        &self,
+        prefix: &str,
+        token: Option<String>,
+    ) -> Result<ObjectPage, R2Error> {
+        let result = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .set_continuation_token(token)
+            .send()
+            .await
+            .map_err(list_error)?;
+
+        let objects = result
+            .contents
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|object| {
+                Some(ListedObject {
+                    key: object.key?,
+                    size: object.size.unwrap_or(0).max(0) as u64,
+                })
+            })
+            .collect();
+        let next_token = if result.is_truncated.unwrap_or(false) {
+            result.next_continuation_token
+        } else {
+            None
+        };
+        Ok(ObjectPage {
+            objects,
+            next_token,
+        })
+    }
+
+    /// Objects and bytes stored under `prefix`, by provider and asset type
+    ///
+    /// Walks every page of the listing, which takes a while on a large
+    /// bucket; `UsageReports` runs it in the background.
+    #[instrument(skip(self))]
+    pub async fn usage_report(&self, prefix: &str) -> Result<UsageReport, R2Error> {
+        walk_usage(self, prefix).await
+    }
+
     /// List all products for a provider
     pub async fn list_products(&self, provider: &str) -> Result<Vec<String>, R2Error> {
         let prefix = format!("{}/products/", provider.to_lowercase());
@@ -509,6 +814,55 @@ impl R2Client {
         Ok(product_ids.into_iter().collect())
     }
 
+    /// Delete generated mockups stored more than `days` days ago
+    ///
+    /// Keys sort by date, so each listing is processed oldest first and pruning
+    /// stops at the first mockup still inside the retention window.
+    #[instrument(skip(self))]
+    pub async fn prune_generated(&self, days: u32) -> Result<usize, R2Error> {
+        let cutoff = Utc::now().date_naive() - Days::new(days as u64);
+        let prefix = format!("{}/", GENERATED_PREFIX);
+        let mut deleted = 0;
+
+        loop {
+            let keys = self.list(&prefix, Some(PRUNE_PAGE_SIZE)).await?;
+            let full_page = keys.len() >= PRUNE_PAGE_SIZE as usize;
+            let mut deleted_in_page = 0;
+            let mut reached_window = false;
+
+            for key in keys {
+                // Cached renders are retired by template reloads, not age
+                if key.starts_with(RENDER_CACHE_PREFIX) {
+                    reached_window = true;
+                    break;
+                }
+                match AssetPath::from_key(&key)
+                    .ok()
+                    .and_then(|p| p.generated_date())
+                {
+                    Some(date) if date < cutoff => {
+                        self.delete(&key).await?;
+                        deleted_in_page += 1;
+                    }
+                    Some(_) => {
+                        reached_window = true;
+                        break;
+                    }
+                    None => warn!(key = %key, "Skipping unrecognized generated mockup key"),
+                }
+            }
+
+            deleted += deleted_in_page;
+            // A page of only unrecognized keys would be listed again forever
+            if reached_window || !full_page || deleted_in_page == 0 {
+                break;
+            }
+        }
+
+        info!(deleted, cutoff = %cutoff, "Pruned generated mockups");
+        Ok(deleted)
+    }
+
     /// Get the public URL for an asset
     pub fn public_url(&self, key: &str) -> Option<String> {
         self.public_url_prefix
@@ -517,55 +871,124 @@ impl R2Client {
     }
 
     /// Download an asset from a URL and upload to R2
-    #[instrument(skip(self, http_client))]
+    ///
+    /// The URL is checked by `urls` and fetched with its client, so internal
+    /// addresses are refused up front, after DNS resolution, and on redirect.
+    /// Interrupted downloads are retried and resumed; the body's size is
+    /// checked against what the server announced and its SHA-256 is verified
+    /// by R2 on upload.
+    #[instrument(skip(self, urls))]
     pub async fn mirror_from_url(
         &self,
         source_url: &str,
         path: &AssetPath,
-        http_client: &reqwest::Client,
+        urls: &UrlGuard,
     ) -> Result<UploadResult, R2Error> {
         debug!("Mirroring {} to R2", source_url);
 
-        let response = http_client
-            .get(source_url)
-            .send()
+        urls.check(source_url).await?;
+        let downloaded = download_resumable(urls.client(), source_url, &RetryPolicy::default())
             .await
-            .map_err(|e| R2Error::DownloadFailed(format!("HTTP error: {:?}", e)))?;
+            .map_err(|e| R2Error::DownloadFailed(format!("{}: {}", source_url, e)))?;
+
+        self.upload_verified(
+            path,
+            downloaded.data,
+            &downloaded.content_type,
+            &downloaded.sha256,
+        )
+        .await
+    }
+}
 
-        if !response.status().is_success() {
-            return Err(R2Error::DownloadFailed(format!(
-                "HTTP {} from {}",
-                response.status(),
-                source_url
-            )));
+/// Error codes R2 and S3 use when asking clients to slow down or try again
+const TRANSIENT_ERROR_CODES: &[&str] = &[
+    "SlowDown",
+    "Throttling",
+    "ThrottlingException",
+    "TooManyRequests",
+    "RequestTimeout",
+    "ServiceUnavailable",
+    "InternalError",
+];
+
+/// Whether an SDK error is throttling, a server error, or a dropped connection
+fn is_transient<E: ProvideErrorMetadata>(err: &SdkError<E>) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::ResponseError(_) => true,
+        SdkError::DispatchFailure(failure) => failure.is_io() || failure.is_timeout(),
+        SdkError::ServiceError(service) => {
+            let status = service.raw().status().as_u16();
+            status == 429
+                || status >= 500
+                || service
+                    .err()
+                    .code()
+                    .is_some_and(|code| TRANSIENT_ERROR_CODES.contains(&code))
         }
+        _ => false,
+    }
+}
 
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("image/png")
-            .to_string();
+/// Convert an SDK error that isn't a missing object
+///
+/// Transient errors become `R2Error::Transient`; the rest go to `failed`.
+fn sdk_error<E>(err: SdkError<E>, failed: fn(String) -> R2Error) -> R2Error
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+{
+    let message = DisplayErrorContext(&err).to_string();
+    if is_transient(&err) {
+        R2Error::Transient(message)
+    } else {
+        failed(message)
+    }
+}
 
-        let data = response
-            .bytes()
-            .await
-            .map_err(|e| R2Error::DownloadFailed(format!("Body error: {:?}", e)))?
-            .to_vec();
+fn upload_error(err: SdkError<PutObjectError>) -> R2Error {
+    match err.as_service_error().and_then(|e| e.code()) {
+        // The body didn't match the SHA-256 sent with it
+        Some("BadDigest") => {
+            return R2Error::ChecksumMismatch(DisplayErrorContext(&err).to_string());
+        }
+        // A conditional upload lost to another writer
+        Some("PreconditionFailed" | "ConditionalRequestConflict") => {
+            return R2Error::PreconditionFailed(DisplayErrorContext(&err).to_string());
+        }
+        _ => {}
+    }
+    sdk_error(err, R2Error::UploadFailed)
+}
+
+fn download_error(key: &str, err: SdkError<GetObjectError>) -> R2Error {
+    match err.as_service_error() {
+        Some(GetObjectError::NoSuchKey(_)) => R2Error::NotFound(key.to_string()),
+        _ => sdk_error(err, R2Error::DownloadFailed),
+    }
+}
 
-        self.upload(path, data, &content_type).await
+fn head_error(key: &str, err: SdkError<HeadObjectError>) -> R2Error {
+    match err.as_service_error() {
+        Some(HeadObjectError::NotFound(_)) => R2Error::NotFound(key.to_string()),
+        _ => sdk_error(err, R2Error::DownloadFailed),
     }
 }
 
-/// Helper to check if an SDK error is a "not found" error
-fn is_not_found_error<E: fmt::Debug>(err: &SdkError<E>) -> bool {
-    let debug_str = format!("{:?}", err);
-    debug_str.contains("NoSuchKey") || debug_str.contains("NotFound") || debug_str.contains("404")
+fn delete_error(err: SdkError<DeleteObjectError>) -> R2Error {
+    sdk_error(err, R2Error::DeleteFailed)
+}
+
+fn list_error(err: SdkError<ListObjectsV2Error>) -> R2Error {
+    sdk_error(err, R2Error::ListFailed)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aws_sdk_s3::config::http::HttpResponse;
+    use aws_sdk_s3::error::{ConnectorError, ErrorMetadata};
+    use aws_sdk_s3::primitives::SdkBody;
+    use aws_sdk_s3::types::error::{NoSuchKey, NotFound};
 
     #[test]
     fn test_asset_path_to_key() {
@@ -600,5 +1023,223 @@ mod tests {
         assert_eq!(path.provider, "printify");
         assert_eq!(path.variant_id, Some("var-001".to_string()));
         assert_eq!(path.filename, "back.png");
+        assert_eq!(path.generated_date(), None);
+    }
+
+    #[test]
+    fn test_generated_path_round_trip() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let id = Uuid::nil();
+        let path = AssetPath::generated(date, id, "png");
+        let key = path.to_key();
+        assert_eq!(key, format!("generated/2026-10-16/{}.png", id));
+
+        let parsed = AssetPath::from_key(&key).unwrap();
+        assert!(matches!(parsed.asset_type, AssetType::GeneratedMockup));
+        assert_eq!(parsed.generated_date(), Some(date));
+        assert_eq!(parsed.to_key(), key);
+
+        assert!(AssetPath::from_key("generated/latest/mockup.png").is_err());
+        assert!(AssetPath::from_key("generated/2026-10-16/nested/mockup.png").is_err());
+    }
+
+    fn placements() -> Vec<PrintPlacement> {
+        vec![
+            PrintPlacement::Front,
+            PrintPlacement::Back,
+            PrintPlacement::SleeveLeft,
+            PrintPlacement::SleeveRight,
+            PrintPlacement::Pocket,
+            PrintPlacement::Hood,
+            PrintPlacement::FullWrap,
+            PrintPlacement::AllOver,
+        ]
+    }
+
+    /// Check `path` survives a trip through its key
+    fn assert_round_trips(path: &AssetPath) {
+        let key = path.to_key();
+        let parsed = AssetPath::from_key(&key).unwrap();
+        assert_eq!(parsed.to_key(), key);
+
+        let mut expected = path.clone();
+        if expected.variant_id.is_some() {
+            // Variant keys don't carry the product
+            expected.product_id = String::new();
+        }
+        assert_eq!(parsed, expected, "{}", key);
+    }
+
+    #[test]
+    fn test_every_constructor_round_trips() {
+        let filenames = ["front.png", "Mockup-1_final.jpg", "shirt", "a.b.c.webp"];
+        for provider in ["printful", "printify", "gelato"] {
+            for filename in filenames {
+                assert_round_trips(&AssetPath::base_image(provider, "12345", filename));
+                assert_round_trips(&AssetPath::thumbnail(provider, "12345", filename));
+            }
+
+            for placement in placements() {
+                assert_round_trips(&AssetPath::variant_asset(
+                    provider,
+                    "12345",
+                    "var_001",
+                    placement.clone(),
+                ));
+
+                // Mockup filenames follow `{placement}.png` or `{placement}_{variant}.png`
+                let filenames = [
+                    format!("{}.png", placement.as_str()),
+                    format!("{}_var_001.png", placement.as_str()),
+                ];
+                for filename in &filenames {
+                    assert_round_trips(&AssetPath::mockup_template(
+                        provider,
+                        "12345",
+                        None,
+                        placement.clone(),
+                        filename,
+                    ));
+                    assert_round_trips(&AssetPath::mockup_template(
+                        provider,
+                        "12345",
+                        Some("var_001"),
+                        placement.clone(),
+                        filename,
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_mockup_filenames_kept_without_placement() {
+        for key in [
+            "printful/products/12345/mockups/shirt-mockup.png",
+            "printful/products/12345/mockups/nested/front.png",
+            "printful/variants/var-001/frontal.png",
+        ] {
+            let parsed = AssetPath::from_key(key).unwrap();
+            assert_eq!(parsed.to_key(), key);
+            assert_eq!(parsed.placement, None, "{}", key);
+        }
+
+        let parsed = AssetPath::from_key("printful/variants/var-001/sleeve_left_42.png").unwrap();
+        assert_eq!(parsed.placement, Some(PrintPlacement::SleeveLeft));
+        // Placements aren't guessed for base images and thumbnails
+        let parsed = AssetPath::from_key("printful/products/12345/base/front.png").unwrap();
+        assert_eq!(parsed.placement, None);
+    }
+
+    #[test]
+    fn test_unparseable_keys_are_invalid() {
+        for key in [
+            "printful/blobs/ab12.png",
+            "printful/products/12345/unknown/front.png",
+            "printful/products/12345/base/",
+            "printful/products//base/front.png",
+            "printful/variants/var-001/",
+            "printful/products/12345/base",
+            "printful/catalog/12345/front.png",
+            "",
+        ] {
+            assert!(
+                matches!(AssetPath::from_key(key), Err(R2Error::InvalidPath(_))),
+                "{}",
+                key
+            );
+        }
+    }
+
+    fn response(status: u16) -> HttpResponse {
+        HttpResponse::new(status.try_into().unwrap(), SdkBody::empty())
+    }
+
+    /// A service error with only an error code, as R2 sends for throttling
+    fn coded<E>(generic: fn(ErrorMetadata) -> E, code: &str, status: u16) -> SdkError<E> {
+        SdkError::service_error(
+            generic(ErrorMetadata::builder().code(code).build()),
+            response(status),
+        )
+    }
+
+    #[test]
+    fn test_missing_objects_are_not_found() {
+        let missing = SdkError::service_error(
+            GetObjectError::NoSuchKey(NoSuchKey::builder().build()),
+            response(404),
+        );
+        assert!(matches!(
+            download_error("a/b.png", missing),
+            R2Error::NotFound(key) if key == "a/b.png"
+        ));
+
+        let missing = SdkError::service_error(
+            HeadObjectError::NotFound(NotFound::builder().build()),
+            response(404),
+        );
+        assert!(matches!(
+            head_error("a/b.png", missing),
+            R2Error::NotFound(_)
+        ));
+
+        // A missing bucket is a 404 too, but not a missing object
+        let no_bucket = coded(GetObjectError::generic, "NoSuchBucket", 404);
+        assert!(matches!(
+            download_error("a/b.png", no_bucket),
+            R2Error::DownloadFailed(_)
+        ));
+    }
+
+    #[test]
+    fn test_throttling_and_server_errors_are_transient() {
+        let slow_down = coded(PutObjectError::generic, "SlowDown", 503);
+        assert!(upload_error(slow_down).is_transient());
+        let too_many = coded(GetObjectError::generic, "TooManyRequests", 429);
+        assert!(download_error("a/b.png", too_many).is_transient());
+        let internal = coded(DeleteObjectError::generic, "InternalError", 500);
+        assert!(delete_error(internal).is_transient());
+        // Throttling codes count whatever the status
+        let throttled = coded(ListObjectsV2Error::generic, "Throttling", 400);
+        assert!(list_error(throttled).is_transient());
+
+        let timeout: SdkError<HeadObjectError> = SdkError::timeout_error("timed out");
+        assert!(head_error("a/b.png", timeout).is_transient());
+        let dropped: SdkError<GetObjectError> =
+            SdkError::dispatch_failure(ConnectorError::io("connection reset".into()));
+        assert!(download_error("a/b.png", dropped).is_transient());
+    }
+
+    #[test]
+    fn test_errors_are_named_for_their_operation() {
+        let denied = || coded(GetObjectError::generic, "AccessDenied", 403);
+        assert!(matches!(
+            download_error("a/b.png", denied()),
+            R2Error::DownloadFailed(_)
+        ));
+        assert!(download_error("a/b.png", denied())
+            .to_string()
+            .starts_with("Download failed"));
+
+        let denied = coded(PutObjectError::generic, "AccessDenied", 403);
+        assert!(matches!(upload_error(denied), R2Error::UploadFailed(_)));
+        let corrupted = coded(PutObjectError::generic, "BadDigest", 400);
+        assert!(matches!(
+            upload_error(corrupted),
+            R2Error::ChecksumMismatch(_)
+        ));
+        let raced = coded(PutObjectError::generic, "PreconditionFailed", 412);
+        assert!(matches!(
+            upload_error(raced),
+            R2Error::PreconditionFailed(_)
+        ));
+        let denied = coded(DeleteObjectError::generic, "AccessDenied", 403);
+        assert!(matches!(delete_error(denied), R2Error::DeleteFailed(_)));
+        let denied = coded(ListObjectsV2Error::generic, "AccessDenied", 403);
+        assert!(matches!(list_error(denied), R2Error::ListFailed(_)));
+
+        let rejected: SdkError<PutObjectError> =
+            SdkError::construction_failure("missing bucket name");
+        assert!(matches!(upload_error(rejected), R2Error::UploadFailed(_)));
     }
 }
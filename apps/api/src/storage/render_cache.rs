@@ -0,0 +1,552 @@
+//! Encoded mockup cache
+//!
+//! Storefronts send the same generate request every time a product page loads.
+//! Renders are keyed by a fingerprint of everything that shapes the output:
+//! the template and its version, each design's content and placement, the
+//! compositing options, and the output encoding. A hit returns the bytes
+//! encoded the first time without compositing again.
+//!
+//! Entries live in an in-memory LRU bounded by bytes, backed by an optional
+//! R2 tier under `generated/cache/{fingerprint}.{ext}` that survives restarts
+//! and is shared between instances. Reloading a template drops its entries.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use super::r2::{R2Client, R2Error, RENDER_CACHE_PREFIX};
+use crate::config::RenderCacheSettings;
+use crate::engine::{DesignSource, MockupRequest, MockupResult, OutputFormat, StageTimings};
+use crate::metrics::Metrics;
+
+/// Identifies one encoded render
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RenderCacheKey {
+    /// Kept so a template's entries can be dropped when it is reloaded
+    pub template_id: String,
+    /// Hex SHA-256 of the request's render inputs
+    pub fingerprint: String,
+    pub format: OutputFormat,
+}
+
+impl RenderCacheKey {
+    /// Key for a request against `template_version` of its template
+    ///
+    /// `None` while a design is still a URL: designs are fingerprinted by
+    /// content, so a changed image behind the same URL misses.
+    pub fn for_request(request: &MockupRequest, template_version: u32) -> Option<Self> {
+        let mut fingerprint = Fingerprint::default();
+        fingerprint.field("template_id", &request.template_id);
+        fingerprint.field("template_version", template_version);
+        for layer in &request.designs {
+            let DesignSource::Bytes(bytes) = &layer.design else {
+                return None;
+            };
+            let placement = &layer.placement;
+            fingerprint.field("design", hex::encode(Sha256::digest(bytes)));
+            fingerprint.field("scale", placement.scale);
+            fingerprint.field("offset_x", placement.offset_x);
+            fingerprint.field("offset_y", placement.offset_y);
+            fingerprint.field("print_area_width", placement.print_area_width);
+            fingerprint.field("print_area_height", placement.print_area_height);
+            fingerprint.field(
+                "coordinate_space",
+                format!("{:?}", placement.coordinate_space),
+            );
+            fingerprint.field("rotation_degrees", placement.rotation_degrees);
+            fingerprint.field("displacement_strength", layer.displacement_strength);
+            fingerprint.field("blend_mode", format!("{:?}", layer.blend_mode));
+        }
+        fingerprint.field(
+            "apply_displacement",
+            format!("{:?}", request.apply_displacement),
+        );
+        fingerprint.field("tint_color", format!("{:?}", request.tint_color));
+        fingerprint.field(
+            "remove_background",
+            format!("{:?}", request.remove_background),
+        );
+        fingerprint.field("output", format!("{:?}", request.output));
+
+        Some(Self {
+            template_id: request.template_id.clone(),
+            fingerprint: fingerprint.finish(),
+            format: request.output.format,
+        })
+    }
+
+    /// R2 object key of the cached render
+    pub fn r2_key(&self) -> String {
+        format!(
+            "{}{}.{}",
+            RENDER_CACHE_PREFIX,
+            self.fingerprint,
+            self.format.extension()
+        )
+    }
+}
+
+/// SHA-256 over named fields, each terminated so adjacent values can't run together
+#[derive(Default)]
+struct Fingerprint(Sha256);
+
+impl Fingerprint {
+    fn field(&mut self, name: &str, value: impl Display) {
+        self.0.update(format!("{}={}\n", name, value));
+    }
+
+    fn finish(self) -> String {
+        hex::encode(self.0.finalize())
+    }
+}
+
+/// Where cached renders are kept
+#[async_trait]
+pub trait RenderCacheStore: Send + Sync {
+    /// Short name used as the `tier` metric label
+    fn name(&self) -> &'static str;
+
+    async fn get(&self, key: &RenderCacheKey) -> Option<Bytes>;
+
+    async fn put(&self, key: &RenderCacheKey, bytes: Bytes);
+
+    /// Drop every entry rendered against `template_id`
+    async fn remove_template(&self, template_id: &str);
+}
+
+/// Whether a render came from the cache, sent as `X-Render-Cache`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    /// Caching was off for the request or the server
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Bypass => "BYPASS",
+        }
+    }
+}
+
+/// Renders looked up tier by tier, fastest first
+pub struct RenderCache {
+    tiers: Vec<Arc<dyn RenderCacheStore>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl RenderCache {
+    pub fn new(tiers: Vec<Arc<dyn RenderCacheStore>>) -> Self {
+        Self {
+            tiers,
+            metrics: None,
+        }
+    }
+
+    /// A cache that never stores anything
+    pub fn disabled() -> Self {
+        Self::new(Vec::new())
+    }
+
+    /// Memory tier unless `max_bytes` is 0, then R2 when enabled and configured
+    pub fn from_settings(settings: &RenderCacheSettings, r2: Option<R2Client>) -> Self {
+        if !settings.enabled {
+            return Self::disabled();
+        }
+        let mut tiers: Vec<Arc<dyn RenderCacheStore>> = Vec::new();
+        if settings.max_bytes > 0 {
+            tiers.push(Arc::new(MemoryRenderStore::new(settings.max_bytes)));
+        }
+        if let (true, Some(r2)) = (settings.r2, r2) {
+            tiers.push(Arc::new(R2RenderStore::new(r2)));
+        }
+        Self::new(tiers)
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.tiers.is_empty()
+    }
+
+    /// The cached render for `key`, copied into the faster tiers that missed
+    pub async fn get(&self, key: &RenderCacheKey) -> Option<MockupResult> {
+        for (index, tier) in self.tiers.iter().enumerate() {
+            let Some(bytes) = tier.get(key).await else {
+                continue;
+            };
+            // An object that no longer decodes is treated as a miss
+            let Some((width, height)) = encoded_dimensions(&bytes) else {
+                warn!(fingerprint = %key.fingerprint, tier = tier.name(), "Cached render is unreadable");
+                continue;
+            };
+            for faster in &self.tiers[..index] {
+                faster.put(key, bytes.clone()).await;
+            }
+            self.record(Some(tier.name()));
+            debug!(fingerprint = %key.fingerprint, tier = tier.name(), "Render cache hit");
+            return Some(MockupResult {
+                width,
+                height,
+                content_type: key.format.content_type(),
+                bytes,
+                timings: StageTimings::default(),
+            });
+        }
+        self.record(None);
+        None
+    }
+
+    /// Store a render in every tier
+    pub async fn put(&self, key: &RenderCacheKey, bytes: Bytes) {
+        for tier in &self.tiers {
+            tier.put(key, bytes.clone()).await;
+        }
+    }
+
+    /// Drop every cached render of `template_id`, in every tier
+    pub async fn invalidate_template(&self, template_id: &str) {
+        for tier in &self.tiers {
+            tier.remove_template(template_id).await;
+        }
+    }
+
+    fn record(&self, tier: Option<&str>) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_render_cache(tier);
+        }
+    }
+}
+
+/// Width and height from an encoded image's header
+fn encoded_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    image::io::Reader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// In-memory renders, evicting the least recently used once over a byte budget
+pub struct MemoryRenderStore {
+    max_bytes: u64,
+    inner: Mutex<MemoryEntries>,
+}
+
+#[derive(Default)]
+struct MemoryEntries {
+    entries: HashMap<RenderCacheKey, MemoryEntry>,
+    /// Keys by last use, oldest first
+    recency: BTreeMap<u64, RenderCacheKey>,
+    tick: u64,
+    bytes: u64,
+}
+
+struct MemoryEntry {
+    bytes: Bytes,
+    last_used: u64,
+}
+
+impl MemoryEntries {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, key: &RenderCacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+            self.bytes -= entry.bytes.len() as u64;
+        }
+    }
+}
+
+impl MemoryRenderStore {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            inner: Mutex::new(MemoryEntries::default()),
+        }
+    }
+
+    /// Bytes of renders currently held
+    pub fn resident_bytes(&self) -> u64 {
+        self.inner.lock().bytes
+    }
+}
+
+#[async_trait]
+impl RenderCacheStore for MemoryRenderStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn get(&self, key: &RenderCacheKey) -> Option<Bytes> {
+        let mut inner = self.inner.lock();
+        let tick = inner.next_tick();
+        let entry = inner.entries.get_mut(key)?;
+        let previous = std::mem::replace(&mut entry.last_used, tick);
+        let bytes = entry.bytes.clone();
+        inner.recency.remove(&previous);
+        inner.recency.insert(tick, key.clone());
+        Some(bytes)
+    }
+
+    async fn put(&self, key: &RenderCacheKey, bytes: Bytes) {
+        let size = bytes.len() as u64;
+        // Would evict everything else and still not fit
+        if size > self.max_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock();
+        inner.remove(key);
+        let tick = inner.next_tick();
+        inner.entries.insert(
+            key.clone(),
+            MemoryEntry {
+                bytes,
+                last_used: tick,
+            },
+        );
+        inner.recency.insert(tick, key.clone());
+        inner.bytes += size;
+
+        while inner.bytes > self.max_bytes {
+            let Some((_, oldest)) = inner.recency.pop_first() else {
+                break;
+            };
+            if let Some(entry) = inner.entries.remove(&oldest) {
+                inner.bytes -= entry.bytes.len() as u64;
+            }
+        }
+    }
+
+    async fn remove_template(&self, template_id: &str) {
+        let mut inner = self.inner.lock();
+        let keys: Vec<_> = inner
+            .entries
+            .keys()
+            .filter(|key| key.template_id == template_id)
+            .cloned()
+            .collect();
+        for key in keys {
+            inner.remove(&key);
+        }
+    }
+}
+
+/// Renders stored in R2 under `generated/cache/`
+///
+/// Keys don't name their template, so the ones this instance wrote or read
+/// are remembered per template for invalidation. Renders cached by an earlier
+/// process are only retired by bumping the template's version.
+pub struct R2RenderStore {
+    r2: R2Client,
+    known: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl R2RenderStore {
+    pub fn new(r2: R2Client) -> Self {
+        Self {
+            r2,
+            known: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn remember(&self, key: &RenderCacheKey) {
+        self.known
+            .lock()
+            .entry(key.template_id.clone())
+            .or_default()
+            .insert(key.r2_key());
+    }
+}
+
+#[async_trait]
+impl RenderCacheStore for R2RenderStore {
+    fn name(&self) -> &'static str {
+        "r2"
+    }
+
+    async fn get(&self, key: &RenderCacheKey) -> Option<Bytes> {
+        match self.r2.download(&key.r2_key()).await {
+            Ok(bytes) => {
+                self.remember(key);
+                Some(Bytes::from(bytes))
+            }
+            Err(R2Error::NotFound(_)) => None,
+            Err(e) => {
+                warn!(error = %e, key = %key.r2_key(), "Failed to read cached render");
+                None
+            }
+        }
+    }
+
+    /// Uploads in the background so the response isn't held up by R2
+    async fn put(&self, key: &RenderCacheKey, bytes: Bytes) {
+        self.remember(key);
+        let r2 = self.r2.clone();
+        let r2_key = key.r2_key();
+        let content_type = key.format.content_type();
+        tokio::spawn(async move {
+            if let Err(e) = r2.upload_key(&r2_key, bytes.to_vec(), content_type).await {
+                warn!(error = %e, key = %r2_key, "Failed to store cached render");
+            }
+        });
+    }
+
+    async fn remove_template(&self, template_id: &str) {
+        let keys = self.known.lock().remove(template_id).unwrap_or_default();
+        for key in keys {
+            if let Err(e) = self.r2.delete(&key).await {
+                warn!(error = %e, key = %key, "Failed to delete cached render");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{PlacementSpec, PlacementType};
+    use crate::engine::{DesignLayer, GenerationLimits, OutputSettings};
+
+    fn request() -> MockupRequest {
+        MockupRequest {
+            designs: vec![DesignLayer {
+                design: DesignSource::Bytes(Bytes::from_static(b"design")),
+                placement: PlacementSpec::new(0.5, 0, -100, PlacementType::Front),
+                displacement_strength: 8.0,
+                blend_mode: None,
+            }],
+            template_id: "tee".to_string(),
+            apply_displacement: None,
+            tint_color: None,
+            remove_background: None,
+            output: OutputSettings::default(),
+            limits: GenerationLimits::default(),
+        }
+    }
+
+    fn key(request: &MockupRequest) -> RenderCacheKey {
+        RenderCacheKey::for_request(request, 1).unwrap()
+    }
+
+    fn png(width: u32, height: u32) -> Bytes {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(width, height)
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        Bytes::from(png)
+    }
+
+    fn memory_cache(max_bytes: u64) -> RenderCache {
+        RenderCache::new(vec![Arc::new(MemoryRenderStore::new(max_bytes))])
+    }
+
+    #[actix_web::test]
+    async fn test_identical_requests_hit() {
+        let cache = memory_cache(1 << 20);
+        assert!(cache.get(&key(&request())).await.is_none());
+
+        cache.put(&key(&request()), png(4, 3)).await;
+        let hit = cache.get(&key(&request())).await.unwrap();
+        assert_eq!((hit.width, hit.height), (4, 3));
+        assert_eq!(hit.content_type, "image/png");
+        assert_eq!(hit.bytes, png(4, 3));
+    }
+
+    #[test]
+    fn test_changing_any_field_misses() {
+        let base = key(&request());
+        let variants: Vec<(&str, fn(&mut MockupRequest))> = vec![
+            ("template_id", |r| r.template_id = "hoodie".to_string()),
+            ("design", |r| {
+                r.designs[0].design = DesignSource::Bytes(Bytes::from_static(b"other"))
+            }),
+            ("scale", |r| r.designs[0].placement.scale = 0.4),
+            ("offset_x", |r| r.designs[0].placement.offset_x = 10),
+            ("offset_y", |r| r.designs[0].placement.offset_y = -90),
+            ("rotation", |r| {
+                r.designs[0].placement.rotation_degrees = 5.0
+            }),
+            ("strength", |r| r.designs[0].displacement_strength = 9.0),
+            ("blend_mode", |r| {
+                r.designs[0].blend_mode = Some("multiply".to_string())
+            }),
+            ("apply_displacement", |r| r.apply_displacement = Some(false)),
+            ("tint_color", |r| r.tint_color = Some("0D0D0D".to_string())),
+            ("output_format", |r| r.output.format = OutputFormat::Webp),
+            ("quality", |r| r.output.quality = 70),
+        ];
+        for (field, change) in variants {
+            let mut changed = request();
+            change(&mut changed);
+            assert_ne!(key(&changed), base, "{}", field);
+        }
+
+        assert_ne!(
+            RenderCacheKey::for_request(&request(), 2).unwrap(),
+            base,
+            "template_version"
+        );
+        let mut by_url = request();
+        by_url.designs[0].design = DesignSource::Url("https://example.com/a.png".to_string());
+        assert!(RenderCacheKey::for_request(&by_url, 1).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_memory_store_evicts_least_recently_used() {
+        let store = MemoryRenderStore::new(250);
+        let keys: Vec<_> = (0..3)
+            .map(|i| {
+                let mut request = request();
+                request.template_id = format!("tee-{}", i);
+                key(&request)
+            })
+            .collect();
+        let bytes = Bytes::from(vec![0u8; 100]);
+        store.put(&keys[0], bytes.clone()).await;
+        store.put(&keys[1], bytes.clone()).await;
+        // Touch the first so the second is the oldest
+        assert!(store.get(&keys[0]).await.is_some());
+        store.put(&keys[2], bytes.clone()).await;
+
+        assert!(store.get(&keys[0]).await.is_some());
+        assert!(store.get(&keys[1]).await.is_none());
+        assert!(store.get(&keys[2]).await.is_some());
+        assert_eq!(store.resident_bytes(), 200);
+
+        // Larger than the whole budget
+        store.put(&keys[1], Bytes::from(vec![0u8; 300])).await;
+        assert!(store.get(&keys[1]).await.is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_invalidate_drops_only_that_template() {
+        let cache = memory_cache(1 << 20);
+        let mut other = request();
+        other.template_id = "hoodie".to_string();
+        cache.put(&key(&request()), png(2, 2)).await;
+        cache.put(&key(&other), png(2, 2)).await;
+
+        cache.invalidate_template("tee").await;
+        assert!(cache.get(&key(&request())).await.is_none());
+        assert!(cache.get(&key(&other)).await.is_some());
+    }
+}
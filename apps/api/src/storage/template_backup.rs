@@ -0,0 +1,1050 @@
+//! Template directory backup to R2
+//!
+//! Mirrors the local templates directory (images and metadata) to R2 under
+//! `templates/`, restores it onto a fresh instance, and detects drift between
+//! the two copies. A `templates/manifest.json` object records the SHA-256 of
+//! every file so unchanged files are never re-uploaded or re-downloaded.
+//! Drift checks also list `templates/` to catch objects the manifest vouches
+//! for that are gone from R2 or no longer the recorded size.
+//! Instances rewrite the manifest with conditional writes, starting over from
+//! the newer copy when another instance wrote it first.
+//!
+//! With `templates.source = "r2"` the backup is the template library itself:
+//! every instance pulls it into its templates directory on startup and
+//! reload, and uploads are published to it one template at a time.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, info, instrument, warn};
+
+use super::r2::{R2Client, R2Error, WriteCondition};
+use super::usage::ObjectLister;
+use crate::engine::{TemplateError, TemplatePull, TemplateSource};
+
+/// R2 prefix for template backups
+pub const TEMPLATE_BACKUP_PREFIX: &str = "templates";
+
+/// Concurrent uploads/downloads during backup and restore
+const TRANSFER_CONCURRENCY: usize = 8;
+
+/// Manifest rewrites tried before giving up on other instances' writes
+const MANIFEST_WRITE_ATTEMPTS: usize = 5;
+
+/// Checksum and size of a single backed-up file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Checksums of every file in a templates directory, keyed by relative path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateManifest {
+    pub generated_at: Option<DateTime<Utc>>,
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+impl TemplateManifest {
+    /// Build a manifest by hashing every file under `dir`
+    pub fn scan(dir: &Path) -> Result<Self, R2Error> {
+        let mut files = BTreeMap::new();
+        if dir.exists() {
+            scan_dir(dir, dir, &mut files)?;
+        }
+        Ok(Self {
+            generated_at: Some(Utc::now()),
+            files,
+        })
+    }
+
+    /// Only the entries for files in template directory `dir`, or all of them
+    pub fn within(&self, dir: Option<&str>) -> TemplateManifest {
+        TemplateManifest {
+            generated_at: self.generated_at,
+            files: self
+                .files
+                .iter()
+                .filter(|(path, _)| dir.map_or(true, |dir| in_dir(path, dir)))
+                .map(|(path, entry)| (path.clone(), entry.clone()))
+                .collect(),
+        }
+    }
+
+    /// Compare this (local) manifest against a remote one
+    pub fn drift(&self, remote: &TemplateManifest) -> DriftReport {
+        let mut report = DriftReport::default();
+
+        for (path, local) in &self.files {
+            match remote.files.get(path) {
+                None => report.missing_remote.push(path.clone()),
+                Some(entry) if entry != local => report.changed.push(path.clone()),
+                Some(_) => report.in_sync += 1,
+            }
+        }
+        for path in remote.files.keys() {
+            if !self.files.contains_key(path) {
+                report.missing_local.push(path.clone());
+            }
+        }
+
+        report
+    }
+}
+
+/// Differences between the local templates directory and its R2 backup
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DriftReport {
+    /// Files identical in both copies
+    pub in_sync: usize,
+    /// Local files not yet backed up
+    pub missing_remote: Vec<String>,
+    /// Backed-up files missing locally
+    pub missing_local: Vec<String>,
+    /// Files whose checksums differ
+    pub changed: Vec<String>,
+    /// Files the manifest lists that have no object in R2
+    pub missing_objects: Vec<String>,
+    /// Files whose object in R2 isn't the size the manifest records
+    pub size_mismatch: Vec<String>,
+}
+
+impl DriftReport {
+    pub fn has_drift(&self) -> bool {
+        !self.missing_remote.is_empty()
+            || !self.missing_local.is_empty()
+            || !self.changed.is_empty()
+            || !self.missing_objects.is_empty()
+            || !self.size_mismatch.is_empty()
+    }
+
+    /// Check the objects behind `remote` against `sizes`, the listed size of
+    /// each backup key
+    ///
+    /// Files found wrong here no longer count as in sync.
+    fn check_objects(&mut self, remote: &TemplateManifest, sizes: &HashMap<String, u64>) {
+        for (path, entry) in &remote.files {
+            let bad = match sizes.get(&backup_key(path)) {
+                None => &mut self.missing_objects,
+                Some(&size) if size != entry.size => &mut self.size_mismatch,
+                Some(_) => continue,
+            };
+            bad.push(path.clone());
+            let counted = !self.missing_local.contains(path) && !self.changed.contains(path);
+            if counted {
+                self.in_sync = self.in_sync.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Outcome of a backup or restore run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TransferSummary {
+    /// Files transferred
+    pub transferred: usize,
+    /// Files skipped because checksums already matched
+    pub unchanged: usize,
+    /// Files that failed, with the error message
+    pub failed: Vec<(String, String)>,
+    pub bytes: u64,
+    /// Local files deleted by a pull because the backup no longer lists them
+    pub deleted: usize,
+}
+
+/// Object storage the backup is kept in; R2 outside tests
+#[async_trait]
+pub trait BackupStore: ObjectLister {
+    /// Fails with [`R2Error::NotFound`] for a missing key
+    async fn get(&self, key: &str) -> Result<Vec<u8>, R2Error>;
+    /// Like `get`, with the object's ETag when the store reports one
+    async fn get_tagged(&self, key: &str) -> Result<(Vec<u8>, Option<String>), R2Error>;
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), R2Error>;
+    /// Like `put`, but fails with [`R2Error::PreconditionFailed`] unless the
+    /// object still meets `condition`
+    async fn put_if(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        condition: WriteCondition<'_>,
+    ) -> Result<(), R2Error>;
+    async fn delete(&self, key: &str) -> Result<(), R2Error>;
+}
+
+#[async_trait]
+impl BackupStore for R2Client {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, R2Error> {
+        self.download(key).await
+    }
+
+    async fn get_tagged(&self, key: &str) -> Result<(Vec<u8>, Option<String>), R2Error> {
+        self.download_tagged(key).await
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), R2Error> {
+        self.upload_key(key, data, content_type).await.map(|_| ())
+    }
+
+    async fn put_if(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        condition: WriteCondition<'_>,
+    ) -> Result<(), R2Error> {
+        self.upload_key_if(key, data, content_type, condition)
+            .await
+            .map(|_| ())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), R2Error> {
+        R2Client::delete(self, key).await
+    }
+}
+
+/// Backs up and restores the templates directory using R2
+pub struct TemplateBackup {
+    store: Arc<dyn BackupStore>,
+    local_dir: PathBuf,
+    /// Serializes manifest rewrites from this instance; other instances are
+    /// caught by the conditional write
+    manifest_lock: tokio::sync::Mutex<()>,
+}
+
+impl TemplateBackup {
+    pub fn new(r2_client: R2Client, local_dir: impl Into<PathBuf>) -> Self {
+        Self::with_store(Arc::new(r2_client), local_dir)
+    }
+
+    /// Back up to any object store
+    pub fn with_store(store: Arc<dyn BackupStore>, local_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            store,
+            local_dir: local_dir.into(),
+            manifest_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Upload new and changed local files, then publish the local manifest
+    #[instrument(skip(self), fields(dir = %self.local_dir.display()))]
+    pub async fn backup(&self) -> Result<TransferSummary, R2Error> {
+        let _manifest = self.manifest_lock.lock().await;
+        let local = self.scan_local(None).await?;
+        let remote = self.fetch_remote_manifest().await?.unwrap_or_default();
+        let drift = local.drift(&remote);
+
+        let to_upload: Vec<String> = drift
+            .missing_remote
+            .iter()
+            .chain(drift.changed.iter())
+            .cloned()
+            .collect();
+
+        let mut summary = TransferSummary {
+            unchanged: drift.in_sync,
+            ..Default::default()
+        };
+
+        let results: Vec<(String, Result<u64, R2Error>)> = stream::iter(to_upload)
+            .map(|rel| async move {
+                let result = self.upload_file(&rel).await;
+                (rel, result)
+            })
+            .buffer_unordered(TRANSFER_CONCURRENCY)
+            .collect()
+            .await;
+
+        for (rel, result) in results {
+            match result {
+                Ok(bytes) => {
+                    summary.transferred += 1;
+                    summary.bytes += bytes;
+                }
+                Err(e) => {
+                    warn!(file = %rel, error = %e, "Failed to back up template file");
+                    summary.failed.push((rel, e.to_string()));
+                }
+            }
+        }
+
+        self.update_manifest(|remote| {
+            // Files deleted locally stay in R2 so a bad local state can't wipe the backup
+            let mut uploaded = remote.unwrap_or_default();
+            uploaded.generated_at = local.generated_at;
+            for (rel, entry) in &local.files {
+                // Failed files keep their previous entry so drift still reports them
+                if !summary.failed.iter().any(|(failed, _)| failed == rel) {
+                    uploaded.files.insert(rel.clone(), entry.clone());
+                }
+            }
+            (Some(uploaded), ())
+        })
+        .await?;
+
+        info!(
+            transferred = summary.transferred,
+            unchanged = summary.unchanged,
+            failed = summary.failed.len(),
+            "Template backup completed"
+        );
+
+        Ok(summary)
+    }
+
+    /// Download every backed-up file that is missing or different locally
+    #[instrument(skip(self), fields(dir = %self.local_dir.display()))]
+    pub async fn restore(&self) -> Result<TransferSummary, R2Error> {
+        let remote = self
+            .fetch_remote_manifest()
+            .await?
+            .ok_or_else(|| R2Error::NotFound(manifest_key()))?;
+        let local = self.scan_local(None).await?;
+        let summary = self.download_drift(&local, &remote).await;
+
+        info!(
+            transferred = summary.transferred,
+            unchanged = summary.unchanged,
+            failed = summary.failed.len(),
+            "Template restore completed"
+        );
+
+        Ok(summary)
+    }
+
+    /// Make the local directory, or one template directory in it, match the backup
+    ///
+    /// Like `restore`, but local files the manifest doesn't list are deleted,
+    /// so templates removed from the library disappear here too. Without a
+    /// manifest there is no library yet, and the local directory is left alone.
+    #[instrument(skip(self), fields(dir = %self.local_dir.display()))]
+    pub async fn pull_templates(&self, dir: Option<&str>) -> Result<TransferSummary, R2Error> {
+        let Some(remote) = self.fetch_remote_manifest().await? else {
+            warn!("No template library in R2 yet; run a template backup to seed it");
+            return Ok(TransferSummary::default());
+        };
+        let remote = remote.within(dir);
+        let local = self.scan_local(dir).await?;
+        let mut summary = self.download_drift(&local, &remote).await;
+
+        for rel in local.drift(&remote).missing_remote {
+            let path = safe_join(&self.local_dir, &rel)?;
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => summary.deleted += 1,
+                Err(e) => {
+                    warn!(file = %rel, error = %e, "Failed to delete template file");
+                    summary.failed.push((rel, e.to_string()));
+                    continue;
+                }
+            }
+            // Drop the template directory once its last file is gone
+            if let Some(parent) = path.parent().filter(|p| *p != self.local_dir) {
+                tokio::fs::remove_dir(parent).await.ok();
+            }
+        }
+
+        info!(
+            transferred = summary.transferred,
+            unchanged = summary.unchanged,
+            deleted = summary.deleted,
+            failed = summary.failed.len(),
+            "Template pull completed"
+        );
+        Ok(summary)
+    }
+
+    /// Upload one template directory and replace its manifest entries
+    ///
+    /// Files the directory no longer has are dropped from the manifest and
+    /// then deleted. Nothing is recorded unless every file uploads.
+    #[instrument(skip(self))]
+    pub async fn publish_template(&self, dir: &str) -> Result<TransferSummary, R2Error> {
+        let _manifest = self.manifest_lock.lock().await;
+        let local = self.scan_local(Some(dir)).await?;
+        if local.files.is_empty() {
+            return Err(R2Error::NotFound(
+                self.local_dir.join(dir).display().to_string(),
+            ));
+        }
+        let remote = self.fetch_remote_manifest().await?.unwrap_or_default();
+        let drift = local.drift(&remote.within(Some(dir)));
+
+        let mut summary = TransferSummary {
+            unchanged: drift.in_sync,
+            ..Default::default()
+        };
+        for rel in drift.missing_remote.iter().chain(&drift.changed) {
+            summary.bytes += self.upload_file(rel).await?;
+            summary.transferred += 1;
+        }
+
+        self.update_manifest(|remote| {
+            let mut remote = remote.unwrap_or_default();
+            remote.files.retain(|path, _| !in_dir(path, dir));
+            remote.files.extend(local.files.clone());
+            remote.generated_at = Some(Utc::now());
+            (Some(remote), ())
+        })
+        .await?;
+
+        for rel in drift.missing_local {
+            if let Err(e) = self.store.delete(&backup_key(&rel)).await {
+                warn!(file = %rel, error = %e, "Failed to delete unpublished template file");
+            }
+        }
+        info!(dir, transferred = summary.transferred, "Published template");
+        Ok(summary)
+    }
+
+    /// Drop one template directory from the manifest, then delete its files
+    ///
+    /// Returns how many files the manifest listed for it.
+    #[instrument(skip(self))]
+    pub async fn unpublish_template(&self, dir: &str) -> Result<usize, R2Error> {
+        let _manifest = self.manifest_lock.lock().await;
+        let removed = self
+            .update_manifest(|remote| {
+                let Some(mut remote) = remote else {
+                    return (None, Vec::new());
+                };
+                let removed: Vec<String> = remote.within(Some(dir)).files.into_keys().collect();
+                if removed.is_empty() {
+                    return (None, removed);
+                }
+                remote.files.retain(|path, _| !in_dir(path, dir));
+                remote.generated_at = Some(Utc::now());
+                (Some(remote), removed)
+            })
+            .await?;
+        if removed.is_empty() {
+            return Ok(0);
+        }
+
+        for rel in &removed {
+            if let Err(e) = self.store.delete(&backup_key(rel)).await {
+                warn!(file = %rel, error = %e, "Failed to delete unpublished template file");
+            }
+        }
+        info!(dir, files = removed.len(), "Unpublished template");
+        Ok(removed.len())
+    }
+
+    /// Download every file in `remote` that is missing or different in `local`
+    async fn download_drift(
+        &self,
+        local: &TemplateManifest,
+        remote: &TemplateManifest,
+    ) -> TransferSummary {
+        let drift = local.drift(remote);
+        let to_download: Vec<String> = drift
+            .missing_local
+            .iter()
+            .chain(drift.changed.iter())
+            .cloned()
+            .collect();
+
+        let mut summary = TransferSummary {
+            unchanged: drift.in_sync,
+            ..Default::default()
+        };
+
+        let results: Vec<(String, Result<u64, R2Error>)> = stream::iter(to_download)
+            .map(|rel| {
+                let expected = remote.files.get(&rel).cloned();
+                async move {
+                    let result = match expected {
+                        Some(entry) => self.restore_file(&rel, &entry).await,
+                        None => Err(R2Error::NotFound(rel.clone())),
+                    };
+                    (rel, result)
+                }
+            })
+            .buffer_unordered(TRANSFER_CONCURRENCY)
+            .collect()
+            .await;
+
+        for (rel, result) in results {
+            match result {
+                Ok(bytes) => {
+                    summary.transferred += 1;
+                    summary.bytes += bytes;
+                }
+                Err(e) => {
+                    warn!(file = %rel, error = %e, "Failed to restore template file");
+                    summary.failed.push((rel, e.to_string()));
+                }
+            }
+        }
+        summary
+    }
+
+    /// Compare local checksums against the backup manifest, and the
+    /// manifest against the objects in R2
+    pub async fn drift(&self) -> Result<DriftReport, R2Error> {
+        let local = self.scan_local(None).await?;
+        let remote = self.fetch_remote_manifest().await?.unwrap_or_default();
+        let mut report = local.drift(&remote);
+        report.check_objects(&remote, &self.list_backup().await?);
+        Ok(report)
+    }
+
+    /// Size of every object under the backup prefix, keyed by object key
+    async fn list_backup(&self) -> Result<HashMap<String, u64>, R2Error> {
+        let prefix = format!("{}/", TEMPLATE_BACKUP_PREFIX);
+        let mut sizes = HashMap::new();
+        let mut token = None;
+        loop {
+            let page = self.store.list_page(&prefix, token).await?;
+            sizes.extend(page.objects.into_iter().map(|o| (o.key, o.size)));
+            token = page.next_token;
+            if token.is_none() {
+                return Ok(sizes);
+            }
+        }
+    }
+
+    /// Checksums of the local directory, or of one template directory in it
+    async fn scan_local(&self, dir: Option<&str>) -> Result<TemplateManifest, R2Error> {
+        let root = self.local_dir.clone();
+        let scanned = match dir {
+            Some(dir) => safe_join(&root, dir)?,
+            None => root.clone(),
+        };
+        tokio::task::spawn_blocking(move || {
+            let mut files = BTreeMap::new();
+            if scanned.exists() {
+                scan_dir(&root, &scanned, &mut files)?;
+            }
+            Ok(TemplateManifest {
+                generated_at: Some(Utc::now()),
+                files,
+            })
+        })
+        .await
+        .map_err(|e| R2Error::IoError(std::io::Error::other(e)))?
+    }
+
+    /// Rewrite the manifest with `change`
+    ///
+    /// `change` gets the current manifest, if there is one, and returns the
+    /// new one, or `None` to leave it alone, with a value to hand back. The
+    /// write only lands if no other instance replaced the manifest since it
+    /// was read; otherwise `change` runs again on the newer manifest.
+    async fn update_manifest<T>(
+        &self,
+        mut change: impl FnMut(Option<TemplateManifest>) -> (Option<TemplateManifest>, T),
+    ) -> Result<T, R2Error> {
+        for _ in 0..MANIFEST_WRITE_ATTEMPTS {
+            let (current, etag) = match self.store.get_tagged(&manifest_key()).await {
+                Ok((data, etag)) => (Some(parse_manifest(&data)?), etag),
+                Err(R2Error::NotFound(_)) => (None, None),
+                Err(e) => return Err(e),
+            };
+            let condition = match (&current, &etag) {
+                (None, _) => WriteCondition::Absent,
+                (Some(_), Some(etag)) => WriteCondition::ETag(etag.as_str()),
+                (Some(_), None) => {
+                    return Err(R2Error::DownloadFailed(format!(
+                        "No ETag for {}",
+                        manifest_key()
+                    )))
+                }
+            };
+
+            let (updated, output) = change(current);
+            let Some(updated) = updated else {
+                return Ok(output);
+            };
+            let manifest_json = serde_json::to_vec_pretty(&updated)
+                .map_err(|e| R2Error::UploadFailed(format!("Failed to encode manifest: {}", e)))?;
+            match self
+                .store
+                .put_if(
+                    &manifest_key(),
+                    manifest_json,
+                    "application/json",
+                    condition,
+                )
+                .await
+            {
+                Ok(()) => return Ok(output),
+                Err(R2Error::PreconditionFailed(_)) => {
+                    debug!("Template manifest changed while it was rewritten; retrying");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(R2Error::PreconditionFailed(format!(
+            "{} kept changing; gave up after {} attempts",
+            manifest_key(),
+            MANIFEST_WRITE_ATTEMPTS
+        )))
+    }
+
+    async fn fetch_remote_manifest(&self) -> Result<Option<TemplateManifest>, R2Error> {
+        match self.store.get(&manifest_key()).await {
+            Ok(data) => parse_manifest(&data).map(Some),
+            Err(R2Error::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn upload_file(&self, rel: &str) -> Result<u64, R2Error> {
+        let data = tokio::fs::read(self.local_dir.join(rel)).await?;
+        let size = data.len() as u64;
+        self.store
+            .put(&backup_key(rel), data, content_type_for(rel))
+            .await?;
+        Ok(size)
+    }
+
+    async fn restore_file(&self, rel: &str, expected: &ManifestEntry) -> Result<u64, R2Error> {
+        let target = safe_join(&self.local_dir, rel)?;
+        let data = self.store.get(&backup_key(rel)).await?;
+
+        if sha256_hex(&data) != expected.sha256 {
+            return Err(R2Error::ChecksumMismatch(rel.to_string()));
+        }
+
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Write to a temp file first so a crash never leaves a truncated template
+        let tmp = target.with_extension("restore-tmp");
+        tokio::fs::write(&tmp, &data).await?;
+        tokio::fs::rename(&tmp, &target).await?;
+
+        Ok(data.len() as u64)
+    }
+}
+
+/// The backup as the shared template library
+#[async_trait]
+impl TemplateSource for TemplateBackup {
+    fn name(&self) -> &'static str {
+        "r2"
+    }
+
+    async fn pull(&self, dir_name: Option<&str>) -> Result<TemplatePull, TemplateError> {
+        let summary = self.pull_templates(dir_name).await.map_err(source_error)?;
+        Ok(TemplatePull {
+            source: self.name().to_string(),
+            downloaded: summary.transferred,
+            unchanged: summary.unchanged,
+            deleted: summary.deleted,
+            failed: summary
+                .failed
+                .into_iter()
+                .map(|(file, error)| format!("{}: {}", file, error))
+                .collect(),
+        })
+    }
+
+    async fn publish(&self, dir_name: &str) -> Result<(), TemplateError> {
+        self.publish_template(dir_name)
+            .await
+            .map(|_| ())
+            .map_err(source_error)
+    }
+
+    async fn unpublish(&self, dir_name: &str) -> Result<(), TemplateError> {
+        self.unpublish_template(dir_name)
+            .await
+            .map(|_| ())
+            .map_err(source_error)
+    }
+}
+
+fn source_error(e: R2Error) -> TemplateError {
+    TemplateError::Source(e.to_string())
+}
+
+/// Whether manifest path `path` is a file in template directory `dir`
+fn in_dir(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir)
+        .map_or(false, |rest| rest.starts_with('/'))
+}
+
+fn parse_manifest(data: &[u8]) -> Result<TemplateManifest, R2Error> {
+    serde_json::from_slice(data)
+        .map_err(|e| R2Error::DownloadFailed(format!("Invalid template manifest: {}", e)))
+}
+
+fn manifest_key() -> String {
+    format!("{}/manifest.json", TEMPLATE_BACKUP_PREFIX)
+}
+
+fn backup_key(rel: &str) -> String {
+    format!("{}/{}", TEMPLATE_BACKUP_PREFIX, rel)
+}
+
+fn content_type_for(rel: &str) -> &'static str {
+    match Path::new(rel).extension().and_then(|e| e.to_str()) {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Join a manifest path onto the local directory, rejecting escapes
+fn safe_join(base: &Path, rel: &str) -> Result<PathBuf, R2Error> {
+    let rel_path = Path::new(rel);
+    if rel.is_empty()
+        || !rel_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(R2Error::InvalidPath(rel.to_string()));
+    }
+    Ok(base.join(rel_path))
+}
+
+fn scan_dir(
+    root: &Path,
+    dir: &Path,
+    files: &mut BTreeMap<String, ManifestEntry>,
+) -> Result<(), R2Error> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan_dir(root, &path, files)?;
+            continue;
+        }
+
+        let rel = path
+            .strip_prefix(root)
+            .map_err(|_| R2Error::InvalidPath(path.display().to_string()))?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        // Skip hidden files and leftovers from interrupted restores
+        if rel.split('/').any(|part| part.starts_with('.')) || rel.ends_with(".restore-tmp") {
+            continue;
+        }
+
+        let data = std::fs::read(&path)?;
+        files.insert(
+            rel,
+            ManifestEntry {
+                sha256: sha256_hex(&data),
+                size: data.len() as u64,
+            },
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ListedObject, ObjectPage};
+
+    fn entry(sha256: &str) -> ManifestEntry {
+        ManifestEntry {
+            sha256: sha256.to_string(),
+            size: 1,
+        }
+    }
+
+    fn manifest(files: &[(&str, &str)]) -> TemplateManifest {
+        TemplateManifest {
+            generated_at: None,
+            files: files
+                .iter()
+                .map(|(path, sha)| (path.to_string(), entry(sha)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_drift_report() {
+        let local = manifest(&[
+            ("a/base.png", "1"),
+            ("a/metadata.json", "2"),
+            ("b/base.png", "3"),
+        ]);
+        let remote = manifest(&[
+            ("a/base.png", "1"),
+            ("a/metadata.json", "x"),
+            ("c/base.png", "4"),
+        ]);
+
+        let drift = local.drift(&remote);
+        assert_eq!(drift.in_sync, 1);
+        assert_eq!(drift.changed, vec!["a/metadata.json"]);
+        assert_eq!(drift.missing_remote, vec!["b/base.png"]);
+        assert_eq!(drift.missing_local, vec!["c/base.png"]);
+        assert!(drift.has_drift());
+
+        assert!(!local.drift(&local).has_drift());
+    }
+
+    #[test]
+    fn test_safe_join_rejects_escapes() {
+        let base = Path::new("/srv/templates");
+        assert!(safe_join(base, "white_male_front/base.png").is_ok());
+        assert!(safe_join(base, "../etc/passwd").is_err());
+        assert!(safe_join(base, "/etc/passwd").is_err());
+        assert!(safe_join(base, "").is_err());
+    }
+
+    /// In-memory bucket counting downloads, with content hashes as ETags
+    #[derive(Default)]
+    struct MemoryStore {
+        objects: parking_lot::Mutex<BTreeMap<String, Vec<u8>>>,
+        gets: std::sync::atomic::AtomicUsize,
+        /// Written just before the next conditional write lands, as another
+        /// instance would
+        race: parking_lot::Mutex<Option<(String, Vec<u8>)>>,
+    }
+
+    impl MemoryStore {
+        fn gets(&self) -> usize {
+            self.gets.load(std::sync::atomic::Ordering::SeqCst)
+        }
+
+        fn has(&self, key: &str) -> bool {
+            self.objects.lock().contains_key(key)
+        }
+    }
+
+    #[async_trait]
+    impl ObjectLister for MemoryStore {
+        async fn list_page(
+            &self,
+            prefix: &str,
+            _token: Option<String>,
+        ) -> Result<ObjectPage, R2Error> {
+            let objects = self
+                .objects
+                .lock()
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(key, data)| ListedObject {
+                    key: key.clone(),
+                    size: data.len() as u64,
+                })
+                .collect();
+            Ok(ObjectPage {
+                objects,
+                next_token: None,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl BackupStore for MemoryStore {
+        async fn get(&self, key: &str) -> Result<Vec<u8>, R2Error> {
+            self.gets.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.objects
+                .lock()
+                .get(key)
+                .cloned()
+                .ok_or_else(|| R2Error::NotFound(key.to_string()))
+        }
+
+        async fn get_tagged(&self, key: &str) -> Result<(Vec<u8>, Option<String>), R2Error> {
+            let data = self.get(key).await?;
+            let etag = sha256_hex(&data);
+            Ok((data, Some(etag)))
+        }
+
+        async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> Result<(), R2Error> {
+            self.objects.lock().insert(key.to_string(), data);
+            Ok(())
+        }
+
+        async fn put_if(
+            &self,
+            key: &str,
+            data: Vec<u8>,
+            _content_type: &str,
+            condition: WriteCondition<'_>,
+        ) -> Result<(), R2Error> {
+            let mut objects = self.objects.lock();
+            if let Some((raced_key, raced)) = self.race.lock().take() {
+                objects.insert(raced_key, raced);
+            }
+            let current = objects.get(key).map(|data| sha256_hex(data));
+            let holds = match condition {
+                WriteCondition::Absent => current.is_none(),
+                WriteCondition::ETag(etag) => current.as_deref() == Some(etag),
+            };
+            if !holds {
+                return Err(R2Error::PreconditionFailed(key.to_string()));
+            }
+            objects.insert(key.to_string(), data);
+            Ok(())
+        }
+
+        async fn delete(&self, key: &str) -> Result<(), R2Error> {
+            self.objects.lock().remove(key);
+            Ok(())
+        }
+    }
+
+    fn write(dir: &Path, rel: &str, data: &[u8]) {
+        let path = dir.join(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pull_downloads_only_changed_files() {
+        let base = std::env::temp_dir().join(format!("library-{}", uuid::Uuid::new_v4()));
+        let (publisher_dir, replica_dir) = (base.join("publisher"), base.join("replica"));
+        write(&publisher_dir, "tee/metadata.json", b"{}");
+        write(&publisher_dir, "tee/base.png", b"base");
+        write(&publisher_dir, "mug/base.png", b"mug");
+
+        let store = Arc::new(MemoryStore::default());
+        let publisher = TemplateBackup::with_store(store.clone(), &publisher_dir);
+        let replica = TemplateBackup::with_store(store.clone(), &replica_dir);
+        publisher.publish_template("tee").await.unwrap();
+        publisher.publish_template("mug").await.unwrap();
+
+        // A fresh replica fetches everything
+        let first = replica.pull_templates(None).await.unwrap();
+        assert_eq!(first.transferred, 3);
+        assert_eq!(
+            std::fs::read(replica_dir.join("tee/base.png")).unwrap(),
+            b"base"
+        );
+
+        // A second pull only reads the manifest
+        let gets = store.gets();
+        let second = replica.pull_templates(None).await.unwrap();
+        assert_eq!((second.transferred, second.unchanged), (0, 3));
+        assert_eq!(store.gets(), gets + 1);
+
+        // A republished file is the only one fetched again
+        write(&publisher_dir, "tee/base.png", b"base v2");
+        publisher.publish_template("tee").await.unwrap();
+        let third = replica.pull_templates(Some("tee")).await.unwrap();
+        assert_eq!((third.transferred, third.unchanged), (1, 1));
+        assert_eq!(
+            std::fs::read(replica_dir.join("tee/base.png")).unwrap(),
+            b"base v2"
+        );
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn test_unpublish_removes_template_everywhere() {
+        let base = std::env::temp_dir().join(format!("unpublish-{}", uuid::Uuid::new_v4()));
+        let (publisher_dir, replica_dir) = (base.join("publisher"), base.join("replica"));
+        write(&publisher_dir, "tee/base.png", b"base");
+        write(&publisher_dir, "mug/base.png", b"mug");
+
+        let store = Arc::new(MemoryStore::default());
+        let publisher = TemplateBackup::with_store(store.clone(), &publisher_dir);
+        let replica = TemplateBackup::with_store(store.clone(), &replica_dir);
+        publisher.publish_template("tee").await.unwrap();
+        publisher.publish_template("mug").await.unwrap();
+        replica.pull_templates(None).await.unwrap();
+
+        assert_eq!(publisher.unpublish_template("mug").await.unwrap(), 1);
+        assert!(!store.has(&backup_key("mug/base.png")));
+
+        let pulled = replica.pull_templates(None).await.unwrap();
+        assert_eq!(pulled.deleted, 1);
+        assert!(!replica_dir.join("mug").exists());
+        assert!(replica_dir.join("tee/base.png").exists());
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn test_publish_keeps_templates_published_meanwhile() {
+        let dir = std::env::temp_dir().join(format!("race-{}", uuid::Uuid::new_v4()));
+        write(&dir, "tee/base.png", b"base");
+        write(&dir, "hat/base.png", b"hat");
+
+        let store = Arc::new(MemoryStore::default());
+        let publisher = TemplateBackup::with_store(store.clone(), &dir);
+        publisher.publish_template("tee").await.unwrap();
+
+        // Another instance publishes a template between this one's read and write
+        let mut raced = publisher.fetch_remote_manifest().await.unwrap().unwrap();
+        raced.files.insert(
+            "mug/base.png".to_string(),
+            ManifestEntry {
+                sha256: sha256_hex(b"mug"),
+                size: 3,
+            },
+        );
+        *store.race.lock() = Some((manifest_key(), serde_json::to_vec(&raced).unwrap()));
+        publisher.publish_template("hat").await.unwrap();
+
+        let published = publisher.fetch_remote_manifest().await.unwrap().unwrap();
+        assert_eq!(
+            published.files.keys().collect::<Vec<_>>(),
+            vec!["hat/base.png", "mug/base.png", "tee/base.png"]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_pull_without_library_keeps_local_templates() {
+        let dir = std::env::temp_dir().join(format!("unseeded-{}", uuid::Uuid::new_v4()));
+        write(&dir, "tee/base.png", b"base");
+
+        let backup = TemplateBackup::with_store(Arc::new(MemoryStore::default()), &dir);
+        let pulled = backup.pull_templates(None).await.unwrap();
+        assert_eq!(pulled.deleted, 0);
+        assert!(dir.join("tee/base.png").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_drift_checks_objects_behind_the_manifest() {
+        let dir = std::env::temp_dir().join(format!("drift-{}", uuid::Uuid::new_v4()));
+        write(&dir, "tee/base.png", b"base");
+        write(&dir, "tee/metadata.json", b"{}");
+        write(&dir, "mug/base.png", b"mug");
+
+        let store = Arc::new(MemoryStore::default());
+        let backup = TemplateBackup::with_store(store.clone(), &dir);
+        backup.backup().await.unwrap();
+        let drift = backup.drift().await.unwrap();
+        assert_eq!(drift.in_sync, 3);
+        assert!(!drift.has_drift());
+
+        // The manifest still vouches for both, but R2 lost one and truncated the other
+        store.objects.lock().remove(&backup_key("mug/base.png"));
+        store
+            .objects
+            .lock()
+            .insert(backup_key("tee/base.png"), b"ba".to_vec());
+        let drift = backup.drift().await.unwrap();
+        assert_eq!(drift.missing_objects, vec!["mug/base.png"]);
+        assert_eq!(drift.size_mismatch, vec!["tee/base.png"]);
+        assert_eq!(drift.in_sync, 1);
+        assert!(drift.has_drift());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_manifest_within_matches_whole_directory_names() {
+        let all = manifest(&[("tee/base.png", "1"), ("tee_v2/base.png", "2")]);
+        let tee = all.within(Some("tee"));
+        assert_eq!(tee.files.keys().collect::<Vec<_>>(), vec!["tee/base.png"]);
+        assert_eq!(all.within(None).files.len(), 2);
+    }
+}
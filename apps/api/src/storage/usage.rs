@@ -0,0 +1,481 @@
+//! R2 storage usage by provider and asset type
+//!
+//! A report walks every object under a prefix and adds its size to the
+//! provider and asset type its key parses to. Walking a large bucket takes a
+//! while, so [`UsageReports`] runs walks in the background and keeps each
+//! prefix's report for a configurable time.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use super::r2::{AssetPath, R2Client, R2Error, RENDER_CACHE_PREFIX};
+
+/// Asset type of keys that don't parse as an asset path
+const UNRECOGNIZED: &str = "other";
+
+/// An object listed from R2
+#[derive(Debug, Clone)]
+pub struct ListedObject {
+    pub key: String,
+    pub size: u64,
+}
+
+/// One page of a listing, with the token for the next page if there is one
+#[derive(Debug, Default)]
+pub struct ObjectPage {
+    pub objects: Vec<ListedObject>,
+    pub next_token: Option<String>,
+}
+
+/// Object storage listed by usage reports; R2 outside tests
+#[async_trait]
+pub trait ObjectLister: Send + Sync {
+    /// Objects under `prefix`, continuing from `token`
+    async fn list_page(&self, prefix: &str, token: Option<String>) -> Result<ObjectPage, R2Error>;
+}
+
+#[async_trait]
+impl ObjectLister for R2Client {
+    async fn list_page(&self, prefix: &str, token: Option<String>) -> Result<ObjectPage, R2Error> {
+        R2Client::list_page(self, prefix, token).await
+    }
+}
+
+/// Objects and bytes stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct StorageUsage {
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+impl StorageUsage {
+    fn add(&mut self, size: u64) {
+        self.objects += 1;
+        self.bytes += size;
+    }
+}
+
+/// Storage used by one provider's assets
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ProviderUsage {
+    pub objects: u64,
+    pub bytes: u64,
+    /// Usage by asset type, e.g. `mockup_template`; `blob` for deduplicated
+    /// assets and `other` for keys that aren't asset paths
+    pub asset_types: BTreeMap<String, StorageUsage>,
+}
+
+/// Storage used under a prefix, grouped by provider and asset type
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UsageReport {
+    /// Prefix walked; empty for the whole bucket
+    pub prefix: String,
+    pub objects: u64,
+    pub bytes: u64,
+    /// Usage by provider code, or by top-level folder such as `generated`
+    pub providers: BTreeMap<String, ProviderUsage>,
+    /// When the walk finished
+    pub as_of: DateTime<Utc>,
+    /// How long the walk took
+    pub duration_ms: u64,
+}
+
+impl UsageReport {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            objects: 0,
+            bytes: 0,
+            providers: BTreeMap::new(),
+            as_of: Utc::now(),
+            duration_ms: 0,
+        }
+    }
+
+    /// Count an object under the provider and asset type of its key
+    pub fn record(&mut self, key: &str, size: u64) {
+        let (provider, asset_type) = usage_group(key);
+        self.objects += 1;
+        self.bytes += size;
+        let provider = self.providers.entry(provider).or_default();
+        provider.objects += 1;
+        provider.bytes += size;
+        provider
+            .asset_types
+            .entry(asset_type)
+            .or_default()
+            .add(size);
+    }
+}
+
+/// Provider and asset type an object's storage is counted under
+///
+/// Keys that aren't asset paths are counted under their top-level folder.
+fn usage_group(key: &str) -> (String, String) {
+    if let Ok(path) = AssetPath::from_key(key) {
+        return (path.provider, path.asset_type.to_string());
+    }
+    if key.starts_with(RENDER_CACHE_PREFIX) {
+        return ("generated".to_string(), "render_cache".to_string());
+    }
+
+    let mut parts = key.splitn(3, '/');
+    let folder = parts
+        .next()
+        .filter(|folder| !folder.is_empty())
+        .unwrap_or(UNRECOGNIZED);
+    let asset_type = match parts.next() {
+        Some("blobs") => "blob",
+        _ => UNRECOGNIZED,
+    };
+    (folder.to_string(), asset_type.to_string())
+}
+
+/// Walk every page of the listing under `prefix` and total what it holds
+pub async fn walk_usage(lister: &dyn ObjectLister, prefix: &str) -> Result<UsageReport, R2Error> {
+    let started = Instant::now();
+    let mut report = UsageReport::new(prefix);
+    let mut token: Option<String> = None;
+
+    loop {
+        let page = lister.list_page(prefix, token.clone()).await?;
+        for object in &page.objects {
+            report.record(&object.key, object.size);
+        }
+        match page.next_token {
+            // A token handed back unchanged would list the same page forever
+            Some(next) if token.as_deref() == Some(next.as_str()) => {
+                return Err(R2Error::ListFailed(format!(
+                    "Listing of '{}' repeated continuation token {}",
+                    prefix, next
+                )));
+            }
+            Some(next) => token = Some(next),
+            None => break,
+        }
+    }
+
+    report.as_of = Utc::now();
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    Ok(report)
+}
+
+/// A prefix's report, as the cache holds it
+#[derive(Debug, Clone, Default)]
+pub struct UsageSnapshot {
+    /// Latest finished report; `None` until the first walk finishes
+    pub report: Option<Arc<UsageReport>>,
+    /// The report is older than the cache's TTL
+    pub stale: bool,
+    /// A walk of this prefix is running
+    pub refreshing: bool,
+    /// Why the last walk failed, if it did
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct UsageEntry {
+    report: Option<Arc<UsageReport>>,
+    refreshing: bool,
+    last_error: Option<String>,
+}
+
+/// Usage reports by prefix, walked in the background and kept for `ttl`
+pub struct UsageReports {
+    lister: Arc<dyn ObjectLister>,
+    ttl: Duration,
+    entries: parking_lot::Mutex<HashMap<String, UsageEntry>>,
+}
+
+impl UsageReports {
+    pub fn new(r2_client: R2Client, ttl: Duration) -> Self {
+        Self::with_lister(Arc::new(r2_client), ttl)
+    }
+
+    /// Report on any object store
+    pub fn with_lister(lister: Arc<dyn ObjectLister>, ttl: Duration) -> Self {
+        Self {
+            lister,
+            ttl,
+            entries: parking_lot::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached report for `prefix`
+    ///
+    /// A walk is started in the background when the report is missing or
+    /// older than the TTL, or when `force_refresh` is set, unless one is
+    /// already running.
+    pub fn get(self: &Arc<Self>, prefix: &str, force_refresh: bool) -> UsageSnapshot {
+        let mut entries = self.entries.lock();
+        if !entries.contains_key(prefix) {
+            // Drop expired reports before caching another prefix
+            entries.retain(|_, entry| entry.refreshing || !self.is_stale(entry));
+        }
+
+        let entry = entries.entry(prefix.to_string()).or_default();
+        let stale = self.is_stale(entry);
+        if (stale || force_refresh) && !entry.refreshing {
+            entry.refreshing = true;
+            let reports = self.clone();
+            let prefix = prefix.to_string();
+            tokio::spawn(async move { reports.refresh(prefix).await });
+        }
+
+        UsageSnapshot {
+            report: entry.report.clone(),
+            stale: entry.report.is_some() && stale,
+            refreshing: entry.refreshing,
+            last_error: entry.last_error.clone(),
+        }
+    }
+
+    /// Missing or older than the TTL
+    fn is_stale(&self, entry: &UsageEntry) -> bool {
+        match &entry.report {
+            Some(report) => (Utc::now() - report.as_of)
+                .to_std()
+                .is_ok_and(|age| age >= self.ttl),
+            None => true,
+        }
+    }
+
+    async fn refresh(&self, prefix: String) {
+        let result = walk_usage(self.lister.as_ref(), &prefix).await;
+
+        let mut entries = self.entries.lock();
+        let entry = entries.entry(prefix.clone()).or_default();
+        entry.refreshing = false;
+        match result {
+            Ok(report) => {
+                info!(
+                    prefix = %prefix,
+                    objects = report.objects,
+                    bytes = report.bytes,
+                    duration_ms = report.duration_ms,
+                    "R2 usage report finished"
+                );
+                entry.report = Some(Arc::new(report));
+                entry.last_error = None;
+            }
+            Err(e) => {
+                warn!(prefix = %prefix, error = %e, "R2 usage report failed");
+                entry.last_error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Serves fixed pages, each token naming the page it leads to
+    struct PagedLister {
+        pages: Vec<Vec<(&'static str, u64)>>,
+        /// Tokens passed to each `list_page` call
+        calls: parking_lot::Mutex<Vec<Option<String>>>,
+        walks: AtomicUsize,
+    }
+
+    impl PagedLister {
+        fn new(pages: Vec<Vec<(&'static str, u64)>>) -> Self {
+            Self {
+                pages,
+                calls: parking_lot::Mutex::new(Vec::new()),
+                walks: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ObjectLister for PagedLister {
+        async fn list_page(
+            &self,
+            prefix: &str,
+            token: Option<String>,
+        ) -> Result<ObjectPage, R2Error> {
+            self.calls.lock().push(token.clone());
+            let index = match token {
+                Some(token) => token.parse::<usize>().unwrap(),
+                None => {
+                    self.walks.fetch_add(1, Ordering::SeqCst);
+                    0
+                }
+            };
+            let objects = self.pages[index]
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(key, size)| ListedObject {
+                    key: key.to_string(),
+                    size: *size,
+                })
+                .collect();
+            let next_token = (index + 1 < self.pages.len()).then(|| (index + 1).to_string());
+            Ok(ObjectPage {
+                objects,
+                next_token,
+            })
+        }
+    }
+
+    #[test]
+    fn test_usage_grouped_by_provider_and_asset_type() {
+        let mut report = UsageReport::new("");
+        for (key, size) in [
+            ("printful/products/71/base/front.png", 100),
+            ("printful/products/71/mockups/front_4012.png", 200),
+            ("printful/variants/4012/back.png", 300),
+            ("printful/products/71/thumbnails/thumb_front.png", 10),
+            ("printful/blobs/ab12cd.png", 50),
+            ("gelato/products/tee/printfiles/front.png", 40),
+            ("generated/2026-10-16/1f0c.png", 7),
+            ("generated/cache/9a/b3.png", 8),
+            ("templates/manifest.json", 2),
+        ] {
+            report.record(key, size);
+        }
+
+        assert_eq!(report.objects, 9);
+        assert_eq!(report.bytes, 717);
+
+        let printful = &report.providers["printful"];
+        assert_eq!((printful.objects, printful.bytes), (5, 660));
+        let usage = |objects, bytes| StorageUsage { objects, bytes };
+        assert_eq!(printful.asset_types["base_image"], usage(1, 100));
+        assert_eq!(printful.asset_types["mockup_template"], usage(2, 500));
+        assert_eq!(printful.asset_types["thumbnail"], usage(1, 10));
+        assert_eq!(printful.asset_types["blob"], usage(1, 50));
+
+        assert_eq!(
+            report.providers["gelato"].asset_types["printfile_preview"],
+            usage(1, 40)
+        );
+        let generated = &report.providers["generated"].asset_types;
+        assert_eq!(generated["generated_mockup"], usage(1, 7));
+        assert_eq!(generated["render_cache"], usage(1, 8));
+        assert_eq!(
+            report.providers["templates"].asset_types[UNRECOGNIZED],
+            usage(1, 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_walk_follows_every_page() {
+        let lister = PagedLister::new(vec![
+            vec![
+                ("printful/products/1/base/a.png", 1),
+                ("printful/products/1/base/b.png", 2),
+            ],
+            vec![],
+            vec![("gelato/products/2/base/c.png", 4)],
+        ]);
+
+        let report = walk_usage(&lister, "").await.unwrap();
+        assert_eq!((report.objects, report.bytes), (3, 7));
+        assert_eq!(report.providers["printful"].bytes, 3);
+        assert_eq!(report.providers["gelato"].bytes, 4);
+        // An empty page in the middle doesn't end the walk
+        assert_eq!(
+            *lister.calls.lock(),
+            vec![None, Some("1".to_string()), Some("2".to_string())]
+        );
+
+        let report = walk_usage(&lister, "gelato/").await.unwrap();
+        assert_eq!(report.prefix, "gelato/");
+        assert_eq!((report.objects, report.bytes), (1, 4));
+        assert!(!report.providers.contains_key("printful"));
+    }
+
+    #[tokio::test]
+    async fn test_walk_stops_on_repeated_token() {
+        struct StuckLister;
+
+        #[async_trait]
+        impl ObjectLister for StuckLister {
+            async fn list_page(
+                &self,
+                _prefix: &str,
+                _token: Option<String>,
+            ) -> Result<ObjectPage, R2Error> {
+                Ok(ObjectPage {
+                    objects: vec![],
+                    next_token: Some("same".to_string()),
+                })
+            }
+        }
+
+        assert!(matches!(
+            walk_usage(&StuckLister, "").await,
+            Err(R2Error::ListFailed(_))
+        ));
+    }
+
+    /// Wait for a background walk of `prefix` to finish
+    async fn settled(reports: &Arc<UsageReports>, prefix: &str) -> UsageSnapshot {
+        for _ in 0..100 {
+            let refreshing = reports.entries.lock()[prefix].refreshing;
+            if !refreshing {
+                return reports.get(prefix, false);
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("usage walk of '{}' never finished", prefix);
+    }
+
+    #[tokio::test]
+    async fn test_reports_cached_until_refreshed() {
+        let lister = Arc::new(PagedLister::new(vec![vec![(
+            "printful/products/1/base/a.png",
+            5,
+        )]]));
+        let reports = Arc::new(UsageReports::with_lister(
+            lister.clone(),
+            Duration::from_secs(3600),
+        ));
+
+        // The first request starts a walk and has nothing to show yet
+        let pending = reports.get("", false);
+        assert!(pending.report.is_none() && pending.refreshing);
+
+        let ready = settled(&reports, "").await;
+        let report = ready.report.unwrap();
+        assert_eq!(report.bytes, 5);
+        assert!(!ready.stale && !ready.refreshing);
+
+        // Within the TTL the same report is served without another walk
+        let cached = reports.get("", false);
+        assert_eq!(cached.report.unwrap().as_of, report.as_of);
+        assert_eq!(lister.walks.load(Ordering::SeqCst), 1);
+
+        // Forcing a refresh walks again, still serving the old report meanwhile
+        let forced = reports.get("", true);
+        assert!(forced.refreshing);
+        assert_eq!(forced.report.unwrap().as_of, report.as_of);
+        settled(&reports, "").await;
+        assert_eq!(lister.walks.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_expired_report_served_while_refreshing() {
+        let lister = Arc::new(PagedLister::new(vec![vec![(
+            "printful/products/1/base/a.png",
+            5,
+        )]]));
+        let reports = Arc::new(UsageReports::with_lister(lister.clone(), Duration::ZERO));
+
+        reports.get("printful/", false);
+        settled(&reports, "printful/").await;
+
+        let expired = reports.get("printful/", false);
+        assert!(expired.stale && expired.refreshing);
+        assert_eq!(expired.report.unwrap().prefix, "printful/");
+    }
+}
@@ -0,0 +1,276 @@
+//! Streaming ZIP archives
+//!
+//! Writes stored (uncompressed) ZIP archives one entry at a time, so a
+//! download never holds more than a single file in memory and needs no temp
+//! files. Mockup outputs are already compressed images, so deflating them
+//! again would only cost CPU. Archives are limited to the classic (non-ZIP64)
+//! format: 65535 entries and 4 GiB.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use futures::{Stream, StreamExt};
+use std::io;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIGNATURE: u32 = 0x0605_4b50;
+
+const LOCAL_HEADER_LEN: u64 = 30;
+const CENTRAL_HEADER_LEN: u64 = 46;
+const END_OF_CENTRAL_DIR_LEN: u64 = 22;
+
+/// ZIP 2.0, the minimum for stored entries in folders
+const VERSION: u16 = 20;
+/// General purpose flag bit 11: file names are UTF-8
+const FLAG_UTF8: u16 = 0x0800;
+const METHOD_STORED: u16 = 0;
+
+/// One file to add to an archive
+pub struct ZipEntry {
+    pub name: String,
+    pub data: Bytes,
+}
+
+/// Builds archive headers while entries are streamed, keeping only the
+/// central directory in memory
+pub struct ZipWriter {
+    central_directory: BytesMut,
+    offset: u64,
+    entries: u16,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl ZipWriter {
+    /// Start an archive whose entries are all stamped with `modified`
+    pub fn new(modified: DateTime<Utc>) -> Self {
+        let (dos_time, dos_date) = dos_datetime(modified);
+        Self {
+            central_directory: BytesMut::new(),
+            offset: 0,
+            entries: 0,
+            dos_time,
+            dos_date,
+        }
+    }
+
+    /// Local file header for an entry; the entry's data must be written next
+    pub fn add_entry(&mut self, name: &str, data: &[u8]) -> io::Result<Bytes> {
+        if self.entries == u16::MAX {
+            return Err(too_large("more than 65535 entries"));
+        }
+        let name_len =
+            u16::try_from(name.len()).map_err(|_| too_large("entry name over 65535 bytes"))?;
+        let size = u32::try_from(data.len()).map_err(|_| too_large("entry over 4 GiB"))?;
+        let offset = u32::try_from(self.offset).map_err(|_| too_large("archive over 4 GiB"))?;
+        let crc = crc32fast::hash(data);
+
+        let mut header = BytesMut::with_capacity(LOCAL_HEADER_LEN as usize + name.len());
+        header.put_u32_le(LOCAL_HEADER_SIGNATURE);
+        header.put_u16_le(VERSION);
+        header.put_u16_le(FLAG_UTF8);
+        header.put_u16_le(METHOD_STORED);
+        header.put_u16_le(self.dos_time);
+        header.put_u16_le(self.dos_date);
+        header.put_u32_le(crc);
+        header.put_u32_le(size); // compressed size
+        header.put_u32_le(size); // uncompressed size
+        header.put_u16_le(name_len);
+        header.put_u16_le(0); // extra field length
+        header.put_slice(name.as_bytes());
+
+        let central = &mut self.central_directory;
+        central.put_u32_le(CENTRAL_HEADER_SIGNATURE);
+        central.put_u16_le(VERSION); // version made by
+        central.put_u16_le(VERSION); // version needed
+        central.put_u16_le(FLAG_UTF8);
+        central.put_u16_le(METHOD_STORED);
+        central.put_u16_le(self.dos_time);
+        central.put_u16_le(self.dos_date);
+        central.put_u32_le(crc);
+        central.put_u32_le(size);
+        central.put_u32_le(size);
+        central.put_u16_le(name_len);
+        central.put_u16_le(0); // extra field length
+        central.put_u16_le(0); // comment length
+        central.put_u16_le(0); // disk number start
+        central.put_u16_le(0); // internal attributes
+        central.put_u32_le(0); // external attributes
+        central.put_u32_le(offset);
+        central.put_slice(name.as_bytes());
+
+        self.offset += header.len() as u64 + data.len() as u64;
+        self.entries += 1;
+        Ok(header.freeze())
+    }
+
+    /// Central directory and end record closing the archive
+    pub fn finish(self) -> io::Result<Bytes> {
+        let cd_offset = u32::try_from(self.offset).map_err(|_| too_large("archive over 4 GiB"))?;
+        let cd_size = self.central_directory.len() as u32;
+
+        let mut tail = self.central_directory;
+        tail.put_u32_le(END_OF_CENTRAL_DIR_SIGNATURE);
+        tail.put_u16_le(0); // this disk
+        tail.put_u16_le(0); // disk with central directory
+        tail.put_u16_le(self.entries);
+        tail.put_u16_le(self.entries);
+        tail.put_u32_le(cd_size);
+        tail.put_u32_le(cd_offset);
+        tail.put_u16_le(0); // comment length
+        Ok(tail.freeze())
+    }
+}
+
+/// Exact archive size for entries of the given name lengths and sizes
+pub fn zip_content_length(entries: impl IntoIterator<Item = (usize, u64)>) -> u64 {
+    entries
+        .into_iter()
+        .map(|(name_len, size)| LOCAL_HEADER_LEN + CENTRAL_HEADER_LEN + 2 * name_len as u64 + size)
+        .sum::<u64>()
+        + END_OF_CENTRAL_DIR_LEN
+}
+
+/// Stream an archive as entries arrive, yielding headers and data as separate chunks
+pub fn zip_stream<S>(entries: S, modified: DateTime<Utc>) -> impl Stream<Item = io::Result<Bytes>>
+where
+    S: Stream<Item = io::Result<ZipEntry>> + Unpin,
+{
+    struct State<S> {
+        entries: S,
+        writer: Option<ZipWriter>,
+        pending: Option<Bytes>,
+    }
+
+    let state = State {
+        entries,
+        writer: Some(ZipWriter::new(modified)),
+        pending: None,
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        if let Some(data) = state.pending.take() {
+            return Some((Ok(data), state));
+        }
+        let writer = state.writer.as_mut()?;
+
+        match state.entries.next().await {
+            Some(Ok(entry)) => match writer.add_entry(&entry.name, &entry.data) {
+                Ok(header) => {
+                    state.pending = Some(entry.data);
+                    Some((Ok(header), state))
+                }
+                Err(e) => {
+                    state.writer = None;
+                    Some((Err(e), state))
+                }
+            },
+            Some(Err(e)) => {
+                state.writer = None;
+                Some((Err(e), state))
+            }
+            None => {
+                let writer = state.writer.take()?;
+                Some((writer.finish(), state))
+            }
+        }
+    })
+}
+
+fn too_large(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("ZIP archive too large: {}", reason),
+    )
+}
+
+/// MS-DOS time and date fields; the format cannot represent years before 1980
+fn dos_datetime(at: DateTime<Utc>) -> (u16, u16) {
+    if at.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = (at.hour() << 11) | (at.minute() << 5) | (at.second() / 2);
+    let date = (((at.year() - 1980) as u32) << 9) | (at.month() << 5) | at.day();
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn read_u16(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([data[at], data[at + 1]])
+    }
+
+    fn read_u32(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    #[test]
+    fn test_zip_stream_layout() {
+        let entries = vec![
+            ZipEntry {
+                name: "a.txt".to_string(),
+                data: Bytes::from_static(b"123456789"),
+            },
+            ZipEntry {
+                name: "mockups/b.png".to_string(),
+                data: Bytes::from_static(b"png"),
+            },
+        ];
+        let expected_len =
+            zip_content_length(entries.iter().map(|e| (e.name.len(), e.data.len() as u64)));
+        let modified = Utc.with_ymd_and_hms(2026, 10, 16, 12, 30, 10).unwrap();
+
+        let chunks: Vec<io::Result<Bytes>> = futures::executor::block_on(
+            zip_stream(futures::stream::iter(entries.into_iter().map(Ok)), modified).collect(),
+        );
+        let archive: Vec<u8> = chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.unwrap().to_vec())
+            .collect();
+
+        assert_eq!(archive.len() as u64, expected_len);
+        assert_eq!(read_u32(&archive, 0), LOCAL_HEADER_SIGNATURE);
+        assert_eq!(read_u32(&archive, 14), 0xCBF4_3926); // CRC-32 check value
+        assert_eq!(&archive[30..35], b"a.txt");
+        assert_eq!(&archive[35..44], b"123456789");
+
+        let eocd = archive.len() - END_OF_CENTRAL_DIR_LEN as usize;
+        assert_eq!(read_u32(&archive, eocd), END_OF_CENTRAL_DIR_SIGNATURE);
+        assert_eq!(read_u16(&archive, eocd + 10), 2);
+        let cd_offset = read_u32(&archive, eocd + 16) as usize;
+        assert_eq!(read_u32(&archive, cd_offset), CENTRAL_HEADER_SIGNATURE);
+
+        // Second entry's local header offset, as recorded in the central directory
+        let second_cd = cd_offset + CENTRAL_HEADER_LEN as usize + "a.txt".len();
+        let second_local = read_u32(&archive, second_cd + 42) as usize;
+        assert_eq!(second_local, 44);
+        assert_eq!(read_u32(&archive, second_local), LOCAL_HEADER_SIGNATURE);
+    }
+
+    #[test]
+    fn test_zip_stream_stops_on_entry_error() {
+        let entries = futures::stream::iter(vec![
+            Ok(ZipEntry {
+                name: "a.txt".to_string(),
+                data: Bytes::from_static(b"a"),
+            }),
+            Err(io::Error::new(io::ErrorKind::NotFound, "missing")),
+        ]);
+
+        let chunks: Vec<io::Result<Bytes>> =
+            futures::executor::block_on(zip_stream(entries, Utc::now()).collect());
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[2].is_err());
+    }
+
+    #[test]
+    fn test_dos_datetime() {
+        let at = Utc.with_ymd_and_hms(2026, 10, 16, 12, 30, 10).unwrap();
+        let (time, date) = dos_datetime(at);
+        assert_eq!(time, (12 << 11) | (30 << 5) | 5);
+        assert_eq!(date, (46 << 9) | (10 << 5) | 16);
+    }
+}
@@ -0,0 +1,78 @@
+//! Orphaned asset cleanup
+//!
+//! Provider assets are mirrored to storage under `{provider}/`, each with a
+//! row in `pod_mockup_assets`. Objects no asset row points at any more are
+//! orphans, left behind when assets move. Assets of withdrawn products keep
+//! their objects, since the product may come back. A cleanup lists the provider's folder a page at a time,
+//! looks each page's keys up in one batch, and deletes the orphans or, in a
+//! dry run, only counts them.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::asset_sync::{AssetStatusStore, AssetSyncError};
+use super::on_demand::ON_DEMAND_FILE_PREFIX;
+use crate::storage::{ListedObject, GENERATED_PREFIX, TEMPLATE_BACKUP_PREFIX};
+
+/// Top-level folders of the bucket that hold no provider assets
+const PROTECTED_PREFIXES: [&str; 2] = [GENERATED_PREFIX, TEMPLATE_BACKUP_PREFIX];
+
+/// What an asset cleanup found and removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CleanupSummary {
+    /// Orphans were only counted, not deleted
+    pub dry_run: bool,
+    /// Objects listed under the provider's folder
+    pub scanned: u64,
+    /// Objects no asset row is stored under
+    pub orphaned: u64,
+    pub deleted: u64,
+    /// Size of the deleted objects; in a dry run, of the orphans found
+    pub bytes_reclaimed: u64,
+}
+
+/// Folder holding a provider's assets, `None` for codes that name no such folder
+pub fn provider_prefix(provider_code: &str) -> Option<String> {
+    let valid = !provider_code.is_empty()
+        && !provider_code.contains('/')
+        && !PROTECTED_PREFIXES.contains(&provider_code);
+    valid.then(|| format!("{}/", provider_code))
+}
+
+/// Objects of one listed page that no stored asset is mirrored under
+///
+/// Templates cached on demand sit next to synced assets without a row of
+/// their own, so they are never orphans.
+pub async fn find_orphans(
+    store: &dyn AssetStatusStore,
+    objects: Vec<ListedObject>,
+) -> Result<Vec<ListedObject>, AssetSyncError> {
+    let candidates: Vec<ListedObject> = objects
+        .into_iter()
+        .filter(|object| {
+            let file_name = object.key.rsplit('/').next().unwrap_or(&object.key);
+            !file_name.starts_with(ON_DEMAND_FILE_PREFIX)
+        })
+        .collect();
+    let keys: Vec<String> = candidates.iter().map(|object| object.key.clone()).collect();
+    let referenced = store.referenced_keys(&keys).await?;
+
+    Ok(candidates
+        .into_iter()
+        .filter(|object| !referenced.contains(&object.key))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_prefix_skips_protected_folders() {
+        assert_eq!(provider_prefix("printful").as_deref(), Some("printful/"));
+        assert_eq!(provider_prefix("generated"), None);
+        assert_eq!(provider_prefix("templates"), None);
+        assert_eq!(provider_prefix("printful/../generated"), None);
+        assert_eq!(provider_prefix(""), None);
+    }
+}
@@ -1,21 +1,39 @@
 //! Asset synchronization service
 //!
-//! Downloads mockup assets from POD providers and uploads them to R2 storage.
-//! Tracks download status in the database.
+//! Downloads mockup assets from POD providers and uploads them to the
+//! storage backend: R2, S3, or a local directory.
+//! Failed assets are retried with backoff, and each asset's progress is
+//! reported to an `AssetStatusStore`, which tracks it in the database.
+//! With deduplication on, assets are stored once per distinct content under
+//! `{provider}/blobs/{sha256}.{ext}`, and identical assets share that key.
+//! With thumbnails on, large images also get a WebP thumbnail under the
+//! product's `thumbnails/` folder.
 
-use std::sync::Arc;
+use async_trait::async_trait;
+use image::codecs::webp::{WebPEncoder, WebPQuality};
+use image::imageops::FilterType;
+use image::ColorType;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::Semaphore;
 use tracing::{debug, error, info, instrument, warn};
 use uuid::Uuid;
 
 use crate::domain::catalog::{AssetType, MockupAsset, PrintPlacement};
-use crate::storage::{AssetPath, R2Client, R2Error, UploadResult};
+use crate::metrics::Metrics;
+use crate::net::{UrlGuard, UrlGuardError, UrlPolicy};
+use crate::storage::{
+    download_resumable, AssetPath, DownloadError, Downloaded, R2Error, RetryPolicy, StorageBackend,
+    UploadResult,
+};
 
 /// Errors that can occur during asset synchronization
 #[derive(Error, Debug)]
 pub enum AssetSyncError {
-    #[error("R2 storage error: {0}")]
+    #[error("Storage error: {0}")]
     StorageError(#[from] R2Error),
 
     #[error("HTTP error: {0}")]
@@ -40,6 +58,27 @@ impl From<reqwest::Error> for AssetSyncError {
     }
 }
 
+impl From<UrlGuardError> for AssetSyncError {
+    fn from(err: UrlGuardError) -> Self {
+        match err {
+            e @ UrlGuardError::Resolve { .. } => AssetSyncError::HttpError(e.to_string()),
+            other => AssetSyncError::InvalidUrl(other.to_string()),
+        }
+    }
+}
+
+impl From<DownloadError> for AssetSyncError {
+    fn from(err: DownloadError) -> Self {
+        match err {
+            DownloadError::NotFound(url) => AssetSyncError::NotFound(url),
+            DownloadError::RateLimited { retry_after_secs } => {
+                AssetSyncError::RateLimited { retry_after_secs }
+            }
+            other => AssetSyncError::HttpError(other.to_string()),
+        }
+    }
+}
+
 /// Result of syncing a single asset
 #[derive(Debug, Clone)]
 pub struct AssetSyncResult {
@@ -55,6 +94,16 @@ pub struct AssetSyncResult {
     pub public_url: Option<String>,
     /// Time taken to sync in milliseconds
     pub sync_time_ms: u64,
+    /// Bucket holding `r2_key`
+    pub bucket: String,
+    /// Hex SHA-256 of the downloaded bytes; `None` when the asset was skipped
+    pub checksum: Option<String>,
+    /// Download attempts that failed before this one succeeded
+    pub retry_count: u32,
+    /// Identical content was already in R2, so `r2_key` points at that blob
+    pub deduplicated: bool,
+    /// R2 key of a thumbnail made from this download
+    pub thumbnail_r2_key: Option<String>,
 }
 
 /// Batch sync result
@@ -66,39 +115,309 @@ pub struct BatchSyncResult {
     pub failed_count: usize,
     /// Number of assets skipped (already exist)
     pub skipped_count: usize,
+    /// Number of assets whose content was already stored under another URL
+    pub deduplicated_count: usize,
     /// Individual results
     pub results: Vec<Result<AssetSyncResult, AssetSyncError>>,
     /// Total time in milliseconds
     pub total_time_ms: u64,
 }
 
+impl BatchSyncResult {
+    /// Add the results of another batch
+    fn merge(&mut self, other: BatchSyncResult) {
+        self.success_count += other.success_count;
+        self.failed_count += other.failed_count;
+        self.skipped_count += other.skipped_count;
+        self.deduplicated_count += other.deduplicated_count;
+        self.results.extend(other.results);
+        self.total_time_ms += other.total_time_ms;
+    }
+}
+
+/// Failed assets retried fewer times than this are picked up by `retry_failed`
+pub const DEFAULT_RETRY_CAP: u32 = 10;
+
+/// Longest side of a thumbnail unless configured otherwise
+pub const DEFAULT_THUMBNAIL_MAX_DIMENSION: u32 = 400;
+
+/// WebP quality of thumbnails
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// Where an asset is in mirroring
+#[derive(Debug)]
+pub enum AssetStatus<'a> {
+    /// Queued for download
+    Pending,
+    /// Being downloaded and uploaded
+    Downloading,
+    /// In R2, after `result.retry_count` failed attempts
+    Downloaded(&'a AssetSyncResult),
+    /// Given up on after `attempts` failed attempts
+    Failed {
+        error: &'a AssetSyncError,
+        attempts: u32,
+    },
+}
+
+/// Records asset mirroring progress and finds failed assets to retry
+///
+/// Assets are identified by provider code, the provider's product ID, and
+/// their source URL.
+#[async_trait]
+pub trait AssetStatusStore: Send + Sync {
+    /// Record an asset's new status
+    async fn record_status(
+        &self,
+        provider_code: &str,
+        product_id: &str,
+        asset: &MockupAsset,
+        status: &AssetStatus<'_>,
+    ) -> Result<(), AssetSyncError>;
+
+    /// Failed assets of a provider with fewer than `retry_cap` failed attempts
+    ///
+    /// Each asset comes with the provider's ID for its product.
+    async fn failed_assets(
+        &self,
+        provider_code: &str,
+        retry_cap: u32,
+    ) -> Result<Vec<(String, MockupAsset)>, AssetSyncError>;
+
+    /// Which of `keys` a stored asset, or its thumbnail, is mirrored under
+    ///
+    /// Assets of products the provider no longer offers count too.
+    async fn referenced_keys(&self, keys: &[String]) -> Result<HashSet<String>, AssetSyncError>;
+}
+
+/// Delay before retrying an asset after its `failures`th failed attempt
+///
+/// `None` once attempts run out, or for errors a retry can't fix. Of R2's
+/// errors only transient failures and corrupted uploads are retried. A rate
+/// limit's `retry_after` is the least we wait.
+fn retry_delay(policy: &RetryPolicy, error: &AssetSyncError, failures: u32) -> Option<Duration> {
+    if failures >= policy.max_attempts.max(1) {
+        return None;
+    }
+    match error {
+        AssetSyncError::NotFound(_) | AssetSyncError::InvalidUrl(_) => None,
+        AssetSyncError::StorageError(e)
+            if !e.is_transient() && !matches!(e, R2Error::ChecksumMismatch(_)) =>
+        {
+            None
+        }
+        AssetSyncError::RateLimited { retry_after_secs } => Some(
+            policy
+                .backoff(failures)
+                .max(Duration::from_secs(*retry_after_secs)),
+        ),
+        _ => Some(policy.backoff(failures)),
+    }
+}
+
+/// Run `attempt` until it succeeds or `retry_delay` gives up
+///
+/// Returns the outcome and the number of attempts that failed.
+async fn with_retries<T, F, Fut>(
+    policy: &RetryPolicy,
+    mut attempt: F,
+) -> (Result<T, AssetSyncError>, u32)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AssetSyncError>>,
+{
+    let mut failures = 0;
+    loop {
+        match attempt().await {
+            Ok(value) => return (Ok(value), failures),
+            Err(e) => {
+                failures += 1;
+                let Some(delay) = retry_delay(policy, &e, failures) else {
+                    return (Err(e), failures);
+                };
+                warn!(
+                    error = %e,
+                    failures,
+                    delay_ms = delay.as_millis() as u64,
+                    "Asset sync failed; retrying"
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Content-addressed key of a blob: `{provider}/blobs/{sha256}.{ext}`
+fn blob_key(provider_code: &str, checksum: &str, content_type: &str) -> String {
+    let ext = match content_type {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        _ => "png",
+    };
+    format!(
+        "{}/blobs/{}.{}",
+        provider_code.to_lowercase(),
+        checksum,
+        ext
+    )
+}
+
+/// A downscaled, WebP-encoded copy of an image
+#[derive(Debug)]
+struct Thumbnail {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// Fit an image within `max_dimension` on both sides, keeping its aspect ratio
+///
+/// Returns `None` for images already that small.
+fn make_thumbnail(data: &[u8], max_dimension: u32) -> Result<Option<Thumbnail>, image::ImageError> {
+    let image = image::load_from_memory(data)?;
+    if image.width() <= max_dimension && image.height() <= max_dimension {
+        return Ok(None);
+    }
+
+    let rgba = image
+        .resize(max_dimension, max_dimension, FilterType::Lanczos3)
+        .to_rgba8();
+    let mut data = Vec::new();
+    WebPEncoder::new_with_quality(&mut data, WebPQuality::lossy(THUMBNAIL_QUALITY)).encode(
+        rgba.as_raw(),
+        rgba.width(),
+        rgba.height(),
+        ColorType::Rgba8,
+    )?;
+    Ok(Some(Thumbnail {
+        data,
+        width: rgba.width(),
+        height: rgba.height(),
+    }))
+}
+
+/// Where an asset's thumbnail goes: `thumb_{type}[_{variant}]_{stem}.webp`
+fn thumbnail_path(path: &AssetPath) -> AssetPath {
+    let stem = path
+        .filename
+        .rsplit_once('.')
+        .map_or(path.filename.as_str(), |(stem, _)| stem);
+    let filename = match path.variant_id {
+        Some(ref variant_id) => format!("{}_{}_{}.webp", path.asset_type, variant_id, stem),
+        None => format!("{}_{}.webp", path.asset_type, stem),
+    };
+    AssetPath::thumbnail(&path.provider, &path.product_id, &filename)
+}
+
+/// Blobs a syncer has stored or found in R2, by checksum
+///
+/// Each checksum has its own lock, so identical assets mirrored at the same
+/// time wait for the first upload rather than racing it.
+#[derive(Default)]
+struct BlobIndex {
+    blobs: Mutex<HashMap<String, Arc<tokio::sync::Mutex<bool>>>>,
+}
+
+impl BlobIndex {
+    /// Store the blob with `checksum` once, returning whether it was already stored
+    ///
+    /// `store` runs unless an earlier asset stored the blob, and reports
+    /// whether it found the blob already in R2.
+    async fn store_once<F, Fut>(&self, checksum: &str, store: F) -> Result<bool, AssetSyncError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<bool, AssetSyncError>>,
+    {
+        let blob = self
+            .blobs
+            .lock()
+            .unwrap()
+            .entry(checksum.to_string())
+            .or_default()
+            .clone();
+        let mut stored = blob.lock().await;
+        if *stored {
+            return Ok(true);
+        }
+        let existed = store().await?;
+        *stored = true;
+        Ok(existed)
+    }
+}
+
+/// Client for source URLs, refusing internal addresses and hosts `policy` doesn't allow
+fn source_guard(policy: UrlPolicy) -> UrlGuard {
+    let builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .user_agent("r-image-magic/1.0 POD-Asset-Syncer");
+    UrlGuard::new(policy, builder).expect("Failed to create HTTP client")
+}
+
 /// Asset synchronization service
 pub struct AssetSyncer {
-    r2_client: R2Client,
-    http_client: reqwest::Client,
+    storage: Arc<dyn StorageBackend>,
+    /// Source URLs come from provider APIs, so they are fetched like design URLs
+    urls: Arc<UrlGuard>,
+    /// Client used without the guard, for tests against a local server
+    #[cfg(test)]
+    unguarded: Option<reqwest::Client>,
     /// Maximum concurrent downloads
     concurrency: usize,
     /// Whether to skip existing assets
     skip_existing: bool,
+    /// Download permits shared with other syncers, replacing the per-batch limit
+    shared_limiter: Option<Arc<Semaphore>>,
+    /// Retries for interrupted downloads
+    retry_policy: RetryPolicy,
+    /// Retries of whole assets, after a download gives up or an upload fails
+    asset_retry_policy: RetryPolicy,
+    /// Where asset progress is recorded
+    status_store: Option<Arc<dyn AssetStatusStore>>,
+    /// Failed attempts after which `retry_failed` leaves an asset alone
+    retry_cap: u32,
+    /// Stored blobs, when identical assets share one R2 object
+    blobs: Option<Arc<BlobIndex>>,
+    /// Whether to store a thumbnail of each large image
+    thumbnails: bool,
+    /// Longest side of a thumbnail
+    thumbnail_max_dimension: u32,
+    /// Where download, upload, and thumbnail timings are recorded
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl AssetSyncer {
     /// Create a new asset syncer
-    pub fn new(r2_client: R2Client) -> Self {
-        let http_client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(60))
-            .user_agent("r-image-magic/1.0 POD-Asset-Syncer")
-            .build()
-            .expect("Failed to create HTTP client");
-
+    pub fn new(storage: Arc<dyn StorageBackend>) -> Self {
         Self {
-            r2_client,
-            http_client,
+            storage,
+            urls: Arc::new(source_guard(UrlPolicy::default())),
+            #[cfg(test)]
+            unguarded: None,
             concurrency: 10,
             skip_existing: true,
+            shared_limiter: None,
+            retry_policy: RetryPolicy::default(),
+            asset_retry_policy: RetryPolicy {
+                max_attempts: 3,
+                initial_backoff: Duration::from_secs(1),
+                max_backoff: Duration::from_secs(30),
+            },
+            status_store: None,
+            retry_cap: DEFAULT_RETRY_CAP,
+            blobs: None,
+            thumbnails: false,
+            thumbnail_max_dimension: DEFAULT_THUMBNAIL_MAX_DIMENSION,
+            metrics: None,
         }
     }
 
+    /// Limit where source URLs may be fetched from
+    pub fn with_url_policy(mut self, policy: UrlPolicy) -> Self {
+        self.urls = Arc::new(source_guard(policy));
+        self
+    }
+
     /// Set the concurrency level
     pub fn with_concurrency(mut self, concurrency: usize) -> Self {
         self.concurrency = concurrency.max(1).min(50);
@@ -111,13 +430,127 @@ impl AssetSyncer {
         self
     }
 
+    /// Draw download permits from a semaphore shared with other syncers.
+    ///
+    /// Tokio semaphores hand out permits in FIFO order, so batches from
+    /// different providers interleave instead of one provider draining the pool.
+    pub fn with_shared_limiter(mut self, limiter: Arc<Semaphore>) -> Self {
+        self.shared_limiter = Some(limiter);
+        self
+    }
+
+    /// Set how many times each asset is attempted before it is marked failed
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.asset_retry_policy.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Record asset progress in `store`
+    pub fn with_status_store(mut self, store: Arc<dyn AssetStatusStore>) -> Self {
+        self.status_store = Some(store);
+        self
+    }
+
+    /// Store each distinct content once under a key derived from its SHA-256
+    ///
+    /// Assets whose bytes are already in R2 skip the upload and point at the
+    /// existing blob.
+    pub fn with_dedup(mut self, enabled: bool) -> Self {
+        self.blobs = enabled.then(|| Arc::new(BlobIndex::default()));
+        self
+    }
+
+    /// Set whether to store a WebP thumbnail of each image larger than the thumbnail size
+    pub fn with_thumbnails(mut self, enabled: bool) -> Self {
+        self.thumbnails = enabled;
+        self
+    }
+
+    /// Set the longest side of thumbnails
+    pub fn with_thumbnail_max_dimension(mut self, max_dimension: u32) -> Self {
+        self.thumbnail_max_dimension = max_dimension.max(1);
+        self
+    }
+
+    /// Record how long each mirroring stage takes in `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Record a stage that started at `started`
+    fn observe_stage(&self, provider_code: &str, stage: &str, started: std::time::Instant) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.observe_asset_stage(provider_code, stage, started.elapsed());
+        }
+    }
+
+    /// Record an asset's status, logging rather than failing the sync on errors
+    async fn report(
+        &self,
+        provider_code: &str,
+        product_id: &str,
+        asset: &MockupAsset,
+        status: AssetStatus<'_>,
+    ) {
+        if let Some(ref store) = self.status_store {
+            if let Err(e) = store
+                .record_status(provider_code, product_id, asset, &status)
+                .await
+            {
+                warn!(source_url = %asset.source_url, error = %e, "Failed to record asset status");
+            }
+        }
+    }
+
     /// Sync a single mockup asset from a provider
+    ///
+    /// Failed attempts are retried with exponential backoff, except for assets
+    /// that don't exist or have an invalid URL.
     #[instrument(skip(self), fields(source_url = %asset.source_url))]
     pub async fn sync_asset(
         &self,
         provider_code: &str,
         product_id: &str,
         asset: &MockupAsset,
+    ) -> Result<AssetSyncResult, AssetSyncError> {
+        self.report(provider_code, product_id, asset, AssetStatus::Downloading)
+            .await;
+
+        let (outcome, failures) = with_retries(&self.asset_retry_policy, move || {
+            self.mirror_asset(provider_code, product_id, asset)
+        })
+        .await;
+
+        match outcome {
+            Ok(mut result) => {
+                result.retry_count += failures;
+                self.report(
+                    provider_code,
+                    product_id,
+                    asset,
+                    AssetStatus::Downloaded(&result),
+                )
+                .await;
+                Ok(result)
+            }
+            Err(error) => {
+                let status = AssetStatus::Failed {
+                    error: &error,
+                    attempts: failures,
+                };
+                self.report(provider_code, product_id, asset, status).await;
+                Err(error)
+            }
+        }
+    }
+
+    /// Download an asset and upload it to R2 once
+    async fn mirror_asset(
+        &self,
+        provider_code: &str,
+        product_id: &str,
+        asset: &MockupAsset,
     ) -> Result<AssetSyncResult, AssetSyncError> {
         let start = std::time::Instant::now();
 
@@ -127,7 +560,7 @@ impl AssetSyncer {
 
         // Check if asset already exists
         if self.skip_existing {
-            match self.r2_client.exists(&r2_key).await {
+            match self.storage.exists(&r2_key).await {
                 Ok(true) => {
                     debug!("Asset already exists, skipping: {}", r2_key);
                     return Ok(AssetSyncResult {
@@ -135,8 +568,13 @@ impl AssetSyncer {
                         r2_key,
                         size_bytes: 0,
                         content_type: "skipped".to_string(),
-                        public_url: self.r2_client.public_url(&path.to_key()),
+                        public_url: self.storage.public_url(&path.to_key()),
                         sync_time_ms: start.elapsed().as_millis() as u64,
+                        bucket: self.storage.bucket().to_string(),
+                        checksum: None,
+                        retry_count: 0,
+                        deduplicated: false,
+                        thumbnail_r2_key: None,
                     });
                 }
                 Ok(false) => {}
@@ -146,64 +584,167 @@ impl AssetSyncer {
             }
         }
 
-        // Download from source
+        // Download from source, resuming if the transfer is interrupted
         debug!("Downloading asset from: {}", asset.source_url);
-        let response = self.http_client.get(&asset.source_url).send().await?;
-
-        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-            let retry_after = response
-                .headers()
-                .get("retry-after")
-                .and_then(|v| v.to_str().ok())
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(60);
-            return Err(AssetSyncError::RateLimited {
-                retry_after_secs: retry_after,
-            });
-        }
-
-        if response.status() == reqwest::StatusCode::NOT_FOUND {
-            return Err(AssetSyncError::NotFound(asset.source_url.clone()));
-        }
-
-        if !response.status().is_success() {
-            return Err(AssetSyncError::HttpError(format!(
-                "HTTP {} from {}",
-                response.status(),
-                asset.source_url
-            )));
+        let stage = std::time::Instant::now();
+        let downloaded = self.download(&asset.source_url).await?;
+        self.observe_stage(provider_code, "download", stage);
+        let content_type = downloaded.content_type;
+        let size_bytes = downloaded.data.len() as u64;
+        let checksum = hex::encode(downloaded.sha256);
+        if downloaded.attempts > 1 {
+            info!(
+                source_url = %asset.source_url,
+                attempts = downloaded.attempts,
+                "Asset downloaded after retries"
+            );
         }
 
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("image/png")
-            .to_string();
+        // Kept for the thumbnail, which is made once the full asset is stored
+        let thumbnail_source = self.wants_thumbnail(asset).then(|| downloaded.data.clone());
 
-        let data = response.bytes().await?.to_vec();
-        let size_bytes = data.len() as u64;
+        let stage = std::time::Instant::now();
+        let (r2_key, public_url, deduplicated) = if let Some(ref blobs) = self.blobs {
+            let key = blob_key(provider_code, &checksum, &content_type);
+            let store = self.store_blob(&key, downloaded.data, &content_type, &downloaded.sha256);
+            let deduplicated = blobs.store_once(&checksum, move || store).await?;
+            let public_url = self.storage.public_url(&key);
+            (key, public_url, deduplicated)
+        } else {
+            // Upload to R2; the checksum lets R2 reject a body corrupted on the way
+            debug!("Uploading {} bytes to R2: {}", size_bytes, r2_key);
+            let upload_result = self
+                .storage
+                .upload_verified(&path, downloaded.data, &content_type, &downloaded.sha256)
+                .await?;
+            (upload_result.key, upload_result.public_url, false)
+        };
+        self.observe_stage(provider_code, "upload", stage);
 
-        // Upload to R2
-        debug!("Uploading {} bytes to R2: {}", size_bytes, r2_key);
-        let upload_result = self.r2_client.upload(&path, data, &content_type).await?;
+        let thumbnail_r2_key = match thumbnail_source {
+            Some(data) => {
+                let stage = std::time::Instant::now();
+                let key = self.store_thumbnail(&path, data).await;
+                self.observe_stage(provider_code, "thumbnail", stage);
+                key
+            }
+            None => None,
+        };
 
         let sync_time_ms = start.elapsed().as_millis() as u64;
-        info!(
-            "Synced asset: {} -> {} ({} bytes in {}ms)",
-            asset.source_url, r2_key, size_bytes, sync_time_ms
-        );
+        if deduplicated {
+            info!(
+                "Deduplicated asset: {} -> {} ({} bytes in {}ms)",
+                asset.source_url, r2_key, size_bytes, sync_time_ms
+            );
+        } else {
+            info!(
+                "Synced asset: {} -> {} ({} bytes in {}ms)",
+                asset.source_url, r2_key, size_bytes, sync_time_ms
+            );
+        }
 
         Ok(AssetSyncResult {
             source_url: asset.source_url.clone(),
-            r2_key: upload_result.key,
+            r2_key,
             size_bytes,
             content_type,
-            public_url: upload_result.public_url,
+            public_url,
             sync_time_ms,
+            bucket: self.storage.bucket().to_string(),
+            checksum: Some(checksum),
+            // Interrupted transfers resumed within this attempt
+            retry_count: downloaded.attempts.saturating_sub(1),
+            deduplicated,
+            thumbnail_r2_key,
         })
     }
 
+    /// Check `url` against the fetch policy and download it with the guarded client
+    async fn download(&self, url: &str) -> Result<Downloaded, AssetSyncError> {
+        #[cfg(test)]
+        if let Some(ref client) = self.unguarded {
+            return Ok(download_resumable(client, url, &self.retry_policy).await?);
+        }
+        self.urls.check(url).await?;
+        Ok(download_resumable(self.urls.client(), url, &self.retry_policy).await?)
+    }
+
+    /// Whether an asset of this type gets a thumbnail
+    fn wants_thumbnail(&self, asset: &MockupAsset) -> bool {
+        self.thumbnails
+            && !matches!(
+                asset.asset_type,
+                AssetType::Thumbnail | AssetType::GeneratedMockup
+            )
+    }
+
+    /// Downscale an asset and upload the thumbnail, returning its key
+    ///
+    /// Images already within the thumbnail size get none. Failures are logged
+    /// rather than failing the asset.
+    async fn store_thumbnail(&self, path: &AssetPath, data: Vec<u8>) -> Option<String> {
+        let max_dimension = self.thumbnail_max_dimension;
+        let thumbnail =
+            match tokio::task::spawn_blocking(move || make_thumbnail(&data, max_dimension)).await {
+                Ok(Ok(Some(thumbnail))) => thumbnail,
+                Ok(Ok(None)) => return None,
+                Ok(Err(e)) => {
+                    warn!(key = %path.to_key(), error = %e, "Failed to make thumbnail");
+                    return None;
+                }
+                Err(e) => {
+                    warn!(key = %path.to_key(), error = %e, "Thumbnail task failed");
+                    return None;
+                }
+            };
+
+        let thumbnail_path = thumbnail_path(path);
+        debug!(
+            "Uploading {}x{} thumbnail to R2: {}",
+            thumbnail.width,
+            thumbnail.height,
+            thumbnail_path.to_key()
+        );
+        match self
+            .storage
+            .upload(&thumbnail_path, thumbnail.data, "image/webp")
+            .await
+        {
+            Ok(result) => Some(result.key),
+            Err(e) => {
+                warn!(key = %thumbnail_path.to_key(), error = %e, "Failed to upload thumbnail");
+                None
+            }
+        }
+    }
+
+    /// Upload a blob unless R2 already has it, returning whether it did
+    async fn store_blob(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        sha256: &[u8; 32],
+    ) -> Result<bool, AssetSyncError> {
+        match self.storage.exists(key).await {
+            Ok(true) => {
+                debug!("Blob already in R2: {}", key);
+                return Ok(true);
+            }
+            Ok(false) => {}
+            Err(e) => {
+                warn!("Failed to check if blob exists: {}", e);
+            }
+        }
+
+        debug!("Uploading {} byte blob to R2: {}", data.len(), key);
+        self.storage
+            .upload_key_verified(key, data, content_type, sha256)
+            .await?;
+        Ok(false)
+    }
+
     /// Sync multiple assets concurrently
     #[instrument(skip(self, assets), fields(asset_count = assets.len()))]
     pub async fn sync_batch(
@@ -213,7 +754,10 @@ impl AssetSyncer {
         assets: &[MockupAsset],
     ) -> BatchSyncResult {
         let start = std::time::Instant::now();
-        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        let semaphore = self
+            .shared_limiter
+            .clone()
+            .unwrap_or_else(|| Arc::new(Semaphore::new(self.concurrency)));
 
         let mut handles = Vec::with_capacity(assets.len());
 
@@ -223,12 +767,23 @@ impl AssetSyncer {
             let product = product_id.to_string();
             let asset = asset.clone();
 
-            // Create a new syncer for each task (R2Client is Clone)
+            // Create a new syncer for each task, sharing the storage backend
             let syncer = AssetSyncer {
-                r2_client: self.r2_client.clone(),
-                http_client: self.http_client.clone(),
+                storage: self.storage.clone(),
+                urls: self.urls.clone(),
+                #[cfg(test)]
+                unguarded: self.unguarded.clone(),
                 concurrency: self.concurrency,
                 skip_existing: self.skip_existing,
+                shared_limiter: None,
+                retry_policy: self.retry_policy.clone(),
+                asset_retry_policy: self.asset_retry_policy.clone(),
+                status_store: self.status_store.clone(),
+                retry_cap: self.retry_cap,
+                blobs: self.blobs.clone(),
+                thumbnails: self.thumbnails,
+                thumbnail_max_dimension: self.thumbnail_max_dimension,
+                metrics: self.metrics.clone(),
             };
 
             let handle = tokio::spawn(async move {
@@ -247,6 +802,8 @@ impl AssetSyncer {
                 Ok(Ok(result)) => {
                     if result.content_type == "skipped" {
                         batch_result.skipped_count += 1;
+                    } else if result.deduplicated {
+                        batch_result.deduplicated_count += 1;
                     } else {
                         batch_result.success_count += 1;
                     }
@@ -271,10 +828,11 @@ impl AssetSyncer {
         batch_result.total_time_ms = start.elapsed().as_millis() as u64;
 
         info!(
-            "Batch sync completed: {} success, {} failed, {} skipped in {}ms",
+            "Batch sync completed: {} success, {} failed, {} skipped, {} deduplicated in {}ms",
             batch_result.success_count,
             batch_result.failed_count,
             batch_result.skipped_count,
+            batch_result.deduplicated_count,
             batch_result.total_time_ms
         );
 
@@ -293,6 +851,42 @@ impl AssetSyncer {
             .await
     }
 
+    /// Sync again the provider's failed assets that are under the retry cap
+    ///
+    /// Needs a status store to find them; without one there is nothing to retry.
+    #[instrument(skip(self))]
+    pub async fn retry_failed(
+        &self,
+        provider_code: &str,
+    ) -> Result<BatchSyncResult, AssetSyncError> {
+        let Some(ref store) = self.status_store else {
+            return Ok(BatchSyncResult::default());
+        };
+
+        let mut by_product: BTreeMap<String, Vec<MockupAsset>> = BTreeMap::new();
+        for (product_id, asset) in store.failed_assets(provider_code, self.retry_cap).await? {
+            by_product.entry(product_id).or_default().push(asset);
+        }
+
+        let mut result = BatchSyncResult::default();
+        for (product_id, assets) in by_product {
+            // Queued again, so they aren't picked up as failed meanwhile
+            for asset in &assets {
+                self.report(provider_code, &product_id, asset, AssetStatus::Pending)
+                    .await;
+            }
+            result.merge(self.sync_batch(provider_code, &product_id, &assets).await);
+        }
+
+        info!(
+            "Retried {} failed assets for {}: {} recovered",
+            result.results.len(),
+            provider_code,
+            result.success_count + result.skipped_count + result.deduplicated_count
+        );
+        Ok(result)
+    }
+
     /// Build an AssetPath from a MockupAsset
     fn build_asset_path(
         &self,
@@ -306,7 +900,9 @@ impl AssetSyncer {
         match asset.asset_type {
             AssetType::BaseImage => AssetPath::base_image(provider_code, product_id, &filename),
             AssetType::Thumbnail => AssetPath::thumbnail(provider_code, product_id, &filename),
-            AssetType::MockupTemplate | AssetType::PrintfilePreview => {
+            AssetType::MockupTemplate
+            | AssetType::PrintfilePreview
+            | AssetType::GeneratedMockup => {
                 let placement = asset.placement.clone().unwrap_or(PrintPlacement::Front);
 
                 if let Some(ref variant_id) = asset.variant_external_id {
@@ -343,6 +939,388 @@ impl AssetSyncer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::LocalDiskStorage;
+    use sha2::Digest;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        }
+    }
+
+    /// Serve `responses` to one connection each, returning how many were served
+    fn serve(
+        listener: TcpListener,
+        responses: Vec<&'static [u8]>,
+    ) -> tokio::task::JoinHandle<usize> {
+        tokio::spawn(async move {
+            let mut served = 0;
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let _ = socket.read(&mut buf).await.unwrap();
+                socket.write_all(response).await.unwrap();
+                served += 1;
+            }
+            served
+        })
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        };
+        let http = AssetSyncError::HttpError("HTTP 503".to_string());
+        assert_eq!(retry_delay(&policy, &http, 1), Some(Duration::from_secs(1)));
+        assert_eq!(retry_delay(&policy, &http, 2), Some(Duration::from_secs(2)));
+        assert_eq!(retry_delay(&policy, &http, 3), None);
+
+        // Rate limits wait at least as long as asked
+        let limited = AssetSyncError::RateLimited {
+            retry_after_secs: 20,
+        };
+        assert_eq!(
+            retry_delay(&policy, &limited, 1),
+            Some(Duration::from_secs(20))
+        );
+        let brief = AssetSyncError::RateLimited {
+            retry_after_secs: 0,
+        };
+        assert_eq!(
+            retry_delay(&policy, &brief, 2),
+            Some(Duration::from_secs(2))
+        );
+
+        let missing = AssetSyncError::NotFound("http://example.com/a.png".to_string());
+        assert_eq!(retry_delay(&policy, &missing, 1), None);
+
+        // R2 throttling is retried, a rejected upload isn't
+        let throttled = AssetSyncError::StorageError(R2Error::Transient("SlowDown".to_string()));
+        assert_eq!(
+            retry_delay(&policy, &throttled, 1),
+            Some(Duration::from_secs(1))
+        );
+        let corrupted =
+            AssetSyncError::StorageError(R2Error::ChecksumMismatch("a/b.png".to_string()));
+        assert_eq!(
+            retry_delay(&policy, &corrupted, 1),
+            Some(Duration::from_secs(1))
+        );
+        let denied =
+            AssetSyncError::StorageError(R2Error::UploadFailed("AccessDenied".to_string()));
+        assert_eq!(retry_delay(&policy, &denied, 1), None);
+    }
+
+    #[tokio::test]
+    async fn test_asset_retried_until_download_succeeds() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/asset.png", listener.local_addr().unwrap());
+        let limited: &[u8] = b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\n\
+            Content-Length: 0\r\nConnection: close\r\n\r\n";
+        let server = serve(
+            listener,
+            vec![
+                limited,
+                limited,
+                b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\nPNG!",
+            ],
+        );
+
+        let client = &reqwest::Client::new();
+        let url = url.as_str();
+        let (outcome, failures) = with_retries(&fast_policy(3), move || async move {
+            Ok::<_, AssetSyncError>(download_resumable(client, url, &fast_policy(1)).await?)
+        })
+        .await;
+
+        assert_eq!(outcome.unwrap().data, b"PNG!");
+        assert_eq!(failures, 2);
+        assert_eq!(server.await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_missing_asset_not_retried() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/asset.png", listener.local_addr().unwrap());
+        let server = serve(
+            listener,
+            vec![b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"],
+        );
+
+        let client = &reqwest::Client::new();
+        let url = url.as_str();
+        let (outcome, failures) = with_retries(&fast_policy(3), move || async move {
+            Ok::<_, AssetSyncError>(download_resumable(client, url, &fast_policy(1)).await?)
+        })
+        .await;
+
+        assert!(matches!(outcome, Err(AssetSyncError::NotFound(_))));
+        assert_eq!(failures, 1);
+        assert_eq!(server.await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_blob_key() {
+        let checksum = "ab".repeat(32);
+        assert_eq!(
+            blob_key("Printful", &checksum, "image/png"),
+            format!("printful/blobs/{}.png", checksum)
+        );
+        assert_eq!(
+            blob_key("gelato", &checksum, "image/jpeg"),
+            format!("gelato/blobs/{}.jpg", checksum)
+        );
+        assert_eq!(
+            blob_key("gelato", &checksum, "application/octet-stream"),
+            format!("gelato/blobs/{}.png", checksum)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_identical_assets_stored_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = serve(
+            listener,
+            vec![
+                b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\nPNG!",
+                b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\nPNG!",
+            ],
+        );
+
+        // Two variants serving the same image at different URLs
+        let client = reqwest::Client::new();
+        let mut checksums = Vec::new();
+        for variant in ["black", "white"] {
+            let url = format!("http://{}/{}/front.png", addr, variant);
+            let downloaded = download_resumable(&client, &url, &fast_policy(1))
+                .await
+                .unwrap();
+            checksums.push(hex::encode(downloaded.sha256));
+        }
+        assert_eq!(server.await.unwrap(), 2);
+        assert_eq!(checksums[0], checksums[1]);
+
+        let index = BlobIndex::default();
+        let uploads = &std::sync::atomic::AtomicUsize::new(0);
+        let mut deduplicated = Vec::new();
+        for checksum in &checksums {
+            let stored = index
+                .store_once(checksum, move || async move {
+                    uploads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Ok(false)
+                })
+                .await
+                .unwrap();
+            deduplicated.push(stored);
+        }
+
+        assert_eq!(deduplicated, vec![false, true]);
+        assert_eq!(uploads.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_identical_assets_wait_for_first_upload() {
+        let index = BlobIndex::default();
+        let uploads = &std::sync::atomic::AtomicUsize::new(0);
+        let store = move || async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            uploads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(false)
+        };
+
+        let (first, second) = tokio::join!(
+            index.store_once("same", store),
+            index.store_once("same", store)
+        );
+        let mut deduplicated = vec![first.unwrap(), second.unwrap()];
+        deduplicated.sort();
+        assert_eq!(deduplicated, vec![false, true]);
+        assert_eq!(uploads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // A blob R2 already had counts as deduplicated too
+        let found = index.store_once("other", || async { Ok(true) }).await;
+        assert!(found.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_failed_blob_upload_is_retried() {
+        let index = BlobIndex::default();
+        let failed = index
+            .store_once("blob", || async {
+                Err(AssetSyncError::HttpError("upload failed".to_string()))
+            })
+            .await;
+        assert!(failed.is_err());
+
+        let stored = index.store_once("blob", || async { Ok(false) }).await;
+        assert!(!stored.unwrap());
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::DynamicImage::new_rgb8(width, height);
+        let mut png = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        png
+    }
+
+    /// A 200 response carrying `body`
+    fn ok_response(body: &[u8]) -> &'static [u8] {
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(body);
+        Box::leak(response.into_boxed_slice())
+    }
+
+    fn local_storage() -> Arc<LocalDiskStorage> {
+        let root = std::env::temp_dir().join(format!("asset-sync-{}", Uuid::new_v4()));
+        Arc::new(LocalDiskStorage::new(root).unwrap())
+    }
+
+    /// Syncer that downloads from the test's local server
+    fn local_syncer(storage: Arc<LocalDiskStorage>) -> AssetSyncer {
+        AssetSyncer {
+            unguarded: Some(reqwest::Client::new()),
+            ..AssetSyncer::new(storage)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_internal_source_urls_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mug.png", listener.local_addr().unwrap());
+        let storage = local_storage();
+        let syncer = AssetSyncer::new(storage.clone());
+        let asset = MockupAsset::new(AssetType::BaseImage, url);
+
+        let result = syncer.sync_asset("printful", "19", &asset).await;
+        assert!(matches!(result, Err(AssetSyncError::InvalidUrl(_))));
+        let key = "printful/products/19/base/mug.png";
+        assert!(!storage.exists(key).await.unwrap());
+
+        // An allowlist keeps other public hosts out too
+        let syncer = AssetSyncer::new(storage).with_url_policy(UrlPolicy {
+            allowed_hosts: vec!["files.cdn.printful.com".to_string()],
+            ..UrlPolicy::default()
+        });
+        let asset = MockupAsset::new(AssetType::BaseImage, "https://example.com/mug.png".into());
+        let result = syncer.sync_asset("printful", "19", &asset).await;
+        assert!(matches!(result, Err(AssetSyncError::InvalidUrl(_))));
+    }
+
+    #[tokio::test]
+    async fn test_asset_mirrored_to_local_storage() {
+        let image = png(1200, 800);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mug.png", listener.local_addr().unwrap());
+        let server = serve(listener, vec![ok_response(&image)]);
+        let storage = local_storage();
+        let syncer = local_syncer(storage.clone()).with_thumbnails(true);
+        let asset = MockupAsset::new(AssetType::BaseImage, url);
+
+        let result = syncer.sync_asset("printful", "19", &asset).await.unwrap();
+        assert_eq!(result.r2_key, "printful/products/19/base/mug.png");
+        assert_eq!(result.size_bytes, image.len() as u64);
+        assert_eq!(result.bucket, storage.bucket());
+        assert_eq!(storage.download(&result.r2_key).await.unwrap(), image);
+
+        let thumbnail_key = result.thumbnail_r2_key.unwrap();
+        assert_eq!(
+            thumbnail_key,
+            "printful/products/19/thumbnails/thumb_base_image_mug.webp"
+        );
+        let thumbnail = image::load_from_memory(&storage.download(&thumbnail_key).await.unwrap());
+        assert_eq!(thumbnail.unwrap().width(), 400);
+
+        // Stored assets aren't downloaded again
+        let again = syncer.sync_asset("printful", "19", &asset).await.unwrap();
+        assert_eq!(again.content_type, "skipped");
+        assert_eq!(server.await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_identical_assets_share_local_blob() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = serve(listener, vec![ok_response(b"PNG!"), ok_response(b"PNG!")]);
+        let storage = local_storage();
+        let syncer = local_syncer(storage.clone()).with_dedup(true);
+        let assets: Vec<MockupAsset> = ["black", "white"]
+            .iter()
+            .map(|variant| {
+                let url = format!("http://{}/{}/front.png", addr, variant);
+                MockupAsset::new(AssetType::BaseImage, url)
+            })
+            .collect();
+
+        let result = syncer.sync_batch("gelato", "7", &assets).await;
+        assert_eq!((result.success_count, result.deduplicated_count), (1, 1));
+        let keys: HashSet<String> = result
+            .results
+            .into_iter()
+            .map(|result| result.unwrap().r2_key)
+            .collect();
+        let sha256: [u8; 32] = sha2::Sha256::digest(b"PNG!").into();
+        let blob = blob_key("gelato", &hex::encode(sha256), "image/png");
+        assert_eq!(keys, HashSet::from([blob.clone()]));
+        assert_eq!(storage.download(&blob).await.unwrap(), b"PNG!");
+        assert_eq!(server.await.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_thumbnail_fits_max_dimension() {
+        let thumbnail = make_thumbnail(&png(2000, 2000), DEFAULT_THUMBNAIL_MAX_DIMENSION)
+            .unwrap()
+            .unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (400, 400));
+
+        // The thumbnail is a WebP of the reported size
+        let decoded = image::load_from_memory(&thumbnail.data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (400, 400));
+
+        // Aspect ratio is kept
+        let wide = make_thumbnail(&png(2000, 1000), 400).unwrap().unwrap();
+        assert_eq!((wide.width, wide.height), (400, 200));
+        let tall = make_thumbnail(&png(600, 1200), 400).unwrap().unwrap();
+        assert_eq!((tall.width, tall.height), (200, 400));
+    }
+
+    #[test]
+    fn test_small_images_get_no_thumbnail() {
+        assert!(make_thumbnail(&png(400, 300), 400).unwrap().is_none());
+        assert!(make_thumbnail(b"not an image", 400).is_err());
+    }
+
+    #[test]
+    fn test_thumbnail_path() {
+        let base = AssetPath::base_image("printful", "71", "front.png");
+        assert_eq!(
+            thumbnail_path(&base).to_key(),
+            "printful/products/71/thumbnails/thumb_base_image_front.webp"
+        );
+
+        let variant = AssetPath::variant_asset("printful", "71", "4012", PrintPlacement::Back);
+        assert_eq!(
+            thumbnail_path(&variant).to_key(),
+            "printful/products/71/thumbnails/thumb_mockup_template_4012_back.webp"
+        );
+    }
 
     #[test]
     fn test_extract_filename() {
@@ -0,0 +1,412 @@
+//! Synced catalog storage
+//!
+//! The orchestrator records synced products through a `CatalogStore`, and
+//! the progress of mirroring their assets through an `AssetStatusStore`.
+//! With a database both are the POD catalog tables; without one an in-memory
+//! store remembers sync hashes and mirrored and failed assets, so incremental
+//! syncs still skip unchanged products within a process.
+
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use super::asset_sync::{AssetStatus, AssetStatusStore, AssetSyncError};
+use super::orchestrator::SyncOrchestratorError;
+use crate::db::catalog::sync_hash;
+use crate::db::{AssetUpdate, CatalogRepository, ProviderRepository, StoredProduct};
+use crate::domain::catalog::{MockupAsset, UnifiedProduct};
+
+/// Storage for synced products used by the orchestrator
+#[async_trait]
+pub trait CatalogStore: Send + Sync {
+    /// Store a product with its variants and print areas, queueing `assets`
+    /// for download
+    ///
+    /// Either all of it is stored or none of it is.
+    async fn store_product(
+        &self,
+        provider_code: &str,
+        product: &UnifiedProduct,
+        assets: &[MockupAsset],
+    ) -> Result<StoredProduct, SyncOrchestratorError>;
+
+    /// Mark a product as synced if a sync would change nothing
+    ///
+    /// True when the stored product has `sync_hash` and none of its assets
+    /// still need mirroring.
+    async fn skip_unchanged(
+        &self,
+        provider_code: &str,
+        external_id: &str,
+        sync_hash: &str,
+    ) -> Result<bool, SyncOrchestratorError>;
+
+    /// Record that a catalog sync of a provider completed
+    async fn provider_synced(&self, provider_code: &str) -> Result<(), SyncOrchestratorError>;
+
+    /// Requests per minute stored for a provider, replacing its client's built-in limit
+    async fn provider_rate_limit(
+        &self,
+        _provider_code: &str,
+    ) -> Result<Option<u32>, SyncOrchestratorError> {
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl CatalogStore for CatalogRepository {
+    async fn store_product(
+        &self,
+        provider_code: &str,
+        product: &UnifiedProduct,
+        assets: &[MockupAsset],
+    ) -> Result<StoredProduct, SyncOrchestratorError> {
+        Ok(self
+            .store_product_with_assets(provider_code, product, assets)
+            .await?)
+    }
+
+    async fn skip_unchanged(
+        &self,
+        provider_code: &str,
+        external_id: &str,
+        sync_hash: &str,
+    ) -> Result<bool, SyncOrchestratorError> {
+        Ok(CatalogRepository::skip_unchanged(self, provider_code, external_id, sync_hash).await?)
+    }
+
+    async fn provider_synced(&self, provider_code: &str) -> Result<(), SyncOrchestratorError> {
+        Ok(self.mark_provider_synced(provider_code).await?)
+    }
+
+    async fn provider_rate_limit(
+        &self,
+        provider_code: &str,
+    ) -> Result<Option<u32>, SyncOrchestratorError> {
+        Ok(ProviderRepository::new(self.pool.clone())
+            .rate_limit(provider_code)
+            .await?)
+    }
+}
+
+#[async_trait]
+impl AssetStatusStore for CatalogRepository {
+    async fn record_status(
+        &self,
+        provider_code: &str,
+        product_id: &str,
+        asset: &MockupAsset,
+        status: &AssetStatus<'_>,
+    ) -> Result<(), AssetSyncError> {
+        let update = match status {
+            AssetStatus::Pending => AssetUpdate::Pending,
+            AssetStatus::Downloading => AssetUpdate::Downloading,
+            AssetStatus::Downloaded(synced) => {
+                // Assets already in R2 keep the details recorded when they were fetched
+                let fresh = synced.checksum.is_some();
+                AssetUpdate::Downloaded {
+                    bucket: &synced.bucket,
+                    r2_key: &synced.r2_key,
+                    file_size_bytes: fresh.then_some(synced.size_bytes as i64),
+                    content_type: fresh.then_some(synced.content_type.as_str()),
+                    checksum: synced.checksum.as_deref(),
+                    retries: synced.retry_count as i32,
+                    thumbnail_r2_key: synced.thumbnail_r2_key.as_deref(),
+                }
+            }
+            AssetStatus::Failed { error, attempts } => AssetUpdate::Failed {
+                error: error.to_string(),
+                attempts: *attempts as i32,
+            },
+        };
+
+        self.record_asset(provider_code, product_id, asset, &update)
+            .await
+            .map_err(|e| AssetSyncError::DatabaseError(e.to_string()))
+    }
+
+    async fn failed_assets(
+        &self,
+        provider_code: &str,
+        retry_cap: u32,
+    ) -> Result<Vec<(String, MockupAsset)>, AssetSyncError> {
+        let retry_cap = i32::try_from(retry_cap).unwrap_or(i32::MAX);
+        CatalogRepository::failed_assets(self, provider_code, retry_cap)
+            .await
+            .map_err(|e| AssetSyncError::DatabaseError(e.to_string()))
+    }
+
+    async fn referenced_keys(&self, keys: &[String]) -> Result<HashSet<String>, AssetSyncError> {
+        CatalogRepository::referenced_keys(self, keys)
+            .await
+            .map_err(|e| AssetSyncError::DatabaseError(e.to_string()))
+    }
+}
+
+/// What the memory store knows about a product
+struct MemoryProduct {
+    id: Uuid,
+    sync_hash: String,
+}
+
+/// An asset that failed to mirror, with its failed attempts so far
+struct FailedAsset {
+    asset: MockupAsset,
+    attempts: u32,
+}
+
+/// Sync hashes and asset progress kept in process memory, used when no database is configured
+#[derive(Default)]
+pub struct MemoryCatalogStore {
+    /// Keyed by provider code and the provider's product ID
+    products: RwLock<HashMap<(String, String), MemoryProduct>>,
+    /// Keyed by provider code, the provider's product ID, and source URL
+    failed_assets: RwLock<HashMap<(String, String, String), FailedAsset>>,
+    /// R2 keys of each mirrored asset and its thumbnail, keyed like `failed_assets`
+    mirrored_keys: RwLock<HashMap<(String, String, String), Vec<String>>>,
+}
+
+impl MemoryCatalogStore {
+    fn has_failed_assets(&self, provider_code: &str, external_id: &str) -> bool {
+        self.failed_assets
+            .read()
+            .unwrap()
+            .keys()
+            .any(|(provider, product, _)| provider == provider_code && product == external_id)
+    }
+}
+
+#[async_trait]
+impl CatalogStore for MemoryCatalogStore {
+    async fn store_product(
+        &self,
+        provider_code: &str,
+        product: &UnifiedProduct,
+        _assets: &[MockupAsset],
+    ) -> Result<StoredProduct, SyncOrchestratorError> {
+        let hash = sync_hash(product);
+        let mut products = self.products.write().unwrap();
+        let key = (provider_code.to_string(), product.external_id.clone());
+
+        match products.get_mut(&key) {
+            Some(stored) if stored.sync_hash == hash => Ok(StoredProduct::Unchanged(stored.id)),
+            Some(stored) => {
+                stored.sync_hash = hash;
+                Ok(StoredProduct::Updated(stored.id))
+            }
+            None => {
+                let id = Uuid::new_v4();
+                products.insert(
+                    key,
+                    MemoryProduct {
+                        id,
+                        sync_hash: hash,
+                    },
+                );
+                Ok(StoredProduct::Updated(id))
+            }
+        }
+    }
+
+    async fn skip_unchanged(
+        &self,
+        provider_code: &str,
+        external_id: &str,
+        sync_hash: &str,
+    ) -> Result<bool, SyncOrchestratorError> {
+        let key = (provider_code.to_string(), external_id.to_string());
+        let unchanged = self
+            .products
+            .read()
+            .unwrap()
+            .get(&key)
+            .is_some_and(|stored| stored.sync_hash == sync_hash);
+        Ok(unchanged && !self.has_failed_assets(provider_code, external_id))
+    }
+
+    async fn provider_synced(&self, _provider_code: &str) -> Result<(), SyncOrchestratorError> {
+        // Scheduled syncs need the database, so there is nothing to track here
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AssetStatusStore for MemoryCatalogStore {
+    async fn record_status(
+        &self,
+        provider_code: &str,
+        product_id: &str,
+        asset: &MockupAsset,
+        status: &AssetStatus<'_>,
+    ) -> Result<(), AssetSyncError> {
+        let key = (
+            provider_code.to_string(),
+            product_id.to_string(),
+            asset.source_url.clone(),
+        );
+        let mut failed = self.failed_assets.write().unwrap();
+        match status {
+            AssetStatus::Pending | AssetStatus::Downloading => {}
+            AssetStatus::Downloaded(synced) => {
+                failed.remove(&key);
+                let keys = std::iter::once(synced.r2_key.clone())
+                    .chain(synced.thumbnail_r2_key.clone())
+                    .collect();
+                self.mirrored_keys.write().unwrap().insert(key, keys);
+            }
+            AssetStatus::Failed { attempts, .. } => {
+                let entry = failed.entry(key).or_insert_with(|| FailedAsset {
+                    asset: asset.clone(),
+                    attempts: 0,
+                });
+                entry.attempts += attempts;
+            }
+        }
+        Ok(())
+    }
+
+    async fn failed_assets(
+        &self,
+        provider_code: &str,
+        retry_cap: u32,
+    ) -> Result<Vec<(String, MockupAsset)>, AssetSyncError> {
+        let failed = self.failed_assets.read().unwrap();
+        let mut assets: Vec<(String, MockupAsset)> = failed
+            .iter()
+            .filter(|((provider, _, _), failed)| {
+                provider == provider_code && failed.attempts < retry_cap
+            })
+            .map(|((_, product, _), failed)| (product.clone(), failed.asset.clone()))
+            .collect();
+        assets.sort_by(|a, b| (&a.0, &a.1.source_url).cmp(&(&b.0, &b.1.source_url)));
+        Ok(assets)
+    }
+
+    async fn referenced_keys(&self, keys: &[String]) -> Result<HashSet<String>, AssetSyncError> {
+        let mirrored = self.mirrored_keys.read().unwrap();
+        let stored: HashSet<&String> = mirrored.values().flatten().collect();
+        Ok(keys
+            .iter()
+            .filter(|key| stored.contains(key))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::catalog::{AssetType, ProductType};
+    use crate::sync::AssetSyncResult;
+
+    fn mug() -> UnifiedProduct {
+        UnifiedProduct::new(
+            "19".to_string(),
+            "printful".to_string(),
+            "White Glossy Mug".to_string(),
+            ProductType::Mug,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_skips_unchanged_products() {
+        let store = MemoryCatalogStore::default();
+        let product = mug();
+        let hash = sync_hash(&product);
+        assert!(!store.skip_unchanged("printful", "19", &hash).await.unwrap());
+
+        let stored = store
+            .store_product("printful", &product, &[])
+            .await
+            .unwrap();
+        assert!(matches!(stored, StoredProduct::Updated(_)));
+        assert!(store.skip_unchanged("printful", "19", &hash).await.unwrap());
+        assert!(!store
+            .skip_unchanged("printful", "19", "stale")
+            .await
+            .unwrap());
+        assert!(!store.skip_unchanged("gelato", "19", &hash).await.unwrap());
+        assert_eq!(
+            store
+                .store_product("printful", &product, &[])
+                .await
+                .unwrap(),
+            StoredProduct::Unchanged(stored.product_id())
+        );
+
+        let mut renamed = product.clone();
+        renamed.name = "Black Glossy Mug".to_string();
+        assert_eq!(
+            store
+                .store_product("printful", &renamed, &[])
+                .await
+                .unwrap(),
+            StoredProduct::Updated(stored.product_id())
+        );
+        assert!(!store.skip_unchanged("printful", "19", &hash).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_resyncs_products_with_failed_assets() {
+        let store = MemoryCatalogStore::default();
+        let product = mug();
+        let hash = sync_hash(&product);
+        store
+            .store_product("printful", &product, &[])
+            .await
+            .unwrap();
+
+        let asset = MockupAsset::new(
+            AssetType::BaseImage,
+            "https://provider.example/mug.png".to_string(),
+        );
+        let error = AssetSyncError::HttpError("HTTP 503".to_string());
+        let failed = AssetStatus::Failed {
+            error: &error,
+            attempts: 3,
+        };
+        store
+            .record_status("printful", "19", &asset, &failed)
+            .await
+            .unwrap();
+        assert!(!store.skip_unchanged("printful", "19", &hash).await.unwrap());
+
+        // Failed assets are offered for retries until they reach the cap
+        let retryable = store.failed_assets("printful", 10).await.unwrap();
+        assert_eq!(retryable.len(), 1);
+        assert_eq!(retryable[0].0, "19");
+        assert_eq!(retryable[0].1.source_url, asset.source_url);
+        assert!(store.failed_assets("printful", 3).await.unwrap().is_empty());
+        assert!(store.failed_assets("gelato", 10).await.unwrap().is_empty());
+
+        let synced = AssetSyncResult {
+            source_url: asset.source_url.clone(),
+            r2_key: "printful/19/mug.png".to_string(),
+            size_bytes: 4,
+            content_type: "image/png".to_string(),
+            public_url: None,
+            sync_time_ms: 1,
+            bucket: "pod-assets".to_string(),
+            checksum: Some("ab".repeat(32)),
+            retry_count: 0,
+            deduplicated: false,
+            thumbnail_r2_key: None,
+        };
+        store
+            .record_status("printful", "19", &asset, &AssetStatus::Downloaded(&synced))
+            .await
+            .unwrap();
+        assert!(store.skip_unchanged("printful", "19", &hash).await.unwrap());
+        let keys = vec![synced.r2_key.clone(), "printful/19/old.png".to_string()];
+        assert_eq!(
+            store.referenced_keys(&keys).await.unwrap(),
+            HashSet::from([synced.r2_key.clone()])
+        );
+        assert!(store
+            .failed_assets("printful", 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
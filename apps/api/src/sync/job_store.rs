@@ -0,0 +1,451 @@
+//! Sync job storage
+//!
+//! The orchestrator writes job progress through a `SyncJobStore` and the sync
+//! handlers read from the same store, so both see one source of truth. With a
+//! database the store is the `pod_sync_jobs` table and survives restarts;
+//! without one an in-memory store keeps the service usable for local runs.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use super::orchestrator::{SyncJob, SyncJobStatus, SyncJobType, SyncOrchestratorError};
+use crate::db::pool::DbError;
+use crate::db::DbPool;
+
+/// Persistence for sync jobs shared by the orchestrator and the sync handlers
+#[async_trait]
+pub trait SyncJobStore: Send + Sync {
+    /// Record a new pending or running job
+    ///
+    /// Fails with `JobAlreadyRunning` when the provider already has an active job.
+    async fn create(&self, job: &SyncJob) -> Result<(), SyncOrchestratorError>;
+
+    /// Save a job's status, counters, cursor, and heartbeat
+    ///
+    /// Returns `false` without writing when the stored job is no longer active,
+    /// for example because it was cancelled; the worker should stop.
+    async fn update(&self, job: &SyncJob) -> Result<bool, SyncOrchestratorError>;
+
+    /// Save the counters and cursor of a job stopped from outside its worker
+    ///
+    /// Unlike `update` this also writes to finished jobs, and keeps their status.
+    async fn save_counts(&self, job: &SyncJob) -> Result<(), SyncOrchestratorError>;
+
+    /// Get a job by ID
+    async fn get(&self, id: Uuid) -> Result<Option<SyncJob>, SyncOrchestratorError>;
+
+    /// Most recent jobs, newest first
+    async fn list(&self, limit: i64) -> Result<Vec<SyncJob>, SyncOrchestratorError>;
+
+    /// Most recent job for a provider
+    async fn latest(&self, provider_code: &str) -> Result<Option<SyncJob>, SyncOrchestratorError>;
+
+    /// Fail active jobs whose heartbeat is older than `timeout`, returning them
+    ///
+    /// Their cursors are kept, so a later sync can resume where they stopped.
+    async fn fail_stale(&self, timeout: Duration) -> Result<Vec<SyncJob>, SyncOrchestratorError>;
+}
+
+/// Error message recorded on jobs whose worker stopped sending heartbeats
+pub const INTERRUPTED_MESSAGE: &str = "Interrupted: worker stopped sending heartbeats";
+
+fn is_active(status: SyncJobStatus) -> bool {
+    matches!(status, SyncJobStatus::Pending | SyncJobStatus::Running)
+}
+
+/// Jobs kept in process memory, used when no database is configured
+#[derive(Default)]
+pub struct MemorySyncJobStore {
+    jobs: RwLock<HashMap<Uuid, SyncJob>>,
+}
+
+#[async_trait]
+impl SyncJobStore for MemorySyncJobStore {
+    async fn create(&self, job: &SyncJob) -> Result<(), SyncOrchestratorError> {
+        let mut jobs = self.jobs.write().unwrap();
+        let busy = jobs
+            .values()
+            .any(|j| j.provider_code == job.provider_code && is_active(j.status));
+        if busy {
+            return Err(SyncOrchestratorError::JobAlreadyRunning(
+                job.provider_code.clone(),
+            ));
+        }
+        let mut job = job.clone();
+        job.heartbeat_at = Some(Utc::now());
+        jobs.insert(job.id, job);
+        Ok(())
+    }
+
+    async fn update(&self, job: &SyncJob) -> Result<bool, SyncOrchestratorError> {
+        let mut jobs = self.jobs.write().unwrap();
+        match jobs.get_mut(&job.id) {
+            Some(stored) if is_active(stored.status) => {
+                *stored = job.clone();
+                stored.heartbeat_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn save_counts(&self, job: &SyncJob) -> Result<(), SyncOrchestratorError> {
+        let mut jobs = self.jobs.write().unwrap();
+        if let Some(stored) = jobs.get_mut(&job.id) {
+            stored.total_items = job.total_items;
+            stored.processed_items = job.processed_items;
+            stored.failed_items = job.failed_items;
+            stored.skipped_items = job.skipped_items;
+            stored.cursor = job.cursor.clone();
+            stored.cleanup = job.cleanup;
+        }
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<SyncJob>, SyncOrchestratorError> {
+        Ok(self.jobs.read().unwrap().get(&id).cloned())
+    }
+
+    async fn list(&self, limit: i64) -> Result<Vec<SyncJob>, SyncOrchestratorError> {
+        let mut list: Vec<SyncJob> = self.jobs.read().unwrap().values().cloned().collect();
+        list.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        list.truncate(limit.max(0) as usize);
+        Ok(list)
+    }
+
+    async fn latest(&self, provider_code: &str) -> Result<Option<SyncJob>, SyncOrchestratorError> {
+        let jobs = self.jobs.read().unwrap();
+        Ok(jobs
+            .values()
+            .filter(|j| j.provider_code == provider_code)
+            .max_by_key(|j| j.created_at)
+            .cloned())
+    }
+
+    async fn fail_stale(&self, timeout: Duration) -> Result<Vec<SyncJob>, SyncOrchestratorError> {
+        let cutoff = Utc::now() - chrono::Duration::from_std(timeout).unwrap_or_default();
+        let mut jobs = self.jobs.write().unwrap();
+        let mut failed = Vec::new();
+        for job in jobs.values_mut() {
+            let last_seen = job.heartbeat_at.unwrap_or(job.created_at);
+            if is_active(job.status) && last_seen < cutoff {
+                job.fail(INTERRUPTED_MESSAGE);
+                failed.push(job.clone());
+            }
+        }
+        Ok(failed)
+    }
+}
+
+/// Jobs in the `pod_sync_jobs` table
+pub struct PgSyncJobStore {
+    pool: DbPool,
+}
+
+const JOB_COLUMNS: &str = "j.id, pr.code AS provider_code, j.job_type, j.status, \
+     j.total_items, j.processed_items, j.failed_items, j.skipped_items, j.created_at, j.started_at, \
+     j.completed_at, j.error_message, j.product_id, j.parent_job_id, j.cursor, j.heartbeat_at, \
+     j.cleanup_summary::TEXT AS cleanup_summary";
+
+/// A job's cleanup summary as JSON, for its `cleanup_summary` column
+fn cleanup_json(job: &SyncJob) -> Option<String> {
+    job.cleanup
+        .and_then(|summary| serde_json::to_string(&summary).ok())
+}
+
+impl PgSyncJobStore {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn from_row(row: &Row) -> SyncJob {
+        let job_type: String = row.get("job_type");
+        let status: String = row.get("status");
+        let count = |column: &str| row.get::<_, Option<i32>>(column).unwrap_or(0).max(0) as u32;
+
+        SyncJob {
+            id: row.get("id"),
+            provider_code: row.get("provider_code"),
+            // Older rows may carry job types the orchestrator never ran
+            job_type: SyncJobType::parse(&job_type).unwrap_or(SyncJobType::FullCatalog),
+            status: SyncJobStatus::parse(&status).unwrap_or(SyncJobStatus::Failed),
+            total_items: count("total_items"),
+            processed_items: count("processed_items"),
+            failed_items: count("failed_items"),
+            skipped_items: count("skipped_items"),
+            created_at: row.get("created_at"),
+            started_at: row.get("started_at"),
+            completed_at: row.get("completed_at"),
+            error_message: row.get("error_message"),
+            product_id: row.get("product_id"),
+            parent_job_id: row.get("parent_job_id"),
+            cursor: row.get("cursor"),
+            heartbeat_at: row.get::<_, Option<DateTime<Utc>>>("heartbeat_at"),
+            cleanup: row
+                .get::<_, Option<&str>>("cleanup_summary")
+                .and_then(|summary| serde_json::from_str(summary).ok()),
+        }
+    }
+
+    async fn query_jobs(
+        &self,
+        filter: &str,
+        params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+    ) -> Result<Vec<SyncJob>, DbError> {
+        let client = self.pool.get().await?;
+        let sql = format!(
+            "SELECT {} FROM pod_sync_jobs j JOIN pod_providers pr ON j.provider_id = pr.id {}",
+            JOB_COLUMNS, filter
+        );
+        let rows = client.query(&sql, params).await?;
+        Ok(rows.iter().map(Self::from_row).collect())
+    }
+}
+
+impl From<DbError> for SyncOrchestratorError {
+    fn from(e: DbError) -> Self {
+        SyncOrchestratorError::DatabaseError(e.to_string())
+    }
+}
+
+#[async_trait]
+impl SyncJobStore for PgSyncJobStore {
+    async fn create(&self, job: &SyncJob) -> Result<(), SyncOrchestratorError> {
+        let client = self.pool.get().await?;
+
+        let result = client
+            .execute(
+                r#"
+            INSERT INTO pod_sync_jobs (
+                id, provider_id, job_type, status, total_items, processed_items, failed_items,
+                skipped_items, created_at, started_at, product_id, parent_job_id, cursor,
+                heartbeat_at, cleanup_summary
+            )
+            SELECT $1, pr.id, $3, $4, $5, $6, $7, $13, $8, $9, $10, $11, $12, NOW(),
+                $14::TEXT::JSONB
+            FROM pod_providers pr
+            WHERE pr.code = $2
+            "#,
+                &[
+                    &job.id,
+                    &job.provider_code,
+                    &job.job_type.to_string(),
+                    &job.status.to_string(),
+                    &(job.total_items as i32),
+                    &(job.processed_items as i32),
+                    &(job.failed_items as i32),
+                    &job.created_at,
+                    &job.started_at,
+                    &job.product_id,
+                    &job.parent_job_id,
+                    &job.cursor,
+                    &(job.skipped_items as i32),
+                    &cleanup_json(job),
+                ],
+            )
+            .await;
+
+        match result {
+            Ok(0) => Err(SyncOrchestratorError::ProviderNotFound(
+                job.provider_code.clone(),
+            )),
+            Ok(_) => Ok(()),
+            Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => Err(
+                SyncOrchestratorError::JobAlreadyRunning(job.provider_code.clone()),
+            ),
+            Err(e) => Err(DbError::from(e).into()),
+        }
+    }
+
+    async fn update(&self, job: &SyncJob) -> Result<bool, SyncOrchestratorError> {
+        let client = self.pool.get().await?;
+
+        let updated = client
+            .execute(
+                r#"
+            UPDATE pod_sync_jobs
+            SET status = $2, total_items = $3, processed_items = $4, failed_items = $5,
+                started_at = $6, completed_at = $7, error_message = $8, cursor = $9,
+                skipped_items = $10, cleanup_summary = $11::TEXT::JSONB, heartbeat_at = NOW()
+            WHERE id = $1 AND status IN ('pending', 'running')
+            "#,
+                &[
+                    &job.id,
+                    &job.status.to_string(),
+                    &(job.total_items as i32),
+                    &(job.processed_items as i32),
+                    &(job.failed_items as i32),
+                    &job.started_at,
+                    &job.completed_at,
+                    &job.error_message,
+                    &job.cursor,
+                    &(job.skipped_items as i32),
+                    &cleanup_json(job),
+                ],
+            )
+            .await
+            .map_err(DbError::from)?;
+
+        Ok(updated > 0)
+    }
+
+    async fn save_counts(&self, job: &SyncJob) -> Result<(), SyncOrchestratorError> {
+        let client = self.pool.get().await?;
+
+        client
+            .execute(
+                r#"
+            UPDATE pod_sync_jobs
+            SET total_items = $2, processed_items = $3, failed_items = $4, skipped_items = $5,
+                cursor = $6, cleanup_summary = $7::TEXT::JSONB
+            WHERE id = $1
+            "#,
+                &[
+                    &job.id,
+                    &(job.total_items as i32),
+                    &(job.processed_items as i32),
+                    &(job.failed_items as i32),
+                    &(job.skipped_items as i32),
+                    &job.cursor,
+                    &cleanup_json(job),
+                ],
+            )
+            .await
+            .map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<SyncJob>, SyncOrchestratorError> {
+        let jobs = self.query_jobs("WHERE j.id = $1", &[&id]).await?;
+        Ok(jobs.into_iter().next())
+    }
+
+    async fn list(&self, limit: i64) -> Result<Vec<SyncJob>, SyncOrchestratorError> {
+        Ok(self
+            .query_jobs("ORDER BY j.created_at DESC LIMIT $1", &[&limit])
+            .await?)
+    }
+
+    async fn latest(&self, provider_code: &str) -> Result<Option<SyncJob>, SyncOrchestratorError> {
+        let jobs = self
+            .query_jobs(
+                "WHERE pr.code = $1 ORDER BY j.created_at DESC LIMIT 1",
+                &[&provider_code],
+            )
+            .await?;
+        Ok(jobs.into_iter().next())
+    }
+
+    async fn fail_stale(&self, timeout: Duration) -> Result<Vec<SyncJob>, SyncOrchestratorError> {
+        let client = self.pool.get().await?;
+        let timeout_secs = timeout.as_secs_f64();
+
+        let rows = client
+            .query(
+                r#"
+            UPDATE pod_sync_jobs
+            SET status = 'failed', completed_at = NOW(), error_message = $2
+            WHERE status IN ('pending', 'running')
+              AND COALESCE(heartbeat_at, created_at) < NOW() - make_interval(secs => $1)
+            RETURNING id
+            "#,
+                &[&timeout_secs, &INTERRUPTED_MESSAGE],
+            )
+            .await
+            .map_err(DbError::from)?;
+
+        let ids: Vec<Uuid> = rows.iter().map(|row| row.get("id")).collect();
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(self.query_jobs("WHERE j.id = ANY($1)", &[&ids]).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_store_one_active_job_per_provider() {
+        let store = MemorySyncJobStore::default();
+        let first = SyncJob::new("printful", SyncJobType::FullCatalog);
+        store.create(&first).await.unwrap();
+
+        let second = SyncJob::new("printful", SyncJobType::FullCatalog);
+        assert!(matches!(
+            store.create(&second).await,
+            Err(SyncOrchestratorError::JobAlreadyRunning(_))
+        ));
+        store
+            .create(&SyncJob::new("gelato", SyncJobType::FullCatalog))
+            .await
+            .unwrap();
+
+        let mut done = first.clone();
+        done.start();
+        done.complete();
+        assert!(store.update(&done).await.unwrap());
+        store.create(&second).await.unwrap();
+        assert_eq!(store.list(10).await.unwrap().len(), 3);
+        assert_eq!(
+            store.latest("printful").await.unwrap().map(|j| j.id),
+            Some(second.id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_rejects_updates_after_cancel() {
+        let store = MemorySyncJobStore::default();
+        let mut job = SyncJob::new("printful", SyncJobType::FullCatalog);
+        job.start();
+        store.create(&job).await.unwrap();
+
+        let mut cancelled = job.clone();
+        cancelled.cancel();
+        assert!(store.update(&cancelled).await.unwrap());
+
+        job.increment_processed();
+        assert!(!store.update(&job).await.unwrap());
+        let stored = store.get(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, SyncJobStatus::Cancelled);
+        assert_eq!(stored.processed_items, 0);
+
+        // The worker can still record how far it got
+        store.save_counts(&job).await.unwrap();
+        let stored = store.get(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, SyncJobStatus::Cancelled);
+        assert_eq!(stored.processed_items, 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_fails_stale_jobs() {
+        let store = MemorySyncJobStore::default();
+        let mut job = SyncJob::new("printful", SyncJobType::FullCatalog);
+        job.start();
+        job.cursor = Some("3".to_string());
+        store.create(&job).await.unwrap();
+
+        assert!(store
+            .fail_stale(Duration::from_secs(60))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let failed = store.fail_stale(Duration::ZERO).await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].status, SyncJobStatus::Failed);
+        assert_eq!(
+            failed[0].error_message.as_deref(),
+            Some(INTERRUPTED_MESSAGE)
+        );
+        assert_eq!(failed[0].cursor.as_deref(), Some("3"));
+    }
+}
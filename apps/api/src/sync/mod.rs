@@ -3,8 +3,23 @@
 //! Handles downloading product catalogs and assets from POD providers
 //! and storing them in our database and R2 storage.
 
+mod asset_cleanup;
 mod asset_sync;
+mod catalog_store;
+mod job_store;
+mod on_demand;
 mod orchestrator;
+mod product_templates;
+mod schedule;
+mod scheduler;
 
+pub use asset_cleanup::CleanupSummary;
 pub use asset_sync::{AssetSyncError, AssetSyncResult, AssetSyncer};
-pub use orchestrator::{SyncJob, SyncJobStatus, SyncJobType, SyncOrchestrator};
+pub use job_store::SyncJobStore;
+pub use on_demand::{OnDemandError, OnDemandTemplates, TemplateSource};
+pub use orchestrator::{
+    SyncJob, SyncJobStatus, SyncJobType, SyncOrchestrator, SyncOrchestratorError,
+};
+pub use product_templates::{ProductTemplateError, ProductTemplates, ResolvedProductTemplate};
+pub use schedule::{any_provider_configured, SyncSchedule};
+pub use scheduler::{SyncScheduler, UmbrellaJob, UmbrellaJobSummary};
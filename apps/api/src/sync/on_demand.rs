@@ -0,0 +1,487 @@
+//! On-demand mockup templates
+//!
+//! Resolves a provider variant's mockup template at render time so products
+//! render before they have been synced. The R2 cache is checked first; on a
+//! miss the template is fetched through `PodProvider::get_mockup_urls`,
+//! cached in R2 next to a JSON sidecar describing its print area, and
+//! returned for rendering in the same request.
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::config::service_user_agent;
+use crate::domain::catalog::{AssetType, MockupAsset, PrintPlacement, TemplatePrintArea};
+use crate::engine::{PrintArea, TemplateDimensions, TemplateImages, TemplateMetadata};
+use crate::net::{UrlGuard, UrlGuardError, UrlPolicy};
+use crate::providers::{ProviderCredentials, ProviderError, ProviderFactory};
+use crate::storage::{AssetPath, R2Client, R2Error};
+
+/// Timeout for downloading a provider template image
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest template image accepted from a provider
+const MAX_TEMPLATE_BYTES: usize = 25 * 1024 * 1024;
+
+/// Start of the file names templates are cached under; these objects have no asset row
+pub(crate) const ON_DEMAND_FILE_PREFIX: &str = "on_demand_";
+
+/// Errors resolving an on-demand template
+#[derive(Error, Debug)]
+pub enum OnDemandError {
+    #[error("No cached template for {0}; retry with fetch_on_demand enabled")]
+    NotCached(String),
+
+    #[error("Provider not found: {0}")]
+    ProviderNotFound(String),
+
+    #[error("Provider error: {0}")]
+    Provider(#[from] ProviderError),
+
+    #[error("Provider has no mockup template with a print area for {0}")]
+    NoMockupTemplate(String),
+
+    #[error("R2 storage error: {0}")]
+    Storage(#[from] R2Error),
+
+    #[error("Template download failed: {0}")]
+    Download(String),
+
+    #[error("Template image error: {0}")]
+    Image(String),
+}
+
+/// Where a resolved template came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateSource {
+    R2Cache,
+    Provider,
+}
+
+impl TemplateSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TemplateSource::R2Cache => "r2_cache",
+            TemplateSource::Provider => "provider",
+        }
+    }
+}
+
+/// Sidecar stored next to a cached template image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedTemplateInfo {
+    source_url: String,
+    width_px: u32,
+    height_px: u32,
+    /// Print area in image pixels
+    print_area: TemplatePrintArea,
+}
+
+/// A template ready to render against
+pub struct ResolvedTemplate {
+    pub metadata: TemplateMetadata,
+    pub images: TemplateImages,
+    pub source: TemplateSource,
+    pub source_url: String,
+    /// R2 key of the cached image, when R2 is configured
+    pub r2_key: Option<String>,
+}
+
+/// Resolves provider mockup templates without a prior sync
+pub struct OnDemandTemplates {
+    r2_client: Option<R2Client>,
+    /// Template URLs come from provider APIs, so they are fetched like design URLs
+    urls: UrlGuard,
+}
+
+impl OnDemandTemplates {
+    pub fn new(r2_client: Option<R2Client>) -> Self {
+        let builder = reqwest::Client::builder()
+            .timeout(DOWNLOAD_TIMEOUT)
+            .user_agent(service_user_agent());
+        let urls =
+            UrlGuard::new(UrlPolicy::default(), builder).expect("Failed to create HTTP client");
+
+        Self { r2_client, urls }
+    }
+
+    /// Resolve the mockup template for a provider product variant
+    ///
+    /// Without `fetch_on_demand` only the R2 cache is consulted.
+    pub async fn resolve(
+        &self,
+        provider_code: &str,
+        product_id: &str,
+        variant_id: Option<&str>,
+        placement: &PrintPlacement,
+        fetch_on_demand: bool,
+    ) -> Result<ResolvedTemplate, OnDemandError> {
+        let template_id = format!(
+            "{}:{}:{}:{}",
+            provider_code.to_lowercase(),
+            product_id,
+            variant_id.unwrap_or("default"),
+            placement.as_str()
+        );
+        let image_key = Self::cache_key(provider_code, product_id, variant_id, placement, "png");
+        let info_key = Self::cache_key(provider_code, product_id, variant_id, placement, "json");
+
+        if let Some(ref r2) = self.r2_client {
+            match Self::load_cached(r2, &image_key, &info_key).await {
+                Ok(Some((info, image))) => {
+                    debug!(key = %image_key, "On-demand template served from R2 cache");
+                    return Ok(ResolvedTemplate {
+                        metadata: Self::metadata(&template_id, placement, &info),
+                        images: TemplateImages::from_base(image),
+                        source: TemplateSource::R2Cache,
+                        source_url: info.source_url,
+                        r2_key: Some(image_key),
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => warn!(key = %image_key, error = %e, "Failed to read cached template"),
+            }
+        }
+
+        if !fetch_on_demand {
+            return Err(OnDemandError::NotCached(template_id));
+        }
+
+        let asset = self
+            .fetch_mockup_asset(provider_code, product_id, variant_id, placement)
+            .await?
+            .ok_or_else(|| OnDemandError::NoMockupTemplate(template_id.clone()))?;
+        let Some(print_area) = asset.template_print_area else {
+            return Err(OnDemandError::NoMockupTemplate(template_id));
+        };
+
+        let (bytes, content_type) = self.download(&asset.source_url).await?;
+        let image = decode(bytes.clone()).await?;
+
+        let info = CachedTemplateInfo {
+            source_url: asset.source_url.clone(),
+            width_px: image.width(),
+            height_px: image.height(),
+            print_area: scale_print_area(
+                print_area,
+                (asset.width_px, asset.height_px),
+                (image.width(), image.height()),
+            ),
+        };
+
+        let mut r2_key = None;
+        if let Some(ref r2) = self.r2_client {
+            let stored =
+                Self::store_cached(r2, &image_key, &info_key, bytes, &content_type, &info).await;
+            match stored {
+                Ok(()) => r2_key = Some(image_key),
+                Err(e) => warn!(key = %image_key, error = %e, "Failed to cache template in R2"),
+            }
+        }
+
+        info!(
+            template_id = %template_id,
+            source_url = %info.source_url,
+            cached = r2_key.is_some(),
+            "Fetched mockup template on demand"
+        );
+
+        Ok(ResolvedTemplate {
+            metadata: Self::metadata(&template_id, placement, &info),
+            images: TemplateImages::from_base(image),
+            source: TemplateSource::Provider,
+            source_url: info.source_url,
+            r2_key,
+        })
+    }
+
+    fn cache_key(
+        provider_code: &str,
+        product_id: &str,
+        variant_id: Option<&str>,
+        placement: &PrintPlacement,
+        extension: &str,
+    ) -> String {
+        let filename = format!(
+            "{}{}.{}",
+            ON_DEMAND_FILE_PREFIX,
+            placement.as_str(),
+            extension
+        );
+        AssetPath::mockup_template(
+            provider_code,
+            product_id,
+            variant_id,
+            placement.clone(),
+            &filename,
+        )
+        .to_key()
+    }
+
+    fn metadata(
+        template_id: &str,
+        placement: &PrintPlacement,
+        info: &CachedTemplateInfo,
+    ) -> TemplateMetadata {
+        TemplateMetadata::from_provider_mockup(
+            template_id,
+            placement.as_str(),
+            TemplateDimensions {
+                width: info.width_px,
+                height: info.height_px,
+            },
+            PrintArea {
+                x: info.print_area.left,
+                y: info.print_area.top,
+                width: info.print_area.width,
+                height: info.print_area.height,
+            },
+        )
+    }
+
+    /// Cached sidecar and decoded image, `None` on a cache miss
+    async fn load_cached(
+        r2: &R2Client,
+        image_key: &str,
+        info_key: &str,
+    ) -> Result<Option<(CachedTemplateInfo, DynamicImage)>, OnDemandError> {
+        let info = match r2.download(info_key).await {
+            Ok(data) => serde_json::from_slice::<CachedTemplateInfo>(&data)
+                .map_err(|e| OnDemandError::Image(format!("Invalid template sidecar: {}", e)))?,
+            Err(R2Error::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let data = match r2.download(image_key).await {
+            Ok(data) => data,
+            Err(R2Error::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Some((info, decode(data).await?)))
+    }
+
+    /// Upload the image first so a sidecar never points at a missing image
+    async fn store_cached(
+        r2: &R2Client,
+        image_key: &str,
+        info_key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        info: &CachedTemplateInfo,
+    ) -> Result<(), OnDemandError> {
+        r2.upload_key(image_key, data, content_type).await?;
+        let sidecar = serde_json::to_vec(info)
+            .map_err(|e| OnDemandError::Image(format!("Failed to encode sidecar: {}", e)))?;
+        r2.upload_key(info_key, sidecar, "application/json").await?;
+        Ok(())
+    }
+
+    /// Ask the provider for the variant's mockup template for a placement
+    async fn fetch_mockup_asset(
+        &self,
+        provider_code: &str,
+        product_id: &str,
+        variant_id: Option<&str>,
+        placement: &PrintPlacement,
+    ) -> Result<Option<MockupAsset>, OnDemandError> {
+        let credentials = ProviderCredentials::from_env(provider_code);
+        let mut provider = ProviderFactory::create(provider_code, credentials)
+            .ok_or_else(|| OnDemandError::ProviderNotFound(provider_code.to_string()))?;
+        provider.authenticate().await?;
+
+        let assets = provider.get_mockup_urls(product_id, variant_id).await?;
+        Ok(select_template(assets, variant_id, placement))
+    }
+
+    /// Download a template image of at most `MAX_TEMPLATE_BYTES`
+    async fn download(&self, url: &str) -> Result<(Vec<u8>, String), OnDemandError> {
+        let fetch_error = |e: reqwest::Error| match UrlGuardError::from_reqwest(&e) {
+            Some(guard) => OnDemandError::Download(format!("URL not allowed: {}", guard)),
+            None => OnDemandError::Download(e.to_string()),
+        };
+        let too_large = |bytes: u64| {
+            OnDemandError::Download(format!(
+                "Template image is {} bytes (max {})",
+                bytes, MAX_TEMPLATE_BYTES
+            ))
+        };
+
+        self.urls
+            .check(url)
+            .await
+            .map_err(|e| OnDemandError::Download(format!("URL not allowed: {}", e)))?;
+        let mut response = self
+            .urls
+            .client()
+            .get(url)
+            .send()
+            .await
+            .map_err(fetch_error)?;
+
+        if !response.status().is_success() {
+            return Err(OnDemandError::Download(format!(
+                "HTTP {} from {}",
+                response.status(),
+                url
+            )));
+        }
+
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("image/png")
+            .to_string();
+
+        if let Some(content_length) = response.content_length() {
+            if content_length > MAX_TEMPLATE_BYTES as u64 {
+                return Err(too_large(content_length));
+            }
+        }
+
+        // Content-Length may be missing or wrong, so count while streaming too
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(fetch_error)? {
+            let received = data.len() + chunk.len();
+            if received > MAX_TEMPLATE_BYTES {
+                return Err(too_large(received as u64));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok((data, content_type))
+    }
+}
+
+async fn decode(data: Vec<u8>) -> Result<DynamicImage, OnDemandError> {
+    tokio::task::spawn_blocking(move || image::load_from_memory(&data))
+        .await
+        .map_err(|e| OnDemandError::Image(format!("Task join error: {}", e)))?
+        .map_err(|e| OnDemandError::Image(e.to_string()))
+}
+
+/// Pick the best mockup template: right placement and a print area, preferring
+/// one tied to the requested variant
+fn select_template(
+    assets: Vec<MockupAsset>,
+    variant_id: Option<&str>,
+    placement: &PrintPlacement,
+) -> Option<MockupAsset> {
+    let mut candidates: Vec<MockupAsset> = assets
+        .into_iter()
+        .filter(|a| a.asset_type == AssetType::MockupTemplate)
+        .filter(|a| a.template_print_area.is_some())
+        .filter(|a| a.placement.as_ref().map_or(true, |p| p == placement))
+        .collect();
+
+    let variant_match = candidates
+        .iter()
+        .position(|a| variant_id.is_some() && a.variant_external_id.as_deref() == variant_id);
+    match variant_match {
+        Some(index) => Some(candidates.swap_remove(index)),
+        None => candidates.into_iter().next(),
+    }
+}
+
+/// Scale a print area given against the provider's reference size onto the
+/// downloaded image, clamped to the image bounds
+fn scale_print_area(
+    area: TemplatePrintArea,
+    reference: (Option<i32>, Option<i32>),
+    actual: (u32, u32),
+) -> TemplatePrintArea {
+    let scale = |value: i32, reference: Option<i32>, actual: u32| -> i32 {
+        match reference {
+            Some(reference) if reference > 0 => {
+                (value as f64 * actual as f64 / reference as f64).round() as i32
+            }
+            _ => value,
+        }
+    };
+
+    let (width_px, height_px) = (actual.0 as i32, actual.1 as i32);
+    let left = scale(area.left, reference.0, actual.0).clamp(0, width_px);
+    let top = scale(area.top, reference.1, actual.1).clamp(0, height_px);
+
+    TemplatePrintArea {
+        left,
+        top,
+        width: scale(area.width, reference.0, actual.0).clamp(0, width_px - left),
+        height: scale(area.height, reference.1, actual.1).clamp(0, height_px - top),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(left: i32, top: i32, width: i32, height: i32) -> TemplatePrintArea {
+        TemplatePrintArea {
+            left,
+            top,
+            width,
+            height,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_refuses_internal_urls() {
+        let templates = OnDemandTemplates::new(None);
+        for url in [
+            "http://127.0.0.1/template.png",
+            "http://169.254.169.254/latest/meta-data/",
+            "file:///etc/passwd",
+        ] {
+            match templates.download(url).await {
+                Err(OnDemandError::Download(message)) => {
+                    assert!(message.starts_with("URL not allowed"), "{url}: {message}")
+                }
+                other => panic!("{url}: {:?}", other.map(|(data, _)| data.len())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_scale_print_area() {
+        // Provider reference 1000x1000, downloaded image 2000x2000
+        let scaled = scale_print_area(
+            area(250, 200, 500, 600),
+            (Some(1000), Some(1000)),
+            (2000, 2000),
+        );
+        assert_eq!(scaled, area(500, 400, 1000, 1200));
+
+        // Unknown reference size keeps the rect, clamped to the image
+        let clamped = scale_print_area(area(100, 100, 2000, 50), (None, None), (800, 600));
+        assert_eq!(clamped, area(100, 100, 700, 50));
+    }
+
+    #[test]
+    fn test_select_template_prefers_variant() {
+        let mut generic = MockupAsset::new(
+            AssetType::MockupTemplate,
+            "https://a/generic.png".to_string(),
+        );
+        generic.placement = Some(PrintPlacement::Front);
+        generic.template_print_area = Some(area(0, 0, 10, 10));
+
+        let mut variant = generic.clone();
+        variant.source_url = "https://a/variant.png".to_string();
+        variant.variant_external_id = Some("42".to_string());
+
+        let mut back = generic.clone();
+        back.placement = Some(PrintPlacement::Back);
+
+        let selected = select_template(
+            vec![back.clone(), generic.clone(), variant],
+            Some("42"),
+            &PrintPlacement::Front,
+        )
+        .unwrap();
+        assert_eq!(selected.source_url, "https://a/variant.png");
+
+        assert!(select_template(vec![back], None, &PrintPlacement::Front).is_none());
+    }
+}
@@ -1,21 +1,35 @@
 //! Sync orchestrator for POD catalog synchronization
 //!
 //! Manages sync jobs, tracks progress, and coordinates between providers,
-//! database, and R2 storage.
+//! database, and asset storage.
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use thiserror::Error;
+use tokio::sync::{Notify, Semaphore};
 use tracing::{debug, error, info, instrument, warn};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::db::DbPool;
-use crate::domain::catalog::{MockupAsset, UnifiedProduct};
+use crate::db::catalog::sync_hash;
+use crate::db::{CatalogRepository, DbPool, StoredProduct};
+use crate::domain::catalog::UnifiedProduct;
+use crate::metrics::Metrics;
+use crate::net::UrlPolicy;
 use crate::providers::{PodProvider, ProviderCredentials, ProviderError, ProviderFactory};
-use crate::storage::R2Client;
+use crate::storage::StorageBackend;
 
-use super::asset_sync::{AssetSyncError, AssetSyncer, BatchSyncResult};
+use super::asset_cleanup::{find_orphans, provider_prefix, CleanupSummary};
+use super::asset_sync::{AssetStatusStore, AssetSyncError, AssetSyncer};
+use super::catalog_store::{CatalogStore, MemoryCatalogStore};
+use super::job_store::{MemorySyncJobStore, PgSyncJobStore, SyncJobStore};
+
+/// Active jobs without a heartbeat for this long are failed so the provider can sync again
+pub const STALE_JOB_TIMEOUT: Duration = Duration::from_secs(15 * 60);
 
 /// Errors that can occur during sync orchestration
 #[derive(Error, Debug)]
@@ -38,12 +52,21 @@ pub enum SyncOrchestratorError {
     #[error("Job not found: {0}")]
     JobNotFound(Uuid),
 
+    #[error("Job {0} already finished with status {1}")]
+    JobFinished(Uuid, SyncJobStatus),
+
     #[error("Job already running for provider: {0}")]
     JobAlreadyRunning(String),
+
+    #[error("Single product sync requires a product ID")]
+    MissingProductId,
+
+    #[error("'{0}' names no provider asset folder that can be cleaned up")]
+    ProtectedPrefix(String),
 }
 
 /// Type of sync job
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum SyncJobType {
     /// Full catalog sync from provider
@@ -54,6 +77,8 @@ pub enum SyncJobType {
     AssetsOnly,
     /// Sync a single product
     SingleProduct,
+    /// Remove stored objects no asset points at
+    AssetCleanup,
 }
 
 impl std::fmt::Display for SyncJobType {
@@ -63,12 +88,26 @@ impl std::fmt::Display for SyncJobType {
             SyncJobType::Incremental => write!(f, "incremental"),
             SyncJobType::AssetsOnly => write!(f, "assets_only"),
             SyncJobType::SingleProduct => write!(f, "single_product"),
+            SyncJobType::AssetCleanup => write!(f, "asset_cleanup"),
+        }
+    }
+}
+
+impl SyncJobType {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "full_catalog" => Some(SyncJobType::FullCatalog),
+            "incremental" => Some(SyncJobType::Incremental),
+            "assets_only" => Some(SyncJobType::AssetsOnly),
+            "single_product" => Some(SyncJobType::SingleProduct),
+            "asset_cleanup" => Some(SyncJobType::AssetCleanup),
+            _ => None,
         }
     }
 }
 
 /// Status of a sync job
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum SyncJobStatus {
     /// Job is pending (not yet started)
@@ -95,8 +134,21 @@ impl std::fmt::Display for SyncJobStatus {
     }
 }
 
+impl SyncJobStatus {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(SyncJobStatus::Pending),
+            "running" => Some(SyncJobStatus::Running),
+            "completed" => Some(SyncJobStatus::Completed),
+            "failed" => Some(SyncJobStatus::Failed),
+            "cancelled" => Some(SyncJobStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
 /// Represents a sync job
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SyncJob {
     /// Unique job ID
     pub id: Uuid,
@@ -112,6 +164,9 @@ pub struct SyncJob {
     pub processed_items: u32,
     /// Items that failed
     pub failed_items: u32,
+    /// Unchanged items an incremental sync left alone
+    #[serde(default)]
+    pub skipped_items: u32,
     /// When the job was created
     pub created_at: DateTime<Utc>,
     /// When the job started running
@@ -122,6 +177,18 @@ pub struct SyncJob {
     pub error_message: Option<String>,
     /// Optional: specific product ID for SingleProduct jobs
     pub product_id: Option<String>,
+    /// Umbrella job this job was scheduled under, if any
+    #[serde(default)]
+    pub parent_job_id: Option<Uuid>,
+    /// Next catalog page to fetch; lets an interrupted job be resumed
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Last time the worker running this job reported in
+    #[serde(default)]
+    pub heartbeat_at: Option<DateTime<Utc>>,
+    /// What an asset cleanup job found and removed
+    #[serde(default)]
+    pub cleanup: Option<CleanupSummary>,
 }
 
 impl SyncJob {
@@ -135,14 +202,70 @@ impl SyncJob {
             total_items: 0,
             processed_items: 0,
             failed_items: 0,
+            skipped_items: 0,
             created_at: Utc::now(),
             started_at: None,
             completed_at: None,
             error_message: None,
             product_id: None,
+            parent_job_id: None,
+            cursor: None,
+            heartbeat_at: None,
+            cleanup: None,
         }
     }
 
+    /// Create a pending job syncing one product by its provider ID
+    pub fn single_product(provider_code: &str, product_id: &str) -> Self {
+        let mut job = Self::new(provider_code, SyncJobType::SingleProduct);
+        job.product_id = Some(product_id.to_string());
+        job
+    }
+
+    /// Create a pending job removing a provider's orphaned stored objects
+    ///
+    /// A dry run only counts the orphans.
+    pub fn asset_cleanup(provider_code: &str, dry_run: bool) -> Self {
+        let mut job = Self::new(provider_code, SyncJobType::AssetCleanup);
+        job.cleanup = Some(CleanupSummary {
+            dry_run,
+            ..Default::default()
+        });
+        job
+    }
+
+    /// Continue from where an interrupted job stopped, keeping its counters
+    pub fn resume_from(mut self, previous: &SyncJob) -> Self {
+        self.cursor = previous.cursor.clone();
+        self.total_items = previous.total_items;
+        self.processed_items = previous.processed_items;
+        self.failed_items = previous.failed_items;
+        self.skipped_items = previous.skipped_items;
+        self
+    }
+
+    /// Whether this job stopped partway through and can be resumed
+    pub fn is_resumable(&self) -> bool {
+        matches!(
+            self.status,
+            SyncJobStatus::Failed | SyncJobStatus::Cancelled
+        ) && self.cursor.is_some()
+    }
+
+    /// Attach this job to an umbrella job
+    pub fn with_parent(mut self, parent_job_id: Uuid) -> Self {
+        self.parent_job_id = Some(parent_job_id);
+        self
+    }
+
+    /// Whether the job has reached a final state
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            SyncJobStatus::Completed | SyncJobStatus::Failed | SyncJobStatus::Cancelled
+        )
+    }
+
     /// Mark the job as started
     pub fn start(&mut self) {
         self.status = SyncJobStatus::Running;
@@ -164,6 +287,11 @@ impl SyncJob {
         self.failed_items += 1;
     }
 
+    /// Increment skipped count
+    pub fn increment_skipped(&mut self) {
+        self.skipped_items += 1;
+    }
+
     /// Mark the job as completed
     pub fn complete(&mut self) {
         self.status = SyncJobStatus::Completed;
@@ -177,12 +305,18 @@ impl SyncJob {
         self.error_message = Some(error.to_string());
     }
 
-    /// Get progress percentage
+    /// Mark the job as cancelled
+    pub fn cancel(&mut self) {
+        self.status = SyncJobStatus::Cancelled;
+        self.completed_at = Some(Utc::now());
+    }
+
+    /// Get progress percentage; skipped items count as done
     pub fn progress(&self) -> f32 {
         if self.total_items == 0 {
             0.0
         } else {
-            (self.processed_items as f32 / self.total_items as f32) * 100.0
+            ((self.processed_items + self.skipped_items) as f32 / self.total_items as f32) * 100.0
         }
     }
 
@@ -197,111 +331,483 @@ impl SyncJob {
 /// Sync progress callback
 pub type ProgressCallback = Box<dyn Fn(&SyncJob) + Send + Sync>;
 
+/// Builds the provider client for a provider code
+type ProviderBuilder = Arc<dyn Fn(&str) -> Option<Box<dyn PodProvider>> + Send + Sync>;
+
+/// What syncing one product did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProductSync {
+    /// Stored, with its assets mirrored
+    Synced,
+    /// Unchanged since the last sync, left alone by an incremental sync
+    Skipped,
+}
+
+/// Cancellation flag for a job running in this process
+#[derive(Default)]
+struct CancelSignal {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelSignal {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the job is cancelled
+    async fn cancelled(&self) {
+        // Register for the wakeup before checking, so a cancel in between isn't missed
+        let notified = self.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Removes a job's cancel signal once its worker finishes or is dropped
+struct RunningJob<'a> {
+    running: &'a Mutex<HashMap<Uuid, Arc<CancelSignal>>>,
+    id: Uuid,
+}
+
+impl Drop for RunningJob<'_> {
+    fn drop(&mut self) {
+        self.running.lock().unwrap().remove(&self.id);
+    }
+}
+
 /// Sync orchestrator for managing catalog synchronization
 pub struct SyncOrchestrator {
-    db_pool: Option<DbPool>,
-    r2_client: Option<R2Client>,
-    /// Active jobs by provider code
-    active_jobs: std::sync::RwLock<std::collections::HashMap<String, SyncJob>>,
+    /// Synced products, in the database when one is configured
+    catalog: Arc<dyn CatalogStore>,
+    /// Mirroring progress of product assets, stored alongside the products
+    assets: Arc<dyn AssetStatusStore>,
+    /// Provider clients, built from environment credentials
+    providers: ProviderBuilder,
+    /// Where assets are mirrored, when storage is configured
+    storage: Option<Arc<dyn StorageBackend>>,
+    /// Where orphaned assets are removed from; only with a database, since
+    /// the memory store doesn't know assets mirrored by earlier runs
+    asset_bucket: Option<Arc<dyn StorageBackend>>,
+    /// Job records, shared with the sync handlers
+    jobs: Arc<dyn SyncJobStore>,
+    /// Asset download permits shared by every provider sync
+    asset_limiter: Option<Arc<Semaphore>>,
+    /// Attempts per asset before it is recorded as failed
+    max_asset_attempts: Option<u32>,
+    /// Store identical asset content once
+    dedup_assets: bool,
+    /// Longest side of asset thumbnails, when they are made
+    thumbnail_max_dimension: Option<u32>,
+    /// Where asset source URLs may be fetched from
+    url_policy: UrlPolicy,
+    /// Cancel signals of the jobs this process is running
+    running: Mutex<HashMap<Uuid, Arc<CancelSignal>>>,
+    /// Where product outcomes and asset timings are recorded
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl SyncOrchestrator {
     /// Create a new sync orchestrator
-    pub fn new(db_pool: Option<DbPool>, r2_client: Option<R2Client>) -> Self {
+    ///
+    /// Jobs and synced products are stored in the database when one is
+    /// configured, otherwise in memory.
+    pub fn new(db_pool: Option<DbPool>, storage: Option<Arc<dyn StorageBackend>>) -> Self {
+        let asset_bucket = db_pool.as_ref().and(storage.clone());
+        let (jobs, catalog, assets): (
+            Arc<dyn SyncJobStore>,
+            Arc<dyn CatalogStore>,
+            Arc<dyn AssetStatusStore>,
+        ) = match db_pool {
+            Some(pool) => {
+                let repository = Arc::new(CatalogRepository::new(pool.clone()));
+                (
+                    Arc::new(PgSyncJobStore::new(pool)),
+                    repository.clone(),
+                    repository,
+                )
+            }
+            None => {
+                let store = Arc::new(MemoryCatalogStore::default());
+                (
+                    Arc::new(MemorySyncJobStore::default()),
+                    store.clone(),
+                    store,
+                )
+            }
+        };
+
         Self {
-            db_pool,
-            r2_client,
-            active_jobs: std::sync::RwLock::new(std::collections::HashMap::new()),
+            catalog,
+            assets,
+            providers: Arc::new(|code: &str| {
+                ProviderFactory::create(code, ProviderCredentials::from_env(code))
+            }),
+            storage,
+            asset_bucket,
+            jobs,
+            asset_limiter: None,
+            max_asset_attempts: None,
+            dedup_assets: false,
+            thumbnail_max_dimension: None,
+            url_policy: UrlPolicy::default(),
+            running: Mutex::new(HashMap::new()),
+            metrics: None,
         }
     }
 
-    /// Check if a sync job is running for a provider
-    pub fn is_running(&self, provider_code: &str) -> bool {
-        let jobs = self.active_jobs.read().unwrap();
-        jobs.get(provider_code)
-            .map(|j| j.status == SyncJobStatus::Running)
-            .unwrap_or(false)
+    /// Share one pool of asset download permits across all provider syncs
+    pub fn with_asset_limit(mut self, max_concurrent_assets: usize) -> Self {
+        self.asset_limiter = Some(Arc::new(Semaphore::new(max_concurrent_assets.max(1))));
+        self
+    }
+
+    /// Attempt each asset up to `max_attempts` times before recording it as failed
+    pub fn with_asset_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_asset_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Point assets with identical bytes at one content-addressed object
+    pub fn with_asset_dedup(mut self, enabled: bool) -> Self {
+        self.dedup_assets = enabled;
+        self
     }
 
-    /// Get the current job for a provider
-    pub fn get_job(&self, provider_code: &str) -> Option<SyncJob> {
-        let jobs = self.active_jobs.read().unwrap();
-        jobs.get(provider_code).cloned()
+    /// Store a WebP thumbnail, at most `max_dimension` on a side, of each large asset
+    pub fn with_asset_thumbnails(mut self, enabled: bool, max_dimension: u32) -> Self {
+        self.thumbnail_max_dimension = enabled.then_some(max_dimension);
+        self
     }
 
-    /// Get all jobs
-    pub fn get_all_jobs(&self) -> Vec<SyncJob> {
-        let jobs = self.active_jobs.read().unwrap();
-        jobs.values().cloned().collect()
+    /// Limit asset downloads to the hosts `policy` allows
+    pub fn with_url_policy(mut self, policy: UrlPolicy) -> Self {
+        self.url_policy = policy;
+        self
     }
 
-    /// Start a full catalog sync for a provider
-    #[instrument(skip(self, on_progress))]
-    pub async fn start_full_sync(
+    /// Record product outcomes and asset mirroring timings in `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Count a product as `processed`, `skipped`, or `failed`
+    fn record_product(&self, provider_code: &str, outcome: &str) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_sync_product(provider_code, outcome);
+        }
+    }
+
+    /// Asset syncer recording progress in the asset store, if storage is configured
+    fn asset_syncer(&self) -> Option<AssetSyncer> {
+        let storage = self.storage.as_ref()?;
+        let mut syncer = AssetSyncer::new(storage.clone())
+            .with_concurrency(5)
+            .with_skip_existing(true)
+            .with_status_store(self.assets.clone())
+            .with_dedup(self.dedup_assets)
+            .with_url_policy(self.url_policy.clone());
+        if let Some(ref limiter) = self.asset_limiter {
+            syncer = syncer.with_shared_limiter(limiter.clone());
+        }
+        if let Some(max_attempts) = self.max_asset_attempts {
+            syncer = syncer.with_max_attempts(max_attempts);
+        }
+        if let Some(max_dimension) = self.thumbnail_max_dimension {
+            syncer = syncer
+                .with_thumbnails(true)
+                .with_thumbnail_max_dimension(max_dimension);
+        }
+        if let Some(ref metrics) = self.metrics {
+            syncer = syncer.with_metrics(metrics.clone());
+        }
+        Some(syncer)
+    }
+
+    /// Build provider clients with `providers` instead of from the environment
+    #[cfg(test)]
+    fn with_providers(
+        mut self,
+        providers: impl Fn(&str) -> Option<Box<dyn PodProvider>> + Send + Sync + 'static,
+    ) -> Self {
+        self.providers = Arc::new(providers);
+        self
+    }
+
+    /// Store synced products in `catalog` instead of the default store
+    #[cfg(test)]
+    fn with_catalog(mut self, catalog: Arc<dyn CatalogStore>) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    /// Record asset progress in `assets` instead of the default store
+    #[cfg(test)]
+    fn with_assets(mut self, assets: Arc<dyn AssetStatusStore>) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    /// Clean up orphaned assets in `bucket`
+    #[cfg(test)]
+    fn with_asset_bucket(mut self, bucket: Arc<dyn StorageBackend>) -> Self {
+        self.asset_bucket = Some(bucket);
+        self
+    }
+
+    /// The store this orchestrator records jobs in
+    pub fn job_store(&self) -> Arc<dyn SyncJobStore> {
+        self.jobs.clone()
+    }
+
+    /// Get a job by ID
+    pub async fn get_job(&self, id: Uuid) -> Result<Option<SyncJob>, SyncOrchestratorError> {
+        self.jobs.get(id).await
+    }
+
+    /// Record a job as pending, reserving its provider
+    ///
+    /// Jobs whose worker stopped sending heartbeats are failed first, so a
+    /// crashed sync does not block the provider forever.
+    pub async fn claim(&self, job: &SyncJob) -> Result<(), SyncOrchestratorError> {
+        for stale in self.jobs.fail_stale(STALE_JOB_TIMEOUT).await? {
+            warn!(
+                job_id = %stale.id,
+                provider = %stale.provider_code,
+                "Failed sync job with no recent heartbeat"
+            );
+        }
+        self.jobs.create(job).await
+    }
+
+    /// Claim and run a full catalog sync for a job created by the caller
+    ///
+    /// Lets schedulers hand out job IDs before the sync actually starts.
+    pub async fn run_full_sync(
         &self,
-        provider_code: &str,
+        job: SyncJob,
         on_progress: Option<ProgressCallback>,
     ) -> Result<SyncJob, SyncOrchestratorError> {
-        // Check if already running
-        if self.is_running(provider_code) {
-            return Err(SyncOrchestratorError::JobAlreadyRunning(
+        self.claim(&job).await?;
+        self.run_claimed(job, on_progress).await
+    }
+
+    /// Sync one product and its assets, recording a single product job
+    ///
+    /// Runs to completion before returning. A sync that fails, for example
+    /// because the provider doesn't know the product, returns the failed job
+    /// with the provider's error in `error_message`.
+    pub async fn start_single_product_sync(
+        &self,
+        provider_code: &str,
+        external_product_id: &str,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        let job = SyncJob::single_product(provider_code, external_product_id);
+        let id = job.id;
+        self.claim(&job).await?;
+
+        match self.run_claimed(job, None).await {
+            Ok(job) => Ok(job),
+            Err(e) => {
+                warn!(job_id = %id, error = %e, "Single product sync failed");
+                self.jobs.get(id).await?.ok_or(e)
+            }
+        }
+    }
+
+    /// Remove a provider's stored objects that no asset row points at
+    ///
+    /// Lists the keys under `{provider}/` page by page and deletes those
+    /// without an asset row, or with `dry_run` only counts them. Claiming the
+    /// provider keeps syncs, which upload before recording their assets, from
+    /// running meanwhile. Runs to completion before returning; a cleanup that
+    /// fails partway returns the failed job with what it removed so far.
+    pub async fn cleanup_orphaned_assets(
+        &self,
+        provider_code: &str,
+        dry_run: bool,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        if provider_prefix(provider_code).is_none() {
+            return Err(SyncOrchestratorError::ProtectedPrefix(
                 provider_code.to_string(),
             ));
         }
+        let job = SyncJob::asset_cleanup(provider_code, dry_run);
+        let id = job.id;
+        self.claim(&job).await?;
+
+        match self.run_claimed(job, None).await {
+            Ok(job) => Ok(job),
+            Err(e) => {
+                warn!(job_id = %id, error = %e, "Asset cleanup failed");
+                self.jobs.get(id).await?.ok_or(e)
+            }
+        }
+    }
+
+    /// Run a sync for a job already recorded by `claim`
+    ///
+    /// Single product jobs sync just the job's `product_id`, assets-only
+    /// jobs retry the provider's failed asset downloads, and asset cleanup
+    /// jobs remove the provider's orphaned stored objects. Catalog syncs
+    /// start at the job's cursor, so a job created with `resume_from` skips
+    /// the pages its predecessor finished; incremental ones also skip products
+    /// that haven't changed since they were last synced. Stops early if the
+    /// stored job is cancelled or failed by someone else; a cancel through
+    /// `cancel_job` also interrupts the product being synced.
+    #[instrument(skip(self, job, on_progress), fields(job_id = %job.id, provider = %job.provider_code))]
+    pub async fn run_claimed(
+        &self,
+        job: SyncJob,
+        on_progress: Option<ProgressCallback>,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        let signal = Arc::new(CancelSignal::default());
+        self.running.lock().unwrap().insert(job.id, signal.clone());
+        let _running = RunningJob {
+            running: &self.running,
+            id: job.id,
+        };
+
+        self.run_job(job, &signal, on_progress).await
+    }
+
+    async fn run_job(
+        &self,
+        mut job: SyncJob,
+        signal: &CancelSignal,
+        on_progress: Option<ProgressCallback>,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        let provider_code = job.provider_code.clone();
+        let provider_code = provider_code.as_str();
 
-        // Create new job
-        let mut job = SyncJob::new(provider_code, SyncJobType::FullCatalog);
         job.start();
+        if !self.save(&job).await {
+            return self.stopped(job).await;
+        }
 
-        // Store job
-        {
-            let mut jobs = self.active_jobs.write().unwrap();
-            jobs.insert(provider_code.to_string(), job.clone());
+        // Failed assets are fetched from their stored source URLs, without the provider
+        if job.job_type == SyncJobType::AssetsOnly {
+            return self.run_failed_assets(job, signal, on_progress).await;
+        }
+        if job.job_type == SyncJobType::AssetCleanup {
+            return self.run_cleanup(job, signal, on_progress).await;
         }
 
-        // Get provider credentials and create provider
-        let credentials = ProviderCredentials::from_env(provider_code);
-        let mut provider = ProviderFactory::create(provider_code, credentials)
-            .ok_or_else(|| SyncOrchestratorError::ProviderNotFound(provider_code.to_string()))?;
+        // Create the provider from its credentials
+        let mut provider = match (self.providers)(provider_code) {
+            Some(provider) => provider,
+            None => {
+                let err = SyncOrchestratorError::ProviderNotFound(provider_code.to_string());
+                job.fail(&err.to_string());
+                self.save(&job).await;
+                return Err(err);
+            }
+        };
+
+        // A rate limit stored for the provider replaces the client's built-in one
+        match self.catalog.provider_rate_limit(provider_code).await {
+            Ok(Some(limit)) => provider.set_rate_limit(limit),
+            Ok(None) => {}
+            Err(e) => warn!(
+                "Using the default rate limit for {}; stored limit unavailable: {}",
+                provider_code, e
+            ),
+        }
 
         // Authenticate
-        provider.authenticate().await?;
+        if let Err(e) = provider.authenticate().await {
+            job.fail(&e.to_string());
+            self.save(&job).await;
+            return Err(e.into());
+        }
 
-        info!("Starting full catalog sync for {}", provider_code);
+        if job.job_type == SyncJobType::SingleProduct {
+            return self
+                .run_single_product(job, &*provider, signal, on_progress)
+                .await;
+        }
 
-        // Sync products in pages
-        let mut page = 1;
+        // Sync products in pages, starting from the cursor when resuming
+        let first_page: u32 = job
+            .cursor
+            .as_deref()
+            .and_then(|cursor| cursor.parse().ok())
+            .unwrap_or(1);
+        let mut page = first_page;
         let per_page = 50;
-        let mut total_products = 0;
+        let incremental = job.job_type == SyncJobType::Incremental;
+
+        info!(
+            "Starting {} sync for {} at page {}",
+            job.job_type, provider_code, first_page
+        );
 
         loop {
+            if signal.is_cancelled() {
+                return self.stopped(job).await;
+            }
+
             match provider.get_products(page, per_page).await {
                 Ok(catalog_page) => {
                     let products = catalog_page.items;
                     let has_more = catalog_page.has_more;
 
-                    if page == 1 {
+                    if page == first_page {
                         // Set total from catalog page
                         job.set_total(catalog_page.total as u32);
-                        self.update_job(&job);
+                        if !self.save(&job).await {
+                            return self.stopped(job).await;
+                        }
                     }
 
                     for product in &products {
-                        // Process product
-                        match self.sync_product(provider_code, product, &*provider).await {
-                            Ok(_) => {
+                        if signal.is_cancelled() {
+                            return self.stopped(job).await;
+                        }
+
+                        // Process product, abandoning it if the job is cancelled meanwhile
+                        let sync =
+                            self.sync_product(provider_code, product, &*provider, incremental);
+                        let synced = tokio::select! {
+                            synced = sync => synced,
+                            _ = signal.cancelled() => return self.stopped(job).await,
+                        };
+                        match synced {
+                            Ok(ProductSync::Synced) => {
                                 job.increment_processed();
+                                self.record_product(provider_code, "processed");
+                            }
+                            Ok(ProductSync::Skipped) => {
+                                job.increment_skipped();
+                                self.record_product(provider_code, "skipped");
+                            }
+                            Err(e) if !provider.is_authenticated() => {
+                                self.record_product(provider_code, "failed");
+                                // Every later request would be rejected too
+                                error!("Lost authentication with {}: {}", provider_code, e);
+                                job.fail(&e.to_string());
+                                self.save(&job).await;
+                                return Err(e);
                             }
                             Err(e) => {
                                 warn!("Failed to sync product {}: {}", product.external_id, e);
                                 job.increment_failed();
+                                self.record_product(provider_code, "failed");
                             }
                         }
 
-                        total_products += 1;
-
                         // Update job and call progress callback
-                        self.update_job(&job);
+                        if !self.save(&job).await {
+                            return self.stopped(job).await;
+                        }
                         if let Some(ref callback) = on_progress {
                             callback(&job);
                         }
@@ -311,105 +817,575 @@ impl SyncOrchestrator {
                         break;
                     }
                     page += 1;
+                    job.cursor = Some(page.to_string());
+                    if !self.save(&job).await {
+                        return self.stopped(job).await;
+                    }
                 }
                 Err(e) => {
                     error!("Failed to get products page {}: {}", page, e);
                     job.fail(&e.to_string());
-                    self.update_job(&job);
+                    self.save(&job).await;
                     return Err(e.into());
                 }
             }
         }
 
         // Update final counts
-        job.set_total(total_products);
+        job.set_total(job.processed_items + job.failed_items + job.skipped_items);
+        job.cursor = None;
+        job.complete();
+        if !self.save(&job).await {
+            return self.stopped(job).await;
+        }
+        if let Err(e) = self.catalog.provider_synced(provider_code).await {
+            warn!(error = %e, "Failed to record provider sync time");
+        }
+
+        info!(
+            "Completed {} sync for {}: {} products ({} failed, {} unchanged)",
+            job.job_type, provider_code, job.processed_items, job.failed_items, job.skipped_items
+        );
+
+        Ok(job)
+    }
+
+    /// Sync the one product named by a single product job
+    async fn run_single_product(
+        &self,
+        mut job: SyncJob,
+        provider: &dyn PodProvider,
+        signal: &CancelSignal,
+        on_progress: Option<ProgressCallback>,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        let Some(product_id) = job.product_id.clone() else {
+            let err = SyncOrchestratorError::MissingProductId;
+            job.fail(&err.to_string());
+            self.save(&job).await;
+            return Err(err);
+        };
+
+        job.set_total(1);
+        if !self.save(&job).await {
+            return self.stopped(job).await;
+        }
+
+        let sync = async {
+            let product = provider.get_product(&product_id).await?;
+            self.sync_product(&job.provider_code, &product, provider, false)
+                .await
+        };
+        let result = tokio::select! {
+            result = sync => result,
+            _ = signal.cancelled() => return self.stopped(job).await,
+        };
+        if let Err(e) = result {
+            warn!("Failed to sync product {}: {}", product_id, e);
+            job.increment_failed();
+            self.record_product(&job.provider_code, "failed");
+            job.fail(&e.to_string());
+            self.save(&job).await;
+            return Err(e);
+        }
+
+        job.increment_processed();
+        self.record_product(&job.provider_code, "processed");
         job.complete();
-        self.update_job(&job);
+        if !self.save(&job).await {
+            return self.stopped(job).await;
+        }
+        if let Some(ref callback) = on_progress {
+            callback(&job);
+        }
 
         info!(
-            "Completed full sync for {}: {} products ({} failed)",
+            "Completed sync of product {} for {}",
+            product_id, job.provider_code
+        );
+        Ok(job)
+    }
+
+    /// Retry the provider's failed asset downloads that are under the retry cap
+    async fn run_failed_assets(
+        &self,
+        mut job: SyncJob,
+        signal: &CancelSignal,
+        on_progress: Option<ProgressCallback>,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        let Some(syncer) = self.asset_syncer() else {
+            let err = SyncOrchestratorError::StorageError("Storage is not configured".to_string());
+            job.fail(&err.to_string());
+            self.save(&job).await;
+            return Err(err);
+        };
+
+        let provider_code = job.provider_code.clone();
+        let result = tokio::select! {
+            result = syncer.retry_failed(&provider_code) => result,
+            _ = signal.cancelled() => return self.stopped(job).await,
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                job.fail(&e.to_string());
+                self.save(&job).await;
+                return Err(e.into());
+            }
+        };
+
+        job.set_total(result.results.len() as u32);
+        job.processed_items =
+            (result.success_count + result.skipped_count + result.deduplicated_count) as u32;
+        job.failed_items = result.failed_count as u32;
+        job.complete();
+        if !self.save(&job).await {
+            return self.stopped(job).await;
+        }
+        if let Some(ref callback) = on_progress {
+            callback(&job);
+        }
+
+        info!(
+            "Retried failed assets for {}: {} recovered, {} still failing",
             provider_code, job.processed_items, job.failed_items
         );
+        Ok(job)
+    }
 
+    /// Delete, or count in a dry run, the provider's stored objects no asset points at
+    ///
+    /// Only one page of keys is held at a time. Objects that fail to delete
+    /// are counted as failed and left for the next cleanup.
+    async fn run_cleanup(
+        &self,
+        mut job: SyncJob,
+        signal: &CancelSignal,
+        on_progress: Option<ProgressCallback>,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        let Some(bucket) = self.asset_bucket.clone() else {
+            let err = SyncOrchestratorError::StorageError(
+                "Asset cleanup needs storage and a database".to_string(),
+            );
+            job.fail(&err.to_string());
+            self.save(&job).await;
+            return Err(err);
+        };
+        let Some(prefix) = provider_prefix(&job.provider_code) else {
+            let err = SyncOrchestratorError::ProtectedPrefix(job.provider_code.clone());
+            job.fail(&err.to_string());
+            self.save(&job).await;
+            return Err(err);
+        };
+
+        // Jobs created without a summary get the harmless kind of cleanup
+        let dry_run = job.cleanup.map(|cleanup| cleanup.dry_run).unwrap_or(true);
+        let mut summary = CleanupSummary {
+            dry_run,
+            ..Default::default()
+        };
+        job.cleanup = Some(summary);
+
+        let mut token = None;
+        loop {
+            if signal.is_cancelled() {
+                return self.stopped(job).await;
+            }
+
+            let page = match bucket.list_page(&prefix, token.take()).await {
+                Ok(page) => page,
+                Err(e) => {
+                    let err = SyncOrchestratorError::StorageError(e.to_string());
+                    job.fail(&err.to_string());
+                    self.save(&job).await;
+                    return Err(err);
+                }
+            };
+            let scanned = page.objects.len();
+            let orphans = match find_orphans(&*self.assets, page.objects).await {
+                Ok(orphans) => orphans,
+                Err(e) => {
+                    job.fail(&e.to_string());
+                    self.save(&job).await;
+                    return Err(e.into());
+                }
+            };
+            summary.scanned += scanned as u64;
+            summary.orphaned += orphans.len() as u64;
+            job.set_total(summary.scanned as u32);
+            job.skipped_items += (scanned - orphans.len()) as u32;
+
+            for orphan in orphans {
+                if signal.is_cancelled() {
+                    job.cleanup = Some(summary);
+                    return self.stopped(job).await;
+                }
+                if !dry_run {
+                    if let Err(e) = bucket.delete(&orphan.key).await {
+                        warn!(key = %orphan.key, error = %e, "Failed to delete orphaned asset");
+                        job.increment_failed();
+                        continue;
+                    }
+                    summary.deleted += 1;
+                }
+                summary.bytes_reclaimed += orphan.size;
+                job.increment_processed();
+            }
+
+            job.cleanup = Some(summary);
+            if !self.save(&job).await {
+                return self.stopped(job).await;
+            }
+            if let Some(ref callback) = on_progress {
+                callback(&job);
+            }
+
+            match page.next_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+
+        job.complete();
+        if !self.save(&job).await {
+            return self.stopped(job).await;
+        }
+
+        info!(
+            "{} orphaned assets for {}: {} of {} objects orphaned, {} deleted, {} bytes reclaimed",
+            if dry_run { "Found" } else { "Cleaned up" },
+            job.provider_code,
+            summary.orphaned,
+            summary.scanned,
+            summary.deleted,
+            summary.bytes_reclaimed
+        );
         Ok(job)
     }
 
     /// Sync a single product and its assets
+    ///
+    /// The product is stored in the catalog first, together with a pending
+    /// row per asset when storage is configured to mirror them, so it shows up
+    /// even if its assets fail to mirror. A failure while storing leaves
+    /// nothing behind and fails the product. With `incremental`, a product
+    /// whose sync hash matches the stored one and whose assets are all
+    /// mirrored is skipped without asking the provider for its mockups.
     #[instrument(skip(self, product, provider))]
     async fn sync_product(
         &self,
         provider_code: &str,
         product: &UnifiedProduct,
         provider: &dyn PodProvider,
-    ) -> Result<(), SyncOrchestratorError> {
+        incremental: bool,
+    ) -> Result<ProductSync, SyncOrchestratorError> {
         debug!(
             "Syncing product: {} - {}",
             product.external_id, product.name
         );
 
+        if incremental
+            && self
+                .catalog
+                .skip_unchanged(provider_code, &product.external_id, &sync_hash(product))
+                .await?
+        {
+            debug!("Product {} unchanged since last sync", product.external_id);
+            return Ok(ProductSync::Skipped);
+        }
+
         // Get mockup URLs for the product
-        let mockup_assets = provider
-            .get_mockup_urls(&product.external_id, None)
-            .await
-            .unwrap_or_default();
+        let mockup_assets = match provider.get_mockup_urls(&product.external_id, None).await {
+            Ok(assets) => assets,
+            // Lost authentication fails the product rather than leaving it without mockups
+            Err(e @ ProviderError::AuthFailed(_)) => return Err(e.into()),
+            Err(_) => Vec::new(),
+        };
+
+        // Without a syncer nothing would ever move the rows out of pending
+        let syncer = self.asset_syncer();
+        let queued = if syncer.is_some() {
+            mockup_assets.as_slice()
+        } else {
+            &[]
+        };
+        let stored = self
+            .catalog
+            .store_product(provider_code, product, queued)
+            .await?;
+        if let StoredProduct::Unchanged(_) = stored {
+            debug!("Product {} unchanged since last sync", product.external_id);
+        }
 
         if mockup_assets.is_empty() {
             debug!("No mockup assets for product {}", product.external_id);
-            return Ok(());
+            return Ok(ProductSync::Synced);
         }
 
-        // Sync assets to R2 if R2 client is configured
-        if let Some(ref r2_client) = self.r2_client {
-            let syncer = AssetSyncer::new(r2_client.clone())
-                .with_concurrency(5)
-                .with_skip_existing(true);
-
+        // Sync assets if storage is configured; the syncer records their progress
+        if let Some(syncer) = syncer {
             let result = syncer
                 .sync_product_assets(provider_code, &product.external_id, mockup_assets)
                 .await;
 
             debug!(
-                "Synced {} assets for product {} ({} failed, {} skipped)",
+                "Synced {} assets for product {} ({} failed, {} skipped, {} deduplicated)",
                 result.success_count,
                 product.external_id,
                 result.failed_count,
-                result.skipped_count
+                result.skipped_count,
+                result.deduplicated_count
             );
         }
 
-        // TODO: Store product in database
-        // if let Some(ref pool) = self.db_pool {
-        //     // Insert/update product in pod_products table
-        // }
-
-        Ok(())
+        Ok(ProductSync::Synced)
     }
 
-    /// Update job in storage
-    fn update_job(&self, job: &SyncJob) {
-        let mut jobs = self.active_jobs.write().unwrap();
-        jobs.insert(job.provider_code.clone(), job.clone());
+    /// Save job progress and refresh its heartbeat
+    ///
+    /// Returns false once the stored job is no longer active. Storage errors
+    /// are logged rather than aborting the sync.
+    async fn save(&self, job: &SyncJob) -> bool {
+        match self.jobs.update(job).await {
+            Ok(active) => active,
+            Err(e) => {
+                warn!(job_id = %job.id, error = %e, "Failed to save sync job progress");
+                true
+            }
+        }
+    }
 
-        // TODO: Persist to database
+    /// Final state of a job that was stopped from outside the worker
+    ///
+    /// Records how far the worker got, keeping the status set by whoever stopped it.
+    async fn stopped(&self, job: SyncJob) -> Result<SyncJob, SyncOrchestratorError> {
+        info!(job_id = %job.id, "Sync job was stopped, saving partial progress");
+        if let Err(e) = self.jobs.save_counts(&job).await {
+            warn!(job_id = %job.id, error = %e, "Failed to save stopped sync job progress");
+        }
+        Ok(self.jobs.get(job.id).await?.unwrap_or(job))
     }
 
-    /// Cancel a running job
-    pub fn cancel_job(&self, provider_code: &str) -> Result<SyncJob, SyncOrchestratorError> {
-        let mut jobs = self.active_jobs.write().unwrap();
-        if let Some(mut job) = jobs.get_mut(provider_code) {
-            if job.status == SyncJobStatus::Running {
-                job.status = SyncJobStatus::Cancelled;
-                job.completed_at = Some(Utc::now());
-                return Ok(job.clone());
-            }
+    /// Cancel a pending or running job
+    ///
+    /// A worker in this process stops right away, abandoning the product it
+    /// is syncing; one elsewhere notices on its next progress update.
+    pub async fn cancel_job(&self, id: Uuid) -> Result<SyncJob, SyncOrchestratorError> {
+        let mut job = self
+            .jobs
+            .get(id)
+            .await?
+            .ok_or(SyncOrchestratorError::JobNotFound(id))?;
+        if job.is_finished() {
+            return Err(SyncOrchestratorError::JobFinished(id, job.status));
+        }
+
+        job.cancel();
+        if !self.jobs.update(&job).await? {
+            // Finished between the read and the update
+            let status = self
+                .jobs
+                .get(id)
+                .await?
+                .map_or(SyncJobStatus::Cancelled, |job| job.status);
+            return Err(SyncOrchestratorError::JobFinished(id, status));
         }
-        Err(SyncOrchestratorError::JobNotFound(Uuid::nil()))
+
+        if let Some(signal) = self.running.lock().unwrap().get(&id) {
+            signal.cancel();
+        }
+        info!(job_id = %id, provider = %job.provider_code, "Cancelled sync job");
+        Ok(job)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::catalog::{
+        AssetType, MockupAsset, ProductType, UnifiedPrintArea, UnifiedVariant,
+    };
+    use crate::providers::{CatalogPage, ProviderResult};
+    use crate::storage::LocalDiskStorage;
+    use crate::sync::asset_sync::AssetStatus;
+    use crate::sync::AssetSyncResult;
+    use async_trait::async_trait;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Provider serving the same products on every call
+    struct StaticProvider {
+        mockup_calls: Arc<AtomicUsize>,
+        product_count: u32,
+        /// How long fetching a product's mockups takes
+        mockup_delay: Duration,
+        /// Product whose mockup request finds the token revoked
+        revoke_at: Option<u32>,
+        revoked: AtomicBool,
+    }
+
+    impl StaticProvider {
+        fn products(&self) -> Vec<UnifiedProduct> {
+            (1..=self.product_count)
+                .map(|n| {
+                    UnifiedProduct::new(
+                        n.to_string(),
+                        "printful".to_string(),
+                        format!("Product {}", n),
+                        ProductType::Tshirt,
+                    )
+                })
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl PodProvider for StaticProvider {
+        fn code(&self) -> &'static str {
+            "printful"
+        }
+
+        fn name(&self) -> &'static str {
+            "Static"
+        }
+
+        fn base_url(&self) -> &str {
+            "http://static.invalid"
+        }
+
+        fn rate_limit(&self) -> u32 {
+            u32::MAX
+        }
+
+        async fn authenticate(&mut self) -> ProviderResult<()> {
+            Ok(())
+        }
+
+        fn is_authenticated(&self) -> bool {
+            !self.revoked.load(Ordering::SeqCst)
+        }
+
+        async fn refresh_auth(&mut self) -> ProviderResult<()> {
+            Ok(())
+        }
+
+        async fn get_products(
+            &self,
+            page: u32,
+            per_page: u32,
+        ) -> ProviderResult<CatalogPage<UnifiedProduct>> {
+            let products = self.products();
+            let total = products.len() as u64;
+            Ok(CatalogPage::new(products, total, page, per_page))
+        }
+
+        async fn get_product(&self, external_id: &str) -> ProviderResult<UnifiedProduct> {
+            self.products()
+                .into_iter()
+                .find(|p| p.external_id == external_id)
+                .ok_or_else(|| ProviderError::NotFound(external_id.to_string()))
+        }
+
+        async fn get_variants(&self, _: &str) -> ProviderResult<Vec<UnifiedVariant>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_print_areas(&self, _: &str) -> ProviderResult<Vec<UnifiedPrintArea>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_mockup_urls(
+            &self,
+            product_external_id: &str,
+            _: Option<&str>,
+        ) -> ProviderResult<Vec<MockupAsset>> {
+            self.mockup_calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.mockup_delay).await;
+            if self.revoke_at.map(|n| n.to_string()).as_deref() == Some(product_external_id) {
+                self.revoked.store(true, Ordering::SeqCst);
+                return Err(ProviderError::AuthFailed(
+                    "Token refresh failed: invalid_grant".to_string(),
+                ));
+            }
+            Ok(vec![MockupAsset::new(
+                AssetType::BaseImage,
+                format!("http://static.invalid/{}.png", product_external_id),
+            )])
+        }
+
+        fn rate_limit_remaining(&self) -> Option<u32> {
+            None
+        }
+    }
+
+    /// Catalog store whose writes of one product fail, as a rolled back
+    /// transaction would
+    #[derive(Default)]
+    struct FailingCatalogStore {
+        inner: MemoryCatalogStore,
+        fail_product: String,
+    }
+
+    #[async_trait]
+    impl CatalogStore for FailingCatalogStore {
+        async fn store_product(
+            &self,
+            provider_code: &str,
+            product: &UnifiedProduct,
+            assets: &[MockupAsset],
+        ) -> Result<StoredProduct, SyncOrchestratorError> {
+            if product.external_id == self.fail_product {
+                return Err(SyncOrchestratorError::DatabaseError(
+                    "could not serialize access due to concurrent update".to_string(),
+                ));
+            }
+            self.inner
+                .store_product(provider_code, product, assets)
+                .await
+        }
+
+        async fn skip_unchanged(
+            &self,
+            provider_code: &str,
+            external_id: &str,
+            sync_hash: &str,
+        ) -> Result<bool, SyncOrchestratorError> {
+            self.inner
+                .skip_unchanged(provider_code, external_id, sync_hash)
+                .await
+        }
+
+        async fn provider_synced(&self, provider_code: &str) -> Result<(), SyncOrchestratorError> {
+            self.inner.provider_synced(provider_code).await
+        }
+    }
+
+    fn static_orchestrator(mockup_calls: Arc<AtomicUsize>) -> SyncOrchestrator {
+        slow_orchestrator(mockup_calls, 3, Duration::ZERO)
+    }
+
+    fn slow_orchestrator(
+        mockup_calls: Arc<AtomicUsize>,
+        product_count: u32,
+        mockup_delay: Duration,
+    ) -> SyncOrchestrator {
+        provider_orchestrator(mockup_calls, product_count, mockup_delay, None)
+    }
+
+    fn provider_orchestrator(
+        mockup_calls: Arc<AtomicUsize>,
+        product_count: u32,
+        mockup_delay: Duration,
+        revoke_at: Option<u32>,
+    ) -> SyncOrchestrator {
+        SyncOrchestrator::new(None, None).with_providers(move |_| {
+            Some(Box::new(StaticProvider {
+                mockup_calls: mockup_calls.clone(),
+                product_count,
+                mockup_delay,
+                revoke_at,
+                revoked: AtomicBool::new(false),
+            }))
+        })
+    }
 
     #[test]
     fn test_sync_job_progress() {
@@ -432,4 +1408,414 @@ mod tests {
         assert_eq!(job.status, SyncJobStatus::Completed);
         assert!(job.completed_at.is_some());
     }
+
+    #[test]
+    fn test_sync_job_resume_from() {
+        let mut previous = SyncJob::new("printful", SyncJobType::FullCatalog);
+        previous.start();
+        previous.set_total(120);
+        previous.processed_items = 48;
+        previous.failed_items = 2;
+        assert!(!previous.is_resumable());
+
+        previous.cursor = Some("2".to_string());
+        assert!(!previous.is_resumable());
+        previous.fail("connection reset");
+        assert!(previous.is_resumable());
+
+        let job = SyncJob::new("printful", SyncJobType::FullCatalog).resume_from(&previous);
+        assert_ne!(job.id, previous.id);
+        assert_eq!(job.status, SyncJobStatus::Pending);
+        assert_eq!(job.cursor.as_deref(), Some("2"));
+        assert_eq!(job.processed_items, 48);
+        assert_eq!(job.failed_items, 2);
+        assert_eq!(job.progress(), 40.0);
+    }
+
+    #[tokio::test]
+    async fn test_single_product_job_without_product_fails() {
+        let orchestrator = static_orchestrator(Arc::new(AtomicUsize::new(0)));
+        let job = SyncJob::new("printful", SyncJobType::SingleProduct);
+
+        let result = orchestrator.run_full_sync(job.clone(), None).await;
+        assert!(matches!(
+            result,
+            Err(SyncOrchestratorError::MissingProductId)
+        ));
+
+        let stored = orchestrator.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, SyncJobStatus::Failed);
+        assert!(stored.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_single_product_job_syncs_one_product() {
+        let mockup_calls = Arc::new(AtomicUsize::new(0));
+        let orchestrator = static_orchestrator(mockup_calls.clone());
+
+        let done = orchestrator
+            .start_single_product_sync("printful", "2")
+            .await
+            .unwrap();
+        assert_eq!(done.status, SyncJobStatus::Completed);
+        assert_eq!(done.job_type, SyncJobType::SingleProduct);
+        assert_eq!(done.product_id.as_deref(), Some("2"));
+        assert_eq!(done.total_items, 1);
+        assert_eq!(done.processed_items, 1);
+        assert_eq!(mockup_calls.load(Ordering::SeqCst), 1);
+
+        let stored = orchestrator.get_job(done.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, SyncJobStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_single_product_sync_of_unknown_product_fails_job() {
+        let mockup_calls = Arc::new(AtomicUsize::new(0));
+        let orchestrator = static_orchestrator(mockup_calls.clone());
+
+        let failed = orchestrator
+            .start_single_product_sync("printful", "404")
+            .await
+            .unwrap();
+        assert_eq!(failed.status, SyncJobStatus::Failed);
+        assert_eq!(failed.failed_items, 1);
+        assert!(failed
+            .error_message
+            .as_deref()
+            .is_some_and(|message| message.contains("Not found: 404")));
+        assert_eq!(mockup_calls.load(Ordering::SeqCst), 0);
+
+        // The failed job doesn't block the provider
+        let done = orchestrator
+            .start_single_product_sync("printful", "1")
+            .await
+            .unwrap();
+        assert_eq!(done.status, SyncJobStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_incremental_sync_skips_unchanged_products() {
+        let mockup_calls = Arc::new(AtomicUsize::new(0));
+        let orchestrator = static_orchestrator(mockup_calls.clone());
+
+        let first = orchestrator
+            .run_full_sync(SyncJob::new("printful", SyncJobType::Incremental), None)
+            .await
+            .unwrap();
+        assert_eq!(first.status, SyncJobStatus::Completed);
+        assert_eq!(first.processed_items, 3);
+        assert_eq!(first.skipped_items, 0);
+        assert_eq!(mockup_calls.load(Ordering::SeqCst), 3);
+
+        // Identical catalog: nothing to store and no mockups to fetch
+        let second = orchestrator
+            .run_full_sync(SyncJob::new("printful", SyncJobType::Incremental), None)
+            .await
+            .unwrap();
+        assert_eq!(second.status, SyncJobStatus::Completed);
+        assert_eq!(second.processed_items, 0);
+        assert_eq!(second.skipped_items, 3);
+        assert_eq!(second.total_items, 3);
+        assert_eq!(second.progress(), 100.0);
+        assert_eq!(mockup_calls.load(Ordering::SeqCst), 3);
+
+        // A full sync still visits every product
+        let full = orchestrator
+            .run_full_sync(SyncJob::new("printful", SyncJobType::FullCatalog), None)
+            .await
+            .unwrap();
+        assert_eq!(full.processed_items, 3);
+        assert_eq!(full.skipped_items, 0);
+        assert_eq!(mockup_calls.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_failed_product_store_is_counted_and_sync_continues() {
+        let mockup_calls = Arc::new(AtomicUsize::new(0));
+        let orchestrator =
+            static_orchestrator(mockup_calls.clone()).with_catalog(Arc::new(FailingCatalogStore {
+                fail_product: "2".to_string(),
+                ..Default::default()
+            }));
+
+        let job = orchestrator
+            .run_full_sync(SyncJob::new("printful", SyncJobType::FullCatalog), None)
+            .await
+            .unwrap();
+        assert_eq!(job.status, SyncJobStatus::Completed);
+        assert_eq!(job.processed_items, 2);
+        assert_eq!(job.failed_items, 1);
+        assert_eq!(mockup_calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_assets_only_job_needs_r2() {
+        let orchestrator = static_orchestrator(Arc::new(AtomicUsize::new(0)));
+        let job = SyncJob::new("printful", SyncJobType::AssetsOnly);
+
+        let result = orchestrator.run_full_sync(job.clone(), None).await;
+        assert!(matches!(
+            result,
+            Err(SyncOrchestratorError::StorageError(_))
+        ));
+        let stored = orchestrator.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, SyncJobStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_job_does_not_run() {
+        let orchestrator = SyncOrchestrator::new(None, None);
+        let job = SyncJob::new("printful", SyncJobType::FullCatalog);
+        orchestrator.claim(&job).await.unwrap();
+        assert!(matches!(
+            orchestrator
+                .claim(&SyncJob::new("printful", SyncJobType::FullCatalog))
+                .await,
+            Err(SyncOrchestratorError::JobAlreadyRunning(_))
+        ));
+
+        let cancelled = orchestrator.cancel_job(job.id).await.unwrap();
+        assert_eq!(cancelled.status, SyncJobStatus::Cancelled);
+        assert!(matches!(
+            orchestrator.cancel_job(job.id).await,
+            Err(SyncOrchestratorError::JobFinished(
+                _,
+                SyncJobStatus::Cancelled
+            ))
+        ));
+        assert!(matches!(
+            orchestrator.cancel_job(Uuid::new_v4()).await,
+            Err(SyncOrchestratorError::JobNotFound(_))
+        ));
+
+        let result = orchestrator.run_claimed(job, None).await.unwrap();
+        assert_eq!(result.status, SyncJobStatus::Cancelled);
+        assert!(result.started_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lost_authentication_fails_job() {
+        let mockup_calls = Arc::new(AtomicUsize::new(0));
+        let orchestrator = provider_orchestrator(mockup_calls.clone(), 5, Duration::ZERO, Some(2));
+        let job = SyncJob::new("printful", SyncJobType::FullCatalog);
+
+        let result = orchestrator.run_full_sync(job.clone(), None).await;
+        assert!(matches!(
+            result,
+            Err(SyncOrchestratorError::ProviderError(
+                ProviderError::AuthFailed(_)
+            ))
+        ));
+
+        // Products after the one that lost authentication are never requested
+        assert_eq!(mockup_calls.load(Ordering::SeqCst), 2);
+        let failed = orchestrator.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(failed.status, SyncJobStatus::Failed);
+        assert_eq!(failed.processed_items, 1);
+        assert!(failed
+            .error_message
+            .as_deref()
+            .is_some_and(|message| message.contains("Authentication failed")));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_running_sync() {
+        let mockup_calls = Arc::new(AtomicUsize::new(0));
+        let orchestrator = Arc::new(slow_orchestrator(
+            mockup_calls.clone(),
+            20,
+            Duration::from_millis(50),
+        ));
+        let job = SyncJob::new("printful", SyncJobType::FullCatalog);
+        let worker = tokio::spawn({
+            let orchestrator = orchestrator.clone();
+            let job = job.clone();
+            async move { orchestrator.run_full_sync(job, None).await }
+        });
+
+        let processed_at_cancel = loop {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            if let Some(running) = orchestrator.get_job(job.id).await.unwrap() {
+                if running.processed_items >= 1 {
+                    break running.processed_items;
+                }
+            }
+        };
+        let cancelled = orchestrator.cancel_job(job.id).await.unwrap();
+        assert_eq!(cancelled.status, SyncJobStatus::Cancelled);
+
+        let result = tokio::time::timeout(Duration::from_secs(1), worker)
+            .await
+            .expect("sync kept running after cancel")
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.status, SyncJobStatus::Cancelled);
+        assert!(result.processed_items <= processed_at_cancel + 1);
+        assert!(mockup_calls.load(Ordering::SeqCst) <= processed_at_cancel as usize + 2);
+
+        // Partial counts are kept, and a finished job can't be cancelled again
+        let stored = orchestrator.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.processed_items, result.processed_items);
+        assert_eq!(stored.total_items, 20);
+        assert!(matches!(
+            orchestrator.cancel_job(job.id).await,
+            Err(SyncOrchestratorError::JobFinished(
+                _,
+                SyncJobStatus::Cancelled
+            ))
+        ));
+    }
+
+    /// Objects in cleanup storage and their sizes; only the mug's asset and thumbnail are mirrored
+    const STORED: [(&str, usize); 6] = [
+        ("printful/products/19/base/mug.png", 10),
+        ("printful/products/19/thumbnails/thumb_mug.webp", 3),
+        ("printful/products/19/base/old.png", 20),
+        ("printful/blobs/ab12.png", 5),
+        (
+            "printful/variants/4011/mockups/front/on_demand_front.png",
+            7,
+        ),
+        ("generated/2026-10-16/render.png", 9),
+    ];
+
+    /// Local storage holding `STORED`, listed two objects a page
+    async fn cleanup_storage() -> Arc<LocalDiskStorage> {
+        let root = std::env::temp_dir().join(format!("cleanup-{}", Uuid::new_v4()));
+        let storage = LocalDiskStorage::new(root).unwrap().with_page_size(2);
+        for (key, size) in STORED {
+            storage
+                .upload_key(key, vec![0; size], "image/png")
+                .await
+                .unwrap();
+        }
+        Arc::new(storage)
+    }
+
+    /// Keys of `STORED` still in `storage`
+    async fn still_stored(storage: &LocalDiskStorage) -> Vec<&'static str> {
+        let mut kept = Vec::new();
+        for (key, _) in STORED {
+            if storage.exists(key).await.unwrap() {
+                kept.push(key);
+            }
+        }
+        kept
+    }
+
+    /// Orchestrator cleaning up `bucket`, with the mug's asset and thumbnail mirrored
+    async fn cleanup_orchestrator(bucket: Arc<LocalDiskStorage>) -> SyncOrchestrator {
+        let store = Arc::new(MemoryCatalogStore::default());
+        let asset = MockupAsset::new(
+            AssetType::BaseImage,
+            "https://provider.example/mug.png".to_string(),
+        );
+        let synced = AssetSyncResult {
+            source_url: asset.source_url.clone(),
+            r2_key: "printful/products/19/base/mug.png".to_string(),
+            size_bytes: 10,
+            content_type: "image/png".to_string(),
+            public_url: None,
+            sync_time_ms: 1,
+            bucket: "pod-assets".to_string(),
+            checksum: None,
+            retry_count: 0,
+            deduplicated: false,
+            thumbnail_r2_key: Some("printful/products/19/thumbnails/thumb_mug.webp".to_string()),
+        };
+        store
+            .record_status("printful", "19", &asset, &AssetStatus::Downloaded(&synced))
+            .await
+            .unwrap();
+
+        SyncOrchestrator::new(None, None)
+            .with_assets(store)
+            .with_asset_bucket(bucket)
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_dry_run_reports_orphans() {
+        let bucket = cleanup_storage().await;
+        let orchestrator = cleanup_orchestrator(bucket.clone()).await;
+
+        let job = orchestrator
+            .cleanup_orphaned_assets("printful", true)
+            .await
+            .unwrap();
+        assert_eq!(job.job_type, SyncJobType::AssetCleanup);
+        assert_eq!(job.status, SyncJobStatus::Completed);
+        assert_eq!(
+            job.cleanup,
+            Some(CleanupSummary {
+                dry_run: true,
+                scanned: 5,
+                orphaned: 2,
+                deleted: 0,
+                bytes_reclaimed: 25,
+            })
+        );
+        assert_eq!(
+            (job.total_items, job.processed_items, job.skipped_items),
+            (5, 2, 3)
+        );
+        assert_eq!(still_stored(&bucket).await.len(), STORED.len());
+
+        // The summary is kept with the job
+        let stored = orchestrator.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.cleanup, job.cleanup);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_deletes_orphans() {
+        let bucket = cleanup_storage().await;
+        let orchestrator = cleanup_orchestrator(bucket.clone()).await;
+
+        let job = orchestrator
+            .cleanup_orphaned_assets("printful", false)
+            .await
+            .unwrap();
+        assert_eq!(job.status, SyncJobStatus::Completed);
+        let summary = job.cleanup.unwrap();
+        assert!(!summary.dry_run);
+        assert_eq!((summary.orphaned, summary.deleted), (2, 2));
+        assert_eq!(summary.bytes_reclaimed, 25);
+
+        // Mirrored assets, their thumbnails, cached templates, and other folders are kept
+        assert_eq!(
+            still_stored(&bucket).await,
+            vec![
+                "printful/products/19/base/mug.png",
+                "printful/products/19/thumbnails/thumb_mug.webp",
+                "printful/variants/4011/mockups/front/on_demand_front.png",
+                "generated/2026-10-16/render.png",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_refuses_protected_prefixes() {
+        let bucket = cleanup_storage().await;
+        let orchestrator = cleanup_orchestrator(bucket.clone()).await;
+
+        for code in ["generated", "templates"] {
+            assert!(matches!(
+                orchestrator.cleanup_orphaned_assets(code, false).await,
+                Err(SyncOrchestratorError::ProtectedPrefix(_))
+            ));
+        }
+        assert!(orchestrator.job_store().list(10).await.unwrap().is_empty());
+        assert_eq!(still_stored(&bucket).await.len(), STORED.len());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_needs_storage_and_database() {
+        let orchestrator = SyncOrchestrator::new(None, None);
+
+        let job = orchestrator
+            .cleanup_orphaned_assets("printful", false)
+            .await
+            .unwrap();
+        assert_eq!(job.status, SyncJobStatus::Failed);
+        assert!(job.error_message.unwrap().contains("storage"));
+    }
 }
@@ -0,0 +1,319 @@
+//! Catalog product templates
+//!
+//! Renders synced catalog products against the mockup images the asset sync
+//! mirrored to R2. The image is looked up through `pod_mockup_assets`, kept in
+//! a local cache directory after the first download, and paired with the
+//! product's print area from `pod_print_areas` to build an in-memory template.
+
+use image::DynamicImage;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::db::{CatalogRepository, DbError, DbPool, ProductTemplate, ProductTemplateAsset};
+use crate::domain::catalog::PrintPlacement;
+use crate::engine::MemoryTemplate;
+use crate::storage::{R2Client, R2Error};
+
+/// Errors resolving a catalog product's template
+#[derive(Error, Debug)]
+pub enum ProductTemplateError {
+    #[error("Catalog products need a database")]
+    NoDatabase,
+
+    #[error("Catalog product not found: {0}")]
+    ProductNotFound(Uuid),
+
+    #[error("Variant {variant_id} does not belong to product {product_id}")]
+    VariantNotFound { product_id: Uuid, variant_id: Uuid },
+
+    #[error("No synced mockup image for the '{0}' placement; sync the product's assets first")]
+    NoMockupImage(String),
+
+    #[error("Product has no '{0}' print area")]
+    NoPrintArea(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbError),
+
+    #[error("R2 storage error: {0}")]
+    Storage(#[from] R2Error),
+
+    #[error("Template image error: {0}")]
+    Image(String),
+}
+
+/// A catalog product's template, ready to render
+pub struct ResolvedProductTemplate {
+    pub template: MemoryTemplate,
+    /// Provider URL the mockup image was mirrored from
+    pub source_url: String,
+    pub r2_key: String,
+    /// Whether the image came from the local cache rather than R2
+    pub cached: bool,
+}
+
+/// Resolves catalog products to templates built from their mirrored assets
+pub struct ProductTemplates {
+    catalog: Option<CatalogRepository>,
+    r2_client: Option<R2Client>,
+    cache_dir: PathBuf,
+}
+
+impl ProductTemplates {
+    pub fn new(
+        db_pool: Option<DbPool>,
+        r2_client: Option<R2Client>,
+        cache_dir: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            catalog: db_pool.map(CatalogRepository::new),
+            r2_client,
+            cache_dir: cache_dir.into(),
+        }
+    }
+
+    /// Build the template for `placement` of a catalog product, or of one of its variants
+    pub async fn resolve(
+        &self,
+        product_id: Uuid,
+        variant_id: Option<Uuid>,
+        placement: &PrintPlacement,
+    ) -> Result<ResolvedProductTemplate, ProductTemplateError> {
+        let catalog = self
+            .catalog
+            .as_ref()
+            .ok_or(ProductTemplateError::NoDatabase)?;
+        let found = catalog
+            .product_template(product_id, variant_id, placement.as_str())
+            .await?
+            .ok_or(ProductTemplateError::ProductNotFound(product_id))?;
+        if let (false, Some(variant_id)) = (found.variant_found, variant_id) {
+            return Err(ProductTemplateError::VariantNotFound {
+                product_id,
+                variant_id,
+            });
+        }
+        let asset = found
+            .asset
+            .clone()
+            .ok_or_else(|| ProductTemplateError::NoMockupImage(placement.to_string()))?;
+
+        let (data, cached) = self.image_bytes(&asset).await?;
+        let image = decode(data).await?;
+        let template = template_for(&found, variant_id, placement, image)?;
+
+        info!(
+            template_id = %template.metadata.id,
+            r2_key = %asset.r2_key,
+            cached,
+            warnings = template.warnings.len(),
+            "Resolved catalog product template"
+        );
+        Ok(ResolvedProductTemplate {
+            template,
+            source_url: asset.source_url,
+            r2_key: asset.r2_key,
+            cached,
+        })
+    }
+
+    /// The asset's image from the local cache, downloading it from R2 on a miss
+    async fn image_bytes(
+        &self,
+        asset: &ProductTemplateAsset,
+    ) -> Result<(Vec<u8>, bool), ProductTemplateError> {
+        let path = self.cache_path(asset);
+        match tokio::fs::read(&path).await {
+            Ok(data) if matches_checksum(&data, asset.checksum.as_deref()) => {
+                debug!(path = %path.display(), "Catalog template served from local cache");
+                return Ok((data, true));
+            }
+            Ok(_) => warn!(path = %path.display(), "Cached catalog template is corrupt"),
+            Err(_) => {}
+        }
+
+        let r2 = self.r2_client.as_ref().ok_or(R2Error::NotConfigured)?;
+        let data = r2.download(&asset.r2_key).await?;
+        if !matches_checksum(&data, asset.checksum.as_deref()) {
+            return Err(R2Error::ChecksumMismatch(asset.r2_key.clone()).into());
+        }
+
+        // Write to a temp file first so a crash never leaves a truncated image
+        let stored = async {
+            tokio::fs::create_dir_all(&self.cache_dir).await?;
+            let tmp = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+            tokio::fs::write(&tmp, &data).await?;
+            tokio::fs::rename(&tmp, &path).await
+        };
+        if let Err(e) = stored.await {
+            warn!(path = %path.display(), error = %e, "Failed to cache catalog template");
+        }
+        Ok((data, false))
+    }
+
+    /// Cache file of an asset; a new checksum means a new file
+    fn cache_path(&self, asset: &ProductTemplateAsset) -> PathBuf {
+        let key = format!(
+            "{}:{}",
+            asset.r2_key,
+            asset.checksum.as_deref().unwrap_or("")
+        );
+        self.cache_dir
+            .join(format!("{}.img", hex::encode(Sha256::digest(key))))
+    }
+}
+
+/// In-memory template for a looked up product and its decoded mockup image
+fn template_for(
+    found: &ProductTemplate,
+    variant_id: Option<Uuid>,
+    placement: &PrintPlacement,
+    image: DynamicImage,
+) -> Result<MemoryTemplate, ProductTemplateError> {
+    let area = found
+        .print_area
+        .ok_or_else(|| ProductTemplateError::NoPrintArea(placement.to_string()))?;
+    let id = format!(
+        "catalog:{}:{}:{}",
+        found.product_id,
+        variant_id.map_or_else(|| "default".to_string(), |id| id.to_string()),
+        placement.as_str()
+    );
+    Ok(MemoryTemplate::new(
+        &id,
+        placement.as_str(),
+        image,
+        (area.width_px, area.height_px),
+        area.offset,
+    ))
+}
+
+/// Whether `data` hashes to `checksum`; anything matches a missing checksum
+fn matches_checksum(data: &[u8], checksum: Option<&str>) -> bool {
+    checksum.map_or(true, |expected| {
+        hex::encode(Sha256::digest(data)).eq_ignore_ascii_case(expected)
+    })
+}
+
+async fn decode(data: Vec<u8>) -> Result<DynamicImage, ProductTemplateError> {
+    tokio::task::spawn_blocking(move || image::load_from_memory(&data))
+        .await
+        .map_err(|e| ProductTemplateError::Image(format!("Task join error: {}", e)))?
+        .map_err(|e| ProductTemplateError::Image(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ProductPrintArea;
+    use image::{Rgba, RgbaImage};
+
+    /// A 120x160 PNG mockup photo
+    fn fixture_png() -> Vec<u8> {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(120, 160, Rgba([240, 240, 240, 255])))
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        png
+    }
+
+    fn asset(checksum: Option<String>) -> ProductTemplateAsset {
+        ProductTemplateAsset {
+            source_url: "https://provider.example/tee-front.png".to_string(),
+            r2_key: "printful/products/71/mockups/front/tee-front.png".to_string(),
+            checksum,
+        }
+    }
+
+    fn templates(cache_dir: &std::path::Path) -> ProductTemplates {
+        ProductTemplates::new(None, None, cache_dir)
+    }
+
+    #[tokio::test]
+    async fn test_cached_images_are_served_without_r2() {
+        let dir = std::env::temp_dir().join(format!("catalog-cache-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let png = fixture_png();
+        let checksum = hex::encode(Sha256::digest(&png));
+
+        let templates = templates(&dir);
+        let asset = asset(Some(checksum));
+        std::fs::write(templates.cache_path(&asset), &png).unwrap();
+        let (data, cached) = templates.image_bytes(&asset).await.unwrap();
+        assert!(cached);
+        assert_eq!(data, png);
+
+        // A corrupt copy isn't trusted, and there's no R2 to fetch a new one from
+        std::fs::write(templates.cache_path(&asset), b"garbage").unwrap();
+        let result = templates.image_bytes(&asset).await;
+        assert!(matches!(
+            result,
+            Err(ProductTemplateError::Storage(R2Error::NotConfigured))
+        ));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_cache_path_changes_with_checksum() {
+        let templates = templates(std::path::Path::new("/tmp/catalog"));
+        let first = templates.cache_path(&asset(Some("aa".to_string())));
+        assert_eq!(first, templates.cache_path(&asset(Some("aa".to_string()))));
+        assert_ne!(first, templates.cache_path(&asset(Some("bb".to_string()))));
+        assert_ne!(first, templates.cache_path(&asset(None)));
+    }
+
+    #[tokio::test]
+    async fn test_template_uses_print_area_from_catalog() {
+        let image = decode(fixture_png()).await.unwrap();
+        let mut found = ProductTemplate {
+            product_id: Uuid::nil(),
+            variant_found: true,
+            asset: Some(asset(None)),
+            print_area: Some(ProductPrintArea {
+                width_px: 40,
+                height_px: 60,
+                offset: Some((30, 20)),
+            }),
+        };
+
+        let template = template_for(&found, None, &PrintPlacement::Front, image.clone()).unwrap();
+        let area = &template.metadata.print_area;
+        assert_eq!((area.x, area.y, area.width, area.height), (30, 20, 40, 60));
+        assert_eq!(
+            (
+                template.metadata.dimensions.width,
+                template.metadata.dimensions.height
+            ),
+            (120, 160)
+        );
+        assert!(template.images.displacement_map.is_none());
+        assert!(template.warnings.is_empty());
+        assert_eq!(
+            template.metadata.id,
+            "catalog:00000000-0000-0000-0000-000000000000:default:front"
+        );
+
+        // Without a position the print area is centered, with a warning
+        found.print_area = found.print_area.map(|area| ProductPrintArea {
+            offset: None,
+            ..area
+        });
+        let template = template_for(&found, None, &PrintPlacement::Front, image.clone()).unwrap();
+        let area = &template.metadata.print_area;
+        assert_eq!((area.x, area.y), (40, 50));
+        assert_eq!(template.warnings.len(), 1);
+
+        found.print_area = None;
+        assert!(matches!(
+            template_for(&found, None, &PrintPlacement::Back, image),
+            Err(ProductTemplateError::NoPrintArea(placement)) if placement == "back"
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+//! Scheduled provider syncs
+//!
+//! A background task checks `pod_providers` every tick and starts an
+//! incremental sync of each active, sync-enabled provider once its
+//! `sync_interval_hours` have passed since `last_sync_at`. Syncs go through
+//! the shared `SyncScheduler`, so they respect its provider limit and the
+//! one-job-per-provider rule.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use super::orchestrator::{SyncJob, SyncJobType, SyncOrchestratorError};
+use super::scheduler::SyncScheduler;
+use crate::db::pool::DbError;
+use crate::db::DbPool;
+use crate::providers::{ProviderCredentials, PROVIDER_CODES};
+
+/// Hours between syncs for providers without a usable `sync_interval_hours`
+const DEFAULT_INTERVAL_HOURS: i32 = 24;
+
+/// When a provider is next due a scheduled sync
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderSchedule {
+    pub provider_code: String,
+    pub interval_hours: i32,
+    pub last_sync_at: Option<DateTime<Utc>>,
+    /// Providers never synced are due right away
+    pub next_run_at: DateTime<Utc>,
+    /// Credentials are set, so the provider can actually be synced
+    pub configured: bool,
+}
+
+impl ProviderSchedule {
+    fn new(
+        provider_code: String,
+        interval_hours: Option<i32>,
+        last_sync_at: Option<DateTime<Utc>>,
+        configured: bool,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let interval_hours = interval_hours
+            .filter(|hours| *hours > 0)
+            .unwrap_or(DEFAULT_INTERVAL_HOURS);
+        let next_run_at = match last_sync_at {
+            Some(last) => last + chrono::Duration::hours(interval_hours as i64),
+            None => now,
+        };
+
+        Self {
+            provider_code,
+            interval_hours,
+            last_sync_at,
+            next_run_at,
+            configured,
+        }
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.configured && self.next_run_at <= now
+    }
+}
+
+/// The scheduler's view as of its last check
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleSnapshot {
+    pub tick_secs: u64,
+    pub checked_at: Option<DateTime<Utc>>,
+    pub next_check_at: Option<DateTime<Utc>>,
+    pub providers: Vec<ProviderSchedule>,
+}
+
+/// Whether credentials are set for any provider we can sync
+pub fn any_provider_configured() -> bool {
+    PROVIDER_CODES
+        .iter()
+        .any(|code| ProviderCredentials::from_env(code).is_configured())
+}
+
+/// Starts incremental syncs of providers whose sync interval has passed
+pub struct SyncSchedule {
+    pool: DbPool,
+    scheduler: Arc<SyncScheduler>,
+    tick: Duration,
+    snapshot: RwLock<ScheduleSnapshot>,
+}
+
+impl SyncSchedule {
+    /// Create a schedule checking for due providers every `tick`
+    pub fn new(pool: DbPool, scheduler: Arc<SyncScheduler>, tick: Duration) -> Self {
+        Self {
+            pool,
+            scheduler,
+            tick,
+            snapshot: RwLock::new(ScheduleSnapshot {
+                tick_secs: tick.as_secs(),
+                checked_at: None,
+                next_check_at: None,
+                providers: Vec::new(),
+            }),
+        }
+    }
+
+    /// Check for due providers now and then every tick
+    pub fn spawn(self: &Arc<Self>) {
+        let schedule = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(schedule.tick);
+            loop {
+                ticker.tick().await;
+                schedule.run_due().await;
+            }
+        });
+    }
+
+    /// Providers and their next runs as of the last check
+    pub fn snapshot(&self) -> ScheduleSnapshot {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    /// Start a sync of every due provider
+    ///
+    /// Failures are logged and the provider is tried again on the next tick.
+    async fn run_due(&self) {
+        let now = Utc::now();
+        let providers = match self.load(now).await {
+            Ok(providers) => providers,
+            Err(e) => {
+                warn!(error = %e, "Failed to load provider sync schedule");
+                self.record(now, None);
+                return;
+            }
+        };
+
+        for provider in providers.iter().filter(|provider| provider.is_due(now)) {
+            let job = SyncJob::new(&provider.provider_code, SyncJobType::Incremental);
+            match self.scheduler.schedule_provider(job).await {
+                Ok(job) => info!(
+                    job_id = %job.id,
+                    provider = %provider.provider_code,
+                    "Started scheduled sync"
+                ),
+                Err(SyncOrchestratorError::JobAlreadyRunning(_)) => debug!(
+                    provider = %provider.provider_code,
+                    "Sync already running, skipping scheduled sync"
+                ),
+                Err(e) => warn!(
+                    provider = %provider.provider_code,
+                    error = %e,
+                    "Failed to start scheduled sync"
+                ),
+            }
+        }
+
+        self.record(now, Some(providers));
+    }
+
+    /// Save the outcome of a check, keeping the last provider list if loading failed
+    fn record(&self, now: DateTime<Utc>, providers: Option<Vec<ProviderSchedule>>) {
+        let mut snapshot = self.snapshot.write().unwrap();
+        snapshot.checked_at = Some(now);
+        snapshot.next_check_at =
+            Some(now + chrono::Duration::from_std(self.tick).unwrap_or_default());
+        if let Some(providers) = providers {
+            snapshot.providers = providers;
+        }
+    }
+
+    async fn load(&self, now: DateTime<Utc>) -> Result<Vec<ProviderSchedule>, DbError> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                r#"
+            SELECT code, sync_interval_hours, last_sync_at
+            FROM pod_providers
+            WHERE is_active = true AND sync_enabled = true
+            ORDER BY code
+            "#,
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let code: String = row.get("code");
+                let configured = PROVIDER_CODES.contains(&code.as_str())
+                    && ProviderCredentials::from_env(&code).is_configured();
+                ProviderSchedule::new(
+                    code,
+                    row.get("sync_interval_hours"),
+                    row.get("last_sync_at"),
+                    configured,
+                    now,
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_run_follows_interval() {
+        let now = Utc::now();
+        let last = now - chrono::Duration::hours(5);
+
+        let schedule = ProviderSchedule::new("printful".into(), Some(6), Some(last), true, now);
+        assert_eq!(schedule.next_run_at, last + chrono::Duration::hours(6));
+        assert!(!schedule.is_due(now));
+        assert!(schedule.is_due(now + chrono::Duration::hours(1)));
+
+        let overdue = ProviderSchedule::new("printful".into(), Some(4), Some(last), true, now);
+        assert!(overdue.is_due(now));
+    }
+
+    #[test]
+    fn test_unset_interval_defaults_to_a_day() {
+        let now = Utc::now();
+        for hours in [None, Some(0), Some(-3)] {
+            let schedule = ProviderSchedule::new("gelato".into(), hours, Some(now), true, now);
+            assert_eq!(schedule.interval_hours, DEFAULT_INTERVAL_HOURS);
+            assert_eq!(schedule.next_run_at, now + chrono::Duration::hours(24));
+        }
+    }
+
+    #[test]
+    fn test_never_synced_provider_is_due_once_configured() {
+        let now = Utc::now();
+        let schedule = ProviderSchedule::new("printify".into(), Some(24), None, true, now);
+        assert_eq!(schedule.next_run_at, now);
+        assert!(schedule.is_due(now));
+
+        let unconfigured = ProviderSchedule::new("printify".into(), Some(24), None, false, now);
+        assert!(!unconfigured.is_due(now));
+    }
+}
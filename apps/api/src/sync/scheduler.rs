@@ -0,0 +1,473 @@
+//! Multi-provider sync scheduler
+//!
+//! Runs full catalog syncs for several providers under one umbrella job.
+//! A global semaphore caps how many providers sync at once, and the
+//! orchestrator's shared asset limiter interleaves asset mirroring between them.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::orchestrator::{
+    ProgressCallback, SyncJob, SyncJobStatus, SyncJobType, SyncOrchestrator, SyncOrchestratorError,
+    STALE_JOB_TIMEOUT,
+};
+use crate::shutdown::Shutdown;
+use crate::webhooks::{EventType, WebhookDispatcher};
+
+/// Umbrella jobs kept in memory before the oldest finished ones are dropped
+const MAX_RETAINED_UMBRELLA_JOBS: usize = 20;
+
+/// A sync across several providers, aggregating one child job per provider
+#[derive(Debug, Clone, Serialize)]
+pub struct UmbrellaJob {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    /// Child jobs keyed by provider code
+    pub children: HashMap<String, SyncJob>,
+}
+
+impl UmbrellaJob {
+    /// Create an umbrella job with a pending child per provider
+    pub fn new(provider_codes: &[String]) -> Self {
+        let id = Uuid::new_v4();
+        let children = provider_codes
+            .iter()
+            .map(|code| {
+                (
+                    code.clone(),
+                    SyncJob::new(code, SyncJobType::FullCatalog).with_parent(id),
+                )
+            })
+            .collect();
+
+        Self {
+            id,
+            created_at: Utc::now(),
+            children,
+        }
+    }
+
+    /// Aggregate status of all child jobs
+    ///
+    /// Running while any child is unfinished; once all are done, failed if
+    /// any child failed, cancelled if every child was cancelled.
+    pub fn status(&self) -> SyncJobStatus {
+        let children = || self.children.values();
+
+        if children().all(|c| c.status == SyncJobStatus::Pending) {
+            SyncJobStatus::Pending
+        } else if children().any(|c| !c.is_finished()) {
+            SyncJobStatus::Running
+        } else if children().any(|c| c.status == SyncJobStatus::Failed) {
+            SyncJobStatus::Failed
+        } else if children().all(|c| c.status == SyncJobStatus::Cancelled) {
+            SyncJobStatus::Cancelled
+        } else {
+            SyncJobStatus::Completed
+        }
+    }
+
+    pub fn total_items(&self) -> u32 {
+        self.children.values().map(|c| c.total_items).sum()
+    }
+
+    pub fn processed_items(&self) -> u32 {
+        self.children.values().map(|c| c.processed_items).sum()
+    }
+
+    pub fn failed_items(&self) -> u32 {
+        self.children.values().map(|c| c.failed_items).sum()
+    }
+
+    pub fn skipped_items(&self) -> u32 {
+        self.children.values().map(|c| c.skipped_items).sum()
+    }
+
+    /// Get progress percentage across all providers; skipped items count as done
+    pub fn progress(&self) -> f32 {
+        let total = self.total_items();
+        if total == 0 {
+            0.0
+        } else {
+            ((self.processed_items() + self.skipped_items()) as f32 / total as f32) * 100.0
+        }
+    }
+
+    /// Earliest child start time
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.children.values().filter_map(|c| c.started_at).min()
+    }
+
+    /// Latest child completion time, once every child has finished
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        if self.children.values().all(|c| c.is_finished()) {
+            self.children.values().filter_map(|c| c.completed_at).max()
+        } else {
+            None
+        }
+    }
+
+    /// Serializable summary including aggregated counters
+    pub fn summary(&self) -> UmbrellaJobSummary {
+        let mut children: Vec<SyncJob> = self.children.values().cloned().collect();
+        children.sort_by(|a, b| a.provider_code.cmp(&b.provider_code));
+
+        UmbrellaJobSummary {
+            id: self.id,
+            status: self.status(),
+            total_items: self.total_items(),
+            processed_items: self.processed_items(),
+            failed_items: self.failed_items(),
+            skipped_items: self.skipped_items(),
+            progress_percent: self.progress(),
+            created_at: self.created_at,
+            started_at: self.started_at(),
+            completed_at: self.completed_at(),
+            children,
+        }
+    }
+}
+
+/// API view of an umbrella job
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UmbrellaJobSummary {
+    pub id: Uuid,
+    pub status: SyncJobStatus,
+    pub total_items: u32,
+    pub processed_items: u32,
+    pub failed_items: u32,
+    pub skipped_items: u32,
+    pub progress_percent: f32,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub children: Vec<SyncJob>,
+}
+
+/// Schedules provider syncs under a global concurrency limit
+pub struct SyncScheduler {
+    orchestrator: Arc<SyncOrchestrator>,
+    /// Provider sync permits, shared by every umbrella job
+    provider_limiter: Arc<Semaphore>,
+    umbrella_jobs: Arc<RwLock<HashMap<Uuid, UmbrellaJob>>>,
+    /// Publishes sync.completed / sync.failed as each provider finishes
+    webhooks: Option<Arc<WebhookDispatcher>>,
+    /// Tracks running syncs so shutdown waits for them
+    shutdown: Arc<Shutdown>,
+}
+
+impl SyncScheduler {
+    /// Create a scheduler running at most `max_concurrent_providers` syncs at once
+    pub fn new(orchestrator: Arc<SyncOrchestrator>, max_concurrent_providers: usize) -> Self {
+        Self {
+            orchestrator,
+            provider_limiter: Arc::new(Semaphore::new(max_concurrent_providers.max(1))),
+            umbrella_jobs: Arc::new(RwLock::new(HashMap::new())),
+            webhooks: None,
+            shutdown: Arc::new(Shutdown::default()),
+        }
+    }
+
+    /// Publish webhook events for finished provider syncs
+    pub fn with_webhooks(mut self, webhooks: Option<Arc<WebhookDispatcher>>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    /// Run syncs as tasks `shutdown` waits for before exit
+    pub fn with_shutdown(mut self, shutdown: Arc<Shutdown>) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Get an umbrella job by ID
+    pub fn get_umbrella(&self, id: Uuid) -> Option<UmbrellaJob> {
+        let jobs = self.umbrella_jobs.read().unwrap();
+        jobs.get(&id).cloned()
+    }
+
+    /// Get all retained umbrella jobs, newest first
+    pub fn list_umbrellas(&self) -> Vec<UmbrellaJob> {
+        let jobs = self.umbrella_jobs.read().unwrap();
+        let mut list: Vec<UmbrellaJob> = jobs.values().cloned().collect();
+        list.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        list
+    }
+
+    /// Get the umbrella job that is still running, if any
+    pub fn running_umbrella(&self) -> Option<UmbrellaJob> {
+        let jobs = self.umbrella_jobs.read().unwrap();
+        Self::find_running(&jobs).cloned()
+    }
+
+    fn find_running(jobs: &HashMap<Uuid, UmbrellaJob>) -> Option<&UmbrellaJob> {
+        jobs.values()
+            .find(|j| matches!(j.status(), SyncJobStatus::Pending | SyncJobStatus::Running))
+    }
+
+    /// Schedule full syncs for the given providers and return the umbrella job
+    ///
+    /// Child syncs run in the background; poll `get_umbrella` for progress.
+    /// While another umbrella job is unfinished nothing is scheduled and that
+    /// job is returned as the error. The check and the insert share one
+    /// lock, so concurrent calls can't both start a sync.
+    pub fn schedule_all(&self, provider_codes: Vec<String>) -> Result<UmbrellaJob, UmbrellaJob> {
+        let umbrella = UmbrellaJob::new(&provider_codes);
+        let umbrella_id = umbrella.id;
+
+        {
+            let mut jobs = self.umbrella_jobs.write().unwrap();
+            if let Some(running) = Self::find_running(&jobs) {
+                return Err(running.clone());
+            }
+            Self::prune(&mut jobs);
+            jobs.insert(umbrella_id, umbrella.clone());
+        }
+
+        info!(
+            umbrella_id = %umbrella_id,
+            providers = provider_codes.len(),
+            "Scheduling sync for all providers"
+        );
+
+        for (code, child) in umbrella.children.clone() {
+            let orchestrator = self.orchestrator.clone();
+            let limiter = self.provider_limiter.clone();
+            let umbrella_jobs = self.umbrella_jobs.clone();
+            let webhooks = self.webhooks.clone();
+
+            self.shutdown.spawn(async move {
+                let _permit = limiter.acquire_owned().await.unwrap();
+
+                let progress_jobs = umbrella_jobs.clone();
+                let on_progress: ProgressCallback = Box::new(move |job: &SyncJob| {
+                    Self::record_child(&progress_jobs, umbrella_id, job);
+                });
+
+                let child_id = child.id;
+                let final_job = match orchestrator.run_full_sync(child, Some(on_progress)).await {
+                    Ok(job) => job,
+                    Err(e) => {
+                        warn!(provider = %code, error = %e, "Provider sync failed");
+                        Self::failed_child(&orchestrator, &code, child_id, umbrella_id, &e).await
+                    }
+                };
+                Self::record_child(&umbrella_jobs, umbrella_id, &final_job);
+
+                let event_type = match final_job.status {
+                    SyncJobStatus::Completed => Some(EventType::SyncCompleted),
+                    SyncJobStatus::Failed => Some(EventType::SyncFailed),
+                    _ => None,
+                };
+                if let (Some(webhooks), Some(event_type)) = (webhooks, event_type) {
+                    match serde_json::to_value(&final_job) {
+                        Ok(data) => webhooks.publish(None, event_type, data),
+                        Err(e) => warn!(error = %e, "Failed to serialize sync job for webhook"),
+                    }
+                }
+            });
+        }
+
+        Ok(umbrella)
+    }
+
+    /// Cancel a pending or running provider sync
+    pub async fn cancel_job(&self, id: Uuid) -> Result<SyncJob, SyncOrchestratorError> {
+        self.orchestrator.cancel_job(id).await
+    }
+
+    /// Sync one product right away, returning the finished job
+    pub async fn sync_product(
+        &self,
+        provider_code: &str,
+        external_product_id: &str,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        self.orchestrator
+            .start_single_product_sync(provider_code, external_product_id)
+            .await
+    }
+
+    /// Remove a provider's orphaned R2 objects right away, returning the finished job
+    pub async fn cleanup_orphaned_assets(
+        &self,
+        provider_code: &str,
+        dry_run: bool,
+    ) -> Result<SyncJob, SyncOrchestratorError> {
+        self.orchestrator
+            .cleanup_orphaned_assets(provider_code, dry_run)
+            .await
+    }
+
+    /// Claim a single-provider sync and run it once a provider permit frees up
+    ///
+    /// The job is recorded as pending right away, so conflicts are reported to
+    /// the caller; it keeps sending heartbeats while queued for a permit.
+    pub async fn schedule_provider(&self, job: SyncJob) -> Result<SyncJob, SyncOrchestratorError> {
+        self.orchestrator.claim(&job).await?;
+
+        let orchestrator = self.orchestrator.clone();
+        let limiter = self.provider_limiter.clone();
+        let queued = job.clone();
+
+        self.shutdown.spawn(async move {
+            let Some(_permit) = Self::wait_for_permit(&orchestrator, limiter, &queued).await else {
+                info!(job_id = %queued.id, "Sync job was cancelled while queued");
+                return;
+            };
+            if let Err(e) = orchestrator.run_claimed(queued, None).await {
+                warn!(error = %e, "Provider sync failed");
+            }
+        });
+
+        Ok(job)
+    }
+
+    /// Wait for a provider permit, refreshing the queued job's heartbeat
+    ///
+    /// Returns None if the job stopped being active while it waited.
+    async fn wait_for_permit(
+        orchestrator: &SyncOrchestrator,
+        limiter: Arc<Semaphore>,
+        job: &SyncJob,
+    ) -> Option<OwnedSemaphorePermit> {
+        let acquire = limiter.acquire_owned();
+        tokio::pin!(acquire);
+        let mut heartbeat = tokio::time::interval(STALE_JOB_TIMEOUT / 3);
+
+        loop {
+            tokio::select! {
+                permit = &mut acquire => return permit.ok(),
+                _ = heartbeat.tick() => {
+                    match orchestrator.job_store().update(job).await {
+                        Ok(false) => return None,
+                        Ok(true) => {}
+                        Err(e) => warn!(job_id = %job.id, error = %e, "Failed to refresh queued sync job"),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Final state of a child whose sync returned an error
+    async fn failed_child(
+        orchestrator: &SyncOrchestrator,
+        provider_code: &str,
+        child_id: Uuid,
+        umbrella_id: Uuid,
+        error: &SyncOrchestratorError,
+    ) -> SyncJob {
+        // The store keeps the failed job unless it was never claimed
+        match orchestrator.get_job(child_id).await {
+            Ok(Some(job)) if job.is_finished() => job,
+            _ => {
+                let mut job =
+                    SyncJob::new(provider_code, SyncJobType::FullCatalog).with_parent(umbrella_id);
+                job.id = child_id;
+                job.fail(&error.to_string());
+                job
+            }
+        }
+    }
+
+    fn record_child(
+        umbrella_jobs: &RwLock<HashMap<Uuid, UmbrellaJob>>,
+        umbrella_id: Uuid,
+        job: &SyncJob,
+    ) {
+        let mut jobs = umbrella_jobs.write().unwrap();
+        if let Some(umbrella) = jobs.get_mut(&umbrella_id) {
+            umbrella
+                .children
+                .insert(job.provider_code.clone(), job.clone());
+        }
+    }
+
+    /// Drop the oldest finished umbrella jobs beyond the retention limit
+    fn prune(jobs: &mut HashMap<Uuid, UmbrellaJob>) {
+        while jobs.len() >= MAX_RETAINED_UMBRELLA_JOBS {
+            let oldest = jobs
+                .values()
+                .filter(|j| j.completed_at().is_some())
+                .min_by_key(|j| j.created_at)
+                .map(|j| j.id);
+            match oldest {
+                Some(id) => {
+                    jobs.remove(&id);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn umbrella(codes: &[&str]) -> UmbrellaJob {
+        let codes: Vec<String> = codes.iter().map(|c| c.to_string()).collect();
+        UmbrellaJob::new(&codes)
+    }
+
+    #[test]
+    fn test_umbrella_aggregates_children() {
+        let mut job = umbrella(&["printful", "gelato"]);
+        assert_eq!(job.status(), SyncJobStatus::Pending);
+
+        let printful = job.children.get_mut("printful").unwrap();
+        assert_eq!(printful.parent_job_id, Some(job.id));
+        printful.start();
+        printful.set_total(10);
+        printful.processed_items = 5;
+        printful.failed_items = 1;
+
+        let gelato = job.children.get_mut("gelato").unwrap();
+        gelato.start();
+        gelato.set_total(30);
+        gelato.processed_items = 15;
+
+        assert_eq!(job.status(), SyncJobStatus::Running);
+        assert_eq!(job.total_items(), 40);
+        assert_eq!(job.processed_items(), 20);
+        assert_eq!(job.failed_items(), 1);
+        assert_eq!(job.progress(), 50.0);
+        assert!(job.completed_at().is_none());
+    }
+
+    #[test]
+    fn test_umbrella_final_status() {
+        let mut job = umbrella(&["printful", "gelato"]);
+        for child in job.children.values_mut() {
+            child.start();
+            child.complete();
+        }
+        assert_eq!(job.status(), SyncJobStatus::Completed);
+        assert!(job.completed_at().is_some());
+
+        job.children.get_mut("gelato").unwrap().fail("auth failed");
+        assert_eq!(job.status(), SyncJobStatus::Failed);
+    }
+
+    #[test]
+    fn test_schedule_all_refused_while_an_umbrella_runs() {
+        let scheduler = SyncScheduler::new(Arc::new(SyncOrchestrator::new(None, None)), 1);
+        let running = umbrella(&["printful"]);
+        scheduler
+            .umbrella_jobs
+            .write()
+            .unwrap()
+            .insert(running.id, running.clone());
+
+        let refused = scheduler
+            .schedule_all(vec!["gelato".to_string()])
+            .unwrap_err();
+        assert_eq!(refused.id, running.id);
+        assert_eq!(scheduler.list_umbrellas().len(), 1);
+    }
+}
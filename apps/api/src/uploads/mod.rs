@@ -0,0 +1,11 @@
+//! Deferred result uploads
+//!
+//! When a Cloudinary or R2 upload fails after a successful render, the encoded
+//! mockup is kept here and retried in the background instead of being thrown
+//! away. `GET /api/v1/renders` and `GET /api/v1/renders/{id}` report progress.
+
+mod queue;
+
+pub use queue::{
+    FailedUpload, RenderUploads, UploadQueue, UploadState, UploadStatus, UploadTarget,
+};
@@ -0,0 +1,470 @@
+//! In-memory retry queue for failed result uploads
+
+use base64::Engine;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::storage::{CloudinaryUploader, StorageBackend};
+
+/// Attempts per upload, counting the one made during the request
+const MAX_UPLOAD_ATTEMPTS: u32 = 6;
+
+/// Delay before the first background retry; doubles per attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Longest delay between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+
+/// How long render records stay queryable
+const RENDER_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Encoded mockups held for retry at once; new failures beyond this are not queued
+const MAX_PENDING_BYTES: usize = 256 * 1024 * 1024;
+
+/// Where a result upload goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadTarget {
+    Cloudinary,
+    R2,
+}
+
+/// Progress of one deferred upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadState {
+    /// Waiting for the next retry
+    Pending,
+    Completed,
+    /// Gave up after the last attempt
+    Failed,
+}
+
+/// One upload of a render, as reported by the renders endpoint
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UploadStatus {
+    pub target: UploadTarget,
+    pub state: UploadState,
+    /// Attempts so far, including the one made during the request
+    pub attempts: u32,
+    /// Destination key for R2 uploads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r2_key: Option<String>,
+    /// Hosted URL once completed: the Cloudinary URL, or the R2 public URL when configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Cloudinary public ID once completed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// A render whose uploads were deferred
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RenderUploads {
+    pub render_id: Uuid,
+    pub template_id: String,
+    pub content_type: String,
+    pub created_at: DateTime<Utc>,
+    pub uploads: Vec<UploadStatus>,
+}
+
+impl RenderUploads {
+    fn is_settled(&self) -> bool {
+        self.uploads
+            .iter()
+            .all(|upload| upload.state != UploadState::Pending)
+    }
+}
+
+/// An upload that failed during the request
+pub struct FailedUpload {
+    pub target: UploadTarget,
+    /// Destination key, for R2 uploads
+    pub r2_key: Option<String>,
+    pub error: String,
+}
+
+struct DeferredRender {
+    api_key_id: Option<Uuid>,
+    record: RenderUploads,
+    /// Encoded mockup, dropped once every upload has settled
+    data: Option<Bytes>,
+}
+
+/// One retry to run outside the lock
+struct RetryAttempt {
+    render_id: Uuid,
+    index: usize,
+    target: UploadTarget,
+    r2_key: Option<String>,
+    content_type: String,
+    data: Bytes,
+}
+
+/// Outcome of a successful retry
+struct Uploaded {
+    url: Option<String>,
+    public_id: Option<String>,
+}
+
+/// Keeps failed uploads' bytes and retries them with exponential backoff
+pub struct UploadQueue {
+    renders: Mutex<HashMap<Uuid, DeferredRender>>,
+    cloudinary: Option<Arc<CloudinaryUploader>>,
+    storage: Option<Arc<dyn StorageBackend>>,
+}
+
+impl UploadQueue {
+    pub fn new(
+        cloudinary: Option<Arc<CloudinaryUploader>>,
+        storage: Option<Arc<dyn StorageBackend>>,
+    ) -> Self {
+        Self {
+            renders: Mutex::new(HashMap::new()),
+            cloudinary,
+            storage,
+        }
+    }
+
+    /// Queue a render's failed uploads for retry, returning its render ID
+    ///
+    /// Returns `None` when nothing failed or the queue already holds
+    /// `MAX_PENDING_BYTES` of mockups.
+    pub fn defer(
+        &self,
+        api_key_id: Option<Uuid>,
+        template_id: &str,
+        content_type: &str,
+        data: Bytes,
+        failed: Vec<FailedUpload>,
+    ) -> Option<Uuid> {
+        if failed.is_empty() {
+            return None;
+        }
+
+        let mut renders = self.renders.lock();
+        prune_expired(&mut renders);
+        let pending_bytes: usize = renders
+            .values()
+            .filter_map(|render| render.data.as_ref().map(Bytes::len))
+            .sum();
+        if pending_bytes + data.len() > MAX_PENDING_BYTES {
+            warn!(
+                pending_bytes,
+                template_id, "Upload retry queue is full; not deferring"
+            );
+            return None;
+        }
+
+        let render_id = Uuid::new_v4();
+        let next_attempt_at = Some(Utc::now() + retry_delay(1));
+        let uploads = failed
+            .into_iter()
+            .map(|failed| UploadStatus {
+                target: failed.target,
+                state: UploadState::Pending,
+                attempts: 1,
+                r2_key: failed.r2_key,
+                url: None,
+                public_id: None,
+                last_error: Some(failed.error),
+                next_attempt_at,
+            })
+            .collect();
+
+        renders.insert(
+            render_id,
+            DeferredRender {
+                api_key_id,
+                record: RenderUploads {
+                    render_id,
+                    template_id: template_id.to_string(),
+                    content_type: content_type.to_string(),
+                    created_at: Utc::now(),
+                    uploads,
+                },
+                data: Some(data),
+            },
+        );
+        debug!(%render_id, template_id, "Deferred result upload");
+        Some(render_id)
+    }
+
+    /// A render owned by `api_key_id`, if it has not expired
+    pub fn get(&self, render_id: Uuid, api_key_id: Option<Uuid>) -> Option<RenderUploads> {
+        let renders = self.renders.lock();
+        renders
+            .get(&render_id)
+            .filter(|render| render.api_key_id == api_key_id && !is_expired(&render.record))
+            .map(|render| render.record.clone())
+    }
+
+    /// Renders owned by `api_key_id`, newest first
+    pub fn list(&self, api_key_id: Option<Uuid>) -> Vec<RenderUploads> {
+        let renders = self.renders.lock();
+        let mut records: Vec<_> = renders
+            .values()
+            .filter(|render| render.api_key_id == api_key_id && !is_expired(&render.record))
+            .map(|render| render.record.clone())
+            .collect();
+        records.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        records
+    }
+
+    /// Retry uploads on a fixed interval until the queue is dropped
+    pub fn spawn_retry_task(self: &Arc<Self>, interval: Duration) {
+        let queue = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match queue.upgrade() {
+                    Some(queue) => {
+                        queue.retry_due().await;
+                    }
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// Retry every upload whose backoff has elapsed, returning how many completed
+    pub async fn retry_due(&self) -> usize {
+        let due = self.take_due(Utc::now());
+        let mut completed = 0;
+
+        for attempt in due {
+            let outcome = self.attempt(&attempt).await;
+            completed += outcome.is_ok() as usize;
+            self.record_outcome(&attempt, outcome);
+        }
+
+        if completed > 0 {
+            info!(completed, "Deferred result uploads completed");
+        }
+        completed
+    }
+
+    /// Pending uploads whose next attempt is due
+    fn take_due(&self, now: DateTime<Utc>) -> Vec<RetryAttempt> {
+        let mut renders = self.renders.lock();
+        prune_expired(&mut renders);
+
+        let mut due = Vec::new();
+        for render in renders.values_mut() {
+            let Some(data) = &render.data else {
+                continue;
+            };
+            for (index, upload) in render.record.uploads.iter_mut().enumerate() {
+                if upload.state == UploadState::Pending
+                    && upload.next_attempt_at.map_or(true, |at| at <= now)
+                {
+                    // Cleared until the outcome is recorded, so overlapping
+                    // sweeps don't retry the same upload twice
+                    upload.next_attempt_at = None;
+                    due.push(RetryAttempt {
+                        render_id: render.record.render_id,
+                        index,
+                        target: upload.target,
+                        r2_key: upload.r2_key.clone(),
+                        content_type: render.record.content_type.clone(),
+                        data: data.clone(),
+                    });
+                }
+            }
+        }
+        due
+    }
+
+    async fn attempt(&self, attempt: &RetryAttempt) -> Result<Uploaded, String> {
+        match attempt.target {
+            UploadTarget::Cloudinary => {
+                let cloudinary = self
+                    .cloudinary
+                    .as_ref()
+                    .ok_or("Cloudinary is not configured")?;
+                let data_uri = format!(
+                    "data:{};base64,{}",
+                    attempt.content_type,
+                    base64::engine::general_purpose::STANDARD.encode(&attempt.data)
+                );
+                let uploaded = cloudinary
+                    .upload(data_uri)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(Uploaded {
+                    url: Some(uploaded.secure_url),
+                    public_id: Some(uploaded.public_id),
+                })
+            }
+            UploadTarget::R2 => {
+                let storage = self.storage.as_ref().ok_or("Storage is not configured")?;
+                let key = attempt.r2_key.as_deref().ok_or("Upload has no key")?;
+                let uploaded = storage
+                    .upload_key(key, attempt.data.to_vec(), &attempt.content_type)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(Uploaded {
+                    url: uploaded.public_url,
+                    public_id: None,
+                })
+            }
+        }
+    }
+
+    fn record_outcome(&self, attempt: &RetryAttempt, outcome: Result<Uploaded, String>) {
+        let mut renders = self.renders.lock();
+        let Some(render) = renders.get_mut(&attempt.render_id) else {
+            return;
+        };
+        let Some(upload) = render.record.uploads.get_mut(attempt.index) else {
+            return;
+        };
+
+        upload.attempts += 1;
+        match outcome {
+            Ok(uploaded) => {
+                upload.state = UploadState::Completed;
+                upload.url = uploaded.url;
+                upload.public_id = uploaded.public_id;
+                upload.last_error = None;
+            }
+            Err(error) if upload.attempts >= MAX_UPLOAD_ATTEMPTS => {
+                warn!(
+                    render_id = %attempt.render_id,
+                    target = ?upload.target,
+                    attempts = upload.attempts,
+                    error = %error,
+                    "Giving up on deferred result upload"
+                );
+                upload.state = UploadState::Failed;
+                upload.last_error = Some(error);
+            }
+            Err(error) => {
+                debug!(
+                    render_id = %attempt.render_id,
+                    attempts = upload.attempts,
+                    error = %error,
+                    "Deferred result upload failed; will retry"
+                );
+                upload.next_attempt_at = Some(Utc::now() + retry_delay(upload.attempts));
+                upload.last_error = Some(error);
+            }
+        }
+
+        if render.record.is_settled() {
+            render.data = None;
+        }
+    }
+}
+
+/// Backoff after `attempts` failed attempts
+fn retry_delay(attempts: u32) -> chrono::Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    let delay = INITIAL_RETRY_DELAY
+        .saturating_mul(factor)
+        .min(MAX_RETRY_DELAY);
+    chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+fn is_expired(record: &RenderUploads) -> bool {
+    let age = Utc::now().signed_duration_since(record.created_at);
+    age.to_std().map_or(false, |age| age > RENDER_RETENTION)
+}
+
+fn prune_expired(renders: &mut HashMap<Uuid, DeferredRender>) {
+    renders.retain(|_, render| !is_expired(&render.record));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed_r2() -> FailedUpload {
+        FailedUpload {
+            target: UploadTarget::R2,
+            r2_key: Some("generated/2026-10-16/a.png".to_string()),
+            error: "timeout".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_and_caps() {
+        assert_eq!(retry_delay(1), chrono::Duration::seconds(10));
+        assert_eq!(retry_delay(3), chrono::Duration::seconds(40));
+        assert_eq!(retry_delay(30), chrono::Duration::minutes(10));
+    }
+
+    #[test]
+    fn test_renders_are_scoped_to_their_key() {
+        let queue = UploadQueue::new(None, None);
+        let owner = Some(Uuid::new_v4());
+        let id = queue
+            .defer(
+                owner,
+                "tee",
+                "image/png",
+                Bytes::from_static(b"png"),
+                vec![failed_r2()],
+            )
+            .unwrap();
+
+        let record = queue.get(id, owner).unwrap();
+        assert_eq!(record.uploads[0].state, UploadState::Pending);
+        assert_eq!(record.uploads[0].attempts, 1);
+        assert!(queue.get(id, Some(Uuid::new_v4())).is_none());
+        assert!(queue.get(id, None).is_none());
+        assert_eq!(queue.list(owner).len(), 1);
+
+        assert!(queue
+            .defer(owner, "tee", "image/png", Bytes::new(), Vec::new())
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_target_fails_after_max_attempts() {
+        let queue = UploadQueue::new(None, None);
+        let id = queue
+            .defer(
+                None,
+                "tee",
+                "image/png",
+                Bytes::from_static(b"png"),
+                vec![failed_r2()],
+            )
+            .unwrap();
+
+        // Nothing is due until the backoff elapses
+        assert!(queue.take_due(Utc::now()).is_empty());
+
+        for _ in 1..MAX_UPLOAD_ATTEMPTS {
+            let later = Utc::now() + chrono::Duration::hours(1);
+            for attempt in queue.take_due(later) {
+                let outcome = queue.attempt(&attempt).await;
+                queue.record_outcome(&attempt, outcome);
+            }
+        }
+
+        let record = queue.get(id, None).unwrap();
+        assert_eq!(record.uploads[0].state, UploadState::Failed);
+        assert_eq!(record.uploads[0].attempts, MAX_UPLOAD_ATTEMPTS);
+        assert_eq!(
+            record.uploads[0].last_error.as_deref(),
+            Some("Storage is not configured")
+        );
+        assert!(queue.renders.lock()[&id].data.is_none());
+    }
+}
@@ -0,0 +1,294 @@
+//! Webhook delivery
+//!
+//! Events are stored first, then POSTed to every matching subscription with an
+//! HMAC-SHA256 signature. Each attempt is logged so it can be inspected and
+//! replayed with `redeliver`.
+
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use super::events::{event_matches, EventType};
+use crate::config::service_user_agent;
+use crate::db::{
+    DbPool, DbWebhookEvent, DbWebhookSubscription, WebhookDelivery, WebhookRepository,
+};
+use crate::net::{UrlGuard, UrlGuardError, UrlPolicy};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Timeout for a single delivery attempt
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Webhook errors
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("Database error: {0}")]
+    Db(#[from] crate::db::pool::DbError),
+    #[error("Delivery log entry {0} not found")]
+    DeliveryNotFound(Uuid),
+}
+
+/// Result of one delivery attempt
+#[derive(Debug)]
+struct DeliveryOutcome {
+    delivered: bool,
+    response_status: Option<i32>,
+    error_message: Option<String>,
+}
+
+/// Check a webhook destination before it is stored
+///
+/// Callers choose these URLs, so local and private hosts are refused like
+/// design URLs; names resolving to internal addresses are refused on delivery.
+pub fn validate_webhook_url(url: &str) -> Result<(), String> {
+    UrlPolicy::default()
+        .check(url)
+        .map(|_| ())
+        .map_err(|e| format!("Webhook url is not allowed: {}", e))
+}
+
+/// Client for webhook destinations, re-checking every address and redirect hop
+pub(super) fn delivery_guard() -> UrlGuard {
+    let builder = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .user_agent(service_user_agent());
+    UrlGuard::new(UrlPolicy::default(), builder).expect("Failed to create HTTP client")
+}
+
+/// Why a request to a webhook destination failed, naming the guard's refusal
+pub(super) fn delivery_error(error: &reqwest::Error) -> String {
+    match UrlGuardError::from_reqwest(error) {
+        Some(guard) => format!("Destination not allowed: {}", guard),
+        None => error.to_string(),
+    }
+}
+
+/// Stores events and delivers them to subscribed webhooks
+pub struct WebhookDispatcher {
+    repo: WebhookRepository,
+    urls: UrlGuard,
+}
+
+impl WebhookDispatcher {
+    pub fn new(pool: DbPool) -> Self {
+        Self {
+            repo: WebhookRepository::new(pool),
+            urls: delivery_guard(),
+        }
+    }
+
+    /// Record an event and deliver it in the background
+    ///
+    /// `api_key_id` scopes the event to one key's subscriptions; `None` marks
+    /// a system event delivered to enterprise subscriptions.
+    pub fn publish(
+        self: &Arc<Self>,
+        api_key_id: Option<Uuid>,
+        event_type: EventType,
+        data: serde_json::Value,
+    ) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = dispatcher.publish_now(api_key_id, event_type, data).await {
+                warn!(event = event_type.as_str(), error = %e, "Failed to publish webhook event");
+            }
+        });
+    }
+
+    /// Record an event and deliver it to every matching subscription
+    pub async fn publish_now(
+        &self,
+        api_key_id: Option<Uuid>,
+        event_type: EventType,
+        data: serde_json::Value,
+    ) -> Result<Uuid, WebhookError> {
+        let subscriptions: Vec<DbWebhookSubscription> = self
+            .repo
+            .candidate_subscriptions(api_key_id)
+            .await?
+            .into_iter()
+            .filter(|s| event_matches(&s.event_types, event_type.as_str()))
+            .collect();
+
+        let event_id = self
+            .repo
+            .record_event(api_key_id, event_type.as_str(), &data.to_string())
+            .await?;
+
+        if subscriptions.is_empty() {
+            return Ok(event_id);
+        }
+
+        let event = match self.repo.get_event(event_id).await? {
+            Some(event) => event,
+            None => return Ok(event_id),
+        };
+
+        let deliveries = subscriptions
+            .iter()
+            .map(|subscription| self.deliver(subscription, &event, false));
+        for result in futures::future::join_all(deliveries).await {
+            if let Err(e) = result {
+                warn!(event_id = %event_id, error = %e, "Failed to log webhook delivery");
+            }
+        }
+
+        Ok(event_id)
+    }
+
+    /// Deliver a stored event to a subscription again
+    pub async fn redeliver(
+        &self,
+        subscription: &DbWebhookSubscription,
+        event: &DbWebhookEvent,
+    ) -> Result<WebhookDelivery, WebhookError> {
+        let delivery_id = self.deliver(subscription, event, true).await?;
+        self.repo
+            .get_delivery(delivery_id)
+            .await?
+            .ok_or(WebhookError::DeliveryNotFound(delivery_id))
+    }
+
+    /// POST an event to a subscription and log the attempt
+    async fn deliver(
+        &self,
+        subscription: &DbWebhookSubscription,
+        event: &DbWebhookEvent,
+        is_redelivery: bool,
+    ) -> Result<Uuid, WebhookError> {
+        let delivery_id = self
+            .repo
+            .start_delivery(subscription.id, event.id, is_redelivery)
+            .await?;
+
+        let start = Instant::now();
+        let outcome = self.send(subscription, event, delivery_id).await;
+        let duration_ms = start.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+        debug!(
+            delivery_id = %delivery_id,
+            subscription_id = %subscription.id,
+            event = %event.event_type,
+            delivered = outcome.delivered,
+            "Webhook delivery attempted"
+        );
+
+        self.repo
+            .finish_delivery(
+                delivery_id,
+                outcome.delivered,
+                outcome.response_status,
+                outcome.error_message.as_deref(),
+                duration_ms,
+            )
+            .await?;
+
+        Ok(delivery_id)
+    }
+
+    async fn send(
+        &self,
+        subscription: &DbWebhookSubscription,
+        event: &DbWebhookEvent,
+        delivery_id: Uuid,
+    ) -> DeliveryOutcome {
+        let data: serde_json::Value =
+            serde_json::from_str(&event.payload).unwrap_or(serde_json::Value::Null);
+        let body = json!({
+            "id": event.id,
+            "type": event.event_type,
+            "created_at": event.created_at,
+            "data": data,
+        })
+        .to_string();
+
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature = sign(
+            &subscription.secret,
+            format!("{}.{}", timestamp, body).as_bytes(),
+        );
+
+        // Subscriptions stored before destinations were checked may still point inside
+        if let Err(e) = self.urls.check(&subscription.url).await {
+            return DeliveryOutcome {
+                delivered: false,
+                response_status: None,
+                error_message: Some(format!("Destination not allowed: {}", e)),
+            };
+        }
+
+        let result = self
+            .urls
+            .client()
+            .post(&subscription.url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Id", delivery_id.to_string())
+            .header("X-Webhook-Event", &event.event_type)
+            .header("X-Webhook-Timestamp", &timestamp)
+            .header("X-Webhook-Signature", format!("sha256={}", signature))
+            .body(body)
+            .send()
+            .await;
+
+        match result {
+            // Only the status is kept: the log is readable by the subscriber,
+            // and the body is whatever the destination returned
+            Ok(response) => {
+                let status = response.status();
+                DeliveryOutcome {
+                    delivered: status.is_success(),
+                    response_status: Some(status.as_u16() as i32),
+                    error_message: (!status.is_success())
+                        .then(|| format!("HTTP {}", status.as_u16())),
+                }
+            }
+            Err(e) => DeliveryOutcome {
+                delivered: false,
+                response_status: None,
+                error_message: Some(delivery_error(&e)),
+            },
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of `message` keyed by the subscription secret
+pub(super) fn sign(secret: &str, message: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_url_must_be_public() {
+        assert!(validate_webhook_url("https://example.com/hooks").is_ok());
+        for url in [
+            "ftp://example.com/hooks",
+            "/hooks",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://localhost:8080/hooks",
+            "http://10.0.0.5/hooks",
+            "http://[::1]/hooks",
+        ] {
+            assert!(validate_webhook_url(url).is_err(), "{} was accepted", url);
+        }
+    }
+
+    #[test]
+    fn test_sign_matches_reference_vector() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
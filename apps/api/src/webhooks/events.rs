@@ -0,0 +1,102 @@
+//! Webhook event types and subscription filters
+
+/// Event types emitted by the service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    RenderCompleted,
+    RenderFailed,
+    SyncCompleted,
+    SyncFailed,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::RenderCompleted => "render.completed",
+            EventType::RenderFailed => "render.failed",
+            EventType::SyncCompleted => "sync.completed",
+            EventType::SyncFailed => "sync.failed",
+        }
+    }
+}
+
+/// All known event type names
+pub const EVENT_TYPES: [&str; 4] = [
+    "render.completed",
+    "render.failed",
+    "sync.completed",
+    "sync.failed",
+];
+
+/// Check a subscription filter against an event type
+///
+/// Filters are exact names (`render.completed`), a category wildcard
+/// (`sync.*`), or `*`. An empty filter list matches every event.
+pub fn event_matches(filters: &[String], event_type: &str) -> bool {
+    filters.is_empty()
+        || filters
+            .iter()
+            .any(|filter| match filter.strip_suffix(".*") {
+                Some(category) => event_type
+                    .strip_prefix(category)
+                    .is_some_and(|rest| rest.starts_with('.')),
+                None => filter == "*" || filter == event_type,
+            })
+}
+
+/// Validate a filter, returning an error message for unknown events
+pub fn validate_filter(filter: &str) -> Result<(), String> {
+    if filter == "*" {
+        return Ok(());
+    }
+    let known = match filter.strip_suffix(".*") {
+        Some(category) => EVENT_TYPES
+            .iter()
+            .any(|t| t.split('.').next() == Some(category)),
+        None => EVENT_TYPES.contains(&filter),
+    };
+    if known {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unknown event type '{}'. Valid types: {}, or a category wildcard like 'sync.*'",
+            filter,
+            EVENT_TYPES.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_event_matches() {
+        assert!(event_matches(&[], "sync.failed"));
+        assert!(event_matches(&filters(&["*"]), "render.completed"));
+
+        let render_only = filters(&["render.completed"]);
+        assert!(event_matches(&render_only, "render.completed"));
+        assert!(!event_matches(&render_only, "render.failed"));
+        assert!(!event_matches(&render_only, "sync.completed"));
+
+        let sync_all = filters(&["sync.*"]);
+        assert!(event_matches(&sync_all, "sync.completed"));
+        assert!(event_matches(&sync_all, "sync.failed"));
+        assert!(!event_matches(&sync_all, "syncer.completed"));
+        assert!(!event_matches(&sync_all, "render.completed"));
+    }
+
+    #[test]
+    fn test_validate_filter() {
+        assert!(validate_filter("*").is_ok());
+        assert!(validate_filter("render.completed").is_ok());
+        assert!(validate_filter("sync.*").is_ok());
+        assert!(validate_filter("render.started").is_err());
+        assert!(validate_filter("billing.*").is_err());
+    }
+}
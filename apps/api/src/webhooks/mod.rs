@@ -0,0 +1,14 @@
+//! Webhook module for outbound event notifications
+//!
+//! Each API key can register webhook subscriptions with event type filters.
+//! Emitted events are stored so missed deliveries can be replayed, and every
+//! delivery attempt is written to a per-subscription delivery log. Keys can
+//! also set a `webhook_url` for quota threshold alerts.
+
+mod dispatcher;
+mod events;
+mod quota;
+
+pub use dispatcher::{validate_webhook_url, WebhookDispatcher, WebhookError};
+pub use events::{validate_filter, EventType};
+pub use quota::{reached_thresholds, QuotaAlert, QuotaAlerter, QuotaWebhook};
@@ -0,0 +1,215 @@
+//! Quota threshold alerts
+//!
+//! When a key's usage in a category first reaches one of the configured
+//! percentages of its quota in a month, an alert is POSTed to the key's own
+//! `webhook_url`. The body carries the send time and is signed like
+//! subscription deliveries, over `"{timestamp}.{body}"`, keyed by the key's
+//! stored hash so receivers can reject forged or replayed alerts. Alerts
+//! aren't stored; a failed delivery is retried a couple of times and dropped.
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use super::dispatcher::{delivery_error, delivery_guard, sign};
+use crate::db::QuotaCategory;
+use crate::net::UrlGuard;
+
+/// Event name sent in `X-Webhook-Event` and the body's `type`
+pub const QUOTA_THRESHOLD_EVENT: &str = "quota.threshold";
+
+/// Delivery attempts before an alert is dropped
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled for each one after
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// A key's usage reaching a percentage of a category's quota
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaAlert {
+    pub api_key_id: Uuid,
+    pub category: QuotaCategory,
+    pub year_month: String,
+    /// Percent of the quota reached
+    pub threshold: i32,
+    pub quota: i32,
+    pub used: i32,
+}
+
+/// Where a key's alerts go and the secret they're signed with
+#[derive(Debug, Clone)]
+pub struct QuotaWebhook {
+    pub url: String,
+    pub secret: String,
+}
+
+/// Thresholds (percent of `quota`) that `used` has reached, lowest first
+pub fn reached_thresholds(thresholds: &[i32], quota: i32, used: i32) -> Vec<i32> {
+    if quota <= 0 {
+        return Vec::new();
+    }
+    let mut reached: Vec<i32> = thresholds
+        .iter()
+        .copied()
+        .filter(|&threshold| i64::from(used) * 100 >= i64::from(threshold) * i64::from(quota))
+        .collect();
+    reached.sort_unstable();
+    reached.dedup();
+    reached
+}
+
+/// Body of an alert sent at `timestamp`, with its hex signature
+fn signed_body(alert: &QuotaAlert, secret: &str, timestamp: i64) -> (String, String) {
+    let body = json!({
+        "type": QUOTA_THRESHOLD_EVENT,
+        "timestamp": timestamp,
+        "data": alert,
+    })
+    .to_string();
+    let signature = sign(secret, format!("{}.{}", timestamp, body).as_bytes());
+    (body, signature)
+}
+
+/// Sends quota alerts in the background
+pub struct QuotaAlerter {
+    urls: UrlGuard,
+}
+
+impl Default for QuotaAlerter {
+    fn default() -> Self {
+        Self {
+            urls: delivery_guard(),
+        }
+    }
+}
+
+impl QuotaAlerter {
+    /// Deliver an alert in the background, retrying failed attempts
+    pub fn send(self: &Arc<Self>, webhook: QuotaWebhook, alert: QuotaAlert) {
+        let alerter = self.clone();
+        tokio::spawn(async move {
+            let mut delay = RETRY_DELAY;
+            for attempt in 1..=MAX_ATTEMPTS {
+                match alerter.deliver(&webhook, &alert).await {
+                    Ok(()) => {
+                        debug!(
+                            key_id = %alert.api_key_id,
+                            category = alert.category.as_str(),
+                            threshold = alert.threshold,
+                            "Quota alert delivered"
+                        );
+                        return;
+                    }
+                    Err(e) if attempt < MAX_ATTEMPTS => {
+                        debug!(attempt, error = %e, "Quota alert delivery failed, retrying");
+                        tokio::time::sleep(delay).await;
+                        delay *= 2;
+                    }
+                    Err(e) => {
+                        warn!(
+                            key_id = %alert.api_key_id,
+                            threshold = alert.threshold,
+                            error = %e,
+                            "Quota alert dropped after {} attempts",
+                            MAX_ATTEMPTS
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    /// POST an alert once, signed with the current time
+    async fn deliver(&self, webhook: &QuotaWebhook, alert: &QuotaAlert) -> Result<(), String> {
+        let timestamp = Utc::now().timestamp();
+        let (body, signature) = signed_body(alert, &webhook.secret, timestamp);
+
+        // URLs set before destinations were checked may still point inside
+        self.urls
+            .check(&webhook.url)
+            .await
+            .map_err(|e| format!("Destination not allowed: {}", e))?;
+
+        let response = self
+            .urls
+            .client()
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Event", QUOTA_THRESHOLD_EVENT)
+            .header("X-Webhook-Timestamp", timestamp.to_string())
+            .header("X-Webhook-Signature", format!("sha256={}", signature))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| delivery_error(&e))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", status.as_u16()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    fn alert(threshold: i32) -> QuotaAlert {
+        QuotaAlert {
+            api_key_id: Uuid::new_v4(),
+            category: QuotaCategory::Render,
+            year_month: "2026-10".to_string(),
+            threshold,
+            quota: 100,
+            used: threshold,
+        }
+    }
+
+    #[test]
+    fn test_reached_thresholds() {
+        let thresholds = [100, 80, 95];
+        assert!(reached_thresholds(&thresholds, 1000, 799).is_empty());
+        assert_eq!(reached_thresholds(&thresholds, 1000, 800), vec![80]);
+        assert_eq!(reached_thresholds(&thresholds, 1000, 960), vec![80, 95]);
+        assert_eq!(
+            reached_thresholds(&thresholds, 1000, 1005),
+            vec![80, 95, 100]
+        );
+        // Large quotas don't overflow
+        assert_eq!(
+            reached_thresholds(&thresholds, i32::MAX, i32::MAX),
+            vec![80, 95, 100]
+        );
+        assert!(reached_thresholds(&thresholds, 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_signature_verifies_with_key_hash() {
+        let secret = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let (body, signature) = signed_body(&alert(80), secret, 1_792_108_800);
+
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["type"], QUOTA_THRESHOLD_EVENT);
+        assert_eq!(payload["timestamp"], 1_792_108_800);
+        assert_eq!(payload["data"]["threshold"], 80);
+        assert_eq!(payload["data"]["category"], "render");
+
+        let verify = |timestamp: i64, secret: &str| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+            mac.update(format!("{}.{}", timestamp, body).as_bytes());
+            mac.verify_slice(&hex::decode(&signature).unwrap()).is_ok()
+        };
+        assert!(verify(1_792_108_800, secret));
+        // A replay with a different timestamp or a guessed secret fails
+        assert!(!verify(1_792_108_801, secret));
+        assert!(!verify(1_792_108_800, "not-the-key-hash"));
+    }
+}